@@ -0,0 +1,11 @@
+// build.rs
+
+//! Generates the gRPC service and messages of `proto/node.proto` (see `rpc::grpc`) with a
+//! vendored `protoc`, so building doesn't need one installed
+
+fn main()->Result<(),Box<dyn std::error::Error>>{
+    let mut config=prost_build::Config::new();
+    config.protoc_executable(protoc_bin_vendored::protoc_bin_path()?);
+    tonic_prost_build::configure().compile_with_config(config,&["proto/node.proto"],&["proto"])?;
+    Ok(())
+}
@@ -0,0 +1,7 @@
+// examples/canonical_vectors.rs
+
+//! Print the canonical encoding test vectors as JSON for wallet implementers
+
+fn main(){
+    println!("{}",netchain::canonical::test_vectors_json());
+}
@@ -0,0 +1,7 @@
+// examples/wire_vectors.rs
+
+//! Print the network wire format test vectors as JSON for implementers in other languages
+
+fn main(){
+    println!("{}",netchain::network::wire::test_vectors_json());
+}
@@ -0,0 +1,170 @@
+// src/aggregation.rs
+
+//! Metric aggregation from multiple observers
+//! - Each observer reports its view of a target node's `NodeMetrics`
+//! - Per metric, the aggregate is the median (or a trimmed mean) across observers
+//! - A minority of lying observers can't move the aggregate past the honest range
+//!
+//! The aggregated pool is what should be handed to `PoiScorer` / `EpochManager`.
+
+use crate::consensus::NodeMetrics;
+use std::collections::{BTreeMap,HashMap};
+
+/// How observations for one metric are combined
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum AggregationMethod{
+    /// Middle value (mean of the two middle values for an even count)
+    Median,
+    /// Mean after dropping `trim_bps` basis points of observations from each end
+    TrimmedMean{trim_bps:u64},
+}
+
+/// Median of `values` (mean of the two middle values for an even count)
+pub fn median(values:&mut [u64])->Option<u64>{
+    if values.is_empty(){
+        return None
+    }
+    values.sort_unstable();
+    let mid=values.len()/2;
+    if values.len()%2==1{
+        Some(values[mid])
+    }else{
+        Some(((values[mid-1] as u128+values[mid] as u128)/2) as u64)
+    }
+}
+
+/// Mean of `values` after dropping `trim_bps` of the observations from each end
+pub fn trimmed_mean(values:&mut [u64],trim_bps:u64)->Option<u64>{
+    if values.is_empty(){
+        return None
+    }
+    values.sort_unstable();
+    let trim=(values.len() as u64*trim_bps.min(4_999)/10_000) as usize;
+    let kept=&values[trim..values.len()-trim];
+    let sum:u128=kept.iter().map(|v| *v as u128).sum();
+    Some((sum/kept.len() as u128) as u64)
+}
+
+impl AggregationMethod{
+    pub fn combine(&self,values:&mut [u64])->Option<u64>{
+        match self{
+            AggregationMethod::Median=>median(values),
+            AggregationMethod::TrimmedMean{trim_bps}=>trimmed_mean(values,*trim_bps),
+        }
+    }
+}
+
+/// Collects observations about each target from many observers
+#[derive(Debug,Clone)]
+pub struct MetricAggregator{
+    method:AggregationMethod,
+    /// Targets with fewer distinct observers are left out of the pool
+    min_observers:usize,
+    /// target -> observer -> latest observation
+    observations:HashMap<String,BTreeMap<String,NodeMetrics>>,
+}
+
+impl MetricAggregator{
+    pub fn new(method:AggregationMethod,min_observers:usize)->Self{
+        MetricAggregator{
+            method,
+            min_observers:min_observers.max(1),
+            observations:HashMap::new(),
+        }
+    }
+
+    /// Record `observer`'s view of `metrics.node_id`. Nodes can't observe themselves.
+    /// A newer observation from the same observer replaces the older one.
+    pub fn observe(&mut self,observer:&str,metrics:NodeMetrics)->bool{
+        if observer==metrics.node_id{
+            return false
+        }
+        self.observations
+        .entry(metrics.node_id.clone())
+        .or_default()
+        .insert(observer.to_string(),metrics);
+        true
+    }
+
+    /// Number of distinct observers reporting on `target`
+    pub fn observer_count(&self,target:&str)->usize{
+        self.observations.get(target).map_or(0,|o| o.len())
+    }
+
+    /// Aggregate metrics for `target`, None if it has too few observers
+    pub fn aggregate(&self,target:&str)->Option<NodeMetrics>{
+        let observed=self.observations.get(target)?;
+        if observed.len()<self.min_observers{
+            return None
+        }
+        let combine=|field:fn(&NodeMetrics)->u64| {
+            let mut values:Vec<u64>=observed.values().map(field).collect();
+            self.method.combine(&mut values)
+        };
+        Some(NodeMetrics{
+            node_id:target.to_string(),
+            upload_mbps:combine(|m| m.upload_mbps)?,
+            download_mbps:combine(|m| m.download_mbps)?,
+            latency_ms:combine(|m| m.latency_ms)?,
+            uptime_bps:combine(|m| m.uptime_bps)?,
+            stability_bps:combine(|m| m.stability_bps)?,
+        })
+    }
+
+    /// Aggregated metrics for every sufficiently observed target, ready for `PoiScorer`
+    pub fn pool(&self)->HashMap<String,NodeMetrics>{
+        self.observations
+        .keys()
+        .filter_map(|target| self.aggregate(target).map(|m| (target.clone(),m)))
+        .collect()
+    }
+
+    /// Drop all observations (e.g. at an epoch boundary once the pool was snapshotted)
+    pub fn clear(&mut self){
+        self.observations.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn observed(target:&str,upload_mbps:u64)->NodeMetrics{
+        NodeMetrics{
+            node_id:target.to_string(),
+            upload_mbps,
+            download_mbps:100,
+            latency_ms:20,
+            uptime_bps:9_900,
+            stability_bps:9_900,
+        }
+    }
+
+    #[test]
+    fn test_median_and_trimmed_mean(){
+        assert_eq!(median(&mut [5,1,3]),Some(3));
+        assert_eq!(median(&mut [4,1,3,2]),Some(2));
+        assert_eq!(median(&mut []),None);
+        // 20% trimmed from each end of 5 values drops 1 on each side
+        assert_eq!(trimmed_mean(&mut [1,10,11,12,1_000],2_000),Some(11));
+    }
+
+    #[test]
+    fn test_lying_observer_is_filtered(){
+        let mut aggregator=MetricAggregator::new(AggregationMethod::Median,3);
+        aggregator.observe("o1",observed("x",50));
+        aggregator.observe("o2",observed("x",52));
+        assert!(aggregator.aggregate("x").is_none());
+
+        // one malicious observer inflates x's upload
+        aggregator.observe("liar",observed("x",100_000));
+        let aggregate=aggregator.aggregate("x").unwrap();
+        assert_eq!(aggregate.upload_mbps,52);
+        assert_eq!(aggregate.latency_ms,20);
+
+        // self reports are not observations
+        assert!(!aggregator.observe("x",observed("x",100_000)));
+        assert_eq!(aggregator.observer_count("x"),3);
+        assert_eq!(aggregator.pool().len(),1);
+    }
+}
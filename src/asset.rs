@@ -0,0 +1,33 @@
+// src/asset.rs
+
+//! User-issued assets
+//! - The native coin (`NATIVE_ASSET`) lives in `Account::balance` and pays all fees
+//! - Any account can create a new asset with an `IssueAsset` transaction: the whole
+//!   supply is credited to the issuer and never changes afterwards
+//! - Asset balances move with `TransferAsset` and sit in `Account::assets`
+
+use serde::{Deserialize,Serialize};
+
+/// Symbol of the native coin; can't be issued
+pub const NATIVE_ASSET:&str="NC";
+
+/// Longest accepted asset symbol
+pub const MAX_ASSET_ID_LEN:usize=12;
+
+/// Registry entry of an issued asset
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct AssetInfo{
+    /// Account that issued the asset
+    pub issuer:String,
+    /// Total units in existence
+    pub supply:u64,
+}
+
+/// Asset symbols are 1..=`MAX_ASSET_ID_LEN` uppercase ASCII letters or digits, other
+/// than the native symbol
+pub fn is_valid_asset_id(id:&str)->bool{
+    !id.is_empty()
+    && id.len()<=MAX_ASSET_ID_LEN
+    && id.bytes().all(|b| b.is_ascii_uppercase() || b.is_ascii_digit())
+    && id!=NATIVE_ASSET
+}
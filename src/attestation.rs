@@ -0,0 +1,75 @@
+// src/attestation.rs
+
+//! On-chain metric attestations
+//! - `MetricReport`: a validator's self-reported `NodeMetrics` for an epoch plus
+//!   challenger-signed `ChallengeResult`s about it
+//! - Carried in `MetricReport` transactions, so the inputs to validator selection
+//!   are recorded on chain and identical on every node at epoch boundaries
+
+use crate::challenge::{ChallengeResult,apply_challenge_results};
+use crate::consensus::NodeMetrics;
+use crate::validator::ValidatorRegistry;
+use serde::{Deserialize,Serialize};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+/// Reasons a metric report can be rejected
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum MetricReportError{
+    #[error("reporter is not a registered validator")]
+    NotAValidator,
+    #[error("report is for another node")]
+    NodeIdMismatch,
+    #[error("report is for another epoch")]
+    WrongEpoch,
+    #[error("attestation is about another node")]
+    AttestationTargetMismatch,
+    #[error("invalid attestation")]
+    InvalidAttestation,
+    #[error("validator already reported for this epoch")]
+    DuplicateReport,
+}
+
+/// Self report + challenger attestations for one validator and epoch
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct MetricReport{
+    pub epoch:u64,
+    /// Self-reported metrics; `node_id` must be the reporting account
+    #[schema(value_type=Object)]
+    pub metrics:NodeMetrics,
+    /// Challenge results signed by other validators about this node
+    #[schema(value_type=Vec<Object>)]
+    pub attestations:Vec<ChallengeResult>,
+}
+
+impl MetricReport{
+    /// Check the report against its sender, the current epoch and the registry
+    pub fn verify(&self,sender:&str,epoch:u64,registry:&ValidatorRegistry)->Result<(),MetricReportError>{
+        if !registry.is_registered(sender){
+            return Err(MetricReportError::NotAValidator)
+        }
+        if self.metrics.node_id!=sender{
+            return Err(MetricReportError::NodeIdMismatch)
+        }
+        if self.epoch!=epoch{
+            return Err(MetricReportError::WrongEpoch)
+        }
+        for attestation in &self.attestations{
+            if attestation.target!=sender || attestation.challenger==sender{
+                return Err(MetricReportError::AttestationTargetMismatch)
+            }
+            attestation
+            .verify(registry)
+            .map_err(|_| MetricReportError::InvalidAttestation)?;
+        }
+        Ok(())
+    }
+
+    /// Metrics used for selection: the self report with bandwidth replaced by the
+    /// median of attested challenge results (when there are any)
+    pub fn effective_metrics(&self,registry:&ValidatorRegistry)->NodeMetrics{
+        let mut metrics=self.metrics.clone();
+        apply_challenge_results(&mut metrics,&self.attestations,registry);
+        metrics
+    }
+}
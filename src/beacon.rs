@@ -0,0 +1,173 @@
+// src/beacon.rs
+
+//! Randomness beacon for validator selection seeds
+//! - Base seed: u128::from_be_bytes(sha256(previous_block_hash || epoch)[0..16])
+//! - Optional commit-reveal round: validators commit to sha256(secret) and later reveal
+//!   the secret; revealed secrets are mixed into the base seed in address order
+//!
+//! Every node derives the same seed from the same chain and the same reveals.
+
+use sha2::{Digest,Sha256};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Reasons a beacon operation can fail
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum BeaconError{
+    /// The previous block hash isn't valid hex
+    #[error("previous block hash isn't valid hex")]
+    InvalidHash,
+    #[error("validator already committed")]
+    DuplicateCommitment,
+    /// Commitments are closed once the first secret has been revealed
+    #[error("commit phase is over")]
+    CommitPhaseOver,
+    #[error("no commitment to reveal")]
+    NoCommitment,
+    #[error("secret already revealed")]
+    AlreadyRevealed,
+    #[error("revealed secret doesn't match the commitment")]
+    CommitmentMismatch,
+}
+
+fn seed_from_digest(digest:&[u8])->u128{
+    let mut bytes=[0u8;16];
+    bytes.copy_from_slice(&digest[..16]);
+    u128::from_be_bytes(bytes)
+}
+
+fn base_hasher(previous_hash:&str,epoch:u64)->Result<Sha256,BeaconError>{
+    let hash_bytes=hex::decode(previous_hash).map_err(|_| BeaconError::InvalidHash)?;
+    let mut hasher=Sha256::new();
+    hasher.update(&hash_bytes);
+    hasher.update(epoch.to_be_bytes());
+    Ok(hasher)
+}
+
+/// Selection seed for `epoch`: first 16 bytes of sha256(previous_block_hash || epoch), big-endian
+pub fn seed_from_hash(previous_hash:&str,epoch:u64)->Result<u128,BeaconError>{
+    Ok(seed_from_digest(&base_hasher(previous_hash,epoch)?.finalize()))
+}
+
+/// Commitment a validator publishes for `secret`
+pub fn commitment(secret:&[u8;32])->[u8;32]{
+    Sha256::digest(secret).into()
+}
+
+/// Commit-reveal round for one epoch
+#[derive(Debug,Clone,Default)]
+pub struct Beacon{
+    epoch:u64,
+    /// validator -> sha256(secret)
+    commitments:BTreeMap<String,[u8;32]>,
+    /// validator -> revealed secret
+    reveals:BTreeMap<String,[u8;32]>,
+}
+
+impl Beacon{
+    pub fn new(epoch:u64)->Self{
+        Beacon{epoch,..Default::default()}
+    }
+
+    pub fn epoch(&self)->u64{
+        self.epoch
+    }
+
+    /// Record `validator`'s commitment
+    pub fn commit(&mut self,validator:String,commitment:[u8;32])->Result<(),BeaconError>{
+        if !self.reveals.is_empty(){
+            return Err(BeaconError::CommitPhaseOver)
+        }
+        if self.commitments.contains_key(&validator){
+            return Err(BeaconError::DuplicateCommitment)
+        }
+        self.commitments.insert(validator,commitment);
+        Ok(())
+    }
+
+    /// Reveal `validator`'s secret; it must match the earlier commitment
+    pub fn reveal(&mut self,validator:&str,secret:[u8;32])->Result<(),BeaconError>{
+        let committed=self.commitments.get(validator).ok_or(BeaconError::NoCommitment)?;
+        if self.reveals.contains_key(validator){
+            return Err(BeaconError::AlreadyRevealed)
+        }
+        if commitment(&secret)!=*committed{
+            return Err(BeaconError::CommitmentMismatch)
+        }
+        self.reveals.insert(validator.to_string(),secret);
+        Ok(())
+    }
+
+    /// Validators that committed but haven't revealed (candidates for penalties)
+    pub fn missing_reveals(&self)->Vec<String>{
+        self.commitments
+        .keys()
+        .filter(|v| !self.reveals.contains_key(*v))
+        .cloned()
+        .collect()
+    }
+
+    /// Final seed: the base seed with every revealed secret mixed in (address order).
+    /// Without reveals this equals `seed_from_hash(previous_hash, epoch)`.
+    pub fn seed(&self,previous_hash:&str)->Result<u128,BeaconError>{
+        if self.reveals.is_empty(){
+            return seed_from_hash(previous_hash,self.epoch)
+        }
+        let mut hasher=base_hasher(previous_hash,self.epoch)?;
+        for (validator,secret) in &self.reveals{
+            hasher.update(validator.as_bytes());
+            hasher.update(secret);
+        }
+        Ok(seed_from_digest(&hasher.finalize()))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const PREV:&str="00ff00ff00ff00ff00ff00ff00ff00ff00ff00ff00ff00ff00ff00ff00ff00ff";
+
+    #[test]
+    fn test_seed_matches_spec(){
+        let mut hasher=Sha256::new();
+        hasher.update(hex::decode(PREV).unwrap());
+        hasher.update(7u64.to_be_bytes());
+        let digest=hasher.finalize();
+        let expected=u128::from_be_bytes(digest[..16].try_into().unwrap());
+
+        assert_eq!(seed_from_hash(PREV,7).unwrap(),expected);
+        assert_ne!(seed_from_hash(PREV,8).unwrap(),expected);
+        assert_eq!(Beacon::new(7).seed(PREV).unwrap(),expected);
+        assert_eq!(seed_from_hash("not-hex",7),Err(BeaconError::InvalidHash));
+    }
+
+    #[test]
+    fn test_commit_reveal(){
+        let mut beacon=Beacon::new(3);
+        let (a,b)=([1u8;32],[2u8;32]);
+        beacon.commit("alice".to_string(),commitment(&a)).unwrap();
+        beacon.commit("bob".to_string(),commitment(&b)).unwrap();
+        assert_eq!(beacon.commit("bob".to_string(),commitment(&a)),Err(BeaconError::DuplicateCommitment));
+
+        assert_eq!(beacon.reveal("alice",b),Err(BeaconError::CommitmentMismatch));
+        beacon.reveal("alice",a).unwrap();
+        assert_eq!(beacon.reveal("alice",a),Err(BeaconError::AlreadyRevealed));
+        assert_eq!(beacon.reveal("carol",a),Err(BeaconError::NoCommitment));
+        assert_eq!(beacon.commit("carol".to_string(),commitment(&a)),Err(BeaconError::CommitPhaseOver));
+        assert_eq!(beacon.missing_reveals(),vec!["bob".to_string()]);
+
+        // reveals change the seed, and the result doesn't depend on reveal order
+        let partial=beacon.seed(PREV).unwrap();
+        assert_ne!(partial,seed_from_hash(PREV,3).unwrap());
+        beacon.reveal("bob",b).unwrap();
+
+        let mut reordered=Beacon::new(3);
+        reordered.commit("bob".to_string(),commitment(&b)).unwrap();
+        reordered.commit("alice".to_string(),commitment(&a)).unwrap();
+        reordered.reveal("bob",b).unwrap();
+        reordered.reveal("alice",a).unwrap();
+        assert_eq!(beacon.seed(PREV).unwrap(),reordered.seed(PREV).unwrap());
+        assert_ne!(beacon.seed(PREV).unwrap(),partial);
+    }
+}
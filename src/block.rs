@@ -0,0 +1,106 @@
+// src/block.rs
+
+//! Block structure & hashing
+//! - `Block`: index, timestamp, data and linkage to the previous block
+//! - `ConsensusData`: proposer and failover round, finality commit for the parent
+//!   (see `finality`, or its BLS aggregate form in `bls`) and the proposer's VRF
+//!   leader proof (see `vrf`)
+//! - `state_root`: sparse Merkle root of the account state after the block (see `smt`)
+//! - `chain_state_hash`: hash of the rest of the state after the block (validators, epoch,
+//!   names, ...; see `State::encode_chain_state`)
+
+use crate::bls::AggregateCommit;
+use crate::finality::Commit;
+use crate::vrf::LeaderProof;
+use chrono::{DateTime,Utc};
+use serde::{Deserialize,Serialize};
+use sha2::{Digest,Sha256};
+use utoipa::ToSchema;
+
+/// Consensus fields carried in a block header
+#[derive(Serialize,Deserialize,Debug,Clone,Default,PartialEq,Eq)]
+pub struct ConsensusData{
+    /// Account address of the validator that produced the block
+    pub proposer:Option<String>,
+    /// Failover round the block was produced in (0 = original proposer)
+    pub round:u32,
+    /// Precommit aggregate that finalized the previous block, if any
+    pub last_commit:Option<Commit>,
+    /// Proposer's VRF proof of leadership for this slot
+    pub leader_proof:Option<LeaderProof>,
+    /// BLS aggregate of the precommits that finalized the previous block, if any
+    #[serde(default)]
+    pub last_aggregate_commit:Option<AggregateCommit>,
+    /// Hex sparse Merkle root of the account state after applying this block
+    #[serde(default)]
+    pub state_root:Option<String>,
+    /// Hex SHA-256 of the chain state besides accounts after applying this block
+    /// (see `State::chain_state_hash`)
+    #[serde(default)]
+    pub chain_state_hash:Option<String>,
+}
+
+#[derive(Serialize,Deserialize,Debug,Clone,ToSchema)]
+pub struct Block{
+    pub index:u64,
+    pub timestamp:DateTime<Utc>,
+    pub data:String,
+    pub previous_hash:String,
+    #[schema(value_type=Object)]
+    pub consensus:ConsensusData,
+    pub hash:String,
+}
+
+impl Block{
+    pub fn new(index:u64,data:String,previous_hash:String)->Self{
+        Block::with_consensus(index,data,previous_hash,ConsensusData::default())
+    }
+
+    /// Build a block carrying consensus data (parent commit, leader proof)
+    pub fn with_consensus(index:u64,data:String,previous_hash:String,consensus:ConsensusData)->Self{
+        let timestamp=Utc::now();
+        let hash=Block::calculate_hash(index,&timestamp,&data,&previous_hash,&consensus);
+        Block{
+            index,
+            timestamp,
+            data,
+            previous_hash,
+            consensus,
+            hash,
+        }
+    }
+
+    pub fn calculate_hash(
+        index:u64,
+        timestamp:&DateTime<Utc>,
+        data:&str,
+        previous_hash:&str,
+        consensus:&ConsensusData,
+    )->String{
+        // Simple hash over fields (Json encoding)
+        let payload=serde_json::json!({
+            "index":index,
+            "timestamp":timestamp.to_rfc3339(),
+            "data":data,
+            "previous_hash":previous_hash,
+            "consensus":consensus,
+        })
+        .to_string();
+
+        let mut hasher=Sha256::new();
+        hasher.update(payload.as_bytes());
+        let result=hasher.finalize();
+        format!("{:x}",result)
+    }
+
+    /// Recompute this block's hash from its fields
+    pub fn recalculate_hash(&self)->String{
+        Block::calculate_hash(
+            self.index,
+            &self.timestamp,
+            &self.data,
+            &self.previous_hash,
+            &self.consensus,
+        )
+    }
+}
@@ -0,0 +1,252 @@
+// src/blockchain.rs
+
+//! Chain logic: appending blocks, validation and finality tracking
+
+use crate::block::{Block,ConsensusData};
+use crate::bls::AggregateCommit;
+use crate::diff::StateDiff;
+use crate::finality::{Commit,FinalityError};
+use crate::receipt::{BlockReceipts,Receipt,ReceiptStore};
+use crate::rewards::EpochSummary;
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Reasons `Blockchain::import_block` refuses a block
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum ImportError{
+    #[error("expected block {expected}, got block {got}")]
+    UnexpectedHeight{expected:u64,got:u64},
+    #[error("block {0} does not extend the chain tip")]
+    ParentMismatch(u64),
+    /// Carries a (aggregate) commit for a block other than its parent
+    #[error("block {0} carries a commit for another block")]
+    CommitMismatch(u64),
+    #[error("block {0} has an invalid hash")]
+    InvalidHash(u64),
+}
+
+pub struct Blockchain{
+    pub chain:Vec<Block>,
+    /// Highest block height with a verified commit
+    finalized_height:Option<u64>,
+    /// Execution receipts of applied blocks
+    receipts:ReceiptStore,
+    /// Account diffs of applied blocks, by height
+    state_diffs:BTreeMap<u64,StateDiff>,
+}
+
+impl Default for Blockchain{
+    fn default()->Self{
+        Self::new()
+    }
+}
+
+impl Blockchain{
+    pub fn new()->Self{
+        Blockchain::with_genesis(Blockchain::genesis_block())
+    }
+
+    /// Chain starting from a given genesis block, e.g. the one shared by a network
+    pub fn with_genesis(genesis:Block)->Self{
+        Blockchain{
+            chain:vec![genesis],
+            finalized_height:None,
+            receipts:ReceiptStore::new(),
+            state_diffs:BTreeMap::new(),
+        }
+    }
+    /// Chain continuing from a final block whose state was restored from a snapshot (see
+    /// `network::statesync`); blocks below it aren't kept
+    pub fn from_snapshot(block:Block)->Self{
+        let height=block.index;
+        Blockchain{finalized_height:Some(height),..Blockchain::with_genesis(block)}
+    }
+
+    fn genesis_block()->Block{
+        //The first block -index 0
+        Block::new(0,"Genesis Block".to_string(),"0".to_string())
+    }
+
+    pub fn last_block(&self)->&Block{
+        self.chain.last().expect("Blockchain must have at least one block")
+    }
+
+    /// Height of the tip
+    pub fn height(&self)->u64{
+        self.last_block().index
+    }
+
+    pub fn block(&self,height:u64)->Option<&Block>{
+        // the first block is the genesis, or the snapshot block the chain starts from
+        let position=height.checked_sub(self.chain[0].index)?;
+        self.chain.get(position as usize)
+    }
+
+    /// Block with hash `hash`, searched from the tip
+    pub fn block_by_hash(&self,hash:&str)->Option<&Block>{
+        self.chain.iter().rev().find(|block| block.hash==hash)
+    }
+
+    /// Append a block produced elsewhere (e.g. downloaded during sync) after checking that
+    /// it extends the tip and its hash is correct
+    pub fn import_block(&mut self,block:Block)->Result<(),ImportError>{
+        let tip=self.last_block();
+        if block.index!=tip.index+1{
+            return Err(ImportError::UnexpectedHeight{expected:tip.index+1,got:block.index})
+        }
+        if block.previous_hash!=tip.hash{
+            return Err(ImportError::ParentMismatch(block.index))
+        }
+        let commit_for_tip=|height:u64,hash:&str| height==tip.index && hash==tip.hash;
+        if block.consensus.last_commit.as_ref().is_some_and(|c| !commit_for_tip(c.height,&c.block_hash))
+        || block.consensus.last_aggregate_commit.as_ref().is_some_and(|c| !commit_for_tip(c.height,&c.block_hash)){
+            return Err(ImportError::CommitMismatch(block.index))
+        }
+        if block.hash!=block.recalculate_hash(){
+            return Err(ImportError::InvalidHash(block.index))
+        }
+        self.chain.push(block);
+        Ok(())
+    }
+
+    pub fn add_block(&mut self,data:String){
+        self.add_block_with_consensus(data,ConsensusData::default());
+    }
+
+    /// Append a block carrying consensus data (e.g. the commit that finalized the current tip)
+    pub fn add_block_with_consensus(&mut self,data:String,consensus:ConsensusData){
+        let last=self.last_block();
+        let new_index=last.index+1;
+        let new_block=Block::with_consensus(new_index,data,last.hash.clone(),consensus);
+        self.chain.push(new_block);
+    }
+
+    /// Record an epoch's reward settlement as its own block entry
+    pub fn add_epoch_summary(&mut self,summary:&EpochSummary){
+        self.add_block(summary.to_block_data());
+    }
+
+    /// Store the receipts produced while applying block `receipts.height`
+    pub fn record_receipts(&mut self,receipts:BlockReceipts){
+        self.receipts.insert(receipts);
+    }
+
+    /// Receipt of an applied transaction
+    pub fn receipt(&self,tx_hash:&str)->Option<&Receipt>{
+        self.receipts.get(tx_hash)
+    }
+
+    /// All receipts of the block at `height`
+    pub fn block_receipts(&self,height:u64)->Option<&BlockReceipts>{
+        self.receipts.block(height)
+    }
+
+    /// Receipts of transactions `address` sent or was paid by, newest first (see
+    /// `ReceiptStore::for_address`)
+    pub fn address_receipts(&self,address:&str,before:Option<(u64,usize)>)->impl Iterator<Item=((u64,usize),&Receipt)>{
+        self.receipts.for_address(address,before)
+    }
+
+    /// Store the account diff produced while applying block `diff.height`
+    pub fn record_state_diff(&mut self,diff:StateDiff){
+        self.state_diffs.insert(diff.height,diff);
+    }
+
+    /// Account diff of the block at `height`
+    pub fn state_diff(&self,height:u64)->Option<&StateDiff>{
+        self.state_diffs.get(&height)
+    }
+
+    /// Mark the block referenced by `commit` as final.
+    /// The commit's votes must already have been verified (e.g. by `VoteSet`).
+    pub fn mark_final(&mut self,commit:&Commit)->Result<(),FinalityError>{
+        self.mark_final_at(commit.height,&commit.block_hash)
+    }
+
+    /// Same as `mark_final` for a BLS aggregate commit (verified with `AggregateCommit::verify`)
+    pub fn mark_final_aggregate(&mut self,commit:&AggregateCommit)->Result<(),FinalityError>{
+        self.mark_final_at(commit.height,&commit.block_hash)
+    }
+
+    fn mark_final_at(&mut self,height:u64,block_hash:&str)->Result<(),FinalityError>{
+        let block=self.block(height).ok_or(FinalityError::UnknownBlock)?;
+        if block.hash!=block_hash{
+            return Err(FinalityError::UnknownBlock)
+        }
+        self.finalized_height=Some(self.finalized_height.map_or(height,|h| h.max(height)));
+        Ok(())
+    }
+
+    /// Highest finalized block height, if any block is final yet
+    pub fn finalized_height(&self)->Option<u64>{
+        self.finalized_height
+    }
+
+    pub fn is_final(&self,height:u64)->bool{
+        self.finalized_height.is_some_and(|h| height<=h)
+    }
+
+    pub fn is_valid(&self)->bool{
+        //Validate chain: hashes and linkage
+        for i in 1..self.chain.len(){
+            let current=&self.chain[i];
+            let previous=&self.chain[i-1];
+
+            //Check previous hash reference
+            if current.previous_hash!=previous.hash{
+                eprintln!(
+                    "Invalid chain: block {} previous_hash mismatch",
+                    current.index
+                );
+                return false;
+            }
+
+            // A carried commit must refer to the parent block
+            if let Some(commit)=&current.consensus.last_commit
+                && (commit.height!=previous.index || commit.block_hash!=previous.hash){
+                eprintln!("Invalid chain: block {} carries a commit for another block",current.index);
+                return false;
+            }
+            if let Some(commit)=&current.consensus.last_aggregate_commit
+                && (commit.height!=previous.index || commit.block_hash!=previous.hash){
+                eprintln!("Invalid chain: block {} carries an aggregate commit for another block",current.index);
+                return false;
+            }
+
+            // Recalculate hash and compare
+            if current.hash!=current.recalculate_hash(){
+                eprintln!("Invalid chain: block {} has invalid hash",current.index);
+                return false;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_import_checks_linkage_and_hash(){
+        let mut source=Blockchain::new();
+        source.add_block("a".to_string());
+        source.add_block("b".to_string());
+        let mut chain=Blockchain::with_genesis(source.chain[0].clone());
+
+        assert_eq!(chain.import_block(source.chain[2].clone()),Err(ImportError::UnexpectedHeight{expected:1,got:2}));
+        let mut tampered=source.chain[1].clone();
+        tampered.data="c".to_string();
+        assert_eq!(chain.import_block(tampered),Err(ImportError::InvalidHash(1)));
+        let foreign=Blockchain::new();
+        let mut orphan=source.chain[1].clone();
+        orphan.previous_hash=foreign.chain[0].hash.clone();
+        assert_eq!(chain.import_block(orphan),Err(ImportError::ParentMismatch(1)));
+
+        chain.import_block(source.chain[1].clone()).unwrap();
+        chain.import_block(source.chain[2].clone()).unwrap();
+        assert_eq!(chain.height(),2);
+        assert_eq!(chain.block(2).map(|b| &b.hash),Some(&source.chain[2].hash));
+        assert!(chain.is_valid());
+    }
+}
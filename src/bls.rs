@@ -0,0 +1,294 @@
+// src/bls.rs
+
+//! BLS12-381 aggregate finality signatures
+//! - Validators register a BLS key (with proof of possession) for their consensus role
+//! - Precommits are BLS signatures over the same (height, block hash) message, so any
+//!   number of them aggregate into one 96-byte signature
+//! - `AggregateCommit` = aggregate signature + signer bitmap over the committee, carried
+//!   in the next block header instead of one Ed25519 vote per validator
+
+use crate::finality::has_supermajority;
+use crate::validator::ValidatorRegistry;
+use base64::{engine::general_purpose,Engine as _};
+use bincode::Options;
+use blst::BLST_ERROR;
+use blst::min_pk::{AggregateSignature,PublicKey,SecretKey,Signature};
+use rand::RngCore;
+use serde::{Deserialize,Serialize};
+use std::collections::{BTreeMap,HashMap};
+use thiserror::Error;
+
+/// Ciphersuite for vote signatures (proof-of-possession scheme, G2 signatures)
+const DST_SIG:&[u8]=b"BLS_SIG_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+/// Ciphersuite for proofs of possession
+const DST_POP:&[u8]=b"BLS_POP_BLS12381G2_XMD:SHA-256_SSWU_RO_POP_";
+
+/// Reasons a BLS key, vote or aggregate is rejected
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum BlsError{
+    #[error("invalid BLS public key")]
+    InvalidKey,
+    #[error("invalid BLS signature")]
+    InvalidSignature,
+    #[error("invalid BLS proof of possession")]
+    InvalidProofOfPossession,
+    /// The validator has no BLS key registered
+    #[error("validator has no BLS key registered")]
+    MissingKey,
+    #[error("validator is not in the committee")]
+    NotInCommittee,
+    /// A precommit is for another height or block
+    #[error("precommit is for another height or block")]
+    WrongBlock,
+    /// Bitmap length doesn't match the committee
+    #[error("signer bitmap doesn't match the committee")]
+    BitmapMismatch,
+    #[error("signers don't reach the finality threshold")]
+    InsufficientWeight,
+}
+
+fn decode_pubkey(encoded:&str)->Result<PublicKey,BlsError>{
+    let bytes=general_purpose::STANDARD
+    .decode(encoded)
+    .map_err(|_| BlsError::InvalidKey)?;
+    PublicKey::key_validate(&bytes).map_err(|_| BlsError::InvalidKey)
+}
+
+fn decode_signature(encoded:&str)->Result<Signature,BlsError>{
+    let bytes=general_purpose::STANDARD
+    .decode(encoded)
+    .map_err(|_| BlsError::InvalidSignature)?;
+    Signature::sig_validate(&bytes,true).map_err(|_| BlsError::InvalidSignature)
+}
+
+/// A validator's BLS secret key
+pub struct BlsKeypair{
+    secret:SecretKey,
+}
+
+impl BlsKeypair{
+    pub fn generate()->Self{
+        let mut ikm=[0u8;32];
+        rand::thread_rng().fill_bytes(&mut ikm);
+        BlsKeypair{
+            secret:SecretKey::key_gen(&ikm,&[]).expect("32 bytes of key material is enough"),
+        }
+    }
+
+    /// Compressed public key encoded as base64
+    pub fn public_key(&self)->String{
+        general_purpose::STANDARD.encode(self.secret.sk_to_pk().compress())
+    }
+
+    /// Signature over our own public key, proving we hold the secret (blocks rogue-key attacks)
+    pub fn proof_of_possession(&self)->String{
+        let pk=self.secret.sk_to_pk().compress();
+        general_purpose::STANDARD.encode(self.secret.sign(&pk,DST_POP,&[]).compress())
+    }
+
+    fn sign(&self,msg:&[u8])->String{
+        general_purpose::STANDARD.encode(self.secret.sign(msg,DST_SIG,&[]).compress())
+    }
+}
+
+/// Check a proof of possession for a base64 public key
+pub fn verify_proof_of_possession(pubkey:&str,proof:&str)->Result<(),BlsError>{
+    let key=decode_pubkey(pubkey)?;
+    let proof=decode_signature(proof).map_err(|_| BlsError::InvalidProofOfPossession)?;
+    match proof.verify(true,&key.compress(),DST_POP,&[],&key,false){
+        BLST_ERROR::BLST_SUCCESS=>Ok(()),
+        _=>Err(BlsError::InvalidProofOfPossession),
+    }
+}
+
+/// Message every committee member signs to precommit `block_hash` at `height`
+pub fn precommit_message(height:u64,block_hash:&str)->Vec<u8>{
+    bincode::DefaultOptions::new()
+    .with_fixint_encoding()
+    .with_little_endian()
+    .serialize(&("precommit",height,block_hash))
+    .expect("bincode serialization should succed for precommit")
+}
+
+/// A single BLS precommit before aggregation
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct BlsPrecommit{
+    pub height:u64,
+    pub block_hash:String,
+    pub validator:String,
+    /// Compressed G2 signature, base64
+    pub signature:String,
+}
+
+impl BlsPrecommit{
+    pub fn sign(height:u64,block_hash:String,validator:String,keypair:&BlsKeypair)->Self{
+        let signature=keypair.sign(&precommit_message(height,&block_hash));
+        BlsPrecommit{height,block_hash,validator,signature}
+    }
+
+    /// Verify against the validator's registered BLS key
+    pub fn verify(&self,registry:&ValidatorRegistry)->Result<(),BlsError>{
+        let key=decode_pubkey(registry.bls_key(&self.validator).ok_or(BlsError::MissingKey)?)?;
+        let signature=decode_signature(&self.signature)?;
+        match signature.verify(false,&precommit_message(self.height,&self.block_hash),DST_SIG,&[],&key,false){
+            BLST_ERROR::BLST_SUCCESS=>Ok(()),
+            _=>Err(BlsError::InvalidSignature),
+        }
+    }
+}
+
+/// Aggregated precommits finalizing a block, committed in the next block header
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct AggregateCommit{
+    pub height:u64,
+    pub block_hash:String,
+    /// Bit i set = i-th committee member (by address order) signed
+    pub signers:Vec<u8>,
+    /// Aggregate G2 signature, base64
+    pub signature:String,
+}
+
+/// Committee addresses in bitmap order
+fn committee_order(committee:&HashMap<String,u64>)->Vec<&String>{
+    let ordered:BTreeMap<&String,()>=committee.keys().map(|k| (k,())).collect();
+    ordered.into_keys().collect()
+}
+
+impl AggregateCommit{
+    /// Verify `precommits` for (`height`, `block_hash`) and aggregate them.
+    /// Fails unless the signers carry >2/3 of committee weight.
+    pub fn aggregate(
+        height:u64,
+        block_hash:&str,
+        precommits:&[BlsPrecommit],
+        registry:&ValidatorRegistry,
+        committee:&HashMap<String,u64>,
+    )->Result<Self,BlsError>{
+        let order=committee_order(committee);
+        let mut signers=vec![0u8;order.len().div_ceil(8)];
+        let mut signatures:BTreeMap<usize,Signature>=BTreeMap::new();
+        for precommit in precommits{
+            if precommit.height!=height || precommit.block_hash!=block_hash{
+                return Err(BlsError::WrongBlock)
+            }
+            let index=order
+            .iter()
+            .position(|v| **v==precommit.validator)
+            .ok_or(BlsError::NotInCommittee)?;
+            precommit.verify(registry)?;
+            signers[index/8]|=1<<(index%8);
+            signatures.insert(index,decode_signature(&precommit.signature)?);
+        }
+        let weight:u64=signatures.keys().map(|i| committee[order[*i]]).sum();
+        if !has_supermajority(weight,committee.values().sum()){
+            return Err(BlsError::InsufficientWeight)
+        }
+        let refs:Vec<&Signature>=signatures.values().collect();
+        let aggregate=AggregateSignature::aggregate(&refs,false).map_err(|_| BlsError::InvalidSignature)?;
+        Ok(AggregateCommit{
+            height,
+            block_hash:block_hash.to_string(),
+            signers,
+            signature:general_purpose::STANDARD.encode(aggregate.to_signature().compress()),
+        })
+    }
+
+    /// Addresses whose signatures are in the aggregate
+    pub fn signer_addresses<'a>(&self,committee:&'a HashMap<String,u64>)->Vec<&'a String>{
+        committee_order(committee)
+        .into_iter()
+        .enumerate()
+        .filter(|(i,_)| self.signers.get(i/8).is_some_and(|byte| byte&(1<<(i%8))!=0))
+        .map(|(_,v)| v)
+        .collect()
+    }
+
+    /// Check the signer set carries >2/3 of committee weight and the aggregate verifies
+    pub fn verify(&self,registry:&ValidatorRegistry,committee:&HashMap<String,u64>)->Result<(),BlsError>{
+        let order=committee_order(committee);
+        if self.signers.len()!=order.len().div_ceil(8){
+            return Err(BlsError::BitmapMismatch)
+        }
+        // no stray bits beyond the committee
+        if (order.len()..self.signers.len()*8).any(|i| self.signers[i/8]&(1<<(i%8))!=0){
+            return Err(BlsError::BitmapMismatch)
+        }
+        let signers=self.signer_addresses(committee);
+        let weight:u64=signers.iter().map(|v| committee[*v]).sum();
+        if !has_supermajority(weight,committee.values().sum()){
+            return Err(BlsError::InsufficientWeight)
+        }
+        let keys=signers
+        .iter()
+        .map(|v| decode_pubkey(registry.bls_key(v).ok_or(BlsError::MissingKey)?))
+        .collect::<Result<Vec<PublicKey>,BlsError>>()?;
+        let key_refs:Vec<&PublicKey>=keys.iter().collect();
+        let signature=decode_signature(&self.signature)?;
+        match signature.fast_aggregate_verify(false,&precommit_message(self.height,&self.block_hash),DST_SIG,&key_refs){
+            BLST_ERROR::BLST_SUCCESS=>Ok(()),
+            _=>Err(BlsError::InvalidSignature),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::validator::testing::validators;
+
+    fn setup(n:usize)->(ValidatorRegistry,Vec<(String,BlsKeypair)>,HashMap<String,u64>){
+        let (mut registry,consensus_keys)=validators(n);
+        let keys:Vec<(String,BlsKeypair)>=consensus_keys
+        .into_iter()
+        .map(|(addr,_)| {
+            let bls=BlsKeypair::generate();
+            registry.set_bls_key(&addr,bls.public_key(),&bls.proof_of_possession()).unwrap();
+            (addr,bls)
+        })
+        .collect();
+        let committee=keys.iter().map(|(addr,_)| (addr.clone(),100)).collect();
+        (registry,keys,committee)
+    }
+
+    #[test]
+    fn test_proof_of_possession(){
+        let kp=BlsKeypair::generate();
+        assert!(verify_proof_of_possession(&kp.public_key(),&kp.proof_of_possession()).is_ok());
+        let other=BlsKeypair::generate();
+        assert_eq!(
+            verify_proof_of_possession(&kp.public_key(),&other.proof_of_possession()),
+            Err(BlsError::InvalidProofOfPossession)
+        );
+    }
+
+    #[test]
+    fn test_aggregate_commit_roundtrip(){
+        let (registry,keys,committee)=setup(10);
+        let precommits:Vec<BlsPrecommit>=keys
+        .iter()
+        .take(7)
+        .map(|(addr,kp)| BlsPrecommit::sign(4,"hash".to_string(),addr.clone(),kp))
+        .collect();
+
+        let commit=AggregateCommit::aggregate(4,"hash",&precommits,&registry,&committee).unwrap();
+        assert_eq!(commit.signers.len(),2);
+        assert_eq!(commit.signer_addresses(&committee).len(),7);
+        assert!(commit.verify(&registry,&committee).is_ok());
+
+        // dropping a signer from the bitmap invalidates the aggregate
+        let mut forged=commit.clone();
+        let first=forged.signers.iter().position(|b| *b!=0).unwrap();
+        forged.signers[first]&=forged.signers[first]-1;
+        assert_eq!(forged.verify(&registry,&committee),Err(BlsError::InsufficientWeight));
+
+        let mut other_block=commit;
+        other_block.block_hash="other".to_string();
+        assert_eq!(other_block.verify(&registry,&committee),Err(BlsError::InvalidSignature));
+
+        // 6 of 10 isn't a supermajority
+        assert_eq!(
+            AggregateCommit::aggregate(4,"hash",&precommits[..6],&registry,&committee),
+            Err(BlsError::InsufficientWeight)
+        );
+    }
+}
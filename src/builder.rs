@@ -0,0 +1,277 @@
+// src/builder.rs
+
+//! Client-side transaction builder
+//! - Fluent setters for the payload and envelope fields
+//! - Fills the timestamp, and the nonce from an `AccountView` (local `State` or an RPC client)
+//! - Checks amounts, fee, memo length and (when known) balance before anything is signed
+//! - Produces an unsigned `Transaction` for offline signing or a `SignedTransaction` directly
+
+use crate::memo::EncryptedMemo;
+use crate::signer::TxSigner;
+use crate::state::State;
+use crate::transaction::{SignedTransaction,TimeLock,Transaction,TxError,TxLimits,TxPayload};
+use std::time::{SystemTime,UNIX_EPOCH};
+use thiserror::Error;
+
+/// Reasons the builder refuses to produce a transaction
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum BuildError{
+    #[error("no payload set")]
+    MissingPayload,
+    /// No explicit nonce and no account view to read it from
+    #[error("no nonce set and no account view to read it from")]
+    MissingNonce,
+    #[error("amount must be non-zero")]
+    ZeroAmount,
+    #[error("fee below the minimum")]
+    FeeTooLow,
+    #[error("memo too long")]
+    MemoTooLong,
+    /// The account view shows the sender (or fee payer) can't cover the transaction
+    #[error("insufficient balance")]
+    InsufficientBalance,
+    /// The signer's key doesn't derive the builder's sender address
+    #[error("signer doesn't match the sender")]
+    SenderMismatch,
+    /// `fee_payer` named a payer after `with_view` read balances, so the payer's is unknown
+    #[error("fee payer set after the account view was read")]
+    FeePayerAfterView,
+    #[error("signing failed: {0}")]
+    Signing(#[from] TxError),
+}
+
+/// Read-only account lookups the builder needs. Implemented by `State`; RPC clients can
+/// implement it over their node connection.
+pub trait AccountView{
+    fn nonce(&self,address:&str)->u64;
+    fn balance(&self,address:&str)->u64;
+}
+
+impl AccountView for State{
+    fn nonce(&self,address:&str)->u64{
+        self.get_nonce(address)
+    }
+
+    fn balance(&self,address:&str)->u64{
+        self.get_balance(address)
+    }
+}
+
+/// Fluent builder for transactions sent by `sender`
+#[derive(Debug,Clone)]
+pub struct TransactionBuilder{
+    sender:String,
+    payload:Option<TxPayload>,
+    fee:u64,
+    min_fee:u64,
+    nonce:Option<u64>,
+    timestamp:Option<u64>,
+    memo:Option<String>,
+    max_memo_bytes:usize,
+    encrypted_memo:Option<EncryptedMemo>,
+    not_before:Option<TimeLock>,
+    fee_payer:Option<String>,
+    /// Balances read from an account view: (sender, (fee payer, its balance) if one was set)
+    balances:Option<(u64,Option<(String,u64)>)>,
+}
+
+impl TransactionBuilder{
+    pub fn new(sender:impl Into<String>)->Self{
+        TransactionBuilder{
+            sender:sender.into(),
+            payload:None,
+            fee:0,
+            min_fee:0,
+            nonce:None,
+            timestamp:None,
+            memo:None,
+            max_memo_bytes:TxLimits::default().max_memo_bytes,
+            encrypted_memo:None,
+            not_before:None,
+            fee_payer:None,
+            balances:None,
+        }
+    }
+
+    /// Transfer `amount` to `receiver`
+    pub fn transfer(self,receiver:impl Into<String>,amount:u64)->Self{
+        self.payload(TxPayload::Transfer{receiver:receiver.into(),amount})
+    }
+
+    pub fn payload(mut self,payload:TxPayload)->Self{
+        self.payload=Some(payload);
+        self
+    }
+
+    pub fn fee(mut self,fee:u64)->Self{
+        self.fee=fee;
+        self
+    }
+
+    /// Refuse to build below this fee (e.g. a node's advertised minimum)
+    pub fn min_fee(mut self,min_fee:u64)->Self{
+        self.min_fee=min_fee;
+        self
+    }
+
+    pub fn nonce(mut self,nonce:u64)->Self{
+        self.nonce=Some(nonce);
+        self
+    }
+
+    /// Override the timestamp (defaults to now)
+    pub fn timestamp(mut self,timestamp:u64)->Self{
+        self.timestamp=Some(timestamp);
+        self
+    }
+
+    pub fn memo(mut self,memo:impl Into<String>)->Self{
+        self.memo=Some(memo.into());
+        self
+    }
+
+    /// Attach a memo sealed to the receiver (see `EncryptedMemo`)
+    pub fn encrypted_memo(mut self,memo:EncryptedMemo)->Self{
+        self.encrypted_memo=Some(memo);
+        self
+    }
+
+    /// Only valid for inclusion once `lock` expires
+    pub fn not_before(mut self,lock:TimeLock)->Self{
+        self.not_before=Some(lock);
+        self
+    }
+
+    pub fn max_memo_bytes(mut self,max:usize)->Self{
+        self.max_memo_bytes=max;
+        self
+    }
+
+    /// Have `fee_payer` cover the fee (they must co-sign, see `SignedTransaction::sign_fee_payer`)
+    pub fn fee_payer(mut self,fee_payer:impl Into<String>)->Self{
+        self.fee_payer=Some(fee_payer.into());
+        self
+    }
+
+    /// Take the next nonce (unless set explicitly) and balances from `view`. Call after
+    /// `fee_payer`: `build` refuses a fee payer whose balance wasn't read.
+    pub fn with_view(mut self,view:&dyn AccountView)->Self{
+        self.nonce=self.nonce.or(Some(view.nonce(&self.sender)));
+        let payer=self.fee_payer.as_ref().map(|payer| (payer.clone(),view.balance(payer)));
+        self.balances=Some((view.balance(&self.sender),payer));
+        self
+    }
+
+    /// Validate and produce the unsigned transaction (for offline or external signing)
+    pub fn build(self)->Result<Transaction,BuildError>{
+        let payload=self.payload.ok_or(BuildError::MissingPayload)?;
+        let nonce=self.nonce.ok_or(BuildError::MissingNonce)?;
+        let amount=match &payload{
+            TxPayload::Transfer{amount,..} | TxPayload::Stake{amount} | TxPayload::Unstake{amount}=>{
+                if *amount==0{
+                    return Err(BuildError::ZeroAmount)
+                }
+                *amount
+            }
+            TxPayload::MultiTransfer{outputs}=>{
+                if outputs.is_empty() || outputs.iter().any(|o| o.amount==0){
+                    return Err(BuildError::ZeroAmount)
+                }
+                outputs.iter().fold(0u64,|total,o| total.saturating_add(o.amount))
+            }
+            _=>0,
+        };
+        if self.fee<self.min_fee{
+            return Err(BuildError::FeeTooLow)
+        }
+        // Unstake releases bonded stake, so only transfers and stakes spend balance
+        let spent=if matches!(payload,TxPayload::Unstake{..}){0}else{amount};
+        if let Some((sender_balance,payer))=&self.balances{
+            let affordable=match (&self.fee_payer,payer){
+                (Some(fee_payer),Some((read,payer_balance))) if fee_payer==read=>*sender_balance>=spent && *payer_balance>=self.fee,
+                (Some(_),_)=>return Err(BuildError::FeePayerAfterView),
+                (None,_)=>*sender_balance>=spent.saturating_add(self.fee),
+            };
+            if !affordable{
+                return Err(BuildError::InsufficientBalance)
+            }
+        }
+        let timestamp=self.timestamp.unwrap_or_else(|| {
+            SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0)
+        });
+        let tx=Transaction{
+            sender:self.sender,
+            payload,
+            fee:self.fee,
+            nonce,
+            timestamp,
+            memo:self.memo,
+            fee_payer:self.fee_payer,
+            encrypted_memo:self.encrypted_memo,
+            not_before:self.not_before,
+        };
+        if tx.memo_bytes()>self.max_memo_bytes{
+            return Err(BuildError::MemoTooLong)
+        }
+        Ok(tx)
+    }
+
+    /// Validate, then sign with `signer`, which must own the sender address
+    pub fn sign(self,signer:&dyn TxSigner)->Result<SignedTransaction,BuildError>{
+        let derived=signer
+        .scheme()
+        .address(&signer.public_key())?;
+        if derived!=self.sender{
+            return Err(BuildError::SenderMismatch)
+        }
+        let tx=self.build()?;
+        Ok(SignedTransaction::sign_with(&tx,signer)?)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::{generate_ed25519_keypair,pubkey_to_address_hex};
+
+    #[test]
+    fn test_builder_fills_nonce_and_validates(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),100)]);
+
+        let signed=TransactionBuilder::new(addr.clone()).transfer("bob",90).fee(5).with_view(&state).sign(&kp).unwrap();
+        assert!(signed.verify().is_ok());
+        state.apply_transaction(&signed).unwrap();
+
+        // nonce now comes from the updated state
+        let next=TransactionBuilder::new(addr.clone()).transfer("bob",1).fee(1).with_view(&state).build().unwrap();
+        assert_eq!(next.nonce,1);
+
+        let builder=||TransactionBuilder::new(addr.clone()).transfer("bob",1).fee(1);
+        assert_eq!(builder().with_view(&state).fee(10).build(),Err(BuildError::InsufficientBalance));
+        assert_eq!(builder().build(),Err(BuildError::MissingNonce));
+        assert_eq!(builder().nonce(1).min_fee(2).build(),Err(BuildError::FeeTooLow));
+        assert_eq!(
+            builder().nonce(1).memo("x".repeat(TxLimits::default().max_memo_bytes+1)).build(),
+            Err(BuildError::MemoTooLong)
+        );
+        assert_eq!(builder().nonce(1).transfer("bob",0).build(),Err(BuildError::ZeroAmount));
+        assert_eq!(builder().nonce(1).sign(&generate_ed25519_keypair()),Err(BuildError::SenderMismatch));
+    }
+
+    #[test]
+    fn test_fee_payer_balance_read_from_view(){
+        let mut state=State::with_genesis(vec![("alice".to_string(),10),("sponsor".to_string(),3)]);
+        let builder=||TransactionBuilder::new("alice").transfer("bob",10).fee(5);
+        assert_eq!(builder().fee_payer("sponsor").with_view(&state).build(),Err(BuildError::InsufficientBalance));
+        // with the view read first the sponsor's balance is unknown
+        assert_eq!(builder().with_view(&state).fee_payer("sponsor").build(),Err(BuildError::FeePayerAfterView));
+
+        state=State::with_genesis(vec![("alice".to_string(),10),("sponsor".to_string(),5)]);
+        assert_eq!(builder().fee_payer("sponsor").with_view(&state).build().unwrap().fee_payer.as_deref(),Some("sponsor"));
+    }
+}
@@ -0,0 +1,203 @@
+// src/canonical.rs
+
+//! Canonical transaction encoding, pinned for external implementers
+//!
+//! Version 1 (current) is bincode with fixed-width integers, little-endian:
+//! - `u64` -> 8 bytes LE, `u32` -> 4 bytes LE, `bool`/`u8` -> 1 byte
+//! - `String` -> u64 LE byte length, then UTF-8 bytes
+//! - `Option<T>` -> 0x00 for None, 0x01 followed by `T` for Some
+//! - enum -> u32 LE variant index (declaration order), then the variant's fields
+//! - struct / tuple -> fields in declaration order, no separators or names
+//!
+//! `Transaction::canonical_bytes` encodes the transaction struct; its hash is SHA-256 of
+//! those bytes. Senders sign `encode(&(scheme,tx))`, fee payers `encode(&("fee_payer",scheme,tx))`.
+//! `generate_test_vectors` emits worked examples (run `cargo run --example canonical_vectors`).
+
+use crate::signer::TxSigner;
+use crate::transaction::{SignatureScheme,SignedTransaction,Transaction,TxPayload};
+use bincode::Options;
+use ed25519_dalek::{Keypair,PublicKey,SecretKey};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize,Serialize};
+use thiserror::Error;
+
+/// Versions of the canonical encoding. Changing the byte layout means adding a version,
+/// never editing an existing one.
+/// Bytes that aren't a canonical encoding of the requested type
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+#[error("canonical decoding failed: {0}")]
+pub struct DecodeError(pub String);
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+pub enum EncodingVersion{
+    /// Fixed-int little-endian bincode
+    V1,
+}
+
+impl EncodingVersion{
+    /// Version used by this node
+    pub const CURRENT:EncodingVersion=EncodingVersion::V1;
+
+    pub fn encode<T:Serialize+?Sized>(&self,value:&T)->Vec<u8>{
+        match self{
+            EncodingVersion::V1=>bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .with_little_endian()
+            .serialize(value)
+            .expect("bincode serialization should succed for canonical encoding"),
+        }
+    }
+
+    pub fn decode<T:DeserializeOwned>(&self,bytes:&[u8])->Result<T,DecodeError>{
+        match self{
+            EncodingVersion::V1=>bincode::DefaultOptions::new()
+            .with_fixint_encoding()
+            .with_little_endian()
+            .reject_trailing_bytes()
+            .deserialize(bytes)
+            .map_err(|e| DecodeError(e.to_string())),
+        }
+    }
+}
+
+/// Encode with the current canonical version
+pub fn encode<T:Serialize+?Sized>(value:&T)->Vec<u8>{
+    EncodingVersion::CURRENT.encode(value)
+}
+
+/// Decode with the current canonical version
+pub fn decode<T:DeserializeOwned>(bytes:&[u8])->Result<T,DecodeError>{
+    EncodingVersion::CURRENT.decode(bytes)
+}
+
+/// One worked example: transaction fields -> canonical bytes -> hash -> signature
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct TestVector{
+    pub name:String,
+    pub version:EncodingVersion,
+    /// The transaction as JSON fields
+    pub tx:Transaction,
+    /// `Transaction::canonical_bytes`, hex
+    pub canonical_hex:String,
+    /// SHA-256 of the canonical bytes, hex
+    pub tx_hash:String,
+    /// Ed25519 secret key (32 bytes) the vector is signed with, hex
+    pub secret_key_hex:String,
+    /// `SignedTransaction::signing_bytes` for Ed25519, hex
+    pub signing_bytes_hex:String,
+    /// Fee payer's secret key for sponsored vectors, hex
+    pub fee_payer_secret_key_hex:Option<String>,
+    /// Resulting signed transaction (signature and pubkey base64)
+    pub signed:SignedTransaction,
+}
+
+/// Fixed, well-known test key: secret bytes `first..first+32`
+fn test_keypair(first:u8)->Keypair{
+    let secret_bytes:Vec<u8>=(first..first+32).collect();
+    let secret=SecretKey::from_bytes(&secret_bytes).expect("32 bytes is a valid ed25519 secret");
+    let public:PublicKey=(&secret).into();
+    Keypair{secret,public}
+}
+
+/// Deterministic test vectors covering the main payload shapes and optional fields
+pub fn generate_test_vectors()->Vec<TestVector>{
+    let keypair=test_keypair(1);
+    let sponsor=test_keypair(33);
+    let address=|kp:&Keypair| SignatureScheme::Ed25519.address(&kp.public_key()).expect("test key is valid");
+    let sender=address(&keypair);
+    let tx=|payload:TxPayload,memo:Option<&str>|Transaction{
+        sender:sender.clone(),
+        payload,
+        fee:10,
+        nonce:7,
+        timestamp:1_700_000_000,
+        memo:memo.map(str::to_string),
+        fee_payer:None,
+        encrypted_memo:None,
+        not_before:None,
+    };
+    let cases=vec![
+        ("transfer",tx(TxPayload::Transfer{receiver:"bob".to_string(),amount:1_000},None)),
+        ("transfer_with_memo",tx(TxPayload::Transfer{receiver:"bob".to_string(),amount:1},Some("hello"))),
+        ("stake",tx(TxPayload::Stake{amount:500},None)),
+        ("unregister_validator",tx(TxPayload::UnregisterValidator,None)),
+        ("governance_vote",tx(TxPayload::GovernanceVote{proposal_id:3,approve:true},None)),
+        (
+            "sponsored_transfer",
+            Transaction{fee_payer:Some(address(&sponsor)),..tx(TxPayload::Transfer{receiver:"bob".to_string(),amount:1},None)},
+        ),
+    ];
+    cases
+    .into_iter()
+    .map(|(name,tx)| {
+        let mut signed=SignedTransaction::sign_with_keypair(&tx,&keypair);
+        if tx.fee_payer.is_some(){
+            signed.sign_fee_payer(&sponsor).expect("local ed25519 signing is infallible");
+        }
+        TestVector{
+            name:name.to_string(),
+            version:EncodingVersion::CURRENT,
+            canonical_hex:hex::encode(tx.canonical_bytes()),
+            tx_hash:tx.tx_hash_hex(),
+            secret_key_hex:hex::encode(keypair.secret.as_bytes()),
+            signing_bytes_hex:hex::encode(SignedTransaction::signing_bytes(signed.scheme,&tx)),
+            fee_payer_secret_key_hex:tx.fee_payer.as_ref().map(|_| hex::encode(sponsor.secret.as_bytes())),
+            tx,
+            signed,
+        }
+    })
+    .collect()
+}
+
+/// Test vectors as pretty-printed JSON
+pub fn test_vectors_json()->String{
+    serde_json::to_string_pretty(&generate_test_vectors()).expect("test vectors serialize to JSON")
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_encoding_is_pinned(){
+        let tx=Transaction{
+            sender:"a".to_string(),
+            payload:TxPayload::Transfer{receiver:"b".to_string(),amount:1},
+            fee:2,
+            nonce:3,
+            timestamp:4,
+            memo:None,
+            fee_payer:None,
+            encrypted_memo:None,
+            not_before:None,
+        };
+        let expected=concat!(
+            "0100000000000000","61",           // sender
+            "00000000",                        // Transfer
+            "0100000000000000","62",           // receiver
+            "0100000000000000",                // amount
+            "0200000000000000",                // fee
+            "0300000000000000",                // nonce
+            "0400000000000000",                // timestamp
+            "00",                              // memo
+            "00",                              // fee_payer
+            "00",                              // encrypted_memo
+            "00",                              // not_before
+        );
+        assert_eq!(hex::encode(tx.canonical_bytes()),expected);
+        assert_eq!(decode::<Transaction>(&tx.canonical_bytes()).unwrap(),tx);
+        assert!(decode::<Transaction>(&[tx.canonical_bytes(),vec![0]].concat()).is_err());
+    }
+
+    #[test]
+    fn test_vectors_are_deterministic_and_verify(){
+        let vectors=generate_test_vectors();
+        assert_eq!(vectors,generate_test_vectors());
+        for vector in &vectors{
+            assert_eq!(hex::decode(&vector.canonical_hex).unwrap(),vector.tx.canonical_bytes());
+            assert!(vector.signed.verify().is_ok(),"{}",vector.name);
+        }
+        let parsed:Vec<TestVector>=serde_json::from_str(&test_vectors_json()).unwrap();
+        assert_eq!(parsed,vectors);
+    }
+}
@@ -0,0 +1,403 @@
+// src/challenge.rs
+
+//! Challenge-response bandwidth proofs
+//! - A challenger sends a sized random payload to a target (target download)
+//!   and asks the target to stream a sized payload back (target upload)
+//! - The challenger times both transfers and signs a `ChallengeResult`
+//! - Verified results replace the self-reported `upload_mbps` / `download_mbps`
+//!   inputs to `PoiScorer`
+//! - Who challenges whom (and with which nonce) is derived from the epoch's block hash,
+//!   so targets can't predict it and challengers can't pick friendly targets
+
+use crate::aggregation::median;
+use crate::consensus::NodeMetrics;
+use crate::validator::ValidatorRegistry;
+use base64::{engine::general_purpose,Engine as _};
+use bincode::Options;
+use ed25519_dalek::{Keypair,PublicKey,Signature,Signer,Verifier};
+use rand::RngCore;
+use serde::{Deserialize,Serialize};
+use sha2::{Digest,Sha256};
+use std::collections::BTreeMap;
+use std::time::Instant;
+use thiserror::Error;
+
+/// Smallest payload accepted for a measurement; tiny payloads only measure latency
+pub const MIN_PAYLOAD_BYTES:u32=64*1024;
+
+/// Reasons a challenge can fail or a result can be rejected
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum ChallengeError{
+    #[error("challenge payload too small")]
+    PayloadTooSmall,
+    #[error("a node can't challenge itself")]
+    SelfChallenge,
+    #[error("transport error: {0}")]
+    Transport(String),
+    #[error("echo doesn't match the challenge")]
+    BadEcho,
+    #[error("unknown challenger")]
+    UnknownChallenger,
+    #[error("invalid challenge signature")]
+    InvalidSignature,
+}
+
+/// A bandwidth challenge issued by `challenger` to `target`
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct BandwidthChallenge{
+    pub challenger:String,
+    pub target:String,
+    /// Random nonce so responses can't be replayed
+    pub nonce:u64,
+    pub payload_bytes:u32,
+}
+
+impl BandwidthChallenge{
+    /// Create a challenge with a fresh random nonce
+    pub fn new(challenger:String,target:String,payload_bytes:u32)->Result<Self,ChallengeError>{
+        if payload_bytes<MIN_PAYLOAD_BYTES{
+            return Err(ChallengeError::PayloadTooSmall)
+        }
+        if challenger==target{
+            return Err(ChallengeError::SelfChallenge)
+        }
+        Ok(BandwidthChallenge{
+            challenger,
+            target,
+            nonce:rand::thread_rng().next_u64(),
+            payload_bytes,
+        })
+    }
+
+    /// Payload the target must stream back for the upload leg (derived from the nonce)
+    pub fn upload_payload(&self)->Vec<u8>{
+        expand_payload(self.nonce,self.payload_bytes)
+    }
+}
+
+/// Challenges every validator must run in an epoch, derived from a block hash
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct ChallengeSchedule{
+    pub epoch:u64,
+    /// Sorted by (challenger, target)
+    pub challenges:Vec<BandwidthChallenge>,
+}
+
+impl ChallengeSchedule{
+    /// Assign each validator `per_challenger` distinct targets for `epoch`.
+    /// Targets are the other validators ranked by sha256(block_hash || epoch || challenger || target);
+    /// the nonce comes from the same digest. Every node derives the same schedule.
+    pub fn derive(
+        block_hash:&str,
+        epoch:u64,
+        validators:&[String],
+        per_challenger:usize,
+        payload_bytes:u32,
+    )->Self{
+        let mut validators:Vec<&String>=validators.iter().collect();
+        validators.sort();
+        validators.dedup();
+
+        let mut challenges=Vec::new();
+        for challenger in &validators{
+            let mut ranked:Vec<([u8;32],&String)>=validators
+            .iter()
+            .filter(|target| *target!=challenger)
+            .map(|target| {
+                let mut hasher=Sha256::new();
+                hasher.update(block_hash.as_bytes());
+                hasher.update(epoch.to_le_bytes());
+                hasher.update(challenger.as_bytes());
+                hasher.update([0u8]);
+                hasher.update(target.as_bytes());
+                (hasher.finalize().into(),*target)
+            })
+            .collect();
+            ranked.sort();
+            let mut picked:Vec<BandwidthChallenge>=ranked
+            .into_iter()
+            .take(per_challenger)
+            .map(|(digest,target)| BandwidthChallenge{
+                challenger:(*challenger).clone(),
+                target:target.clone(),
+                nonce:u64::from_le_bytes(digest[..8].try_into().expect("digest has 32 bytes")),
+                payload_bytes:payload_bytes.max(MIN_PAYLOAD_BYTES),
+            })
+            .collect();
+            picked.sort_by(|a,b| a.target.cmp(&b.target));
+            challenges.extend(picked);
+        }
+        ChallengeSchedule{epoch,challenges}
+    }
+
+    /// Challenges `challenger` must run this epoch
+    pub fn for_challenger<'a>(&'a self,challenger:&'a str)->impl Iterator<Item=&'a BandwidthChallenge>+'a{
+        self.challenges.iter().filter(move |c| c.challenger==challenger)
+    }
+
+    /// True if `result` answers a challenge in this schedule
+    pub fn contains(&self,result:&ChallengeResult)->bool{
+        self.challenges
+        .iter()
+        .any(|c| c.challenger==result.challenger && c.target==result.target && c.nonce==result.nonce)
+    }
+
+    /// Keep only results that answer a scheduled challenge
+    pub fn retain_scheduled(&self,results:&mut Vec<ChallengeResult>){
+        results.retain(|r| self.contains(r));
+    }
+}
+
+/// Deterministically expand a nonce into `len` bytes (sha256 in counter mode)
+pub fn expand_payload(nonce:u64,len:u32)->Vec<u8>{
+    let mut out=Vec::with_capacity(len as usize);
+    let mut counter=0u64;
+    while out.len()<len as usize{
+        let mut hasher=Sha256::new();
+        hasher.update(nonce.to_le_bytes());
+        hasher.update(counter.to_le_bytes());
+        out.extend_from_slice(&hasher.finalize());
+        counter+=1;
+    }
+    out.truncate(len as usize);
+    out
+}
+
+/// Throughput in whole Mbps for `bytes` transferred in `elapsed_us` microseconds
+pub fn throughput_mbps(bytes:u64,elapsed_us:u64)->u64{
+    // bits / us == Mbit / s
+    (bytes.saturating_mul(8))/elapsed_us.max(1)
+}
+
+/// Moves challenge payloads between the challenger and the target
+pub trait ChallengeTransport{
+    /// Deliver `payload` to the target; returns the sha256 digest the target computed over it
+    fn send_payload(&mut self,challenge:&BandwidthChallenge,payload:&[u8])->Result<[u8;32],ChallengeError>;
+    /// Ask the target to stream back `challenge.upload_payload()`
+    fn fetch_payload(&mut self,challenge:&BandwidthChallenge)->Result<Vec<u8>,ChallengeError>;
+}
+
+/// Signed outcome of a bandwidth challenge
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct ChallengeResult{
+    pub challenger:String,
+    pub target:String,
+    pub nonce:u64,
+    /// Target's measured upload (target -> challenger)
+    pub upload_mbps:u64,
+    /// Target's measured download (challenger -> target)
+    pub download_mbps:u64,
+    /// Signature by the challenger's consensus key, base64
+    pub signature:String,
+}
+
+impl ChallengeResult{
+    fn signing_bytes(&self)->Vec<u8>{
+        bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+        .serialize(&(&self.challenger,&self.target,self.nonce,self.upload_mbps,self.download_mbps))
+        .expect("bincode serialization should succed for challenge result")
+    }
+
+    /// Verify the challenger's signature against its registered consensus key
+    pub fn verify(&self,registry:&ValidatorRegistry)->Result<(),ChallengeError>{
+        let info=registry
+        .get(&self.challenger)
+        .ok_or(ChallengeError::UnknownChallenger)?;
+        let pk_bytes=general_purpose::STANDARD
+        .decode(&info.consensus_pubkey)
+        .map_err(|_| ChallengeError::InvalidSignature)?;
+        let sig_bytes=general_purpose::STANDARD
+        .decode(&self.signature)
+        .map_err(|_| ChallengeError::InvalidSignature)?;
+        let public_key=PublicKey::from_bytes(&pk_bytes).map_err(|_| ChallengeError::InvalidSignature)?;
+        let signature=Signature::from_bytes(&sig_bytes).map_err(|_| ChallengeError::InvalidSignature)?;
+        public_key
+        .verify(&self.signing_bytes(),&signature)
+        .map_err(|_| ChallengeError::InvalidSignature)
+    }
+}
+
+/// Run both legs of `challenge` over `transport` and sign the measured throughput
+pub fn run_challenge<T:ChallengeTransport>(
+    transport:&mut T,
+    challenge:&BandwidthChallenge,
+    keypair:&Keypair,
+)->Result<ChallengeResult,ChallengeError>{
+    // download leg: random bytes the target can't have cached
+    let mut payload=vec![0u8;challenge.payload_bytes as usize];
+    rand::thread_rng().fill_bytes(&mut payload);
+    let expected:[u8;32]=Sha256::digest(&payload).into();
+    let started=Instant::now();
+    let digest=transport.send_payload(challenge,&payload)?;
+    let download_us=started.elapsed().as_micros() as u64;
+    if digest!=expected{
+        return Err(ChallengeError::BadEcho)
+    }
+
+    // upload leg: target streams the nonce-derived payload back
+    let started=Instant::now();
+    let echoed=transport.fetch_payload(challenge)?;
+    let upload_us=started.elapsed().as_micros() as u64;
+    if echoed!=challenge.upload_payload(){
+        return Err(ChallengeError::BadEcho)
+    }
+
+    let mut result=ChallengeResult{
+        challenger:challenge.challenger.clone(),
+        target:challenge.target.clone(),
+        nonce:challenge.nonce,
+        upload_mbps:throughput_mbps(challenge.payload_bytes as u64,upload_us),
+        download_mbps:throughput_mbps(challenge.payload_bytes as u64,download_us),
+        signature:String::new(),
+    };
+    let sig:Signature=keypair.sign(&result.signing_bytes());
+    result.signature=general_purpose::STANDARD.encode(sig.to_bytes());
+    Ok(result)
+}
+
+/// Replace the self-reported bandwidth in `metrics` with the median of verified challenge
+/// results about that node (latest result per challenger), so a single lying challenger
+/// can't set a node's bandwidth. Results about other nodes or failing verification are ignored.
+/// Returns true if the metrics were updated.
+pub fn apply_challenge_results(
+    metrics:&mut NodeMetrics,
+    results:&[ChallengeResult],
+    registry:&ValidatorRegistry,
+)->bool{
+    let mut latest:BTreeMap<&str,&ChallengeResult>=BTreeMap::new();
+    for result in results{
+        if result.target==metrics.node_id && result.challenger!=result.target && result.verify(registry).is_ok(){
+            latest.insert(&result.challenger,result);
+        }
+    }
+    let mut uploads:Vec<u64>=latest.values().map(|r| r.upload_mbps).collect();
+    let mut downloads:Vec<u64>=latest.values().map(|r| r.download_mbps).collect();
+    match (median(&mut uploads),median(&mut downloads)){
+        (Some(upload),Some(download))=>{
+            metrics.upload_mbps=upload;
+            metrics.download_mbps=download;
+            true
+        }
+        _=>false,
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::generate_ed25519_keypair;
+    use crate::validator::testing::registry_with;
+
+    /// In-memory target that behaves honestly unless told to cheat
+    struct LoopbackTarget{
+        truncate:bool,
+    }
+
+    impl ChallengeTransport for LoopbackTarget{
+        fn send_payload(&mut self,_challenge:&BandwidthChallenge,payload:&[u8])->Result<[u8;32],ChallengeError>{
+            Ok(Sha256::digest(payload).into())
+        }
+        fn fetch_payload(&mut self,challenge:&BandwidthChallenge)->Result<Vec<u8>,ChallengeError>{
+            let mut payload=challenge.upload_payload();
+            if self.truncate{
+                payload.truncate(payload.len()/2);
+            }
+            Ok(payload)
+        }
+    }
+
+    #[test]
+    fn test_throughput_math(){
+        // 1 MB in 1 s = 8 Mbps
+        assert_eq!(throughput_mbps(1_000_000,1_000_000),8);
+        assert_eq!(throughput_mbps(1_000,0),8_000);
+    }
+
+    #[test]
+    fn test_challenge_replaces_self_report(){
+        let kp=generate_ed25519_keypair();
+        let registry=registry_with("challenger",&kp);
+        let challenge=BandwidthChallenge::new("challenger".to_string(),"target".to_string(),MIN_PAYLOAD_BYTES).unwrap();
+        let result=run_challenge(&mut LoopbackTarget{truncate:false},&challenge,&kp).unwrap();
+        assert!(result.verify(&registry).is_ok());
+
+        let mut metrics=NodeMetrics{
+            node_id:"target".to_string(),
+            upload_mbps:u64::MAX,
+            download_mbps:u64::MAX,
+            latency_ms:10,
+            uptime_bps:10_000,
+            stability_bps:10_000,
+        };
+        assert!(apply_challenge_results(&mut metrics,std::slice::from_ref(&result),&registry));
+        assert_eq!(metrics.upload_mbps,result.upload_mbps);
+        assert_eq!(metrics.download_mbps,result.download_mbps);
+
+        // tampered results are ignored
+        let mut forged=result.clone();
+        forged.upload_mbps+=1;
+        assert_eq!(forged.verify(&registry),Err(ChallengeError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_rejects_bad_echo_and_small_payloads(){
+        let kp=generate_ed25519_keypair();
+        let challenge=BandwidthChallenge::new("a".to_string(),"b".to_string(),MIN_PAYLOAD_BYTES).unwrap();
+        assert_eq!(
+            run_challenge(&mut LoopbackTarget{truncate:true},&challenge,&kp),
+            Err(ChallengeError::BadEcho)
+        );
+        assert_eq!(
+            BandwidthChallenge::new("a".to_string(),"b".to_string(),1),
+            Err(ChallengeError::PayloadTooSmall)
+        );
+        assert_eq!(
+            BandwidthChallenge::new("a".to_string(),"a".to_string(),MIN_PAYLOAD_BYTES),
+            Err(ChallengeError::SelfChallenge)
+        );
+    }
+
+    #[test]
+    fn test_schedule_is_deterministic_and_verifiable(){
+        let validators:Vec<String>=["dave","alice","carol","bob"].iter().map(|v| v.to_string()).collect();
+        let schedule=ChallengeSchedule::derive("abc123",4,&validators,2,MIN_PAYLOAD_BYTES);
+
+        let mut shuffled=validators.clone();
+        shuffled.reverse();
+        assert_eq!(schedule,ChallengeSchedule::derive("abc123",4,&shuffled,2,MIN_PAYLOAD_BYTES));
+        assert_ne!(schedule,ChallengeSchedule::derive("abc124",4,&validators,2,MIN_PAYLOAD_BYTES));
+
+        assert_eq!(schedule.challenges.len(),8);
+        for validator in &validators{
+            let targets:Vec<&String>=schedule.for_challenger(validator).map(|c| &c.target).collect();
+            assert_eq!(targets.len(),2);
+            assert_ne!(targets[0],targets[1]);
+            assert!(!targets.contains(&validator));
+        }
+
+        let assigned=schedule.for_challenger("alice").next().unwrap().clone();
+        let mut results=vec![
+            ChallengeResult{
+                challenger:"alice".to_string(),
+                target:assigned.target.clone(),
+                nonce:assigned.nonce,
+                upload_mbps:10,
+                download_mbps:10,
+                signature:String::new(),
+            },
+            ChallengeResult{
+                challenger:"alice".to_string(),
+                target:assigned.target.clone(),
+                nonce:assigned.nonce.wrapping_add(1),
+                upload_mbps:10,
+                download_mbps:10,
+                signature:String::new(),
+            },
+        ];
+        schedule.retain_scheduled(&mut results);
+        assert_eq!(results.len(),1);
+        assert_eq!(results[0].nonce,assigned.nonce);
+    }
+}
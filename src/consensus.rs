@@ -1,193 +1,818 @@
 // src/consensus.rs
+
+pub mod messages;
+
 use rand::Rng; // keep for testing helpers only
 use serde::{Deserialize, Serialize}; // For config serialization (optional)
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use thiserror::Error;
+
+/// Errors that can occur during validator scoring and selection
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ConsensusError {
+    /// The validator pool passed to selection was empty
+    #[error("validator pool is empty")]
+    EmptyPool,
+    /// The selection pick fell outside the cumulative weight range
+    #[error("selection fell outside the cumulative weight range")]
+    SelectionOutOfRange,
+    /// Epoch length must be at least one block
+    #[error("epoch length must be at least one block")]
+    InvalidEpochLength,
+    /// No validator snapshot has been taken yet
+    #[error("no validator snapshot has been taken")]
+    NoEpochSnapshot,
+    /// The requested height belongs to a different epoch than the pinned snapshot
+    #[error("height belongs to a different epoch than the snapshot")]
+    EpochMismatch,
+    /// Committee size must be at least one validator
+    #[error("committee size must be at least one")]
+    InvalidCommitteeSize,
+    /// Round timeouts must be non-zero
+    #[error("round timeouts must be non-zero")]
+    InvalidRoundTimeout,
+    /// A block was proposed by someone other than the expected proposer for its round
+    #[error("block proposed by someone other than the expected proposer")]
+    UnexpectedProposer,
+}
+
+/// Fixed-point scale used for all PoI scores and weights (10_000 = 100%)
+pub const BPS_SCALE: u64 = 10_000;
+
+/// How far the metric weights may drift from `BPS_SCALE` (rounding in hand-written configs)
+pub const WEIGHT_SUM_TOLERANCE_BPS: u64 = 10;
+
+/// Errors from loading or validating a `PoiConfig`
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ConfigError {
+    /// The config file couldn't be read
+    #[error("cannot read config: {0}")]
+    Io(String),
+    /// The file extension is neither `.toml` nor `.json`
+    #[error("unsupported config format: {0}")]
+    UnsupportedFormat(String),
+    /// The file isn't valid TOML/JSON for a `PoiConfig` (includes negative values)
+    #[error("invalid config: {0}")]
+    Parse(String),
+    /// Metric weights (excluding the diversity bonus) don't sum to ~`BPS_SCALE`
+    #[error("metric weights sum to {0}, expected about {scale}", scale = BPS_SCALE)]
+    WeightSum(u64),
+    /// A normalization threshold is zero, which would zero that metric for every node
+    #[error("threshold `{0}` must be non-zero")]
+    ZeroThreshold(&'static str),
+    /// A basis-point or epoch-count setting is outside its valid range
+    #[error("`{0}` is out of range")]
+    OutOfRange(&'static str),
+}
 
 /// Config for PoI weights and thresholds (load from TOML/JSON)
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct PoiConfig {
     pub weights: Weights,
     pub thresholds: Thresholds,
+    #[serde(default)]
+    pub fraud: FraudConfig,
+    /// EMA weight of the newest epoch score in basis points.
+    /// `BPS_SCALE` = no smoothing; lower values blend in more history.
+    #[serde(default = "default_ema_alpha_bps")]
+    pub ema_alpha_bps: u64, // e.g., 3_000
+    /// Share of a node's smoothed score lost each epoch without a fresh attestation
+    /// (basis points, 0 disables decay)
+    #[serde(default)]
+    pub decay_bps: u64, // e.g., 2_000
+    /// Nodes scoring below this (basis points) are jailed and excluded from selection
+    #[serde(default)]
+    pub min_score: u64, // e.g., 2_000
+    /// Consecutive epochs at or above `min_score` needed to be unjailed
+    #[serde(default = "default_unjail_epochs")]
+    pub unjail_epochs: u64, // e.g., 3
+    /// Known node locations for the diversity component (node id -> location)
+    #[serde(default)]
+    pub locations: HashMap<String, NodeLocation>,
+    /// How bonded stake is mixed into selection weight
+    #[serde(default)]
+    pub stake: StakeConfig,
+    /// Cap on the active validator set per epoch (0 = unbounded)
+    #[serde(default)]
+    pub max_validators: usize, // e.g., 100
+}
+
+/// Where a node runs, used to reward under-represented regions / networks
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct NodeLocation {
+    pub region: String, // e.g., "eu-west"
+    pub asn: u32,       // autonomous system number of the node's network
+}
+
+impl PoiConfig {
+    /// Load a config from a `.toml` or `.json` file and validate it
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        let path = path.as_ref();
+        let is_toml = match path.extension().and_then(|ext| ext.to_str()) {
+            Some("toml") => true,
+            Some("json") => false,
+            _ => return Err(ConfigError::UnsupportedFormat(path.display().to_string())),
+        };
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ConfigError::Io(format!("{}: {}", path.display(), e)))?;
+        let config: PoiConfig = if is_toml {
+            toml::from_str(&text).map_err(|e| ConfigError::Parse(e.to_string()))?
+        } else {
+            serde_json::from_str(&text).map_err(|e| ConfigError::Parse(e.to_string()))?
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Reject configs that would silently produce meaningless scores
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let w = &self.weights;
+        let sum = w.upload + w.download + w.latency + w.uptime + w.stability;
+        if sum.abs_diff(BPS_SCALE) > WEIGHT_SUM_TOLERANCE_BPS {
+            return Err(ConfigError::WeightSum(sum));
+        }
+        if w.diversity > BPS_SCALE {
+            return Err(ConfigError::OutOfRange("weights.diversity"));
+        }
+
+        let t = &self.thresholds;
+        for (name, value) in [
+            ("thresholds.upload_mbps", t.upload_mbps),
+            ("thresholds.download_mbps", t.download_mbps),
+            ("thresholds.latency_ms", t.latency_ms),
+            ("thresholds.uptime_bps", t.uptime_bps),
+            ("thresholds.stability_bps", t.stability_bps),
+        ] {
+            if value == 0 {
+                return Err(ConfigError::ZeroThreshold(name));
+            }
+        }
+
+        if self.ema_alpha_bps == 0 || self.ema_alpha_bps > BPS_SCALE {
+            return Err(ConfigError::OutOfRange("ema_alpha_bps"));
+        }
+        if self.decay_bps > BPS_SCALE {
+            return Err(ConfigError::OutOfRange("decay_bps"));
+        }
+        if self.min_score > BPS_SCALE {
+            return Err(ConfigError::OutOfRange("min_score"));
+        }
+        // 0 would release a score-jailed validator in the epoch it was jailed
+        if self.unjail_epochs == 0 {
+            return Err(ConfigError::OutOfRange("unjail_epochs"));
+        }
+        if self.fraud.discount_bps > BPS_SCALE {
+            return Err(ConfigError::OutOfRange("fraud.discount_bps"));
+        }
+        if self.stake.mixing != StakeMixing::None && self.stake.bond_cap == 0 {
+            return Err(ConfigError::ZeroThreshold("stake.bond_cap"));
+        }
+        if let StakeMixing::Linear { poi_bps } = self.stake.mixing
+            && poi_bps > BPS_SCALE
+        {
+            return Err(ConfigError::OutOfRange("stake.mixing.poi_bps"));
+        }
+        Ok(())
+    }
+}
+
+fn default_unjail_epochs() -> u64 {
+    3
+}
+
+fn default_ema_alpha_bps() -> u64 {
+    BPS_SCALE
+}
+
+/// How PoI score and bonded stake combine into a selection weight
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StakeMixing {
+    /// Pure PoI: stake is ignored (apart from `min_bond`)
+    #[default]
+    None,
+    /// sqrt(poi * stake): weak in either dimension drags the weight down
+    GeometricMean,
+    /// poi_bps * poi + (BPS_SCALE - poi_bps) * stake
+    Linear { poi_bps: u64 },
 }
 
+/// Economic weighting settings
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct StakeConfig {
+    #[serde(default)]
+    pub mixing: StakeMixing,
+    /// Validators with less bonded stake than this are never selected (0 disables)
+    #[serde(default)]
+    pub min_bond: u64,
+    /// Stake at which the stake component saturates at `BPS_SCALE`
+    #[serde(default)]
+    pub bond_cap: u64, // e.g., 100_000
+}
+
+/// Outlier / fraud detection settings
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FraudConfig {
+    /// A metric claimed better than its reference by more than this (bps of the reference) is flagged
+    pub max_deviation_bps: u64, // e.g., 5_000 (50%)
+    /// Score discount applied to a flagged node, in basis points
+    pub discount_bps: u64, // e.g., 5_000 (halve the score)
+}
+
+impl Default for FraudConfig {
+    fn default() -> Self {
+        Self {
+            max_deviation_bps: 5_000,
+            discount_bps: 5_000,
+        }
+    }
+}
+
+/// Metric weights in basis points; they should sum to `BPS_SCALE`
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Weights {
-    pub upload: f64,    // e.g., 0.25
-    pub download: f64,  // e.g., 0.25
-    pub latency: f64,   // e.g., 0.20 (lower latency = higher score)
-    pub uptime: f64,    // e.g., 0.20
-    pub stability: f64, // e.g., 0.10
+    pub upload: u64,    // e.g., 2_500
+    pub download: u64,  // e.g., 2_500
+    pub latency: u64,   // e.g., 2_000 (lower latency = higher score)
+    pub uptime: u64,    // e.g., 2_000
+    pub stability: u64, // e.g., 1_000
+    /// Bonus for nodes in under-represented regions/ASNs (0 disables it)
+    #[serde(default)]
+    pub diversity: u64, // e.g., 1_000
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Thresholds {
-    pub upload_mbps: f64,     // Max for normalization, e.g., 100.0
-    pub download_mbps: f64,   // e.g., 1000.0
-    pub latency_ms: f64,      // Max penalty at this, e.g., 200.0
-    pub uptime_percent: f64,  // Max, e.g., 100.0
-    pub stability_percent: f64, // Packet success rate, e.g., 100.0
+    pub upload_mbps: u64,   // Max for normalization, e.g., 100
+    pub download_mbps: u64, // e.g., 1000
+    pub latency_ms: u64,    // Max penalty at this, e.g., 200
+    pub uptime_bps: u64,    // Max, e.g., 10_000 (100%)
+    pub stability_bps: u64, // Packet success rate, e.g., 10_000 (100%)
 }
 
 /// Node's internet metrics (self-reported or proven via P2P challenges)
-#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+///
+/// All values are integers so every node scores them identically.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
 pub struct NodeMetrics {
     pub node_id: String, // e.g., pubkey hash
-    pub upload_mbps: f64,
-    pub download_mbps: f64,
-    pub latency_ms: f64,     // Avg RTT to peers
-    pub uptime_percent: f64, // Over last epoch (e.g., 99.5)
-    pub stability_percent: f64, // % successful packets
+    pub upload_mbps: u64,
+    pub download_mbps: u64,
+    pub latency_ms: u64,    // Avg RTT to peers
+    pub uptime_bps: u64,    // Over last epoch in basis points (e.g., 9_950 = 99.5%)
+    pub stability_bps: u64, // Successful packets in basis points
+}
+
+/// Normalize a value to basis points: (val / max) clamped to [0, BPS_SCALE]
+fn normalize_bps(val: u64, max: u64) -> u64 {
+    if max == 0 {
+        return 0;
+    }
+    let clamped = val.min(max) as u128;
+    (clamped * BPS_SCALE as u128 / max as u128) as u64
+}
+
+/// Inverted normalize for penalties (e.g., latency: higher = worse)
+fn invert_normalize_bps(val: u64, max: u64) -> u64 {
+    if max == 0 {
+        return 0;
+    }
+    BPS_SCALE - normalize_bps(val, max)
+}
+
+/// Round timeouts for proposer failover.
+/// Round `r` lasts `base_ms + r * delta_ms`; when it expires without a block the next round starts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub struct RoundTimeouts {
+    pub base_ms: u64,
+    pub delta_ms: u64,
 }
 
-impl NodeMetrics {
-    /// Normalize a value: (val / max) clamped to [0.0, 1.0]
-    fn normalize(_self: &Self, val: f64, max: f64) -> f64 {
-        if max <= 0.0 {
-            return 0.0;
+impl RoundTimeouts {
+    pub fn new(base_ms: u64, delta_ms: u64) -> Result<Self, ConsensusError> {
+        if base_ms == 0 {
+            return Err(ConsensusError::InvalidRoundTimeout);
         }
-        (val / max).clamp(0.0, 1.0)
+        Ok(Self { base_ms, delta_ms })
     }
 
-    /// Inverted normalize for penalties (e.g., latency: higher = worse)
-    fn invert_normalize(_self: &Self, val: f64, max: f64) -> f64 {
-        1.0 - NodeMetrics::normalize(_self, val, max)
+    /// How long round `round` waits for a proposal
+    pub fn timeout_for_round(&self, round: u32) -> u64 {
+        self.base_ms
+            .saturating_add(self.delta_ms.saturating_mul(round as u64))
+    }
+
+    /// Round that is active `elapsed_ms` after the height started
+    pub fn round_at(&self, elapsed_ms: u64) -> u32 {
+        let mut round = 0u32;
+        let mut remaining = elapsed_ms;
+        while round < u32::MAX && remaining >= self.timeout_for_round(round) {
+            remaining -= self.timeout_for_round(round);
+            round += 1;
+        }
+        round
     }
 }
 
+/// Seed for failover round `round`; round 0 uses the height seed unchanged
+pub fn round_seed(seed_u128: u128, round: u32) -> u128 {
+    if round == 0 {
+        return seed_u128;
+    }
+    let mut hasher = Sha256::new();
+    hasher.update(seed_u128.to_be_bytes());
+    hasher.update(round.to_be_bytes());
+    let digest = hasher.finalize();
+    let mut bytes = [0u8; 16];
+    bytes.copy_from_slice(&digest[..16]);
+    u128::from_be_bytes(bytes)
+}
+
 /// PoI Scorer: Main engine for computing importance scores
 #[derive(Debug, Clone)]
 pub struct PoiScorer {
     config: PoiConfig,
+    /// node -> smoothed score carried across epochs
+    smoothed: HashMap<String, u64>,
+    /// node -> bonded stake
+    stakes: HashMap<String, u64>,
+    /// Nodes with fresh attestations since the last epoch update
+    attested: HashSet<String>,
 }
 
 impl PoiScorer {
     pub fn new(config: PoiConfig) -> Self {
-        Self { config }
-    }
-
-    /// Compute PoI score for a node (0.0 = useless, 1.0 = god-tier connection)
-    pub fn poi_score(&self, metrics: &NodeMetrics) -> f64 {
-        // Weighted sum of normalized metrics
-        let upload_norm =
-            NodeMetrics::normalize(metrics, metrics.upload_mbps, self.config.thresholds.upload_mbps);
-        let download_norm = NodeMetrics::normalize(
-            metrics,
-            metrics.download_mbps,
-            self.config.thresholds.download_mbps,
-        );
-        let latency_norm = NodeMetrics::invert_normalize(
-            metrics,
-            metrics.latency_ms,
-            self.config.thresholds.latency_ms,
-        );
-        let uptime_norm =
-            NodeMetrics::normalize(metrics, metrics.uptime_percent, self.config.thresholds.uptime_percent);
-        let stability_norm = NodeMetrics::normalize(
-            metrics,
-            metrics.stability_percent,
-            self.config.thresholds.stability_percent,
-        );
+        Self {
+            config,
+            smoothed: HashMap::new(),
+            stakes: HashMap::new(),
+            attested: HashSet::new(),
+        }
+    }
+
+    pub fn config(&self) -> &PoiConfig {
+        &self.config
+    }
 
-        let score = self.config.weights.upload * upload_norm
-            + self.config.weights.download * download_norm
-            + self.config.weights.latency * latency_norm
-            + self.config.weights.uptime * uptime_norm
-            + self.config.weights.stability * stability_norm;
+    /// Record or update a node's location (e.g. from a registered validator attribute)
+    pub fn set_location(&mut self, node_id: String, location: NodeLocation) {
+        self.config.locations.insert(node_id, location);
+    }
+
+    /// Diversity of `node_id` within `pool` in basis points: the average over region
+    /// and ASN of the share of pool nodes *not* sharing that attribute.
+    /// Nodes with unknown location get 0.
+    pub fn diversity_bps(&self, node_id: &str, pool: &HashMap<String, NodeMetrics>) -> u64 {
+        let Some(location) = self.config.locations.get(node_id) else {
+            return 0;
+        };
+        let located: Vec<&NodeLocation> = pool
+            .keys()
+            .filter_map(|id| self.config.locations.get(id))
+            .collect();
+        let total = located.len().max(1) as u64;
+        let same_region = located.iter().filter(|l| l.region == location.region).count() as u64;
+        let same_asn = located.iter().filter(|l| l.asn == location.asn).count() as u64;
+        let region_bps = BPS_SCALE - same_region.min(total) * BPS_SCALE / total;
+        let asn_bps = BPS_SCALE - same_asn.min(total) * BPS_SCALE / total;
+        (region_bps + asn_bps) / 2
+    }
 
-        // Clamp to 0..=1 and return
-        score.clamp(0.0, 1.0)
+    /// True if `score` clears the minimum eligibility threshold
+    pub fn is_eligible(&self, score: u64) -> bool {
+        score >= self.config.min_score
+    }
+
+    /// Compute PoI score for a node in basis points (0 = useless, `BPS_SCALE` = god-tier connection)
+    pub fn poi_score(&self, metrics: &NodeMetrics) -> u64 {
+        let thresholds = &self.config.thresholds;
+        let weights = &self.config.weights;
+
+        // Weighted sum of normalized metrics, all in u128 to avoid overflow
+        let weighted: u128 = [
+            (weights.upload, normalize_bps(metrics.upload_mbps, thresholds.upload_mbps)),
+            (weights.download, normalize_bps(metrics.download_mbps, thresholds.download_mbps)),
+            (weights.latency, invert_normalize_bps(metrics.latency_ms, thresholds.latency_ms)),
+            (weights.uptime, normalize_bps(metrics.uptime_bps, thresholds.uptime_bps)),
+            (weights.stability, normalize_bps(metrics.stability_bps, thresholds.stability_bps)),
+        ]
+        .iter()
+        .map(|(weight, norm)| *weight as u128 * *norm as u128)
+        .sum();
+
+        // Scale back to basis points and clamp to 0..=BPS_SCALE
+        (weighted / BPS_SCALE as u128).min(BPS_SCALE as u128) as u64
+    }
+
+    /// Build cumulative selection weights in a canonical (sorted by id) order.
+    /// HashMap iteration order differs between nodes, so sorting is required for determinism.
+    fn cumulative_weights(scores: &HashMap<String, u64>) -> (Vec<(String, u128)>, u128) {
+        let mut ids: Vec<&String> = scores.keys().collect();
+        ids.sort();
+
+        let mut cum_weights: Vec<(String, u128)> = Vec::with_capacity(scores.len());
+        let mut total_weight = 0u128;
+        for id in ids {
+            total_weight += scores[id] as u128;
+            cum_weights.push((id.clone(), total_weight));
+        }
+        (cum_weights, total_weight)
     }
 
     /// Deterministic selection: choose validator using a shared `seed_u128`.
     /// IMPORTANT: `seed_u128` must be derived the same way on all nodes for determinism.
-    /// Example: u128::from_be_bytes(sha256(previous_block_hash || epoch) [0..16])
+    /// See `beacon::seed_from_hash`: u128::from_be_bytes(sha256(previous_block_hash || epoch)[0..16])
     pub fn select_validator_with_seed(
         &self,
         pool: &HashMap<String, NodeMetrics>,
         seed_u128: u128,
-    ) -> String {
-        if pool.is_empty() {
-            panic!("No validators in pool!");
-        }
+    ) -> Result<String, ConsensusError> {
+        Self::select_from_scores(&self.score_pool(pool), seed_u128)
+    }
 
-        // Compute cumulative weights
-        let mut cum_weights: Vec<(String, f64)> = Vec::with_capacity(pool.len());
-        let mut total_weight = 0.0f64;
-        for (id, metrics) in pool.iter() {
-            let score = self.poi_score(metrics).max(0.0);
-            // scale to integer-space-like but keep f64
-            let weight = score * 1_000.0;
-            total_weight += weight;
-            cum_weights.push((id.clone(), total_weight));
+    /// Deterministic weighted selection over precomputed scores (e.g., an epoch snapshot)
+    pub fn select_from_scores(
+        scores: &HashMap<String, u64>,
+        seed_u128: u128,
+    ) -> Result<String, ConsensusError> {
+        if scores.is_empty() {
+            return Err(ConsensusError::EmptyPool);
         }
 
+        let (cum_weights, total_weight) = Self::cumulative_weights(scores);
+
         // If total weight is zero (all scores zero), fallback deterministically using lexicographic order + seed
-        if total_weight <= f64::EPSILON {
-            let mut ids: Vec<&String> = pool.keys().collect();
-            ids.sort();
-            let idx = (seed_u128 as usize) % ids.len();
-            return ids[idx].clone().to_owned();
+        if total_weight == 0 {
+            let idx = (seed_u128 % cum_weights.len() as u128) as usize;
+            return Ok(cum_weights[idx].0.clone());
         }
 
-        // Convert seed to fractional in [0,1)
-        let seed_frac = (seed_u128 as f64) / (u128::MAX as f64);
-        let pick = seed_frac * total_weight;
+        // Reduce seed into [0, total_weight)
+        let pick = seed_u128 % total_weight;
 
         // Find first cumulative weight greater than pick
         let idx = cum_weights
             .iter()
             .position(|(_, cum)| pick < *cum)
-            .expect("position must exist when total_weight > 0");
+            .ok_or(ConsensusError::SelectionOutOfRange)?;
+
+        Ok(cum_weights[idx].0.clone())
+    }
 
-        cum_weights[idx].0.clone()
+    /// Failover selection: round `r` excludes the leaders of rounds `0..r` (who failed to
+    /// produce a block) and draws again with `round_seed(seed, r)`. Once every candidate has
+    /// been skipped the exclusion list resets, so rounds keep cycling through the pool.
+    pub fn select_from_scores_for_round(
+        scores: &HashMap<String, u64>,
+        seed_u128: u128,
+        round: u32,
+    ) -> Result<String, ConsensusError> {
+        let mut remaining = scores.clone();
+        let mut leader = Self::select_from_scores(&remaining, seed_u128)?;
+        for r in 1..=round {
+            remaining.remove(&leader);
+            if remaining.is_empty() {
+                remaining = scores.clone();
+            }
+            leader = Self::select_from_scores(&remaining, round_seed(seed_u128, r))?;
+        }
+        Ok(leader)
+    }
+
+    /// Split `scores` into the epoch's active set (top `max_validators` by score, ties
+    /// rotated by `seed_u128` like `select_committee`) and the sorted standby list.
+    /// Standby validators with equal scores get their turn as the seed changes each epoch.
+    pub fn cap_active_set(
+        &self,
+        scores: &HashMap<String, u64>,
+        seed_u128: u128,
+    ) -> (HashMap<String, u64>, Vec<String>) {
+        let max = self.config.max_validators;
+        if max == 0 || scores.len() <= max {
+            return (scores.clone(), Vec::new());
+        }
+        let active: HashSet<String> = Self::select_committee(scores, max, seed_u128)
+            .unwrap_or_default()
+            .into_iter()
+            .collect();
+        let mut standby: Vec<String> = scores
+            .keys()
+            .filter(|id| !active.contains(*id))
+            .cloned()
+            .collect();
+        standby.sort();
+        let active = active
+            .into_iter()
+            .map(|id| {
+                let score = scores[&id];
+                (id, score)
+            })
+            .collect();
+        (active, standby)
+    }
+
+    /// Deterministic committee selection: the top `size` nodes by score.
+    /// Equal scores are ordered by sha256(seed || id) so ties can't be won by picking a low id.
+    pub fn select_committee(
+        scores: &HashMap<String, u64>,
+        size: usize,
+        seed_u128: u128,
+    ) -> Result<Vec<String>, ConsensusError> {
+        if scores.is_empty() {
+            return Err(ConsensusError::EmptyPool);
+        }
+        if size == 0 {
+            return Err(ConsensusError::InvalidCommitteeSize);
+        }
+
+        let mut ranked: Vec<(u64, [u8; 32], &String)> = scores
+            .iter()
+            .map(|(id, score)| {
+                let mut hasher = Sha256::new();
+                hasher.update(seed_u128.to_be_bytes());
+                hasher.update(id.as_bytes());
+                (*score, hasher.finalize().into(), id)
+            })
+            .collect();
+        // Highest score first, then lowest tie-break hash
+        ranked.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| a.1.cmp(&b.1)));
+
+        Ok(ranked
+            .into_iter()
+            .take(size)
+            .map(|(_, _, id)| id.clone())
+            .collect())
     }
 
     /// Non-deterministic RNG helper (ONLY for local tests). For consensus use deterministic seed.
-    pub fn select_validator_rng<R: Rng>(&self, pool: &HashMap<String, NodeMetrics>, rng: &mut R) -> String {
+    pub fn select_validator_rng<R: Rng>(
+        &self,
+        pool: &HashMap<String, NodeMetrics>,
+        rng: &mut R,
+    ) -> Result<String, ConsensusError> {
         if pool.is_empty() {
-            panic!("No validators in pool!");
+            return Err(ConsensusError::EmptyPool);
         }
 
-        // compute cumulative weights
-        let mut cum_weights: Vec<(String, f64)> = Vec::with_capacity(pool.len());
-        let mut total_weight = 0.0f64;
-        for (id, metrics) in pool.iter() {
-            let score = self.poi_score(metrics).max(0.0);
-            let weight = score * 1_000.0;
-            total_weight += weight;
-            cum_weights.push((id.clone(), total_weight));
-        }
+        let (cum_weights, total_weight) = Self::cumulative_weights(&self.score_pool(pool));
 
-        if total_weight <= f64::EPSILON {
+        if total_weight == 0 {
             // fallback: deterministic lexicographic pick
-            let mut ids: Vec<&String> = pool.keys().collect();
-            ids.sort();
-            return ids[0].clone().to_owned();
+            return Ok(cum_weights[0].0.clone());
         }
 
-        let pick = rng.gen_range(0.0..total_weight);
+        let pick = rng.gen_range(0..total_weight);
         let idx = cum_weights
             .iter()
             .position(|(_, cum)| pick < *cum)
-            .expect("position must exist when total_weight > 0");
-        cum_weights[idx].0.clone()
+            .ok_or(ConsensusError::SelectionOutOfRange)?;
+        Ok(cum_weights[idx].0.clone())
     }
 
-    /// Epoch update: Re-score all nodes (call every N blocks)
-    pub fn update_epoch(&mut self, pool: &mut HashMap<String, NodeMetrics>) -> HashMap<String, f64> {
+    /// Raw (unsmoothed) scores for every node in `pool`, including the diversity
+    /// component (which depends on the rest of the pool)
+    pub fn score_pool(&self, pool: &HashMap<String, NodeMetrics>) -> HashMap<String, u64> {
+        let diversity_weight = self.config.weights.diversity as u128;
         pool.iter()
-            .map(|(id, metrics)| (id.clone(), self.poi_score(metrics)))
+            .map(|(id, metrics)| {
+                let bonus = (diversity_weight * self.diversity_bps(id, pool) as u128
+                    / BPS_SCALE as u128) as u64;
+                (id.clone(), (self.poi_score(metrics) + bonus).min(BPS_SCALE))
+            })
+            .collect()
+    }
+
+    /// Note fresh metrics for `node_id` (self report backed by attestations / challenges)
+    /// since the last epoch update. Only consulted when `decay_bps` is non-zero.
+    pub fn mark_attested(&mut self, node_id: &str) {
+        self.attested.insert(node_id.to_string());
+    }
+
+    /// Epoch update: Re-score all nodes (driven every N blocks by `EpochManager`).
+    /// Each score is an EMA: alpha * current + (1 - alpha) * previous, where nodes
+    /// without history start from 0 so a pre-boundary spike can't buy a full score.
+    /// With `decay_bps` set, nodes not attested since the last update ignore their
+    /// (stale) metrics and lose `decay_bps` of their previous score instead.
+    pub fn update_epoch(&mut self, pool: &HashMap<String, NodeMetrics>) -> HashMap<String, u64> {
+        let alpha = self.config.ema_alpha_bps.min(BPS_SCALE) as u128;
+        let decay = self.config.decay_bps.min(BPS_SCALE) as u128;
+        let scale = BPS_SCALE as u128;
+        let mut scores = HashMap::with_capacity(pool.len());
+        for (id, current) in self.score_pool(pool) {
+            let current = current as u128;
+            let previous = self.smoothed.get(&id).copied().unwrap_or(0) as u128;
+            let blended = if decay > 0 && !self.attested.contains(&id) {
+                (previous * (scale - decay) / scale) as u64
+            } else {
+                ((alpha * current + (scale - alpha) * previous) / scale) as u64
+            };
+            self.smoothed.insert(id.clone(), blended);
+            scores.insert(id, blended);
+        }
+        self.attested.clear();
+        scores
+    }
+
+    /// Record the stake currently bonded by `node_id`
+    pub fn set_stake(&mut self, node_id: String, bonded: u64) {
+        self.stakes.insert(node_id, bonded);
+    }
+
+    /// Stake bonded by `node_id` (0 if unknown)
+    pub fn stake(&self, node_id: &str) -> u64 {
+        self.stakes.get(node_id).copied().unwrap_or(0)
+    }
+
+    /// Selection weight for a node with PoI score `poi` (basis points), per `StakeConfig`.
+    /// Returns 0 when the node's bond is below `min_bond`.
+    pub fn selection_weight(&self, node_id: &str, poi: u64) -> u64 {
+        let config = &self.config.stake;
+        let bonded = self.stake(node_id);
+        if bonded < config.min_bond {
+            return 0;
+        }
+        let stake_bps = normalize_bps(bonded, config.bond_cap);
+        match config.mixing {
+            StakeMixing::None => poi,
+            StakeMixing::GeometricMean => (poi * stake_bps).isqrt(),
+            StakeMixing::Linear { poi_bps } => {
+                let poi_bps = poi_bps.min(BPS_SCALE);
+                (poi_bps * poi + (BPS_SCALE - poi_bps) * stake_bps) / BPS_SCALE
+            }
+        }
+    }
+
+    /// Mix bonded stake into `scores`, dropping nodes whose selection weight is 0
+    pub fn stake_weighted(&self, scores: &HashMap<String, u64>) -> HashMap<String, u64> {
+        scores
+            .iter()
+            .map(|(id, poi)| (id.clone(), self.selection_weight(id, *poi)))
+            .filter(|(_, weight)| *weight > 0)
             .collect()
     }
+
+    /// Smoothed score carried for `node_id`, if it has been scored before
+    pub fn smoothed_score(&self, node_id: &str) -> Option<u64> {
+        self.smoothed.get(node_id).copied()
+    }
 }
 
-// Helper trait for RNG (for testing/mocking) — now returns String
-trait WeightedSelect {
-    fn select_validator<R: Rng>(&self, pool: &HashMap<String, NodeMetrics>, rng: &mut R) -> String;
+/// Which PoI input an anomaly was found in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MetricKind {
+    Upload,
+    Download,
+    Latency,
+    Uptime,
+    Stability,
+}
+
+/// What a reported metric was compared against
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnomalySource {
+    /// Independent measurement (challenge results / observer aggregate)
+    Observed,
+    /// The node's own previously accepted metrics
+    Baseline,
+}
+
+/// Operator-facing event emitted when a node's report looks fraudulent
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnomalyEvent {
+    pub node_id: String,
+    pub metric: MetricKind,
+    pub source: AnomalySource,
+    pub reported: u64,
+    pub reference: u64,
+    /// How much better than the reference the claim is, in bps of the reference
+    pub deviation_bps: u64,
+}
+
+/// Flags over-claimed metrics and discounts the offenders' PoI scores
+#[derive(Debug, Clone)]
+pub struct AnomalyDetector {
+    config: FraudConfig,
+    /// node -> last accepted (non-anomalous) report
+    baselines: HashMap<String, NodeMetrics>,
+    /// nodes flagged since the last `apply_discounts`
+    flagged: HashMap<String, Vec<AnomalyEvent>>,
+    events: Vec<AnomalyEvent>,
+}
+
+/// How far `claimed` over-states `reference` in bps; `lower_is_better` flips the direction
+fn overclaim_bps(claimed: u64, reference: u64, lower_is_better: bool) -> u64 {
+    let (better, worse) = if lower_is_better {
+        (reference, claimed)
+    } else {
+        (claimed, reference)
+    };
+    if better <= worse {
+        return 0;
+    }
+    ((better - worse) as u128 * BPS_SCALE as u128 / reference.max(1) as u128)
+        .min(u64::MAX as u128) as u64
+}
+
+impl AnomalyDetector {
+    pub fn new(config: FraudConfig) -> Self {
+        Self {
+            config,
+            baselines: HashMap::new(),
+            flagged: HashMap::new(),
+            events: Vec::new(),
+        }
+    }
+
+    fn compare(
+        &self,
+        reported: &NodeMetrics,
+        reference: &NodeMetrics,
+        source: AnomalySource,
+    ) -> Vec<AnomalyEvent> {
+        [
+            (MetricKind::Upload, reported.upload_mbps, reference.upload_mbps, false),
+            (MetricKind::Download, reported.download_mbps, reference.download_mbps, false),
+            (MetricKind::Latency, reported.latency_ms, reference.latency_ms, true),
+            (MetricKind::Uptime, reported.uptime_bps, reference.uptime_bps, false),
+            (MetricKind::Stability, reported.stability_bps, reference.stability_bps, false),
+        ]
+        .into_iter()
+        .filter_map(|(metric, claimed, reference, lower_is_better)| {
+            let deviation_bps = overclaim_bps(claimed, reference, lower_is_better);
+            (deviation_bps > self.config.max_deviation_bps).then(|| AnomalyEvent {
+                node_id: reported.node_id.clone(),
+                metric,
+                source,
+                reported: claimed,
+                reference,
+                deviation_bps,
+            })
+        })
+        .collect()
+    }
+
+    /// Check a node's self-report against independent observations and its own history.
+    /// Clean reports become the new baseline; anomalies are recorded and returned.
+    pub fn check(&mut self, reported: &NodeMetrics, observed: Option<&NodeMetrics>) -> Vec<AnomalyEvent> {
+        let mut found = Vec::new();
+        if let Some(observed) = observed {
+            found.extend(self.compare(reported, observed, AnomalySource::Observed));
+        }
+        if let Some(baseline) = self.baselines.get(&reported.node_id) {
+            found.extend(self.compare(reported, baseline, AnomalySource::Baseline));
+        }
+
+        if found.is_empty() {
+            self.baselines.insert(reported.node_id.clone(), reported.clone());
+        } else {
+            self.flagged
+                .entry(reported.node_id.clone())
+                .or_default()
+                .extend(found.iter().cloned());
+            self.events.extend(found.iter().cloned());
+        }
+        found
+    }
+
+    /// True if `node_id` has been flagged since the last `apply_discounts`
+    pub fn is_flagged(&self, node_id: &str) -> bool {
+        self.flagged.contains_key(node_id)
+    }
+
+    /// Discount flagged nodes' scores by `discount_bps` and clear the flags
+    pub fn apply_discounts(&mut self, scores: &mut HashMap<String, u64>) {
+        let keep = BPS_SCALE - self.config.discount_bps.min(BPS_SCALE);
+        for node_id in self.flagged.keys() {
+            if let Some(score) = scores.get_mut(node_id) {
+                *score = *score * keep / BPS_SCALE;
+            }
+        }
+        self.flagged.clear();
+    }
+
+    /// Take the events emitted since the last call (for logging / operator alerts)
+    pub fn drain_events(&mut self) -> Vec<AnomalyEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+// Helper trait for RNG (for testing/mocking)
+pub trait WeightedSelect {
+    fn select_validator<R: Rng>(
+        &self,
+        pool: &HashMap<String, NodeMetrics>,
+        rng: &mut R,
+    ) -> Result<String, ConsensusError>;
 }
 
 impl WeightedSelect for PoiScorer {
-    fn select_validator<R: Rng>(&self, pool: &HashMap<String, NodeMetrics>, rng: &mut R) -> String {
+    fn select_validator<R: Rng>(
+        &self,
+        pool: &HashMap<String, NodeMetrics>,
+        rng: &mut R,
+    ) -> Result<String, ConsensusError> {
         self.select_validator_rng(pool, rng)
     }
 }
@@ -201,19 +826,28 @@ mod tests {
     fn build_test_config() -> PoiConfig {
         PoiConfig {
             weights: Weights {
-                upload: 0.25,
-                download: 0.25,
-                latency: 0.20,
-                uptime: 0.20,
-                stability: 0.10,
+                upload: 2_500,
+                download: 2_500,
+                latency: 2_000,
+                uptime: 2_000,
+                stability: 1_000,
+                diversity: 0,
             },
             thresholds: Thresholds {
-                upload_mbps: 100.0,
-                download_mbps: 1000.0,
-                latency_ms: 200.0,
-                uptime_percent: 100.0,
-                stability_percent: 100.0,
+                upload_mbps: 100,
+                download_mbps: 1000,
+                latency_ms: 200,
+                uptime_bps: 10_000,
+                stability_bps: 10_000,
             },
+            fraud: FraudConfig::default(),
+            ema_alpha_bps: BPS_SCALE,
+            decay_bps: 0,
+            min_score: 0,
+            unjail_epochs: 3,
+            locations: HashMap::new(),
+            stake: StakeConfig::default(),
+            max_validators: 0,
         }
     }
 
@@ -223,14 +857,14 @@ mod tests {
         let scorer = PoiScorer::new(config);
         let metrics = NodeMetrics {
             node_id: "test".to_string(),
-            upload_mbps: 100.0,
-            download_mbps: 1000.0,
-            latency_ms: 0.0,
-            uptime_percent: 100.0,
-            stability_percent: 100.0,
+            upload_mbps: 100,
+            download_mbps: 1000,
+            latency_ms: 0,
+            uptime_bps: 10_000,
+            stability_bps: 10_000,
         };
         let score = scorer.poi_score(&metrics);
-        assert_eq!(score, 1.0);
+        assert_eq!(score, BPS_SCALE);
     }
 
     #[test]
@@ -244,11 +878,11 @@ mod tests {
             "A".to_string(),
             NodeMetrics {
                 node_id: "A".to_string(),
-                upload_mbps: 90.0,
-                download_mbps: 900.0,
-                latency_ms: 5.0,
-                uptime_percent: 99.9,
-                stability_percent: 99.9,
+                upload_mbps: 90,
+                download_mbps: 900,
+                latency_ms: 5,
+                uptime_bps: 9_990,
+                stability_bps: 9_990,
             },
         );
 
@@ -257,11 +891,11 @@ mod tests {
             "B".to_string(),
             NodeMetrics {
                 node_id: "B".to_string(),
-                upload_mbps: 40.0,
-                download_mbps: 400.0,
-                latency_ms: 50.0,
-                uptime_percent: 98.0,
-                stability_percent: 97.0,
+                upload_mbps: 40,
+                download_mbps: 400,
+                latency_ms: 50,
+                uptime_bps: 9_800,
+                stability_bps: 9_700,
             },
         );
 
@@ -270,35 +904,38 @@ mod tests {
             "C".to_string(),
             NodeMetrics {
                 node_id: "C".to_string(),
-                upload_mbps: 1.0,
-                download_mbps: 10.0,
-                latency_ms: 180.0,
-                uptime_percent: 80.0,
-                stability_percent: 70.0,
+                upload_mbps: 1,
+                download_mbps: 10,
+                latency_ms: 180,
+                uptime_bps: 8_000,
+                stability_bps: 7_000,
             },
         );
 
         // Use a fixed seed; the highest scorer ("A") should often be selected for most seeds.
         let seed: u128 = 0x123456789abcdef0u128;
-        let winner = scorer.select_validator_with_seed(&pool, seed);
+        let winner = scorer.select_validator_with_seed(&pool, seed).unwrap();
         // We expect a deterministic output. We assert that winner is one of A/B/C
         assert!(["A", "B", "C"].contains(&winner.as_str()));
 
         // Also test rng helper (local only)
         let mut rng = thread_rng();
-        let w2 = scorer.select_validator_rng(&pool, &mut rng);
+        let w2 = scorer.select_validator_rng(&pool, &mut rng).unwrap();
         assert!(["A", "B", "C"].contains(&w2.as_str()));
     }
 
     #[test]
     fn test_select_validator_all_zero_weights() {
         let mut config = build_test_config();
-        // set thresholds such that metrics normalize to 0 (make thresholds tiny)
-        config.thresholds.upload_mbps = 0.0001;
-        config.thresholds.download_mbps = 0.0001;
-        config.thresholds.latency_ms = 0.0001;
-        config.thresholds.uptime_percent = 0.0001;
-        config.thresholds.stability_percent = 0.0001;
+        // zero every weight so all scores collapse to 0
+        config.weights = Weights {
+            upload: 0,
+            download: 0,
+            latency: 0,
+            uptime: 0,
+            stability: 0,
+            diversity: 0,
+        };
 
         let scorer = PoiScorer::new(config);
         let mut pool: HashMap<String, NodeMetrics> = HashMap::new();
@@ -306,28 +943,439 @@ mod tests {
             "x".to_string(),
             NodeMetrics {
                 node_id: "x".to_string(),
-                upload_mbps: 0.0,
-                download_mbps: 0.0,
-                latency_ms: 0.0,
-                uptime_percent: 0.0,
-                stability_percent: 0.0,
+                upload_mbps: 0,
+                download_mbps: 0,
+                latency_ms: 0,
+                uptime_bps: 0,
+                stability_bps: 0,
             },
         );
         pool.insert(
             "y".to_string(),
             NodeMetrics {
                 node_id: "y".to_string(),
-                upload_mbps: 0.0,
-                download_mbps: 0.0,
-                latency_ms: 0.0,
-                uptime_percent: 0.0,
-                stability_percent: 0.0,
+                upload_mbps: 0,
+                download_mbps: 0,
+                latency_ms: 0,
+                uptime_bps: 0,
+                stability_bps: 0,
             },
         );
 
         // Deterministic fallback must return one of them and be deterministic
         let seed = 42u128;
-        let winner = scorer.select_validator_with_seed(&pool, seed);
+        let winner = scorer.select_validator_with_seed(&pool, seed).unwrap();
         assert!(["x", "y"].contains(&winner.as_str()));
+        assert_eq!(winner, scorer.select_validator_with_seed(&pool, seed).unwrap());
+    }
+
+    #[test]
+    fn test_select_validator_empty_pool() {
+        let scorer = PoiScorer::new(build_test_config());
+        let pool: HashMap<String, NodeMetrics> = HashMap::new();
+        assert_eq!(
+            scorer.select_validator_with_seed(&pool, 7),
+            Err(ConsensusError::EmptyPool)
+        );
+        let mut rng = thread_rng();
+        assert_eq!(
+            scorer.select_validator_rng(&pool, &mut rng),
+            Err(ConsensusError::EmptyPool)
+        );
+    }
+
+    #[test]
+    fn test_poi_score_partial_node_is_exact() {
+        let scorer = PoiScorer::new(build_test_config());
+        let metrics = NodeMetrics {
+            node_id: "half".to_string(),
+            upload_mbps: 50,
+            download_mbps: 500,
+            latency_ms: 100,
+            uptime_bps: 5_000,
+            stability_bps: 5_000,
+        };
+        assert_eq!(scorer.poi_score(&metrics), 5_000);
+    }
+
+    #[test]
+    fn test_select_validator_with_seed_walks_cumulative_weights() {
+        let scorer = PoiScorer::new(build_test_config());
+        let mut pool: HashMap<String, NodeMetrics> = HashMap::new();
+        for id in ["a", "b"] {
+            pool.insert(
+                id.to_string(),
+                NodeMetrics {
+                    node_id: id.to_string(),
+                    upload_mbps: 100,
+                    download_mbps: 1000,
+                    latency_ms: 0,
+                    uptime_bps: 10_000,
+                    stability_bps: 10_000,
+                },
+            );
+        }
+        // Two perfect nodes: "a" owns [0, 10_000), "b" owns [10_000, 20_000)
+        assert_eq!(scorer.select_validator_with_seed(&pool, 9_999).unwrap(), "a");
+        assert_eq!(scorer.select_validator_with_seed(&pool, 10_000).unwrap(), "b");
+        assert_eq!(scorer.select_validator_with_seed(&pool, 20_000).unwrap(), "a");
+    }
+
+    #[test]
+    fn test_select_committee_top_n_with_tie_break() {
+        let mut scores: HashMap<String, u64> = HashMap::new();
+        scores.insert("low".to_string(), 100);
+        scores.insert("high".to_string(), 9_000);
+        scores.insert("tie1".to_string(), 5_000);
+        scores.insert("tie2".to_string(), 5_000);
+
+        let committee = PoiScorer::select_committee(&scores, 2, 1).unwrap();
+        assert_eq!(committee[0], "high");
+        assert!(committee[1].starts_with("tie"));
+        assert_eq!(committee, PoiScorer::select_committee(&scores, 2, 1).unwrap());
+
+        // some seed must flip the tie ordering
+        let flipped = (0..64u128)
+            .map(|seed| PoiScorer::select_committee(&scores, 2, seed).unwrap()[1].clone())
+            .any(|second| second != committee[1]);
+        assert!(flipped);
+
+        assert_eq!(PoiScorer::select_committee(&scores, 10, 1).unwrap().len(), 4);
+        assert_eq!(
+            PoiScorer::select_committee(&scores, 0, 1),
+            Err(ConsensusError::InvalidCommitteeSize)
+        );
+    }
+
+    #[test]
+    fn test_round_timeouts() {
+        let timeouts = RoundTimeouts::new(1_000, 500).unwrap();
+        assert_eq!(timeouts.timeout_for_round(2), 2_000);
+        assert_eq!(timeouts.round_at(999), 0);
+        assert_eq!(timeouts.round_at(1_000), 1);
+        assert_eq!(timeouts.round_at(2_499), 1);
+        assert_eq!(timeouts.round_at(2_500), 2);
+        assert_eq!(RoundTimeouts::new(0, 10), Err(ConsensusError::InvalidRoundTimeout));
+    }
+
+    #[test]
+    fn test_failover_rounds_skip_previous_leaders() {
+        let scores: HashMap<String, u64> = [("a", 9_000), ("b", 5_000), ("c", 1_000)]
+            .iter()
+            .map(|(id, s)| (id.to_string(), *s))
+            .collect();
+        let seed = 0xfeed_u128;
+
+        let rounds: Vec<String> = (0..3)
+            .map(|r| PoiScorer::select_from_scores_for_round(&scores, seed, r).unwrap())
+            .collect();
+        assert_eq!(rounds[0], PoiScorer::select_from_scores(&scores, seed).unwrap());
+        // the first three rounds visit every candidate exactly once
+        let mut visited = rounds.clone();
+        visited.sort();
+        assert_eq!(visited, vec!["a", "b", "c"]);
+        // and keep cycling afterwards
+        assert!(PoiScorer::select_from_scores_for_round(&scores, seed, 7).is_ok());
+    }
+
+    #[test]
+    fn test_anomaly_detection_discounts_overclaimers() {
+        let mut detector = AnomalyDetector::new(FraudConfig::default());
+        let honest = NodeMetrics {
+            node_id: "n".to_string(),
+            upload_mbps: 50,
+            download_mbps: 500,
+            latency_ms: 40,
+            uptime_bps: 9_900,
+            stability_bps: 9_900,
+        };
+        assert!(detector.check(&honest, Some(&honest)).is_empty());
+
+        // claims 4x the observed upload and a quarter of the historical latency
+        let inflated = NodeMetrics {
+            upload_mbps: 200,
+            latency_ms: 10,
+            ..honest.clone()
+        };
+        let events = detector.check(&inflated, Some(&honest));
+        assert!(events.iter().any(|e| e.metric == MetricKind::Upload
+            && e.source == AnomalySource::Observed
+            && e.deviation_bps == 30_000));
+        assert!(events.iter().any(|e| e.metric == MetricKind::Latency
+            && e.source == AnomalySource::Baseline));
+        assert!(detector.is_flagged("n"));
+
+        let mut scores: HashMap<String, u64> = [("n".to_string(), 8_000), ("m".to_string(), 8_000)]
+            .into_iter()
+            .collect();
+        detector.apply_discounts(&mut scores);
+        assert_eq!(scores["n"], 4_000);
+        assert_eq!(scores["m"], 8_000);
+        assert!(!detector.is_flagged("n"));
+        assert_eq!(detector.drain_events().len(), events.len());
+        assert!(detector.drain_events().is_empty());
+
+        // under-claiming is never fraud
+        let modest = NodeMetrics {
+            upload_mbps: 1,
+            ..honest
+        };
+        assert!(detector.check(&modest, None).is_empty());
+    }
+
+    #[test]
+    fn test_update_epoch_ema_smoothing() {
+        let mut config = build_test_config();
+        config.ema_alpha_bps = 5_000;
+        let mut scorer = PoiScorer::new(config);
+
+        let perfect = NodeMetrics {
+            node_id: "n".to_string(),
+            upload_mbps: 100,
+            download_mbps: 1000,
+            latency_ms: 0,
+            uptime_bps: 10_000,
+            stability_bps: 10_000,
+        };
+        let dead = NodeMetrics {
+            node_id: "n".to_string(),
+            upload_mbps: 0,
+            download_mbps: 0,
+            latency_ms: 200,
+            uptime_bps: 0,
+            stability_bps: 0,
+        };
+        let pool = |m: &NodeMetrics| -> HashMap<String, NodeMetrics> {
+            [("n".to_string(), m.clone())].into_iter().collect()
+        };
+
+        // a fresh node ramps up instead of jumping straight to its spike
+        assert_eq!(scorer.update_epoch(&pool(&perfect))["n"], 5_000);
+        assert_eq!(scorer.update_epoch(&pool(&perfect))["n"], 7_500);
+        assert_eq!(scorer.update_epoch(&pool(&dead))["n"], 3_750);
+        assert_eq!(scorer.smoothed_score("n"), Some(3_750));
+
+        // alpha = 100% keeps only the latest score
+        let mut unsmoothed = PoiScorer::new(build_test_config());
+        assert_eq!(unsmoothed.update_epoch(&pool(&perfect))["n"], BPS_SCALE);
+    }
+
+    #[test]
+    fn test_score_decays_without_attestations() {
+        let mut config = build_test_config();
+        config.decay_bps = 5_000;
+        config.min_score = 3_000;
+        let mut scorer = PoiScorer::new(config);
+
+        let pool: HashMap<String, NodeMetrics> = [(
+            "n".to_string(),
+            NodeMetrics {
+                node_id: "n".to_string(),
+                upload_mbps: 100,
+                download_mbps: 1000,
+                latency_ms: 0,
+                uptime_bps: 10_000,
+                stability_bps: 10_000,
+            },
+        )]
+        .into_iter()
+        .collect();
+
+        scorer.mark_attested("n");
+        assert_eq!(scorer.update_epoch(&pool)["n"], BPS_SCALE);
+
+        // the same great metrics keep being reported, but nobody attests them any more
+        assert_eq!(scorer.update_epoch(&pool)["n"], 5_000);
+        let faded = scorer.update_epoch(&pool)["n"];
+        assert_eq!(faded, 2_500);
+        assert!(!scorer.is_eligible(faded));
+
+        // a fresh attestation restores normal scoring
+        scorer.mark_attested("n");
+        assert_eq!(scorer.update_epoch(&pool)["n"], BPS_SCALE);
+    }
+
+    #[test]
+    fn test_diversity_boosts_under_represented_nodes() {
+        let mut config = build_test_config();
+        // trade some upload weight for diversity
+        config.weights.upload = 1_500;
+        config.weights.diversity = 1_000;
+        let mut scorer = PoiScorer::new(config);
+
+        let mut pool: HashMap<String, NodeMetrics> = HashMap::new();
+        for id in ["dc1", "dc2", "dc3", "home"] {
+            pool.insert(
+                id.to_string(),
+                NodeMetrics {
+                    node_id: id.to_string(),
+                    upload_mbps: 50,
+                    download_mbps: 500,
+                    latency_ms: 50,
+                    uptime_bps: 9_900,
+                    stability_bps: 9_900,
+                },
+            );
+        }
+        for id in ["dc1", "dc2", "dc3"] {
+            scorer.set_location(
+                id.to_string(),
+                NodeLocation {
+                    region: "us-east".to_string(),
+                    asn: 16509,
+                },
+            );
+        }
+        scorer.set_location(
+            "home".to_string(),
+            NodeLocation {
+                region: "af-south".to_string(),
+                asn: 37100,
+            },
+        );
+
+        assert_eq!(scorer.diversity_bps("dc1", &pool), 2_500);
+        assert_eq!(scorer.diversity_bps("home", &pool), 7_500);
+        assert_eq!(scorer.diversity_bps("unknown", &pool), 0);
+
+        let scores = scorer.score_pool(&pool);
+        assert!(scores["home"] > scores["dc1"]);
+        assert_eq!(scores["dc1"], scores["dc2"]);
+    }
+
+    #[test]
+    fn test_config_validation() {
+        assert!(build_test_config().validate().is_ok());
+
+        let mut config = build_test_config();
+        config.weights.stability = 3_000;
+        assert_eq!(config.validate(), Err(ConfigError::WeightSum(12_000)));
+
+        let mut config = build_test_config();
+        config.thresholds.latency_ms = 0;
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::ZeroThreshold("thresholds.latency_ms"))
+        );
+
+        let mut config = build_test_config();
+        config.ema_alpha_bps = 0;
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::OutOfRange("ema_alpha_bps"))
+        );
+
+        let mut config = build_test_config();
+        config.unjail_epochs = 0;
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::OutOfRange("unjail_epochs"))
+        );
+    }
+
+    #[test]
+    fn test_config_from_file() {
+        let dir = std::env::temp_dir().join(format!("netchain-poi-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let toml_path = dir.join("poi.toml");
+        std::fs::write(
+            &toml_path,
+            "[weights]\nupload = 2500\ndownload = 2500\nlatency = 2000\nuptime = 2000\nstability = 1000\n\n\
+             [thresholds]\nupload_mbps = 100\ndownload_mbps = 1000\nlatency_ms = 200\n\
+             uptime_bps = 10000\nstability_bps = 10000\n",
+        )
+        .unwrap();
+        let config = PoiConfig::from_file(&toml_path).unwrap();
+        assert_eq!(config.thresholds.download_mbps, 1000);
+        assert_eq!(config.ema_alpha_bps, BPS_SCALE);
+
+        let json_path = dir.join("poi.json");
+        let mut json = serde_json::to_value(build_test_config()).unwrap();
+        json["weights"]["upload"] = serde_json::json!(-2500);
+        std::fs::write(&json_path, json.to_string()).unwrap();
+        assert!(matches!(
+            PoiConfig::from_file(&json_path),
+            Err(ConfigError::Parse(_))
+        ));
+
+        assert!(matches!(
+            PoiConfig::from_file(dir.join("poi.yaml")),
+            Err(ConfigError::UnsupportedFormat(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_stake_mixing_and_min_bond() {
+        let mut config = build_test_config();
+        config.stake = StakeConfig {
+            mixing: StakeMixing::GeometricMean,
+            min_bond: 1_000,
+            bond_cap: 100_000,
+        };
+        let mut scorer = PoiScorer::new(config);
+        scorer.set_stake("fast-poor".to_string(), 1_000);
+        scorer.set_stake("balanced".to_string(), 40_000);
+        scorer.set_stake("unbonded".to_string(), 999);
+
+        // sqrt(9_000 * 100) = 948, sqrt(4_000 * 4_000) = 4_000
+        assert_eq!(scorer.selection_weight("fast-poor", 9_000), 948);
+        assert_eq!(scorer.selection_weight("balanced", 4_000), 4_000);
+        assert_eq!(scorer.selection_weight("unbonded", 10_000), 0);
+
+        let scores: HashMap<String, u64> = [
+            ("fast-poor".to_string(), 9_000),
+            ("balanced".to_string(), 4_000),
+            ("unbonded".to_string(), 10_000),
+        ]
+        .into_iter()
+        .collect();
+        let weighted = scorer.stake_weighted(&scores);
+        assert_eq!(weighted.len(), 2);
+        assert!(weighted["balanced"] > weighted["fast-poor"]);
+
+        let mut linear = build_test_config();
+        linear.stake = StakeConfig {
+            mixing: StakeMixing::Linear { poi_bps: 7_000 },
+            min_bond: 0,
+            bond_cap: 100_000,
+        };
+        let scorer = PoiScorer::new(linear);
+        // 0.7 * 10_000 + 0.3 * 0
+        assert_eq!(scorer.selection_weight("anyone", 10_000), 7_000);
+
+        let mut invalid = build_test_config();
+        invalid.stake.mixing = StakeMixing::GeometricMean;
+        assert_eq!(
+            invalid.validate(),
+            Err(ConfigError::ZeroThreshold("stake.bond_cap"))
+        );
+    }
+
+    #[test]
+    fn test_cap_active_set_rotates_equal_scores() {
+        let mut config = build_test_config();
+        config.max_validators = 3;
+        let scorer = PoiScorer::new(config);
+
+        let mut scores: HashMap<String, u64> =
+            (0..5).map(|i| (format!("standby{}", i), 5_000)).collect();
+        scores.insert("top".to_string(), 9_000);
+
+        let mut had_turn = HashSet::new();
+        for epoch_seed in 0..32u128 {
+            let (active, standby) = scorer.cap_active_set(&scores, epoch_seed);
+            assert_eq!(active.len(), 3);
+            assert_eq!(standby.len(), 3);
+            assert!(active.contains_key("top"));
+            assert_eq!(scorer.cap_active_set(&scores, epoch_seed).0, active);
+            had_turn.extend(active.into_keys());
+        }
+        // every equal-score candidate got at least one epoch in the active set
+        assert_eq!(had_turn.len(), 6);
+
+        let unbounded = PoiScorer::new(build_test_config());
+        assert_eq!(unbounded.cap_active_set(&scores, 0).0.len(), 6);
     }
 }
@@ -0,0 +1,261 @@
+// src/consensus/messages.rs
+
+//! Wire messages for running PoI/BFT consensus between nodes
+//! - `Proposal`: the round's proposer broadcasts a block for a (height, round)
+//! - `Prevote` / `Precommit`: committee votes (see `finality::Vote`)
+//! - `Commit`: the precommit aggregate that finalized a block
+//!
+//! Every message has one canonical binary encoding (fixed-int, little-endian bincode)
+//! so peers hash and sign exactly the same bytes.
+
+use crate::block::Block;
+use crate::finality::{Commit, FinalityError, Vote, VoteType};
+use crate::validator::ValidatorRegistry;
+use base64::{Engine as _, engine::general_purpose};
+use bincode::Options;
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Reasons a consensus message can be rejected
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum MessageError {
+    /// The bytes don't decode to a consensus message
+    #[error("malformed consensus message")]
+    Malformed,
+    /// A vote arrived wrapped as the wrong step (e.g. a precommit sent as `Prevote`)
+    #[error("vote sent as the wrong step")]
+    WrongVoteType,
+    /// The proposed block's hash or height doesn't match the proposal
+    #[error("block doesn't match the proposal")]
+    BlockMismatch,
+    #[error("unknown validator")]
+    UnknownValidator,
+    #[error("validator is not in the committee")]
+    NotInCommittee,
+    #[error("invalid message signature")]
+    InvalidSignature,
+    /// Vote or commit verification failed
+    #[error("vote verification failed: {0}")]
+    Finality(#[from] FinalityError),
+}
+
+fn canonical_options() -> impl Options {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+}
+
+/// Proposer's signed block for a (height, round)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Proposal {
+    pub height: u64,
+    pub round: u32,
+    pub block: Block,
+    /// Account address of the proposer
+    pub proposer: String,
+    /// Signature by the proposer's consensus key, base64
+    pub signature: String,
+}
+
+impl Proposal {
+    fn signing_bytes(height: u64, round: u32, block_hash: &str, proposer: &str) -> Vec<u8> {
+        canonical_options()
+            .serialize(&("proposal", height, round, block_hash, proposer))
+            .expect("bincode serialization should succeed for proposal")
+    }
+
+    /// Sign a proposal of `block` (at its own height) with the proposer's consensus keypair
+    pub fn sign(block: Block, round: u32, proposer: String, keypair: &Keypair) -> Self {
+        let sig: Signature = keypair.sign(&Self::signing_bytes(
+            block.index,
+            round,
+            &block.hash,
+            &proposer,
+        ));
+        Proposal {
+            height: block.index,
+            round,
+            block,
+            proposer,
+            signature: general_purpose::STANDARD.encode(sig.to_bytes()),
+        }
+    }
+
+    /// Check the block matches the proposal and the signature against the registered key
+    pub fn verify(&self, registry: &ValidatorRegistry) -> Result<(), MessageError> {
+        if self.block.index != self.height || self.block.recalculate_hash() != self.block.hash {
+            return Err(MessageError::BlockMismatch);
+        }
+        let info = registry
+            .get(&self.proposer)
+            .ok_or(MessageError::UnknownValidator)?;
+        let pk_bytes = general_purpose::STANDARD
+            .decode(&info.consensus_pubkey)
+            .map_err(|_| MessageError::InvalidSignature)?;
+        let sig_bytes = general_purpose::STANDARD
+            .decode(&self.signature)
+            .map_err(|_| MessageError::InvalidSignature)?;
+        let public_key =
+            PublicKey::from_bytes(&pk_bytes).map_err(|_| MessageError::InvalidSignature)?;
+        let signature =
+            Signature::from_bytes(&sig_bytes).map_err(|_| MessageError::InvalidSignature)?;
+        public_key
+            .verify(
+                &Self::signing_bytes(self.height, self.round, &self.block.hash, &self.proposer),
+                &signature,
+            )
+            .map_err(|_| MessageError::InvalidSignature)
+    }
+}
+
+/// Any message exchanged while running consensus for a height
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum ConsensusMessage {
+    Proposal(Box<Proposal>),
+    Prevote(Vote),
+    Precommit(Vote),
+    Commit(Commit),
+}
+
+impl ConsensusMessage {
+    /// Canonical wire encoding
+    pub fn encode(&self) -> Vec<u8> {
+        canonical_options()
+            .serialize(self)
+            .expect("bincode serialization should succeed for consensus message")
+    }
+
+    /// Decode a message produced by `encode`
+    pub fn decode(bytes: &[u8]) -> Result<Self, MessageError> {
+        canonical_options()
+            .deserialize(bytes)
+            .map_err(|_| MessageError::Malformed)
+    }
+
+    /// Height the message is about
+    pub fn height(&self) -> u64 {
+        match self {
+            ConsensusMessage::Proposal(p) => p.height,
+            ConsensusMessage::Prevote(v) | ConsensusMessage::Precommit(v) => v.height,
+            ConsensusMessage::Commit(c) => c.height,
+        }
+    }
+
+    /// Validator that signed the message (`None` for commits, which carry many signers)
+    pub fn sender(&self) -> Option<&str> {
+        match self {
+            ConsensusMessage::Proposal(p) => Some(&p.proposer),
+            ConsensusMessage::Prevote(v) | ConsensusMessage::Precommit(v) => Some(&v.validator),
+            ConsensusMessage::Commit(_) => None,
+        }
+    }
+
+    /// Verify signatures and that signers belong to `committee` (address -> voting weight)
+    pub fn verify(
+        &self,
+        registry: &ValidatorRegistry,
+        committee: &HashMap<String, u64>,
+    ) -> Result<(), MessageError> {
+        if let Some(sender) = self.sender()
+            && !committee.contains_key(sender)
+        {
+            return Err(MessageError::NotInCommittee);
+        }
+        match self {
+            ConsensusMessage::Proposal(p) => p.verify(registry),
+            ConsensusMessage::Prevote(v) => Self::verify_vote(v, VoteType::Prevote, registry),
+            ConsensusMessage::Precommit(v) => Self::verify_vote(v, VoteType::Precommit, registry),
+            ConsensusMessage::Commit(c) => c
+                .verify(registry, committee)
+                .map_err(MessageError::Finality),
+        }
+    }
+
+    fn verify_vote(
+        vote: &Vote,
+        expected: VoteType,
+        registry: &ValidatorRegistry,
+    ) -> Result<(), MessageError> {
+        if vote.vote_type != expected {
+            return Err(MessageError::WrongVoteType);
+        }
+        vote.verify(registry).map_err(MessageError::Finality)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::generate_ed25519_keypair;
+    use crate::validator::testing::registry_with;
+
+    fn setup() -> (ValidatorRegistry, Keypair, HashMap<String, u64>) {
+        let keypair = generate_ed25519_keypair();
+        let registry = registry_with("alice", &keypair);
+        let committee = [("alice".to_string(), 10_000)].into_iter().collect();
+        (registry, keypair, committee)
+    }
+
+    #[test]
+    fn test_proposal_roundtrip_and_verify() {
+        let (registry, keypair, committee) = setup();
+        let block = Block::new(5, "txs".to_string(), "prev".to_string());
+        let msg = ConsensusMessage::Proposal(Box::new(Proposal::sign(
+            block,
+            1,
+            "alice".to_string(),
+            &keypair,
+        )));
+
+        let bytes = msg.encode();
+        let decoded = ConsensusMessage::decode(&bytes).unwrap();
+        assert_eq!(decoded.encode(), bytes);
+        assert_eq!(decoded.height(), 5);
+        assert!(decoded.verify(&registry, &committee).is_ok());
+
+        // tampering with the block breaks the proposal
+        let ConsensusMessage::Proposal(mut tampered) = decoded else {
+            panic!("expected a proposal");
+        };
+        tampered.block.data = "other txs".to_string();
+        assert_eq!(tampered.verify(&registry), Err(MessageError::BlockMismatch));
+        tampered.block.hash = tampered.block.recalculate_hash();
+        assert_eq!(
+            tampered.verify(&registry),
+            Err(MessageError::InvalidSignature)
+        );
+
+        assert_eq!(
+            ConsensusMessage::decode(&bytes[..bytes.len() / 2]).unwrap_err(),
+            MessageError::Malformed
+        );
+    }
+
+    #[test]
+    fn test_votes_checked_by_step_and_committee() {
+        let (registry, keypair, committee) = setup();
+        let precommit = Vote::sign(
+            VoteType::Precommit,
+            5,
+            "abc".to_string(),
+            "alice".to_string(),
+            &keypair,
+        );
+
+        assert!(
+            ConsensusMessage::Precommit(precommit.clone())
+                .verify(&registry, &committee)
+                .is_ok()
+        );
+        assert_eq!(
+            ConsensusMessage::Prevote(precommit.clone()).verify(&registry, &committee),
+            Err(MessageError::WrongVoteType)
+        );
+        assert_eq!(
+            ConsensusMessage::Precommit(precommit).verify(&registry, &HashMap::new()),
+            Err(MessageError::NotInCommittee)
+        );
+    }
+}
@@ -0,0 +1,90 @@
+// src/diff.rs
+
+//! Per-block account diffs
+//! - `StateDiff`: every account a block created, modified or deleted, with its value
+//!   before and after the block
+//! - Produced by `State::apply_block`, kept next to the block (`Blockchain::record_state_diff`)
+//! - Undoing a block is applying the `before` values (`State::revert_diff`); indexers can
+//!   consume the serialized form directly
+//! - Accounts the dust policy removed are listed with the balance that was burned; they
+//!   also show up as deleted
+
+use crate::state::Account;
+use serde::{Deserialize,Serialize};
+use std::collections::BTreeMap;
+
+/// What happened to one account
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum ChangeKind{
+    Created,
+    Modified,
+    Deleted,
+}
+
+/// An account's value before and after a block; `None` means it didn't exist
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct AccountChange{
+    pub before:Option<Account>,
+    pub after:Option<Account>,
+}
+
+impl AccountChange{
+    pub fn kind(&self)->ChangeKind{
+        match (&self.before,&self.after){
+            (None,_)=>ChangeKind::Created,
+            (Some(_),None)=>ChangeKind::Deleted,
+            (Some(_),Some(_))=>ChangeKind::Modified,
+        }
+    }
+}
+
+/// Account changes made by the block at `height`
+#[derive(Debug,Clone,Default,Serialize,Deserialize,PartialEq,Eq)]
+pub struct StateDiff{
+    pub height:u64,
+    /// address -> change; accounts written but left unchanged are omitted
+    pub changes:BTreeMap<String,AccountChange>,
+    /// address -> balance burned when the account was removed as dust
+    #[serde(default)]
+    pub dust_removed:BTreeMap<String,u64>,
+}
+
+impl StateDiff{
+    pub fn is_empty(&self)->bool{
+        self.changes.is_empty()
+    }
+
+    /// Addresses changed in a particular way
+    pub fn addresses(&self,kind:ChangeKind)->impl Iterator<Item=&str>{
+        self.changes
+        .iter()
+        .filter(move |(_,change)| change.kind()==kind)
+        .map(|(address,_)| address.as_str())
+    }
+
+    /// The block's writes in the shape `StateStore::commit_block` takes
+    pub fn store_changes(&self)->Vec<(String,Option<Account>)>{
+        self.changes
+        .iter()
+        .map(|(address,change)| (address.clone(),change.after.clone()))
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_change_kinds(){
+        let mut diff=StateDiff{height:4,..Default::default()};
+        diff.changes.insert("a".to_string(),AccountChange{before:None,after:Some(Account::new(1))});
+        diff.changes.insert("b".to_string(),AccountChange{before:Some(Account::new(1)),after:Some(Account::new(2))});
+        diff.changes.insert("c".to_string(),AccountChange{before:Some(Account::new(1)),after:None});
+
+        assert_eq!(diff.addresses(ChangeKind::Created).collect::<Vec<_>>(),vec!["a"]);
+        assert_eq!(diff.addresses(ChangeKind::Modified).collect::<Vec<_>>(),vec!["b"]);
+        assert_eq!(diff.addresses(ChangeKind::Deleted).collect::<Vec<_>>(),vec!["c"]);
+        assert_eq!(diff.store_changes()[2],("c".to_string(),None));
+    }
+}
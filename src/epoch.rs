@@ -0,0 +1,478 @@
+// src/epoch.rs
+
+//! Epoch management for PoI consensus
+//! - Tracks epoch boundaries (every `epoch_length` blocks)
+//! - Snapshots the scored validator set at each boundary
+//! - Pins leader selection to the snapshot for the whole epoch
+//! - Picks the epoch committee (top-N by PoI score) for proposal rotation and finality voting
+//! - Publishes the epoch's full leader schedule so nodes know in advance when they propose
+//!
+//! Metric updates that arrive mid-epoch only take effect at the next boundary,
+//! so they can't retroactively change who leads blocks in the current epoch.
+
+use crate::block::Block;
+use crate::consensus::{ConsensusError, NodeMetrics, PoiScorer};
+use crate::validator::ValidatorRegistry;
+use std::collections::HashMap;
+
+/// Frozen view of the validator set for a single epoch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EpochSnapshot {
+    /// Epoch number (height / epoch_length)
+    pub epoch: u64,
+    /// First block height of the epoch
+    pub start_height: u64,
+    /// Metrics as they were at the boundary
+    pub metrics: HashMap<String, NodeMetrics>,
+    /// PoI scores (basis points) computed from `metrics`, limited to eligible nodes
+    pub scores: HashMap<String, u64>,
+    /// Top-N validators by score, in rank order
+    pub committee: Vec<String>,
+    /// Eligible validators left out of the active set by `max_validators`, sorted
+    pub standby: Vec<String>,
+    /// Round-0 proposer for every height of the epoch, published at the boundary
+    pub schedule: LeaderSchedule,
+}
+
+/// Proposer for each height of one epoch (height -> validator)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LeaderSchedule {
+    pub start_height: u64,
+    /// `slots[i]` proposes block `start_height + i`; empty if the committee is empty
+    pub slots: Vec<String>,
+}
+
+impl LeaderSchedule {
+    /// Round-robin over `committee` for `length` heights starting at `start_height`
+    pub fn round_robin(start_height: u64, length: u64, committee: &[String]) -> Self {
+        let slots = if committee.is_empty() {
+            Vec::new()
+        } else {
+            (0..length)
+                .map(|offset| committee[(offset % committee.len() as u64) as usize].clone())
+                .collect()
+        };
+        Self {
+            start_height,
+            slots,
+        }
+    }
+
+    /// Expected proposer for `height`, if it falls in this epoch
+    pub fn leader_at(&self, height: u64) -> Option<&str> {
+        let offset = height.checked_sub(self.start_height)?;
+        self.slots.get(offset as usize).map(String::as_str)
+    }
+
+    /// Heights at which `validator` must produce a block
+    pub fn heights_for(&self, validator: &str) -> Vec<u64> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter(|(_, v)| v.as_str() == validator)
+            .map(|(offset, _)| self.start_height + offset as u64)
+            .collect()
+    }
+}
+
+/// Drives `PoiScorer::update_epoch` at epoch boundaries and serves pinned selection
+#[derive(Debug, Clone)]
+pub struct EpochManager {
+    scorer: PoiScorer,
+    epoch_length: u64,
+    committee_size: usize,
+    current: Option<EpochSnapshot>,
+}
+
+/// Default number of validators in an epoch committee
+pub const DEFAULT_COMMITTEE_SIZE: usize = 21;
+
+impl EpochManager {
+    /// Create a manager that rotates epochs every `epoch_length` blocks
+    pub fn new(scorer: PoiScorer, epoch_length: u64) -> Result<Self, ConsensusError> {
+        if epoch_length == 0 {
+            return Err(ConsensusError::InvalidEpochLength);
+        }
+        Ok(Self {
+            scorer,
+            epoch_length,
+            committee_size: DEFAULT_COMMITTEE_SIZE,
+            current: None,
+        })
+    }
+
+    /// Override the committee size (takes effect at the next snapshot)
+    pub fn with_committee_size(mut self, committee_size: usize) -> Result<Self, ConsensusError> {
+        if committee_size == 0 {
+            return Err(ConsensusError::InvalidCommitteeSize);
+        }
+        self.committee_size = committee_size;
+        Ok(self)
+    }
+
+    /// Scorer used at epoch boundaries (e.g. to update bonded stakes)
+    pub fn scorer_mut(&mut self) -> &mut PoiScorer {
+        &mut self.scorer
+    }
+
+    pub fn epoch_length(&self) -> u64 {
+        self.epoch_length
+    }
+
+    /// Epoch a block height belongs to
+    pub fn epoch_for_height(&self, height: u64) -> u64 {
+        height / self.epoch_length
+    }
+
+    /// True if `height` is the first block of an epoch
+    pub fn is_epoch_boundary(&self, height: u64) -> bool {
+        height.is_multiple_of(self.epoch_length)
+    }
+
+    /// Currently pinned snapshot, if any
+    pub fn current_snapshot(&self) -> Option<&EpochSnapshot> {
+        self.current.as_ref()
+    }
+
+    /// Notify the manager of a new block height.
+    /// Takes a fresh snapshot of `pool` when `height` enters a new epoch (or none exists yet),
+    /// using `seed_u128` to break committee ties. Only validators `registry` counts as active
+    /// that epoch are selectable, so jailed ones stay out until their jail (or score-jail
+    /// streak) ends. Returns true if a new snapshot was taken.
+    pub fn on_block(
+        &mut self,
+        height: u64,
+        pool: &HashMap<String, NodeMetrics>,
+        registry: &ValidatorRegistry,
+        seed_u128: u128,
+    ) -> bool {
+        let epoch = self.epoch_for_height(height);
+        if self.current.as_ref().is_some_and(|s| s.epoch == epoch) {
+            return false;
+        }
+
+        let mut scores = self.scorer.update_epoch(&registry.pool(pool, epoch));
+        // Below-threshold nodes are never selectable, even if the caller didn't filter them
+        scores.retain(|_, score| self.scorer.is_eligible(*score));
+        // Selection weight mixes in bonded stake; under-bonded nodes drop out
+        let scores = self.scorer.stake_weighted(&scores);
+        // Only the top `max_validators` are active; equal-score candidates rotate by seed
+        let (scores, standby) = self.scorer.cap_active_set(&scores, seed_u128);
+        // An empty pool yields an empty committee; selection then reports EmptyPool
+        let committee = PoiScorer::select_committee(&scores, self.committee_size, seed_u128)
+            .unwrap_or_default();
+        let start_height = epoch * self.epoch_length;
+        let schedule = LeaderSchedule::round_robin(start_height, self.epoch_length, &committee);
+        self.current = Some(EpochSnapshot {
+            epoch,
+            start_height,
+            metrics: pool.clone(),
+            scores,
+            committee,
+            standby,
+            schedule,
+        });
+        true
+    }
+
+    /// Snapshot for the epoch containing `height`
+    fn snapshot_for(&self, height: u64) -> Result<&EpochSnapshot, ConsensusError> {
+        let snapshot = self
+            .current
+            .as_ref()
+            .ok_or(ConsensusError::NoEpochSnapshot)?;
+        if snapshot.epoch != self.epoch_for_height(height) {
+            return Err(ConsensusError::EpochMismatch);
+        }
+        Ok(snapshot)
+    }
+
+    /// Proposer schedule for the pinned epoch, if a snapshot exists
+    pub fn leader_schedule(&self) -> Option<&LeaderSchedule> {
+        self.current.as_ref().map(|s| &s.schedule)
+    }
+
+    /// Round-robin block proposer for `height` among the epoch committee
+    pub fn proposer_for_height(&self, height: u64) -> Result<String, ConsensusError> {
+        self.proposer_for_round(height, 0)
+    }
+
+    /// Proposer for `height` after `round` failovers: each round moves to the next committee member
+    pub fn proposer_for_round(&self, height: u64, round: u32) -> Result<String, ConsensusError> {
+        let snapshot = self.snapshot_for(height)?;
+        if snapshot.committee.is_empty() {
+            return Err(ConsensusError::EmptyPool);
+        }
+        let len = snapshot.committee.len() as u64;
+        let slot = ((height - snapshot.start_height) % len + round as u64 % len) % len;
+        Ok(snapshot.committee[slot as usize].clone())
+    }
+
+    /// Check that `block` was produced by the committee member expected for its tagged round
+    pub fn validate_block_proposer(&self, block: &Block) -> Result<(), ConsensusError> {
+        let expected = self.proposer_for_round(block.index, block.consensus.round)?;
+        if block.consensus.proposer.as_deref() != Some(expected.as_str()) {
+            return Err(ConsensusError::UnexpectedProposer);
+        }
+        Ok(())
+    }
+
+    /// Finality voting weights (PoI scores) of the committee for `height`
+    pub fn committee_weights(&self, height: u64) -> Result<HashMap<String, u64>, ConsensusError> {
+        let snapshot = self.snapshot_for(height)?;
+        Ok(snapshot
+            .committee
+            .iter()
+            .map(|id| (id.clone(), snapshot.scores[id]))
+            .collect())
+    }
+
+    /// Select the leader for `height` from the pinned snapshot.
+    /// Fails if no snapshot exists or `height` is outside the pinned epoch.
+    pub fn select_validator(&self, height: u64, seed_u128: u128) -> Result<String, ConsensusError> {
+        self.select_validator_for_round(height, seed_u128, 0)
+    }
+
+    /// Weighted selection for `height` after `round` failovers (see `select_from_scores_for_round`)
+    pub fn select_validator_for_round(
+        &self,
+        height: u64,
+        seed_u128: u128,
+        round: u32,
+    ) -> Result<String, ConsensusError> {
+        let snapshot = self.snapshot_for(height)?;
+        PoiScorer::select_from_scores_for_round(&snapshot.scores, seed_u128, round)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consensus::{BPS_SCALE, FraudConfig, PoiConfig, StakeConfig, Thresholds, Weights};
+    use crate::transaction::generate_ed25519_keypair;
+    use crate::validator::testing::register;
+
+    /// Registry with `ids` registered under fresh keys
+    fn registry_of(ids: &[&str]) -> ValidatorRegistry {
+        let mut registry = ValidatorRegistry::new();
+        for id in ids {
+            let (consensus, vrf) = (generate_ed25519_keypair(), schnorrkel::Keypair::generate());
+            register(&mut registry, id, &consensus.public, &vrf.public);
+        }
+        registry
+    }
+
+    fn build_scorer() -> PoiScorer {
+        PoiScorer::new(PoiConfig {
+            weights: Weights {
+                upload: 2_500,
+                download: 2_500,
+                latency: 2_000,
+                uptime: 2_000,
+                stability: 1_000,
+                diversity: 0,
+            },
+            thresholds: Thresholds {
+                upload_mbps: 100,
+                download_mbps: 1000,
+                latency_ms: 200,
+                uptime_bps: 10_000,
+                stability_bps: 10_000,
+            },
+            fraud: FraudConfig::default(),
+            ema_alpha_bps: BPS_SCALE,
+            decay_bps: 0,
+            min_score: 0,
+            unjail_epochs: 3,
+            locations: HashMap::new(),
+            stake: StakeConfig::default(),
+            max_validators: 0,
+        })
+    }
+
+    fn metrics(id: &str, upload_mbps: u64) -> NodeMetrics {
+        NodeMetrics {
+            node_id: id.to_string(),
+            upload_mbps,
+            download_mbps: 500,
+            latency_ms: 50,
+            uptime_bps: 9_900,
+            stability_bps: 9_900,
+        }
+    }
+
+    #[test]
+    fn test_rejects_zero_epoch_length() {
+        assert!(matches!(
+            EpochManager::new(build_scorer(), 0),
+            Err(ConsensusError::InvalidEpochLength)
+        ));
+    }
+
+    #[test]
+    fn test_snapshot_pinned_for_whole_epoch() {
+        let mut manager = EpochManager::new(build_scorer(), 10).unwrap();
+        let registry = registry_of(&["A", "B", "C"]);
+        let mut pool = HashMap::new();
+        pool.insert("A".to_string(), metrics("A", 100));
+        pool.insert("B".to_string(), metrics("B", 10));
+
+        assert!(manager.on_block(0, &pool, &registry, 0));
+        let pinned = manager.current_snapshot().unwrap().scores.clone();
+
+        // Mid-epoch metric change must not alter the snapshot
+        pool.insert("B".to_string(), metrics("B", 100));
+        assert!(!manager.on_block(5, &pool, &registry, 0));
+        assert_eq!(manager.current_snapshot().unwrap().scores, pinned);
+
+        let leader = manager.select_validator(5, 42).unwrap();
+        assert_eq!(leader, PoiScorer::select_from_scores(&pinned, 42).unwrap());
+
+        // Next boundary picks up the new metrics
+        assert!(manager.on_block(10, &pool, &registry, 0));
+        let snapshot = manager.current_snapshot().unwrap();
+        assert_eq!(snapshot.epoch, 1);
+        assert_eq!(snapshot.start_height, 10);
+        assert_eq!(snapshot.scores["A"], snapshot.scores["B"]);
+    }
+
+    #[test]
+    fn test_select_requires_matching_epoch() {
+        let mut manager = EpochManager::new(build_scorer(), 10).unwrap();
+        assert!(matches!(
+            manager.select_validator(0, 1),
+            Err(ConsensusError::NoEpochSnapshot)
+        ));
+
+        let registry = registry_of(&["A", "B", "C"]);
+        let mut pool = HashMap::new();
+        pool.insert("A".to_string(), metrics("A", 100));
+        manager.on_block(3, &pool, &registry, 0);
+        assert_eq!(manager.current_snapshot().unwrap().start_height, 0);
+        assert!(matches!(
+            manager.select_validator(12, 1),
+            Err(ConsensusError::EpochMismatch)
+        ));
+    }
+
+    #[test]
+    fn test_committee_rotation_and_weights() {
+        let mut manager = EpochManager::new(build_scorer(), 10)
+            .unwrap()
+            .with_committee_size(2)
+            .unwrap();
+        let registry = registry_of(&["A", "B", "C"]);
+        let mut pool = HashMap::new();
+        pool.insert("A".to_string(), metrics("A", 100));
+        pool.insert("B".to_string(), metrics("B", 50));
+        pool.insert("C".to_string(), metrics("C", 1));
+        manager.on_block(0, &pool, &registry, 9);
+
+        assert_eq!(
+            manager.current_snapshot().unwrap().committee,
+            vec!["A", "B"]
+        );
+        assert_eq!(manager.proposer_for_height(0).unwrap(), "A");
+        assert_eq!(manager.proposer_for_height(1).unwrap(), "B");
+        assert_eq!(manager.proposer_for_height(2).unwrap(), "A");
+
+        let weights = manager.committee_weights(3).unwrap();
+        assert_eq!(weights.len(), 2);
+        assert!(!weights.contains_key("C"));
+    }
+
+    #[test]
+    fn test_failover_round_moves_to_next_proposer() {
+        use crate::block::ConsensusData;
+
+        let mut manager = EpochManager::new(build_scorer(), 10)
+            .unwrap()
+            .with_committee_size(3)
+            .unwrap();
+        let registry = registry_of(&["A", "B", "C"]);
+        let mut pool = HashMap::new();
+        pool.insert("A".to_string(), metrics("A", 100));
+        pool.insert("B".to_string(), metrics("B", 50));
+        pool.insert("C".to_string(), metrics("C", 1));
+        manager.on_block(0, &pool, &registry, 0);
+
+        assert_eq!(manager.proposer_for_round(1, 0).unwrap(), "B");
+        assert_eq!(manager.proposer_for_round(1, 1).unwrap(), "C");
+        assert_eq!(manager.proposer_for_round(1, 2).unwrap(), "A");
+
+        // "B" went offline at height 1; "C" produced the block in round 1
+        let block = Block::with_consensus(
+            1,
+            "data".to_string(),
+            "prev".to_string(),
+            ConsensusData {
+                proposer: Some("C".to_string()),
+                round: 1,
+                ..Default::default()
+            },
+        );
+        assert!(manager.validate_block_proposer(&block).is_ok());
+
+        let mut wrong_round = block.clone();
+        wrong_round.consensus.round = 0;
+        assert_eq!(
+            manager.validate_block_proposer(&wrong_round),
+            Err(ConsensusError::UnexpectedProposer)
+        );
+    }
+
+    #[test]
+    fn test_leader_schedule_published_at_boundary() {
+        let mut manager = EpochManager::new(build_scorer(), 5)
+            .unwrap()
+            .with_committee_size(2)
+            .unwrap();
+        assert!(manager.leader_schedule().is_none());
+
+        let registry = registry_of(&["A", "B", "C"]);
+        let mut pool = HashMap::new();
+        pool.insert("A".to_string(), metrics("A", 100));
+        pool.insert("B".to_string(), metrics("B", 50));
+        manager.on_block(5, &pool, &registry, 0);
+
+        let schedule = manager.leader_schedule().unwrap().clone();
+        assert_eq!(schedule.slots, vec!["A", "B", "A", "B", "A"]);
+        for height in 5..10 {
+            assert_eq!(
+                schedule.leader_at(height),
+                Some(manager.proposer_for_height(height).unwrap().as_str())
+            );
+        }
+        assert_eq!(schedule.leader_at(4), None);
+        assert_eq!(schedule.leader_at(10), None);
+        assert_eq!(schedule.heights_for("B"), vec![6, 8]);
+    }
+
+    #[test]
+    fn test_jailed_validators_not_selected() {
+        let mut manager = EpochManager::new(build_scorer(), 10).unwrap();
+        let mut registry = registry_of(&["A", "B"]);
+        let mut pool = HashMap::new();
+        pool.insert("A".to_string(), metrics("A", 100));
+        pool.insert("B".to_string(), metrics("B", 50));
+        // "A" fell below the minimum score last epoch and has one good epoch of the three it needs
+        let scores = |a: u64| HashMap::from([("A".to_string(), a), ("B".to_string(), 9_000)]);
+        registry.update_eligibility(&scores(1_000), 2_000, 3);
+        registry.update_eligibility(&scores(9_000), 2_000, 3);
+
+        manager.on_block(0, &pool, &registry, 0);
+        let snapshot = manager.current_snapshot().unwrap();
+        assert_eq!(snapshot.committee, vec!["B"]);
+        assert!(!snapshot.scores.contains_key("A"));
+
+        // unregistered nodes aren't selectable either
+        pool.insert("X".to_string(), metrics("X", 100));
+        registry.update_eligibility(&scores(9_000), 2_000, 3);
+        registry.update_eligibility(&scores(9_000), 2_000, 3);
+        manager.on_block(10, &pool, &registry, 0);
+        assert_eq!(
+            manager.current_snapshot().unwrap().committee,
+            vec!["A", "B"]
+        );
+    }
+}
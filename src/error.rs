@@ -0,0 +1,124 @@
+// src/error.rs
+
+//! Crate-wide error type
+//! - Each module keeps its own error enum (`StateError`, `MempoolError`, ...), all
+//!   implementing `std::error::Error` with a readable message
+//! - `Error` wraps every one of them, so code that spans modules can use `?` with a
+//!   single error type and still match on the cause
+
+use crate::attestation::MetricReportError;
+use crate::beacon::BeaconError;
+use crate::bls::BlsError;
+use crate::blockchain::ImportError;
+use crate::builder::BuildError;
+use crate::canonical::DecodeError;
+use crate::challenge::ChallengeError;
+use crate::consensus::messages::MessageError;
+use crate::consensus::{ConfigError,ConsensusError};
+use crate::evidence::EvidenceError;
+use crate::fees::FeePolicyError;
+use crate::finality::FinalityError;
+use crate::genesis::GenesisError;
+use crate::heartbeat::HeartbeatError;
+use crate::memo::MemoError;
+use crate::mempool::MempoolError;
+use crate::multisig::MultisigError;
+use crate::names::NameError;
+use crate::network::NetworkError;
+use crate::state::{SnapshotError,StateError};
+use crate::store::StoreError;
+use crate::transaction::{LimitError,TxError};
+use crate::validator::RegistryError;
+use crate::vesting::VestingError;
+use crate::vrf::VrfError;
+use thiserror::Error;
+
+/// Any error returned by this crate
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum Error{
+    #[error(transparent)]
+    Attestation(#[from] MetricReportError),
+    #[error(transparent)]
+    Beacon(#[from] BeaconError),
+    #[error(transparent)]
+    Bls(#[from] BlsError),
+    #[error(transparent)]
+    Build(#[from] BuildError),
+    #[error(transparent)]
+    Challenge(#[from] ChallengeError),
+    #[error(transparent)]
+    Config(#[from] ConfigError),
+    #[error(transparent)]
+    Consensus(#[from] ConsensusError),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    #[error(transparent)]
+    Evidence(#[from] EvidenceError),
+    #[error(transparent)]
+    FeePolicy(#[from] FeePolicyError),
+    #[error(transparent)]
+    Finality(#[from] FinalityError),
+    #[error(transparent)]
+    Genesis(#[from] GenesisError),
+    #[error(transparent)]
+    Heartbeat(#[from] HeartbeatError),
+    #[error(transparent)]
+    Import(#[from] ImportError),
+    #[error(transparent)]
+    Limit(#[from] LimitError),
+    #[error(transparent)]
+    Memo(#[from] MemoError),
+    #[error(transparent)]
+    Mempool(#[from] MempoolError),
+    #[error(transparent)]
+    Message(#[from] MessageError),
+    #[error(transparent)]
+    Multisig(#[from] MultisigError),
+    #[error(transparent)]
+    Name(#[from] NameError),
+    #[error(transparent)]
+    Network(#[from] NetworkError),
+    #[error(transparent)]
+    Registry(#[from] RegistryError),
+    #[error(transparent)]
+    Snapshot(#[from] SnapshotError),
+    #[error(transparent)]
+    State(#[from] StateError),
+    #[error(transparent)]
+    Store(#[from] StoreError),
+    #[error(transparent)]
+    Transaction(#[from] TxError),
+    #[error(transparent)]
+    Vesting(#[from] VestingError),
+    #[error(transparent)]
+    Vrf(#[from] VrfError),
+}
+
+pub type Result<T>=std::result::Result<T,Error>;
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::state::State;
+    use crate::transaction::{SignedTransaction,Transaction,generate_ed25519_keypair};
+    use std::error::Error as _;
+
+    fn apply(state:&mut State,tx:&SignedTransaction)->Result<()>{
+        tx.verify()?;
+        state.apply_transaction(tx)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_causes_survive_conversion(){
+        let kp=generate_ed25519_keypair();
+        let signed=SignedTransaction::sign_with_keypair(&Transaction::new("not-my-address".to_string(),"bob".to_string(),1,1,0,None),&kp);
+        let err=apply(&mut State::new(),&signed).unwrap_err();
+        assert!(matches!(err,Error::Transaction(TxError::SenderMismatch{..})));
+        assert!(err.to_string().starts_with("sender mismatch"));
+
+        let wrapped=StateError::from(TxError::BadSignature);
+        assert_eq!(wrapped.to_string(),"invalid transaction: signature verification failed");
+        assert_eq!(wrapped.source().map(|e| e.to_string()),Some("signature verification failed".to_string()));
+    }
+}
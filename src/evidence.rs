@@ -0,0 +1,291 @@
+// src/evidence.rs
+
+//! Double-sign evidence for NetChain
+//! - `SignedHeader`: a proposer's signature over (height, block hash)
+//! - `DoubleSignEvidence`: two conflicting headers at the same height from the same proposer
+//! - Verification against the proposer's registered consensus key
+//!
+//! Any node that observes two such headers can submit them in an `Evidence`
+//! transaction; the state transition slashes and jails the offender.
+//! `EvidencePool` watches every signed header a node sees, detects conflicts and
+//! queues the resulting evidence (oldest offence first) for the next block.
+
+use crate::validator::ValidatorRegistry;
+use base64::{engine::general_purpose,Engine as _};
+use bincode::Options;
+use ed25519_dalek::{Keypair,PublicKey,Signature,Signer,Verifier};
+use serde::{Deserialize,Serialize};
+use std::collections::{BTreeMap,HashSet};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+/// Share of the offender's balance burned per offence (basis points)
+pub const SLASH_FRACTION_BPS:u64=500;
+/// Number of epochs an offender is jailed from the validator pool
+pub const JAIL_EPOCHS:u64=4;
+
+/// Reasons evidence can be rejected
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum EvidenceError{
+    #[error("headers are for different heights")]
+    HeightMismatch,
+    #[error("headers have different proposers")]
+    ProposerMismatch,
+    #[error("headers are identical")]
+    IdenticalBlocks,
+    #[error("unknown proposer")]
+    UnknownProposer,
+    #[error("invalid header signature")]
+    InvalidSignature,
+    #[error("evidence already processed")]
+    AlreadyProcessed,
+}
+
+/// A block header commitment signed by its proposer's consensus key
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct SignedHeader{
+    pub height:u64,
+    /// Hex hash of the proposed block
+    pub block_hash:String,
+    /// Account address of the proposer
+    pub proposer:String,
+    /// Signature encoded as base64
+    pub signature:String,
+}
+
+impl SignedHeader{
+    /// Deterministic bytes that the proposer signs
+    fn signing_bytes(height:u64,block_hash:&str,proposer:&str)->Vec<u8>{
+        bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+        .serialize(&(height,block_hash,proposer))
+        .expect("bincode serialization should succed for header")
+    }
+
+    /// Sign a header with the proposer's consensus keypair
+    pub fn sign(height:u64,block_hash:String,proposer:String,keypair:&Keypair)->Self{
+        let sig:Signature=keypair.sign(&Self::signing_bytes(height,&block_hash,&proposer));
+        SignedHeader{
+            height,
+            block_hash,
+            proposer,
+            signature:general_purpose::STANDARD.encode(sig.to_bytes()),
+        }
+    }
+
+    /// Verify the signature against a base64 encoded consensus public key
+    pub fn verify(&self,consensus_pubkey:&str)->Result<(),EvidenceError>{
+        let pk_bytes=general_purpose::STANDARD
+        .decode(consensus_pubkey)
+        .map_err(|_| EvidenceError::InvalidSignature)?;
+        let sig_bytes=general_purpose::STANDARD
+        .decode(&self.signature)
+        .map_err(|_| EvidenceError::InvalidSignature)?;
+        let public_key=PublicKey::from_bytes(&pk_bytes).map_err(|_| EvidenceError::InvalidSignature)?;
+        let signature=Signature::from_bytes(&sig_bytes).map_err(|_| EvidenceError::InvalidSignature)?;
+        public_key
+        .verify(&Self::signing_bytes(self.height,&self.block_hash,&self.proposer),&signature)
+        .map_err(|_| EvidenceError::InvalidSignature)
+    }
+}
+
+/// Proof that a proposer signed two different blocks at the same height
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct DoubleSignEvidence{
+    #[schema(value_type=Object)]
+    pub first:SignedHeader,
+    #[schema(value_type=Object)]
+    pub second:SignedHeader,
+}
+
+impl DoubleSignEvidence{
+    /// Offending proposer address
+    pub fn offender(&self)->&str{
+        &self.first.proposer
+    }
+
+    /// Height at which the offence happened
+    pub fn height(&self)->u64{
+        self.first.height
+    }
+
+    /// Check the evidence is well-formed and both signatures come from the registered consensus key
+    pub fn verify(&self,registry:&ValidatorRegistry)->Result<(),EvidenceError>{
+        if self.first.height!=self.second.height{
+            return Err(EvidenceError::HeightMismatch)
+        }
+        if self.first.proposer!=self.second.proposer{
+            return Err(EvidenceError::ProposerMismatch)
+        }
+        if self.first.block_hash==self.second.block_hash{
+            return Err(EvidenceError::IdenticalBlocks)
+        }
+        let validator=registry
+        .get(self.offender())
+        .ok_or(EvidenceError::UnknownProposer)?;
+        self.first.verify(&validator.consensus_pubkey)?;
+        self.second.verify(&validator.consensus_pubkey)?;
+        Ok(())
+    }
+}
+
+/// Signed headers seen so far plus evidence waiting to be included on chain
+#[derive(Debug,Clone,Default)]
+pub struct EvidencePool{
+    /// (proposer, height) -> first verified header seen
+    seen:BTreeMap<(String,u64),SignedHeader>,
+    /// (height, offender) -> evidence not yet on chain; iteration puts older offences first
+    pending:BTreeMap<(u64,String),DoubleSignEvidence>,
+    /// (offender, height) offences already included in a block
+    committed:HashSet<(String,u64)>,
+}
+
+impl EvidencePool{
+    pub fn new()->Self{
+        Self::default()
+    }
+
+    /// Record a header observed on the network. Returns evidence if it conflicts with a
+    /// header already seen from the same proposer at the same height.
+    pub fn record(&mut self,header:SignedHeader,registry:&ValidatorRegistry)->Result<Option<DoubleSignEvidence>,EvidenceError>{
+        let validator=registry
+        .get(&header.proposer)
+        .ok_or(EvidenceError::UnknownProposer)?;
+        header.verify(&validator.consensus_pubkey)?;
+
+        let key=(header.proposer.clone(),header.height);
+        let Some(first)=self.seen.get(&key) else{
+            self.seen.insert(key,header);
+            return Ok(None)
+        };
+        if first.block_hash==header.block_hash{
+            return Ok(None)
+        }
+        let evidence=DoubleSignEvidence{first:first.clone(),second:header};
+        self.queue(evidence.clone());
+        Ok(Some(evidence))
+    }
+
+    /// Add evidence received from a peer after verifying it
+    pub fn add_evidence(&mut self,evidence:DoubleSignEvidence,registry:&ValidatorRegistry)->Result<(),EvidenceError>{
+        evidence.verify(registry)?;
+        if self.committed.contains(&(evidence.offender().to_string(),evidence.height())){
+            return Err(EvidenceError::AlreadyProcessed)
+        }
+        self.queue(evidence);
+        Ok(())
+    }
+
+    fn queue(&mut self,evidence:DoubleSignEvidence){
+        let offence=(evidence.offender().to_string(),evidence.height());
+        if !self.committed.contains(&offence){
+            self.pending.entry((offence.1,offence.0)).or_insert(evidence);
+        }
+    }
+
+    /// Number of offences waiting for inclusion
+    pub fn pending_len(&self)->usize{
+        self.pending.len()
+    }
+
+    /// Up to `max` pending pieces of evidence, oldest offence first. Proposers include these
+    /// ahead of ordinary transactions; they stay pending until `mark_committed`.
+    pub fn next_for_block(&self,max:usize)->Vec<DoubleSignEvidence>{
+        self.pending.values().take(max).cloned().collect()
+    }
+
+    /// Forget evidence once it has been included in a block
+    pub fn mark_committed(&mut self,evidence:&DoubleSignEvidence){
+        self.pending.remove(&(evidence.height(),evidence.offender().to_string()));
+        self.committed.insert((evidence.offender().to_string(),evidence.height()));
+    }
+
+    /// Drop observed headers below `height` (they can no longer be part of new evidence)
+    pub fn prune_below(&mut self,height:u64){
+        self.seen.retain(|(_,h),_| *h>=height);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::generate_ed25519_keypair;
+    use crate::validator::testing::registry_with;
+
+    #[test]
+    fn test_valid_double_sign_evidence(){
+        let kp=generate_ed25519_keypair();
+        let registry=registry_with("val",&kp);
+        let evidence=DoubleSignEvidence{
+            first:SignedHeader::sign(7,"aa".to_string(),"val".to_string(),&kp),
+            second:SignedHeader::sign(7,"bb".to_string(),"val".to_string(),&kp),
+        };
+        assert!(evidence.verify(&registry).is_ok());
+        assert_eq!(evidence.offender(),"val");
+    }
+
+    #[test]
+    fn test_rejects_malformed_evidence(){
+        let kp=generate_ed25519_keypair();
+        let registry=registry_with("val",&kp);
+
+        let same=DoubleSignEvidence{
+            first:SignedHeader::sign(7,"aa".to_string(),"val".to_string(),&kp),
+            second:SignedHeader::sign(7,"aa".to_string(),"val".to_string(),&kp),
+        };
+        assert_eq!(same.verify(&registry),Err(EvidenceError::IdenticalBlocks));
+
+        let heights=DoubleSignEvidence{
+            first:SignedHeader::sign(7,"aa".to_string(),"val".to_string(),&kp),
+            second:SignedHeader::sign(8,"bb".to_string(),"val".to_string(),&kp),
+        };
+        assert_eq!(heights.verify(&registry),Err(EvidenceError::HeightMismatch));
+
+        // signed by someone other than the registered consensus key
+        let other=generate_ed25519_keypair();
+        let forged=DoubleSignEvidence{
+            first:SignedHeader::sign(7,"aa".to_string(),"val".to_string(),&kp),
+            second:SignedHeader::sign(7,"bb".to_string(),"val".to_string(),&other),
+        };
+        assert_eq!(forged.verify(&registry),Err(EvidenceError::InvalidSignature));
+    }
+
+    #[test]
+    fn test_pool_detects_and_queues_double_signs(){
+        let kp=generate_ed25519_keypair();
+        let mut registry=registry_with("val",&kp);
+        let other=generate_ed25519_keypair();
+        registry
+        .register(
+            "other".to_string(),
+            general_purpose::STANDARD.encode(other.public.to_bytes()),
+            general_purpose::STANDARD.encode(schnorrkel::Keypair::generate().public.to_bytes()),
+            "127.0.0.1:30334".to_string(),
+        )
+        .unwrap();
+        let mut pool=EvidencePool::new();
+
+        let header=|h:u64,hash:&str,who:&str,key:&Keypair| SignedHeader::sign(h,hash.to_string(),who.to_string(),key);
+        assert_eq!(pool.record(header(9,"aa","val",&kp),&registry),Ok(None));
+        assert_eq!(pool.record(header(9,"aa","val",&kp),&registry),Ok(None));
+        let late=pool.record(header(9,"bb","val",&kp),&registry).unwrap().unwrap();
+        assert!(late.verify(&registry).is_ok());
+
+        // older offences are included first
+        pool.record(header(4,"cc","other",&other),&registry).unwrap();
+        let early=pool.record(header(4,"dd","other",&other),&registry).unwrap().unwrap();
+        assert_eq!(pool.next_for_block(10),vec![early.clone(),late.clone()]);
+        assert_eq!(pool.next_for_block(1),vec![early.clone()]);
+
+        // forged headers are rejected outright
+        assert_eq!(pool.record(header(9,"ee","val",&other),&registry),Err(EvidenceError::InvalidSignature));
+
+        pool.mark_committed(&early);
+        assert_eq!(pool.pending_len(),1);
+        assert_eq!(pool.add_evidence(early,&registry),Err(EvidenceError::AlreadyProcessed));
+
+        pool.prune_below(10);
+        assert_eq!(pool.record(header(9,"ff","val",&kp),&registry),Ok(None));
+    }
+}
@@ -0,0 +1,97 @@
+// src/fees.rs
+
+//! Fee routing
+//! - Fees are deducted from senders as transactions apply; at the end of the block the
+//!   total is split between the block proposer, the epoch reward pool and burn
+//! - Shares are in basis points and must add up to `BPS_SCALE`
+//! - Integer rounding dust is burned, so every node routes exactly the same amounts
+
+use crate::consensus::BPS_SCALE;
+use serde::{Deserialize,Serialize};
+use thiserror::Error;
+
+/// Reasons a fee policy is rejected
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum FeePolicyError{
+    /// Shares don't add up to `BPS_SCALE`
+    #[error("fee shares must add up to {} basis points",BPS_SCALE)]
+    InvalidSplit,
+}
+
+/// How collected fees are divided
+#[derive(Debug,Clone,Copy,Serialize,Deserialize,PartialEq,Eq)]
+pub struct FeePolicy{
+    /// Credited to the block proposer
+    pub proposer_bps:u64,
+    /// Added to the reward pool of the block's epoch, paid out at settlement
+    pub reward_pool_bps:u64,
+    /// Destroyed
+    pub burn_bps:u64,
+}
+
+impl Default for FeePolicy{
+    fn default()->Self{
+        Self{proposer_bps:4_000,reward_pool_bps:4_000,burn_bps:2_000}
+    }
+}
+
+impl FeePolicy{
+    pub fn new(proposer_bps:u64,reward_pool_bps:u64,burn_bps:u64)->Result<Self,FeePolicyError>{
+        let policy=Self{proposer_bps,reward_pool_bps,burn_bps};
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn validate(&self)->Result<(),FeePolicyError>{
+        let total=self
+        .proposer_bps
+        .checked_add(self.reward_pool_bps)
+        .and_then(|sum| sum.checked_add(self.burn_bps));
+        if total!=Some(BPS_SCALE){
+            return Err(FeePolicyError::InvalidSplit)
+        }
+        Ok(())
+    }
+
+    /// Divide `total` fees; the rounding remainder is burned
+    pub fn split(&self,total:u64)->FeeSplit{
+        let share=|bps:u64| (total as u128*bps as u128/BPS_SCALE as u128) as u64;
+        let proposer=share(self.proposer_bps);
+        let reward_pool=share(self.reward_pool_bps);
+        FeeSplit{
+            total,
+            proposer,
+            reward_pool,
+            burned:total-proposer-reward_pool,
+        }
+    }
+}
+
+/// Where one block's fees went
+#[derive(Debug,Clone,Copy,Default,Serialize,Deserialize,PartialEq,Eq)]
+pub struct FeeSplit{
+    /// Sum of the fees of every transaction in the block
+    pub total:u64,
+    pub proposer:u64,
+    pub reward_pool:u64,
+    pub burned:u64,
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_split_sums_to_total(){
+        let policy=FeePolicy::new(5_000,3_333,1_667).unwrap();
+        let split=policy.split(1_001);
+        assert_eq!(split.proposer,500);
+        assert_eq!(split.reward_pool,333);
+        assert_eq!(split.burned,168);
+        assert_eq!(split.proposer+split.reward_pool+split.burned,split.total);
+
+        assert_eq!(FeePolicy::default().split(0),FeeSplit::default());
+        assert_eq!(FeePolicy::new(5_000,5_000,1),Err(FeePolicyError::InvalidSplit));
+        assert_eq!(FeePolicy::new(u64::MAX,1,0),Err(FeePolicyError::InvalidSplit));
+    }
+}
@@ -0,0 +1,296 @@
+// src/finality.rs
+
+//! BFT finality votes for NetChain
+//! - Committee members sign `Prevote` / `Precommit` votes for a proposed block
+//! - `VoteSet` collects votes for one height, weighted by the committee's PoI scores
+//! - Once precommits for a block exceed 2/3 of total weight it is final and a
+//!   `Commit` (the vote aggregate) is produced for inclusion in the next header
+
+use crate::validator::ValidatorRegistry;
+use base64::{engine::general_purpose,Engine as _};
+use bincode::Options;
+use ed25519_dalek::{Keypair,PublicKey,Signature,Signer,Verifier};
+use serde::{Deserialize,Serialize};
+use std::collections::{BTreeMap,HashMap};
+use thiserror::Error;
+
+/// Reasons a vote or commit can be rejected
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum FinalityError{
+    #[error("vote is for another height")]
+    WrongHeight,
+    #[error("validator is not in the committee")]
+    NotInCommittee,
+    #[error("unknown validator")]
+    UnknownValidator,
+    #[error("invalid vote signature")]
+    InvalidSignature,
+    #[error("validator already voted for another block")]
+    ConflictingVote,
+    #[error("votes don't reach the finality threshold")]
+    InsufficientWeight,
+    #[error("commit references an unknown block")]
+    UnknownBlock,
+}
+
+/// Voting step
+#[derive(Debug,Clone,Copy,Serialize,Deserialize,PartialEq,Eq,Hash)]
+pub enum VoteType{
+    Prevote,
+    Precommit,
+}
+
+/// A committee member's signed vote for a block at a height
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct Vote{
+    pub vote_type:VoteType,
+    pub height:u64,
+    /// Hex hash of the block voted for
+    pub block_hash:String,
+    /// Account address of the voting validator
+    pub validator:String,
+    /// Signature encoded as base64
+    pub signature:String,
+}
+
+impl Vote{
+    /// Deterministic bytes that the validator signs
+    fn signing_bytes(vote_type:VoteType,height:u64,block_hash:&str,validator:&str)->Vec<u8>{
+        bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+        .serialize(&(vote_type,height,block_hash,validator))
+        .expect("bincode serialization should succed for vote")
+    }
+
+    /// Sign a vote with the validator's consensus keypair
+    pub fn sign(vote_type:VoteType,height:u64,block_hash:String,validator:String,keypair:&Keypair)->Self{
+        let sig:Signature=keypair.sign(&Self::signing_bytes(vote_type,height,&block_hash,&validator));
+        Vote{
+            vote_type,
+            height,
+            block_hash,
+            validator,
+            signature:general_purpose::STANDARD.encode(sig.to_bytes()),
+        }
+    }
+
+    /// Verify the signature against the validator's registered consensus key
+    pub fn verify(&self,registry:&ValidatorRegistry)->Result<(),FinalityError>{
+        let info=registry
+        .get(&self.validator)
+        .ok_or(FinalityError::UnknownValidator)?;
+        let pk_bytes=general_purpose::STANDARD
+        .decode(&info.consensus_pubkey)
+        .map_err(|_| FinalityError::InvalidSignature)?;
+        let sig_bytes=general_purpose::STANDARD
+        .decode(&self.signature)
+        .map_err(|_| FinalityError::InvalidSignature)?;
+        let public_key=PublicKey::from_bytes(&pk_bytes).map_err(|_| FinalityError::InvalidSignature)?;
+        let signature=Signature::from_bytes(&sig_bytes).map_err(|_| FinalityError::InvalidSignature)?;
+        public_key
+        .verify(
+            &Self::signing_bytes(self.vote_type,self.height,&self.block_hash,&self.validator),
+            &signature,
+        )
+        .map_err(|_| FinalityError::InvalidSignature)
+    }
+}
+
+/// True if `voted` is strictly more than 2/3 of `total`
+pub fn has_supermajority(voted:u64,total:u64)->bool{
+    total>0 && (voted as u128)*3>(total as u128)*2
+}
+
+/// Aggregate of precommits proving a block is final; stored in the next block header
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct Commit{
+    pub height:u64,
+    pub block_hash:String,
+    /// Precommits sorted by validator address
+    pub precommits:Vec<Vote>,
+}
+
+impl Commit{
+    /// Check every precommit and that together they carry >2/3 of committee weight
+    pub fn verify(&self,registry:&ValidatorRegistry,committee:&HashMap<String,u64>)->Result<(),FinalityError>{
+        let total:u64=committee.values().sum();
+        let mut seen=BTreeMap::new();
+        for vote in &self.precommits{
+            if vote.vote_type!=VoteType::Precommit || vote.height!=self.height || vote.block_hash!=self.block_hash{
+                return Err(FinalityError::ConflictingVote)
+            }
+            let weight=*committee.get(&vote.validator).ok_or(FinalityError::NotInCommittee)?;
+            vote.verify(registry)?;
+            seen.insert(vote.validator.as_str(),weight);
+        }
+        if !has_supermajority(seen.values().sum(),total){
+            return Err(FinalityError::InsufficientWeight)
+        }
+        Ok(())
+    }
+}
+
+/// Collects prevotes and precommits for a single height
+#[derive(Debug,Clone)]
+pub struct VoteSet{
+    height:u64,
+    /// validator address -> voting weight (e.g. epoch PoI score)
+    committee:HashMap<String,u64>,
+    total_weight:u64,
+    votes:HashMap<VoteType,BTreeMap<String,Vote>>,
+}
+
+impl VoteSet{
+    pub fn new(height:u64,committee:HashMap<String,u64>)->Self{
+        let total_weight=committee.values().sum();
+        Self{
+            height,
+            committee,
+            total_weight,
+            votes:HashMap::new(),
+        }
+    }
+
+    pub fn height(&self)->u64{
+        self.height
+    }
+
+    /// Verify and record a vote. Re-sending an identical vote is a no-op.
+    pub fn add_vote(&mut self,vote:Vote,registry:&ValidatorRegistry)->Result<(),FinalityError>{
+        if vote.height!=self.height{
+            return Err(FinalityError::WrongHeight)
+        }
+        if !self.committee.contains_key(&vote.validator){
+            return Err(FinalityError::NotInCommittee)
+        }
+        vote.verify(registry)?;
+
+        let step=self.votes.entry(vote.vote_type).or_default();
+        if let Some(existing)=step.get(&vote.validator){
+            if existing.block_hash!=vote.block_hash{
+                return Err(FinalityError::ConflictingVote)
+            }
+            return Ok(())
+        }
+        step.insert(vote.validator.clone(),vote);
+        Ok(())
+    }
+
+    /// Total committee weight behind `block_hash` for a voting step
+    pub fn weight_for(&self,vote_type:VoteType,block_hash:&str)->u64{
+        self.votes
+        .get(&vote_type)
+        .map(|step| {
+            step.values()
+            .filter(|v| v.block_hash==block_hash)
+            .map(|v| self.committee[&v.validator])
+            .sum()
+        })
+        .unwrap_or(0)
+    }
+
+    /// True once a step has >2/3 of committee weight for `block_hash`
+    pub fn has_quorum(&self,vote_type:VoteType,block_hash:&str)->bool{
+        has_supermajority(self.weight_for(vote_type,block_hash),self.total_weight)
+    }
+
+    /// Build the commit for `block_hash` if its precommits reached quorum
+    pub fn commit(&self,block_hash:&str)->Option<Commit>{
+        if !self.has_quorum(VoteType::Precommit,block_hash){
+            return None
+        }
+        let precommits=self.votes[&VoteType::Precommit]
+        .values()
+        .filter(|v| v.block_hash==block_hash)
+        .cloned()
+        .collect();
+        Some(Commit{
+            height:self.height,
+            block_hash:block_hash.to_string(),
+            precommits,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::block::ConsensusData;
+    use crate::blockchain::Blockchain;
+    use crate::validator::testing::validators;
+
+    fn setup(n:usize)->(ValidatorRegistry,Vec<(String,Keypair)>,HashMap<String,u64>){
+        let (registry,keys)=validators(n);
+        let committee=keys.iter().map(|(addr,_)| (addr.clone(),100)).collect();
+        (registry,keys,committee)
+    }
+
+    #[test]
+    fn test_supermajority_threshold(){
+        assert!(!has_supermajority(2,3));
+        assert!(has_supermajority(3,4));
+        assert!(!has_supermajority(0,0));
+    }
+
+    #[test]
+    fn test_block_final_after_two_thirds_precommits(){
+        let (registry,keys,committee)=setup(4);
+        let mut chain=Blockchain::new();
+        chain.add_block("tx batch".to_string());
+        let block_hash=chain.last_block().hash.clone();
+
+        let mut votes=VoteSet::new(1,committee.clone());
+        for (addr,kp) in keys.iter().take(3){
+            votes
+            .add_vote(Vote::sign(VoteType::Prevote,1,block_hash.clone(),addr.clone(),kp),&registry)
+            .unwrap();
+        }
+        assert!(votes.has_quorum(VoteType::Prevote,&block_hash));
+
+        for (addr,kp) in keys.iter().take(3){
+            assert!(votes.commit(&block_hash).is_none());
+            votes
+            .add_vote(Vote::sign(VoteType::Precommit,1,block_hash.clone(),addr.clone(),kp),&registry)
+            .unwrap();
+        }
+        let commit=votes.commit(&block_hash).expect("3 of 4 precommits is a quorum");
+        assert!(commit.verify(&registry,&committee).is_ok());
+
+        chain.mark_final(&commit).unwrap();
+        assert!(chain.is_final(1));
+        chain.add_block_with_consensus(
+            "next".to_string(),
+            ConsensusData{
+                last_commit:Some(commit),
+                ..Default::default()
+            },
+        );
+        assert!(chain.is_valid());
+    }
+
+    #[test]
+    fn test_rejects_conflicting_and_foreign_votes(){
+        let (registry,keys,committee)=setup(2);
+        let mut votes=VoteSet::new(5,committee);
+        let (addr,kp)=&keys[0];
+
+        votes
+        .add_vote(Vote::sign(VoteType::Prevote,5,"aa".to_string(),addr.clone(),kp),&registry)
+        .unwrap();
+        assert_eq!(
+            votes.add_vote(Vote::sign(VoteType::Prevote,5,"bb".to_string(),addr.clone(),kp),&registry),
+            Err(FinalityError::ConflictingVote)
+        );
+        assert_eq!(
+            votes.add_vote(Vote::sign(VoteType::Prevote,6,"aa".to_string(),addr.clone(),kp),&registry),
+            Err(FinalityError::WrongHeight)
+        );
+        // signed with a key other than the registered one
+        let (other_addr,_)=&keys[1];
+        assert_eq!(
+            votes.add_vote(Vote::sign(VoteType::Prevote,5,"aa".to_string(),other_addr.clone(),kp),&registry),
+            Err(FinalityError::InvalidSignature)
+        );
+    }
+}
@@ -0,0 +1,147 @@
+// src/gas.rs
+
+//! Transaction weight (gas) model
+//! - gas = base cost + per-byte cost of the encoded transaction + per-operation cost
+//! - A transaction's gas price is `fee / gas`; the mempool refuses anything below
+//!   `min_gas_price` and block building caps the total gas per block
+
+use crate::transaction::{SignedTransaction,TxPayload};
+use serde::{Deserialize,Serialize};
+
+/// Extra gas charged per payload type on top of base + size costs.
+/// New payload types get their own field here.
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+#[serde(default)]
+pub struct OperationCosts{
+    pub transfer:u64,
+    pub register_validator:u64,
+    pub unregister_validator:u64,
+    /// Free by default so slashing reports are never priced out
+    pub evidence:u64,
+    pub metric_report:u64,
+    pub stake:u64,
+    pub unstake:u64,
+    pub governance_vote:u64,
+    /// Proof-of-possession check is a pairing, the most expensive operation we verify
+    pub register_bls_key:u64,
+    pub cancel:u64,
+    /// Charged per output of a `MultiTransfer`
+    pub multi_transfer_output:u64,
+    /// Adds a registry entry that lives forever
+    pub issue_asset:u64,
+    pub transfer_asset:u64,
+    pub register_name:u64,
+}
+
+impl Default for OperationCosts{
+    fn default()->Self{
+        OperationCosts{
+            transfer:0,
+            register_validator:10_000,
+            unregister_validator:1_000,
+            evidence:0,
+            metric_report:2_000,
+            stake:2_000,
+            unstake:2_000,
+            governance_vote:1_000,
+            register_bls_key:20_000,
+            cancel:0,
+            multi_transfer_output:100,
+            issue_asset:50_000,
+            transfer_asset:100,
+            register_name:5_000,
+        }
+    }
+}
+
+/// Gas costs and the minimum accepted gas price
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+#[serde(default)]
+pub struct GasSchedule{
+    /// Charged once per transaction
+    pub base:u64, // e.g., 1_000
+    /// Charged per encoded byte (see `SignedTransaction::encoded_size`)
+    pub per_byte:u64, // e.g., 10
+    pub operations:OperationCosts,
+    /// Lowest fee per unit of gas accepted (0 disables the floor)
+    pub min_gas_price:u64,
+}
+
+impl Default for GasSchedule{
+    fn default()->Self{
+        GasSchedule{
+            base:1_000,
+            per_byte:10,
+            operations:OperationCosts::default(),
+            min_gas_price:0,
+        }
+    }
+}
+
+impl GasSchedule{
+    /// Per-operation cost of `payload`
+    pub fn operation_gas(&self,payload:&TxPayload)->u64{
+        let ops=&self.operations;
+        match payload{
+            TxPayload::Transfer{..}=>ops.transfer,
+            TxPayload::RegisterValidator{..}=>ops.register_validator,
+            TxPayload::UnregisterValidator=>ops.unregister_validator,
+            TxPayload::Evidence(_)=>ops.evidence,
+            TxPayload::MetricReport(_)=>ops.metric_report,
+            TxPayload::Stake{..}=>ops.stake,
+            TxPayload::Unstake{..}=>ops.unstake,
+            TxPayload::GovernanceVote{..}=>ops.governance_vote,
+            TxPayload::RegisterBlsKey{..}=>ops.register_bls_key,
+            TxPayload::Cancel=>ops.cancel,
+            TxPayload::MultiTransfer{outputs}=>ops.multi_transfer_output.saturating_mul(outputs.len() as u64),
+            TxPayload::IssueAsset{..}=>ops.issue_asset,
+            TxPayload::TransferAsset{..}=>ops.transfer_asset,
+            TxPayload::RegisterName{..}=>ops.register_name,
+        }
+    }
+
+    /// Total gas of a transaction
+    pub fn gas_used(&self,tx:&SignedTransaction)->u64{
+        self.base
+        .saturating_add(self.per_byte.saturating_mul(tx.encoded_size() as u64))
+        .saturating_add(self.operation_gas(&tx.tx.payload))
+    }
+
+    /// Smallest fee that meets `min_gas_price` for this transaction
+    pub fn min_fee(&self,tx:&SignedTransaction)->u64{
+        self.gas_used(tx).saturating_mul(self.min_gas_price)
+    }
+
+    /// True if the transaction pays at least `min_gas_price` per unit of gas
+    pub fn meets_min_price(&self,tx:&SignedTransaction)->bool{
+        tx.tx.fee>=self.min_fee(tx)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::{Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+
+    #[test]
+    fn test_gas_grows_with_memo_and_operation(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let schedule=GasSchedule{min_gas_price:2,..GasSchedule::default()};
+
+        let short=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),1,0,0,Some("x".to_string())),&kp);
+        let long=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),1,0,0,Some("x".repeat(10_000))),&kp);
+        assert_eq!(schedule.gas_used(&long)-schedule.gas_used(&short),9_999*schedule.per_byte);
+
+        let stake=SignedTransaction::sign_with_keypair(
+            &Transaction::with_payload(addr,TxPayload::Stake{amount:1},0,0,None),
+            &kp,
+        );
+        assert!(schedule.gas_used(&stake)>schedule.base+schedule.operations.stake);
+
+        let mut paid=short.tx.clone();
+        paid.fee=schedule.min_fee(&short);
+        assert!(schedule.meets_min_price(&SignedTransaction::sign_with_keypair(&paid,&kp)));
+        assert!(!schedule.meets_min_price(&short));
+    }
+}
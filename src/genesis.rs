@@ -0,0 +1,185 @@
+// src/genesis.rs
+
+//! Genesis file format
+//! - Initial accounts with their balances and optional vesting schedules
+//! - Loaded from TOML or JSON (by file extension) and turned into `State` with
+//!   `State::from_genesis`
+//! - `State::export_genesis` writes a running chain's accounts (nonces, stake and
+//!   unbonding included) back into this format, for restarts and hard forks
+//! - Issued assets are listed with their issuer and supply; account asset balances
+//!   must add up to each asset's supply
+
+use crate::asset::{AssetInfo,is_valid_asset_id};
+use crate::state::Unbonding;
+use crate::vesting::{VestingError,VestingSchedule};
+use serde::{Deserialize,Serialize};
+use std::collections::{BTreeMap,BTreeSet};
+use std::path::Path;
+use thiserror::Error;
+
+/// Reasons a genesis file is rejected
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum GenesisError{
+    #[error("cannot read genesis: {0}")]
+    Io(String),
+    #[error("invalid genesis: {0}")]
+    Parse(String),
+    /// Extension is neither `.toml` nor `.json`
+    #[error("unsupported genesis format: {0}")]
+    UnsupportedFormat(String),
+    /// The genesis can't be written in the requested format (TOML integers are signed 64-bit)
+    #[error("cannot encode genesis: {0}")]
+    Encode(String),
+    #[error("duplicate genesis account {0}")]
+    DuplicateAccount(String),
+    #[error("invalid vesting for {0}: {1}")]
+    InvalidVesting(String,#[source] VestingError),
+    /// A vesting schedule locks more than the account's balance at the genesis height
+    #[error("vesting for {0} exceeds its balance")]
+    VestingExceedsBalance(String),
+    /// Bad symbol, not in the asset list, or balances not adding up to its supply
+    #[error("invalid asset {0}")]
+    InvalidAsset(String),
+}
+
+/// One initial account
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct GenesisAccount{
+    pub address:String,
+    pub balance:u64,
+    /// Lock on part of `balance` (team / investor allocations)
+    #[serde(default)]
+    pub vesting:Option<VestingSchedule>,
+    /// Next nonce; non-zero when carried over from an exported chain so old
+    /// transactions can't be replayed
+    #[serde(default)]
+    pub nonce:u64,
+    /// Bonded stake
+    #[serde(default)]
+    pub staked:u64,
+    /// Stake still unbonding, released by epoch as on a running chain
+    #[serde(default)]
+    pub unbonding:Vec<Unbonding>,
+    /// asset -> balance of issued assets
+    #[serde(default)]
+    pub assets:BTreeMap<String,u64>,
+}
+
+impl GenesisAccount{
+    /// Fresh account holding just `balance`
+    pub fn new(address:String,balance:u64)->Self{
+        GenesisAccount{address,balance,vesting:None,nonce:0,staked:0,unbonding:Vec::new(),assets:BTreeMap::new()}
+    }
+}
+
+#[derive(Debug,Clone,Default,Serialize,Deserialize,PartialEq,Eq)]
+pub struct Genesis{
+    /// Block height the accounts are as of (0 for a new chain)
+    #[serde(default)]
+    pub height:u64,
+    /// Consensus epoch the chain starts in
+    #[serde(default)]
+    pub epoch:u64,
+    /// Issued assets by symbol
+    #[serde(default)]
+    pub assets:BTreeMap<String,AssetInfo>,
+    pub accounts:Vec<GenesisAccount>,
+}
+
+/// Whether `path` names a TOML (true) or JSON (false) file
+fn is_toml(path:&Path)->Result<bool,GenesisError>{
+    match path.extension().and_then(|ext| ext.to_str()){
+        Some("toml")=>Ok(true),
+        Some("json")=>Ok(false),
+        _=>Err(GenesisError::UnsupportedFormat(path.display().to_string())),
+    }
+}
+
+impl Genesis{
+    pub fn from_file(path:impl AsRef<Path>)->Result<Self,GenesisError>{
+        let path=path.as_ref();
+        let is_toml=is_toml(path)?;
+        let text=std::fs::read_to_string(path).map_err(|e| GenesisError::Io(format!("{}: {}",path.display(),e)))?;
+        let genesis:Genesis=if is_toml{
+            toml::from_str(&text).map_err(|e| GenesisError::Parse(e.to_string()))?
+        }else{
+            serde_json::from_str(&text).map_err(|e| GenesisError::Parse(e.to_string()))?
+        };
+        genesis.validate()?;
+        Ok(genesis)
+    }
+
+    /// Write as TOML or JSON, by file extension
+    pub fn to_file(&self,path:impl AsRef<Path>)->Result<(),GenesisError>{
+        let path=path.as_ref();
+        let text=if is_toml(path)?{
+            toml::to_string(self).map_err(|e| GenesisError::Encode(e.to_string()))?
+        }else{
+            serde_json::to_string_pretty(self).map_err(|e| GenesisError::Encode(e.to_string()))?
+        };
+        std::fs::write(path,text).map_err(|e| GenesisError::Io(format!("{}: {}",path.display(),e)))
+    }
+
+    pub fn validate(&self)->Result<(),GenesisError>{
+        let mut seen=BTreeSet::new();
+        let mut held:BTreeMap<&str,u128>=BTreeMap::new();
+        for account in &self.accounts{
+            if !seen.insert(&account.address){
+                return Err(GenesisError::DuplicateAccount(account.address.clone()))
+            }
+            for (asset,amount) in &account.assets{
+                *held.entry(asset).or_insert(0)+=*amount as u128;
+            }
+            if let Some(vesting)=&account.vesting{
+                vesting
+                .validate()
+                .map_err(|e| GenesisError::InvalidVesting(account.address.clone(),e))?;
+                if vesting.locked_at(self.height)>account.balance{
+                    return Err(GenesisError::VestingExceedsBalance(account.address.clone()))
+                }
+            }
+        }
+        for (asset,info) in &self.assets{
+            if !is_valid_asset_id(asset) || held.remove(asset.as_str()).unwrap_or(0)!=info.supply as u128{
+                return Err(GenesisError::InvalidAsset(asset.clone()))
+            }
+        }
+        // held, but never issued
+        if let Some((asset,_))=held.into_iter().next(){
+            return Err(GenesisError::InvalidAsset(asset.to_string()))
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_genesis_from_file(){
+        let dir=std::env::temp_dir().join(format!("netchain-genesis-{}",std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let path=dir.join("genesis.toml");
+        std::fs::write(
+            &path,
+            "[[accounts]]\naddress = \"alice\"\nbalance = 1000\n\n\
+             [[accounts]]\naddress = \"team\"\nbalance = 500\n\
+             vesting = { total = 500, start_height = 0, cliff = 10, duration = 100 }\n",
+        )
+        .unwrap();
+        let genesis=Genesis::from_file(&path).unwrap();
+        assert_eq!(genesis.accounts.len(),2);
+        assert_eq!(genesis.accounts[1].vesting.as_ref().map(|v| v.cliff),Some(10));
+
+        let json_path=dir.join("genesis.json");
+        let mut json=serde_json::to_value(&genesis).unwrap();
+        json["accounts"][1]["balance"]=serde_json::json!(499);
+        std::fs::write(&json_path,json.to_string()).unwrap();
+        assert_eq!(Genesis::from_file(&json_path),Err(GenesisError::VestingExceedsBalance("team".to_string())));
+        assert!(matches!(Genesis::from_file(dir.join("genesis.yaml")),Err(GenesisError::UnsupportedFormat(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
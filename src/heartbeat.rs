@@ -0,0 +1,181 @@
+// src/heartbeat.rs
+
+//! Uptime tracking via signed heartbeats
+//! - Validators broadcast a signed `Heartbeat` once per slot (a fixed slice of the epoch)
+//! - `UptimeTracker` verifies heartbeats and counts distinct slots seen per validator
+//! - Uptime for an epoch = slots seen / slots per epoch, exported as `NodeMetrics.uptime_bps`
+
+use crate::consensus::{BPS_SCALE,NodeMetrics};
+use crate::validator::ValidatorRegistry;
+use base64::{engine::general_purpose,Engine as _};
+use bincode::Options;
+use ed25519_dalek::{Keypair,PublicKey,Signature,Signer,Verifier};
+use serde::{Deserialize,Serialize};
+use std::collections::{BTreeSet,HashMap};
+use thiserror::Error;
+
+/// Reasons a heartbeat can be rejected
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum HeartbeatError{
+    #[error("unknown validator")]
+    UnknownValidator,
+    #[error("invalid heartbeat signature")]
+    InvalidSignature,
+    #[error("slot outside the epoch")]
+    SlotOutOfRange,
+    #[error("an epoch needs at least one slot")]
+    InvalidSlotCount,
+}
+
+/// Signed liveness message for one slot of an epoch
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct Heartbeat{
+    /// Account address of the validator
+    pub validator:String,
+    pub epoch:u64,
+    /// Slot index within the epoch (0..slots_per_epoch)
+    pub slot:u64,
+    /// Signature by the validator's consensus key, base64
+    pub signature:String,
+}
+
+impl Heartbeat{
+    fn signing_bytes(validator:&str,epoch:u64,slot:u64)->Vec<u8>{
+        bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .with_little_endian()
+        .serialize(&(validator,epoch,slot))
+        .expect("bincode serialization should succed for heartbeat")
+    }
+
+    /// Sign a heartbeat with the validator's consensus keypair
+    pub fn sign(validator:String,epoch:u64,slot:u64,keypair:&Keypair)->Self{
+        let sig:Signature=keypair.sign(&Self::signing_bytes(&validator,epoch,slot));
+        Heartbeat{
+            validator,
+            epoch,
+            slot,
+            signature:general_purpose::STANDARD.encode(sig.to_bytes()),
+        }
+    }
+
+    /// Verify the signature against the validator's registered consensus key
+    pub fn verify(&self,registry:&ValidatorRegistry)->Result<(),HeartbeatError>{
+        let info=registry
+        .get(&self.validator)
+        .ok_or(HeartbeatError::UnknownValidator)?;
+        let pk_bytes=general_purpose::STANDARD
+        .decode(&info.consensus_pubkey)
+        .map_err(|_| HeartbeatError::InvalidSignature)?;
+        let sig_bytes=general_purpose::STANDARD
+        .decode(&self.signature)
+        .map_err(|_| HeartbeatError::InvalidSignature)?;
+        let public_key=PublicKey::from_bytes(&pk_bytes).map_err(|_| HeartbeatError::InvalidSignature)?;
+        let signature=Signature::from_bytes(&sig_bytes).map_err(|_| HeartbeatError::InvalidSignature)?;
+        public_key
+        .verify(&Self::signing_bytes(&self.validator,self.epoch,self.slot),&signature)
+        .map_err(|_| HeartbeatError::InvalidSignature)
+    }
+}
+
+/// Counts verified heartbeat slots per (validator, epoch)
+#[derive(Debug,Clone)]
+pub struct UptimeTracker{
+    slots_per_epoch:u64,
+    seen:HashMap<(String,u64),BTreeSet<u64>>,
+}
+
+impl UptimeTracker{
+    pub fn new(slots_per_epoch:u64)->Result<Self,HeartbeatError>{
+        if slots_per_epoch==0{
+            return Err(HeartbeatError::InvalidSlotCount)
+        }
+        Ok(UptimeTracker{
+            slots_per_epoch,
+            seen:HashMap::new(),
+        })
+    }
+
+    pub fn slots_per_epoch(&self)->u64{
+        self.slots_per_epoch
+    }
+
+    /// Verify and record a heartbeat. Duplicates for the same slot are counted once.
+    pub fn record(&mut self,heartbeat:&Heartbeat,registry:&ValidatorRegistry)->Result<(),HeartbeatError>{
+        if heartbeat.slot>=self.slots_per_epoch{
+            return Err(HeartbeatError::SlotOutOfRange)
+        }
+        heartbeat.verify(registry)?;
+        self.seen
+        .entry((heartbeat.validator.clone(),heartbeat.epoch))
+        .or_default()
+        .insert(heartbeat.slot);
+        Ok(())
+    }
+
+    /// Uptime of `validator` in `epoch`, in basis points
+    pub fn uptime_bps(&self,validator:&str,epoch:u64)->u64{
+        let slots=self
+        .seen
+        .get(&(validator.to_string(),epoch))
+        .map_or(0,|s| s.len() as u64);
+        slots*BPS_SCALE/self.slots_per_epoch
+    }
+
+    /// Overwrite `metrics.uptime_bps` with the observed uptime for `epoch`
+    pub fn apply_to(&self,metrics:&mut NodeMetrics,epoch:u64){
+        metrics.uptime_bps=self.uptime_bps(&metrics.node_id,epoch);
+    }
+
+    /// Drop observations for epochs before `epoch`
+    pub fn prune_before(&mut self,epoch:u64){
+        self.seen.retain(|(_,e),_| *e>=epoch);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::generate_ed25519_keypair;
+    use crate::validator::testing::registry_with;
+
+    #[test]
+    fn test_uptime_from_heartbeats(){
+        let kp=generate_ed25519_keypair();
+        let registry=registry_with("val",&kp);
+        let mut tracker=UptimeTracker::new(4).unwrap();
+
+        for slot in [0,1,1,3]{
+            tracker.record(&Heartbeat::sign("val".to_string(),2,slot,&kp),&registry).unwrap();
+        }
+        assert_eq!(tracker.uptime_bps("val",2),7_500);
+        assert_eq!(tracker.uptime_bps("val",1),0);
+
+        let mut metrics=NodeMetrics{
+            node_id:"val".to_string(),
+            upload_mbps:10,
+            download_mbps:10,
+            latency_ms:10,
+            uptime_bps:10_000,
+            stability_bps:10_000,
+        };
+        tracker.apply_to(&mut metrics,2);
+        assert_eq!(metrics.uptime_bps,7_500);
+
+        tracker.prune_before(3);
+        assert_eq!(tracker.uptime_bps("val",2),0);
+    }
+
+    #[test]
+    fn test_rejects_forged_and_out_of_range(){
+        let kp=generate_ed25519_keypair();
+        let registry=registry_with("val",&kp);
+        let mut tracker=UptimeTracker::new(4).unwrap();
+
+        let forged=Heartbeat::sign("val".to_string(),0,0,&generate_ed25519_keypair());
+        assert_eq!(tracker.record(&forged,&registry),Err(HeartbeatError::InvalidSignature));
+        let late=Heartbeat::sign("val".to_string(),0,4,&kp);
+        assert_eq!(tracker.record(&late,&registry),Err(HeartbeatError::SlotOutOfRange));
+        assert!(matches!(UptimeTracker::new(0),Err(HeartbeatError::InvalidSlotCount)));
+    }
+}
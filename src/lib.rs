@@ -0,0 +1,39 @@
+pub mod aggregation;
+pub mod asset;
+pub mod attestation;
+pub mod beacon;
+pub mod block;
+pub mod blockchain;
+pub mod bls;
+pub mod builder;
+pub mod canonical;
+pub mod challenge;
+pub mod consensus;
+pub mod diff;
+pub mod epoch;
+pub mod error;
+pub mod evidence;
+pub mod fees;
+pub mod finality;
+pub mod gas;
+pub mod genesis;
+pub mod heartbeat;
+pub mod memo;
+pub mod mempool;
+pub mod multisig;
+pub mod names;
+pub mod netprobe;
+pub mod network;
+pub mod receipt;
+pub mod rewards;
+pub mod rpc;
+pub mod shared;
+pub mod signer;
+pub mod smt;
+pub mod stability;
+pub mod state;
+pub mod store;
+pub mod transaction;
+pub mod validator;
+pub mod vesting;
+pub mod vrf;
@@ -0,0 +1,149 @@
+// src/memo.rs
+
+//! Encrypted transaction memos
+//! - Sealed to the receiver's X25519 key: either derived from their Ed25519 account key or a
+//!   separately published encryption key
+//! - Ephemeral-static X25519 + ChaCha20-Poly1305; a fresh ephemeral key per memo means every
+//!   memo gets its own symmetric key
+//! - The sealed memo is a `Transaction` field, so the ciphertext is covered by canonical bytes
+//!   (and the sender's signature)
+
+use base64::{engine::general_purpose,Engine as _};
+use chacha20poly1305::aead::{Aead,KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305,Key,Nonce};
+use curve25519_dalek::edwards::CompressedEdwardsY;
+use curve25519_dalek::montgomery::MontgomeryPoint;
+use ed25519_dalek::{Keypair,PublicKey};
+use rand::RngCore;
+use serde::{Deserialize,Serialize};
+use sha2::{Digest,Sha256,Sha512};
+use thiserror::Error;
+
+/// Domain separation for the memo key derivation
+const MEMO_KDF_DOMAIN:&[u8]=b"netchain-memo-v1";
+
+/// Reasons sealing or opening a memo fails
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum MemoError{
+    /// The Ed25519 key isn't a valid curve point
+    #[error("invalid memo key")]
+    InvalidKey,
+    #[error("malformed encrypted memo")]
+    Malformed,
+    /// Wrong key, or the ciphertext was tampered with
+    #[error("memo decryption failed")]
+    DecryptionFailed,
+}
+
+/// X25519 public key for an Ed25519 account key (birational map to Montgomery form)
+pub fn x25519_public_from_ed25519(pubkey:&PublicKey)->Result<[u8;32],MemoError>{
+    CompressedEdwardsY(pubkey.to_bytes())
+    .decompress()
+    .map(|point| point.to_montgomery().to_bytes())
+    .ok_or(MemoError::InvalidKey)
+}
+
+/// X25519 secret matching `x25519_public_from_ed25519` for this keypair
+/// (the Ed25519 expanded secret scalar; clamped when used)
+pub fn x25519_secret_from_ed25519(keypair:&Keypair)->[u8;32]{
+    let hash=Sha512::digest(keypair.secret.as_bytes());
+    let mut secret=[0u8;32];
+    secret.copy_from_slice(&hash[..32]);
+    secret
+}
+
+/// X25519 public key for a published encryption secret
+pub fn x25519_public(secret:&[u8;32])->[u8;32]{
+    MontgomeryPoint::mul_base_clamped(*secret).to_bytes()
+}
+
+fn memo_cipher(shared:&MontgomeryPoint,ephemeral:&[u8;32],recipient:&[u8;32])->ChaCha20Poly1305{
+    let mut hasher=Sha256::new();
+    hasher.update(MEMO_KDF_DOMAIN);
+    hasher.update(shared.as_bytes());
+    hasher.update(ephemeral);
+    hasher.update(recipient);
+    ChaCha20Poly1305::new(Key::from_slice(&hasher.finalize()))
+}
+
+/// A memo only the receiver can read
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct EncryptedMemo{
+    /// Sender's one-time X25519 public key, base64
+    pub ephemeral_pubkey:String,
+    /// ChaCha20-Poly1305 ciphertext with tag, base64
+    pub ciphertext:String,
+}
+
+impl EncryptedMemo{
+    /// Seal `plaintext` to an X25519 public key
+    pub fn encrypt(plaintext:&str,recipient:&[u8;32])->Self{
+        let mut ephemeral_secret=[0u8;32];
+        rand::thread_rng().fill_bytes(&mut ephemeral_secret);
+        let ephemeral=x25519_public(&ephemeral_secret);
+        let shared=MontgomeryPoint(*recipient).mul_clamped(ephemeral_secret);
+        // the key is unique per memo, so a fixed nonce is safe
+        let ciphertext=memo_cipher(&shared,&ephemeral,recipient)
+        .encrypt(Nonce::from_slice(&[0u8;12]),plaintext.as_bytes())
+        .expect("ChaCha20-Poly1305 encryption of an in-memory buffer can't fail");
+        EncryptedMemo{
+            ephemeral_pubkey:general_purpose::STANDARD.encode(ephemeral),
+            ciphertext:general_purpose::STANDARD.encode(ciphertext),
+        }
+    }
+
+    /// Seal `plaintext` to the receiver's Ed25519 account key
+    pub fn encrypt_for_ed25519(plaintext:&str,recipient:&PublicKey)->Result<Self,MemoError>{
+        Ok(Self::encrypt(plaintext,&x25519_public_from_ed25519(recipient)?))
+    }
+
+    /// Open with the receiver's X25519 secret
+    pub fn decrypt(&self,secret:&[u8;32])->Result<String,MemoError>{
+        let ephemeral:[u8;32]=general_purpose::STANDARD
+        .decode(&self.ephemeral_pubkey)
+        .ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(MemoError::Malformed)?;
+        let ciphertext=general_purpose::STANDARD
+        .decode(&self.ciphertext)
+        .map_err(|_| MemoError::Malformed)?;
+        let shared=MontgomeryPoint(ephemeral).mul_clamped(*secret);
+        let plaintext=memo_cipher(&shared,&ephemeral,&x25519_public(secret))
+        .decrypt(Nonce::from_slice(&[0u8;12]),ciphertext.as_slice())
+        .map_err(|_| MemoError::DecryptionFailed)?;
+        String::from_utf8(plaintext).map_err(|_| MemoError::Malformed)
+    }
+
+    /// Open with the receiver's Ed25519 account keypair
+    pub fn decrypt_with_keypair(&self,keypair:&Keypair)->Result<String,MemoError>{
+        self.decrypt(&x25519_secret_from_ed25519(keypair))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::generate_ed25519_keypair;
+
+    #[test]
+    fn test_memo_roundtrip_with_account_key(){
+        let receiver=generate_ed25519_keypair();
+        let memo=EncryptedMemo::encrypt_for_ed25519("invoice #42",&receiver.public).unwrap();
+        assert_eq!(memo.decrypt_with_keypair(&receiver).unwrap(),"invoice #42");
+        assert_eq!(
+            memo.decrypt_with_keypair(&generate_ed25519_keypair()),
+            Err(MemoError::DecryptionFailed)
+        );
+
+        let mut tampered=memo.clone();
+        tampered.ciphertext=general_purpose::STANDARD.encode(b"garbage that is long enough");
+        assert_eq!(tampered.decrypt_with_keypair(&receiver),Err(MemoError::DecryptionFailed));
+    }
+
+    #[test]
+    fn test_memo_to_published_encryption_key(){
+        let secret=[7u8;32];
+        let memo=EncryptedMemo::encrypt("hi",&x25519_public(&secret));
+        assert_eq!(memo.decrypt(&secret).unwrap(),"hi");
+    }
+}
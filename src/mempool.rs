@@ -0,0 +1,848 @@
+// src/mempool.rs
+
+//! Transaction mempool
+//! - Accepts signature-checked `SignedTransaction`s that aren't already stale
+//! - Refuses transactions paying less than the `GasSchedule` minimum gas price
+//! - Orders them by gas price, keeping each sender's transactions in nonce order
+//! - Replace-by-fee: a same-nonce transaction (e.g. `Transaction::cancel`) replaces the
+//!   pending one if it bumps the fee by `MIN_REPLACEMENT_BUMP_BPS` without lowering gas price
+//! - Time-locked transactions wait in a separate queue until `prune` sees their lock expire.
+//!   The queue has its own byte budget (`LockLimits`), evicting the lowest gas price when
+//!   full, holds one transaction per sender and nonce, and refuses locks ending further
+//!   ahead than `LockLimits` allows, so nothing sits in it indefinitely
+//! - Reserves each pooled transaction's spend against its accounts, so a sender's pending
+//!   transactions together never spend more than its confirmed spendable balance
+//! - Caps the pool size; when full, the lowest gas-price tail transaction is evicted
+//! - `take_for_block` hands the proposer the best executable set within block limits
+//!   (count, bytes and gas), with double-sign evidence always first
+//! - `next_nonce` tells wallets which nonce to use next, counting what's already pooled
+//! - `pooled` lists what's waiting, with each transaction's gas and gas price, for inspection
+//!   and fee estimation; `clear` empties the pool at an operator's request
+
+use crate::consensus::BPS_SCALE;
+use crate::gas::GasSchedule;
+use crate::state::State;
+use crate::transaction::{LimitError,SignedTransaction,TimeLock,TxError,TxLimits,TxPayload};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap,BTreeSet,HashSet};
+use thiserror::Error;
+
+/// Minimum fee increase for a same-nonce replacement, in basis points of the old fee
+/// (at least 1 unit). Stops peers being flooded with near-identical replacements.
+pub const MIN_REPLACEMENT_BUMP_BPS:u64=1_000;
+
+/// Furthest ahead a height lock may end when admitted, by default (about a week of blocks)
+pub const DEFAULT_MAX_LOCK_BLOCKS:u64=100_000;
+/// Furthest ahead a timestamp lock may end when admitted, by default (a week)
+pub const DEFAULT_MAX_LOCK_SECONDS:u64=7*24*60*60;
+
+/// Reasons a transaction is refused by the pool
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum MempoolError{
+    #[error("invalid transaction: {0}")]
+    InvalidSignature(#[from] TxError),
+    /// The sender's account has already used this nonce
+    #[error("nonce already used")]
+    NonceTooLow,
+    /// Same transaction already pooled
+    #[error("transaction already pooled")]
+    Duplicate,
+    /// Same-nonce replacement without the required fee bump, or at a lower gas price
+    #[error("replacement doesn't pay enough more")]
+    ReplacementUnderpriced,
+    /// Fee below `min_gas_price` times the transaction's gas
+    #[error("gas price below the minimum")]
+    Underpriced,
+    /// Larger than the whole pool
+    #[error("transaction larger than the whole pool")]
+    TooLarge,
+    /// Memo or encoded size over the pool's `TxLimits`
+    #[error(transparent)]
+    ExceedsLimit(#[from] LimitError),
+    /// Together with the account's already-pooled transactions, spends more than its
+    /// confirmed spendable balance
+    #[error("pending transactions would overspend the account")]
+    InsufficientBalance,
+    /// Pool is full of transactions paying at least as much per unit of gas
+    #[error("pool is full")]
+    PoolFull,
+    /// Time lock ends further ahead than the pool holds transactions for
+    #[error("time lock ends too far ahead")]
+    LockTooFar,
+}
+
+/// Bounds on the time-locked queue
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct LockLimits{
+    /// Bytes the queue may hold, on top of the ready pool's
+    pub max_bytes:usize,
+    /// Blocks past the current height a height lock may end
+    pub max_blocks_ahead:u64,
+    /// Seconds past the current block time a timestamp lock may end
+    pub max_seconds_ahead:u64,
+}
+
+impl LockLimits{
+    /// Default limits for a pool of `max_bytes`: a quarter of that again for locked ones
+    pub fn for_pool(max_bytes:usize)->Self{
+        LockLimits{max_bytes:max_bytes/4,max_blocks_ahead:DEFAULT_MAX_LOCK_BLOCKS,max_seconds_ahead:DEFAULT_MAX_LOCK_SECONDS}
+    }
+
+    fn horizon(&self,lock:&TimeLock)->u64{
+        match lock{
+            TimeLock::Height(_)=>self.max_blocks_ahead,
+            TimeLock::Timestamp(_)=>self.max_seconds_ahead,
+        }
+    }
+}
+
+/// Limits on what a proposer may put in one block
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct BlockLimits{
+    pub max_txs:usize,
+    pub max_bytes:usize,
+    pub max_gas:u64,
+}
+
+impl Default for BlockLimits{
+    fn default()->Self{
+        BlockLimits{max_txs:1_000,max_bytes:1024*1024,max_gas:10_000_000}
+    }
+}
+
+/// A pooled transaction with what it pays, as `Mempool::pooled` lists it
+#[derive(Debug,Clone,Copy)]
+pub struct PoolEntry<'a>{
+    pub tx:&'a SignedTransaction,
+    pub size:usize,
+    pub gas:u64,
+    /// Fee per unit of gas, rounded down
+    pub gas_price:u64,
+}
+
+#[derive(Debug,Clone)]
+struct PooledTx{
+    tx:SignedTransaction,
+    hash:String,
+    size:usize,
+    gas:u64,
+    /// account -> liquid balance the transaction spends (see `Transaction::debits`)
+    debits:BTreeMap<String,u64>,
+}
+
+impl PooledTx{
+    fn new(tx:SignedTransaction,schedule:&GasSchedule)->Self{
+        let size=tx_size(&tx);
+        let gas=schedule.gas_used(&tx);
+        let debits=tx.tx.debits().into_iter().map(|(account,debit)| (account.to_string(),debit)).collect();
+        PooledTx{hash:tx.tx_hash_hex(),tx,size,gas,debits}
+    }
+
+    fn is_evidence(&self)->bool{
+        matches!(self.tx.tx.payload,TxPayload::Evidence(_))
+    }
+
+    /// Compare gas price without floats: fee_a / gas_a vs fee_b / gas_b
+    fn cmp_rate(&self,other:&PooledTx)->Ordering{
+        (self.tx.tx.fee as u128*other.gas as u128).cmp(&(other.tx.tx.fee as u128*self.gas as u128))
+    }
+
+    /// Block inclusion priority: evidence first, then gas price
+    fn cmp_priority(&self,other:&PooledTx)->Ordering{
+        self.is_evidence().cmp(&other.is_evidence()).then_with(|| self.cmp_rate(other))
+    }
+
+    /// Refuse to let `self` replace `existing` (same sender and nonce) unless it bumps the
+    /// fee by `MIN_REPLACEMENT_BUMP_BPS` without lowering the gas price
+    fn check_replaces(&self,existing:&PooledTx)->Result<(),MempoolError>{
+        let old_fee=existing.tx.tx.fee;
+        let bump=((old_fee as u128*MIN_REPLACEMENT_BUMP_BPS as u128/BPS_SCALE as u128) as u64).max(1);
+        if self.tx.tx.fee<old_fee.saturating_add(bump) || self.cmp_rate(existing)==Ordering::Less{
+            return Err(MempoolError::ReplacementUnderpriced)
+        }
+        Ok(())
+    }
+}
+
+/// Encoded size used for per-byte gas and size limits (see `SignedTransaction::encoded_size`)
+pub fn tx_size(tx:&SignedTransaction)->usize{
+    tx.encoded_size()
+}
+
+/// Pending transactions keyed by sender, then nonce
+#[derive(Debug,Clone)]
+pub struct Mempool{
+    max_bytes:usize,
+    /// Ready and time-locked together
+    total_bytes:usize,
+    gas:GasSchedule,
+    limits:TxLimits,
+    lock_limits:LockLimits,
+    by_sender:BTreeMap<String,BTreeMap<u64,PooledTx>>,
+    /// Time-locked transactions keyed by sender and nonce, not yet eligible for blocks
+    locked:BTreeMap<(String,u64),PooledTx>,
+    locked_bytes:usize,
+    hashes:HashSet<String>,
+    /// account -> total spend of its pooled transactions (ready and time-locked)
+    reserved:BTreeMap<String,u64>,
+}
+
+impl Mempool{
+    /// Pool holding at most `max_bytes` of transactions, priced with the default `GasSchedule`
+    pub fn new(max_bytes:usize)->Self{
+        Mempool::with_gas_schedule(max_bytes,GasSchedule::default())
+    }
+
+    /// Pool holding at most `max_bytes` of transactions, priced with `gas`
+    pub fn with_gas_schedule(max_bytes:usize,gas:GasSchedule)->Self{
+        Mempool{
+            max_bytes,
+            total_bytes:0,
+            gas,
+            limits:TxLimits::default(),
+            lock_limits:LockLimits::for_pool(max_bytes),
+            by_sender:BTreeMap::new(),
+            locked:BTreeMap::new(),
+            locked_bytes:0,
+            hashes:HashSet::new(),
+            reserved:BTreeMap::new(),
+        }
+    }
+
+    /// Use `limits` instead of the default `TxLimits` for admission
+    pub fn with_tx_limits(mut self,limits:TxLimits)->Self{
+        self.limits=limits;
+        self
+    }
+
+    /// Bound the time-locked queue by `limits` instead of `LockLimits::for_pool`
+    pub fn with_lock_limits(mut self,limits:LockLimits)->Self{
+        self.lock_limits=limits;
+        self
+    }
+
+    pub fn len(&self)->usize{
+        self.hashes.len()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.hashes.is_empty()
+    }
+
+    /// Pooled transactions still waiting for their time lock
+    pub fn locked_len(&self)->usize{
+        self.locked.len()
+    }
+
+    pub fn total_bytes(&self)->usize{
+        self.total_bytes
+    }
+
+    pub fn contains(&self,hash:&str)->bool{
+        self.hashes.contains(hash)
+    }
+
+    /// Every pooled transaction: ready ones by sender and nonce, then time-locked ones
+    pub fn pooled(&self)->impl Iterator<Item=PoolEntry<'_>>{
+        self.by_sender
+        .values()
+        .flat_map(|txs| txs.values())
+        .chain(self.locked.values())
+        .map(|pooled| PoolEntry{tx:&pooled.tx,size:pooled.size,gas:pooled.gas,gas_price:pooled.tx.tx.fee/pooled.gas.max(1)})
+    }
+
+    /// Drop every pooled transaction; returns how many there were
+    pub fn clear(&mut self)->usize{
+        let cleared=self.len();
+        self.by_sender.clear();
+        self.locked.clear();
+        self.locked_bytes=0;
+        self.hashes.clear();
+        self.reserved.clear();
+        self.total_bytes=0;
+        cleared
+    }
+
+    /// Gas costs and minimum gas price the pool admits transactions under
+    pub fn gas_schedule(&self)->&GasSchedule{
+        &self.gas
+    }
+
+    /// Pooled transaction with hash `hash`, ready or time-locked
+    pub fn get(&self,hash:&str)->Option<&SignedTransaction>{
+        if !self.hashes.contains(hash){
+            return None
+        }
+        self.by_sender
+        .values()
+        .flat_map(|txs| txs.values())
+        .chain(self.locked.values())
+        .find(|pooled| pooled.hash==hash)
+        .map(|pooled| &pooled.tx)
+    }
+
+    /// Balance of `address` already spoken for by pooled transactions
+    pub fn reserved(&self,address:&str)->u64{
+        self.reserved.get(address).copied().unwrap_or(0)
+    }
+
+    /// Refuse `pooled` if, on top of what's already reserved (less what `replacing` frees),
+    /// it would spend more than one of its accounts can
+    fn check_reservation(&self,pooled:&PooledTx,replacing:Option<&PooledTx>,state:&State)->Result<(),MempoolError>{
+        for (account,debit) in &pooled.debits{
+            let freed=replacing.and_then(|old| old.debits.get(account)).copied().unwrap_or(0);
+            let pending=(self.reserved(account)-freed) as u128+*debit as u128;
+            if pending>state.get_spendable_balance(account) as u128{
+                return Err(MempoolError::InsufficientBalance)
+            }
+        }
+        Ok(())
+    }
+
+    fn reserve(&mut self,pooled:&PooledTx){
+        for (account,debit) in &pooled.debits{
+            let reserved=self.reserved.entry(account.clone()).or_insert(0);
+            *reserved=reserved.saturating_add(*debit);
+        }
+    }
+
+    fn unreserve(&mut self,pooled:&PooledTx){
+        for (account,debit) in &pooled.debits{
+            if let Some(reserved)=self.reserved.get_mut(account){
+                *reserved=reserved.saturating_sub(*debit);
+                if *reserved==0{
+                    self.reserved.remove(account);
+                }
+            }
+        }
+    }
+
+    /// Next nonce `address` should sign with: its account nonce, advanced past every
+    /// consecutive nonce already waiting in the pool (time-locked ones included), so several
+    /// transactions submitted back to back don't collide on the confirmed nonce
+    pub fn next_nonce(&self,address:&str,state:&State)->u64{
+        let mut pending:BTreeSet<u64>=self
+        .locked
+        .keys()
+        .filter(|(sender,_)| sender==address)
+        .map(|(_,nonce)| *nonce)
+        .collect();
+        pending.extend(self.by_sender.get(address).into_iter().flat_map(|txs| txs.keys().copied()));
+        let mut next=state.get_nonce(address);
+        while pending.contains(&next){
+            next+=1;
+        }
+        next
+    }
+
+    /// Add a transaction. A pending transaction with the same sender and nonce is replaced
+    /// only if the new fee is at least `MIN_REPLACEMENT_BUMP_BPS` higher and its gas price is
+    /// no lower. Returns the transaction hash.
+    pub fn insert(&mut self,tx:SignedTransaction,state:&State)->Result<String,MempoolError>{
+        tx.check_limits(&self.limits).map_err(MempoolError::ExceedsLimit)?;
+        tx.verify()?;
+        if tx.tx.nonce<state.get_nonce(&tx.tx.sender){
+            return Err(MempoolError::NonceTooLow)
+        }
+        if !self.gas.meets_min_price(&tx){
+            return Err(MempoolError::Underpriced)
+        }
+        let pooled=PooledTx::new(tx,&self.gas);
+        if self.hashes.contains(&pooled.hash){
+            return Err(MempoolError::Duplicate)
+        }
+        if pooled.size>self.max_bytes{
+            return Err(MempoolError::TooLarge)
+        }
+
+        // held aside until the lock expires, in a queue of its own
+        if let Some(lock)=pooled.tx.tx.not_before.filter(|lock| !state.is_unlocked(lock)){
+            return self.insert_locked(pooled,lock,state)
+        }
+
+        let sender=pooled.tx.tx.sender.clone();
+        let nonce=pooled.tx.tx.nonce;
+        let replaced=self.by_sender.get(&sender).and_then(|txs| txs.get(&nonce));
+        if let Some(existing)=replaced{
+            pooled.check_replaces(existing)?;
+        }
+        self.check_reservation(&pooled,replaced,state)?;
+        // nothing changes until the transaction is known to fit
+        let bytes=self.total_bytes-self.locked_bytes-replaced.map_or(0,|old| old.size);
+        let victims=self.eviction_victims(&pooled,bytes)?;
+        for (victim_sender,victim_nonce) in victims.into_iter().chain(replaced.is_some().then(|| (sender.clone(),nonce))){
+            self.remove(&victim_sender,victim_nonce);
+        }
+
+        let hash=pooled.hash.clone();
+        self.reserve(&pooled);
+        self.total_bytes+=pooled.size;
+        self.hashes.insert(hash.clone());
+        self.by_sender.entry(sender).or_default().insert(nonce,pooled);
+        Ok(hash)
+    }
+
+    /// Queue a time-locked transaction, replacing a locked one with the same sender and
+    /// nonce or evicting the cheapest locked ones to make room
+    fn insert_locked(&mut self,pooled:PooledTx,lock:TimeLock,state:&State)->Result<String,MempoolError>{
+        if state.lock_remaining(&lock)>self.lock_limits.horizon(&lock){
+            return Err(MempoolError::LockTooFar)
+        }
+        if pooled.size>self.lock_limits.max_bytes{
+            return Err(MempoolError::PoolFull)
+        }
+        let key=(pooled.tx.tx.sender.clone(),pooled.tx.tx.nonce);
+        let replaced=self.locked.get(&key);
+        if let Some(existing)=replaced{
+            pooled.check_replaces(existing)?;
+        }
+        self.check_reservation(&pooled,replaced,state)?;
+
+        // pick victims before touching anything, so a refusal leaves the queue as it was
+        let mut bytes=self.locked_bytes-replaced.map_or(0,|old| old.size)+pooled.size;
+        let mut candidates:Vec<(&(String,u64),&PooledTx)>=self.locked.iter().filter(|(k,_)| **k!=key).collect();
+        candidates.sort_by(|a,b| a.1.cmp_priority(b.1));
+        let mut victims=Vec::new();
+        for (victim_key,victim) in candidates{
+            if bytes<=self.lock_limits.max_bytes{
+                break
+            }
+            if victim.cmp_priority(&pooled)!=Ordering::Less{
+                return Err(MempoolError::PoolFull)
+            }
+            bytes-=victim.size;
+            victims.push(victim_key.clone());
+        }
+        if bytes>self.lock_limits.max_bytes{
+            return Err(MempoolError::PoolFull)
+        }
+
+        for victim in victims.iter().chain(std::iter::once(&key)){
+            self.remove_locked(victim);
+        }
+        let hash=pooled.hash.clone();
+        self.reserve(&pooled);
+        self.total_bytes+=pooled.size;
+        self.locked_bytes+=pooled.size;
+        self.hashes.insert(hash.clone());
+        self.locked.insert(key,pooled);
+        Ok(hash)
+    }
+
+    fn remove_locked(&mut self,key:&(String,u64))->Option<PooledTx>{
+        let pooled=self.locked.remove(key)?;
+        self.total_bytes-=pooled.size;
+        self.locked_bytes-=pooled.size;
+        self.hashes.remove(&pooled.hash);
+        self.unreserve(&pooled);
+        Some(pooled)
+    }
+
+    /// Ready transactions to evict so `incoming` fits next to `bytes` of them, lowest priority
+    /// first. Each is the highest nonce its sender has left, so no nonce gap is left behind
+    /// still-pooled transactions; of `incoming`'s sender only nonces above it qualify (its own
+    /// slot, if replaced, is already left out of `bytes`). `PoolFull` if evicting everything
+    /// ranking below `incoming` isn't enough. Nothing is removed.
+    fn eviction_victims(&self,incoming:&PooledTx,mut bytes:usize)->Result<Vec<(String,u64)>,MempoolError>{
+        let mut remaining:BTreeMap<&str,Vec<(u64,&PooledTx)>>=self
+        .by_sender
+        .iter()
+        .map(|(sender,txs)| {
+            let floor=if *sender==incoming.tx.tx.sender{incoming.tx.tx.nonce+1} else{0};
+            (sender.as_str(),txs.range(floor..).map(|(nonce,tx)| (*nonce,tx)).collect())
+        })
+        .collect();
+        let mut victims=Vec::new();
+        while bytes+incoming.size>self.max_bytes{
+            let (sender,(nonce,victim))=remaining
+            .iter()
+            .filter_map(|(sender,txs)| txs.last().map(|top| (*sender,*top)))
+            .min_by(|a,b| a.1.1.cmp_priority(b.1.1))
+            .ok_or(MempoolError::PoolFull)?;
+            if victim.cmp_priority(incoming)!=Ordering::Less{
+                return Err(MempoolError::PoolFull)
+            }
+            bytes-=victim.size;
+            victims.push((sender.to_string(),nonce));
+            remaining.get_mut(sender).expect("sender listed above").pop();
+        }
+        Ok(victims)
+    }
+
+    fn remove(&mut self,sender:&str,nonce:u64)->Option<SignedTransaction>{
+        let txs=self.by_sender.get_mut(sender)?;
+        let pooled=txs.remove(&nonce)?;
+        if txs.is_empty(){
+            self.by_sender.remove(sender);
+        }
+        self.total_bytes-=pooled.size;
+        self.hashes.remove(&pooled.hash);
+        self.unreserve(&pooled);
+        Some(pooled.tx)
+    }
+
+    /// Remove and return the best executable transactions for the next block.
+    /// Each sender's transactions are taken in nonce order starting at its account nonce;
+    /// among senders the highest-priority next transaction goes first.
+    pub fn take_for_block(&mut self,limits:BlockLimits,state:&State)->Vec<SignedTransaction>{
+        let mut next_nonce:BTreeMap<String,u64>=self
+        .by_sender
+        .keys()
+        .map(|sender| (sender.clone(),state.get_nonce(sender)))
+        .collect();
+        let mut picked:Vec<(String,u64)>=Vec::new();
+        let mut bytes=0usize;
+        let mut gas=0u64;
+
+        while picked.len()<limits.max_txs{
+            let best=next_nonce
+            .iter()
+            .filter_map(|(sender,nonce)| self.by_sender.get(sender)?.get(nonce).map(|tx| (sender,*nonce,tx)))
+            .filter(|(_,_,tx)| bytes+tx.size<=limits.max_bytes && gas.saturating_add(tx.gas)<=limits.max_gas)
+            .max_by(|a,b| a.2.cmp_priority(b.2).then_with(|| b.0.cmp(a.0)))
+            .map(|(sender,nonce,tx)| (sender.clone(),nonce,tx.size,tx.gas));
+            let Some((sender,nonce,size,tx_gas))=best else{
+                break
+            };
+            bytes+=size;
+            gas+=tx_gas;
+            next_nonce.insert(sender.clone(),nonce+1);
+            picked.push((sender,nonce));
+        }
+
+        picked
+        .into_iter()
+        .filter_map(|(sender,nonce)| self.remove(&sender,nonce))
+        .collect()
+    }
+
+    /// Drop transactions made stale by a newly applied block (nonce already used), time-locked
+    /// ones included, and move time-locked ones whose lock has expired into the ready pool
+    pub fn prune(&mut self,state:&State){
+        let stale:Vec<(String,u64)>=self
+        .by_sender
+        .iter()
+        .flat_map(|(sender,txs)| {
+            let account_nonce=state.get_nonce(sender);
+            txs.range(..account_nonce).map(move |(nonce,_)| (sender.clone(),*nonce))
+        })
+        .collect();
+        for (sender,nonce) in stale{
+            self.remove(&sender,nonce);
+        }
+
+        let stale_locked:Vec<(String,u64)>=self
+        .locked
+        .keys()
+        .filter(|(sender,nonce)| *nonce<state.get_nonce(sender))
+        .cloned()
+        .collect();
+        for key in stale_locked{
+            self.remove_locked(&key);
+        }
+
+        let released:Vec<(String,u64)>=self
+        .locked
+        .iter()
+        .filter(|(_,pooled)| pooled.tx.tx.not_before.is_none_or(|lock| state.is_unlocked(&lock)))
+        .map(|(key,_)| key.clone())
+        .collect();
+        for key in released{
+            let pooled=self.remove_locked(&key).expect("key collected from the locked queue");
+            // goes through normal admission; outbid transactions are dropped
+            let _=self.insert(pooled.tx,state);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::{Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+    use ed25519_dalek::Keypair;
+
+    fn transfer(kp:&Keypair,fee:u64,nonce:u64)->SignedTransaction{
+        let tx=Transaction::new(pubkey_to_address_hex(&kp.public),"bob".to_string(),10,fee,nonce,None);
+        SignedTransaction::sign_with_keypair(&tx,kp)
+    }
+
+    fn funded(keys:&[&Keypair])->State{
+        State::with_genesis(keys.iter().map(|kp| (pubkey_to_address_hex(&kp.public),1_000_000)).collect())
+    }
+
+    const ROOMY:BlockLimits=BlockLimits{max_txs:100,max_bytes:1_000_000,max_gas:u64::MAX};
+
+    #[test]
+    fn test_fee_priority_respects_nonce_order(){
+        let (alice,carol)=(generate_ed25519_keypair(),generate_ed25519_keypair());
+        let state=funded(&[&alice,&carol]);
+        let mut pool=Mempool::new(1_000_000);
+
+        // alice's cheap nonce 0 gates her expensive nonce 1
+        pool.insert(transfer(&alice,1,0),&state).unwrap();
+        pool.insert(transfer(&alice,100,1),&state).unwrap();
+        pool.insert(transfer(&carol,50,0),&state).unwrap();
+        // nonce gap: never executable until nonce 1 arrives
+        pool.insert(transfer(&carol,500,2),&state).unwrap();
+
+        let block=pool.take_for_block(ROOMY,&state);
+        let fees:Vec<u64>=block.iter().map(|t| t.tx.fee).collect();
+        assert_eq!(fees,vec![50,1,100]);
+        assert_eq!(pool.len(),1);
+
+        let tight=BlockLimits{max_txs:1,max_bytes:1_000_000,max_gas:u64::MAX};
+        pool.insert(transfer(&carol,5,0),&state).unwrap();
+        assert_eq!(pool.take_for_block(tight,&state).len(),1);
+    }
+
+    #[test]
+    fn test_replacement_duplicates_and_stale_nonces(){
+        let alice=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&alice.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),1_000)]);
+        let mut pool=Mempool::new(1_000_000);
+
+        let cheap=transfer(&alice,1,0);
+        pool.insert(cheap.clone(),&state).unwrap();
+        assert_eq!(pool.insert(cheap.clone(),&state),Err(MempoolError::Duplicate));
+        pool.insert(transfer(&alice,2,0),&state).unwrap();
+        assert_eq!(pool.len(),1);
+        assert!(!pool.contains(&cheap.tx_hash_hex()));
+
+        let mut forged=transfer(&alice,9,1);
+        forged.tx.fee=10;
+        assert!(matches!(pool.insert(forged,&state),Err(MempoolError::InvalidSignature(_))));
+
+        state.apply_transaction(&transfer(&alice,2,0)).unwrap();
+        assert_eq!(pool.insert(transfer(&alice,5,0),&state),Err(MempoolError::NonceTooLow));
+        pool.prune(&state);
+        assert!(pool.is_empty());
+        assert_eq!(pool.total_bytes(),0);
+    }
+
+    #[test]
+    fn test_full_pool_evicts_lowest_fee(){
+        let (a,b,c)=(generate_ed25519_keypair(),generate_ed25519_keypair(),generate_ed25519_keypair());
+        let state=funded(&[&a,&b,&c]);
+        let size=tx_size(&transfer(&a,1,0));
+        let mut pool=Mempool::new(size*2);
+
+        pool.insert(transfer(&a,10,0),&state).unwrap();
+        pool.insert(transfer(&b,1,0),&state).unwrap();
+        assert_eq!(pool.insert(transfer(&c,1,0),&state),Err(MempoolError::PoolFull));
+
+        pool.insert(transfer(&c,20,0),&state).unwrap();
+        assert_eq!(pool.len(),2);
+        let fees:Vec<u64>=pool.take_for_block(ROOMY,&state).iter().map(|t| t.tx.fee).collect();
+        assert_eq!(fees,vec![20,10]);
+    }
+
+    #[test]
+    fn test_refused_replacement_leaves_full_pool_alone(){
+        let (a,b)=(generate_ed25519_keypair(),generate_ed25519_keypair());
+        let state=funded(&[&a,&b]);
+        let (original,rich)=(transfer(&a,1_000,0),transfer(&b,100_000,0));
+        let mut pool=Mempool::new(tx_size(&original)+tx_size(&rich));
+        pool.insert(original.clone(),&state).unwrap();
+        pool.insert(rich.clone(),&state).unwrap();
+
+        // pays enough to replace, but only fits by evicting a better-paying transaction
+        let larger=Transaction::new(pubkey_to_address_hex(&a.public),"bob".to_string(),10,5_000,0,Some("x".repeat(64)));
+        let larger=SignedTransaction::sign_with_keypair(&larger,&a);
+        assert_eq!(pool.insert(larger,&state),Err(MempoolError::PoolFull));
+        assert!(pool.contains(&original.tx_hash_hex()) && pool.contains(&rich.tx_hash_hex()));
+        assert_eq!((pool.len(),pool.total_bytes(),pool.reserved(&pubkey_to_address_hex(&a.public))),(2,tx_size(&original)+tx_size(&rich),1_010));
+
+        // a same-size replacement fits in the room the original frees
+        let bumped=transfer(&a,2_000,0);
+        pool.insert(bumped.clone(),&state).unwrap();
+        assert!(pool.contains(&bumped.tx_hash_hex()) && !pool.contains(&original.tx_hash_hex()) && pool.contains(&rich.tx_hash_hex()));
+    }
+
+    #[test]
+    fn test_min_gas_price_and_block_gas_limit(){
+        let (alice,bob)=(generate_ed25519_keypair(),generate_ed25519_keypair());
+        let state=funded(&[&alice,&bob]);
+        let schedule=GasSchedule{min_gas_price:1,..GasSchedule::default()};
+        let mut pool=Mempool::with_gas_schedule(1_000_000,schedule);
+
+        let probe=transfer(&alice,0,0);
+        let gas=schedule.gas_used(&probe);
+        assert_eq!(pool.insert(probe,&state),Err(MempoolError::Underpriced));
+        pool.insert(transfer(&alice,gas,0),&state).unwrap();
+        pool.insert(transfer(&bob,gas*2,0),&state).unwrap();
+
+        // room for one transfer's worth of gas: the higher gas price wins
+        let limits=BlockLimits{max_gas:gas+gas/2,..ROOMY};
+        let block=pool.take_for_block(limits,&state);
+        assert_eq!(block.len(),1);
+        assert_eq!(block[0].tx.fee,gas*2);
+        assert_eq!(pool.len(),1);
+    }
+
+    #[test]
+    fn test_replace_by_fee_and_cancel(){
+        let alice=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&alice.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),1_000)]);
+        let mut pool=Mempool::new(1_000_000);
+
+        pool.insert(transfer(&alice,100,0),&state).unwrap();
+        // +5% isn't enough, +10% is
+        assert_eq!(pool.insert(transfer(&alice,105,0),&state),Err(MempoolError::ReplacementUnderpriced));
+        pool.insert(transfer(&alice,110,0),&state).unwrap();
+
+        let cancel=SignedTransaction::sign_with_keypair(&Transaction::cancel(addr.clone(),0,121),&alice);
+        let hash=pool.insert(cancel,&state).unwrap();
+        assert_eq!(pool.len(),1);
+
+        let block=pool.take_for_block(ROOMY,&state);
+        assert_eq!(block[0].tx_hash_hex(),hash);
+        state.apply_transaction(&block[0]).unwrap();
+        assert_eq!(state.get_balance(&addr),879);
+        assert_eq!(state.get_nonce(&addr),1);
+    }
+
+    #[test]
+    fn test_time_locked_wait_in_separate_queue(){
+        use crate::transaction::TimeLock;
+
+        let alice=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&alice.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),1_000)]);
+        let mut pool=Mempool::new(1_000_000);
+
+        let tx=Transaction::new(addr,"bob".to_string(),10,1,0,None).with_time_lock(TimeLock::Height(5));
+        let locked=SignedTransaction::sign_with_keypair(&tx,&alice);
+        pool.insert(locked.clone(),&state).unwrap();
+        assert_eq!(pool.locked_len(),1);
+        assert!(pool.take_for_block(ROOMY,&state).is_empty());
+        assert!(matches!(state.validate_transaction(&locked),Err(crate::state::StateError::TimeLocked)));
+
+        state.set_block_context(5,0);
+        pool.prune(&state);
+        assert_eq!(pool.locked_len(),0);
+        let block=pool.take_for_block(ROOMY,&state);
+        assert_eq!(block.len(),1);
+        state.apply_transaction(&block[0]).unwrap();
+    }
+
+    #[test]
+    fn test_locked_queue_is_bounded(){
+        use crate::transaction::TimeLock;
+
+        let (alice,carol)=(generate_ed25519_keypair(),generate_ed25519_keypair());
+        let mut state=funded(&[&alice,&carol]);
+        let locked=|kp:&Keypair,fee,nonce,lock|{
+            let tx=Transaction::new(pubkey_to_address_hex(&kp.public),"bob".to_string(),10,fee,nonce,None).with_time_lock(lock);
+            SignedTransaction::sign_with_keypair(&tx,kp)
+        };
+        let size=tx_size(&locked(&alice,1_000,0,TimeLock::Height(5)));
+        let limits=LockLimits{max_bytes:size*2,max_blocks_ahead:100,max_seconds_ahead:3_600};
+        let mut pool=Mempool::new(size*2).with_lock_limits(limits);
+
+        assert_eq!(pool.insert(locked(&alice,1_000,0,TimeLock::Height(101)),&state),Err(MempoolError::LockTooFar));
+        assert_eq!(pool.insert(locked(&alice,1_000,0,TimeLock::Timestamp(3_601)),&state),Err(MempoolError::LockTooFar));
+        assert_eq!(pool.insert(locked(&alice,1_000,0,TimeLock::Height(u64::MAX)),&state),Err(MempoolError::LockTooFar));
+
+        // one per sender and nonce, replaced only for a higher fee
+        pool.insert(locked(&alice,1_000,0,TimeLock::Height(5)),&state).unwrap();
+        assert_eq!(pool.insert(locked(&alice,1_000,0,TimeLock::Height(6)),&state),Err(MempoolError::ReplacementUnderpriced));
+        let bumped=pool.insert(locked(&alice,2_000,0,TimeLock::Height(6)),&state).unwrap();
+        assert_eq!((pool.locked_len(),pool.get(&bumped).map(|tx| tx.tx.fee)),(1,Some(2_000)));
+
+        // full: the cheapest locked one makes way for a better one, not for a worse one
+        pool.insert(locked(&alice,1_000,1,TimeLock::Height(5)),&state).unwrap();
+        assert_eq!(pool.insert(locked(&carol,500,0,TimeLock::Height(5)),&state),Err(MempoolError::PoolFull));
+        pool.insert(locked(&carol,5_000,0,TimeLock::Height(5)),&state).unwrap();
+        assert_eq!(pool.locked_len(),2);
+        assert_eq!(pool.next_nonce(&pubkey_to_address_hex(&alice.public),&state),1);
+
+        // ready transactions have their own room
+        pool.insert(transfer(&alice,1_000,0),&state).unwrap();
+        pool.insert(transfer(&alice,1_000,1),&state).unwrap();
+        assert_eq!(pool.len(),4);
+
+        // a used nonce drops the locked transaction before its lock ends
+        let block=pool.take_for_block(ROOMY,&state);
+        for tx in &block{
+            state.apply_transaction(tx).unwrap();
+        }
+        pool.prune(&state);
+        assert_eq!((pool.len(),pool.locked_len()),(1,1));
+    }
+
+    #[test]
+    fn test_size_limits_enforced_on_admission(){
+        let alice=generate_ed25519_keypair();
+        let state=funded(&[&alice]);
+        let limits=TxLimits{max_memo_bytes:8,max_tx_bytes:400};
+        let mut pool=Mempool::new(1_000_000).with_tx_limits(limits);
+        let with_memo=|memo:String|{
+            let tx=Transaction::new(pubkey_to_address_hex(&alice.public),"bob".to_string(),1,1,0,Some(memo));
+            SignedTransaction::sign_with_keypair(&tx,&alice)
+        };
+
+        assert!(matches!(
+            pool.insert(with_memo("x".repeat(9)),&state),
+            Err(MempoolError::ExceedsLimit(LimitError::MemoTooLarge{bytes:9,max:8}))
+        ));
+        let bulky=SignedTransaction::sign_with_keypair(
+            &Transaction::with_payload(
+                pubkey_to_address_hex(&alice.public),
+                TxPayload::RegisterValidator{consensus_pubkey:"k".repeat(300),vrf_pubkey:String::new(),endpoint:String::new()},
+                1,
+                0,
+                None,
+            ),
+            &alice,
+        );
+        assert!(matches!(pool.insert(bulky,&state),Err(MempoolError::ExceedsLimit(LimitError::TxTooLarge{max:400,..}))));
+        pool.insert(with_memo("x".repeat(8)),&state).unwrap();
+    }
+
+    #[test]
+    fn test_next_nonce_counts_pooled_transactions(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(sender.clone(),1_000)]);
+        let mut pool=Mempool::new(1_000_000);
+        assert_eq!(pool.next_nonce(&sender,&state),0);
+
+        pool.insert(transfer(&kp,5,0),&state).unwrap();
+        pool.insert(transfer(&kp,5,1),&state).unwrap();
+        // a gap: nonce 3 can't execute before 2, so 2 is still next
+        pool.insert(transfer(&kp,5,3),&state).unwrap();
+        assert_eq!(pool.next_nonce(&sender,&state),2);
+        assert_eq!(pool.next_nonce("bob",&state),0);
+
+        state.apply_transaction(&transfer(&kp,5,0)).unwrap();
+        pool.prune(&state);
+        assert_eq!(pool.next_nonce(&sender,&state),2);
+    }
+
+    #[test]
+    fn test_pending_spend_reserved_against_balance(){
+        let alice=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&alice.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),25)]);
+        let mut pool=Mempool::new(1_000_000);
+
+        // each transfer spends 11; two fit in 25, a third doesn't
+        pool.insert(transfer(&alice,1,0),&state).unwrap();
+        pool.insert(transfer(&alice,1,1),&state).unwrap();
+        assert_eq!(pool.reserved(&addr),22);
+        assert_eq!(pool.insert(transfer(&alice,1,2),&state),Err(MempoolError::InsufficientBalance));
+
+        // a replacement only needs room for the difference
+        pool.insert(transfer(&alice,3,1),&state).unwrap();
+        assert_eq!(pool.reserved(&addr),24);
+        assert_eq!(pool.insert(transfer(&alice,5,1),&state),Err(MempoolError::InsufficientBalance));
+
+        // once the first executes, its reservation goes with it
+        state.apply_transaction(&transfer(&alice,1,0)).unwrap();
+        pool.prune(&state);
+        assert_eq!(pool.reserved(&addr),13);
+        assert_eq!(pool.insert(transfer(&alice,1,2),&state),Err(MempoolError::InsufficientBalance));
+    }
+}
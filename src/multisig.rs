@@ -0,0 +1,186 @@
+// src/multisig.rs
+
+//! M-of-N multisig accounts
+//! - `MultisigPolicy`: N Ed25519 public keys plus a threshold M
+//! - The account address is derived from the policy, so funds sent there can only
+//!   move with M distinct signatures from the listed keys
+//! - `MultisigSignatures` travel inside `SignedTransaction` in place of the single signature
+
+use crate::signer::TxSigner;
+use crate::transaction::SignatureScheme;
+use base64::{engine::general_purpose,Engine as _};
+use ed25519_dalek::{PublicKey,Signature,Verifier};
+use serde::{Deserialize,Serialize};
+use sha2::{Digest,Sha256};
+use std::collections::BTreeMap;
+use thiserror::Error;
+
+/// Domain separation so a multisig address can never collide with a single-key address
+const MULTISIG_DOMAIN:&[u8]=b"netchain-multisig";
+
+/// Reasons a multisig policy or signature set is rejected
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum MultisigError{
+    /// Threshold is zero or larger than the number of keys
+    #[error("threshold must be between 1 and the number of keys")]
+    InvalidThreshold,
+    #[error("duplicate key in policy")]
+    DuplicateKey,
+    #[error("invalid Ed25519 key")]
+    InvalidKey,
+    /// The signing key isn't part of the policy
+    #[error("key is not part of the policy")]
+    NotASigner,
+    #[error("invalid multisig signature")]
+    InvalidSignature,
+    /// Fewer valid distinct signatures than the threshold
+    #[error("not enough signatures to meet the threshold")]
+    ThresholdNotMet,
+}
+
+/// M-of-N signing policy
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct MultisigPolicy{
+    pub threshold:u32,
+    /// Ed25519 public keys encoded as base64, sorted
+    pub pubkeys:Vec<String>,
+}
+
+impl MultisigPolicy{
+    /// Build a policy; keys are sorted so the same set always yields the same address
+    pub fn new(threshold:u32,mut pubkeys:Vec<String>)->Result<Self,MultisigError>{
+        pubkeys.sort();
+        let policy=MultisigPolicy{threshold,pubkeys};
+        policy.validate()?;
+        Ok(policy)
+    }
+
+    pub fn validate(&self)->Result<(),MultisigError>{
+        if self.threshold==0 || self.threshold as usize>self.pubkeys.len(){
+            return Err(MultisigError::InvalidThreshold)
+        }
+        if self.pubkeys.windows(2).any(|w| w[0]>=w[1]){
+            return Err(MultisigError::DuplicateKey)
+        }
+        for key in &self.pubkeys{
+            decode_pubkey(key)?;
+        }
+        Ok(())
+    }
+
+    /// Account address: sha256(domain || threshold || keys), first 20 bytes hex encoded
+    pub fn address(&self)->String{
+        let mut hasher=Sha256::new();
+        hasher.update(MULTISIG_DOMAIN);
+        hasher.update(self.threshold.to_le_bytes());
+        for key in &self.pubkeys{
+            hasher.update(key.as_bytes());
+        }
+        hex::encode(&hasher.finalize()[0..20])
+    }
+}
+
+fn decode_pubkey(encoded:&str)->Result<PublicKey,MultisigError>{
+    let bytes=general_purpose::STANDARD
+    .decode(encoded)
+    .map_err(|_| MultisigError::InvalidKey)?;
+    PublicKey::from_bytes(&bytes).map_err(|_| MultisigError::InvalidKey)
+}
+
+/// Signatures collected for one message under a policy
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct MultisigSignatures{
+    pub policy:MultisigPolicy,
+    /// index into `policy.pubkeys` -> base64 signature
+    pub signatures:BTreeMap<u32,String>,
+}
+
+impl MultisigSignatures{
+    pub fn new(policy:MultisigPolicy)->Self{
+        MultisigSignatures{policy,signatures:BTreeMap::new()}
+    }
+
+    /// Add `signer`'s signature over `message` (replacing an earlier one from the same key).
+    /// The signer must use Ed25519.
+    pub fn sign(&mut self,message:&[u8],signer:&dyn TxSigner)->Result<(),MultisigError>{
+        if signer.scheme()!=SignatureScheme::Ed25519{
+            return Err(MultisigError::InvalidKey)
+        }
+        let encoded=general_purpose::STANDARD.encode(signer.public_key());
+        let index=self
+        .policy
+        .pubkeys
+        .iter()
+        .position(|k| *k==encoded)
+        .ok_or(MultisigError::NotASigner)?;
+        let sig=signer.sign(message).map_err(|_| MultisigError::InvalidSignature)?;
+        self.signatures.insert(index as u32,general_purpose::STANDARD.encode(sig));
+        Ok(())
+    }
+
+    /// Check the policy and that at least `threshold` listed keys signed `message`
+    pub fn verify(&self,message:&[u8])->Result<(),MultisigError>{
+        self.policy.validate()?;
+        for (index,encoded) in &self.signatures{
+            let key=self
+            .policy
+            .pubkeys
+            .get(*index as usize)
+            .ok_or(MultisigError::NotASigner)?;
+            let sig_bytes=general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|_| MultisigError::InvalidSignature)?;
+            let signature=Signature::from_bytes(&sig_bytes).map_err(|_| MultisigError::InvalidSignature)?;
+            decode_pubkey(key)?
+            .verify(message,&signature)
+            .map_err(|_| MultisigError::InvalidSignature)?;
+        }
+        if self.signatures.len()<self.policy.threshold as usize{
+            return Err(MultisigError::ThresholdNotMet)
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::generate_ed25519_keypair;
+    use ed25519_dalek::Keypair;
+
+    fn encoded(kp:&Keypair)->String{
+        general_purpose::STANDARD.encode(kp.public.to_bytes())
+    }
+
+    #[test]
+    fn test_policy_address_and_validation(){
+        let keys:Vec<Keypair>=(0..3).map(|_| generate_ed25519_keypair()).collect();
+        let a=MultisigPolicy::new(2,keys.iter().map(encoded).collect()).unwrap();
+        let b=MultisigPolicy::new(2,keys.iter().rev().map(encoded).collect()).unwrap();
+        assert_eq!(a.address(),b.address());
+        assert_ne!(a.address(),MultisigPolicy::new(3,a.pubkeys.clone()).unwrap().address());
+
+        assert_eq!(MultisigPolicy::new(4,a.pubkeys.clone()),Err(MultisigError::InvalidThreshold));
+        assert_eq!(
+            MultisigPolicy::new(1,vec![encoded(&keys[0]),encoded(&keys[0])]),
+            Err(MultisigError::DuplicateKey)
+        );
+    }
+
+    #[test]
+    fn test_threshold_signatures(){
+        let keys:Vec<Keypair>=(0..3).map(|_| generate_ed25519_keypair()).collect();
+        let policy=MultisigPolicy::new(2,keys.iter().map(encoded).collect()).unwrap();
+        let mut sigs=MultisigSignatures::new(policy);
+
+        sigs.sign(b"msg",&keys[0]).unwrap();
+        // signing twice with the same key doesn't count twice
+        sigs.sign(b"msg",&keys[0]).unwrap();
+        assert_eq!(sigs.verify(b"msg"),Err(MultisigError::ThresholdNotMet));
+
+        sigs.sign(b"msg",&keys[2]).unwrap();
+        assert!(sigs.verify(b"msg").is_ok());
+        assert_eq!(sigs.verify(b"other"),Err(MultisigError::InvalidSignature));
+        assert_eq!(sigs.sign(b"msg",&generate_ed25519_keypair()),Err(MultisigError::NotASigner));
+    }
+}
@@ -0,0 +1,135 @@
+// src/names.rs
+
+//! On-chain name registry
+//! - A `RegisterName` transaction binds a unique name to the sender's address for
+//!   `NAME_REGISTRATION_BLOCKS` blocks, burning `NAME_REGISTRATION_COST`
+//! - The owner renews by registering the same name again (the period is added on top);
+//!   once a name expires anyone can take it
+//! - Transfers can name their receiver as `@name` instead of an address
+
+use imbl::OrdMap;
+use serde::{Deserialize,Serialize};
+use thiserror::Error;
+
+/// Native coins burned per registration or renewal
+pub const NAME_REGISTRATION_COST:u64=1_000;
+
+/// Blocks a registration (or renewal) lasts
+pub const NAME_REGISTRATION_BLOCKS:u64=5_000_000;
+
+/// Length limits of a name (without the `@`)
+pub const MIN_NAME_LEN:usize=3;
+pub const MAX_NAME_LEN:usize=32;
+
+/// Prefix marking a receiver as a name to resolve
+pub const NAME_PREFIX:char='@';
+
+/// Errors returned by registry operations
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum NameError{
+    /// Not 3..=32 lowercase letters, digits or '-', or starting / ending with '-'
+    #[error("invalid name")]
+    InvalidName,
+    /// Held by another account and not expired
+    #[error("name is taken")]
+    Taken,
+    /// `@name` isn't registered or has expired
+    #[error("name not registered")]
+    Unregistered,
+}
+
+/// Who holds a name, and until when
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct NameRecord{
+    pub owner:String,
+    /// First block height at which the name is no longer valid
+    pub expires_at:u64,
+}
+
+pub fn is_valid_name(name:&str)->bool{
+    (MIN_NAME_LEN..=MAX_NAME_LEN).contains(&name.len())
+    && name.bytes().all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b==b'-')
+    && !name.starts_with('-')
+    && !name.ends_with('-')
+}
+
+/// Registered names. Expired records stay until someone else takes the name.
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct NameRegistry{
+    names:OrdMap<String,NameRecord>,
+}
+
+impl NameRegistry{
+    pub fn new()->Self{
+        Self::default()
+    }
+
+    /// Check that `owner` may register (or renew) `name` at `height`
+    pub fn validate_register(&self,name:&str,owner:&str,height:u64)->Result<(),NameError>{
+        if !is_valid_name(name){
+            return Err(NameError::InvalidName)
+        }
+        match self.names.get(name){
+            Some(record) if record.owner!=owner && record.expires_at>height=>Err(NameError::Taken),
+            _=>Ok(()),
+        }
+    }
+
+    /// Register or renew `name` for `owner`; returns the new expiry height.
+    /// A renewal extends the current expiry, a new registration starts at `height`.
+    pub fn register(&mut self,name:&str,owner:&str,height:u64)->Result<u64,NameError>{
+        self.validate_register(name,owner,height)?;
+        let start=match self.names.get(name){
+            Some(record) if record.owner==owner=>record.expires_at.max(height),
+            _=>height,
+        };
+        let expires_at=start.saturating_add(NAME_REGISTRATION_BLOCKS);
+        self.names.insert(name.to_string(),NameRecord{owner:owner.to_string(),expires_at});
+        Ok(expires_at)
+    }
+
+    /// Record of `name` if it is valid at `height`
+    pub fn get(&self,name:&str,height:u64)->Option<&NameRecord>{
+        self.names.get(name).filter(|record| record.expires_at>height)
+    }
+
+    /// Address behind `name` at `height`
+    pub fn resolve(&self,name:&str,height:u64)->Option<&str>{
+        self.get(name,height).map(|record| record.owner.as_str())
+    }
+
+    /// Names held by `owner` at `height`
+    pub fn names_of(&self,owner:&str,height:u64)->Vec<&str>{
+        self.names
+        .iter()
+        .filter(|(_,record)| record.owner==owner && record.expires_at>height)
+        .map(|(name,_)| name.as_str())
+        .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_register_renew_and_expire(){
+        let mut names=NameRegistry::new();
+        assert_eq!(names.register("Alice",&"a".repeat(40),0),Err(NameError::InvalidName));
+        assert_eq!(names.register("-al",&"a".repeat(40),0),Err(NameError::InvalidName));
+
+        let expiry=names.register("alice","addr-a",10).unwrap();
+        assert_eq!(expiry,10+NAME_REGISTRATION_BLOCKS);
+        assert_eq!(names.register("alice","addr-b",20),Err(NameError::Taken));
+        assert_eq!(names.resolve("alice",expiry-1),Some("addr-a"));
+
+        // renewing adds a period on top of the current one
+        assert_eq!(names.register("alice","addr-a",20).unwrap(),expiry+NAME_REGISTRATION_BLOCKS);
+        let expiry=expiry+NAME_REGISTRATION_BLOCKS;
+        assert_eq!(names.names_of("addr-a",0),vec!["alice"]);
+
+        assert_eq!(names.resolve("alice",expiry),None);
+        names.register("alice","addr-b",expiry).unwrap();
+        assert_eq!(names.resolve("alice",expiry),Some("addr-b"));
+    }
+}
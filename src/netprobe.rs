@@ -0,0 +1,219 @@
+// src/netprobe.rs
+
+//! Active latency probing
+//! - Periodically measures RTT to a configurable set of peers
+//! - Keeps a rolling window of samples per peer
+//! - Feeds `NodeMetrics.latency_ms` from real measurements instead of hand-entered values
+//!
+//! The probe method is pluggable (`Prober`); `TcpConnectProber` times a TCP
+//! handshake, which works against any listening peer without protocol support.
+
+use crate::consensus::NodeMetrics;
+use std::collections::{HashMap,VecDeque};
+use std::net::{SocketAddr,TcpStream,ToSocketAddrs};
+use std::time::{Duration,Instant};
+
+/// Probe configuration
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct ProbeConfig{
+    /// peer id -> address ("host:port")
+    pub peers:HashMap<String,String>,
+    /// How often `NetProbe::due` reports that a new round should run
+    pub interval:Duration,
+    /// Give up on a probe after this long (counted as a failure)
+    pub timeout:Duration,
+    /// Number of recent samples kept per peer for the rolling average
+    pub window:usize,
+}
+
+impl Default for ProbeConfig{
+    fn default()->Self{
+        ProbeConfig{
+            peers:HashMap::new(),
+            interval:Duration::from_secs(30),
+            timeout:Duration::from_secs(2),
+            window:20,
+        }
+    }
+}
+
+/// Measures a single round trip to a peer
+pub trait Prober{
+    /// Returns the measured RTT, or None if the peer didn't answer within `timeout`
+    fn probe(&mut self,address:&str,timeout:Duration)->Option<Duration>;
+}
+
+/// RTT = time to complete a TCP handshake
+#[derive(Debug,Clone,Copy,Default)]
+pub struct TcpConnectProber;
+
+impl Prober for TcpConnectProber{
+    fn probe(&mut self,address:&str,timeout:Duration)->Option<Duration>{
+        let addr:SocketAddr=address.to_socket_addrs().ok()?.next()?;
+        let started=Instant::now();
+        TcpStream::connect_timeout(&addr,timeout).ok()?;
+        Some(started.elapsed())
+    }
+}
+
+/// Rolling RTT samples per peer
+#[derive(Debug,Clone)]
+pub struct NetProbe{
+    config:ProbeConfig,
+    /// peer id -> recent RTTs in ms (None = timed out)
+    samples:HashMap<String,VecDeque<Option<u64>>>,
+    last_round:Option<Instant>,
+}
+
+impl NetProbe{
+    pub fn new(config:ProbeConfig)->Self{
+        NetProbe{
+            config,
+            samples:HashMap::new(),
+            last_round:None,
+        }
+    }
+
+    pub fn config(&self)->&ProbeConfig{
+        &self.config
+    }
+
+    /// True if `interval` has passed since the last probing round
+    pub fn due(&self,now:Instant)->bool{
+        self.last_round
+        .is_none_or(|last| now.duration_since(last)>=self.config.interval)
+    }
+
+    /// Record an RTT sample (or a timeout) for `peer`
+    pub fn record(&mut self,peer:&str,rtt:Option<Duration>){
+        let window=self.config.window.max(1);
+        let samples=self.samples.entry(peer.to_string()).or_default();
+        samples.push_back(rtt.map(|d| d.as_millis() as u64));
+        while samples.len()>window{
+            samples.pop_front();
+        }
+    }
+
+    /// Probe every configured peer once
+    pub fn run_round<P:Prober>(&mut self,prober:&mut P,now:Instant){
+        let peers:Vec<(String,String)>=self
+        .config
+        .peers
+        .iter()
+        .map(|(id,addr)| (id.clone(),addr.clone()))
+        .collect();
+        for (id,addr) in peers{
+            let rtt=prober.probe(&addr,self.config.timeout);
+            self.record(&id,rtt);
+        }
+        self.last_round=Some(now);
+    }
+
+    /// Rolling average RTT to `peer` in ms over successful probes.
+    /// Timeouts count as `timeout` so unreachable peers look slow rather than fast.
+    pub fn average_rtt_ms(&self,peer:&str)->Option<u64>{
+        let samples=self.samples.get(peer)?;
+        if samples.is_empty(){
+            return None
+        }
+        let timeout_ms=self.config.timeout.as_millis() as u64;
+        let total:u64=samples.iter().map(|s| s.unwrap_or(timeout_ms)).sum();
+        Some(total/samples.len() as u64)
+    }
+
+    /// Average RTT across all probed peers: this node's view of its own latency
+    pub fn mean_latency_ms(&self)->Option<u64>{
+        let averages:Vec<u64>=self.samples.keys().filter_map(|p| self.average_rtt_ms(p)).collect();
+        if averages.is_empty(){
+            return None
+        }
+        Some(averages.iter().sum::<u64>()/averages.len() as u64)
+    }
+
+    /// Overwrite `metrics.latency_ms` with the measured mean. Returns false if nothing was measured yet.
+    pub fn apply_to(&self,metrics:&mut NodeMetrics)->bool{
+        match self.mean_latency_ms(){
+            Some(latency)=>{
+                metrics.latency_ms=latency;
+                true
+            }
+            None=>false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::net::TcpListener;
+
+    /// Replays fixed RTTs per address
+    struct ScriptedProber{
+        rtts:HashMap<String,Option<u64>>,
+    }
+
+    impl Prober for ScriptedProber{
+        fn probe(&mut self,address:&str,_timeout:Duration)->Option<Duration>{
+            self.rtts.get(address).copied().flatten().map(Duration::from_millis)
+        }
+    }
+
+    fn config()->ProbeConfig{
+        let mut peers=HashMap::new();
+        peers.insert("a".to_string(),"a:1".to_string());
+        peers.insert("b".to_string(),"b:1".to_string());
+        ProbeConfig{
+            peers,
+            window:3,
+            timeout:Duration::from_millis(500),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_rolling_average_and_timeouts(){
+        let mut probe=NetProbe::new(config());
+        let mut prober=ScriptedProber{
+            rtts:[("a:1".to_string(),Some(20)),("b:1".to_string(),None)].into_iter().collect(),
+        };
+        let now=Instant::now();
+        assert!(probe.due(now));
+        probe.run_round(&mut prober,now);
+        assert!(!probe.due(now));
+
+        assert_eq!(probe.average_rtt_ms("a"),Some(20));
+        assert_eq!(probe.average_rtt_ms("b"),Some(500));
+        assert_eq!(probe.mean_latency_ms(),Some(260));
+
+        // window drops the oldest samples
+        for rtt in [40,40,40]{
+            probe.record("a",Some(Duration::from_millis(rtt)));
+        }
+        assert_eq!(probe.average_rtt_ms("a"),Some(40));
+    }
+
+    #[test]
+    fn test_apply_to_metrics(){
+        let mut probe=NetProbe::new(config());
+        let mut metrics=NodeMetrics{
+            node_id:"me".to_string(),
+            upload_mbps:10,
+            download_mbps:10,
+            latency_ms:1,
+            uptime_bps:10_000,
+            stability_bps:10_000,
+        };
+        assert!(!probe.apply_to(&mut metrics));
+        probe.record("a",Some(Duration::from_millis(75)));
+        assert!(probe.apply_to(&mut metrics));
+        assert_eq!(metrics.latency_ms,75);
+    }
+
+    #[test]
+    fn test_tcp_connect_prober(){
+        let listener=TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr=listener.local_addr().unwrap().to_string();
+        assert!(TcpConnectProber.probe(&addr,Duration::from_secs(1)).is_some());
+        drop(listener);
+    }
+}
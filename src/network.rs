@@ -0,0 +1,1028 @@
+// src/network.rs
+
+//! Peer-to-peer networking
+//! - Async TCP transport on tokio; every node has a persistent identity key and a `PeerId`
+//!   derived from it (see `identity`)
+//! - Connections are encrypted and authenticated with Noise; the `Hello` exchange happens
+//!   inside the handshake and proves the peer holds its identity key (see `noise`), then
+//!   frames flow (see `wire`)
+//! - Connection management: dialing, accepting up to `max_peers`, one connection per peer,
+//!   disconnects. Nodes behind NAT map their port on the gateway (see `nat`) or are reached
+//!   through relays (see `relay`)
+//! - Message bus: chain, mempool and consensus `subscribe` to a `Topic` and `send` or
+//!   `broadcast` payloads on it; peer connects / disconnects, failed dials and background
+//!   failures (port mapping, relay reservations, saving state to disk) are published on
+//!   `events`
+//! - Services `report` misbehaving peers; peers whose score drops too low are disconnected
+//!   and refused until their ban ends (see `reputation`)
+//! - Handshakes must finish within `handshake_timeout`, and at most `max_pending_handshakes`
+//!   inbound ones run at once, so silent or slow dialers can't pile up connections
+//! - Inbound messages are checked against per-peer size caps and rate limits before they
+//!   reach subscribers; services `allow` costly requests (see `ratelimit`)
+
+pub mod bandwidth;
+pub mod compression;
+pub mod discovery;
+pub mod gossip;
+pub mod identity;
+pub mod nat;
+pub mod noise;
+pub mod observer;
+pub mod quic;
+pub mod ratelimit;
+pub mod relay;
+pub mod reputation;
+pub mod statesync;
+pub mod sync;
+pub mod wire;
+
+use crate::canonical::DecodeError;
+use bandwidth::{Bandwidth,BandwidthConfig,BandwidthStats};
+use compression::CompressionConfig;
+use identity::{NodeIdentity,PeerId};
+use serde::{Deserialize,Serialize};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant,SystemTime,UNIX_EPOCH};
+use thiserror::Error;
+use tokio::io::{AsyncRead,AsyncWrite};
+use tokio::net::{TcpListener,TcpStream};
+use tokio::sync::{Semaphore,broadcast,mpsc};
+use tokio::task::AbortHandle;
+use noise::{NoiseKeys,SecureReader,SecureWriter};
+use observer::PeerObserver;
+use quic::LinkStats;
+use ratelimit::{PeerLimiter,RateClass,RateLimitConfig};
+use reputation::{Misbehavior,PeerReputation,PeerStanding,ReputationConfig};
+use std::sync::atomic::{AtomicU64,Ordering};
+use wire::{DisconnectReason,Frame,Hello,MIN_PROTOCOL_VERSION,PROTOCOL_VERSION,WireMessage,default_capabilities};
+
+/// Frames queued for one peer before `send` starts failing for it
+const PEER_QUEUE:usize=1024;
+
+/// Network events kept for slow `events` subscribers
+const EVENT_BUFFER:usize=256;
+
+/// Longest wait for a refused peer's verdict before closing
+const REFUSAL_LINGER:Duration=Duration::from_secs(1);
+
+/// Networking failures
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum NetworkError{
+    #[error("network I/O error: {0}")]
+    Io(String),
+    #[error("invalid node identity key")]
+    InvalidIdentity,
+    #[error("frame of {0} bytes exceeds the limit")]
+    FrameTooLarge(usize),
+    #[error(transparent)]
+    Decode(#[from] DecodeError),
+    /// The peer's `Hello` was missing, malformed or for another protocol / network
+    #[error("handshake failed: {0}")]
+    Handshake(String),
+    #[error("connected to ourselves")]
+    SelfConnection,
+    #[error("already connected to {0}")]
+    AlreadyConnected(PeerId),
+    #[error("peer limit reached")]
+    PeerLimit,
+    #[error("not connected to {0}")]
+    NotConnected(PeerId),
+    /// The peer's outgoing queue is full or its connection is closing
+    #[error("peer {0} is not accepting messages")]
+    PeerBusy(PeerId),
+    #[error("peer {0} is banned")]
+    Banned(PeerId),
+    /// We turned the peer away after its `Hello`
+    #[error("refused peer: {0}")]
+    Refused(DisconnectReason),
+    /// The peer turned us away after our `Hello`
+    #[error("peer refused the connection: {0}")]
+    PeerRefused(DisconnectReason),
+    #[error("port mapping failed: {0}")]
+    Nat(String),
+    #[error("relay failed: {0}")]
+    Relay(String),
+    #[error("quic: {0}")]
+    Quic(String),
+}
+
+impl NetworkError{
+    /// What to tell a peer we refuse because of this error
+    fn disconnect_reason(&self)->DisconnectReason{
+        match self{
+            NetworkError::Refused(reason)=>reason.clone(),
+            NetworkError::SelfConnection=>DisconnectReason::SelfConnection,
+            NetworkError::AlreadyConnected(_)=>DisconnectReason::AlreadyConnected,
+            NetworkError::PeerLimit=>DisconnectReason::TooManyPeers,
+            NetworkError::Banned(_)=>DisconnectReason::Banned,
+            _=>DisconnectReason::Requested,
+        }
+    }
+}
+
+impl From<std::io::Error> for NetworkError{
+    fn from(e:std::io::Error)->Self{
+        NetworkError::Io(e.to_string())
+    }
+}
+
+/// Message bus channels
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash,PartialOrd,Ord,Serialize,Deserialize)]
+pub enum Topic{
+    Transactions,
+    Blocks,
+    Consensus,
+    Sync,
+    Discovery,
+    State,
+}
+
+#[derive(Debug,Clone)]
+pub struct NetworkConfig{
+    /// Address to accept connections on (port 0 picks a free one)
+    pub listen_addr:SocketAddr,
+    /// Peers with a different id are refused during the handshake
+    pub network_id:String,
+    /// Hash of our genesis block; peers with another genesis are refused
+    pub genesis_hash:String,
+    /// Protocols we announce (see `wire`)
+    pub capabilities:Vec<String>,
+    /// Most simultaneous connections, inbound and outbound together
+    pub max_peers:usize,
+    pub reputation:ReputationConfig,
+    pub rate_limits:RateLimitConfig,
+    /// Also accept and allow dialing QUIC, on the UDP port numbered like the TCP listener
+    pub quic:bool,
+    pub compression:CompressionConfig,
+    /// Pre-shared key of a private network: only nodes holding it can connect (see `noise`)
+    pub network_key:Option<[u8;32]>,
+    /// Node-wide upload / download caps (see `bandwidth`)
+    pub bandwidth:BandwidthConfig,
+    /// Longest a connection may take from accept / dial to both sides being ready
+    pub handshake_timeout:Duration,
+    /// Inbound handshakes in flight at once; further connections wait to be accepted
+    pub max_pending_handshakes:usize,
+}
+
+impl Default for NetworkConfig{
+    fn default()->Self{
+        NetworkConfig{
+            listen_addr:SocketAddr::from(([0,0,0,0],30333)),
+            network_id:"netchain".to_string(),
+            genesis_hash:String::new(),
+            capabilities:default_capabilities(),
+            max_peers:50,
+            reputation:ReputationConfig::default(),
+            rate_limits:RateLimitConfig::default(),
+            quic:false,
+            compression:CompressionConfig::default(),
+            network_key:None,
+            bandwidth:BandwidthConfig::default(),
+            handshake_timeout:Duration::from_secs(10),
+            max_pending_handshakes:64,
+        }
+    }
+}
+
+/// A payload received on a subscribed topic
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct InboundMessage{
+    pub from:PeerId,
+    pub topic:Topic,
+    pub payload:Vec<u8>,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub enum NetworkEvent{
+    PeerConnected{peer:PeerId,addr:SocketAddr,outbound:bool},
+    /// `reason` is what either side gave for closing, `None` if the connection just dropped
+    PeerDisconnected{peer:PeerId,reason:Option<DisconnectReason>},
+    /// A `dial` that didn't end in a connection
+    DialFailed{addr:SocketAddr,error:NetworkError},
+    /// Mapping our port on the gateway failed; retried after a delay
+    PortMappingFailed{error:NetworkError},
+    /// Reserving a slot on `relay` failed; retried after a delay
+    ReservationFailed{relay:SocketAddr,error:NetworkError},
+    /// Peer reputation or a sync checkpoint couldn't be written to disk
+    SaveFailed{error:NetworkError},
+}
+
+/// A connected peer, as reported by `Network::peers`
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct PeerInfo{
+    pub peer_id:PeerId,
+    /// Remote address of the connection
+    pub addr:SocketAddr,
+    /// Where the peer accepts connections, if it listens
+    pub listen_addr:Option<SocketAddr>,
+    /// Whether we dialed it
+    pub outbound:bool,
+    pub protocol_version:u32,
+    /// The peer's chain height when it connected
+    pub height:u64,
+    /// Capabilities both sides announced
+    pub capabilities:Vec<String>,
+    pub transport:Transport,
+}
+
+/// What carries a peer connection
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum Transport{
+    Tcp,
+    /// Through a relay, whose address is then the connection's `addr`
+    Relay,
+    Quic,
+}
+
+/// Transport of a connection, keeping what we need of it after the handshake
+enum Link{
+    Tcp,
+    Relay,
+    Quic(quinn::Connection),
+}
+
+impl Link{
+    fn transport(&self)->Transport{
+        match self{
+            Link::Tcp=>Transport::Tcp,
+            Link::Relay=>Transport::Relay,
+            Link::Quic(_)=>Transport::Quic,
+        }
+    }
+}
+
+struct Connection{
+    /// Distinguishes a connection from a later one to the same peer
+    id:u64,
+    info:PeerInfo,
+    sender:mpsc::Sender<Frame>,
+    reader:AbortHandle,
+    limiter:PeerLimiter,
+    link:Link,
+}
+
+struct Shared{
+    identity:NodeIdentity,
+    noise:NoiseKeys,
+    peer_id:PeerId,
+    config:NetworkConfig,
+    local_addr:SocketAddr,
+    /// QUIC endpoint, when `NetworkConfig::quic` is set
+    quic:Option<quinn::Endpoint>,
+    connections:Mutex<HashMap<PeerId,Connection>>,
+    next_connection:Mutex<u64>,
+    subscribers:Mutex<HashMap<Topic,Vec<mpsc::UnboundedSender<InboundMessage>>>>,
+    events:broadcast::Sender<NetworkEvent>,
+    reputation:Mutex<PeerReputation>,
+    /// Chain height announced in our `Hello`
+    height:AtomicU64,
+    /// Public address of our listener when the gateway maps it
+    external_addr:Mutex<Option<SocketAddr>>,
+    observer:PeerObserver,
+    bandwidth:Bandwidth,
+    /// Permits for inbound handshakes, `max_pending_handshakes` of them
+    handshakes:Arc<Semaphore>,
+}
+
+fn unix_now()->u64{
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Handle on the running network service; cheap to clone
+#[derive(Clone)]
+pub struct Network{
+    shared:Arc<Shared>,
+}
+
+impl Network{
+    /// Bind the listener (and the QUIC endpoint, if enabled) and start accepting connections
+    pub async fn start(identity:NodeIdentity,config:NetworkConfig)->Result<Self,NetworkError>{
+        let listener=TcpListener::bind(config.listen_addr).await?;
+        let local_addr=listener.local_addr()?;
+        let quic=if config.quic{Some(quic::endpoint(local_addr)?)} else{None};
+        let reputation=PeerReputation::load(config.reputation.clone())?;
+        let shared=Arc::new(Shared{
+            peer_id:identity.peer_id(),
+            identity,
+            noise:NoiseKeys::generate(),
+            local_addr,
+            quic,
+            connections:Mutex::new(HashMap::new()),
+            next_connection:Mutex::new(0),
+            subscribers:Mutex::new(HashMap::new()),
+            events:broadcast::channel(EVENT_BUFFER).0,
+            reputation:Mutex::new(reputation),
+            height:AtomicU64::new(0),
+            external_addr:Mutex::new(None),
+            observer:PeerObserver::default(),
+            bandwidth:Bandwidth::new(&config.bandwidth,Instant::now()),
+            handshakes:Arc::new(Semaphore::new(config.max_pending_handshakes)),
+            config,
+        });
+        let network=Network{shared};
+        let acceptor=network.clone();
+        tokio::spawn(async move {
+            loop{
+                // connections past the cap wait in the listen backlog
+                let Ok(permit)=acceptor.shared.handshakes.clone().acquire_owned().await else{break};
+                let Ok((stream,addr))=listener.accept().await else{break};
+                let network=acceptor.clone();
+                // a failed inbound handshake only affects that connection
+                tokio::spawn(async move {
+                    let _=network.establish(stream,addr,false,Link::Tcp).await;
+                    drop(permit);
+                });
+            }
+        });
+        if let Some(endpoint)=network.shared.quic.clone(){
+            let acceptor=network.clone();
+            tokio::spawn(async move {
+                loop{
+                    let Ok(permit)=acceptor.shared.handshakes.clone().acquire_owned().await else{break};
+                    let Some(incoming)=endpoint.accept().await else{break};
+                    let network=acceptor.clone();
+                    tokio::spawn(async move {
+                        let _=quic::accept(&network,incoming).await;
+                        drop(permit);
+                    });
+                }
+            });
+        }
+        Ok(network)
+    }
+
+    pub fn local_peer_id(&self)->&PeerId{
+        &self.shared.peer_id
+    }
+
+    pub fn network_id(&self)->&str{
+        &self.shared.config.network_id
+    }
+
+    /// Chain height to announce to new peers; the node updates it as the chain grows
+    pub fn set_height(&self,height:u64){
+        self.shared.height.store(height,Ordering::Relaxed);
+    }
+
+    /// Address the listener is bound to
+    pub fn local_addr(&self)->SocketAddr{
+        self.shared.local_addr
+    }
+
+    /// Public address peers can reach the listener at, when a port mapping provides one
+    pub fn external_addr(&self)->Option<SocketAddr>{
+        *self.shared.external_addr.lock().expect("external address lock poisoned")
+    }
+
+    /// Record the mapped public address; its port is announced to peers from now on
+    pub fn set_external_addr(&self,addr:Option<SocketAddr>){
+        *self.shared.external_addr.lock().expect("external address lock poisoned")=addr;
+    }
+
+    /// Connect to the node at `addr` and return its peer id
+    pub async fn dial(&self,addr:SocketAddr)->Result<PeerId,NetworkError>{
+        let dialed=match TcpStream::connect(addr).await{
+            Ok(stream)=>self.establish(stream,addr,true,Link::Tcp).await,
+            Err(e)=>Err(e.into()),
+        };
+        if let Err(error)=&dialed{
+            let _=self.shared.events.send(NetworkEvent::DialFailed{addr,error:error.clone()});
+        }
+        dialed
+    }
+
+    /// `dial` over QUIC; fails unless `NetworkConfig::quic` is set
+    pub async fn dial_quic(&self,addr:SocketAddr)->Result<PeerId,NetworkError>{
+        let endpoint=self.shared.quic.as_ref().ok_or_else(|| NetworkError::Quic("disabled".to_string()))?;
+        quic::dial(self,endpoint,addr).await
+    }
+
+    /// What peers' traffic showed about them, for the PoI metrics (see `observer`)
+    pub fn observer(&self)->&PeerObserver{
+        &self.shared.observer
+    }
+
+    /// Bytes sent and received per topic, and time spent under the bandwidth caps
+    pub fn bandwidth(&self)->BandwidthStats{
+        self.shared.bandwidth.stats()
+    }
+
+    /// Hand the current QUIC measurements of every QUIC peer to the observer
+    pub fn sample_links(&self){
+        let samples:Vec<(PeerId,LinkStats)>={
+            let connections=self.shared.connections.lock().expect("connections lock poisoned");
+            connections
+            .iter()
+            .filter_map(|(peer,c)| match &c.link{
+                Link::Quic(connection)=>Some((peer.clone(),quic::link_stats(connection))),
+                Link::Tcp|Link::Relay=>None,
+            })
+            .collect()
+        };
+        for (peer,stats) in samples{
+            self.shared.observer.record_link(&peer,stats);
+        }
+    }
+
+    /// Round-trip time and packet loss QUIC measured on the link to `peer`; `None` unless
+    /// it is connected over QUIC
+    pub fn link_stats(&self,peer:&PeerId)->Option<LinkStats>{
+        let connections=self.shared.connections.lock().expect("connections lock poisoned");
+        match &connections.get(peer)?.link{
+            Link::Quic(connection)=>Some(quic::link_stats(connection)),
+            Link::Tcp|Link::Relay=>None,
+        }
+    }
+
+    /// Close the connection to `peer`, if any
+    pub fn disconnect(&self,peer:&PeerId){
+        self.disconnect_with(peer,DisconnectReason::Requested);
+    }
+
+    /// Close the connection to `peer`, telling it why
+    fn disconnect_with(&self,peer:&PeerId,reason:DisconnectReason){
+        let removed=self.shared.connections.lock().expect("connections lock poisoned").remove(peer);
+        if let Some(connection)=removed{
+            // the writer sends this, then stops as the queue is closed
+            let _=connection.sender.try_send(Frame::Disconnect(reason.clone()));
+            connection.reader.abort();
+            let _=self.shared.events.send(NetworkEvent::PeerDisconnected{peer:peer.clone(),reason:Some(reason)});
+        }
+    }
+
+    pub fn peers(&self)->Vec<PeerInfo>{
+        let connections=self.shared.connections.lock().expect("connections lock poisoned");
+        let mut peers:Vec<PeerInfo>=connections.values().map(|c| c.info.clone()).collect();
+        peers.sort_by(|a,b| a.peer_id.cmp(&b.peer_id));
+        peers
+    }
+
+    pub fn is_connected(&self,peer:&PeerId)->bool{
+        self.shared.connections.lock().expect("connections lock poisoned").contains_key(peer)
+    }
+
+    /// Penalize `peer` for `misbehavior`, disconnecting it if that gets it banned
+    pub fn report(&self,peer:&PeerId,misbehavior:Misbehavior){
+        let banned={
+            let mut reputation=self.shared.reputation.lock().expect("reputation lock poisoned");
+            let banned=reputation.report(peer,misbehavior,unix_now());
+            if let Err(error)=reputation.save(){
+                self.emit(NetworkEvent::SaveFailed{error});
+            }
+            banned
+        };
+        if banned.is_some(){
+            self.disconnect_with(peer,DisconnectReason::Banned);
+        }
+    }
+
+    /// Credit `peer` for useful behaviour (e.g. a valid block it sent first)
+    pub fn reward(&self,peer:&PeerId,points:i32){
+        self.shared.reputation.lock().expect("reputation lock poisoned").reward(peer,points,unix_now());
+    }
+
+    pub fn peer_score(&self,peer:&PeerId)->i32{
+        self.shared.reputation.lock().expect("reputation lock poisoned").score(peer,unix_now())
+    }
+
+    pub fn is_banned(&self,peer:&PeerId)->bool{
+        self.shared.reputation.lock().expect("reputation lock poisoned").is_banned(peer,unix_now())
+    }
+
+    /// Scores and bans of every peer seen, for operators
+    pub fn peer_standings(&self)->Vec<PeerStanding>{
+        self.shared.reputation.lock().expect("reputation lock poisoned").peers(unix_now())
+    }
+
+    pub fn banned_peers(&self)->Vec<PeerStanding>{
+        self.shared.reputation.lock().expect("reputation lock poisoned").banned(unix_now())
+    }
+
+    /// Ban `peer` for `seconds` and disconnect it
+    pub fn ban(&self,peer:&PeerId,seconds:u64)->Result<(),NetworkError>{
+        {
+            let mut reputation=self.shared.reputation.lock().expect("reputation lock poisoned");
+            let now=unix_now();
+            reputation.ban(peer,now.saturating_add(seconds),now);
+            reputation.save()?;
+        }
+        self.disconnect_with(peer,DisconnectReason::Banned);
+        Ok(())
+    }
+
+    pub fn unban(&self,peer:&PeerId)->Result<(),NetworkError>{
+        let mut reputation=self.shared.reputation.lock().expect("reputation lock poisoned");
+        reputation.unban(peer);
+        reputation.save()
+    }
+
+    /// Receive every message arriving on `topic` from now on
+    pub fn subscribe(&self,topic:Topic)->mpsc::UnboundedReceiver<InboundMessage>{
+        let (sender,receiver)=mpsc::unbounded_channel();
+        self.shared.subscribers.lock().expect("subscribers lock poisoned").entry(topic).or_default().push(sender);
+        receiver
+    }
+
+    /// Peer connect / disconnect notifications and background failures
+    pub fn events(&self)->broadcast::Receiver<NetworkEvent>{
+        self.shared.events.subscribe()
+    }
+
+    fn emit(&self,event:NetworkEvent){
+        let _=self.shared.events.send(event);
+    }
+
+    /// Queue `payload` on `topic` for one peer
+    pub fn send(&self,peer:&PeerId,topic:Topic,payload:Vec<u8>)->Result<(),NetworkError>{
+        let connections=self.shared.connections.lock().expect("connections lock poisoned");
+        let connection=connections.get(peer).ok_or_else(|| NetworkError::NotConnected(peer.clone()))?;
+        connection
+        .sender
+        .try_send(Frame::Message{topic,payload})
+        .map_err(|_| NetworkError::PeerBusy(peer.clone()))
+    }
+
+    /// Queue `message` on its topic for one peer
+    pub fn send_message<M:WireMessage>(&self,peer:&PeerId,message:&M)->Result<(),NetworkError>{
+        self.send(peer,M::TOPIC,message.to_payload())
+    }
+
+    /// `broadcast_except` a `WireMessage` on its topic
+    pub fn broadcast_message<M:WireMessage>(&self,message:&M,skip:Option<&PeerId>)->usize{
+        self.broadcast_except(M::TOPIC,message.to_payload(),skip)
+    }
+
+    /// Queue `payload` on `topic` for every connected peer; returns how many accepted it
+    pub fn broadcast(&self,topic:Topic,payload:Vec<u8>)->usize{
+        self.broadcast_except(topic,payload,None)
+    }
+
+    /// `broadcast` to every peer but `skip` (e.g. the peer a relayed message came from)
+    pub fn broadcast_except(&self,topic:Topic,payload:Vec<u8>,skip:Option<&PeerId>)->usize{
+        let connections=self.shared.connections.lock().expect("connections lock poisoned");
+        connections
+        .iter()
+        .filter(|(peer,_)| Some(*peer)!=skip)
+        .filter(|(_,c)| c.sender.try_send(Frame::Message{topic,payload:payload.clone()}).is_ok())
+        .count()
+    }
+
+    /// Run the Noise handshake on a fresh connection, trade verdicts on each other's `Hello`,
+    /// register the peer and start its reader and writer tasks. `addr` is the remote end of
+    /// `stream`: the peer's, or the relay's when the link is `Link::Relay`
+    async fn establish<S>(&self,stream:S,addr:SocketAddr,outbound:bool,link:Link)->Result<PeerId,NetworkError>
+    where
+        S:AsyncRead+AsyncWrite+Send+'static,
+    {
+        let shared=&self.shared;
+        let (mut reader,mut writer)=tokio::io::split(stream);
+        let listen_port=self.external_addr().map_or(shared.local_addr.port(),|a| a.port());
+        let hello=Hello{
+            protocol_version:PROTOCOL_VERSION,
+            min_protocol_version:MIN_PROTOCOL_VERSION,
+            network_id:shared.config.network_id.clone(),
+            genesis_hash:shared.config.genesis_hash.clone(),
+            height:shared.height.load(Ordering::Relaxed),
+            capabilities:shared.config.capabilities.clone(),
+            public_key:shared.identity.public_key().to_vec(),
+            signature:shared.noise.sign_with(&shared.identity),
+            listen_port,
+        };
+        let handshake=async {
+            let (remote,cipher)=noise::handshake(&mut reader,&mut writer,&shared.noise,shared.config.network_key.as_ref(),outbound,&hello).await?;
+            let mut reader=SecureReader::new(reader,cipher.clone());
+            let mut writer=SecureWriter::new(writer,cipher);
+            let peer_id=PeerId::from_public_key(&remote.public_key);
+            if let Err(e)=self.check_hello(&remote,&peer_id){
+                let _=writer.write_frame(&Frame::Disconnect(e.disconnect_reason())).await;
+                // read the peer's verdict first: closing with it unread resets the connection,
+                // which can beat our reason to the peer
+                let _=tokio::time::timeout(REFUSAL_LINGER,reader.read_frame()).await;
+                return Err(e)
+            }
+            writer.write_frame(&Frame::Ready).await?;
+            match reader.read_frame().await?{
+                Frame::Ready=>Ok((reader,writer,remote,peer_id)),
+                Frame::Disconnect(reason)=>Err(NetworkError::PeerRefused(reason)),
+                _=>Err(NetworkError::Handshake("expected ready".to_string())),
+            }
+        };
+        let (mut reader,mut writer,remote,peer_id)=tokio::time::timeout(shared.config.handshake_timeout,handshake).await
+            .map_err(|_| NetworkError::Handshake("timed out".to_string()))??;
+        let info=PeerInfo{
+            peer_id:peer_id.clone(),
+            addr,
+            listen_addr:(remote.listen_port!=0 && !matches!(link,Link::Relay)).then(|| SocketAddr::new(addr.ip(),remote.listen_port)),
+            outbound,
+            protocol_version:remote.protocol_version,
+            height:remote.height,
+            capabilities:remote.capabilities.into_iter().filter(|c| shared.config.capabilities.contains(c)).collect(),
+            transport:link.transport(),
+        };
+        let codec=compression::negotiate(&info.capabilities);
+
+        let (sender,mut outgoing)=mpsc::channel::<Frame>(PEER_QUEUE);
+        let id={
+            let mut next=shared.next_connection.lock().expect("connection counter poisoned");
+            *next+=1;
+            *next
+        };
+        {
+            let mut connections=shared.connections.lock().expect("connections lock poisoned");
+            if connections.contains_key(&peer_id){
+                return Err(NetworkError::AlreadyConnected(peer_id))
+            }
+            if connections.len()>=shared.config.max_peers{
+                return Err(NetworkError::PeerLimit)
+            }
+            let network=self.clone();
+            let from=peer_id.clone();
+            let reader=tokio::spawn(async move {
+                let mut reason=None;
+                while let Ok(frame)=reader.read_frame().await{
+                    network.shared.bandwidth.receive(&frame).await;
+                    match frame{
+                        Frame::Message{topic,payload}=>network.receive(&from,topic,payload),
+                        Frame::Compressed{topic,codec,payload}=>{
+                            let max=network.shared.config.rate_limits.max_payload(topic);
+                            match compression::decompress(codec,&payload,max){
+                                Ok(payload)=>network.receive(&from,topic,payload),
+                                Err(_)=>network.report(&from,Misbehavior::BadMessage),
+                            }
+                        }
+                        Frame::Disconnect(given)=>{
+                            reason=Some(given);
+                            break
+                        }
+                        _=>{}
+                    }
+                }
+                network.connection_closed(&from,id,reason);
+            });
+            let limiter=PeerLimiter::new(shared.config.rate_limits.clone(),Instant::now());
+            connections.insert(peer_id.clone(),Connection{id,info,sender,reader:reader.abort_handle(),limiter,link});
+        }
+        // ends once the connection is dropped from the table
+        let network=self.clone();
+        tokio::spawn(async move {
+            let compression=&network.shared.config.compression;
+            while let Some(frame)=outgoing.recv().await{
+                let frame=compression::outgoing(frame,codec,compression);
+                network.shared.bandwidth.send(&frame).await;
+                if writer.write_frame(&frame).await.is_err(){
+                    break
+                }
+            }
+        });
+        let _=shared.events.send(NetworkEvent::PeerConnected{peer:peer_id.clone(),addr,outbound});
+        Ok(peer_id)
+    }
+
+    /// Our verdict on a peer's `Hello`
+    fn check_hello(&self,remote:&Hello,peer_id:&PeerId)->Result<(),NetworkError>{
+        let config=&self.shared.config;
+        if remote.protocol_version<MIN_PROTOCOL_VERSION || PROTOCOL_VERSION<remote.min_protocol_version{
+            return Err(NetworkError::Refused(DisconnectReason::IncompatibleVersion{
+                version:PROTOCOL_VERSION,
+                min_version:MIN_PROTOCOL_VERSION,
+            }))
+        }
+        if remote.network_id!=config.network_id{
+            return Err(NetworkError::Refused(DisconnectReason::NetworkMismatch{network_id:config.network_id.clone()}))
+        }
+        if remote.genesis_hash!=config.genesis_hash{
+            return Err(NetworkError::Refused(DisconnectReason::GenesisMismatch{genesis_hash:config.genesis_hash.clone()}))
+        }
+        if peer_id==&self.shared.peer_id{
+            return Err(NetworkError::SelfConnection)
+        }
+        if self.is_banned(peer_id){
+            return Err(NetworkError::Banned(peer_id.clone()))
+        }
+        let connections=self.shared.connections.lock().expect("connections lock poisoned");
+        if connections.contains_key(peer_id){
+            return Err(NetworkError::AlreadyConnected(peer_id.clone()))
+        }
+        if connections.len()>=config.max_peers{
+            return Err(NetworkError::PeerLimit)
+        }
+        Ok(())
+    }
+
+    /// Check an inbound message against `peer`'s limits, reporting it as spam if over
+    fn admit(&self,peer:&PeerId,topic:Topic,len:usize)->bool{
+        let admitted={
+            let mut connections=self.shared.connections.lock().expect("connections lock poisoned");
+            connections.get_mut(peer).is_some_and(|c| c.limiter.admit(topic,len,Instant::now()))
+        };
+        if !admitted{
+            self.report(peer,Misbehavior::Spam);
+        }
+        admitted
+    }
+
+    /// Spend one of `peer`'s `class` requests; `false` (and the peer reported as spam) when
+    /// it is over its limit and the request should be ignored
+    pub fn allow(&self,peer:&PeerId,class:RateClass)->bool{
+        let allowed={
+            let mut connections=self.shared.connections.lock().expect("connections lock poisoned");
+            connections.get_mut(peer).is_some_and(|c| c.limiter.allow(class,Instant::now()))
+        };
+        if !allowed{
+            self.report(peer,Misbehavior::Spam);
+        }
+        allowed
+    }
+
+    /// Dispatch an inbound message if it is within `peer`'s limits
+    fn receive(&self,peer:&PeerId,topic:Topic,payload:Vec<u8>){
+        if self.admit(peer,topic,payload.len()){
+            self.dispatch(InboundMessage{from:peer.clone(),topic,payload});
+        }
+    }
+
+    /// Hand an inbound message to every live subscriber of its topic
+    fn dispatch(&self,message:InboundMessage){
+        let mut subscribers=self.shared.subscribers.lock().expect("subscribers lock poisoned");
+        if let Some(senders)=subscribers.get_mut(&message.topic){
+            senders.retain(|sender| sender.send(message.clone()).is_ok());
+        }
+    }
+
+    /// Reader of connection `id` stopped; forget it unless it was already replaced
+    fn connection_closed(&self,peer:&PeerId,id:u64,reason:Option<DisconnectReason>){
+        let mut connections=self.shared.connections.lock().expect("connections lock poisoned");
+        if connections.get(peer).is_some_and(|c| c.id==id){
+            connections.remove(peer);
+            drop(connections);
+            let _=self.shared.events.send(NetworkEvent::PeerDisconnected{peer:peer.clone(),reason});
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use std::time::Duration;
+    use tokio::time::timeout;
+
+    fn local_config()->NetworkConfig{
+        NetworkConfig{listen_addr:SocketAddr::from(([127,0,0,1],0)),..NetworkConfig::default()}
+    }
+
+    /// A `Hello` matching `network`'s settings
+    fn hello_of(network:&Network)->Hello{
+        let config=&network.shared.config;
+        Hello{
+            protocol_version:PROTOCOL_VERSION,
+            min_protocol_version:MIN_PROTOCOL_VERSION,
+            network_id:config.network_id.clone(),
+            genesis_hash:config.genesis_hash.clone(),
+            height:0,
+            capabilities:config.capabilities.clone(),
+            public_key:Vec::new(),
+            signature:Vec::new(),
+            listen_port:0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_and_exchange_messages(){
+        let a=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let b=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let mut b_txs=b.subscribe(Topic::Transactions);
+        let mut b_events=b.events();
+
+        let peer=a.dial(b.local_addr()).await.unwrap();
+        assert_eq!(&peer,b.local_peer_id());
+        let connected=timeout(Duration::from_secs(5),b_events.recv()).await.unwrap().unwrap();
+        assert!(matches!(connected,NetworkEvent::PeerConnected{ref peer,outbound:false,..} if peer==a.local_peer_id()));
+        assert_eq!(b.peers()[0].listen_addr,Some(a.local_addr()));
+
+        assert_eq!(a.broadcast(Topic::Transactions,b"tx".to_vec()),1);
+        a.send(&peer,Topic::Blocks,b"block".to_vec()).unwrap();
+        let received=timeout(Duration::from_secs(5),b_txs.recv()).await.unwrap().unwrap();
+        assert_eq!(received,InboundMessage{from:a.local_peer_id().clone(),topic:Topic::Transactions,payload:b"tx".to_vec()});
+
+        // one connection per peer, never to ourselves
+        assert!(matches!(a.dial(b.local_addr()).await,Err(NetworkError::AlreadyConnected(_))));
+        assert_eq!(a.dial(a.local_addr()).await,Err(NetworkError::SelfConnection));
+
+        a.disconnect(&peer);
+        let closed=timeout(Duration::from_secs(5),b_events.recv()).await.unwrap().unwrap();
+        assert_eq!(closed,NetworkEvent::PeerDisconnected{peer:a.local_peer_id().clone(),reason:Some(DisconnectReason::Requested)});
+        assert!(a.peers().is_empty());
+        assert_eq!(a.send(&peer,Topic::Blocks,vec![]),Err(NetworkError::NotConnected(peer)));
+    }
+
+    #[tokio::test]
+    async fn test_foreign_network_refused(){
+        let a=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let other=NetworkConfig{network_id:"devnet".to_string(),..local_config()};
+        let b=Network::start(NodeIdentity::generate(),other).await.unwrap();
+        let refused=a.dial(b.local_addr()).await.unwrap_err();
+        assert_eq!(refused,NetworkError::Refused(DisconnectReason::NetworkMismatch{network_id:"netchain".to_string()}));
+        assert!(a.peers().is_empty());
+
+        let forked=NetworkConfig{genesis_hash:"ab".repeat(32),..local_config()};
+        let c=Network::start(NodeIdentity::generate(),forked).await.unwrap();
+        let refused=a.dial(c.local_addr()).await.unwrap_err();
+        assert!(matches!(refused,NetworkError::Refused(DisconnectReason::GenesisMismatch{..})));
+        assert!(refused.to_string().contains("different genesis"));
+    }
+
+    #[tokio::test]
+    async fn test_silent_dialers_time_out_and_queue_behind_the_handshake_cap(){
+        let bounded=NetworkConfig{handshake_timeout:Duration::from_millis(300),max_pending_handshakes:1,..local_config()};
+        let a=Network::start(NodeIdentity::generate(),bounded).await.unwrap();
+        let b=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let mut silent=TcpStream::connect(a.local_addr()).await.unwrap();
+
+        // b waits for the silent dialer's permit, then connects
+        let started=Instant::now();
+        assert_eq!(&b.dial(a.local_addr()).await.unwrap(),a.local_peer_id());
+        assert!(started.elapsed()>=Duration::from_millis(100));
+        let mut buf=[0u8;64];
+        let closed=timeout(Duration::from_secs(5),tokio::io::AsyncReadExt::read(&mut silent,&mut buf)).await.unwrap();
+        assert!(matches!(closed,Ok(0) | Err(_)));
+    }
+
+    #[tokio::test]
+    async fn test_handshake_negotiates_and_reports_refusals(){
+        let a=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        a.set_height(42);
+        let sync_only=NetworkConfig{capabilities:vec![wire::CAP_SYNC.to_string(),"future/9".to_string()],..local_config()};
+        let b=Network::start(NodeIdentity::generate(),sync_only).await.unwrap();
+        let peer=b.dial(a.local_addr()).await.unwrap();
+        let info=b.peers().into_iter().find(|p| p.peer_id==peer).unwrap();
+        assert_eq!((info.protocol_version,info.height),(PROTOCOL_VERSION,42));
+        assert_eq!(info.capabilities,vec![wire::CAP_SYNC.to_string()]);
+
+        // only the full side refuses; the dialer learns why
+        let full=NetworkConfig{max_peers:0,..local_config()};
+        let c=Network::start(NodeIdentity::generate(),full).await.unwrap();
+        assert_eq!(a.dial(c.local_addr()).await,Err(NetworkError::PeerRefused(DisconnectReason::TooManyPeers)));
+
+        let old=Hello{protocol_version:MIN_PROTOCOL_VERSION-1,..hello_of(&a)};
+        assert!(matches!(
+            a.check_hello(&old,&NodeIdentity::generate().peer_id()),
+            Err(NetworkError::Refused(DisconnectReason::IncompatibleVersion{..}))
+        ));
+        let too_new=Hello{min_protocol_version:PROTOCOL_VERSION+1,..hello_of(&a)};
+        assert!(a.check_hello(&too_new,&NodeIdentity::generate().peer_id()).is_err());
+        assert!(a.check_hello(&hello_of(&a),&NodeIdentity::generate().peer_id()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_misbehaving_peer_banned(){
+        let a=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let b=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let peer=a.dial(b.local_addr()).await.unwrap();
+        a.report(&peer,Misbehavior::InvalidBlock);
+        assert!(a.is_connected(&peer));
+        a.report(&peer,Misbehavior::InvalidBlock);
+        assert!(!a.is_connected(&peer));
+        assert_eq!(a.banned_peers()[0].peer_id,peer);
+        assert_eq!(a.dial(b.local_addr()).await,Err(NetworkError::Banned(peer.clone())));
+
+        a.unban(&peer).unwrap();
+        assert_eq!(a.peer_standings()[0].score,0);
+        timeout(Duration::from_secs(5),async {
+            // b may still be tearing down the old connection
+            while a.dial(b.local_addr()).await.is_err(){
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reputation_save_failure_published(){
+        let path=std::env::temp_dir().join("netchain-missing-dir").join("reputation.json");
+        let reputation=ReputationConfig{path:Some(path),..ReputationConfig::default()};
+        let a=Network::start(NodeIdentity::generate(),NetworkConfig{reputation,..local_config()}).await.unwrap();
+        let mut events=a.events();
+        a.report(&NodeIdentity::generate().peer_id(),Misbehavior::InvalidBlock);
+        let event=timeout(Duration::from_secs(5),events.recv()).await.unwrap().unwrap();
+        assert!(matches!(event,NetworkEvent::SaveFailed{error:NetworkError::Io(_)}));
+    }
+
+    #[tokio::test]
+    async fn test_flooding_peer_dropped_and_banned(){
+        let limits=RateLimitConfig{transactions:ratelimit::RateLimit::new(0.0,3.0),..RateLimitConfig::default()};
+        let a=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let b=Network::start(NodeIdentity::generate(),NetworkConfig{rate_limits:limits,..local_config()}).await.unwrap();
+        let mut b_txs=b.subscribe(Topic::Transactions);
+        let mut b_events=b.events();
+        a.dial(b.local_addr()).await.unwrap();
+        let peer=a.local_peer_id().clone();
+
+        // oversized payloads never reach subscribers
+        a.broadcast(Topic::Transactions,vec![0;200*1024]);
+        for i in 0..10u8{
+            a.broadcast(Topic::Transactions,vec![i]);
+        }
+        for i in 0..3u8{
+            let received=timeout(Duration::from_secs(5),b_txs.recv()).await.unwrap().unwrap();
+            assert_eq!(received.payload,vec![i]);
+        }
+        timeout(Duration::from_secs(5),async {
+            while !matches!(b_events.recv().await,Ok(NetworkEvent::PeerDisconnected{..})){}
+        })
+        .await
+        .unwrap();
+        assert!(b.is_banned(&peer));
+        assert!(b_txs.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_upload_cap_paces_traffic(){
+        use rand::RngCore;
+        let capped=BandwidthConfig{upload:Some(ratelimit::RateLimit::new(1_000_000.0,100_000.0)),download:None};
+        let a=Network::start(NodeIdentity::generate(),NetworkConfig{bandwidth:capped,..local_config()}).await.unwrap();
+        let b=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let mut b_blocks=b.subscribe(Topic::Blocks);
+        let peer=a.dial(b.local_addr()).await.unwrap();
+
+        // incompressible, so the cap sees the full size
+        let mut payload=vec![0u8;100_000];
+        rand::thread_rng().fill_bytes(&mut payload);
+        let started=Instant::now();
+        for _ in 0..6{
+            a.send(&peer,Topic::Blocks,payload.clone()).unwrap();
+        }
+        for _ in 0..6{
+            timeout(Duration::from_secs(5),b_blocks.recv()).await.unwrap().unwrap();
+        }
+        // the burst covers one message, the other 500 kB go at 1 MB/s
+        assert!(started.elapsed()>=Duration::from_millis(400));
+        let sent=a.bandwidth().upload;
+        assert_eq!((sent.bytes[&Topic::Blocks],sent.limit_bps),(600_000,Some(1_000_000)));
+        assert!(sent.throttled_ms>0);
+        assert_eq!(b.bandwidth().download.total_bytes(),600_000);
+    }
+
+    #[tokio::test]
+    async fn test_quic_connection_measures_link(){
+        let quic=NetworkConfig{quic:true,..local_config()};
+        let a=Network::start(NodeIdentity::generate(),quic.clone()).await.unwrap();
+        let b=Network::start(NodeIdentity::generate(),quic).await.unwrap();
+        let mut b_blocks=b.subscribe(Topic::Blocks);
+
+        let peer=a.dial_quic(b.local_addr()).await.unwrap();
+        assert_eq!(&peer,b.local_peer_id());
+        let info=&a.peers()[0];
+        assert_eq!((info.transport,info.listen_addr),(Transport::Quic,Some(b.local_addr())));
+        a.send(&peer,Topic::Blocks,b"block".to_vec()).unwrap();
+        let received=timeout(Duration::from_secs(5),b_blocks.recv()).await.unwrap().unwrap();
+        assert_eq!(received.payload,b"block".to_vec());
+
+        let stats=a.link_stats(&peer).unwrap();
+        assert!(stats.sent_packets>0 && stats.rtt>Duration::ZERO);
+        assert_eq!(b.peers()[0].transport,Transport::Quic);
+
+        // TCP links have no QUIC measurements, and QUIC stays off unless configured
+        let c=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let tcp=c.dial(a.local_addr()).await.unwrap();
+        assert_eq!(c.link_stats(&tcp),None);
+        assert!(matches!(c.dial_quic(b.local_addr()).await,Err(NetworkError::Quic(_))));
+    }
+
+    #[tokio::test]
+    async fn test_large_payloads_compressed_when_negotiated(){
+        let a=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let b=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let plain=NetworkConfig{capabilities:vec![wire::CAP_GOSSIP.to_string(),wire::CAP_SYNC.to_string()],..local_config()};
+        let c=Network::start(NodeIdentity::generate(),plain).await.unwrap();
+        let mut b_sync=b.subscribe(Topic::Sync);
+        let mut c_sync=c.subscribe(Topic::Sync);
+        let to_b=a.dial(b.local_addr()).await.unwrap();
+        let to_c=a.dial(c.local_addr()).await.unwrap();
+        assert_eq!(compression::negotiate(&a.peers().iter().find(|p| p.peer_id==to_b).unwrap().capabilities),Some(compression::Codec::Zstd));
+        assert_eq!(compression::negotiate(&c.peers()[0].capabilities),None);
+
+        let batch=b"header ".repeat(100_000);
+        a.send(&to_b,Topic::Sync,batch.clone()).unwrap();
+        a.send(&to_c,Topic::Sync,batch.clone()).unwrap();
+        for sync in [&mut b_sync,&mut c_sync]{
+            let received=timeout(Duration::from_secs(5),sync.recv()).await.unwrap().unwrap();
+            assert_eq!(received.payload,batch);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_private_network_rejects_outsiders(){
+        let private=NetworkConfig{network_key:Some([42u8;32]),..local_config()};
+        let a=Network::start(NodeIdentity::generate(),private.clone()).await.unwrap();
+        let b=Network::start(NodeIdentity::generate(),private).await.unwrap();
+        let outsider=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        a.dial(b.local_addr()).await.unwrap();
+        assert!(matches!(outsider.dial(a.local_addr()).await,Err(NetworkError::Io(_) | NetworkError::Handshake(_))));
+        assert!(matches!(a.dial(outsider.local_addr()).await,Err(NetworkError::Handshake(_))));
+        assert_eq!(a.peers().len(),1);
+        assert!(outsider.peers().is_empty());
+    }
+}
@@ -0,0 +1,152 @@
+// src/network/bandwidth.rs
+
+//! Node-wide bandwidth caps
+//! - `BandwidthConfig` caps upload and download in bytes per second, summed over all peers
+//!   and topics, gossip and sync alike; `None` leaves a direction unlimited
+//! - A connection's writer waits for upload budget before sending a message and its reader
+//!   for download budget after receiving one. A reader that waits stops draining the socket,
+//!   so TCP / QUIC flow control slows the sender down
+//! - Budgets may run into debt, so a message bigger than the burst still goes out, followed
+//!   by a matching pause
+//! - Bytes moved per topic and time spent throttled are counted live (`Network::bandwidth`)
+
+use super::Topic;
+use super::ratelimit::{RateLimit,TokenBucket};
+use super::wire::Frame;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration,Instant};
+
+#[derive(Debug,Clone,Default)]
+pub struct BandwidthConfig{
+    /// Bytes per second sent to all peers together
+    pub upload:Option<RateLimit>,
+    /// Bytes per second received from all peers together
+    pub download:Option<RateLimit>,
+}
+
+/// Traffic in one direction since the node started
+#[derive(Debug,Clone,Default,PartialEq,Eq,Serialize)]
+pub struct TrafficStats{
+    /// Message payload bytes per topic, as they went over the wire (compressed or not)
+    pub bytes:BTreeMap<Topic,u64>,
+    /// Time connections spent waiting for budget
+    pub throttled_ms:u64,
+    /// Configured cap in bytes per second
+    pub limit_bps:Option<u64>,
+}
+
+impl TrafficStats{
+    pub fn total_bytes(&self)->u64{
+        self.bytes.values().sum()
+    }
+}
+
+#[derive(Debug,Clone,Default,PartialEq,Eq,Serialize)]
+pub struct BandwidthStats{
+    pub upload:TrafficStats,
+    pub download:TrafficStats,
+}
+
+/// Topic and size of the traffic `frame` carries; handshake frames aren't counted
+pub fn traffic(frame:&Frame)->Option<(Topic,usize)>{
+    match frame{
+        Frame::Message{topic,payload}|Frame::Compressed{topic,payload,..}=>Some((*topic,payload.len())),
+        Frame::Hello(_)|Frame::Ready|Frame::Disconnect(_)=>None,
+    }
+}
+
+/// Budget and counters of one direction
+struct Throttle{
+    limit:Option<RateLimit>,
+    bucket:Option<TokenBucket>,
+    bytes:BTreeMap<Topic,u64>,
+    throttled:Duration,
+}
+
+impl Throttle{
+    fn new(limit:Option<RateLimit>,now:Instant)->Self{
+        Throttle{limit,bucket:limit.map(|limit| TokenBucket::new(limit,now)),bytes:BTreeMap::new(),throttled:Duration::ZERO}
+    }
+
+    /// Count `len` bytes on `topic`; returns how long to wait to stay under the cap
+    fn charge(&mut self,topic:Topic,len:usize,now:Instant)->Duration{
+        *self.bytes.entry(topic).or_default()+=len as u64;
+        let wait=self.bucket.as_mut().map_or(Duration::ZERO,|bucket| bucket.reserve(len as f64,now));
+        self.throttled=self.throttled.saturating_add(wait);
+        wait
+    }
+
+    fn stats(&self)->TrafficStats{
+        TrafficStats{
+            bytes:self.bytes.clone(),
+            throttled_ms:self.throttled.as_millis() as u64,
+            limit_bps:self.limit.map(|limit| limit.per_second as u64),
+        }
+    }
+}
+
+/// The node's upload and download budgets, shared by every connection
+pub struct Bandwidth{
+    upload:Mutex<Throttle>,
+    download:Mutex<Throttle>,
+}
+
+impl Bandwidth{
+    pub fn new(config:&BandwidthConfig,now:Instant)->Self{
+        Bandwidth{
+            upload:Mutex::new(Throttle::new(config.upload,now)),
+            download:Mutex::new(Throttle::new(config.download,now)),
+        }
+    }
+
+    /// Count `frame` as sent, waiting first if the upload cap requires it
+    pub async fn send(&self,frame:&Frame){
+        if let Some((topic,len))=traffic(frame){
+            let wait=self.upload.lock().expect("bandwidth lock poisoned").charge(topic,len,Instant::now());
+            if !wait.is_zero(){
+                tokio::time::sleep(wait).await;
+            }
+        }
+    }
+
+    /// Count `frame` as received, waiting first if the download cap requires it
+    pub async fn receive(&self,frame:&Frame){
+        if let Some((topic,len))=traffic(frame){
+            let wait=self.download.lock().expect("bandwidth lock poisoned").charge(topic,len,Instant::now());
+            if !wait.is_zero(){
+                tokio::time::sleep(wait).await;
+            }
+        }
+    }
+
+    pub fn stats(&self)->BandwidthStats{
+        BandwidthStats{
+            upload:self.upload.lock().expect("bandwidth lock poisoned").stats(),
+            download:self.download.lock().expect("bandwidth lock poisoned").stats(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_caps_pace_traffic_and_count_it(){
+        let now=Instant::now();
+        let mut throttle=Throttle::new(Some(RateLimit::new(1_000.0,1_000.0)),now);
+        assert_eq!(throttle.charge(Topic::Blocks,1_000,now),Duration::ZERO);
+        // over the burst: pay the debt back at the sustained rate
+        assert_eq!(throttle.charge(Topic::Sync,500,now),Duration::from_millis(500));
+        assert_eq!(throttle.charge(Topic::Sync,500,now+Duration::from_millis(500)),Duration::from_millis(500));
+        let stats=throttle.stats();
+        assert_eq!(stats.bytes,BTreeMap::from([(Topic::Blocks,1_000),(Topic::Sync,1_000)]));
+        assert_eq!((stats.total_bytes(),stats.throttled_ms,stats.limit_bps),(2_000,1_000,Some(1_000)));
+
+        let mut unlimited=Throttle::new(None,now);
+        assert_eq!(unlimited.charge(Topic::Blocks,usize::MAX/2,now),Duration::ZERO);
+        assert_eq!(traffic(&Frame::Ready),None);
+    }
+}
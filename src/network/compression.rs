@@ -0,0 +1,138 @@
+// src/network/compression.rs
+
+//! Payload compression
+//! - Nodes announce the codecs they can decode as capabilities ("zstd/1", "snappy/1"); a
+//!   connection uses the first of `CODECS` both sides list, or none
+//! - Payloads of at least `CompressionConfig::min_bytes` (in practice full blocks, proposals
+//!   and sync batches) travel as `Frame::Compressed` when that makes them smaller; small
+//!   messages aren't worth the CPU
+//! - Decompressed sizes are capped by the topic's payload limit (see `ratelimit`), so a
+//!   small frame can't inflate into an arbitrary allocation
+
+use super::wire::{CAP_SNAPPY,CAP_ZSTD,Frame};
+use serde::{Deserialize,Serialize};
+use thiserror::Error;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Codec{
+    Zstd,
+    Snappy,
+}
+
+/// Codecs in order of preference: zstd compresses better, snappy is cheaper
+pub const CODECS:[Codec;2]=[Codec::Zstd,Codec::Snappy];
+
+impl Codec{
+    /// Capability announcing that we decode this codec
+    pub fn capability(self)->&'static str{
+        match self{
+            Codec::Zstd=>CAP_ZSTD,
+            Codec::Snappy=>CAP_SNAPPY,
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum CompressionError{
+    #[error("decompressed payload exceeds {0} bytes")]
+    TooLarge(usize),
+    #[error("corrupt {0:?} payload: {1}")]
+    Corrupt(Codec,String),
+}
+
+#[derive(Debug,Clone)]
+pub struct CompressionConfig{
+    /// Smallest payload worth compressing
+    pub min_bytes:usize,
+    pub zstd_level:i32,
+}
+
+impl Default for CompressionConfig{
+    fn default()->Self{
+        CompressionConfig{min_bytes:4*1024,zstd_level:3}
+    }
+}
+
+/// Codec for a connection with these (already negotiated) capabilities
+pub fn negotiate(capabilities:&[String])->Option<Codec>{
+    CODECS.into_iter().find(|codec| capabilities.iter().any(|c| c==codec.capability()))
+}
+
+pub fn compress(codec:Codec,payload:&[u8],level:i32)->Vec<u8>{
+    match codec{
+        // both only fail on allocation or buffer-size errors they size for themselves
+        Codec::Zstd=>zstd::bulk::compress(payload,level).expect("zstd compresses into a sized buffer"),
+        Codec::Snappy=>snap::raw::Encoder::new().compress_vec(payload).expect("snappy compresses into a sized buffer"),
+    }
+}
+
+/// Decompress `payload`, refusing output over `max_bytes`
+pub fn decompress(codec:Codec,payload:&[u8],max_bytes:usize)->Result<Vec<u8>,CompressionError>{
+    let corrupt=|e:&dyn std::fmt::Display| CompressionError::Corrupt(codec,e.to_string());
+    match codec{
+        Codec::Zstd=>{
+            let len=zstd::zstd_safe::get_frame_content_size(payload)
+            .map_err(|_| corrupt(&"bad frame header"))?
+            .ok_or_else(|| corrupt(&"frame doesn't record its size"))?;
+            if len>max_bytes as u64{
+                return Err(CompressionError::TooLarge(max_bytes))
+            }
+            zstd::bulk::decompress(payload,len as usize).map_err(|e| corrupt(&e))
+        }
+        Codec::Snappy=>{
+            let len=snap::raw::decompress_len(payload).map_err(|e| corrupt(&e))?;
+            if len>max_bytes{
+                return Err(CompressionError::TooLarge(max_bytes))
+            }
+            snap::raw::Decoder::new().decompress_vec(payload).map_err(|e| corrupt(&e))
+        }
+    }
+}
+
+/// `frame` as it should go out on a connection using `codec`
+pub fn outgoing(frame:Frame,codec:Option<Codec>,config:&CompressionConfig)->Frame{
+    match (frame,codec){
+        (Frame::Message{topic,payload},Some(codec)) if payload.len()>=config.min_bytes=>{
+            let compressed=compress(codec,&payload,config.zstd_level);
+            if compressed.len()<payload.len(){
+                Frame::Compressed{topic,codec,payload:compressed}
+            } else{
+                Frame::Message{topic,payload}
+            }
+        }
+        (frame,_)=>frame,
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::network::Topic;
+
+    #[test]
+    fn test_roundtrip_and_size_cap(){
+        let payload=b"block data ".repeat(1_000);
+        for codec in CODECS{
+            let compressed=compress(codec,&payload,3);
+            assert!(compressed.len()<payload.len()/4);
+            assert_eq!(decompress(codec,&compressed,payload.len()).unwrap(),payload);
+            assert_eq!(decompress(codec,&compressed,payload.len()-1),Err(CompressionError::TooLarge(payload.len()-1)));
+            assert!(matches!(decompress(codec,b"not compressed at all",1_000),Err(CompressionError::Corrupt(..))));
+        }
+    }
+
+    #[test]
+    fn test_only_large_payloads_compressed(){
+        let config=CompressionConfig::default();
+        let capabilities=vec![CAP_SNAPPY.to_string(),CAP_ZSTD.to_string()];
+        assert_eq!(negotiate(&capabilities),Some(Codec::Zstd));
+        assert_eq!(negotiate(&capabilities[..1]),Some(Codec::Snappy));
+        assert_eq!(negotiate(&[]),None);
+
+        let small=Frame::Message{topic:Topic::Blocks,payload:vec![0;100]};
+        assert_eq!(outgoing(small.clone(),Some(Codec::Zstd),&config),small);
+        let large=Frame::Message{topic:Topic::Sync,payload:vec![0;64*1024]};
+        assert!(matches!(outgoing(large.clone(),Some(Codec::Zstd),&config),Frame::Compressed{topic:Topic::Sync,codec:Codec::Zstd,..}));
+        assert_eq!(outgoing(large,None,&config),Frame::Message{topic:Topic::Sync,payload:vec![0;64*1024]});
+    }
+}
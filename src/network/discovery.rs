@@ -0,0 +1,556 @@
+// src/network/discovery.rs
+
+//! Peer discovery
+//! - Bootnodes: fixed addresses dialed whenever the node is below its target peer count
+//! - mDNS: on devnets, nodes announce themselves with multicast beacons on the mDNS group
+//!   and learn about each other on the local network
+//! - Kademlia: nodes are kept in a routing table of k-buckets by XOR distance between peer
+//!   ids; connected peers are asked `FindNode` for our own id when they connect and for a
+//!   random id every round, and the nodes they return fill the table
+//! - Every `interval` the node dials bootnodes, then the closest known nodes, until it has
+//!   `target_peers` connections
+//! - Persistent peers are kept connected whatever the peer count, redialed with exponential
+//!   backoff while down; private peers are never handed out in `Nodes`, so e.g. a validator
+//!   behind sentries stays unknown to the rest of the network
+
+use super::identity::PeerId;
+use super::wire::WireMessage;
+use super::{InboundMessage,Network,NetworkError,NetworkEvent,Topic};
+use crate::canonical;
+use serde::{Deserialize,Serialize};
+use socket2::{Domain,Protocol,Socket,Type};
+use std::collections::HashSet;
+use std::net::{Ipv4Addr,SocketAddr,SocketAddrV4};
+use std::sync::{Arc,Mutex};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::{interval,sleep,timeout};
+
+/// Bucket size and number of nodes returned per `FindNode`
+pub const K:usize=20;
+
+/// Bits in a peer id, one bucket per bit
+pub const ID_BITS:usize=160;
+
+/// Connected peers queried per lookup
+pub const ALPHA:usize=3;
+
+pub const MDNS_GROUP:Ipv4Addr=Ipv4Addr::new(224,0,0,251);
+pub const MDNS_PORT:u16=5353;
+
+/// Prefix of our beacons, so other traffic on the mDNS port is ignored
+const MDNS_MAGIC:&[u8]=b"netchain-mdns";
+
+/// Give up on a dial after this long
+const DIAL_TIMEOUT:Duration=Duration::from_secs(5);
+
+/// First and longest wait before redialing a persistent peer
+const RECONNECT_MIN:Duration=Duration::from_secs(1);
+const RECONNECT_MAX:Duration=Duration::from_secs(5*60);
+
+#[derive(Debug,Clone)]
+pub struct DiscoveryConfig{
+    /// Dialed first whenever we are below `target_peers`
+    pub bootnodes:Vec<SocketAddr>,
+    /// Connections to keep; discovery stops dialing once reached
+    pub target_peers:usize,
+    /// Announce and discover nodes on the local network
+    pub mdns:bool,
+    pub mdns_port:u16,
+    /// Run Kademlia lookups through connected peers
+    pub kademlia:bool,
+    /// Time between discovery rounds
+    pub interval:Duration,
+    /// Always kept connected, regardless of `target_peers`
+    pub persistent_peers:Vec<SocketAddr>,
+    /// Peers whose addresses we never gossip
+    pub private_peers:Vec<PeerId>,
+}
+
+impl Default for DiscoveryConfig{
+    fn default()->Self{
+        DiscoveryConfig{
+            bootnodes:Vec::new(),
+            target_peers:25,
+            mdns:false,
+            mdns_port:MDNS_PORT,
+            kademlia:true,
+            interval:Duration::from_secs(10),
+            persistent_peers:Vec::new(),
+            private_peers:Vec::new(),
+        }
+    }
+}
+
+/// A node and the address it accepts connections on
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct NodeRecord{
+    pub peer_id:PeerId,
+    pub addr:SocketAddr,
+}
+
+/// Payloads of `Topic::Discovery`
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub enum DiscoveryMessage{
+    /// Ask for the nodes closest to `target`
+    FindNode{target:PeerId},
+    Nodes{target:PeerId,nodes:Vec<NodeRecord>},
+}
+
+impl WireMessage for DiscoveryMessage{
+    const TOPIC:Topic=Topic::Discovery;
+}
+
+/// mDNS announcement
+#[derive(Debug,Clone,Serialize,Deserialize)]
+struct Beacon{
+    network_id:String,
+    peer_id:PeerId,
+    listen_port:u16,
+}
+
+fn distance(a:&[u8;20],b:&[u8;20])->[u8;20]{
+    std::array::from_fn(|i| a[i]^b[i])
+}
+
+/// Kademlia routing table: bucket `i` holds nodes whose distance from us has its highest
+/// set bit at position `i`
+#[derive(Debug,Clone)]
+pub struct RoutingTable{
+    local:[u8;20],
+    buckets:Vec<Vec<NodeRecord>>,
+}
+
+impl RoutingTable{
+    pub fn new(local:&PeerId)->Self{
+        RoutingTable{local:local.to_bytes().unwrap_or_default(),buckets:vec![Vec::new();ID_BITS]}
+    }
+
+    /// `None` for our own id or a malformed one
+    fn bucket_index(&self,peer:&PeerId)->Option<usize>{
+        let d=distance(&self.local,&peer.to_bytes()?);
+        let zeros=d.iter().position(|b| *b!=0).map(|i| i*8+d[i].leading_zeros() as usize)?;
+        Some(ID_BITS-1-zeros)
+    }
+
+    /// Add or refresh a node. A full bucket keeps its existing, longer known nodes.
+    pub fn insert(&mut self,record:NodeRecord)->bool{
+        let Some(index)=self.bucket_index(&record.peer_id) else{
+            return false
+        };
+        let bucket=&mut self.buckets[index];
+        if let Some(pos)=bucket.iter().position(|r| r.peer_id==record.peer_id){
+            bucket.remove(pos);
+        } else if bucket.len()>=K{
+            return false
+        }
+        bucket.push(record);
+        true
+    }
+
+    pub fn remove(&mut self,peer:&PeerId){
+        if let Some(index)=self.bucket_index(peer){
+            self.buckets[index].retain(|r| &r.peer_id!=peer);
+        }
+    }
+
+    pub fn contains(&self,peer:&PeerId)->bool{
+        self.bucket_index(peer).is_some_and(|index| self.buckets[index].iter().any(|r| &r.peer_id==peer))
+    }
+
+    pub fn len(&self)->usize{
+        self.buckets.iter().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.len()==0
+    }
+
+    /// Up to `count` known nodes, closest to `target` first
+    pub fn closest(&self,target:&PeerId,count:usize)->Vec<NodeRecord>{
+        let Some(target)=target.to_bytes() else{
+            return Vec::new()
+        };
+        let mut nodes:Vec<([u8;20],&NodeRecord)>=self
+        .buckets
+        .iter()
+        .flatten()
+        .filter_map(|r| Some((distance(&target,&r.peer_id.to_bytes()?),r)))
+        .collect();
+        nodes.sort_by_key(|(d,_)| *d);
+        nodes.into_iter().take(count).map(|(_,r)| r.clone()).collect()
+    }
+}
+
+/// Discovery service over a running `Network`
+#[derive(Clone)]
+pub struct Discovery{
+    network:Network,
+    config:DiscoveryConfig,
+    table:Arc<Mutex<RoutingTable>>,
+}
+
+impl Discovery{
+    pub fn new(network:Network,config:DiscoveryConfig)->Self{
+        let table=RoutingTable::new(network.local_peer_id());
+        Discovery{network,config,table:Arc::new(Mutex::new(table))}
+    }
+
+    /// Known nodes, closest to us first
+    pub fn known_nodes(&self)->Vec<NodeRecord>{
+        let table=self.table.lock().expect("routing table poisoned");
+        table.closest(self.network.local_peer_id(),usize::MAX)
+    }
+
+    pub fn add_node(&self,record:NodeRecord)->bool{
+        self.table.lock().expect("routing table poisoned").insert(record)
+    }
+
+    /// Spawn the discovery tasks. Fails only if mDNS is enabled and its socket can't be
+    /// opened.
+    pub fn start(self)->Result<(),NetworkError>{
+        if self.config.mdns{
+            let socket=mdns_socket(self.config.mdns_port)?;
+            tokio::spawn(self.clone().run_mdns(socket));
+        }
+        for addr in self.config.persistent_peers.clone(){
+            tokio::spawn(self.clone().keep_connected(addr));
+        }
+        let rounds=self.clone();
+        tokio::spawn(async move {
+            let mut ticker=interval(rounds.config.interval);
+            loop{
+                ticker.tick().await;
+                rounds.fill_peers().await;
+                if rounds.config.kademlia{
+                    rounds.lookup(&PeerId::from_bytes(rand::random()));
+                }
+            }
+        });
+        let mut messages=self.network.subscribe(Topic::Discovery);
+        let mut events=self.network.events();
+        tokio::spawn(async move {
+            loop{
+                tokio::select!{
+                    Some(message)=messages.recv()=>self.handle(message),
+                    Ok(event)=events.recv()=>self.peer_event(event),
+                    else=>break,
+                }
+            }
+        });
+        Ok(())
+    }
+
+    /// Dial bootnodes, then known nodes closest to us, until `target_peers` are connected
+    pub async fn fill_peers(&self){
+        let peers=self.network.peers();
+        let mut missing=self.config.target_peers.saturating_sub(peers.len());
+        if missing==0{
+            return
+        }
+        let connected:HashSet<&PeerId>=peers.iter().map(|p| &p.peer_id).collect();
+        let addrs:HashSet<SocketAddr>=peers.iter().flat_map(|p| [Some(p.addr),p.listen_addr]).flatten().collect();
+        let mut candidates:Vec<(Option<PeerId>,SocketAddr)>=self
+        .config
+        .bootnodes
+        .iter()
+        .filter(|addr| !addrs.contains(addr) && !self.config.persistent_peers.contains(addr))
+        .map(|addr| (None,*addr))
+        .collect();
+        candidates.extend(
+            self.known_nodes()
+            .into_iter()
+            .filter(|r| !connected.contains(&r.peer_id))
+            .map(|r| (Some(r.peer_id),r.addr)),
+        );
+        for (peer,addr) in candidates{
+            if missing==0{
+                break
+            }
+            match timeout(DIAL_TIMEOUT,self.network.dial(addr)).await{
+                Ok(Ok(_))=>missing-=1,
+                Ok(Err(NetworkError::AlreadyConnected(_)))=>{}
+                // unreachable nodes leave the table; bootnodes are retried next round
+                _=>{
+                    if let Some(peer)=peer{
+                        self.table.lock().expect("routing table poisoned").remove(&peer);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Whether a connection to (or from) the node listening at `addr` is up
+    fn connected_to(&self,addr:SocketAddr)->bool{
+        self.network.peers().iter().any(|p| p.addr==addr || p.listen_addr==Some(addr))
+    }
+
+    /// Keep a connection to the persistent peer at `addr`, redialing with exponential
+    /// backoff while it is down
+    async fn keep_connected(self,addr:SocketAddr){
+        let mut backoff=RECONNECT_MIN;
+        loop{
+            let wait=if self.connected_to(addr){
+                backoff=RECONNECT_MIN;
+                self.config.interval
+            } else{
+                match timeout(DIAL_TIMEOUT,self.network.dial(addr)).await{
+                    Ok(Ok(_) | Err(NetworkError::AlreadyConnected(_)))=>{
+                        backoff=RECONNECT_MIN;
+                        self.config.interval
+                    }
+                    _=>{
+                        let wait=backoff;
+                        backoff=(backoff*2).min(RECONNECT_MAX);
+                        wait
+                    }
+                }
+            };
+            sleep(wait).await;
+        }
+    }
+
+    fn is_private(&self,peer:&PeerId)->bool{
+        self.config.private_peers.contains(peer)
+    }
+
+    /// Ask the `ALPHA` connected peers closest to `target` for nodes near it
+    pub fn lookup(&self,target:&PeerId){
+        let Some(target_bytes)=target.to_bytes() else{
+            return
+        };
+        let mut peers:Vec<([u8;20],PeerId)>=self
+        .network
+        .peers()
+        .into_iter()
+        .filter_map(|p| Some((distance(&target_bytes,&p.peer_id.to_bytes()?),p.peer_id)))
+        .collect();
+        peers.sort();
+        let request=DiscoveryMessage::FindNode{target:target.clone()};
+        for (_,peer) in peers.into_iter().take(ALPHA){
+            let _=self.network.send_message(&peer,&request);
+        }
+    }
+
+    fn handle(&self,message:InboundMessage){
+        let Ok(discovery)=DiscoveryMessage::from_payload(&message.payload) else{
+            return
+        };
+        match discovery{
+            DiscoveryMessage::FindNode{target}=>{
+                let mut nodes=self.table.lock().expect("routing table poisoned").closest(&target,K+1);
+                nodes.retain(|r| r.peer_id!=message.from && !self.is_private(&r.peer_id));
+                nodes.truncate(K);
+                let _=self.network.send_message(&message.from,&DiscoveryMessage::Nodes{target,nodes});
+            }
+            DiscoveryMessage::Nodes{nodes,..}=>{
+                let mut table=self.table.lock().expect("routing table poisoned");
+                for record in nodes.into_iter().take(K){
+                    table.insert(record);
+                }
+            }
+        }
+    }
+
+    /// Remember connected peers that listen (private ones excepted), and look ourselves up
+    /// through new ones
+    fn peer_event(&self,event:NetworkEvent){
+        let NetworkEvent::PeerConnected{peer,..}=event else{
+            return
+        };
+        let listen_addr=self.network.peers().into_iter().find(|p| p.peer_id==peer).and_then(|p| p.listen_addr);
+        if let Some(addr)=listen_addr
+        && !self.is_private(&peer){
+            self.add_node(NodeRecord{peer_id:peer.clone(),addr});
+        }
+        if self.config.kademlia{
+            let request=DiscoveryMessage::FindNode{target:self.network.local_peer_id().clone()};
+            let _=self.network.send_message(&peer,&request);
+        }
+    }
+
+    async fn run_mdns(self,socket:UdpSocket){
+        let group=SocketAddr::from((MDNS_GROUP,self.config.mdns_port));
+        let beacon=Beacon{
+            network_id:self.network.network_id().to_string(),
+            peer_id:self.network.local_peer_id().clone(),
+            listen_port:self.network.local_addr().port(),
+        };
+        let mut packet=MDNS_MAGIC.to_vec();
+        packet.extend(canonical::encode(&beacon));
+        let mut ticker=interval(self.config.interval);
+        let mut buf=vec![0u8;1024];
+        loop{
+            tokio::select!{
+                _=ticker.tick()=>{
+                    let _=socket.send_to(&packet,group).await;
+                }
+                Ok((len,from))=socket.recv_from(&mut buf)=>{
+                    self.handle_beacon(&buf[..len],from);
+                }
+            }
+        }
+    }
+
+    /// Add the sender of a beacon from our network; returns the node learned
+    fn handle_beacon(&self,packet:&[u8],from:SocketAddr)->Option<NodeRecord>{
+        let beacon:Beacon=canonical::decode(packet.strip_prefix(MDNS_MAGIC)?).ok()?;
+        if beacon.network_id!=self.network.network_id() || &beacon.peer_id==self.network.local_peer_id(){
+            return None
+        }
+        let record=NodeRecord{peer_id:beacon.peer_id,addr:SocketAddr::new(from.ip(),beacon.listen_port)};
+        self.add_node(record.clone()).then_some(record)
+    }
+}
+
+/// UDP socket joined to the mDNS group, shared with other listeners on the port
+fn mdns_socket(port:u16)->Result<UdpSocket,NetworkError>{
+    let socket=Socket::new(Domain::IPV4,Type::DGRAM,Some(Protocol::UDP))?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&SocketAddr::from(SocketAddrV4::new(Ipv4Addr::UNSPECIFIED,port)).into())?;
+    socket.join_multicast_v4(&MDNS_GROUP,&Ipv4Addr::UNSPECIFIED)?;
+    socket.set_multicast_loop_v4(true)?;
+    socket.set_nonblocking(true)?;
+    Ok(UdpSocket::from_std(socket.into())?)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::network::NetworkConfig;
+    use crate::network::identity::NodeIdentity;
+    use tokio::time::sleep;
+
+    fn record(byte:u8)->NodeRecord{
+        let mut id=[0u8;20];
+        id[0]=byte;
+        NodeRecord{peer_id:PeerId::from_bytes(id),addr:SocketAddr::from(([127,0,0,1],byte as u16))}
+    }
+
+    async fn node(config:DiscoveryConfig)->(Network,Discovery){
+        let network_config=NetworkConfig{listen_addr:SocketAddr::from(([127,0,0,1],0)),..NetworkConfig::default()};
+        let network=Network::start(NodeIdentity::generate(),network_config).await.unwrap();
+        let discovery=Discovery::new(network.clone(),config);
+        discovery.clone().start().unwrap();
+        (network,discovery)
+    }
+
+    #[test]
+    fn test_routing_table_buckets_and_closest(){
+        let mut table=RoutingTable::new(&PeerId::from_bytes([0u8;20]));
+        assert!(!table.insert(NodeRecord{peer_id:PeerId::from_bytes([0u8;20]),addr:record(1).addr}));
+        for byte in [0x80,0x81,0x01,0x02]{
+            assert!(table.insert(record(byte)));
+        }
+        assert_eq!(table.bucket_index(&record(0x80).peer_id),Some(ID_BITS-1));
+        assert_eq!(table.bucket_index(&record(0x01).peer_id),Some(ID_BITS-8));
+        let closest:Vec<NodeRecord>=table.closest(&record(0x03).peer_id,2);
+        assert_eq!(closest,vec![record(0x02),record(0x01)]);
+
+        // a full bucket keeps the nodes it has
+        let mut full=RoutingTable::new(&PeerId::from_bytes([0u8;20]));
+        for byte in 0x80..0x80+K as u8{
+            assert!(full.insert(record(byte)));
+        }
+        assert!(!full.insert(record(0xff)));
+        assert!(full.insert(record(0x80)));
+        assert_eq!(full.len(),K);
+        full.remove(&record(0x80).peer_id);
+        assert!(!full.contains(&record(0x80).peer_id));
+    }
+
+    #[tokio::test]
+    async fn test_nodes_found_through_bootnode(){
+        let (boot,_)=node(DiscoveryConfig{interval:Duration::from_millis(50),..DiscoveryConfig::default()}).await;
+        let config=DiscoveryConfig{
+            bootnodes:vec![boot.local_addr()],
+            target_peers:2,
+            interval:Duration::from_millis(50),
+            ..DiscoveryConfig::default()
+        };
+        let (b,_)=node(config.clone()).await;
+        let (c,c_discovery)=node(config).await;
+        // c learns b from the bootnode's table, then dials it to reach its target
+        timeout(Duration::from_secs(10),async {
+            while !c.is_connected(b.local_peer_id()){
+                sleep(Duration::from_millis(20)).await;
+            }
+        })
+        .await
+        .unwrap();
+        assert!(c_discovery.known_nodes().iter().any(|r| &r.peer_id==boot.local_peer_id()));
+        assert_eq!(c.peers().len(),2);
+    }
+
+    #[tokio::test]
+    async fn test_beacons_from_other_networks_ignored(){
+        let network_config=NetworkConfig{listen_addr:SocketAddr::from(([127,0,0,1],0)),..NetworkConfig::default()};
+        let network=Network::start(NodeIdentity::generate(),network_config).await.unwrap();
+        let discovery=Discovery::new(network,DiscoveryConfig::default());
+        let beacon=|network_id:&str,peer_id:PeerId|{
+            let mut packet=MDNS_MAGIC.to_vec();
+            packet.extend(canonical::encode(&Beacon{network_id:network_id.to_string(),peer_id,listen_port:4000}));
+            packet
+        };
+        let from=SocketAddr::from(([192,168,1,7],5353));
+        let other=NodeIdentity::generate().peer_id();
+        assert_eq!(discovery.handle_beacon(&beacon("devnet",other.clone()),from),None);
+        assert_eq!(discovery.handle_beacon(b"not a beacon",from),None);
+        let found=discovery.handle_beacon(&beacon("netchain",other.clone()),from).unwrap();
+        assert_eq!(found,NodeRecord{peer_id:other,addr:SocketAddr::from(([192,168,1,7],4000))});
+        assert_eq!(discovery.known_nodes(),vec![found]);
+    }
+
+    #[tokio::test]
+    async fn test_persistent_peer_redialed(){
+        let (target,_)=node(DiscoveryConfig::default()).await;
+        let config=DiscoveryConfig{
+            target_peers:0,
+            kademlia:false,
+            interval:Duration::from_millis(50),
+            persistent_peers:vec![target.local_addr()],
+            ..DiscoveryConfig::default()
+        };
+        let (a,_)=node(config).await;
+        let connected=||async {
+            timeout(Duration::from_secs(10),async {
+                while !target.is_connected(a.local_peer_id()){
+                    sleep(Duration::from_millis(20)).await;
+                }
+            })
+            .await
+            .unwrap();
+        };
+        connected().await;
+        target.disconnect(a.local_peer_id());
+        connected().await;
+    }
+
+    #[tokio::test]
+    async fn test_private_peers_not_gossiped(){
+        let validator=record(0x01);
+        // no target, so the sentry doesn't dial (and drop) the unreachable test records
+        let config=DiscoveryConfig{target_peers:0,private_peers:vec![validator.peer_id.clone()],..DiscoveryConfig::default()};
+        let (sentry,sentry_discovery)=node(config).await;
+        sentry_discovery.add_node(validator.clone());
+        sentry_discovery.add_node(record(0x02));
+
+        let network_config=NetworkConfig{listen_addr:SocketAddr::from(([127,0,0,1],0)),..NetworkConfig::default()};
+        let other=Network::start(NodeIdentity::generate(),network_config).await.unwrap();
+        let mut replies=other.subscribe(Topic::Discovery);
+        let peer=other.dial(sentry.local_addr()).await.unwrap();
+        other.send_message(&peer,&DiscoveryMessage::FindNode{target:validator.peer_id.clone()}).unwrap();
+        // the sentry also looks itself up through us
+        let nodes=timeout(Duration::from_secs(5),async {
+            loop{
+                let reply=replies.recv().await.unwrap();
+                if let Ok(DiscoveryMessage::Nodes{nodes,..})=DiscoveryMessage::from_payload(&reply.payload){
+                    return nodes
+                }
+            }
+        })
+        .await
+        .unwrap();
+        assert_eq!(nodes,vec![record(0x02)]);
+    }
+}
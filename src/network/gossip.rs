@@ -0,0 +1,386 @@
+// src/network/gossip.rs
+
+//! Gossip of transactions and blocks
+//! - `Topic::Transactions`: whole signed transactions. Locally submitted ones are admitted
+//!   to the mempool and broadcast; received ones go through the same admission and are
+//!   relayed only if admitted
+//! - `Topic::Blocks`: new blocks are announced by hash; peers that don't have the block ask
+//!   the announcer for it, check its hash, hand it to the node and announce it onwards
+//! - Every transaction and block hash is remembered for `SEEN_TTL` in a bounded `SeenCache`,
+//!   so each node processes and relays an item at most once however many peers send it
+//! - An announced block is requested from one announcer at a time; other announcements of
+//!   it are ignored for `REQUEST_TTL` unless the block arrives first
+
+use super::identity::PeerId;
+use super::ratelimit::RateClass;
+use super::reputation::Misbehavior;
+use super::wire::WireMessage;
+use super::{InboundMessage,Network,Topic};
+use crate::block::Block;
+use crate::mempool::{Mempool,MempoolError};
+use crate::shared::SharedState;
+use crate::transaction::SignedTransaction;
+use serde::{Deserialize,Serialize};
+use std::collections::{HashMap,VecDeque};
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use tokio::sync::mpsc;
+
+/// Hashes remembered per kind before the oldest are forgotten
+pub const SEEN_CACHE_SIZE:usize=100_000;
+
+/// How long a hash is remembered; past that, stale items are dropped by the mempool or
+/// chain anyway
+pub const SEEN_TTL:Duration=Duration::from_secs(10*60);
+
+/// How long to wait for a requested block before asking the next announcer
+pub const REQUEST_TTL:Duration=Duration::from_secs(5);
+
+/// Recently produced or fetched blocks kept to answer `BlockMessage::Request`
+pub const RECENT_BLOCKS:usize=256;
+
+/// Payloads of `Topic::Blocks`
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub enum BlockMessage{
+    /// The sender has block `hash` at height `index`
+    Announce{index:u64,hash:String},
+    /// Ask for the full block
+    Request{hash:String},
+    Block(Box<Block>),
+}
+
+impl WireMessage for BlockMessage{
+    const TOPIC:Topic=Topic::Blocks;
+}
+
+/// Bounded set of recently seen hashes; entries expire after `ttl`, and the oldest are
+/// forgotten first when full
+#[derive(Debug,Clone)]
+pub struct SeenCache{
+    capacity:usize,
+    ttl:Duration,
+    order:VecDeque<String>,
+    /// Hash -> when it was recorded
+    hashes:HashMap<String,Instant>,
+}
+
+impl SeenCache{
+    pub fn new(capacity:usize,ttl:Duration)->Self{
+        SeenCache{capacity,ttl,order:VecDeque::new(),hashes:HashMap::new()}
+    }
+
+    /// Record `hash`; false if it was already known
+    pub fn insert(&mut self,hash:&str,now:Instant)->bool{
+        self.expire(now);
+        if self.hashes.contains_key(hash){
+            return false
+        }
+        if self.order.len()>=self.capacity
+        && let Some(oldest)=self.order.pop_front(){
+            self.hashes.remove(&oldest);
+        }
+        self.order.push_back(hash.to_string());
+        self.hashes.insert(hash.to_string(),now);
+        true
+    }
+
+    pub fn contains(&self,hash:&str,now:Instant)->bool{
+        self.seen_at(hash,now).is_some()
+    }
+
+    /// When `hash` was recorded, if it hasn't expired
+    pub fn seen_at(&self,hash:&str,now:Instant)->Option<Instant>{
+        self.hashes.get(hash).copied().filter(|seen| now.saturating_duration_since(*seen)<self.ttl)
+    }
+
+    /// Forget `hash`, e.g. when a request for it failed
+    pub fn remove(&mut self,hash:&str){
+        if self.hashes.remove(hash).is_some(){
+            self.order.retain(|h| h!=hash);
+        }
+    }
+
+    pub fn len(&self)->usize{
+        self.order.len()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.order.is_empty()
+    }
+
+    /// Drop expired entries; insertion order is time order, so they are at the front
+    fn expire(&mut self,now:Instant){
+        while let Some(oldest)=self.order.front(){
+            if self.contains(oldest,now){
+                break
+            }
+            self.hashes.remove(oldest);
+            self.order.pop_front();
+        }
+    }
+}
+
+/// Recently produced or fetched blocks, newest `RECENT_BLOCKS` only
+#[derive(Debug,Default)]
+struct RecentBlocks{
+    blocks:HashMap<String,Block>,
+    order:VecDeque<String>,
+}
+
+/// Gossip service over a running `Network`
+#[derive(Clone)]
+pub struct Gossip{
+    network:Network,
+    mempool:Arc<Mutex<Mempool>>,
+    state:Arc<SharedState>,
+    seen_txs:Arc<Mutex<SeenCache>>,
+    seen_blocks:Arc<Mutex<SeenCache>>,
+    /// Blocks requested from an announcer and not yet received
+    requested:Arc<Mutex<SeenCache>>,
+    recent:Arc<Mutex<RecentBlocks>>,
+    /// Fetched blocks go to the node for import
+    blocks_out:mpsc::UnboundedSender<(PeerId,Block)>,
+}
+
+impl Gossip{
+    /// Gossip service and the stream of blocks fetched from peers
+    pub fn new(
+        network:Network,
+        mempool:Arc<Mutex<Mempool>>,
+        state:Arc<SharedState>,
+    )->(Self,mpsc::UnboundedReceiver<(PeerId,Block)>){
+        let (blocks_out,blocks)=mpsc::unbounded_channel();
+        let gossip=Gossip{
+            network,
+            mempool,
+            state,
+            seen_txs:Arc::new(Mutex::new(SeenCache::new(SEEN_CACHE_SIZE,SEEN_TTL))),
+            seen_blocks:Arc::new(Mutex::new(SeenCache::new(SEEN_CACHE_SIZE,SEEN_TTL))),
+            requested:Arc::new(Mutex::new(SeenCache::new(RECENT_BLOCKS,REQUEST_TTL))),
+            recent:Arc::new(Mutex::new(RecentBlocks::default())),
+            blocks_out,
+        };
+        (gossip,blocks)
+    }
+
+    /// Process gossip from peers until the network shuts down
+    pub async fn run(self){
+        let mut txs=self.network.subscribe(Topic::Transactions);
+        let mut blocks=self.network.subscribe(Topic::Blocks);
+        loop{
+            tokio::select!{
+                Some(message)=txs.recv()=>self.handle_transaction(message),
+                Some(message)=blocks.recv()=>self.handle_block_message(message),
+                else=>break,
+            }
+        }
+    }
+
+    /// Admit a locally submitted transaction and broadcast it; returns its hash
+    pub fn submit_transaction(&self,tx:SignedTransaction)->Result<String,MempoolError>{
+        let hash=self.admit(&tx)?;
+        self.seen_txs.lock().expect("seen cache poisoned").insert(&hash,Instant::now());
+        self.network.broadcast_message(&tx,None);
+        Ok(hash)
+    }
+
+    /// Announce a block this node produced (or imported)
+    pub fn announce_block(&self,block:Block){
+        self.seen_blocks.lock().expect("seen cache poisoned").insert(&block.hash,Instant::now());
+        self.announce(&block,None);
+        self.remember(block);
+    }
+
+    fn admit(&self,tx:&SignedTransaction)->Result<String,MempoolError>{
+        let state=self.state.snapshot();
+        self.mempool.lock().expect("mempool lock poisoned").insert(tx.clone(),&state)
+    }
+
+    fn handle_transaction(&self,message:InboundMessage){
+        let Ok(tx)=SignedTransaction::from_payload(&message.payload) else{
+            self.network.report(&message.from,Misbehavior::BadMessage);
+            return
+        };
+        if !self.seen_txs.lock().expect("seen cache poisoned").insert(&tx.tx_hash_hex(),Instant::now()){
+            return
+        }
+        // only relay what our own mempool accepts
+        match self.admit(&tx){
+            Ok(_)=>{
+                self.network.broadcast_except(Topic::Transactions,message.payload,Some(&message.from));
+            }
+            // no honest node relays these
+            Err(MempoolError::InvalidSignature(_) | MempoolError::ExceedsLimit(_) | MempoolError::TooLarge)=>{
+                self.network.report(&message.from,Misbehavior::InvalidTransaction);
+            }
+            Err(_)=>{}
+        }
+    }
+
+    fn handle_block_message(&self,message:InboundMessage){
+        let Ok(block_message)=BlockMessage::from_payload(&message.payload) else{
+            self.network.report(&message.from,Misbehavior::BadMessage);
+            return
+        };
+        match block_message{
+            BlockMessage::Announce{hash,..}=>{
+                let now=Instant::now();
+                if self.seen_blocks.lock().expect("seen cache poisoned").contains(&hash,now){
+                    return
+                }
+                if !self.requested.lock().expect("requested blocks poisoned").insert(&hash,now){
+                    return
+                }
+                if self.network.send_message(&message.from,&BlockMessage::Request{hash:hash.clone()}).is_err(){
+                    self.requested.lock().expect("requested blocks poisoned").remove(&hash);
+                }
+            }
+            BlockMessage::Request{hash}=>{
+                if !self.network.allow(&message.from,RateClass::BlockRequests){
+                    return
+                }
+                let block=self.recent.lock().expect("recent blocks poisoned").blocks.get(&hash).cloned();
+                if let Some(block)=block{
+                    let _=self.network.send_message(&message.from,&BlockMessage::Block(Box::new(block)));
+                }
+            }
+            BlockMessage::Block(block)=>{
+                if block.recalculate_hash()!=block.hash{
+                    self.network.report(&message.from,Misbehavior::InvalidBlock);
+                    return
+                }
+                let now=Instant::now();
+                if !self.seen_blocks.lock().expect("seen cache poisoned").insert(&block.hash,now){
+                    return
+                }
+                let requested=self.requested.lock().expect("requested blocks poisoned").seen_at(&block.hash,now);
+                if let Some(sent)=requested{
+                    self.network.observer().record_response(&message.from,message.payload.len(),now-sent);
+                    self.requested.lock().expect("requested blocks poisoned").remove(&block.hash);
+                }
+                self.announce(&block,Some(&message.from));
+                self.remember((*block).clone());
+                self.network.reward(&message.from,1);
+                let _=self.blocks_out.send((message.from,*block));
+            }
+        }
+    }
+
+    fn announce(&self,block:&Block,skip:Option<&PeerId>){
+        let announce=BlockMessage::Announce{index:block.index,hash:block.hash.clone()};
+        self.network.broadcast_message(&announce,skip);
+    }
+
+    fn remember(&self,block:Block){
+        let mut recent=self.recent.lock().expect("recent blocks poisoned");
+        if recent.blocks.insert(block.hash.clone(),block.clone()).is_none(){
+            recent.order.push_back(block.hash);
+        }
+        while recent.order.len()>RECENT_BLOCKS{
+            if let Some(oldest)=recent.order.pop_front(){
+                recent.blocks.remove(&oldest);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::network::NetworkConfig;
+    use crate::network::identity::NodeIdentity;
+    use crate::state::State;
+    use crate::transaction::{Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+    use std::net::SocketAddr;
+    use std::time::Duration;
+    use tokio::time::{sleep,timeout};
+
+    async fn node(state:&State)->(Gossip,Arc<Mutex<Mempool>>,mpsc::UnboundedReceiver<(PeerId,Block)>){
+        let config=NetworkConfig{listen_addr:SocketAddr::from(([127,0,0,1],0)),..NetworkConfig::default()};
+        let network=Network::start(NodeIdentity::generate(),config).await.unwrap();
+        let mempool=Arc::new(Mutex::new(Mempool::new(1_000_000)));
+        let (gossip,blocks)=Gossip::new(network,mempool.clone(),Arc::new(SharedState::new(state.clone())));
+        tokio::spawn(gossip.clone().run());
+        (gossip,mempool,blocks)
+    }
+
+    async fn until(mut check:impl FnMut()->bool){
+        timeout(Duration::from_secs(5),async {
+            while !check(){
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn test_seen_cache_forgets_oldest(){
+        let now=Instant::now();
+        let mut seen=SeenCache::new(2,SEEN_TTL);
+        assert!(seen.insert("a",now));
+        assert!(!seen.insert("a",now));
+        seen.insert("b",now);
+        seen.insert("c",now);
+        assert!(!seen.contains("a",now));
+        assert!(seen.contains("b",now) && seen.contains("c",now));
+    }
+
+    #[test]
+    fn test_seen_cache_entries_expire(){
+        let start=Instant::now();
+        let mut seen=SeenCache::new(10,Duration::from_secs(60));
+        seen.insert("a",start);
+        seen.insert("b",start+Duration::from_secs(30));
+        assert!(!seen.insert("a",start+Duration::from_secs(59)));
+        let later=start+Duration::from_secs(60);
+        assert!(!seen.contains("a",later) && seen.contains("b",later));
+        // expired hashes can be relayed again, and no longer take space
+        assert!(seen.insert("a",later));
+        assert_eq!(seen.len(),2);
+        seen.remove("b");
+        assert!(!seen.contains("b",later) && seen.len()==1);
+    }
+
+    #[tokio::test]
+    async fn test_transactions_and_blocks_propagate_along_a_line(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let state=State::with_genesis(vec![(addr.clone(),1_000)]);
+        let (a,_,_)=node(&state).await;
+        let (b,b_pool,mut b_blocks)=node(&state).await;
+        let (c,c_pool,mut c_blocks)=node(&state).await;
+        // a - b - c: c only hears about a's gossip through b
+        a.network.dial(b.network.local_addr()).await.unwrap();
+        b.network.dial(c.network.local_addr()).await.unwrap();
+        until(|| c.network.peers().len()==1 && b.network.peers().len()==2).await;
+
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),10,1,0,None),&kp);
+        let hash=a.submit_transaction(tx.clone()).unwrap();
+        until(|| c_pool.lock().unwrap().contains(&hash)).await;
+        assert!(b_pool.lock().unwrap().contains(&hash));
+        // already seen: b doesn't admit or relay it again
+        assert!(b.seen_txs.lock().unwrap().contains(&hash,Instant::now()));
+
+        // an unfunded sender's transaction is refused by b and goes no further
+        let broke=generate_ed25519_keypair();
+        let bad=SignedTransaction::sign_with_keypair(&Transaction::new(pubkey_to_address_hex(&broke.public),"bob".to_string(),10,1,0,None),&broke);
+        a.network.broadcast_message(&bad,None);
+
+        // a forged signature costs the sender reputation
+        let mut forged=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),10,1,1,None),&kp);
+        forged.tx.fee=900;
+        a.network.broadcast_message(&forged,None);
+        until(|| b.network.peer_score(a.network.local_peer_id())<0).await;
+
+        let block=Block::new(1,"data".to_string(),"0".repeat(64));
+        a.announce_block(block.clone());
+        let (from,fetched)=timeout(Duration::from_secs(5),b_blocks.recv()).await.unwrap().unwrap();
+        assert_eq!((&from,&fetched.hash),(a.network.local_peer_id(),&block.hash));
+        // the request is settled once the block is in
+        assert!(b.requested.lock().unwrap().is_empty());
+        let (from,_)=timeout(Duration::from_secs(5),c_blocks.recv()).await.unwrap().unwrap();
+        assert_eq!(&from,b.network.local_peer_id());
+        assert_eq!(c_pool.lock().unwrap().len(),1);
+    }
+}
@@ -0,0 +1,129 @@
+// src/network/identity.rs
+
+//! Node network identity
+//! - Each node holds an Ed25519 keypair, separate from any account key, kept on disk so
+//!   the node keeps its identity across restarts
+//! - `PeerId` is derived from the public key, so scores and bans stick to a node rather
+//!   than to an IP address
+
+use super::NetworkError;
+use crate::transaction::generate_ed25519_keypair;
+use ed25519_dalek::{Keypair,PublicKey,SecretKey,Signature,Signer,Verifier};
+use serde::{Deserialize,Serialize};
+use sha2::{Digest,Sha256};
+use std::fmt;
+use std::path::Path;
+
+/// Domain separation so a peer id never equals an account address for the same key
+const PEER_ID_DOMAIN:&[u8]=b"netchain-peer";
+
+/// Stable identifier of a node: first 20 bytes of sha256(domain || public key), hex
+#[derive(Debug,Clone,PartialEq,Eq,Hash,PartialOrd,Ord,Serialize,Deserialize)]
+pub struct PeerId(String);
+
+impl PeerId{
+    pub fn from_public_key(public_key:&[u8])->Self{
+        let digest=Sha256::new().chain_update(PEER_ID_DOMAIN).chain_update(public_key).finalize();
+        PeerId(hex::encode(&digest[..20]))
+    }
+
+    /// Peer id with the given 20 id bytes (e.g. a random Kademlia lookup target)
+    pub fn from_bytes(bytes:[u8;20])->Self{
+        PeerId(hex::encode(bytes))
+    }
+
+    /// The 20 id bytes; `None` for a malformed id received from a peer
+    pub fn to_bytes(&self)->Option<[u8;20]>{
+        hex::decode(&self.0).ok()?.try_into().ok()
+    }
+
+    pub fn as_str(&self)->&str{
+        &self.0
+    }
+}
+
+impl fmt::Display for PeerId{
+    fn fmt(&self,f:&mut fmt::Formatter<'_>)->fmt::Result{
+        f.write_str(&self.0)
+    }
+}
+
+/// A node's identity keypair
+#[derive(Debug)]
+pub struct NodeIdentity{
+    keypair:Keypair,
+}
+
+impl NodeIdentity{
+    /// Fresh random identity
+    pub fn generate()->Self{
+        NodeIdentity{keypair:generate_ed25519_keypair()}
+    }
+
+    /// Identity from a 32-byte Ed25519 secret key
+    pub fn from_secret(secret:&[u8])->Result<Self,NetworkError>{
+        let secret=SecretKey::from_bytes(secret).map_err(|_| NetworkError::InvalidIdentity)?;
+        let public:PublicKey=(&secret).into();
+        Ok(NodeIdentity{keypair:Keypair{secret,public}})
+    }
+
+    /// Load the identity stored at `path` (hex secret key), or create and store a new one
+    pub fn load_or_generate(path:impl AsRef<Path>)->Result<Self,NetworkError>{
+        let path=path.as_ref();
+        match std::fs::read_to_string(path){
+            Ok(text)=>{
+                let secret=hex::decode(text.trim()).map_err(|_| NetworkError::InvalidIdentity)?;
+                Self::from_secret(&secret)
+            }
+            Err(e) if e.kind()==std::io::ErrorKind::NotFound=>{
+                let identity=Self::generate();
+                std::fs::write(path,hex::encode(identity.keypair.secret.as_bytes()))
+                .map_err(|e| NetworkError::Io(format!("{}: {}",path.display(),e)))?;
+                Ok(identity)
+            }
+            Err(e)=>Err(NetworkError::Io(format!("{}: {}",path.display(),e))),
+        }
+    }
+
+    pub fn public_key(&self)->[u8;32]{
+        self.keypair.public.to_bytes()
+    }
+
+    pub fn peer_id(&self)->PeerId{
+        PeerId::from_public_key(&self.public_key())
+    }
+
+    pub fn sign(&self,message:&[u8])->Vec<u8>{
+        self.keypair.sign(message).to_bytes().to_vec()
+    }
+}
+
+/// Check an identity signature made with `NodeIdentity::sign`
+pub fn verify_identity_signature(public_key:&[u8],message:&[u8],signature:&[u8])->bool{
+    let Ok(key)=PublicKey::from_bytes(public_key) else{
+        return false
+    };
+    let Ok(signature)=Signature::from_bytes(signature) else{
+        return false
+    };
+    key.verify(message,&signature).is_ok()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_identity_persists(){
+        let path=std::env::temp_dir().join(format!("netchain-node-key-{}",std::process::id()));
+        let first=NodeIdentity::load_or_generate(&path).unwrap();
+        let again=NodeIdentity::load_or_generate(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(first.peer_id(),again.peer_id());
+        assert_ne!(first.peer_id(),NodeIdentity::generate().peer_id());
+
+        let signature=first.sign(b"hello");
+        assert!(verify_identity_signature(&again.public_key(),b"hello",&signature));
+        assert!(!verify_identity_signature(&again.public_key(),b"other",&signature));
+    }
+}
@@ -0,0 +1,249 @@
+// src/network/nat.rs
+
+//! Port mapping for nodes behind NAT
+//! - `PortMapper` asks the gateway to forward the listen port, over UPnP IGD first and
+//!   NAT-PMP (RFC 6886) second, and renews the mapping at half its lease
+//! - The mapped public port is what the node announces in its `Hello` (see
+//!   `Network::set_external_addr`), so peers that learn about it can dial it
+//! - Nodes whose gateway does neither fall back to relays and hole punching (see `relay`)
+
+use super::{Network,NetworkError,NetworkEvent};
+use igd_next::aio::tokio::search_gateway;
+use igd_next::{PortMappingProtocol,SearchOptions};
+use std::net::{IpAddr,Ipv4Addr,SocketAddr,SocketAddrV4};
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::{sleep,timeout};
+
+/// Port NAT-PMP gateways listen on
+pub const NATPMP_PORT:u16=5351;
+
+const NATPMP_VERSION:u8=0;
+const OP_EXTERNAL_ADDRESS:u8=0;
+const OP_MAP_TCP:u8=2;
+/// Responses echo the request opcode plus 128
+const OP_RESPONSE:u8=128;
+
+/// Mapping description shown in the router's UPnP table
+const DESCRIPTION:&str="netchain";
+
+/// Wait before retrying after the gateway refused or didn't answer
+const RETRY_DELAY:Duration=Duration::from_secs(60);
+
+#[derive(Debug,Clone)]
+pub struct NatConfig{
+    pub upnp:bool,
+    pub natpmp:bool,
+    /// NAT-PMP gateway; the default route's gateway when unset
+    pub gateway:Option<Ipv4Addr>,
+    /// Requested lease in seconds
+    pub lease:u32,
+    /// How long to wait for the gateway to answer
+    pub timeout:Duration,
+}
+
+impl Default for NatConfig{
+    fn default()->Self{
+        NatConfig{upnp:true,natpmp:true,gateway:None,lease:3600,timeout:Duration::from_secs(3)}
+    }
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum MappingProtocol{
+    Upnp,
+    NatPmp,
+}
+
+/// A port forwarded by the gateway
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct PortMapping{
+    pub protocol:MappingProtocol,
+    /// Public address peers can dial
+    pub external_addr:SocketAddr,
+    /// Seconds the gateway keeps the mapping
+    pub lease:u32,
+}
+
+/// Keeps the listen port mapped on the gateway
+pub struct PortMapper{
+    network:Network,
+    config:NatConfig,
+}
+
+impl PortMapper{
+    pub fn new(network:Network,config:NatConfig)->Self{
+        PortMapper{network,config}
+    }
+
+    /// Map the listen port with the first protocol the gateway answers
+    pub async fn map(&self)->Result<PortMapping,NetworkError>{
+        let port=self.network.local_addr().port();
+        let mut errors=Vec::new();
+        if self.config.upnp{
+            match self.map_upnp(port).await{
+                Ok(mapping)=>return Ok(mapping),
+                Err(e)=>errors.push(format!("upnp: {}",e)),
+            }
+        }
+        if self.config.natpmp{
+            let gateway=self.config.gateway.or_else(default_gateway);
+            match gateway{
+                Some(gateway)=>{
+                    let gateway=SocketAddr::V4(SocketAddrV4::new(gateway,NATPMP_PORT));
+                    match natpmp_map(gateway,port,self.config.lease,self.config.timeout).await{
+                        Ok(mapping)=>return Ok(mapping),
+                        Err(e)=>errors.push(format!("nat-pmp: {}",e)),
+                    }
+                }
+                None=>errors.push("nat-pmp: no default gateway".to_string()),
+            }
+        }
+        Err(NetworkError::Nat(if errors.is_empty(){"no mapping protocol enabled".to_string()} else{errors.join("; ")}))
+    }
+
+    async fn map_upnp(&self,port:u16)->Result<PortMapping,NetworkError>{
+        let options=SearchOptions{timeout:Some(self.config.timeout),..SearchOptions::default()};
+        let gateway=search_gateway(options).await.map_err(|e| NetworkError::Nat(e.to_string()))?;
+        let local_ip=local_ip_towards(gateway.addr).await?;
+        let external_ip=gateway.get_external_ip().await.map_err(|e| NetworkError::Nat(e.to_string()))?;
+        gateway
+        .add_port(PortMappingProtocol::TCP,port,SocketAddr::new(local_ip,port),self.config.lease,DESCRIPTION)
+        .await
+        .map_err(|e| NetworkError::Nat(e.to_string()))?;
+        Ok(PortMapping{protocol:MappingProtocol::Upnp,external_addr:SocketAddr::new(external_ip,port),lease:self.config.lease})
+    }
+
+    /// Keep the port mapped and announced, renewing at half the lease; never returns
+    pub async fn run(self){
+        loop{
+            match self.map().await{
+                Ok(mapping)=>{
+                    self.network.set_external_addr(Some(mapping.external_addr));
+                    sleep(Duration::from_secs((mapping.lease/2).max(1) as u64)).await;
+                }
+                Err(error)=>{
+                    self.network.emit(NetworkEvent::PortMappingFailed{error});
+                    self.network.set_external_addr(None);
+                    sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    }
+}
+
+/// Our address on the interface that reaches `remote`
+async fn local_ip_towards(remote:SocketAddr)->Result<IpAddr,NetworkError>{
+    let socket=UdpSocket::bind(if remote.is_ipv4(){"0.0.0.0:0"} else{"[::]:0"}).await?;
+    socket.connect(remote).await?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Gateway of the IPv4 default route, from the kernel's routing table (Linux only)
+pub fn default_gateway()->Option<Ipv4Addr>{
+    let routes=std::fs::read_to_string("/proc/net/route").ok()?;
+    routes.lines().skip(1).find_map(|line|{
+        let fields:Vec<&str>=line.split_whitespace().collect();
+        // destination 0.0.0.0, gateway in little-endian hex
+        if fields.get(1)!=Some(&"00000000"){
+            return None
+        }
+        let gateway=u32::from_str_radix(fields.get(2)?,16).ok()?;
+        Some(Ipv4Addr::from(gateway.to_le_bytes()))
+    })
+}
+
+/// Send a NAT-PMP request, retrying with doubling waits (RFC 6886 section 3.1), and return
+/// the response after checking its opcode and result code
+async fn natpmp_request(socket:&UdpSocket,request:&[u8],wait:Duration)->Result<Vec<u8>,NetworkError>{
+    let mut delay=Duration::from_millis(250);
+    let mut waited=Duration::ZERO;
+    let mut buf=[0u8;16];
+    while waited<wait{
+        socket.send(request).await?;
+        if let Ok(received)=timeout(delay,socket.recv(&mut buf)).await{
+            let len=received?;
+            if len<8 || buf[0]!=NATPMP_VERSION || buf[1]!=request[1]+OP_RESPONSE{
+                return Err(NetworkError::Nat("malformed nat-pmp response".to_string()))
+            }
+            let result=u16::from_be_bytes([buf[2],buf[3]]);
+            if result!=0{
+                return Err(NetworkError::Nat(format!("gateway refused with result code {}",result)))
+            }
+            return Ok(buf[..len].to_vec())
+        }
+        waited+=delay;
+        delay*=2;
+    }
+    Err(NetworkError::Nat("gateway did not answer".to_string()))
+}
+
+/// Map TCP `port` through the NAT-PMP gateway at `gateway`
+pub async fn natpmp_map(gateway:SocketAddr,port:u16,lease:u32,wait:Duration)->Result<PortMapping,NetworkError>{
+    let socket=UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(gateway).await?;
+    let response=natpmp_request(&socket,&[NATPMP_VERSION,OP_EXTERNAL_ADDRESS],wait).await?;
+    let external_ip:[u8;4]=response.get(8..12).and_then(|b| b.try_into().ok()).ok_or_else(|| NetworkError::Nat("short nat-pmp response".to_string()))?;
+
+    let mut request=vec![NATPMP_VERSION,OP_MAP_TCP,0,0];
+    request.extend_from_slice(&port.to_be_bytes());
+    request.extend_from_slice(&port.to_be_bytes());
+    request.extend_from_slice(&lease.to_be_bytes());
+    let response=natpmp_request(&socket,&request,wait).await?;
+    if response.len()<16{
+        return Err(NetworkError::Nat("short nat-pmp response".to_string()))
+    }
+    let external_port=u16::from_be_bytes([response[10],response[11]]);
+    let lifetime=u32::from_be_bytes([response[12],response[13],response[14],response[15]]);
+    Ok(PortMapping{
+        protocol:MappingProtocol::NatPmp,
+        external_addr:SocketAddr::new(IpAddr::V4(Ipv4Addr::from(external_ip)),external_port),
+        lease:lifetime,
+    })
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    /// Minimal NAT-PMP gateway mapping every port to `external_port` on 203.0.113.7
+    async fn fake_gateway(external_port:u16)->SocketAddr{
+        let socket=UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr=socket.local_addr().unwrap();
+        tokio::spawn(async move {
+            let mut buf=[0u8;12];
+            while let Ok((len,from))=socket.recv_from(&mut buf).await{
+                let mut response=vec![0,buf[1]+OP_RESPONSE,0,0,0,0,0,42];
+                match (buf[1],len){
+                    (OP_EXTERNAL_ADDRESS,2)=>response.extend_from_slice(&[203,0,113,7]),
+                    (OP_MAP_TCP,12)=>{
+                        response.extend_from_slice(&buf[4..6]);
+                        response.extend_from_slice(&external_port.to_be_bytes());
+                        response.extend_from_slice(&buf[8..12]);
+                    }
+                    _=>response[3]=5,
+                }
+                socket.send_to(&response,from).await.unwrap();
+            }
+        });
+        addr
+    }
+
+    #[tokio::test]
+    async fn test_natpmp_mapping(){
+        let gateway=fake_gateway(40_000).await;
+        let mapping=natpmp_map(gateway,30333,7200,Duration::from_secs(2)).await.unwrap();
+        assert_eq!(
+            mapping,
+            PortMapping{protocol:MappingProtocol::NatPmp,external_addr:"203.0.113.7:40000".parse().unwrap(),lease:7200}
+        );
+    }
+
+    #[tokio::test]
+    async fn test_natpmp_silent_gateway(){
+        // bound, but never answers
+        let silent=UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let addr=silent.local_addr().unwrap();
+        let result=natpmp_map(addr,30333,3600,Duration::from_millis(300)).await;
+        assert!(matches!(result,Err(NetworkError::Nat(_))));
+    }
+}
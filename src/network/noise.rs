@@ -0,0 +1,295 @@
+// src/network/noise.rs
+
+//! Encrypted, authenticated transport
+//! - Every connection runs a Noise XX handshake (`NOISE_PARAMS`) with the node's X25519
+//!   static key, generated when the network starts
+//! - The handshake payloads carry each side's `Hello`, with its Ed25519 identity key and a
+//!   signature over its Noise static key, so the `PeerId` of a connection belongs to the
+//!   holder of the identity key and not just to whoever completed the handshake
+//! - Private networks set a pre-shared network key: the handshake becomes XXpsk0
+//!   (`PRIVATE_NOISE_PARAMS`), whose very first message only decrypts for holders of the
+//!   key, so outsiders are turned away before either side reveals anything
+//! - Afterwards each frame is encrypted in chunks of at most `MAX_CHUNK` bytes, every chunk
+//!   sent as a 2-byte big-endian length and the ciphertext
+
+use super::NetworkError;
+use super::identity::{NodeIdentity,verify_identity_signature};
+use super::wire::{Frame,Hello,MAX_FRAME_BYTES,decode_frame,encode_frame};
+use snow::params::NoiseParams;
+use snow::{Builder,StatelessTransportState};
+use std::sync::Arc;
+use tokio::io::{AsyncRead,AsyncReadExt,AsyncWrite,AsyncWriteExt};
+
+pub const NOISE_PARAMS:&str="Noise_XX_25519_ChaChaPoly_SHA256";
+
+/// Handshake of private networks, keyed with the network key
+pub const PRIVATE_NOISE_PARAMS:&str="Noise_XXpsk0_25519_ChaChaPoly_SHA256";
+
+/// Largest Noise message
+const MAX_NOISE_MESSAGE:usize=65535;
+
+/// Authentication tag added to every encrypted chunk
+const TAG_LEN:usize=16;
+
+/// Largest plaintext per encrypted chunk
+const MAX_CHUNK:usize=MAX_NOISE_MESSAGE-TAG_LEN;
+
+/// Domain separation for the identity signature over the Noise static key
+const STATIC_KEY_DOMAIN:&[u8]=b"netchain-noise-static";
+
+impl From<snow::Error> for NetworkError{
+    fn from(e:snow::Error)->Self{
+        NetworkError::Handshake(format!("noise: {}",e))
+    }
+}
+
+fn params(private:bool)->NoiseParams{
+    let params=if private{PRIVATE_NOISE_PARAMS} else{NOISE_PARAMS};
+    params.parse().expect("valid noise parameters")
+}
+
+/// The node's Noise static keypair
+pub struct NoiseKeys{
+    private:Vec<u8>,
+    public:Vec<u8>,
+}
+
+impl NoiseKeys{
+    pub fn generate()->Self{
+        let keypair=Builder::new(params(false)).generate_keypair().expect("noise keypair");
+        NoiseKeys{private:keypair.private,public:keypair.public}
+    }
+
+    pub fn public_key(&self)->&[u8]{
+        &self.public
+    }
+
+    /// Signature by `identity` binding it to this static key, for `Hello::signature`
+    pub fn sign_with(&self,identity:&NodeIdentity)->Vec<u8>{
+        identity.sign(&static_key_message(&self.public))
+    }
+}
+
+pub(super) fn static_key_message(static_key:&[u8])->Vec<u8>{
+    [STATIC_KEY_DOMAIN,static_key].concat()
+}
+
+async fn write_message<W:AsyncWrite+Unpin>(writer:&mut W,message:&[u8])->Result<(),NetworkError>{
+    writer.write_all(&(message.len() as u16).to_be_bytes()).await?;
+    writer.write_all(message).await?;
+    Ok(())
+}
+
+async fn read_message<R:AsyncRead+Unpin>(reader:&mut R)->Result<Vec<u8>,NetworkError>{
+    let mut len=[0u8;2];
+    reader.read_exact(&mut len).await?;
+    let mut message=vec![0u8;u16::from_be_bytes(len) as usize];
+    reader.read_exact(&mut message).await?;
+    Ok(message)
+}
+
+/// Run the XX handshake, trading `hello` for the peer's. The peer's `Hello` is only
+/// returned if its signature binds its identity key to the static key it proved it holds.
+/// With a `network_key` only peers holding the same key get through.
+pub async fn handshake<R,W>(
+    reader:&mut R,
+    writer:&mut W,
+    keys:&NoiseKeys,
+    network_key:Option<&[u8;32]>,
+    initiator:bool,
+    hello:&Hello,
+)->Result<(Hello,Arc<StatelessTransportState>),NetworkError>
+where
+    R:AsyncRead+Unpin,
+    W:AsyncWrite+Unpin,
+{
+    let mut builder=Builder::new(params(network_key.is_some())).local_private_key(&keys.private);
+    if let Some(key)=network_key{
+        builder=builder.psk(0,key);
+    }
+    let mut state=if initiator{builder.build_initiator()?} else{builder.build_responder()?};
+    let ours=encode_frame(&Frame::Hello(hello.clone()))?;
+    let mut buf=vec![0u8;MAX_NOISE_MESSAGE];
+    let mut payload=vec![0u8;MAX_NOISE_MESSAGE];
+    // -> e; <- e, ee, s, es + responder hello; -> s, se + initiator hello
+    let theirs=if initiator{
+        let len=state.write_message(&[],&mut buf)?;
+        write_message(writer,&buf[..len]).await?;
+        let len=state.read_message(&read_message(reader).await?,&mut payload)?;
+        let theirs=payload[..len].to_vec();
+        let len=state.write_message(&ours,&mut buf)?;
+        write_message(writer,&buf[..len]).await?;
+        theirs
+    } else{
+        state.read_message(&read_message(reader).await?,&mut payload)?;
+        let len=state.write_message(&ours,&mut buf)?;
+        write_message(writer,&buf[..len]).await?;
+        let len=state.read_message(&read_message(reader).await?,&mut payload)?;
+        payload[..len].to_vec()
+    };
+    writer.flush().await?;
+    let Frame::Hello(remote)=decode_frame(&theirs)? else{
+        return Err(NetworkError::Handshake("expected hello".to_string()))
+    };
+    let remote_static=state.get_remote_static().ok_or_else(|| NetworkError::Handshake("no static key".to_string()))?;
+    if !verify_identity_signature(&remote.public_key,&static_key_message(remote_static),&remote.signature){
+        return Err(NetworkError::Handshake("identity key not bound to the noise session".to_string()))
+    }
+    Ok((remote,Arc::new(state.into_stateless_transport_mode()?)))
+}
+
+/// Sending half of an encrypted connection
+pub struct SecureWriter<W>{
+    writer:W,
+    cipher:Arc<StatelessTransportState>,
+    nonce:u64,
+}
+
+impl<W:AsyncWrite+Unpin> SecureWriter<W>{
+    pub fn new(writer:W,cipher:Arc<StatelessTransportState>)->Self{
+        SecureWriter{writer,cipher,nonce:0}
+    }
+
+    /// Encrypt and send one frame: its 4-byte length and encoding, split into chunks
+    pub async fn write_frame(&mut self,frame:&Frame)->Result<(),NetworkError>{
+        let bytes=encode_frame(frame)?;
+        let plain=[(bytes.len() as u32).to_be_bytes().as_slice(),&bytes].concat();
+        let mut buf=vec![0u8;MAX_NOISE_MESSAGE];
+        for chunk in plain.chunks(MAX_CHUNK){
+            let len=self.cipher.write_message(self.nonce,chunk,&mut buf)?;
+            self.nonce+=1;
+            write_message(&mut self.writer,&buf[..len]).await?;
+        }
+        self.writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Receiving half of an encrypted connection
+pub struct SecureReader<R>{
+    reader:R,
+    cipher:Arc<StatelessTransportState>,
+    nonce:u64,
+}
+
+impl<R:AsyncRead+Unpin> SecureReader<R>{
+    pub fn new(reader:R,cipher:Arc<StatelessTransportState>)->Self{
+        SecureReader{reader,cipher,nonce:0}
+    }
+
+    async fn read_chunk(&mut self,plain:&mut Vec<u8>)->Result<(),NetworkError>{
+        let message=read_message(&mut self.reader).await?;
+        let mut buf=vec![0u8;message.len()];
+        let len=self.cipher.read_message(self.nonce,&message,&mut buf)?;
+        self.nonce+=1;
+        plain.extend_from_slice(&buf[..len]);
+        Ok(())
+    }
+
+    pub async fn read_frame(&mut self)->Result<Frame,NetworkError>{
+        let mut plain=Vec::new();
+        while plain.len()<4{
+            self.read_chunk(&mut plain).await?;
+        }
+        let len=u32::from_be_bytes(plain[..4].try_into().expect("4 bytes")) as usize;
+        if len>MAX_FRAME_BYTES{
+            return Err(NetworkError::FrameTooLarge(len))
+        }
+        while plain.len()<4+len{
+            self.read_chunk(&mut plain).await?;
+        }
+        decode_frame(&plain[4..4+len])
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::network::Topic;
+    use crate::network::wire::PROTOCOL_VERSION;
+    use tokio::io::duplex;
+
+    fn hello(identity:&NodeIdentity,keys:&NoiseKeys)->Hello{
+        Hello{
+            protocol_version:PROTOCOL_VERSION,
+            min_protocol_version:PROTOCOL_VERSION,
+            network_id:"netchain".to_string(),
+            genesis_hash:"0".repeat(64),
+            height:0,
+            capabilities:Vec::new(),
+            public_key:identity.public_key().to_vec(),
+            signature:keys.sign_with(identity),
+            listen_port:0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_handshake_and_encrypted_frames(){
+        let (a,b)=duplex(1<<20);
+        let (mut a_read,mut a_write)=tokio::io::split(a);
+        let (mut b_read,mut b_write)=tokio::io::split(b);
+        let (a_id,b_id)=(NodeIdentity::generate(),NodeIdentity::generate());
+        let (a_keys,b_keys)=(NoiseKeys::generate(),NoiseKeys::generate());
+        let (a_hello,b_hello)=(hello(&a_id,&a_keys),hello(&b_id,&b_keys));
+        let (a_side,b_side)=tokio::join!(
+            handshake(&mut a_read,&mut a_write,&a_keys,None,true,&a_hello),
+            handshake(&mut b_read,&mut b_write,&b_keys,None,false,&b_hello),
+        );
+        let ((seen_by_a,a_cipher),(seen_by_b,b_cipher))=(a_side.unwrap(),b_side.unwrap());
+        assert_eq!((seen_by_a,seen_by_b),(b_hello,a_hello));
+
+        // frames bigger than one noise message are split and put back together
+        let frame=Frame::Message{topic:Topic::Blocks,payload:vec![7u8;200_000]};
+        let mut writer=SecureWriter::new(a_write,a_cipher);
+        let mut reader=SecureReader::new(b_read,b_cipher);
+        writer.write_frame(&frame).await.unwrap();
+        writer.write_frame(&Frame::Message{topic:Topic::Sync,payload:vec![1]}).await.unwrap();
+        assert_eq!(reader.read_frame().await.unwrap(),frame);
+        assert_eq!(reader.read_frame().await.unwrap(),Frame::Message{topic:Topic::Sync,payload:vec![1]});
+    }
+
+    #[tokio::test]
+    async fn test_identity_must_sign_the_session_key(){
+        let (a,b)=duplex(1<<16);
+        let (mut a_read,mut a_write)=tokio::io::split(a);
+        let (mut b_read,mut b_write)=tokio::io::split(b);
+        let (a_keys,b_keys)=(NoiseKeys::generate(),NoiseKeys::generate());
+        // a claims an identity that signed some other session's key
+        let stolen=NodeIdentity::generate();
+        let a_hello=hello(&stolen,&NoiseKeys::generate());
+        let b_hello=hello(&NodeIdentity::generate(),&b_keys);
+        let (_,b_side)=tokio::join!(
+            handshake(&mut a_read,&mut a_write,&a_keys,None,true,&a_hello),
+            handshake(&mut b_read,&mut b_write,&b_keys,None,false,&b_hello),
+        );
+        assert!(matches!(b_side,Err(NetworkError::Handshake(_))));
+    }
+
+    /// Handshake between two fresh nodes with these network keys; each side hangs up when
+    /// done, as the network does on failure
+    async fn keyed_handshake(a_key:Option<&[u8;32]>,b_key:Option<&[u8;32]>)->(Result<Hello,NetworkError>,Result<Hello,NetworkError>){
+        let (a,b)=duplex(1<<16);
+        let (mut a_read,mut a_write)=tokio::io::split(a);
+        let (mut b_read,mut b_write)=tokio::io::split(b);
+        let (a_keys,b_keys)=(NoiseKeys::generate(),NoiseKeys::generate());
+        let a_hello=hello(&NodeIdentity::generate(),&a_keys);
+        let b_hello=hello(&NodeIdentity::generate(),&b_keys);
+        let (a_side,b_side)=tokio::join!(
+            async move {handshake(&mut a_read,&mut a_write,&a_keys,a_key,true,&a_hello).await},
+            async move {handshake(&mut b_read,&mut b_write,&b_keys,b_key,false,&b_hello).await},
+        );
+        (a_side.map(|(hello,_)| hello),b_side.map(|(hello,_)| hello))
+    }
+
+    #[tokio::test]
+    async fn test_network_key_required(){
+        let key=[7u8;32];
+        let (seen_by_a,seen_by_b)=keyed_handshake(Some(&key),Some(&key)).await;
+        assert!(seen_by_a.is_ok() && seen_by_b.is_ok());
+        // without the key, or with another, nobody gets the other's hello
+        for (a_key,b_key) in [(None,Some(&key)),(Some(&[8u8;32]),Some(&key)),(Some(&key),None)]{
+            let (seen_by_a,seen_by_b)=keyed_handshake(a_key,b_key).await;
+            assert!(seen_by_a.is_err() && seen_by_b.is_err());
+        }
+    }
+}
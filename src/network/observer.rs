@@ -0,0 +1,253 @@
+// src/network/observer.rs
+
+//! Peer metrics observed from real traffic
+//! - Latency: QUIC's smoothed RTT on QUIC links (`Network::sample_links`), otherwise the
+//!   response time of header and block requests
+//! - Upload: throughput of the block, header and body transfers a peer sends us, once they
+//!   are big enough (`MIN_TRANSFER_BYTES`) not to just measure latency
+//! - Stability: share of our requests a peer answered in time, or QUIC's packet delivery
+//! - Uptime: heartbeats seen from the validator (`heartbeat::UptimeTracker`)
+//!
+//! `observe_into` turns this node's view of each connected validator into an observation
+//! for `MetricAggregator`, so the pool handed to `PoiScorer` comes from what peers saw
+//! rather than hand-filled values. Download can't be seen passively and keeps the
+//! challenge-attested figure of the validator's report.
+
+use super::PeerInfo;
+use super::identity::PeerId;
+use super::quic::LinkStats;
+use crate::aggregation::{MetricAggregator,median};
+use crate::challenge::throughput_mbps;
+use crate::consensus::NodeMetrics;
+use crate::heartbeat::UptimeTracker;
+use crate::netprobe::{NetProbe,ProbeConfig};
+use crate::stability::StabilityTracker;
+use crate::validator::ValidatorRegistry;
+use std::collections::{HashMap,VecDeque};
+use std::net::SocketAddr;
+use std::sync::{Arc,Mutex};
+use std::time::Duration;
+
+/// Smallest transfer counted as a throughput sample
+pub const MIN_TRANSFER_BYTES:usize=64*1024;
+
+/// Samples kept per peer and metric
+pub const OBSERVATION_WINDOW:usize=50;
+
+/// Observed figures for one peer; `None` where nothing was measured yet
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Default)]
+pub struct ObservedMetrics{
+    pub latency_ms:Option<u64>,
+    pub upload_mbps:Option<u64>,
+    pub stability_bps:Option<u64>,
+}
+
+impl ObservedMetrics{
+    /// Overwrite the fields of `metrics` that were observed; false if none were
+    pub fn apply_to(&self,metrics:&mut NodeMetrics)->bool{
+        let mut applied=false;
+        for (observed,field) in [
+            (self.latency_ms,&mut metrics.latency_ms),
+            (self.upload_mbps,&mut metrics.upload_mbps),
+            (self.stability_bps,&mut metrics.stability_bps),
+        ]{
+            if let Some(value)=observed{
+                *field=value;
+                applied=true;
+            }
+        }
+        applied
+    }
+}
+
+struct Observations{
+    latency:NetProbe,
+    stability:StabilityTracker,
+    /// Peer -> recent throughput samples in Mbps
+    throughput:HashMap<String,VecDeque<u64>>,
+    /// Latest QUIC measurements, which take precedence over request timings
+    links:HashMap<String,LinkStats>,
+}
+
+/// Records what peers' traffic shows about them; cheap to clone
+#[derive(Clone)]
+pub struct PeerObserver{
+    inner:Arc<Mutex<Observations>>,
+}
+
+impl Default for PeerObserver{
+    fn default()->Self{
+        let latency=NetProbe::new(ProbeConfig{window:OBSERVATION_WINDOW,..ProbeConfig::default()});
+        PeerObserver{
+            inner:Arc::new(Mutex::new(Observations{
+                latency,
+                stability:StabilityTracker::new(OBSERVATION_WINDOW),
+                throughput:HashMap::new(),
+                links:HashMap::new(),
+            })),
+        }
+    }
+}
+
+impl PeerObserver{
+    /// `peer` answered a request of ours with `bytes` after `elapsed`
+    pub fn record_response(&self,peer:&PeerId,bytes:usize,elapsed:Duration){
+        let peer=peer.to_string();
+        let mut inner=self.inner.lock().expect("observer lock poisoned");
+        inner.stability.record_success(&peer);
+        if bytes>=MIN_TRANSFER_BYTES{
+            let samples=inner.throughput.entry(peer).or_default();
+            samples.push_back(throughput_mbps(bytes as u64,elapsed.as_micros() as u64));
+            while samples.len()>OBSERVATION_WINDOW{
+                samples.pop_front();
+            }
+        } else{
+            // small responses are dominated by the round trip
+            inner.latency.record(&peer,Some(elapsed));
+        }
+    }
+
+    /// `peer` left a request of ours unanswered
+    pub fn record_timeout(&self,peer:&PeerId){
+        self.inner.lock().expect("observer lock poisoned").stability.record_failure(&peer.to_string());
+    }
+
+    /// Latest QUIC measurements of the link to `peer`
+    pub fn record_link(&self,peer:&PeerId,stats:LinkStats){
+        self.inner.lock().expect("observer lock poisoned").links.insert(peer.to_string(),stats);
+    }
+
+    pub fn observed(&self,peer:&PeerId)->ObservedMetrics{
+        let peer=peer.to_string();
+        let inner=self.inner.lock().expect("observer lock poisoned");
+        let link=inner.links.get(&peer);
+        ObservedMetrics{
+            latency_ms:link.map(LinkStats::latency_ms).or_else(|| inner.latency.average_rtt_ms(&peer)),
+            upload_mbps:inner.throughput.get(&peer).and_then(|samples| median(&mut samples.iter().copied().collect::<Vec<u64>>())),
+            stability_bps:link.map(LinkStats::stability_bps).or_else(|| inner.stability.success_bps(&peer)),
+        }
+    }
+
+    /// Record our view of every connected validator in `aggregator`, as `observer`.
+    /// `reports` (validator -> reported metrics, e.g. `MetricReport::effective_metrics`)
+    /// supply what we can't observe; validators without a report are skipped.
+    pub fn observe_into(
+        &self,
+        aggregator:&mut MetricAggregator,
+        observer:&str,
+        validators:&HashMap<PeerId,String>,
+        reports:&HashMap<String,NodeMetrics>,
+        uptime:&UptimeTracker,
+        epoch:u64,
+    )->usize{
+        let mut observed=0;
+        for (peer,validator) in validators{
+            let Some(mut metrics)=reports.get(validator).cloned() else{
+                continue
+            };
+            self.observed(peer).apply_to(&mut metrics);
+            uptime.apply_to(&mut metrics,epoch);
+            if aggregator.observe(observer,metrics){
+                observed+=1;
+            }
+        }
+        observed
+    }
+}
+
+/// Connected peers that are registered validators, matched by the endpoint they registered
+pub fn validator_peers(peers:&[PeerInfo],registry:&ValidatorRegistry)->HashMap<PeerId,String>{
+    let endpoints:HashMap<SocketAddr,&str>=registry
+    .iter()
+    .filter_map(|info| Some((info.endpoint.parse().ok()?,info.address.as_str())))
+    .collect();
+    peers
+    .iter()
+    .filter_map(|peer| {
+        let validator=endpoints.get(&peer.listen_addr?)?;
+        Some((peer.peer_id.clone(),validator.to_string()))
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::aggregation::AggregationMethod;
+    use crate::heartbeat::Heartbeat;
+    use crate::network::identity::NodeIdentity;
+    use crate::network::Transport;
+    use crate::network::wire::PROTOCOL_VERSION;
+    use crate::transaction::generate_ed25519_keypair;
+    use base64::{Engine as _,engine::general_purpose};
+
+    fn reported(node_id:&str)->NodeMetrics{
+        NodeMetrics{
+            node_id:node_id.to_string(),
+            upload_mbps:1_000,
+            download_mbps:500,
+            latency_ms:1,
+            uptime_bps:10_000,
+            stability_bps:10_000,
+        }
+    }
+
+    #[test]
+    fn test_observations_from_traffic(){
+        let observer=PeerObserver::default();
+        let peer=NodeIdentity::generate().peer_id();
+        assert_eq!(observer.observed(&peer),ObservedMetrics::default());
+
+        observer.record_response(&peer,200,Duration::from_millis(40));
+        observer.record_response(&peer,200,Duration::from_millis(60));
+        // 1 MB in 400 ms is 20 Mbps
+        observer.record_response(&peer,1_000_000,Duration::from_millis(400));
+        observer.record_timeout(&peer);
+        let observed=observer.observed(&peer);
+        assert_eq!(observed,ObservedMetrics{latency_ms:Some(50),upload_mbps:Some(20),stability_bps:Some(7_500)});
+
+        // QUIC's own measurements win over request timings
+        let stats=LinkStats{rtt:Duration::from_millis(12),min_rtt:Duration::from_millis(10),sent_packets:1_000,lost_packets:20,congestion_events:0};
+        observer.record_link(&peer,stats);
+        let mut metrics=reported("v");
+        assert!(observer.observed(&peer).apply_to(&mut metrics));
+        assert_eq!((metrics.latency_ms,metrics.upload_mbps,metrics.stability_bps,metrics.download_mbps),(12,20,9_800,500));
+    }
+
+    #[test]
+    fn test_validators_observed_into_the_pool(){
+        let keypair=generate_ed25519_keypair();
+        let mut registry=ValidatorRegistry::new();
+        registry
+        .register(
+            "val".to_string(),
+            general_purpose::STANDARD.encode(keypair.public.to_bytes()),
+            general_purpose::STANDARD.encode(schnorrkel::Keypair::generate().public.to_bytes()),
+            "10.0.0.7:30333".to_string(),
+        )
+        .unwrap();
+        let info=|listen_addr:&str|PeerInfo{
+            peer_id:NodeIdentity::generate().peer_id(),
+            addr:"10.0.0.7:51000".parse().unwrap(),
+            listen_addr:Some(listen_addr.parse().unwrap()),
+            outbound:false,
+            protocol_version:PROTOCOL_VERSION,
+            height:0,
+            capabilities:Vec::new(),
+            transport:Transport::Tcp,
+        };
+        let (validator,other)=(info("10.0.0.7:30333"),info("10.0.0.8:30333"));
+        let validators=validator_peers(&[validator.clone(),other],&registry);
+        assert_eq!(validators,HashMap::from([(validator.peer_id.clone(),"val".to_string())]));
+
+        let observer=PeerObserver::default();
+        observer.record_response(&validator.peer_id,100,Duration::from_millis(80));
+        let mut uptime=UptimeTracker::new(4).unwrap();
+        uptime.record(&Heartbeat::sign("val".to_string(),3,0,&keypair),&registry).unwrap();
+        let reports=HashMap::from([("val".to_string(),reported("val"))]);
+        let mut aggregator=MetricAggregator::new(AggregationMethod::Median,1);
+        assert_eq!(observer.observe_into(&mut aggregator,"me",&validators,&reports,&uptime,3),1);
+        let pooled=aggregator.aggregate("val").unwrap();
+        assert_eq!((pooled.latency_ms,pooled.uptime_bps,pooled.upload_mbps),(80,2_500,1_000));
+    }
+}
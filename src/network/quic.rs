@@ -0,0 +1,186 @@
+// src/network/quic.rs
+
+//! QUIC transport
+//! - With `NetworkConfig::quic` a node also listens for QUIC on the UDP port numbered like
+//!   its TCP listener, so a peer's `listen_addr` works for either transport
+//! - Each connection carries one bidirectional stream running the same Noise handshake and
+//!   frames as TCP. The TLS layer QUIC requires uses a throwaway self-signed certificate
+//!   that nobody verifies: peers are authenticated by Noise, as on every transport
+//! - QUIC's own round-trip and loss accounting is exposed as `LinkStats`, a live measurement
+//!   of the latency and stability inputs of PoI (see `consensus::NodeMetrics`)
+
+use super::identity::PeerId;
+use super::{Link,Network,NetworkError};
+use quinn::crypto::rustls::QuicClientConfig;
+use quinn::{ClientConfig,Connection,ConnectionStats,Endpoint,Incoming,ServerConfig,TransportConfig};
+use rustls::DigitallySignedStruct;
+use rustls::client::danger::{HandshakeSignatureValid,ServerCertVerified,ServerCertVerifier};
+use rustls::crypto::{CryptoProvider,verify_tls12_signature,verify_tls13_signature};
+use rustls::pki_types::{CertificateDer,PrivatePkcs8KeyDer,ServerName,UnixTime};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Name in the self-signed certificate; never checked
+const SERVER_NAME:&str="netchain";
+
+/// Keep-alives keep NAT bindings open and the RTT estimate fresh on idle links
+const KEEP_ALIVE:Duration=Duration::from_secs(10);
+
+const IDLE_TIMEOUT:Duration=Duration::from_secs(30);
+
+impl From<quinn::ConnectionError> for NetworkError{
+    fn from(e:quinn::ConnectionError)->Self{
+        NetworkError::Quic(e.to_string())
+    }
+}
+
+impl From<quinn::ConnectError> for NetworkError{
+    fn from(e:quinn::ConnectError)->Self{
+        NetworkError::Quic(e.to_string())
+    }
+}
+
+/// Link quality of a QUIC connection, as measured by the QUIC stack
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct LinkStats{
+    /// Smoothed round-trip time
+    pub rtt:Duration,
+    pub min_rtt:Duration,
+    pub sent_packets:u64,
+    pub lost_packets:u64,
+    pub congestion_events:u64,
+}
+
+impl LinkStats{
+    fn of(stats:&ConnectionStats)->Self{
+        LinkStats{
+            rtt:stats.path.rtt,
+            min_rtt:stats.path.min_rtt,
+            sent_packets:stats.path.sent_packets,
+            lost_packets:stats.path.lost_packets,
+            congestion_events:stats.path.congestion_events,
+        }
+    }
+
+    pub fn latency_ms(&self)->u64{
+        self.rtt.as_millis() as u64
+    }
+
+    /// Share of packets delivered, in basis points (10_000 before anything was sent)
+    pub fn stability_bps(&self)->u64{
+        if self.sent_packets==0{
+            return 10_000
+        }
+        let delivered=self.sent_packets.saturating_sub(self.lost_packets);
+        (delivered as u128*10_000/self.sent_packets as u128) as u64
+    }
+}
+
+pub(super) fn link_stats(connection:&Connection)->LinkStats{
+    LinkStats::of(&connection.stats())
+}
+
+/// Accepts whatever certificate the server shows; Noise authenticates the peer
+#[derive(Debug)]
+struct AnyCertificate(Arc<CryptoProvider>);
+
+impl ServerCertVerifier for AnyCertificate{
+    fn verify_server_cert(
+        &self,
+        _end_entity:&CertificateDer<'_>,
+        _intermediates:&[CertificateDer<'_>],
+        _server_name:&ServerName<'_>,
+        _ocsp_response:&[u8],
+        _now:UnixTime,
+    )->Result<ServerCertVerified,rustls::Error>{
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message:&[u8],
+        cert:&CertificateDer<'_>,
+        dss:&DigitallySignedStruct,
+    )->Result<HandshakeSignatureValid,rustls::Error>{
+        verify_tls12_signature(message,cert,dss,&self.0.signature_verification_algorithms)
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message:&[u8],
+        cert:&CertificateDer<'_>,
+        dss:&DigitallySignedStruct,
+    )->Result<HandshakeSignatureValid,rustls::Error>{
+        verify_tls13_signature(message,cert,dss,&self.0.signature_verification_algorithms)
+    }
+
+    fn supported_verify_schemes(&self)->Vec<rustls::SignatureScheme>{
+        self.0.signature_verification_algorithms.supported_schemes()
+    }
+}
+
+fn transport_config()->Arc<TransportConfig>{
+    let mut transport=TransportConfig::default();
+    transport.keep_alive_interval(Some(KEEP_ALIVE));
+    transport.max_idle_timeout(Some(IDLE_TIMEOUT.try_into().expect("idle timeout fits a varint")));
+    Arc::new(transport)
+}
+
+fn quic_error(e:impl std::fmt::Display)->NetworkError{
+    NetworkError::Quic(e.to_string())
+}
+
+/// UDP endpoint that both accepts and dials QUIC connections
+pub(super) fn endpoint(addr:SocketAddr)->Result<Endpoint,NetworkError>{
+    let provider=Arc::new(rustls::crypto::ring::default_provider());
+    let certified=rcgen::generate_simple_self_signed(vec![SERVER_NAME.to_string()]).map_err(quic_error)?;
+    let key=PrivatePkcs8KeyDer::from(certified.key_pair.serialize_der());
+    let server_crypto=rustls::ServerConfig::builder_with_provider(provider.clone())
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .map_err(quic_error)?
+    .with_no_client_auth()
+    .with_single_cert(vec![certified.cert.der().clone()],key.into())
+    .map_err(quic_error)?;
+    let mut server=ServerConfig::with_crypto(Arc::new(quinn::crypto::rustls::QuicServerConfig::try_from(server_crypto).map_err(quic_error)?));
+    server.transport_config(transport_config());
+
+    let client_crypto=rustls::ClientConfig::builder_with_provider(provider.clone())
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .map_err(quic_error)?
+    .dangerous()
+    .with_custom_certificate_verifier(Arc::new(AnyCertificate(provider)))
+    .with_no_client_auth();
+    let mut client=ClientConfig::new(Arc::new(QuicClientConfig::try_from(client_crypto).map_err(quic_error)?));
+    client.transport_config(transport_config());
+
+    let mut endpoint=Endpoint::server(server,addr)?;
+    endpoint.set_default_client_config(client);
+    Ok(endpoint)
+}
+
+/// Finish an inbound QUIC connection and run the peer handshake on its first stream
+pub(super) async fn accept(network:&Network,incoming:Incoming)->Result<PeerId,NetworkError>{
+    let connection=incoming.await?;
+    let (send,recv)=connection.accept_bi().await?;
+    let addr=connection.remote_address();
+    network.establish(tokio::io::join(recv,send),addr,false,Link::Quic(connection)).await
+}
+
+pub(super) async fn dial(network:&Network,endpoint:&Endpoint,addr:SocketAddr)->Result<PeerId,NetworkError>{
+    let connection=endpoint.connect(addr,SERVER_NAME)?.await?;
+    let (send,recv)=connection.open_bi().await?;
+    network.establish(tokio::io::join(recv,send),addr,true,Link::Quic(connection)).await
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_stability_from_loss(){
+        let stats=LinkStats{rtt:Duration::from_millis(42),min_rtt:Duration::from_millis(40),sent_packets:2_000,lost_packets:10,congestion_events:1};
+        assert_eq!((stats.latency_ms(),stats.stability_bps()),(42,9_950));
+        assert_eq!(LinkStats{sent_packets:0,lost_packets:0,..stats}.stability_bps(),10_000);
+    }
+}
@@ -0,0 +1,203 @@
+// src/network/ratelimit.rs
+
+//! Per-peer rate limits
+//! - Every connection has token buckets for inbound messages and bytes on any topic, plus
+//!   one per `RateClass` of costly requests: gossiped transactions, block requests (gossip
+//!   `Request`, sync `GetHeaders` / `GetBodies`) and bandwidth challenges
+//! - Payloads over their topic's size cap are dropped before anyone decodes them
+//! - A message over a limit is dropped and its sender reported for `Misbehavior::Spam`, so
+//!   a peer that keeps flooding gets banned (see `reputation`)
+
+use super::Topic;
+use super::wire::MAX_FRAME_BYTES;
+use std::collections::HashMap;
+use std::time::{Duration,Instant};
+
+/// Sustained rate and burst of a token bucket
+#[derive(Debug,Clone,Copy,PartialEq)]
+pub struct RateLimit{
+    /// Tokens added per second
+    pub per_second:f64,
+    /// Bucket size: what a peer can spend at once after being quiet
+    pub burst:f64,
+}
+
+impl RateLimit{
+    pub const fn new(per_second:f64,burst:f64)->Self{
+        RateLimit{per_second,burst}
+    }
+}
+
+#[derive(Debug,Clone)]
+pub struct TokenBucket{
+    limit:RateLimit,
+    tokens:f64,
+    updated_at:Instant,
+}
+
+impl TokenBucket{
+    /// A full bucket
+    pub fn new(limit:RateLimit,now:Instant)->Self{
+        TokenBucket{limit,tokens:limit.burst,updated_at:now}
+    }
+
+    /// Spend `cost` tokens if the bucket holds them
+    pub fn try_take(&mut self,cost:f64,now:Instant)->bool{
+        let elapsed=now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens=(self.tokens+elapsed*self.limit.per_second).min(self.limit.burst);
+        self.updated_at=now;
+        if self.tokens<cost{
+            return false
+        }
+        self.tokens-=cost;
+        true
+    }
+
+    /// Spend `cost` tokens even if that runs the bucket into debt; returns how long until
+    /// the debt is paid back, which a throttled sender waits out
+    pub fn reserve(&mut self,cost:f64,now:Instant)->Duration{
+        let elapsed=now.saturating_duration_since(self.updated_at).as_secs_f64();
+        self.tokens=(self.tokens+elapsed*self.limit.per_second).min(self.limit.burst)-cost;
+        self.updated_at=now;
+        if self.tokens>=0.0{
+            return Duration::ZERO
+        }
+        Duration::try_from_secs_f64(-self.tokens/self.limit.per_second).unwrap_or(Duration::MAX)
+    }
+}
+
+/// Requests that cost the receiver enough to have their own budget
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Hash)]
+pub enum RateClass{
+    /// Transactions gossiped to us, each checked and maybe relayed
+    Transactions,
+    /// Requests for blocks, headers or bodies we have to read and send
+    BlockRequests,
+    /// Bandwidth challenges we are asked to answer (see `challenge`)
+    Challenges,
+}
+
+#[derive(Debug,Clone)]
+pub struct RateLimitConfig{
+    /// Inbound messages on any topic
+    pub messages:RateLimit,
+    /// Inbound payload bytes on any topic
+    pub bytes:RateLimit,
+    pub transactions:RateLimit,
+    pub block_requests:RateLimit,
+    pub challenges:RateLimit,
+    /// Largest payload accepted on `Topic::Transactions`
+    pub max_transaction_bytes:usize,
+    /// Largest payload accepted on `Topic::Discovery`
+    pub max_discovery_bytes:usize,
+}
+
+impl Default for RateLimitConfig{
+    fn default()->Self{
+        RateLimitConfig{
+            messages:RateLimit::new(500.0,2_000.0),
+            bytes:RateLimit::new(16.0*1024.0*1024.0,2.0*MAX_FRAME_BYTES as f64),
+            transactions:RateLimit::new(100.0,500.0),
+            block_requests:RateLimit::new(20.0,100.0),
+            challenges:RateLimit::new(1.0/60.0,2.0),
+            max_transaction_bytes:128*1024,
+            max_discovery_bytes:64*1024,
+        }
+    }
+}
+
+impl RateLimitConfig{
+    /// Largest payload accepted on `topic`; blocks, consensus proposals, sync responses
+    /// and snapshot chunks may use a whole frame
+    pub fn max_payload(&self,topic:Topic)->usize{
+        match topic{
+            Topic::Transactions=>self.max_transaction_bytes,
+            Topic::Discovery=>self.max_discovery_bytes,
+            Topic::Blocks|Topic::Consensus|Topic::Sync|Topic::State=>MAX_FRAME_BYTES,
+        }
+    }
+
+    fn limit(&self,class:RateClass)->RateLimit{
+        match class{
+            RateClass::Transactions=>self.transactions,
+            RateClass::BlockRequests=>self.block_requests,
+            RateClass::Challenges=>self.challenges,
+        }
+    }
+}
+
+/// The buckets of one peer
+#[derive(Debug,Clone)]
+pub struct PeerLimiter{
+    config:RateLimitConfig,
+    messages:TokenBucket,
+    bytes:TokenBucket,
+    classes:HashMap<RateClass,TokenBucket>,
+}
+
+impl PeerLimiter{
+    pub fn new(config:RateLimitConfig,now:Instant)->Self{
+        let classes=[RateClass::Transactions,RateClass::BlockRequests,RateClass::Challenges]
+        .into_iter()
+        .map(|class| (class,TokenBucket::new(config.limit(class),now)))
+        .collect();
+        PeerLimiter{
+            messages:TokenBucket::new(config.messages,now),
+            bytes:TokenBucket::new(config.bytes,now),
+            classes,
+            config,
+        }
+    }
+
+    /// Whether an inbound `len`-byte payload on `topic` is within the limits; spends from
+    /// the message and byte budgets, and the transaction budget on `Topic::Transactions`
+    pub fn admit(&mut self,topic:Topic,len:usize,now:Instant)->bool{
+        if len>self.config.max_payload(topic){
+            return false
+        }
+        if !self.messages.try_take(1.0,now) || !self.bytes.try_take(len as f64,now){
+            return false
+        }
+        topic!=Topic::Transactions || self.allow(RateClass::Transactions,now)
+    }
+
+    /// Spend one request of `class`
+    pub fn allow(&mut self,class:RateClass,now:Instant)->bool{
+        self.classes.get_mut(&class).is_some_and(|bucket| bucket.try_take(1.0,now))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_bucket_refills_up_to_burst(){
+        let start=Instant::now();
+        let mut bucket=TokenBucket::new(RateLimit::new(2.0,3.0),start);
+        assert!((0..3).all(|_| bucket.try_take(1.0,start)));
+        assert!(!bucket.try_take(1.0,start));
+        assert!(bucket.try_take(1.0,start+Duration::from_millis(500)));
+        assert!(!bucket.try_take(1.0,start+Duration::from_millis(500)));
+        // a long pause only refills to the burst
+        let later=start+Duration::from_secs(60);
+        assert!((0..3).all(|_| bucket.try_take(1.0,later)));
+        assert!(!bucket.try_take(1.0,later));
+    }
+
+    #[test]
+    fn test_peer_limiter_caps_size_and_classes(){
+        let now=Instant::now();
+        let config=RateLimitConfig{transactions:RateLimit::new(0.0,2.0),..RateLimitConfig::default()};
+        let mut limiter=PeerLimiter::new(config,now);
+        assert!(!limiter.admit(Topic::Transactions,128*1024+1,now));
+        assert!(limiter.admit(Topic::Transactions,100,now));
+        assert!(limiter.admit(Topic::Transactions,100,now));
+        assert!(!limiter.admit(Topic::Transactions,100,now));
+        // other topics have their own budgets
+        assert!(limiter.admit(Topic::Blocks,MAX_FRAME_BYTES,now));
+        assert!(limiter.allow(RateClass::Challenges,now));
+        assert!(limiter.allow(RateClass::Challenges,now));
+        assert!(!limiter.allow(RateClass::Challenges,now));
+    }
+}
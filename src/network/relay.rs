@@ -0,0 +1,453 @@
+// src/network/relay.rs
+
+//! Circuit relays and hole punching for nodes that can't accept connections
+//! - A node behind NAT keeps a reservation on a relay: a control connection over which the
+//!   relay announces incoming circuits
+//! - A dialer asks the relay for a circuit to a reserved peer; the target opens a second
+//!   connection to the relay to accept it, the relay splices the two, and both ends run the
+//!   usual Noise handshake through it, so the relay only sees ciphertext and can't pose as
+//!   either side
+//! - Before falling back to the circuit both sides may punch a hole: the relay tells each
+//!   the other's observed address, and both open TCP to it at once from the port they reach
+//!   the relay from, which gets through NATs that keep a port's mapping for every destination
+//! - Relay control messages are `RelayMessage`s, canonical-encoded behind a 4-byte
+//!   big-endian length, in the clear
+
+use super::identity::PeerId;
+use super::{Link,Network,NetworkError,NetworkEvent};
+use crate::canonical;
+use serde::{Deserialize,Serialize};
+use std::collections::HashMap;
+use std::net::{IpAddr,SocketAddr};
+use std::sync::atomic::{AtomicU64,AtomicUsize,Ordering};
+use std::sync::{Arc,Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead,AsyncReadExt,AsyncWrite,AsyncWriteExt,copy_bidirectional};
+use tokio::net::{TcpListener,TcpSocket,TcpStream};
+use tokio::sync::{mpsc,oneshot};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant,sleep,timeout};
+
+/// Largest relay control message
+const MAX_MESSAGE_BYTES:usize=4096;
+
+/// How long the relay holds a circuit open for the target to accept it
+const CIRCUIT_TIMEOUT:Duration=Duration::from_secs(15);
+
+/// Pause between hole punching attempts
+const PUNCH_RETRY:Duration=Duration::from_millis(100);
+
+/// Wait before reserving again after losing a relay
+const RESERVE_RETRY:Duration=Duration::from_secs(10);
+
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub enum RelayMessage{
+    /// Client: keep this connection as `peer`'s control channel
+    Reserve{peer:PeerId},
+    /// Relay: reservation held; `observed` is the client's address as the relay sees it
+    Reserved{observed:SocketAddr},
+    /// Client: open a circuit to `target`
+    Connect{target:PeerId,punch:bool},
+    /// Relay, on the target's control channel: a dialer at `addr` wants circuit `circuit`
+    Incoming{circuit:u64,addr:SocketAddr,punch:bool},
+    /// Relay, to the dialer: the target was told; `addr` is where it reaches the relay from
+    Pending{addr:SocketAddr},
+    /// Client: this connection carries circuit `circuit`
+    Accept{circuit:u64},
+    /// Relay: spliced; from now on the connection carries the peers' bytes
+    Connected,
+    /// Relay: no reservation for the target, or no room for another
+    Unavailable,
+}
+
+async fn write_message<W:AsyncWrite+Unpin>(writer:&mut W,message:&RelayMessage)->Result<(),NetworkError>{
+    let bytes=canonical::encode(message);
+    writer.write_all(&(bytes.len() as u32).to_be_bytes()).await?;
+    writer.write_all(&bytes).await?;
+    writer.flush().await?;
+    Ok(())
+}
+
+async fn read_message<R:AsyncRead+Unpin>(reader:&mut R)->Result<RelayMessage,NetworkError>{
+    let mut len=[0u8;4];
+    reader.read_exact(&mut len).await?;
+    let len=u32::from_be_bytes(len) as usize;
+    if len>MAX_MESSAGE_BYTES{
+        return Err(NetworkError::FrameTooLarge(len))
+    }
+    let mut bytes=vec![0u8;len];
+    reader.read_exact(&mut bytes).await?;
+    Ok(canonical::decode(&bytes)?)
+}
+
+/// TCP socket bound to `local_port` (0 = any) that other sockets may share the port with
+fn reusable_socket(remote:SocketAddr,local_port:u16)->Result<TcpSocket,NetworkError>{
+    let (socket,any)=if remote.is_ipv4(){
+        (TcpSocket::new_v4()?,IpAddr::from([0u8;4]))
+    } else{
+        (TcpSocket::new_v6()?,IpAddr::from([0u16;8]))
+    };
+    socket.set_reuseaddr(true)?;
+    #[cfg(unix)]
+    socket.set_reuseport(true)?;
+    socket.bind(SocketAddr::new(any,local_port))?;
+    Ok(socket)
+}
+
+/// Connect to `remote` from `local_port` over and over until it works or `wait` runs out
+async fn punch(local_port:u16,remote:SocketAddr,wait:Duration)->Result<TcpStream,NetworkError>{
+    let deadline=Instant::now()+wait;
+    loop{
+        let socket=reusable_socket(remote,local_port)?;
+        if let Ok(Ok(stream))=timeout(deadline.saturating_duration_since(Instant::now()),socket.connect(remote)).await{
+            return Ok(stream)
+        }
+        if Instant::now()+PUNCH_RETRY>=deadline{
+            return Err(NetworkError::Relay(format!("hole punch to {} failed",remote)))
+        }
+        sleep(PUNCH_RETRY).await;
+    }
+}
+
+#[derive(Debug,Clone)]
+pub struct RelayServiceConfig{
+    pub listen_addr:SocketAddr,
+    pub max_reservations:usize,
+    /// Most circuits spliced at once
+    pub max_circuits:usize,
+}
+
+impl Default for RelayServiceConfig{
+    fn default()->Self{
+        RelayServiceConfig{listen_addr:SocketAddr::from(([0,0,0,0],30334)),max_reservations:128,max_circuits:64}
+    }
+}
+
+struct Reservation{
+    notify:mpsc::UnboundedSender<RelayMessage>,
+    /// Where the reserved peer reaches us from
+    addr:SocketAddr,
+}
+
+struct RelayState{
+    config:RelayServiceConfig,
+    reservations:Mutex<HashMap<PeerId,Reservation>>,
+    /// Circuits waiting for their target to accept
+    pending:Mutex<HashMap<u64,oneshot::Sender<TcpStream>>>,
+    next_circuit:AtomicU64,
+    circuits:AtomicUsize,
+}
+
+/// A relay other nodes reserve slots on
+pub struct RelayService{
+    state:Arc<RelayState>,
+    local_addr:SocketAddr,
+}
+
+impl RelayService{
+    pub async fn start(config:RelayServiceConfig)->Result<Self,NetworkError>{
+        let listener=TcpListener::bind(config.listen_addr).await?;
+        let local_addr=listener.local_addr()?;
+        let state=Arc::new(RelayState{
+            config,
+            reservations:Mutex::new(HashMap::new()),
+            pending:Mutex::new(HashMap::new()),
+            next_circuit:AtomicU64::new(0),
+            circuits:AtomicUsize::new(0),
+        });
+        let service=state.clone();
+        tokio::spawn(async move {
+            while let Ok((stream,addr))=listener.accept().await{
+                let state=service.clone();
+                tokio::spawn(async move {
+                    let _=serve(state,stream,addr).await;
+                });
+            }
+        });
+        Ok(RelayService{state,local_addr})
+    }
+
+    pub fn local_addr(&self)->SocketAddr{
+        self.local_addr
+    }
+
+    /// Peers holding a reservation
+    pub fn reserved_peers(&self)->Vec<PeerId>{
+        let mut peers:Vec<PeerId>=self.state.reservations.lock().expect("reservations poisoned").keys().cloned().collect();
+        peers.sort();
+        peers
+    }
+
+    /// Circuits currently spliced
+    pub fn active_circuits(&self)->usize{
+        self.state.circuits.load(Ordering::Relaxed)
+    }
+}
+
+async fn serve(state:Arc<RelayState>,mut stream:TcpStream,addr:SocketAddr)->Result<(),NetworkError>{
+    match read_message(&mut stream).await?{
+        RelayMessage::Reserve{peer}=>hold_reservation(state,stream,addr,peer).await,
+        RelayMessage::Connect{target,punch}=>open_circuit(state,stream,addr,target,punch).await,
+        RelayMessage::Accept{circuit}=>{
+            let waiting=state.pending.lock().expect("pending circuits poisoned").remove(&circuit);
+            if let Some(waiting)=waiting{
+                let _=waiting.send(stream);
+            }
+            Ok(())
+        }
+        _=>Ok(()),
+    }
+}
+
+async fn hold_reservation(state:Arc<RelayState>,mut stream:TcpStream,addr:SocketAddr,peer:PeerId)->Result<(),NetworkError>{
+    let (notify,mut incoming)=mpsc::unbounded_channel();
+    let admitted={
+        let mut reservations=state.reservations.lock().expect("reservations poisoned");
+        let full=reservations.len()>=state.config.max_reservations && !reservations.contains_key(&peer);
+        if !full{
+            // a newer reservation of the same peer replaces the old one
+            reservations.insert(peer.clone(),Reservation{notify:notify.clone(),addr});
+        }
+        !full
+    };
+    if !admitted{
+        return write_message(&mut stream,&RelayMessage::Unavailable).await
+    }
+    let (mut reader,mut writer)=stream.into_split();
+    let result=async {
+        write_message(&mut writer,&RelayMessage::Reserved{observed:addr}).await?;
+        let mut byte=[0u8;1];
+        loop{
+            tokio::select!{
+                Some(message)=incoming.recv()=>write_message(&mut writer,&message).await?,
+                // clients send nothing more; EOF or anything else ends the reservation
+                _=reader.read(&mut byte)=>return Ok(()),
+            }
+        }
+    }
+    .await;
+    let mut reservations=state.reservations.lock().expect("reservations poisoned");
+    if reservations.get(&peer).is_some_and(|r| r.notify.same_channel(&notify)){
+        reservations.remove(&peer);
+    }
+    result
+}
+
+async fn open_circuit(state:Arc<RelayState>,mut stream:TcpStream,addr:SocketAddr,target:PeerId,punch:bool)->Result<(),NetworkError>{
+    let reservation=state.reservations.lock().expect("reservations poisoned").get(&target).map(|r| (r.notify.clone(),r.addr));
+    let Some((notify,target_addr))=reservation else{
+        return write_message(&mut stream,&RelayMessage::Unavailable).await
+    };
+    if state.circuits.load(Ordering::Relaxed)>=state.config.max_circuits{
+        return write_message(&mut stream,&RelayMessage::Unavailable).await
+    }
+    let circuit=state.next_circuit.fetch_add(1,Ordering::Relaxed);
+    let (accepted,waiting)=oneshot::channel();
+    state.pending.lock().expect("pending circuits poisoned").insert(circuit,accepted);
+    let _=notify.send(RelayMessage::Incoming{circuit,addr,punch});
+    write_message(&mut stream,&RelayMessage::Pending{addr:target_addr}).await?;
+    let accepted=timeout(CIRCUIT_TIMEOUT,waiting).await;
+    state.pending.lock().expect("pending circuits poisoned").remove(&circuit);
+    let Ok(Ok(mut target_stream))=accepted else{
+        return write_message(&mut stream,&RelayMessage::Unavailable).await
+    };
+    write_message(&mut stream,&RelayMessage::Connected).await?;
+    write_message(&mut target_stream,&RelayMessage::Connected).await?;
+    state.circuits.fetch_add(1,Ordering::Relaxed);
+    let _=copy_bidirectional(&mut stream,&mut target_stream).await;
+    state.circuits.fetch_sub(1,Ordering::Relaxed);
+    Ok(())
+}
+
+#[derive(Debug,Clone)]
+pub struct RelayConfig{
+    /// Relays to keep reservations on
+    pub relays:Vec<SocketAddr>,
+    pub hole_punch:bool,
+    /// How long both sides try to punch before using the circuit
+    pub punch_timeout:Duration,
+}
+
+impl Default for RelayConfig{
+    fn default()->Self{
+        RelayConfig{relays:Vec::new(),hole_punch:true,punch_timeout:Duration::from_secs(3)}
+    }
+}
+
+/// A reservation held on a relay; dropping it gives the slot up
+pub struct RelayReservation{
+    /// Our address as the relay sees it
+    pub observed:SocketAddr,
+    task:JoinHandle<()>,
+}
+
+impl RelayReservation{
+    /// Wait until the relay drops the reservation
+    pub async fn closed(&mut self){
+        let _=(&mut self.task).await;
+    }
+}
+
+impl Drop for RelayReservation{
+    fn drop(&mut self){
+        self.task.abort();
+    }
+}
+
+/// Reaches and is reached by peers through relays
+#[derive(Clone)]
+pub struct RelayClient{
+    network:Network,
+    config:RelayConfig,
+}
+
+impl RelayClient{
+    pub fn new(network:Network,config:RelayConfig)->Self{
+        RelayClient{network,config}
+    }
+
+    /// Reserve a slot on `relay` and accept the circuits it announces
+    pub async fn reserve(&self,relay:SocketAddr)->Result<RelayReservation,NetworkError>{
+        let mut stream=reusable_socket(relay,0)?.connect(relay).await?;
+        let local_port=stream.local_addr()?.port();
+        write_message(&mut stream,&RelayMessage::Reserve{peer:self.network.local_peer_id().clone()}).await?;
+        let RelayMessage::Reserved{observed}=read_message(&mut stream).await? else{
+            return Err(NetworkError::Relay(format!("{} refused the reservation",relay)))
+        };
+        let client=self.clone();
+        let task=tokio::spawn(async move {
+            while let Ok(RelayMessage::Incoming{circuit,addr,punch})=read_message(&mut stream).await{
+                let client=client.clone();
+                tokio::spawn(async move {
+                    let _=client.accept(relay,local_port,circuit,addr,punch).await;
+                });
+            }
+        });
+        Ok(RelayReservation{observed,task})
+    }
+
+    /// Take circuit `circuit`, or connect straight to the dialer if punching works
+    async fn accept(&self,relay:SocketAddr,local_port:u16,circuit:u64,addr:SocketAddr,punch_requested:bool)->Result<PeerId,NetworkError>{
+        if punch_requested && let Some(direct)=self.punch(local_port,addr).await{
+            return self.network.establish(direct,addr,false,Link::Tcp).await
+        }
+        let mut stream=TcpStream::connect(relay).await?;
+        write_message(&mut stream,&RelayMessage::Accept{circuit}).await?;
+        if read_message(&mut stream).await?!=RelayMessage::Connected{
+            return Err(NetworkError::Relay(format!("circuit {} closed",circuit)))
+        }
+        self.network.establish(stream,relay,false,Link::Relay).await
+    }
+
+    /// Connect to `target` through `relay`, directly if hole punching works
+    pub async fn dial(&self,relay:SocketAddr,target:&PeerId)->Result<PeerId,NetworkError>{
+        let mut stream=reusable_socket(relay,0)?.connect(relay).await?;
+        let local_port=stream.local_addr()?.port();
+        write_message(&mut stream,&RelayMessage::Connect{target:target.clone(),punch:self.config.hole_punch}).await?;
+        let RelayMessage::Pending{addr}=read_message(&mut stream).await? else{
+            return Err(NetworkError::Relay(format!("{} is not reachable through {}",target,relay)))
+        };
+        let connected=if let Some(direct)=self.punch(local_port,addr).await{
+            self.network.establish(direct,addr,true,Link::Tcp).await?
+        } else{
+            let answer=timeout(CIRCUIT_TIMEOUT,read_message(&mut stream))
+            .await
+            .map_err(|_| NetworkError::Relay(format!("{} did not accept the circuit",target)))??;
+            if answer!=RelayMessage::Connected{
+                return Err(NetworkError::Relay(format!("{} did not accept the circuit",target)))
+            }
+            self.network.establish(stream,relay,true,Link::Relay).await?
+        };
+        if &connected!=target{
+            self.network.disconnect(&connected);
+            return Err(NetworkError::Relay(format!("{} connected us to {} instead of {}",relay,connected,target)))
+        }
+        Ok(connected)
+    }
+
+    async fn punch(&self,local_port:u16,addr:SocketAddr)->Option<TcpStream>{
+        if !self.config.hole_punch{
+            return None
+        }
+        punch(local_port,addr,self.config.punch_timeout).await.ok()
+    }
+
+    /// Keep a reservation on every configured relay, reserving again when one is lost;
+    /// never returns
+    pub async fn run(self){
+        let tasks:Vec<JoinHandle<()>>=self
+        .config
+        .relays
+        .iter()
+        .map(|&relay|{
+            let client=self.clone();
+            tokio::spawn(async move {
+                loop{
+                    match client.reserve(relay).await{
+                        Ok(mut reservation)=>reservation.closed().await,
+                        Err(error)=>client.network.emit(NetworkEvent::ReservationFailed{relay,error}),
+                    }
+                    sleep(RESERVE_RETRY).await;
+                }
+            })
+        })
+        .collect();
+        for task in tasks{
+            let _=task.await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::network::identity::NodeIdentity;
+    use crate::network::{NetworkConfig,Topic,Transport};
+
+    fn local_config()->NetworkConfig{
+        NetworkConfig{listen_addr:SocketAddr::from(([127,0,0,1],0)),..NetworkConfig::default()}
+    }
+
+    async fn relay()->RelayService{
+        RelayService::start(RelayServiceConfig{listen_addr:SocketAddr::from(([127,0,0,1],0)),..RelayServiceConfig::default()}).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_connect_through_relay(){
+        let relay=relay().await;
+        let no_punch=RelayConfig{hole_punch:false,..RelayConfig::default()};
+        let hidden=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let dialer=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let _reservation=RelayClient::new(hidden.clone(),no_punch.clone()).reserve(relay.local_addr()).await.unwrap();
+        assert_eq!(relay.reserved_peers(),vec![hidden.local_peer_id().clone()]);
+
+        let mut messages=hidden.subscribe(Topic::Transactions);
+        let client=RelayClient::new(dialer.clone(),no_punch);
+        let peer=client.dial(relay.local_addr(),hidden.local_peer_id()).await.unwrap();
+        assert_eq!(&peer,hidden.local_peer_id());
+        let info=&dialer.peers()[0];
+        assert_eq!(info.transport,Transport::Relay);
+        assert_eq!((info.addr,info.listen_addr),(relay.local_addr(),None));
+
+        dialer.send(&peer,Topic::Transactions,b"tx".to_vec()).unwrap();
+        let received=timeout(Duration::from_secs(5),messages.recv()).await.unwrap().unwrap();
+        assert_eq!(received.payload,b"tx".to_vec());
+        assert_eq!(relay.active_circuits(),1);
+
+        // nobody reserved under this id
+        let stranger=NodeIdentity::generate().peer_id();
+        assert!(matches!(client.dial(relay.local_addr(),&stranger).await,Err(NetworkError::Relay(_))));
+    }
+
+    #[tokio::test]
+    async fn test_punch_falls_back_to_circuit(){
+        let relay=relay().await;
+        let config=RelayConfig{punch_timeout:Duration::from_millis(300),..RelayConfig::default()};
+        let hidden=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let dialer=Network::start(NodeIdentity::generate(),local_config()).await.unwrap();
+        let _reservation=RelayClient::new(hidden.clone(),config.clone()).reserve(relay.local_addr()).await.unwrap();
+        // whether or not the punch gets through, the peers end up connected
+        let peer=RelayClient::new(dialer.clone(),config).dial(relay.local_addr(),hidden.local_peer_id()).await.unwrap();
+        assert!(dialer.is_connected(&peer));
+    }
+}
@@ -0,0 +1,236 @@
+// src/network/reputation.rs
+
+//! Peer reputation
+//! - Every peer id has a score starting at 0; misbehaviour (invalid blocks or transactions,
+//!   spam, timeouts, undecodable messages) subtracts its `Misbehavior::penalty`, good
+//!   behaviour adds a little, and scores drift back towards 0 by `recovery_per_hour`
+//! - A peer whose score reaches `ban_threshold` is banned for `ban_duration` seconds and
+//!   disconnected; when the ban ends its score restarts at half the threshold, so it gets
+//!   banned again quickly if it keeps misbehaving
+//! - Scores and bans are saved to `path`, so a restart doesn't clear them
+//! - Times are unix seconds, passed in by the caller
+
+use super::NetworkError;
+use super::identity::PeerId;
+use serde::{Deserialize,Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// Highest score a peer can build up
+pub const MAX_SCORE:i32=100;
+
+/// Ways a peer can misbehave
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Misbehavior{
+    /// A block or header that fails validation
+    InvalidBlock,
+    /// A transaction with a bad signature or over the protocol limits
+    InvalidTransaction,
+    /// Unsolicited or excessive messages
+    Spam,
+    /// No answer to a request in time
+    Timeout,
+    /// A payload that can't be decoded
+    BadMessage,
+}
+
+impl Misbehavior{
+    pub fn penalty(self)->i32{
+        match self{
+            Misbehavior::InvalidBlock=>50,
+            Misbehavior::InvalidTransaction=>20,
+            Misbehavior::Spam=>20,
+            Misbehavior::Timeout=>5,
+            Misbehavior::BadMessage=>25,
+        }
+    }
+}
+
+#[derive(Debug,Clone)]
+pub struct ReputationConfig{
+    /// Score at or below which a peer is banned
+    pub ban_threshold:i32,
+    /// Seconds a ban lasts
+    pub ban_duration:u64,
+    /// Points per hour a score moves back towards 0
+    pub recovery_per_hour:i32,
+    /// File keeping scores and bans across restarts
+    pub path:Option<PathBuf>,
+}
+
+impl Default for ReputationConfig{
+    fn default()->Self{
+        ReputationConfig{ban_threshold:-100,ban_duration:3600,recovery_per_hour:10,path:None}
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+struct PeerRecord{
+    score:i32,
+    /// When `score` was last brought up to date
+    updated_at:u64,
+    banned_until:Option<u64>,
+}
+
+/// A peer's standing, as listed by `PeerReputation::peers`
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct PeerStanding{
+    pub peer_id:PeerId,
+    pub score:i32,
+    pub banned_until:Option<u64>,
+}
+
+/// Scores and bans of every peer seen
+#[derive(Debug,Clone)]
+pub struct PeerReputation{
+    config:ReputationConfig,
+    peers:BTreeMap<PeerId,PeerRecord>,
+}
+
+impl PeerReputation{
+    pub fn new(config:ReputationConfig)->Self{
+        PeerReputation{config,peers:BTreeMap::new()}
+    }
+
+    /// Reputation saved at `config.path`, or an empty one if there is no file yet
+    pub fn load(config:ReputationConfig)->Result<Self,NetworkError>{
+        let mut reputation=PeerReputation::new(config);
+        let Some(path)=&reputation.config.path else{
+            return Ok(reputation)
+        };
+        match std::fs::read_to_string(path){
+            Ok(text)=>{
+                reputation.peers=serde_json::from_str(&text).map_err(|e| NetworkError::Io(format!("{}: {}",path.display(),e)))?;
+                Ok(reputation)
+            }
+            Err(e) if e.kind()==std::io::ErrorKind::NotFound=>Ok(reputation),
+            Err(e)=>Err(NetworkError::Io(format!("{}: {}",path.display(),e))),
+        }
+    }
+
+    /// Write scores and bans to `config.path`, if set
+    pub fn save(&self)->Result<(),NetworkError>{
+        let Some(path)=&self.config.path else{
+            return Ok(())
+        };
+        let json=serde_json::to_string_pretty(&self.peers).expect("reputation serializes");
+        std::fs::write(path,json).map_err(|e| NetworkError::Io(format!("{}: {}",path.display(),e)))
+    }
+
+    /// Bring `peer`'s record up to `now`: recovery since the last update, expired ban
+    fn record(&mut self,peer:&PeerId,now:u64)->&mut PeerRecord{
+        let config=&self.config;
+        let record=self.peers.entry(peer.clone()).or_insert(PeerRecord{score:0,updated_at:now,banned_until:None});
+        if record.banned_until.is_some_and(|until| until<=now){
+            record.banned_until=None;
+            record.score=config.ban_threshold/2;
+            record.updated_at=now;
+        }
+        let hours=now.saturating_sub(record.updated_at)/3600;
+        if hours>0 && record.banned_until.is_none(){
+            let recovery=(hours as i64*config.recovery_per_hour as i64).min(i32::MAX as i64) as i32;
+            record.score=if record.score<0{(record.score+recovery).min(0)} else{(record.score-recovery).max(0)};
+            record.updated_at+=hours*3600;
+        }
+        record
+    }
+
+    pub fn score(&mut self,peer:&PeerId,now:u64)->i32{
+        self.record(peer,now).score
+    }
+
+    pub fn is_banned(&mut self,peer:&PeerId,now:u64)->bool{
+        self.record(peer,now).banned_until.is_some()
+    }
+
+    /// Penalize `peer`; returns the end of its ban if this pushed it over the threshold
+    pub fn report(&mut self,peer:&PeerId,misbehavior:Misbehavior,now:u64)->Option<u64>{
+        let (threshold,duration)=(self.config.ban_threshold,self.config.ban_duration);
+        let record=self.record(peer,now);
+        record.score=record.score.saturating_sub(misbehavior.penalty());
+        if record.banned_until.is_none() && record.score<=threshold{
+            let until=now.saturating_add(duration);
+            record.banned_until=Some(until);
+            return Some(until)
+        }
+        None
+    }
+
+    /// Credit `peer` for useful behaviour, up to `MAX_SCORE`
+    pub fn reward(&mut self,peer:&PeerId,points:i32,now:u64){
+        let record=self.record(peer,now);
+        record.score=record.score.saturating_add(points).min(MAX_SCORE);
+    }
+
+    /// Ban `peer` until `until`, whatever its score
+    pub fn ban(&mut self,peer:&PeerId,until:u64,now:u64){
+        self.record(peer,now).banned_until=Some(until);
+    }
+
+    /// Lift a ban and reset the score
+    pub fn unban(&mut self,peer:&PeerId){
+        if let Some(record)=self.peers.get_mut(peer){
+            record.banned_until=None;
+            record.score=0;
+        }
+    }
+
+    /// Every peer with a record, as of `now`
+    pub fn peers(&mut self,now:u64)->Vec<PeerStanding>{
+        let ids:Vec<PeerId>=self.peers.keys().cloned().collect();
+        ids
+        .into_iter()
+        .map(|peer_id|{
+            let record=self.record(&peer_id,now);
+            PeerStanding{score:record.score,banned_until:record.banned_until,peer_id}
+        })
+        .collect()
+    }
+
+    /// Peers banned as of `now`
+    pub fn banned(&mut self,now:u64)->Vec<PeerStanding>{
+        self.peers(now).into_iter().filter(|p| p.banned_until.is_some()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::network::identity::NodeIdentity;
+
+    #[test]
+    fn test_misbehaving_peer_banned_then_released(){
+        let peer=NodeIdentity::generate().peer_id();
+        let mut reputation=PeerReputation::new(ReputationConfig::default());
+        assert_eq!(reputation.report(&peer,Misbehavior::InvalidBlock,0),None);
+        assert_eq!(reputation.score(&peer,0),-50);
+        // two hours later it has partly recovered
+        assert_eq!(reputation.score(&peer,7200),-30);
+        assert_eq!(reputation.report(&peer,Misbehavior::InvalidBlock,7200),None);
+        assert_eq!(reputation.report(&peer,Misbehavior::BadMessage,7200),Some(7200+3600));
+        assert!(reputation.is_banned(&peer,10_000));
+        assert_eq!(reputation.banned(10_000).len(),1);
+
+        // released on probation
+        assert!(!reputation.is_banned(&peer,7200+3600));
+        assert_eq!(reputation.score(&peer,7200+3600),-50);
+        reputation.unban(&peer);
+        assert_eq!(reputation.score(&peer,7200+3600),0);
+        reputation.reward(&peer,500,7200+3600);
+        assert_eq!(reputation.score(&peer,7200+3600),MAX_SCORE);
+    }
+
+    #[test]
+    fn test_reputation_persists(){
+        let path=std::env::temp_dir().join(format!("netchain-reputation-{}.json",std::process::id()));
+        let config=ReputationConfig{path:Some(path.clone()),..ReputationConfig::default()};
+        let peer=NodeIdentity::generate().peer_id();
+        let mut reputation=PeerReputation::load(config.clone()).unwrap();
+        reputation.ban(&peer,500,0);
+        reputation.save().unwrap();
+
+        let mut reloaded=PeerReputation::load(config).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(reloaded.peers(0),vec![PeerStanding{peer_id:peer,score:0,banned_until:Some(500)}]);
+    }
+}
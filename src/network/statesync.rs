@@ -0,0 +1,702 @@
+// src/network/statesync.rs
+
+//! State sync: start from a recent state snapshot instead of re-executing the whole chain
+//! - Serving nodes snapshot their accounts after every `snapshot_interval`-th block once it
+//!   is final (`SnapshotServer::on_finalized`) and keep the newest `keep_snapshots`. A
+//!   snapshot is split into chunks of `chunk_accounts` accounts in address order, and its
+//!   `SnapshotManifest` lists the SHA-256 of every chunk. Operators can `take` one at any
+//!   height as well
+//! - A new node (`bootstrap`) asks each peer announcing "state/1" for its newest manifest.
+//!   Which one it takes depends on `StateSyncConfig::trust`, without which it refuses to run:
+//!   a pinned block (`SnapshotTrust::Block`) admits only the snapshot of that block; a trusted
+//!   committee (`SnapshotTrust::Committee`) admits the highest manifest offered by at least
+//!   `min_providers` peers. Provider agreement only raises the cost of lying, as peer ids are
+//!   free to make; the committee check below is what rules out made-up snapshots
+//! - It downloads the headers from its genesis to the block after the snapshot, linked as in
+//!   `sync`: the snapshot block must carry the manifest's state root and chain state hash,
+//!   and the next block a commit for it. Under `Committee` that commit must verify against
+//!   the committee, so it has to be the one that finalized the snapshot height
+//! - Chunks are fetched in parallel from every peer offering the same manifest, each checked
+//!   against its hash on arrival; together their accounts must rebuild the snapshot block's
+//!   state root
+//! - The rest of the state (validators, epoch, names, fee pools, ...) travels whole as the
+//!   serving node's `State::encode_chain_state`, checked against the `chain_state_hash` the
+//!   snapshot block carries
+//! - The node then continues from the snapshot block (`Blockchain::from_snapshot`) and only
+//!   replays the blocks after it, e.g. by starting `SyncEngine` on the restored chain
+
+use super::identity::PeerId;
+use super::ratelimit::RateClass;
+use super::reputation::Misbehavior;
+use super::sync::{BlockHeader,SyncMessage,linked};
+use super::wire::{CAP_STATE_SYNC,WireMessage};
+use super::{InboundMessage,Network,Topic};
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::canonical;
+use crate::state::{Account,State};
+use crate::validator::ValidatorRegistry;
+use serde::{Deserialize,Serialize};
+use sha2::{Digest,Sha256};
+use std::collections::{BTreeMap,HashMap,HashSet,VecDeque};
+use std::sync::{Arc,Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{Instant,timeout_at};
+
+/// Accounts of one chunk, by address
+pub type Chunk=BTreeMap<String,Account>;
+
+/// What a snapshot holds, sent before any of it
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct SnapshotManifest{
+    /// Height of the block the snapshot was taken after
+    pub height:u64,
+    pub block_hash:String,
+    /// Hex state root of that block, which the accounts rebuild to
+    pub state_root:String,
+    /// Hex SHA-256 of each chunk's canonical encoding
+    pub chunk_hashes:Vec<String>,
+    /// Hex hash of the block's chain state (`State::chain_state_hash`)
+    pub chain_state_hash:String,
+}
+
+/// Payloads of `Topic::State`
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub enum StateSyncMessage{
+    /// Ask for the newest snapshot the peer serves
+    GetManifest,
+    Manifest{manifest:Option<SnapshotManifest>},
+    GetChunk{height:u64,index:u32},
+    /// `accounts` is None when the peer doesn't serve that snapshot (any more)
+    Chunk{height:u64,index:u32,accounts:Option<Chunk>},
+    GetChainState{height:u64},
+    /// `State::encode_chain_state` of the snapshot, None as for `Chunk`
+    ChainState{height:u64,chain_state:Option<Vec<u8>>},
+}
+
+impl WireMessage for StateSyncMessage{
+    const TOPIC:Topic=Topic::State;
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum StateSyncError{
+    #[error("no snapshot trust configured")]
+    NoTrust,
+    /// No manifest the trust settings admit
+    #[error("no peer offers a state snapshot")]
+    NoSnapshot,
+    #[error("timed out waiting for {0}")]
+    Timeout(&'static str),
+    /// The headers don't link our genesis to the snapshot block, or that block doesn't match
+    /// the manifest
+    #[error("headers don't lead to snapshot block {0}")]
+    InvalidHeaders(u64),
+    /// No block carrying a commit for the snapshot block yet
+    #[error("snapshot block {0} is not final")]
+    NotFinal(u64),
+    /// The commit for the snapshot block doesn't verify against the trusted committee
+    #[error("commit for snapshot block {0} doesn't verify")]
+    InvalidCommit(u64),
+    #[error("snapshot accounts don't match the state root of block {0}")]
+    StateRootMismatch(u64),
+    #[error("snapshot chain state doesn't match block {0}")]
+    ChainStateMismatch(u64),
+}
+
+#[derive(Debug,Clone)]
+pub struct StateSyncConfig{
+    /// Blocks between the snapshots a serving node takes (0: none)
+    pub snapshot_interval:u64,
+    /// Snapshots kept for serving, newest first
+    pub keep_snapshots:usize,
+    pub chunk_accounts:usize,
+    /// Chunk requests outstanding at once, over all peers
+    pub parallel_requests:usize,
+    pub header_batch:u32,
+    /// A request unanswered for this long goes to another peer
+    pub request_timeout:Duration,
+    /// What `bootstrap` checks a snapshot against; it won't run without
+    pub trust:Option<SnapshotTrust>,
+    /// Distinct peers that must offer a manifest under `SnapshotTrust::Committee`
+    pub min_providers:usize,
+}
+
+/// What a bootstrapping node trusts to tell a real snapshot from a made-up one
+#[derive(Debug,Clone)]
+pub enum SnapshotTrust{
+    /// Only the snapshot of this block, e.g. one read off a node the operator runs
+    Block{height:u64,hash:String},
+    /// The snapshot block must be finalized by >2/3 of this committee's weight, e.g. the
+    /// genesis validators while the set hasn't changed since
+    Committee{registry:ValidatorRegistry,weights:HashMap<String,u64>},
+}
+
+impl Default for StateSyncConfig{
+    fn default()->Self{
+        StateSyncConfig{
+            snapshot_interval:1_000,
+            keep_snapshots:2,
+            chunk_accounts:1_000,
+            parallel_requests:4,
+            header_batch:512,
+            request_timeout:Duration::from_secs(10),
+            trust:None,
+            min_providers:2,
+        }
+    }
+}
+
+fn chunk_hash(chunk:&Chunk)->String{
+    hex::encode(Sha256::digest(canonical::encode(chunk)))
+}
+
+/// A snapshot as served
+#[derive(Debug,Clone)]
+pub struct Snapshot{
+    pub manifest:SnapshotManifest,
+    chunks:Vec<Chunk>,
+    chain_state:Vec<u8>,
+}
+
+impl Snapshot{
+    /// Snapshot of `state`, taken right after `block` with its state root committed
+    pub fn of(block:&Block,state:&State,chunk_accounts:usize)->Self{
+        let mut chunks:Vec<Chunk>=Vec::new();
+        for (address,account) in state.accounts_iter(None,usize::MAX){
+            match chunks.last_mut(){
+                Some(chunk) if chunk.len()<chunk_accounts.max(1)=>{
+                    chunk.insert(address.to_string(),account.clone());
+                }
+                _=>chunks.push(Chunk::from([(address.to_string(),account.clone())])),
+            }
+        }
+        let chain_state=state.encode_chain_state();
+        let manifest=SnapshotManifest{
+            height:block.index,
+            block_hash:block.hash.clone(),
+            state_root:hex::encode(state.state_root()),
+            chunk_hashes:chunks.iter().map(chunk_hash).collect(),
+            chain_state_hash:hex::encode(Sha256::digest(&chain_state)),
+        };
+        Snapshot{manifest,chunks,chain_state}
+    }
+
+    pub fn chunk(&self,index:u32)->Option<&Chunk>{
+        self.chunks.get(index as usize)
+    }
+
+    pub fn chain_state(&self)->&[u8]{
+        &self.chain_state
+    }
+}
+
+/// Takes snapshots and serves them to bootstrapping peers; cheap to clone
+#[derive(Clone)]
+pub struct SnapshotServer{
+    network:Network,
+    config:StateSyncConfig,
+    /// Oldest first
+    snapshots:Arc<Mutex<VecDeque<Arc<Snapshot>>>>,
+}
+
+impl SnapshotServer{
+    pub fn new(network:Network,config:StateSyncConfig)->Self{
+        SnapshotServer{network,config,snapshots:Arc::new(Mutex::new(VecDeque::new()))}
+    }
+
+    /// Call when `block` becomes final, with `state` as of right after it; takes a snapshot
+    /// if the block is at a snapshot height. Returns whether it did.
+    pub fn on_finalized(&self,block:&Block,state:&State)->bool{
+        let interval=self.config.snapshot_interval;
+        if interval==0 || !block.index.is_multiple_of(interval){
+            return false
+        }
+        self.push(block,state)
+    }
+
+    /// Snapshot `block` now, whatever its height (e.g. at an operator's request), with
+    /// `state` as of right after it. `block` must be final: peers bootstrap from it.
+    /// Returns the newest manifest, which is of an earlier snapshot if one at this height
+    /// or above is already kept.
+    pub fn take(&self,block:&Block,state:&State)->SnapshotManifest{
+        self.push(block,state);
+        self.latest().expect("a snapshot is kept")
+    }
+
+    fn push(&self,block:&Block,state:&State)->bool{
+        let mut snapshots=self.snapshots.lock().expect("snapshots lock poisoned");
+        if snapshots.back().is_some_and(|s| s.manifest.height>=block.index){
+            return false
+        }
+        snapshots.push_back(Arc::new(Snapshot::of(block,state,self.config.chunk_accounts)));
+        while snapshots.len()>self.config.keep_snapshots.max(1){
+            snapshots.pop_front();
+        }
+        true
+    }
+
+    /// Manifest of the newest snapshot
+    pub fn latest(&self)->Option<SnapshotManifest>{
+        self.snapshots.lock().expect("snapshots lock poisoned").back().map(|s| s.manifest.clone())
+    }
+
+    /// Answer snapshot requests until the network shuts down
+    pub async fn run(self){
+        let mut messages=self.network.subscribe(Topic::State);
+        while let Some(message)=messages.recv().await{
+            self.handle(message);
+        }
+    }
+
+    fn handle(&self,message:InboundMessage){
+        let Ok(request)=StateSyncMessage::from_payload(&message.payload) else{
+            self.network.report(&message.from,Misbehavior::BadMessage);
+            return
+        };
+        let reply=match request{
+            StateSyncMessage::GetManifest=>StateSyncMessage::Manifest{manifest:self.latest()},
+            StateSyncMessage::GetChunk{..}|StateSyncMessage::GetChainState{..}
+            if !self.network.allow(&message.from,RateClass::BlockRequests)=>return,
+            StateSyncMessage::GetChunk{height,index}=>{
+                StateSyncMessage::Chunk{height,index,accounts:self.snapshot_at(height).and_then(|s| s.chunk(index).cloned())}
+            }
+            StateSyncMessage::GetChainState{height}=>{
+                StateSyncMessage::ChainState{height,chain_state:self.snapshot_at(height).map(|s| s.chain_state.clone())}
+            }
+            // answers are for `bootstrap`
+            StateSyncMessage::Manifest{..}|StateSyncMessage::Chunk{..}|StateSyncMessage::ChainState{..}=>return,
+        };
+        let _=self.network.send_message(&message.from,&reply);
+    }
+
+    fn snapshot_at(&self,height:u64)->Option<Arc<Snapshot>>{
+        self.snapshots.lock().expect("snapshots lock poisoned").iter().find(|s| s.manifest.height==height).cloned()
+    }
+}
+
+/// Chain and state restored from a snapshot
+pub struct Bootstrap{
+    /// Starts at the snapshot block
+    pub chain:Blockchain,
+    /// State as of the snapshot block, state root committed
+    pub state:State,
+    pub manifest:SnapshotManifest,
+}
+
+/// Restore the chain and state from the newest snapshot the connected peers offer that
+/// `config.trust` admits. Run it on a node that only has `genesis`, before starting
+/// `SyncEngine`; on error, sync in full.
+pub async fn bootstrap(network:&Network,genesis:&Block,config:&StateSyncConfig)->Result<Bootstrap,StateSyncError>{
+    let trust=config.trust.as_ref().ok_or(StateSyncError::NoTrust)?;
+    let mut state_messages=network.subscribe(Topic::State);
+    let mut sync_messages=network.subscribe(Topic::Sync);
+    let (manifest,providers)=find_snapshot(network,&mut state_messages,trust,config).await?;
+    let height=manifest.height;
+    let source=&providers[0];
+    let (header,next)=download_headers(network,&mut sync_messages,source,genesis,height+1,config).await?;
+    if header.index!=height
+    || header.hash!=manifest.block_hash
+    || header.consensus.state_root.as_ref()!=Some(&manifest.state_root)
+    || header.consensus.chain_state_hash.as_ref()!=Some(&manifest.chain_state_hash){
+        network.report(source,Misbehavior::InvalidBlock);
+        return Err(StateSyncError::InvalidHeaders(height))
+    }
+    if next.consensus.last_commit.is_none() && next.consensus.last_aggregate_commit.is_none(){
+        return Err(StateSyncError::NotFinal(height))
+    }
+    // `linked` already tied the commit to the snapshot block
+    if let SnapshotTrust::Committee{registry,weights}=trust{
+        let verified=next.consensus.last_commit.as_ref().is_some_and(|c| c.verify(registry,weights).is_ok())
+        || next.consensus.last_aggregate_commit.as_ref().is_some_and(|c| c.verify(registry,weights).is_ok());
+        if !verified{
+            network.report(source,Misbehavior::InvalidBlock);
+            return Err(StateSyncError::InvalidCommit(height))
+        }
+    }
+    let _=network.send_message(source,&SyncMessage::GetBodies{heights:vec![height]});
+    let body=reply(&mut sync_messages,source,config.request_timeout,"snapshot block",|message| match message{
+        SyncMessage::Bodies{bodies}=>bodies.into_iter().find(|b| b.index==height),
+        _=>None,
+    })
+    .await?;
+    let Some(block)=header.with_body(body.data) else{
+        network.report(source,Misbehavior::InvalidBlock);
+        return Err(StateSyncError::InvalidHeaders(height))
+    };
+
+    let chunks=download_chunks(network,&mut state_messages,&manifest,providers.clone(),config).await?;
+    let chain_state=download_chain_state(network,&mut state_messages,&manifest,&providers,config).await?;
+    // every chunk and the chain state matched the manifest, so if they don't fit, it lied
+    let liars=|error|{
+        for peer in &providers{
+            network.report(peer,Misbehavior::InvalidBlock);
+        }
+        error
+    };
+    let mut state=State::from_snapshot(chunks.into_iter().flatten().collect(),&chain_state)
+    .map_err(|_| liars(StateSyncError::ChainStateMismatch(height)))?;
+    if hex::encode(state.commit_state_root())!=manifest.state_root{
+        return Err(liars(StateSyncError::StateRootMismatch(height)))
+    }
+    Ok(Bootstrap{chain:Blockchain::from_snapshot(block),state,manifest})
+}
+
+/// Newest manifest offered that `trust` admits, and the peers offering it
+async fn find_snapshot(
+    network:&Network,
+    messages:&mut UnboundedReceiver<InboundMessage>,
+    trust:&SnapshotTrust,
+    config:&StateSyncConfig,
+)->Result<(SnapshotManifest,Vec<PeerId>),StateSyncError>{
+    let peers:HashSet<PeerId>=network
+    .peers()
+    .into_iter()
+    .filter(|p| p.capabilities.iter().any(|c| c==CAP_STATE_SYNC))
+    .map(|p| p.peer_id)
+    .collect();
+    for peer in &peers{
+        let _=network.send_message(peer,&StateSyncMessage::GetManifest);
+    }
+    let mut answered=HashSet::new();
+    let mut offers:HashMap<PeerId,SnapshotManifest>=HashMap::new();
+    let deadline=Instant::now()+config.request_timeout;
+    while answered.len()<peers.len(){
+        let Ok(Some(message))=timeout_at(deadline,messages.recv()).await else{
+            break
+        };
+        if !peers.contains(&message.from){
+            continue
+        }
+        if let Ok(StateSyncMessage::Manifest{manifest})=StateSyncMessage::from_payload(&message.payload){
+            answered.insert(message.from.clone());
+            if let Some(manifest)=manifest{
+                offers.insert(message.from,manifest);
+            }
+        }
+    }
+    let offered_by=|manifest:&SnapshotManifest| offers.values().filter(|m| *m==manifest).count();
+    let admitted=|manifest:&SnapshotManifest| match trust{
+        SnapshotTrust::Block{height,hash}=>manifest.height==*height && manifest.block_hash==*hash,
+        SnapshotTrust::Committee{..}=>offered_by(manifest)>=config.min_providers.max(1),
+    };
+    let best=offers
+    .values()
+    .filter(|m| admitted(m))
+    .max_by_key(|m| (m.height,offered_by(m)))
+    .cloned()
+    .ok_or(StateSyncError::NoSnapshot)?;
+    let mut providers:Vec<PeerId>=offers.into_iter().filter(|(_,m)| *m==best).map(|(peer,_)| peer).collect();
+    providers.sort();
+    Ok((best,providers))
+}
+
+/// Headers from `genesis` up to `target`, checked for linkage; returns the last two
+async fn download_headers(
+    network:&Network,
+    messages:&mut UnboundedReceiver<InboundMessage>,
+    peer:&PeerId,
+    genesis:&Block,
+    target:u64,
+    config:&StateSyncConfig,
+)->Result<(BlockHeader,BlockHeader),StateSyncError>{
+    let snapshot_height=target-1;
+    let mut tail:Vec<BlockHeader>=Vec::new();
+    loop{
+        let (parent_index,parent_hash)=tail.last().map_or((genesis.index,genesis.hash.clone()),|h| (h.index,h.hash.clone()));
+        if parent_index>=target{
+            let next=tail.pop().ok_or(StateSyncError::InvalidHeaders(snapshot_height))?;
+            let header=tail.pop().ok_or(StateSyncError::InvalidHeaders(snapshot_height))?;
+            return Ok((header,next))
+        }
+        let max=config.header_batch.min((target-parent_index).min(u32::MAX as u64) as u32);
+        let _=network.send_message(peer,&SyncMessage::GetHeaders{from:parent_index+1,max});
+        let headers=reply(messages,peer,config.request_timeout,"headers",|message| match message{
+            SyncMessage::Headers{headers}=>Some(headers),
+            _=>None,
+        })
+        .await?;
+        let headers:Vec<BlockHeader>=headers.into_iter().take_while(|h| h.index<=target).collect();
+        if headers.is_empty(){
+            // the peer's chain ends at the snapshot: nothing final about it yet
+            return Err(if parent_index==snapshot_height{StateSyncError::NotFinal(snapshot_height)} else{StateSyncError::InvalidHeaders(snapshot_height)})
+        }
+        if !linked(parent_index,&parent_hash,&headers){
+            network.report(peer,Misbehavior::InvalidBlock);
+            return Err(StateSyncError::InvalidHeaders(snapshot_height))
+        }
+        tail.extend(headers);
+        // only the snapshot block and the one after are needed in the end
+        tail.drain(..tail.len().saturating_sub(2));
+    }
+}
+
+/// Every chunk of `manifest`, spread over `providers`; a provider that times out, sends a
+/// wrong chunk or no longer has the snapshot isn't asked again
+async fn download_chunks(
+    network:&Network,
+    messages:&mut UnboundedReceiver<InboundMessage>,
+    manifest:&SnapshotManifest,
+    mut providers:Vec<PeerId>,
+    config:&StateSyncConfig,
+)->Result<Vec<Chunk>,StateSyncError>{
+    let mut chunks:Vec<Option<Chunk>>=vec![None;manifest.chunk_hashes.len()];
+    let mut pending:VecDeque<u32>=(0..chunks.len() as u32).collect();
+    // chunk -> (peer, sent)
+    let mut requests:HashMap<u32,(PeerId,Instant)>=HashMap::new();
+    let mut turn=0;
+    while chunks.iter().any(Option::is_none){
+        while requests.len()<config.parallel_requests.max(1)
+        && let Some(index)=pending.pop_front(){
+            if providers.is_empty(){
+                return Err(StateSyncError::Timeout("snapshot chunks"))
+            }
+            let peer=providers[turn%providers.len()].clone();
+            turn+=1;
+            let _=network.send_message(&peer,&StateSyncMessage::GetChunk{height:manifest.height,index});
+            requests.insert(index,(peer,Instant::now()));
+        }
+        let deadline=requests.values().map(|(_,sent)| *sent+config.request_timeout).min().unwrap_or_else(Instant::now);
+        match timeout_at(deadline,messages.recv()).await{
+            Ok(Some(message))=>{
+                let Ok(StateSyncMessage::Chunk{height,index,accounts})=StateSyncMessage::from_payload(&message.payload) else{
+                    continue
+                };
+                let Some((_,sent))=requests.get(&index).filter(|(peer,_)| peer==&message.from && height==manifest.height).cloned() else{
+                    continue
+                };
+                requests.remove(&index);
+                match accounts{
+                    Some(accounts) if chunk_hash(&accounts)==manifest.chunk_hashes[index as usize]=>{
+                        network.observer().record_response(&message.from,message.payload.len(),sent.elapsed());
+                        chunks[index as usize]=Some(accounts);
+                    }
+                    wrong=>{
+                        if wrong.is_some(){
+                            network.report(&message.from,Misbehavior::InvalidBlock);
+                        }
+                        providers.retain(|p| p!=&message.from);
+                        pending.push_back(index);
+                    }
+                }
+            }
+            Ok(None)=>return Err(StateSyncError::Timeout("snapshot chunks")),
+            Err(_)=>{
+                let now=Instant::now();
+                let expired:Vec<u32>=requests.iter().filter(|(_,(_,sent))| now.duration_since(*sent)>=config.request_timeout).map(|(index,_)| *index).collect();
+                for index in expired{
+                    let (peer,_)=requests.remove(&index).expect("expired request exists");
+                    network.report(&peer,Misbehavior::Timeout);
+                    network.observer().record_timeout(&peer);
+                    providers.retain(|p| p!=&peer);
+                    pending.push_back(index);
+                }
+            }
+        }
+    }
+    Ok(chunks.into_iter().flatten().collect())
+}
+
+/// The snapshot's chain state from the first provider sending one that matches its hash
+async fn download_chain_state(
+    network:&Network,
+    messages:&mut UnboundedReceiver<InboundMessage>,
+    manifest:&SnapshotManifest,
+    providers:&[PeerId],
+    config:&StateSyncConfig,
+)->Result<Vec<u8>,StateSyncError>{
+    for peer in providers{
+        let _=network.send_message(peer,&StateSyncMessage::GetChainState{height:manifest.height});
+        let answer=reply(messages,peer,config.request_timeout,"chain state",|message| match message{
+            StateSyncMessage::ChainState{height,chain_state} if height==manifest.height=>Some(chain_state),
+            _=>None,
+        })
+        .await;
+        match answer{
+            Ok(Some(chain_state)) if hex::encode(Sha256::digest(&chain_state))==manifest.chain_state_hash=>return Ok(chain_state),
+            Ok(Some(_))=>network.report(peer,Misbehavior::InvalidBlock),
+            Ok(None)=>{}
+            Err(_)=>network.report(peer,Misbehavior::Timeout),
+        }
+    }
+    Err(StateSyncError::Timeout("chain state"))
+}
+
+/// The first `M` from `peer` within `wait` that `accept` takes
+async fn reply<M:WireMessage,T>(
+    messages:&mut UnboundedReceiver<InboundMessage>,
+    peer:&PeerId,
+    wait:Duration,
+    what:&'static str,
+    mut accept:impl FnMut(M)->Option<T>,
+)->Result<T,StateSyncError>{
+    let deadline=Instant::now()+wait;
+    loop{
+        let Ok(Some(message))=timeout_at(deadline,messages.recv()).await else{
+            return Err(StateSyncError::Timeout(what))
+        };
+        if &message.from==peer
+        && let Ok(message)=M::from_payload(&message.payload)
+        && let Some(value)=accept(message){
+            return Ok(value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::block::ConsensusData;
+    use crate::finality::{Commit,Vote,VoteType};
+    use crate::network::NetworkConfig;
+    use crate::network::identity::NodeIdentity;
+    use crate::network::sync::{SyncConfig,SyncEngine};
+    use crate::validator::testing::validators;
+    use ed25519_dalek::Keypair;
+    use std::net::SocketAddr;
+    use tokio::time::{sleep,timeout};
+
+    fn state()->State{
+        let mut state=State::with_genesis((1..=5).map(|i| (format!("account{}",i),i*100)).collect());
+        state.set_epoch(3);
+        state.commit_state_root();
+        state
+    }
+
+    /// Blocks each finalizing their parent with precommits of `signers`, all with `state`'s root
+    fn chain(len:u64,state:&State,signers:&[(String,Keypair)])->Blockchain{
+        let mut chain=Blockchain::new();
+        for i in 1..=len{
+            let parent=chain.last_block();
+            let precommits=signers
+            .iter()
+            .map(|(address,kp)| Vote::sign(VoteType::Precommit,parent.index,parent.hash.clone(),address.clone(),kp))
+            .collect();
+            let consensus=ConsensusData{
+                last_commit:Some(Commit{height:parent.index,block_hash:parent.hash.clone(),precommits}),
+                state_root:Some(hex::encode(state.state_root())),
+                chain_state_hash:Some(hex::encode(state.chain_state_hash())),
+                ..ConsensusData::default()
+            };
+            chain.add_block_with_consensus(format!("block {}",i),consensus);
+        }
+        chain
+    }
+
+    fn config()->StateSyncConfig{
+        StateSyncConfig{snapshot_interval:5,chunk_accounts:2,request_timeout:Duration::from_secs(5),..StateSyncConfig::default()}
+    }
+
+    async fn network()->Network{
+        let config=NetworkConfig{listen_addr:SocketAddr::from(([127,0,0,1],0)),..NetworkConfig::default()};
+        Network::start(NodeIdentity::generate(),config).await.unwrap()
+    }
+
+    #[test]
+    fn test_snapshot_chunks_rebuild_the_state_root(){
+        let state=state();
+        let chain=chain(1,&state,&[]);
+        let snapshot=Snapshot::of(chain.last_block(),&state,2);
+        assert_eq!(snapshot.manifest.chunk_hashes.len(),3);
+        assert_eq!(snapshot.chunk(2).map(|c| c.len()),Some(1));
+        assert!(snapshot.chunk(3).is_none());
+
+        let chunks:Vec<Chunk>=(0..3).map(|i| snapshot.chunk(i).unwrap().clone()).collect();
+        for (chunk,hash) in chunks.iter().zip(&snapshot.manifest.chunk_hashes){
+            assert_eq!(&chunk_hash(chunk),hash);
+        }
+        let mut tampered=chunks[0].clone();
+        tampered.get_mut("account1").unwrap().balance+=1;
+        assert_ne!(chunk_hash(&tampered),snapshot.manifest.chunk_hashes[0]);
+
+        assert!(State::from_snapshot(Chunk::new(),b"not a chain state").is_err());
+        let mut restored=State::from_snapshot(chunks.into_iter().flatten().collect(),snapshot.chain_state()).unwrap();
+        assert_eq!(hex::encode(restored.commit_state_root()),snapshot.manifest.state_root);
+        assert_eq!(restored.get_balance("account3"),300);
+        assert_eq!(restored.current_epoch(),3);
+        assert_eq!(restored.chain_state_hash(),state.chain_state_hash());
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_from_snapshot_then_replay(){
+        let state=state();
+        let source=chain(12,&state,&[]);
+        let genesis=source.chain[0].clone();
+        let trust=SnapshotTrust::Block{height:10,hash:source.block(10).unwrap().hash.clone()};
+        // peers only offer their newest snapshot
+        let stale=SnapshotTrust::Block{height:5,hash:source.block(5).unwrap().hash.clone()};
+        let server=network().await;
+        let server=SnapshotServer::new(server,config());
+        for height in [5,10]{
+            assert!(server.on_finalized(source.block(height).unwrap(),&state));
+        }
+        assert!(!server.on_finalized(source.block(11).unwrap(),&state));
+        assert_eq!(server.latest().map(|m| m.height),Some(10));
+        assert_eq!(server.take(source.block(7).unwrap(),&state).height,10);
+        let sync=SyncConfig{tick:Duration::from_millis(10),status_interval:Duration::from_millis(50),..SyncConfig::default()};
+        let (engine,_)=SyncEngine::new(server.network.clone(),Arc::new(Mutex::new(source)),sync.clone());
+        tokio::spawn(engine.run());
+        tokio::spawn(server.clone().run());
+
+        let client=network().await;
+        client.dial(server.network.local_addr()).await.unwrap();
+        assert_eq!(bootstrap(&client,&genesis,&config()).await.err(),Some(StateSyncError::NoTrust));
+        assert_eq!(
+            bootstrap(&client,&genesis,&StateSyncConfig{trust:Some(stale),..config()}).await.err(),
+            Some(StateSyncError::NoSnapshot)
+        );
+        let restored=bootstrap(&client,&genesis,&StateSyncConfig{trust:Some(trust),..config()}).await.unwrap();
+        assert_eq!((restored.manifest.height,restored.chain.height()),(10,10));
+        assert_eq!(restored.chain.finalized_height(),Some(10));
+        assert_eq!(restored.state.state_root(),state.state_root());
+        assert_eq!(restored.state.get_balance("account5"),500);
+        assert_eq!(restored.state.current_epoch(),3);
+
+        // only the blocks after the snapshot are downloaded
+        let chain=Arc::new(Mutex::new(restored.chain));
+        let (engine,mut imported)=SyncEngine::new(client,chain.clone(),sync);
+        tokio::spawn(engine.run());
+        for height in 11..=12{
+            let block=timeout(Duration::from_secs(10),imported.recv()).await.unwrap().unwrap();
+            assert_eq!(block.index,height);
+        }
+        sleep(Duration::from_millis(50)).await;
+        assert_eq!(chain.lock().unwrap().height(),12);
+        assert!(chain.lock().unwrap().is_valid());
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_checks_the_commit_against_the_committee(){
+        let (registry,keys)=validators(3);
+        let weights:HashMap<String,u64>=keys.iter().map(|(address,_)| (address.clone(),1)).collect();
+        let committee=|registry:&ValidatorRegistry,min_providers|{
+            let trust=SnapshotTrust::Committee{registry:registry.clone(),weights:weights.clone()};
+            StateSyncConfig{trust:Some(trust),min_providers,..config()}
+        };
+        let state=state();
+        let source=chain(11,&state,&keys);
+        let genesis=source.chain[0].clone();
+        let server=SnapshotServer::new(network().await,config());
+        assert!(server.on_finalized(source.block(10).unwrap(),&state));
+        let sync=SyncConfig{tick:Duration::from_millis(10),status_interval:Duration::from_millis(50),..SyncConfig::default()};
+        let (engine,_)=SyncEngine::new(server.network.clone(),Arc::new(Mutex::new(source)),sync);
+        tokio::spawn(engine.run());
+        tokio::spawn(server.clone().run());
+
+        let client=network().await;
+        client.dial(server.network.local_addr()).await.unwrap();
+        // one peer alone isn't enough agreement
+        assert_eq!(bootstrap(&client,&genesis,&committee(&registry,2)).await.err(),Some(StateSyncError::NoSnapshot));
+        let restored=bootstrap(&client,&genesis,&committee(&registry,1)).await.unwrap();
+        assert_eq!(restored.manifest.height,10);
+        assert_eq!(restored.state.state_root(),state.state_root());
+
+        // the same names with other keys: the commit no longer verifies
+        let (impostors,_)=validators(3);
+        assert_eq!(
+            bootstrap(&client,&genesis,&committee(&impostors,1)).await.err(),
+            Some(StateSyncError::InvalidCommit(10))
+        );
+    }
+}
@@ -0,0 +1,838 @@
+// src/network/sync.rs
+
+//! Headers-first block synchronization
+//! - Peers exchange `Status` (tip height and hash) when they connect and every
+//!   `status_interval`
+//! - A node behind its best peer downloads headers from that peer in batches of
+//!   `header_batch`, checking that each one links to the one before it
+//! - Once the headers reach the peer's tip, bodies are requested by height in batches of
+//!   `body_batch`, up to `parallel_requests` at a time and `requests_per_peer` per peer,
+//!   spread over every peer that has them; a body is only accepted if it hashes to its
+//!   header's hash
+//! - Each batch goes to the peer expected to deliver it soonest, judged by its measured time
+//!   per block (`PeerPerformance`) and what it already has outstanding
+//! - Bodies arriving out of order wait in a reordering buffer; blocks are imported into the
+//!   chain in order and handed to the node for execution while later ones download. At most
+//!   `max_buffered_blocks` are downloaded ahead of what the node has taken, so a node that
+//!   executes slowly slows the download rather than filling memory
+//! - When the buffer is full and waits on a slow request for the next block, that request
+//!   is handed to another peer
+//! - Downloaded headers are saved to `checkpoint_path`, so after a restart only the missing
+//!   bodies are fetched
+
+use super::identity::PeerId;
+use super::ratelimit::RateClass;
+use super::reputation::Misbehavior;
+use super::wire::WireMessage;
+use super::{InboundMessage,Network,NetworkError,NetworkEvent,Topic};
+use crate::block::{Block,ConsensusData};
+use crate::blockchain::Blockchain;
+use chrono::{DateTime,Utc};
+use serde::{Deserialize,Serialize};
+use std::collections::{BTreeMap,BTreeSet,HashMap};
+use std::path::PathBuf;
+use std::sync::{Arc,Mutex};
+use std::time::{Duration,Instant};
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{mpsc,watch};
+use tokio::time::interval;
+
+/// Most headers served per `GetHeaders`
+pub const MAX_HEADERS_PER_REQUEST:u32=2048;
+
+/// Most bodies served per `GetBodies`
+pub const MAX_BODIES_PER_REQUEST:usize=256;
+
+/// A block without its data
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct BlockHeader{
+    pub index:u64,
+    pub timestamp:DateTime<Utc>,
+    pub previous_hash:String,
+    pub consensus:ConsensusData,
+    /// Hash of the full block, data included
+    pub hash:String,
+}
+
+impl BlockHeader{
+    pub fn of(block:&Block)->Self{
+        BlockHeader{
+            index:block.index,
+            timestamp:block.timestamp,
+            previous_hash:block.previous_hash.clone(),
+            consensus:block.consensus.clone(),
+            hash:block.hash.clone(),
+        }
+    }
+
+    /// Whether this header directly follows block `parent_hash` at `parent_index`, with any
+    /// carried commit referring to that parent
+    pub fn extends(&self,parent_index:u64,parent_hash:&str)->bool{
+        let for_parent=|height:u64,hash:&str| height==parent_index && hash==parent_hash;
+        self.index==parent_index+1
+        && self.previous_hash==parent_hash
+        && self.consensus.last_commit.as_ref().is_none_or(|c| for_parent(c.height,&c.block_hash))
+        && self.consensus.last_aggregate_commit.as_ref().is_none_or(|c| for_parent(c.height,&c.block_hash))
+    }
+
+    /// The full block, if `data` is the body this header commits to
+    pub fn with_body(&self,data:String)->Option<Block>{
+        let block=Block{
+            index:self.index,
+            timestamp:self.timestamp,
+            data,
+            previous_hash:self.previous_hash.clone(),
+            consensus:self.consensus.clone(),
+            hash:self.hash.clone(),
+        };
+        (block.recalculate_hash()==self.hash).then_some(block)
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct BlockBody{
+    pub index:u64,
+    pub data:String,
+}
+
+/// Payloads of `Topic::Sync`
+#[derive(Debug,Clone,Serialize,Deserialize)]
+pub enum SyncMessage{
+    /// The sender's chain tip
+    Status{height:u64,hash:String},
+    GetHeaders{from:u64,max:u32},
+    Headers{headers:Vec<BlockHeader>},
+    GetBodies{heights:Vec<u64>},
+    Bodies{bodies:Vec<BlockBody>},
+}
+
+impl WireMessage for SyncMessage{
+    const TOPIC:Topic=Topic::Sync;
+}
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub enum SyncPhase{
+    /// Up to date with every known peer
+    Idle,
+    DownloadingHeaders,
+    DownloadingBodies,
+}
+
+/// Where a sync stands, for status displays
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct SyncProgress{
+    pub phase:SyncPhase,
+    /// Chain height when the current sync began
+    pub start_height:u64,
+    /// Chain height now
+    pub current_height:u64,
+    /// Highest header downloaded and checked
+    pub headers_height:u64,
+    /// Tip of the peer being synced from
+    pub target_height:u64,
+    pub peer:Option<PeerId>,
+}
+
+/// How a peer has answered our body requests
+#[derive(Debug,Clone,PartialEq)]
+pub struct PeerPerformance{
+    pub peer:PeerId,
+    /// Valid bodies delivered
+    pub blocks:u64,
+    /// Requests that timed out or were handed to another peer for being slow
+    pub timeouts:u64,
+    /// Moving average of the response time per block; 0 until something was delivered
+    pub ms_per_block:f64,
+}
+
+impl PeerPerformance{
+    /// Weight of a new sample in `ms_per_block`
+    const SMOOTHING:f64=0.3;
+
+    fn new(peer:PeerId)->Self{
+        PeerPerformance{peer,blocks:0,timeouts:0,ms_per_block:0.0}
+    }
+
+    fn sample(&mut self,elapsed:Duration,blocks:usize){
+        let per_block=elapsed.as_secs_f64()*1_000.0/blocks.max(1) as f64;
+        self.ms_per_block=if self.blocks==0 && self.timeouts==0{
+            per_block
+        } else{
+            self.ms_per_block+(per_block-self.ms_per_block)*Self::SMOOTHING
+        };
+    }
+}
+
+#[derive(Debug,Clone)]
+pub struct SyncConfig{
+    pub header_batch:u32,
+    pub body_batch:usize,
+    /// Body requests outstanding at once, over all peers
+    pub parallel_requests:usize,
+    /// Body requests outstanding at once to one peer
+    pub requests_per_peer:usize,
+    /// Most blocks requested or downloaded but not yet taken by the node for execution
+    pub max_buffered_blocks:usize,
+    /// A request unanswered for this long is sent again (headers: to another peer)
+    pub request_timeout:Duration,
+    pub status_interval:Duration,
+    /// How often requests are checked and sent
+    pub tick:Duration,
+    /// File keeping downloaded headers across restarts
+    pub checkpoint_path:Option<PathBuf>,
+}
+
+impl Default for SyncConfig{
+    fn default()->Self{
+        SyncConfig{
+            header_batch:512,
+            body_batch:64,
+            parallel_requests:8,
+            requests_per_peer:2,
+            max_buffered_blocks:1024,
+            request_timeout:Duration::from_secs(10),
+            status_interval:Duration::from_secs(5),
+            tick:Duration::from_millis(200),
+            checkpoint_path:None,
+        }
+    }
+}
+
+/// Saved to `SyncConfig::checkpoint_path`
+#[derive(Debug,Clone,Serialize,Deserialize)]
+struct SyncCheckpoint{
+    target_height:u64,
+    headers:Vec<BlockHeader>,
+}
+
+struct BodyRequest{
+    peer:PeerId,
+    heights:BTreeSet<u64>,
+    sent:Instant,
+}
+
+struct SyncState{
+    /// Last `Status` of each connected peer
+    peers:HashMap<PeerId,(u64,String)>,
+    phase:SyncPhase,
+    sync_peer:Option<PeerId>,
+    target_height:u64,
+    start_height:u64,
+    /// Checked headers above the chain tip
+    headers:BTreeMap<u64,BlockHeader>,
+    /// When the outstanding `GetHeaders` was sent
+    headers_requested:Option<Instant>,
+    /// Heights whose bodies are neither requested nor downloaded
+    pending:BTreeSet<u64>,
+    requests:Vec<BodyRequest>,
+    /// Reordering buffer: downloaded blocks waiting for their parent to be imported
+    blocks:BTreeMap<u64,Block>,
+    last_status:Option<Instant>,
+    performance:HashMap<PeerId,PeerPerformance>,
+}
+
+/// Sync engine over a running `Network`
+#[derive(Clone)]
+pub struct SyncEngine{
+    network:Network,
+    chain:Arc<Mutex<Blockchain>>,
+    config:SyncConfig,
+    state:Arc<Mutex<SyncState>>,
+    progress:Arc<watch::Sender<SyncProgress>>,
+    imported:mpsc::Sender<Block>,
+}
+
+impl SyncEngine{
+    /// Sync engine and the stream of blocks it imports, to be executed by the node; at most
+    /// `max_buffered_blocks` wait in it. Headers saved by an earlier run are picked up if
+    /// they still extend the chain.
+    pub fn new(
+        network:Network,
+        chain:Arc<Mutex<Blockchain>>,
+        config:SyncConfig,
+    )->(Self,mpsc::Receiver<Block>){
+        let (tip_height,tip_hash)={
+            let chain=chain.lock().expect("chain lock poisoned");
+            (chain.height(),chain.last_block().hash.clone())
+        };
+        let mut state=SyncState{
+            peers:HashMap::new(),
+            phase:SyncPhase::Idle,
+            sync_peer:None,
+            target_height:tip_height,
+            start_height:tip_height,
+            headers:BTreeMap::new(),
+            headers_requested:None,
+            pending:BTreeSet::new(),
+            requests:Vec::new(),
+            blocks:BTreeMap::new(),
+            last_status:None,
+            performance:HashMap::new(),
+        };
+        if let Some(checkpoint)=config.checkpoint_path.as_ref().and_then(load_checkpoint){
+            let headers:Vec<BlockHeader>=checkpoint.headers.into_iter().filter(|h| h.index>tip_height).collect();
+            if linked(tip_height,&tip_hash,&headers){
+                state.target_height=checkpoint.target_height;
+                state.headers=headers.into_iter().map(|h| (h.index,h)).collect();
+                if state.headers.last_key_value().is_some_and(|(height,_)| *height>=state.target_height){
+                    state.phase=SyncPhase::DownloadingBodies;
+                    state.pending=state.headers.keys().copied().collect();
+                }
+            }
+        }
+        let (imported,blocks)=mpsc::channel(config.max_buffered_blocks.max(1));
+        let progress=watch::channel(progress_of(&state,tip_height)).0;
+        let engine=SyncEngine{
+            network,
+            chain,
+            config,
+            state:Arc::new(Mutex::new(state)),
+            progress:Arc::new(progress),
+            imported,
+        };
+        (engine,blocks)
+    }
+
+    pub fn progress(&self)->SyncProgress{
+        self.progress.borrow().clone()
+    }
+
+    /// Progress updates as they happen
+    pub fn watch_progress(&self)->watch::Receiver<SyncProgress>{
+        self.progress.subscribe()
+    }
+
+    /// Highest tip a connected peer reported in its last `Status`
+    pub fn best_peer_height(&self)->Option<u64>{
+        self.state.lock().expect("sync state poisoned").peers.values().map(|(height,_)| *height).max()
+    }
+
+    /// How each peer has served body requests, fastest first
+    pub fn peer_performance(&self)->Vec<PeerPerformance>{
+        let state=self.state.lock().expect("sync state poisoned");
+        let mut performance:Vec<PeerPerformance>=state.performance.values().cloned().collect();
+        performance.sort_by(|a,b| a.ms_per_block.total_cmp(&b.ms_per_block).then_with(|| a.peer.cmp(&b.peer)));
+        performance
+    }
+
+    /// Serve and sync until the network shuts down
+    pub async fn run(self){
+        let mut messages=self.network.subscribe(Topic::Sync);
+        let mut events=self.network.events();
+        let mut ticker=interval(self.config.tick);
+        loop{
+            tokio::select!{
+                _=ticker.tick()=>self.tick(),
+                Some(message)=messages.recv()=>self.handle(message),
+                Ok(event)=events.recv()=>self.peer_event(event),
+                else=>break,
+            }
+        }
+    }
+
+    fn tip(&self)->(u64,String){
+        let chain=self.chain.lock().expect("chain lock poisoned");
+        (chain.height(),chain.last_block().hash.clone())
+    }
+
+    fn status(&self)->SyncMessage{
+        let (height,hash)=self.tip();
+        SyncMessage::Status{height,hash}
+    }
+
+    fn peer_event(&self,event:NetworkEvent){
+        match event{
+            NetworkEvent::PeerConnected{peer,..}=>{
+                let _=self.network.send_message(&peer,&self.status());
+            }
+            NetworkEvent::PeerDisconnected{peer,..}=>{
+                let mut state=self.state.lock().expect("sync state poisoned");
+                state.peers.remove(&peer);
+                self.drop_requests_of(&mut state,&peer);
+            }
+            NetworkEvent::DialFailed{..}
+            | NetworkEvent::PortMappingFailed{..}
+            | NetworkEvent::ReservationFailed{..}
+            | NetworkEvent::SaveFailed{..}=>{}
+        }
+    }
+
+    /// Forget what was asked of `peer`; a lost sync peer ends the header download
+    fn drop_requests_of(&self,state:&mut SyncState,peer:&PeerId){
+        let (lost,kept):(Vec<BodyRequest>,Vec<BodyRequest>)=std::mem::take(&mut state.requests).into_iter().partition(|r| &r.peer==peer);
+        state.requests=kept;
+        for request in lost{
+            state.pending.extend(request.heights);
+        }
+        if state.sync_peer.as_ref()==Some(peer) && state.phase==SyncPhase::DownloadingHeaders{
+            state.phase=SyncPhase::Idle;
+            state.sync_peer=None;
+            state.headers_requested=None;
+        }
+    }
+
+    fn handle(&self,message:InboundMessage){
+        let Ok(sync)=SyncMessage::from_payload(&message.payload) else{
+            self.network.report(&message.from,Misbehavior::BadMessage);
+            return
+        };
+        match sync{
+            SyncMessage::Status{height,hash}=>{
+                self.state.lock().expect("sync state poisoned").peers.insert(message.from,(height,hash));
+            }
+            SyncMessage::GetHeaders{..}|SyncMessage::GetBodies{..} if !self.network.allow(&message.from,RateClass::BlockRequests)=>return,
+            SyncMessage::GetHeaders{from,max}=>{
+                let chain=self.chain.lock().expect("chain lock poisoned");
+                let headers=(from..from.saturating_add(max.min(MAX_HEADERS_PER_REQUEST) as u64))
+                .map_while(|height| chain.block(height).map(BlockHeader::of))
+                .collect();
+                drop(chain);
+                let _=self.network.send_message(&message.from,&SyncMessage::Headers{headers});
+            }
+            SyncMessage::GetBodies{heights}=>{
+                let chain=self.chain.lock().expect("chain lock poisoned");
+                let bodies=heights
+                .into_iter()
+                .take(MAX_BODIES_PER_REQUEST)
+                .filter_map(|index| chain.block(index).map(|b| BlockBody{index,data:b.data.clone()}))
+                .collect();
+                drop(chain);
+                let _=self.network.send_message(&message.from,&SyncMessage::Bodies{bodies});
+            }
+            SyncMessage::Headers{headers}=>self.on_headers(&message.from,headers,message.payload.len()),
+            SyncMessage::Bodies{bodies}=>self.on_bodies(&message.from,bodies,message.payload.len()),
+        }
+        self.import_ready();
+    }
+
+    /// `size`: bytes of the response, for the peer's observed throughput
+    fn on_headers(&self,from:&PeerId,headers:Vec<BlockHeader>,size:usize){
+        let (tip_height,tip_hash)=self.tip();
+        let mut state=self.state.lock().expect("sync state poisoned");
+        if state.phase!=SyncPhase::DownloadingHeaders || state.sync_peer.as_ref()!=Some(from){
+            return
+        }
+        if let Some(sent)=state.headers_requested.take(){
+            self.network.observer().record_response(from,size,sent.elapsed());
+        }
+        let (parent_height,parent_hash)=match state.headers.last_key_value(){
+            Some((height,header))=>(*height,header.hash.clone()),
+            None=>(tip_height,tip_hash),
+        };
+        let headers:Vec<BlockHeader>=headers.into_iter().take_while(|h| h.index<=state.target_height).collect();
+        if headers.is_empty() || !linked(parent_height,&parent_hash,&headers){
+            // the peer can't take us to the tip it announced; try another one
+            if !headers.is_empty(){
+                self.network.report(from,Misbehavior::InvalidBlock);
+            }
+            state.peers.remove(from);
+            state.phase=SyncPhase::Idle;
+            state.sync_peer=None;
+            return
+        }
+        state.headers.extend(headers.into_iter().map(|h| (h.index,h)));
+        let headers_height=state.headers.last_key_value().map_or(tip_height,|(height,_)| *height);
+        if headers_height>=state.target_height{
+            state.phase=SyncPhase::DownloadingBodies;
+            let heights:Vec<u64>=state.headers.keys().copied().filter(|h| !state.blocks.contains_key(h)).collect();
+            state.pending.extend(heights);
+        } else{
+            self.request_headers(&mut state,from.clone(),headers_height+1);
+        }
+        self.save_checkpoint(&state);
+        self.publish(&state,tip_height);
+    }
+
+    fn on_bodies(&self,from:&PeerId,bodies:Vec<BlockBody>,size:usize){
+        let mut state=self.state.lock().expect("sync state poisoned");
+        let sent=bodies.first().and_then(|body| state.requests.iter().find(|r| &r.peer==from && r.heights.contains(&body.index))).map(|r| r.sent);
+        if let Some(sent)=sent{
+            self.network.observer().record_response(from,size,sent.elapsed());
+        }
+        let mut delivered=0;
+        for body in bodies{
+            let Some(request)=state.requests.iter_mut().find(|r| &r.peer==from && r.heights.contains(&body.index)) else{
+                continue
+            };
+            request.heights.remove(&body.index);
+            let block=state.headers.get(&body.index).and_then(|header| header.with_body(body.data));
+            match block{
+                Some(block)=>{
+                    state.blocks.insert(block.index,block);
+                    delivered+=1;
+                }
+                // wrong body: ask again, possibly from someone else
+                None=>{
+                    self.network.report(from,Misbehavior::InvalidBlock);
+                    state.pending.insert(body.index);
+                }
+            }
+        }
+        state.requests.retain(|r| !r.heights.is_empty());
+        if let Some(sent)=sent
+        && delivered>0{
+            let performance=state.performance.entry(from.clone()).or_insert_with(|| PeerPerformance::new(from.clone()));
+            performance.sample(sent.elapsed(),delivered);
+            performance.blocks+=delivered as u64;
+        }
+    }
+
+    /// Import downloaded blocks that extend the tip, as far as the node keeps up with
+    /// executing them
+    fn import_ready(&self){
+        let mut state=self.state.lock().expect("sync state poisoned");
+        let mut chain=self.chain.lock().expect("chain lock poisoned");
+        let mut imported=false;
+        while state.blocks.contains_key(&(chain.height()+1)){
+            let permit=match self.imported.try_reserve(){
+                Ok(permit)=>Some(permit),
+                // the node is still executing earlier blocks
+                Err(TrySendError::Full(()))=>break,
+                // nobody executes them; keep the chain going anyway
+                Err(TrySendError::Closed(()))=>None,
+            };
+            let block=state.blocks.remove(&(chain.height()+1)).expect("next block is buffered");
+            if chain.import_block(block.clone()).is_err(){
+                // the headers didn't extend our chain after all; start over
+                state.headers.clear();
+                state.blocks.clear();
+                state.pending.clear();
+                state.requests.clear();
+                state.phase=SyncPhase::Idle;
+                state.sync_peer=None;
+                break
+            }
+            state.headers.remove(&block.index);
+            if let Some(permit)=permit{
+                permit.send(block);
+            }
+            imported=true;
+        }
+        let height=chain.height();
+        drop(chain);
+        if state.phase==SyncPhase::DownloadingBodies && height>=state.target_height{
+            state.phase=SyncPhase::Idle;
+            state.sync_peer=None;
+        }
+        if imported{
+            self.save_checkpoint(&state);
+        }
+        self.publish(&state,height);
+    }
+
+    fn tick(&self){
+        // the node may have taken blocks since, making room for more
+        self.import_ready();
+        let (tip_height,_)=self.tip();
+        let now=Instant::now();
+        let mut state=self.state.lock().expect("sync state poisoned");
+        if state.last_status.is_none_or(|t| now.duration_since(t)>=self.config.status_interval){
+            state.last_status=Some(now);
+            self.network.broadcast_message(&self.status(),None);
+        }
+
+        // timed out requests
+        if state.headers_requested.is_some_and(|t| now.duration_since(t)>=self.config.request_timeout)
+        && let Some(peer)=state.sync_peer.clone(){
+            self.network.report(&peer,Misbehavior::Timeout);
+            self.network.observer().record_timeout(&peer);
+            state.peers.remove(&peer);
+            self.drop_requests_of(&mut state,&peer);
+        }
+        let (expired,live):(Vec<BodyRequest>,Vec<BodyRequest>)=std::mem::take(&mut state.requests)
+        .into_iter()
+        .partition(|r| now.duration_since(r.sent)>=self.config.request_timeout);
+        state.requests=live;
+        for request in expired{
+            self.network.report(&request.peer,Misbehavior::Timeout);
+            self.network.observer().record_timeout(&request.peer);
+            self.slow(&mut state,request,now);
+        }
+        // a full buffer waiting on a slow request for the next block: ask someone else
+        if self.buffer_full(&state)
+        && let Some(position)=state
+        .requests
+        .iter()
+        .position(|r| r.heights.contains(&(tip_height+1)) && now.duration_since(r.sent)>=self.config.request_timeout/4){
+            let request=state.requests.remove(position);
+            self.slow(&mut state,request,now);
+        }
+
+        match state.phase{
+            SyncPhase::Idle=>{
+                let best=state.peers.iter().max_by_key(|(_,(height,_))| *height).map(|(peer,(height,_))| (peer.clone(),*height));
+                if let Some((peer,height))=best.filter(|(_,height)| *height>tip_height){
+                    let from=state.headers.last_key_value().map_or(tip_height,|(h,_)| *h)+1;
+                    state.phase=SyncPhase::DownloadingHeaders;
+                    state.start_height=tip_height;
+                    state.target_height=height;
+                    state.sync_peer=Some(peer.clone());
+                    self.request_headers(&mut state,peer,from);
+                }
+            }
+            SyncPhase::DownloadingHeaders=>{}
+            SyncPhase::DownloadingBodies=>self.request_bodies(&mut state,now),
+        }
+        self.publish(&state,tip_height);
+    }
+
+    /// Put a request that took too long back in the queue and count it against its peer
+    fn slow(&self,state:&mut SyncState,request:BodyRequest,now:Instant){
+        let performance=state.performance.entry(request.peer.clone()).or_insert_with(|| PeerPerformance::new(request.peer.clone()));
+        performance.sample(now.duration_since(request.sent),request.heights.len());
+        performance.timeouts+=1;
+        state.pending.extend(request.heights);
+    }
+
+    /// Whether as many blocks as `max_buffered_blocks` are requested, waiting for import or
+    /// waiting for the node
+    fn buffer_full(&self,state:&SyncState)->bool{
+        let requested:usize=state.requests.iter().map(|r| r.heights.len()).sum();
+        let handed_over=self.imported.max_capacity()-self.imported.capacity();
+        requested+state.blocks.len()+handed_over>=self.config.max_buffered_blocks
+    }
+
+    fn request_headers(&self,state:&mut SyncState,peer:PeerId,from:u64){
+        state.headers_requested=Some(Instant::now());
+        let request=SyncMessage::GetHeaders{from,max:self.config.header_batch};
+        let _=self.network.send_message(&peer,&request);
+    }
+
+    /// Hand out pending heights in batches, lowest first, to the peer expected to deliver
+    /// soonest among those that have them and aren't at `requests_per_peer`; peers not
+    /// measured yet are tried first
+    fn request_bodies(&self,state:&mut SyncState,now:Instant){
+        while state.requests.len()<self.config.parallel_requests && !state.pending.is_empty(){
+            let requested:usize=state.requests.iter().map(|r| r.heights.len()).sum();
+            let handed_over=self.imported.max_capacity()-self.imported.capacity();
+            let room=self.config.max_buffered_blocks.saturating_sub(requested+state.blocks.len()+handed_over);
+            if room==0{
+                break
+            }
+            let batch:BTreeSet<u64>=state.pending.iter().copied().take(self.config.body_batch.max(1).min(room)).collect();
+            let last=*batch.last().expect("batch is not empty");
+            let load=|peer:&PeerId| state.requests.iter().filter(|r| &r.peer==peer).count();
+            let expected=|peer:&PeerId| state.performance.get(peer).map_or(0.0,|p| p.ms_per_block)*(load(peer)+1) as f64;
+            let Some(peer)=state
+            .peers
+            .iter()
+            .filter(|(_,(height,_))| *height>=last)
+            .map(|(peer,_)| peer)
+            .filter(|peer| load(peer)<self.config.requests_per_peer.max(1))
+            .min_by(|a,b| expected(a).total_cmp(&expected(b)).then_with(|| load(a).cmp(&load(b))).then_with(|| a.cmp(b)))
+            .cloned()
+            else{
+                break
+            };
+            let request=SyncMessage::GetBodies{heights:batch.iter().copied().collect()};
+            if self.network.send_message(&peer,&request).is_err(){
+                break
+            }
+            state.pending.retain(|h| !batch.contains(h));
+            state.requests.push(BodyRequest{peer,heights:batch,sent:now});
+        }
+    }
+
+    fn publish(&self,state:&SyncState,current_height:u64){
+        self.progress.send_if_modified(|progress|{
+            let next=progress_of(state,current_height);
+            let changed=*progress!=next;
+            *progress=next;
+            changed
+        });
+    }
+
+    fn save_checkpoint(&self,state:&SyncState){
+        let Some(path)=&self.config.checkpoint_path else{
+            return
+        };
+        let result=if state.headers.is_empty(){
+            std::fs::remove_file(path).or_else(|e| if e.kind()==std::io::ErrorKind::NotFound{Ok(())} else{Err(e)})
+        } else{
+            let checkpoint=SyncCheckpoint{target_height:state.target_height,headers:state.headers.values().cloned().collect()};
+            let json=serde_json::to_string(&checkpoint).expect("checkpoint serializes");
+            std::fs::write(path,json)
+        };
+        if let Err(e)=result{
+            self.network.emit(NetworkEvent::SaveFailed{error:NetworkError::Io(format!("{}: {}",path.display(),e))});
+        }
+    }
+}
+
+fn progress_of(state:&SyncState,current_height:u64)->SyncProgress{
+    SyncProgress{
+        phase:state.phase,
+        start_height:state.start_height,
+        current_height,
+        headers_height:state.headers.last_key_value().map_or(current_height,|(height,_)| *height),
+        target_height:state.target_height.max(current_height),
+        peer:state.sync_peer.clone(),
+    }
+}
+
+fn load_checkpoint(path:&PathBuf)->Option<SyncCheckpoint>{
+    let text=std::fs::read_to_string(path).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Whether `headers` form a chain on top of block `parent_hash` at `parent_index`
+pub(super) fn linked(parent_index:u64,parent_hash:&str,headers:&[BlockHeader])->bool{
+    let mut parent=(parent_index,parent_hash);
+    for header in headers{
+        if !header.extends(parent.0,parent.1){
+            return false
+        }
+        parent=(header.index,&header.hash);
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::network::NetworkConfig;
+    use crate::network::identity::NodeIdentity;
+    use std::net::SocketAddr;
+    use tokio::time::{sleep,timeout};
+
+    fn chain(len:u64)->Blockchain{
+        let mut chain=Blockchain::new();
+        for i in 1..=len{
+            chain.add_block(format!("block {}",i));
+        }
+        chain
+    }
+
+    fn copy(chain:&Blockchain)->Blockchain{
+        let mut copy=Blockchain::with_genesis(chain.chain[0].clone());
+        for block in &chain.chain[1..]{
+            copy.import_block(block.clone()).unwrap();
+        }
+        copy
+    }
+
+    fn config()->SyncConfig{
+        SyncConfig{
+            header_batch:8,
+            body_batch:5,
+            parallel_requests:3,
+            status_interval:Duration::from_millis(50),
+            tick:Duration::from_millis(10),
+            ..SyncConfig::default()
+        }
+    }
+
+    async fn node(chain:Blockchain,config:SyncConfig)->(Network,SyncEngine,Arc<Mutex<Blockchain>>,mpsc::Receiver<Block>){
+        let network_config=NetworkConfig{listen_addr:SocketAddr::from(([127,0,0,1],0)),..NetworkConfig::default()};
+        let network=Network::start(NodeIdentity::generate(),network_config).await.unwrap();
+        let chain=Arc::new(Mutex::new(chain));
+        let (engine,imported)=SyncEngine::new(network.clone(),chain.clone(),config);
+        tokio::spawn(engine.clone().run());
+        (network,engine,chain,imported)
+    }
+
+    async fn synced(chain:&Arc<Mutex<Blockchain>>,height:u64){
+        timeout(Duration::from_secs(10),async {
+            while chain.lock().unwrap().height()<height{
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn test_headers_link_and_bodies_match(){
+        let chain=chain(3);
+        let headers:Vec<BlockHeader>=chain.chain[1..].iter().map(BlockHeader::of).collect();
+        let genesis=&chain.chain[0].hash;
+        assert!(linked(0,genesis,&headers));
+        assert!(!linked(0,genesis,&headers[1..]));
+        assert!(!linked(0,&"0".repeat(64),&headers));
+
+        assert_eq!(headers[0].with_body("block 1".to_string()).unwrap().hash,chain.chain[1].hash);
+        assert!(headers[0].with_body("block 2".to_string()).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_catch_up_from_several_peers(){
+        let source=chain(40);
+        let genesis=source.chain[0].clone();
+        let (a,_,_,_)=node(copy(&source),config()).await;
+        let (b,_,_,_)=node(source,config()).await;
+        let (c,engine,c_chain,mut imported)=node(Blockchain::with_genesis(genesis),config()).await;
+        c.dial(a.local_addr()).await.unwrap();
+        c.dial(b.local_addr()).await.unwrap();
+
+        synced(&c_chain,40).await;
+        assert!(c_chain.lock().unwrap().is_valid());
+        for height in 1..=40{
+            assert_eq!(imported.recv().await.unwrap().index,height);
+        }
+        timeout(Duration::from_secs(5),async {
+            while engine.progress().phase!=SyncPhase::Idle{
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        let progress=engine.progress();
+        assert_eq!((progress.start_height,progress.current_height,progress.target_height),(0,40,40));
+    }
+
+    #[tokio::test]
+    async fn test_download_waits_for_execution(){
+        let source=chain(40);
+        let genesis=source.chain[0].clone();
+        let (a,_,_,_)=node(copy(&source),config()).await;
+        let (b,_,_,_)=node(source,config()).await;
+        let buffered=SyncConfig{max_buffered_blocks:10,..config()};
+        let (c,engine,c_chain,mut imported)=node(Blockchain::with_genesis(genesis),buffered).await;
+        c.dial(a.local_addr()).await.unwrap();
+        c.dial(b.local_addr()).await.unwrap();
+
+        // the node takes nothing: only what fits in the buffer is fetched
+        synced(&c_chain,10).await;
+        sleep(Duration::from_millis(200)).await;
+        assert_eq!(c_chain.lock().unwrap().height(),10);
+
+        for height in 1..=40{
+            let block=timeout(Duration::from_secs(10),imported.recv()).await.unwrap().unwrap();
+            assert_eq!(block.index,height);
+        }
+        assert!(c_chain.lock().unwrap().is_valid());
+        let performance=engine.peer_performance();
+        assert_eq!(performance.iter().map(|p| p.blocks).sum::<u64>(),40);
+        assert!(performance.iter().all(|p| p.timeouts==0));
+    }
+
+    #[tokio::test]
+    async fn test_resume_from_saved_headers(){
+        let source=chain(12);
+        let path=std::env::temp_dir().join(format!("netchain-sync-{}.json",std::process::id()));
+        // a previous run got all headers and the first four blocks
+        let mut local=Blockchain::with_genesis(source.chain[0].clone());
+        for block in &source.chain[1..=4]{
+            local.import_block(block.clone()).unwrap();
+        }
+        let checkpoint=SyncCheckpoint{target_height:12,headers:source.chain[1..].iter().map(BlockHeader::of).collect()};
+        std::fs::write(&path,serde_json::to_string(&checkpoint).unwrap()).unwrap();
+
+        let (a,_,_,_)=node(source,config()).await;
+        let (b,engine,b_chain,_)=node(local,SyncConfig{checkpoint_path:Some(path.clone()),..config()}).await;
+        let progress=engine.progress();
+        assert_eq!((progress.phase,progress.current_height,progress.headers_height),(SyncPhase::DownloadingBodies,4,12));
+
+        b.dial(a.local_addr()).await.unwrap();
+        synced(&b_chain,12).await;
+        assert!(b_chain.lock().unwrap().is_valid());
+        // fully synced: nothing left to resume
+        timeout(Duration::from_secs(5),async {
+            while path.exists(){
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+    }
+}
@@ -0,0 +1,320 @@
+// src/network/wire.rs
+
+//! Wire format of peer connections
+//! - Frames are the canonical encoding of a `Frame` (see `canonical`), carried encrypted
+//!   by `noise`
+//! - Each side's `Frame::Hello` travels inside the Noise handshake. Both sides then check
+//!   the other's version, network and genesis and answer `Frame::Ready`, or
+//!   `Frame::Disconnect` with the reason before hanging up; afterwards only topic messages
+//!   flow
+//! - Capabilities are free-form names ("sync/1"), so nodes can announce protocols older
+//!   peers don't know about; a connection uses the ones both sides list
+//! - Large payloads may travel as `Frame::Compressed` with a codec both sides announced
+//!   (see `compression`)
+//! - `SCHEMA` describes every frame and topic payload byte for byte, so nodes in other
+//!   languages can join; each payload type implements `WireMessage` for its topic, and
+//!   `generate_test_vectors` emits worked examples (`cargo run --example wire_vectors`)
+
+use super::compression::{self,Codec};
+use super::identity::NodeIdentity;
+use super::noise::static_key_message;
+use super::{NetworkError,Topic};
+use crate::block::{Block,ConsensusData};
+use crate::canonical::{self,DecodeError};
+use crate::consensus::messages::ConsensusMessage;
+use crate::finality::{Vote,VoteType};
+use crate::transaction::SignedTransaction;
+use chrono::DateTime;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize,Serialize};
+use thiserror::Error;
+
+/// Version of this wire protocol
+pub const PROTOCOL_VERSION:u32=6;
+
+/// Oldest peer protocol version this node still talks to; 6 added `chain_state_hash` to
+/// block headers, which older peers can't decode
+pub const MIN_PROTOCOL_VERSION:u32=6;
+
+pub const CAP_GOSSIP:&str="gossip/1";
+pub const CAP_SYNC:&str="sync/1";
+pub const CAP_DISCOVERY:&str="kad/1";
+/// The sender serves state snapshots (see `statesync`)
+pub const CAP_STATE_SYNC:&str="state/1";
+/// The sender decodes `Frame::Compressed` with this codec
+pub const CAP_ZSTD:&str="zstd/1";
+pub const CAP_SNAPPY:&str="snappy/1";
+
+/// Capabilities announced by default
+pub fn default_capabilities()->Vec<String>{
+    [CAP_GOSSIP,CAP_SYNC,CAP_DISCOVERY,CAP_STATE_SYNC,CAP_ZSTD,CAP_SNAPPY].map(String::from).to_vec()
+}
+
+/// Language-neutral description of the wire format for `PROTOCOL_VERSION`
+pub const SCHEMA:&str=include_str!("wire.schema");
+
+/// Largest accepted frame, so a peer can't make us allocate arbitrary amounts
+pub const MAX_FRAME_BYTES:usize=4*1024*1024;
+
+/// First frame on a connection
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Hello{
+    pub protocol_version:u32,
+    /// Oldest version the sender accepts
+    pub min_protocol_version:u32,
+    /// Peers on different networks (e.g. mainnet vs a devnet) refuse each other
+    pub network_id:String,
+    /// Hash of the sender's genesis block; peers on another chain are refused
+    pub genesis_hash:String,
+    /// Sender's chain height when connecting
+    pub height:u64,
+    pub capabilities:Vec<String>,
+    /// Sender's Ed25519 identity key; its `PeerId` is derived from it
+    pub public_key:Vec<u8>,
+    /// Identity signature over the sender's Noise static key (see `noise`)
+    pub signature:Vec<u8>,
+    /// Port the sender accepts connections on (0 = doesn't listen)
+    pub listen_port:u16,
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub enum Frame{
+    Hello(Hello),
+    /// The sender accepted the peer's `Hello`
+    Ready,
+    /// The sender is closing the connection
+    Disconnect(DisconnectReason),
+    Message{topic:Topic,payload:Vec<u8>},
+    /// A `Message` whose payload is compressed with `codec`; only sent to peers that
+    /// announced the codec
+    Compressed{topic:Topic,codec:Codec,payload:Vec<u8>},
+}
+
+/// Why a node refused or closed a connection, told to the peer before hanging up.
+/// Fields describe the sender.
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize,Error)]
+pub enum DisconnectReason{
+    #[error("incompatible protocol version (sender speaks {version}, accepts {min_version} and newer)")]
+    IncompatibleVersion{version:u32,min_version:u32},
+    #[error("different network (sender is on {network_id})")]
+    NetworkMismatch{network_id:String},
+    #[error("different genesis (sender's genesis is {genesis_hash})")]
+    GenesisMismatch{genesis_hash:String},
+    #[error("connected to itself")]
+    SelfConnection,
+    #[error("already connected")]
+    AlreadyConnected,
+    #[error("too many peers")]
+    TooManyPeers,
+    #[error("banned")]
+    Banned,
+    #[error("disconnect requested")]
+    Requested,
+}
+
+pub fn encode_frame(frame:&Frame)->Result<Vec<u8>,NetworkError>{
+    let bytes=canonical::encode(frame);
+    if bytes.len()>MAX_FRAME_BYTES{
+        return Err(NetworkError::FrameTooLarge(bytes.len()))
+    }
+    Ok(bytes)
+}
+
+pub fn decode_frame(bytes:&[u8])->Result<Frame,NetworkError>{
+    if bytes.len()>MAX_FRAME_BYTES{
+        return Err(NetworkError::FrameTooLarge(bytes.len()))
+    }
+    Ok(canonical::decode(bytes)?)
+}
+
+/// A payload type and the topic it travels on; payloads are its canonical encoding
+pub trait WireMessage:Serialize+DeserializeOwned{
+    const TOPIC:Topic;
+
+    fn to_payload(&self)->Vec<u8>{
+        canonical::encode(self)
+    }
+
+    fn from_payload(payload:&[u8])->Result<Self,DecodeError>{
+        canonical::decode(payload)
+    }
+
+    fn to_frame(&self)->Frame{
+        Frame::Message{topic:Self::TOPIC,payload:self.to_payload()}
+    }
+}
+
+impl WireMessage for SignedTransaction{
+    const TOPIC:Topic=Topic::Transactions;
+}
+
+impl WireMessage for ConsensusMessage{
+    const TOPIC:Topic=Topic::Consensus;
+}
+
+/// One worked example: a frame as JSON fields -> its bytes
+#[derive(Debug,Clone,PartialEq,Serialize,Deserialize)]
+pub struct WireVector{
+    pub name:String,
+    pub protocol_version:u32,
+    /// Topic of a `Frame::Message`
+    pub topic:Option<Topic>,
+    /// The frame's payload (or the frame itself) as JSON fields
+    pub value:serde_json::Value,
+    /// Canonical encoding of the payload, hex
+    pub payload_hex:Option<String>,
+    /// `encode_frame`, hex, before the length prefix and encryption
+    pub frame_hex:String,
+}
+
+fn frame_vector(name:&str,frame:Frame)->WireVector{
+    WireVector{
+        name:name.to_string(),
+        protocol_version:PROTOCOL_VERSION,
+        topic:None,
+        value:serde_json::to_value(&frame).expect("frames serialize to JSON"),
+        payload_hex:None,
+        frame_hex:hex::encode(canonical::encode(&frame)),
+    }
+}
+
+fn message_vector<M:WireMessage>(name:&str,message:&M)->WireVector{
+    WireVector{
+        name:name.to_string(),
+        protocol_version:PROTOCOL_VERSION,
+        topic:Some(M::TOPIC),
+        value:serde_json::to_value(message).expect("wire messages serialize to JSON"),
+        payload_hex:Some(hex::encode(message.to_payload())),
+        frame_hex:hex::encode(canonical::encode(&message.to_frame())),
+    }
+}
+
+/// Deterministic test vectors: the connection frames and one message per topic. Keys are
+/// the fixed ones of `canonical::generate_test_vectors`; signatures other than the
+/// transaction's are placeholders, only their encoding matters here.
+pub fn generate_test_vectors()->Vec<WireVector>{
+    use super::discovery::{DiscoveryMessage,NodeRecord};
+    use super::gossip::BlockMessage;
+    use super::statesync::{SnapshotManifest,StateSyncMessage};
+    use super::sync::{BlockHeader,SyncMessage};
+
+    let secret:Vec<u8>=(1..33).collect();
+    let identity=NodeIdentity::from_secret(&secret).expect("32 bytes is a valid ed25519 secret");
+    let hello=Hello{
+        protocol_version:PROTOCOL_VERSION,
+        min_protocol_version:MIN_PROTOCOL_VERSION,
+        network_id:"netchain".to_string(),
+        genesis_hash:"00".repeat(32),
+        height:42,
+        capabilities:default_capabilities(),
+        public_key:identity.public_key().to_vec(),
+        // signed over an all-zero Noise static key
+        signature:identity.sign(&static_key_message(&[0u8;32])),
+        listen_port:30333,
+    };
+    let timestamp=DateTime::from_timestamp(1_700_000_000,0).expect("valid timestamp");
+    let block=Block{
+        index:1,
+        timestamp,
+        data:"hello".to_string(),
+        previous_hash:"00".repeat(32),
+        consensus:ConsensusData{proposer:Some("alice".to_string()),round:0,..ConsensusData::default()},
+        hash:"11".repeat(32),
+    };
+    let tx=canonical::generate_test_vectors().remove(0).signed;
+    let vote=Vote{
+        vote_type:VoteType::Prevote,
+        height:1,
+        block_hash:"11".repeat(32),
+        validator:"alice".to_string(),
+        signature:"c2lnbmF0dXJl".to_string(),
+    };
+    let peer_id=identity.peer_id();
+    let record=NodeRecord{peer_id:peer_id.clone(),addr:"127.0.0.1:30333".parse().expect("valid address")};
+    vec![
+        frame_vector("hello",Frame::Hello(hello)),
+        frame_vector("ready",Frame::Ready),
+        frame_vector("disconnect_genesis_mismatch",Frame::Disconnect(DisconnectReason::GenesisMismatch{genesis_hash:"00".repeat(32)})),
+        message_vector("transaction",&tx),
+        message_vector("block_announce",&BlockMessage::Announce{index:1,hash:"11".repeat(32)}),
+        message_vector("block",&BlockMessage::Block(Box::new(block.clone()))),
+        frame_vector(
+            "block_snappy",
+            Frame::Compressed{
+                topic:Topic::Blocks,
+                codec:Codec::Snappy,
+                payload:compression::compress(Codec::Snappy,&BlockMessage::Block(Box::new(block.clone())).to_payload(),0),
+            },
+        ),
+        message_vector("prevote",&ConsensusMessage::Prevote(vote)),
+        message_vector("sync_status",&SyncMessage::Status{height:1,hash:"11".repeat(32)}),
+        message_vector("sync_get_headers",&SyncMessage::GetHeaders{from:1,max:512}),
+        message_vector("sync_headers",&SyncMessage::Headers{headers:vec![BlockHeader::of(&block)]}),
+        message_vector(
+            "state_manifest",
+            &StateSyncMessage::Manifest{
+                manifest:Some(SnapshotManifest{
+                    height:1,
+                    block_hash:"11".repeat(32),
+                    state_root:"22".repeat(32),
+                    chunk_hashes:vec!["33".repeat(32)],
+                    chain_state_hash:"44".repeat(32),
+                }),
+            },
+        ),
+        message_vector("state_get_chunk",&StateSyncMessage::GetChunk{height:1,index:0}),
+        message_vector("state_get_chain_state",&StateSyncMessage::GetChainState{height:1}),
+        message_vector("find_node",&DiscoveryMessage::FindNode{target:peer_id.clone()}),
+        message_vector("nodes",&DiscoveryMessage::Nodes{target:peer_id,nodes:vec![record]}),
+    ]
+}
+
+/// Wire test vectors as pretty-printed JSON
+pub fn test_vectors_json()->String{
+    serde_json::to_string_pretty(&generate_test_vectors()).expect("test vectors serialize to JSON")
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::network::sync::SyncMessage;
+
+    #[test]
+    fn test_encoding_is_pinned(){
+        let message=SyncMessage::GetHeaders{from:1,max:512};
+        assert_eq!(hex::encode(message.to_payload()),"01000000010000000000000000020000");
+        // Frame::Message = 3, Topic::Sync = 3, payload length, payload
+        assert_eq!(
+            hex::encode(encode_frame(&message.to_frame()).unwrap()),
+            "0300000003000000100000000000000001000000010000000000000000020000",
+        );
+        assert_eq!(hex::encode(encode_frame(&Frame::Ready).unwrap()),"01000000");
+    }
+
+    #[test]
+    fn test_vectors_decode_to_their_values(){
+        let vectors=generate_test_vectors();
+        assert_eq!(vectors,generate_test_vectors());
+        for vector in &vectors{
+            let frame=decode_frame(&hex::decode(&vector.frame_hex).unwrap()).unwrap();
+            match (&frame,&vector.payload_hex){
+                (Frame::Message{topic,payload},Some(payload_hex))=>{
+                    assert_eq!(Some(*topic),vector.topic);
+                    assert_eq!(&hex::encode(payload),payload_hex);
+                }
+                (_,None)=>assert_eq!(serde_json::to_value(&frame).unwrap(),vector.value),
+                _=>panic!("{}: payload without a message frame",vector.name),
+            }
+        }
+        let parsed:Vec<WireVector>=serde_json::from_str(&test_vectors_json()).unwrap();
+        assert_eq!(parsed,vectors);
+    }
+
+    #[test]
+    fn test_schema_lists_every_topic_payload(){
+        for name in ["SignedTransaction","BlockMessage","ConsensusMessage","SyncMessage","DiscoveryMessage","StateSyncMessage"]{
+            assert!(SCHEMA.contains(&format!("enum {} {{",name)) || SCHEMA.contains(&format!("struct {} {{",name)),"{} missing",name);
+        }
+        assert!(SCHEMA.contains(&format!("protocol version {}",PROTOCOL_VERSION)));
+    }
+}
@@ -0,0 +1,145 @@
+// src/receipt.rs
+
+//! Transaction receipts
+//! - One `Receipt` per applied transaction: outcome, gas charged and emitted events
+//! - `BlockReceipts` groups a block's receipts in inclusion order, with how its fees were routed
+//! - `ReceiptStore` keeps them per block and answers lookups by transaction hash, and by
+//!   account for the transactions an address sent or was paid by
+
+use crate::fees::FeeSplit;
+use serde::{Deserialize,Serialize};
+use std::collections::{BTreeMap,BTreeSet,HashMap};
+use utoipa::ToSchema;
+
+/// Something a transaction did, for clients and indexers
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub enum Event{
+    FeePaid{payer:String,amount:u64},
+    Transfer{from:String,to:String,amount:u64},
+    ValidatorRegistered{address:String},
+    ValidatorUnregistered{address:String},
+    /// Double-sign penalty burned from the offender's balance
+    Slashed{offender:String,amount:u64},
+    Jailed{address:String,until_epoch:u64},
+    MetricReported{validator:String,epoch:u64},
+    Staked{address:String,amount:u64},
+    /// Stake moved to unbonding; it becomes spendable at `release_epoch`
+    Unstaked{address:String,amount:u64,release_epoch:u64},
+    GovernanceVoted{voter:String,proposal_id:u64,approve:bool},
+    BlsKeyRegistered{validator:String},
+    AssetIssued{asset:String,issuer:String,supply:u64},
+    AssetTransfer{asset:String,from:String,to:String,amount:u64},
+    /// `burned` native coins paid for holding `name` until `expires_at`
+    NameRegistered{name:String,owner:String,expires_at:u64,burned:u64},
+}
+
+/// Outcome of a transaction
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub enum ReceiptStatus{
+    Success,
+    /// Rejected by the state transition; nothing was applied
+    Failed{reason:String},
+}
+
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct Receipt{
+    pub tx_hash:String,
+    /// Account that sent the transaction
+    #[serde(default)]
+    pub sender:String,
+    pub status:ReceiptStatus,
+    pub gas_used:u64,
+    /// Empty unless the transaction succeeded
+    pub events:Vec<Event>,
+    pub block_height:u64,
+    /// Position of the transaction in its block
+    pub index:u32,
+}
+
+impl Receipt{
+    pub fn is_success(&self)->bool{
+        self.status==ReceiptStatus::Success
+    }
+
+    /// Accounts the transaction concerns: its sender and whoever it paid
+    pub fn parties(&self)->BTreeSet<&str>{
+        let mut parties=BTreeSet::from([self.sender.as_str()]);
+        for event in &self.events{
+            match event{
+                Event::Transfer{to,..} | Event::AssetTransfer{to,..}=>{
+                    parties.insert(to);
+                }
+                _=>{}
+            }
+        }
+        parties
+    }
+}
+
+/// Receipts of one block, in transaction order
+#[derive(Debug,Clone,Default,Serialize,Deserialize,PartialEq,Eq)]
+pub struct BlockReceipts{
+    pub height:u64,
+    pub receipts:Vec<Receipt>,
+    /// Split of the block's fees between proposer, reward pool and burn
+    #[serde(default)]
+    pub fees:FeeSplit,
+}
+
+/// Receipts by block height, indexed by transaction hash
+#[derive(Debug,Clone,Default)]
+pub struct ReceiptStore{
+    blocks:BTreeMap<u64,BlockReceipts>,
+    /// tx hash -> (height, index into that block's receipts)
+    by_hash:HashMap<String,(u64,usize)>,
+    /// address -> positions of the receipts it is a party to
+    by_address:HashMap<String,BTreeSet<(u64,usize)>>,
+}
+
+impl ReceiptStore{
+    pub fn new()->Self{
+        Self::default()
+    }
+
+    /// Store a block's receipts, replacing any earlier set for the same height
+    pub fn insert(&mut self,block:BlockReceipts){
+        if let Some(old)=self.blocks.remove(&block.height){
+            for (i,receipt) in old.receipts.iter().enumerate(){
+                self.by_hash.remove(&receipt.tx_hash);
+                for party in receipt.parties(){
+                    if let Some(positions)=self.by_address.get_mut(party){
+                        positions.remove(&(old.height,i));
+                        if positions.is_empty(){
+                            self.by_address.remove(party);
+                        }
+                    }
+                }
+            }
+        }
+        for (i,receipt) in block.receipts.iter().enumerate(){
+            self.by_hash.insert(receipt.tx_hash.clone(),(block.height,i));
+            for party in receipt.parties(){
+                self.by_address.entry(party.to_string()).or_default().insert((block.height,i));
+            }
+        }
+        self.blocks.insert(block.height,block);
+    }
+
+    pub fn get(&self,tx_hash:&str)->Option<&Receipt>{
+        let (height,index)=self.by_hash.get(tx_hash)?;
+        self.blocks.get(height)?.receipts.get(*index)
+    }
+
+    pub fn block(&self,height:u64)->Option<&BlockReceipts>{
+        self.blocks.get(&height)
+    }
+
+    /// Receipts `address` is a party to with their positions (height, index in block),
+    /// newest first, starting below position `before` when given
+    pub fn for_address(&self,address:&str,before:Option<(u64,usize)>)->impl Iterator<Item=((u64,usize),&Receipt)>{
+        let positions=self.by_address.get(address).into_iter().flat_map(move |positions| {
+            positions.range(..before.unwrap_or((u64::MAX,usize::MAX))).rev()
+        });
+        positions.filter_map(|&(height,index)| Some(((height,index),self.blocks.get(&height)?.receipts.get(index)?)))
+    }
+}
@@ -0,0 +1,100 @@
+// src/rewards.rs
+
+//! End-of-epoch reward distribution
+//! - A fixed reward pool is split across all active validators proportionally to their
+//!   PoI scores, not just the block proposers
+//! - The split is integer-only; the rounding remainder goes to the highest scorer
+//!   (ties broken by address) so every node credits exactly the same amounts
+//! - The settlement is recorded as an `EpochSummary` block entry
+
+use serde::{Deserialize,Serialize};
+use std::collections::{BTreeMap,HashMap};
+
+/// Rewards paid out at the end of one epoch
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct EpochSummary{
+    pub epoch:u64,
+    /// Total reward pool for the epoch
+    pub reward_pool:u64,
+    /// validator -> amount credited
+    pub rewards:BTreeMap<String,u64>,
+}
+
+impl EpochSummary{
+    /// Serialized form stored as the data of an epoch-summary block
+    pub fn to_block_data(&self)->String{
+        serde_json::to_string(self).expect("epoch summary serialization should succeed")
+    }
+
+    /// Parse an epoch summary back out of block data
+    pub fn from_block_data(data:&str)->Option<Self>{
+        serde_json::from_str(data).ok()
+    }
+
+    /// Sum of all credited rewards (equals `reward_pool` unless nobody was eligible)
+    pub fn total_paid(&self)->u64{
+        self.rewards.values().sum()
+    }
+}
+
+/// Split `reward_pool` across `scores` proportionally. Zero scores get nothing;
+/// an empty or all-zero score set pays nothing.
+pub fn distribute(reward_pool:u64,scores:&HashMap<String,u64>)->BTreeMap<String,u64>{
+    let ranked:BTreeMap<&String,u64>=scores
+    .iter()
+    .filter(|(_,score)| **score>0)
+    .map(|(id,score)| (id,*score))
+    .collect();
+    let total:u128=ranked.values().map(|s| *s as u128).sum();
+    if total==0{
+        return BTreeMap::new()
+    }
+
+    let mut rewards:BTreeMap<String,u64>=ranked
+    .iter()
+    .map(|(id,score)| ((*id).clone(),(reward_pool as u128*(*score) as u128/total) as u64))
+    .collect();
+    let paid:u64=rewards.values().sum();
+    // highest score wins the dust; BTreeMap order makes the lowest address win ties
+    if let Some((top,_))=ranked.iter().max_by(|a,b| a.1.cmp(b.1).then(b.0.cmp(a.0))){
+        *rewards.get_mut(*top).expect("top scorer has a reward entry")+=reward_pool-paid;
+    }
+    rewards
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_distribute_proportionally_without_dust(){
+        let scores:HashMap<String,u64>=[
+            ("alice".to_string(),5_000),
+            ("bob".to_string(),2_500),
+            ("carol".to_string(),2_500),
+            ("dave".to_string(),0),
+        ]
+        .into_iter()
+        .collect();
+
+        let rewards=distribute(1_001,&scores);
+        assert_eq!(rewards["alice"],501);
+        assert_eq!(rewards["bob"],250);
+        assert_eq!(rewards["carol"],250);
+        assert!(!rewards.contains_key("dave"));
+        assert_eq!(rewards.values().sum::<u64>(),1_001);
+
+        assert!(distribute(1_000,&HashMap::new()).is_empty());
+    }
+
+    #[test]
+    fn test_summary_roundtrip(){
+        let summary=EpochSummary{
+            epoch:3,
+            reward_pool:10,
+            rewards:[("alice".to_string(),10)].into_iter().collect(),
+        };
+        assert_eq!(EpochSummary::from_block_data(&summary.to_block_data()),Some(summary));
+        assert_eq!(EpochSummary::from_block_data("Genesis Block"),None);
+    }
+}
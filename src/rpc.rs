@@ -0,0 +1,872 @@
+// src/rpc.rs
+
+//! Node API for wallets and explorers
+//! - `RpcContext` answers queries against the chain, the published state and the mempool,
+//!   and admits submitted transactions (gossiping them when the node is networked)
+//! - `RpcServer` serves it over HTTP on axum: `jsonrpc` speaks JSON-RPC 2.0 on `POST /`,
+//!   `rest` offers the same queries as REST resources, and `ws` pushes `NodeEvent`s to
+//!   WebSocket subscribers on `GET /ws`
+//! - `RpcConfig` sets the bind address and request limits: body size, requests in flight
+//!   (503 beyond it) and time per request (408 past it), and optionally TLS (see `tls`)
+//! - `graphql` answers nested explorer queries on `POST /graphql`
+//! - `grpc` serves the same API, typed and with block streams, on its own port (see
+//!   `proto/node.proto`)
+//! - Long listings (blocks in a range, an address's transactions, validators) are served in
+//!   cursor pages (see `page`)
+//! - `fee` suggests gas prices from recent blocks and the mempool backlog
+//! - `admin` lets operators manage peers, the mempool, snapshots, logs and transaction
+//!   submission on a running node
+//! - `status` answers liveness and readiness probes and reports the node's status
+//! - Every request is authenticated to a `Role` that gates what it may call (see `auth`)
+//! - Reads never block block execution: state queries use `SharedState::snapshot`, and the
+//!   chain and mempool locks are only held to copy out a result
+
+pub mod admin;
+pub mod auth;
+pub mod fee;
+pub mod graphql;
+pub mod grpc;
+pub mod jsonrpc;
+pub mod page;
+pub mod rest;
+pub mod status;
+pub mod tls;
+pub mod ws;
+
+use crate::block::Block;
+use crate::blockchain::Blockchain;
+use crate::mempool::{BlockLimits,Mempool,MempoolError};
+use crate::network::Network;
+use crate::network::gossip::Gossip;
+use crate::network::sync::SyncEngine;
+use crate::network::statesync::SnapshotServer;
+use crate::receipt::{BlockReceipts,Event,Receipt};
+use crate::shared::SharedState;
+use crate::transaction::SignedTransaction;
+use axum::Router;
+use axum::extract::{DefaultBodyLimit,Extension,Request,State};
+use axum::http::StatusCode;
+use admin::{Admin,LogRotation};
+use auth::{AuthConfig,Authenticator,Role};
+use fee::{FEE_HISTORY_BLOCKS,FeeEstimate,Pending,Priority};
+use page::{Page,PageRequest,PoolPosition,TxPosition};
+use status::Probe;
+use tls::{TlsConfig,TlsListener};
+use axum::http::header::AUTHORIZATION;
+use axum::middleware::{self,Next};
+use axum::response::{IntoResponse,Json,Response};
+use axum::extract::ws::WebSocketUpgrade;
+use axum::routing::{get,post};
+use serde::{Deserialize,Serialize};
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::{Arc,Mutex,MutexGuard};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::net::TcpListener;
+use tokio::sync::{Semaphore,broadcast};
+use tokio::task::AbortHandle;
+use utoipa::ToSchema;
+
+#[derive(Debug,Error)]
+pub enum RpcError{
+    #[error("rpc listener failed: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("transaction rejected: {0}")]
+    Rejected(#[from] MempoolError),
+    #[error("invalid cursor")]
+    InvalidCursor,
+    #[error("tls setup failed: {0}")]
+    Tls(String),
+    /// Turned off with `admin_setTxAcceptance`
+    #[error("transaction submission is paused")]
+    SubmissionsPaused,
+    /// The node didn't give the API what an admin method needs
+    #[error("{0} is not available on this node")]
+    Unavailable(&'static str),
+    #[error("invalid peer id")]
+    InvalidPeerId,
+    #[error("admin operation failed: {0}")]
+    Admin(String),
+}
+
+#[derive(Debug,Clone)]
+pub struct RpcConfig{
+    pub listen_addr:SocketAddr,
+    /// Largest request body accepted
+    pub max_body_bytes:usize,
+    /// Requests handled at once; more are refused with 503
+    pub max_concurrent_requests:usize,
+    /// Time allowed to receive and answer a request
+    pub request_timeout:Duration,
+    pub auth:AuthConfig,
+    /// Serve HTTPS instead of plain HTTP
+    pub tls:Option<TlsConfig>,
+}
+
+impl Default for RpcConfig{
+    fn default()->Self{
+        RpcConfig{
+            listen_addr:SocketAddr::from(([127,0,0,1],8545)),
+            max_body_bytes:1024*1024,
+            max_concurrent_requests:256,
+            request_timeout:Duration::from_secs(30),
+            auth:AuthConfig::default(),
+            tls:None,
+        }
+    }
+}
+
+/// Events kept for slow WebSocket sessions before they start missing some
+const EVENT_BUFFER:usize=1024;
+
+/// What subscribers are told about. The node publishes a block once it's applied and its
+/// receipts are recorded, and again once it's final.
+#[derive(Debug,Clone)]
+pub enum NodeEvent{
+    NewBlock(Arc<Block>),
+    Finalized(Arc<Block>),
+    /// Transaction admitted to the mempool
+    PendingTx(Arc<SignedTransaction>),
+}
+
+/// Where a transaction is, as far as this node knows
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+#[serde(tag="status",rename_all="snake_case")]
+pub enum TxStatus{
+    /// Waiting in the mempool
+    Pending{transaction:Box<SignedTransaction>},
+    /// Applied in a block; `finalized` once that block has a commit
+    Included{receipt:Receipt,finalized:bool},
+}
+
+/// An account as served to clients
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct AccountView{
+    pub address:String,
+    pub balance:u64,
+    /// Confirmed nonce, not counting pooled transactions
+    pub nonce:u64,
+    pub stake:u64,
+}
+
+/// Size of the mempool and what its transactions pay
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct MempoolView{
+    pub transactions:usize,
+    /// Of which still waiting for their time lock
+    pub locked:usize,
+    pub bytes:usize,
+    /// Non-empty gas price buckets, cheapest first
+    pub fee_histogram:Vec<FeeBucket>,
+}
+
+/// Pooled transactions paying from `min_gas_price` up to (not including) twice that; the
+/// first bucket starts at 0 and holds those paying below 1
+#[derive(Debug,Clone,Copy,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct FeeBucket{
+    pub min_gas_price:u64,
+    pub transactions:usize,
+}
+
+/// A pooled transaction as listed to clients
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct PooledTxView{
+    pub hash:String,
+    /// Fee per unit of gas, which orders the pool
+    pub gas_price:u64,
+    pub transaction:SignedTransaction,
+}
+
+/// A registered validator as served to clients
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct ValidatorView{
+    pub address:String,
+    pub consensus_pubkey:String,
+    pub vrf_pubkey:String,
+    pub endpoint:String,
+    pub stake:u64,
+    /// Jailed at the current epoch, for double signing or a low PoI score
+    pub jailed:bool,
+}
+
+/// What the API reads and writes; cheap to clone
+#[derive(Clone)]
+pub struct RpcContext{
+    chain:Arc<Mutex<Blockchain>>,
+    state:Arc<SharedState>,
+    mempool:Arc<Mutex<Mempool>>,
+    gossip:Option<Gossip>,
+    events:broadcast::Sender<NodeEvent>,
+    /// What proposers fit in a block, for fee estimates
+    block_limits:BlockLimits,
+    admin:Admin,
+    probe:Probe,
+}
+
+impl RpcContext{
+    pub fn new(chain:Arc<Mutex<Blockchain>>,state:Arc<SharedState>,mempool:Arc<Mutex<Mempool>>)->Self{
+        let (events,_)=broadcast::channel(EVENT_BUFFER);
+        RpcContext{chain,state,mempool,gossip:None,events,block_limits:BlockLimits::default(),admin:Admin::default(),probe:Probe::default()}
+    }
+
+    /// Gossip submitted transactions to peers instead of only pooling them locally
+    pub fn with_gossip(mut self,gossip:Gossip)->Self{
+        self.gossip=Some(gossip);
+        self
+    }
+
+    /// Let admins manage `network`'s peers, and count them in the status
+    pub fn with_network(mut self,network:Network)->Self{
+        self.admin.network=Some(network);
+        self
+    }
+
+    /// Let admins take state snapshots on `snapshots`
+    pub fn with_snapshots(mut self,snapshots:SnapshotServer)->Self{
+        self.admin.snapshots=Some(snapshots);
+        self
+    }
+
+    /// Let admins rotate logs by calling `rotate`
+    pub fn with_log_rotation(mut self,rotate:LogRotation)->Self{
+        self.admin.rotate_logs=Some(rotate);
+        self
+    }
+
+    /// Judge readiness by the peer heights `sync` sees
+    pub fn with_sync(mut self,sync:SyncEngine)->Self{
+        self.probe.sync=Some(sync);
+        self
+    }
+
+    /// Report the standing of validator `address` in the status
+    pub fn with_validator(mut self,address:String)->Self{
+        self.probe.validator=Some(address);
+        self
+    }
+
+    /// Stay ready up to `blocks` behind the best known height instead of
+    /// `status::DEFAULT_MAX_READY_LAG`
+    pub fn with_max_ready_lag(mut self,blocks:u64)->Self{
+        self.probe.max_ready_lag=blocks;
+        self
+    }
+
+    /// Estimate fees against these block limits instead of the defaults
+    pub fn with_block_limits(mut self,limits:BlockLimits)->Self{
+        self.block_limits=limits;
+        self
+    }
+
+    /// Tell subscribers about `event`
+    pub fn publish(&self,event:NodeEvent){
+        // no subscribers is fine
+        let _=self.events.send(event);
+    }
+
+    pub fn subscribe(&self)->broadcast::Receiver<NodeEvent>{
+        self.events.subscribe()
+    }
+
+    /// Read scope for a group of queries, e.g. a JSON-RPC batch
+    pub fn reads(&self)->Reads<'_>{
+        Reads{ctx:self,state:None,chain:None}
+    }
+
+    pub fn height(&self)->u64{
+        self.reads().height()
+    }
+
+    pub fn block(&self,height:u64)->Option<Block>{
+        self.reads().block(height)
+    }
+
+    pub fn block_by_hash(&self,hash:&str)->Option<Block>{
+        self.reads().block_by_hash(hash)
+    }
+
+    /// Receipts of the block at `height`, if it was applied here
+    pub fn block_receipts(&self,height:u64)->Option<BlockReceipts>{
+        self.reads().block_receipts(height)
+    }
+
+    pub fn is_final(&self,height:u64)->bool{
+        self.reads().is_final(height)
+    }
+
+    pub fn balance(&self,address:&str)->u64{
+        self.reads().balance(address)
+    }
+
+    /// Confirmed account nonce, not counting pooled transactions
+    pub fn nonce(&self,address:&str)->u64{
+        self.reads().nonce(address)
+    }
+
+    pub fn account(&self,address:&str)->AccountView{
+        self.reads().account(address)
+    }
+
+    /// Admit `tx` to the mempool and, when networked, gossip it. Returns its hash.
+    pub fn send_transaction(&self,tx:SignedTransaction)->Result<String,RpcError>{
+        if !self.accepting_transactions(){
+            return Err(RpcError::SubmissionsPaused)
+        }
+        let pending=Arc::new(tx.clone());
+        let hash=match &self.gossip{
+            Some(gossip)=>gossip.submit_transaction(tx)?,
+            None=>{
+                let state=self.state.snapshot();
+                self.mempool.lock().expect("mempool lock poisoned").insert(tx,&state)?
+            }
+        };
+        self.publish(NodeEvent::PendingTx(pending));
+        Ok(hash)
+    }
+
+    /// Receipt of an applied transaction, or the pooled transaction; `None` if unknown
+    pub fn transaction(&self,hash:&str)->Option<TxStatus>{
+        self.reads().transaction(hash)
+    }
+
+    pub fn mempool(&self)->MempoolView{
+        self.reads().mempool()
+    }
+
+    /// Pooled transactions, of `sender` only when given, by sender and nonce
+    pub fn pooled_transactions(&self,sender:Option<&str>,page:&PageRequest)->Result<Page<PooledTxView>,RpcError>{
+        self.reads().pooled_transactions(sender,page)
+    }
+
+    pub fn is_pending(&self,hash:&str)->bool{
+        self.reads().is_pending(hash)
+    }
+
+    /// Gas price likely to confirm within `priority`'s target; `fee` priced for `gas`
+    pub fn fee_estimate(&self,priority:Priority,gas:Option<u64>)->FeeEstimate{
+        self.reads().fee_estimate(priority,gas)
+    }
+
+    pub fn validators(&self)->Vec<ValidatorView>{
+        self.reads().validators()
+    }
+
+    /// Blocks from `from` up to `to` (the tip when unset), lowest first
+    pub fn list_blocks(&self,from:u64,to:Option<u64>,page:&PageRequest)->Result<Page<Block>,RpcError>{
+        self.reads().list_blocks(from,to,page)
+    }
+
+    /// Included transactions `address` sent or was paid by, newest first
+    pub fn address_transactions(&self,address:&str,page:&PageRequest)->Result<Page<TxStatus>,RpcError>{
+        self.reads().address_transactions(address,page)
+    }
+
+    /// Validators in address order
+    pub fn list_validators(&self,page:&PageRequest)->Result<Page<ValidatorView>,RpcError>{
+        self.reads().list_validators(page)
+    }
+}
+
+/// Reads that share one state snapshot and one hold of the chain lock, taken on first use
+/// and kept until the scope is dropped, so a group of queries pays for them once and sees
+/// one consistent chain and state. Don't keep a scope around: it holds block imports off.
+pub struct Reads<'a>{
+    ctx:&'a RpcContext,
+    state:Option<Arc<crate::state::State>>,
+    chain:Option<MutexGuard<'a,Blockchain>>,
+}
+
+impl Reads<'_>{
+    fn state(&mut self)->&crate::state::State{
+        self.state.get_or_insert_with(|| self.ctx.state.snapshot())
+    }
+
+    fn chain(&mut self)->&Blockchain{
+        self.chain.get_or_insert_with(|| self.ctx.chain.lock().expect("chain lock poisoned"))
+    }
+
+    pub fn height(&mut self)->u64{
+        self.chain().height()
+    }
+
+    pub fn block(&mut self,height:u64)->Option<Block>{
+        self.chain().block(height).cloned()
+    }
+
+    pub fn block_by_hash(&mut self,hash:&str)->Option<Block>{
+        self.chain().block_by_hash(hash).cloned()
+    }
+
+    pub fn block_receipts(&mut self,height:u64)->Option<BlockReceipts>{
+        self.chain().block_receipts(height).cloned()
+    }
+
+    pub fn is_final(&mut self,height:u64)->bool{
+        self.chain().is_final(height)
+    }
+
+    pub fn balance(&mut self,address:&str)->u64{
+        self.state().get_balance(address)
+    }
+
+    pub fn nonce(&mut self,address:&str)->u64{
+        self.state().get_nonce(address)
+    }
+
+    pub fn account(&mut self,address:&str)->AccountView{
+        let state=self.state();
+        AccountView{
+            address:address.to_string(),
+            balance:state.get_balance(address),
+            nonce:state.get_nonce(address),
+            stake:state.get_stake(address),
+        }
+    }
+
+    pub fn transaction(&mut self,hash:&str)->Option<TxStatus>{
+        let chain=self.chain();
+        if let Some(receipt)=chain.receipt(hash){
+            return Some(TxStatus::Included{receipt:receipt.clone(),finalized:chain.is_final(receipt.block_height)})
+        }
+        let mempool=self.ctx.mempool.lock().expect("mempool lock poisoned");
+        mempool.get(hash).map(|tx| TxStatus::Pending{transaction:Box::new(tx.clone())})
+    }
+
+    pub fn mempool(&self)->MempoolView{
+        let mempool=self.ctx.mempool.lock().expect("mempool lock poisoned");
+        let mut buckets:BTreeMap<u64,usize>=BTreeMap::new();
+        for entry in mempool.pooled(){
+            // power-of-two buckets: 0, 1, 2-3, 4-7, ...
+            let floor=entry.gas_price.checked_ilog2().map_or(0,|log| 1<<log);
+            *buckets.entry(floor).or_default()+=1;
+        }
+        MempoolView{
+            transactions:mempool.len(),
+            locked:mempool.locked_len(),
+            bytes:mempool.total_bytes(),
+            fee_histogram:buckets.into_iter().map(|(min_gas_price,transactions)| FeeBucket{min_gas_price,transactions}).collect(),
+        }
+    }
+
+    pub fn pooled_transactions(&self,sender:Option<&str>,page:&PageRequest)->Result<Page<PooledTxView>,RpcError>{
+        let limit=page.limit();
+        let after=page.after::<PoolPosition>()?;
+        let mempool=self.ctx.mempool.lock().expect("mempool lock poisoned");
+        let mut pooled:Vec<(PoolPosition,PooledTxView)>=mempool
+        .pooled()
+        .filter(|entry| sender.is_none_or(|sender| entry.tx.tx.sender==sender))
+        .map(|entry| {
+            let hash=entry.tx.tx_hash_hex();
+            let position=PoolPosition{sender:entry.tx.tx.sender.clone(),nonce:entry.tx.tx.nonce,hash:hash.clone()};
+            (position,PooledTxView{hash,gas_price:entry.gas_price,transaction:entry.tx.clone()})
+        })
+        .filter(|(position,_)| after.as_ref().is_none_or(|after| position>after))
+        .collect();
+        drop(mempool);
+        pooled.sort_by(|a,b| a.0.cmp(&b.0));
+        pooled.truncate(limit+1);
+        Ok(Page::new(pooled,limit,|(position,_)| position.clone()).map(|(_,view)| view))
+    }
+
+    pub fn is_pending(&self,hash:&str)->bool{
+        self.ctx.mempool.lock().expect("mempool lock poisoned").contains(hash)
+    }
+
+    pub fn fee_estimate(&mut self,priority:Priority,gas:Option<u64>)->FeeEstimate{
+        let chain=self.chain();
+        let tip=chain.height();
+        let mut recent:Vec<u64>=(tip.saturating_sub(FEE_HISTORY_BLOCKS-1)..=tip)
+        .filter_map(|height| chain.block_receipts(height))
+        .flat_map(|block| &block.receipts)
+        .filter(|receipt| receipt.gas_used>0)
+        .filter_map(|receipt| {
+            receipt.events.iter().find_map(|event| match event{
+                Event::FeePaid{amount,..}=>Some(amount.div_ceil(receipt.gas_used)),
+                _=>None,
+            })
+        })
+        .collect();
+        let mempool=self.ctx.mempool.lock().expect("mempool lock poisoned");
+        let mut pending:Vec<Pending>=mempool
+        .pooled()
+        .map(|entry| Pending{gas_price:entry.gas_price,gas:entry.gas,size:entry.size})
+        .collect();
+        let min_gas_price=mempool.gas_schedule().min_gas_price;
+        drop(mempool);
+        fee::estimate(priority,&mut recent,&mut pending,self.ctx.block_limits,min_gas_price,gas)
+    }
+
+    pub fn validators(&mut self)->Vec<ValidatorView>{
+        let state=self.state();
+        let registry=state.validators();
+        let epoch=state.current_epoch();
+        registry
+        .iter()
+        .map(|info| ValidatorView{
+            address:info.address.clone(),
+            consensus_pubkey:info.consensus_pubkey.clone(),
+            vrf_pubkey:info.vrf_pubkey.clone(),
+            endpoint:info.endpoint.clone(),
+            stake:state.get_stake(&info.address),
+            jailed:registry.is_jailed(&info.address,epoch) || registry.is_score_jailed(&info.address),
+        })
+        .collect()
+    }
+
+    pub fn list_blocks(&mut self,from:u64,to:Option<u64>,page:&PageRequest)->Result<Page<Block>,RpcError>{
+        let limit=page.limit();
+        let start=match page.after::<u64>()?{
+            Some(last)=>last.saturating_add(1),
+            None=>from,
+        };
+        let chain=self.chain();
+        // blocks below a snapshot the chain started from aren't kept
+        let start=start.max(chain.chain[0].index);
+        let end=to.unwrap_or(u64::MAX).min(chain.height());
+        let blocks=(start..=end).take(limit+1).filter_map(|height| chain.block(height).cloned()).collect();
+        Ok(Page::new(blocks,limit,|block| block.index))
+    }
+
+    pub fn address_transactions(&mut self,address:&str,page:&PageRequest)->Result<Page<TxStatus>,RpcError>{
+        let limit=page.limit();
+        let before=page.after::<TxPosition>()?.map(|TxPosition(height,index)| (height,index));
+        let chain=self.chain();
+        let receipts:Vec<_>=chain
+        .address_receipts(address,before)
+        .take(limit+1)
+        .map(|((height,index),receipt)| (TxPosition(height,index),TxStatus::Included{receipt:receipt.clone(),finalized:chain.is_final(height)}))
+        .collect();
+        Ok(Page::new(receipts,limit,|(position,_)| *position).map(|(_,status)| status))
+    }
+
+    pub fn list_validators(&mut self,page:&PageRequest)->Result<Page<ValidatorView>,RpcError>{
+        let limit=page.limit();
+        let after=page.after::<String>()?;
+        let mut validators=self.validators();
+        validators.retain(|validator| after.as_ref().is_none_or(|after| validator.address>*after));
+        validators.truncate(limit+1);
+        Ok(Page::new(validators,limit,|validator| validator.address.clone()))
+    }
+}
+
+/// Running HTTP server; stops when dropped
+pub struct RpcServer{
+    local_addr:SocketAddr,
+    task:AbortHandle,
+}
+
+impl RpcServer{
+    pub async fn start(config:RpcConfig,context:RpcContext)->Result<Self,RpcError>{
+        let acceptor=config.tls.as_ref().map(tls::acceptor).transpose()?;
+        let listener=TcpListener::bind(config.listen_addr).await?;
+        let local_addr=listener.local_addr()?;
+        let app=router(&config,context);
+        let task=match acceptor{
+            Some(acceptor)=>{
+                let listener=TlsListener::new(listener,acceptor,config.request_timeout)?;
+                tokio::spawn(async move {
+                    let _=axum::serve(listener,app).await;
+                })
+            }
+            None=>tokio::spawn(async move {
+                let _=axum::serve(listener,app).await;
+            }),
+        }
+        .abort_handle();
+        Ok(RpcServer{local_addr,task})
+    }
+
+    /// Bound address, e.g. to find the port picked for `127.0.0.1:0`
+    pub fn local_addr(&self)->SocketAddr{
+        self.local_addr
+    }
+}
+
+impl Drop for RpcServer{
+    fn drop(&mut self){
+        self.task.abort();
+    }
+}
+
+struct RequestLimits{
+    in_flight:Arc<Semaphore>,
+    timeout:Duration,
+}
+
+async fn enforce_limits(State(limits):State<Arc<RequestLimits>>,request:Request,next:Next)->Response{
+    let Ok(_permit)=limits.in_flight.clone().try_acquire_owned() else{
+        return (StatusCode::SERVICE_UNAVAILABLE,"too many requests in flight").into_response()
+    };
+    match tokio::time::timeout(limits.timeout,next.run(request)).await{
+        Ok(response)=>response,
+        Err(_)=>(StatusCode::REQUEST_TIMEOUT,"request timed out").into_response(),
+    }
+}
+
+/// Header carrying an API key, for clients that can't set `Authorization`
+pub const API_KEY_HEADER:&str="x-api-key";
+
+/// Resolve the request's credentials to a `Role` for the handlers, or refuse it with 401
+async fn authenticate(State(auth):State<Arc<Authenticator>>,mut request:Request,next:Next)->Response{
+    let headers=request.headers();
+    let credentials=headers.get(AUTHORIZATION).or_else(|| headers.get(API_KEY_HEADER)).map(|value| value.to_str().unwrap_or_default());
+    match auth.authenticate(credentials){
+        Ok(role)=>{
+            request.extensions_mut().insert(role);
+            next.run(request).await
+        }
+        Err(e)=>(StatusCode::UNAUTHORIZED,Json(serde_json::json!({"error":e.to_string()}))).into_response(),
+    }
+}
+
+fn router(config:&RpcConfig,context:RpcContext)->Router{
+    let limits=Arc::new(RequestLimits{
+        in_flight:Arc::new(Semaphore::new(config.max_concurrent_requests)),
+        timeout:config.request_timeout,
+    });
+    let max_message=config.max_body_bytes;
+    let schema=graphql::schema(context.clone());
+    Router::new()
+    .route("/",post(jsonrpc::serve))
+    .route("/graphql",post(move |request| graphql::serve(schema.clone(),request)))
+    .route("/ws",get(move |ctx:State<RpcContext>,role:Extension<Role>,upgrade:WebSocketUpgrade| ws::upgrade(ctx,role,upgrade,max_message)))
+    .merge(rest::routes())
+    .layer(DefaultBodyLimit::max(config.max_body_bytes))
+    .layer(middleware::from_fn_with_state(Arc::new(Authenticator::new(&config.auth)),authenticate))
+    .layer(middleware::from_fn_with_state(limits,enforce_limits))
+    .with_state(context)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::gas::GasSchedule;
+    use crate::receipt::BlockReceipts;
+    use crate::state::State;
+    use crate::transaction::{Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+    use tokio::io::{AsyncReadExt,AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    /// Context over a fresh chain with `funded` holding 1_000_000
+    pub(super) fn context(funded:&str)->RpcContext{
+        let state=State::with_genesis(vec![(funded.to_string(),1_000_000)]);
+        RpcContext::new(
+            Arc::new(Mutex::new(Blockchain::new())),
+            Arc::new(SharedState::new(state)),
+            Arc::new(Mutex::new(Mempool::new(1_000_000))),
+        )
+    }
+
+    /// Raw HTTP/1.1 exchange: (status code, body)
+    pub(super) async fn http(addr:SocketAddr,method:&str,path:&str,body:&str)->(u16,String){
+        http_with(addr,method,path,&[],body).await
+    }
+
+    pub(super) async fn http_with(addr:SocketAddr,method:&str,path:&str,headers:&[(&str,&str)],body:&str)->(u16,String){
+        let mut stream=TcpStream::connect(addr).await.unwrap();
+        let headers:String=headers.iter().map(|(name,value)| format!("{name}: {value}\r\n")).collect();
+        let request=format!(
+            "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Type: application/json\r\n{headers}Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        stream.write_all(request.as_bytes()).await.unwrap();
+        let mut response=String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        let (head,body)=response.split_once("\r\n\r\n").unwrap();
+        let status=head.split(' ').nth(1).unwrap().parse().unwrap();
+        (status,body.to_string())
+    }
+
+    #[test]
+    fn test_transaction_pending_then_included(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let ctx=context(&sender);
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(sender.clone(),"bob".to_string(),10,1_000,0,None),&kp);
+        let hash=ctx.send_transaction(tx.clone()).unwrap();
+        assert_eq!(ctx.transaction(&hash),Some(TxStatus::Pending{transaction:Box::new(tx.clone())}));
+        assert!(matches!(ctx.send_transaction(tx.clone()),Err(RpcError::Rejected(MempoolError::Duplicate))));
+
+        let receipt=ctx.state.write(|state| state.apply_with_receipt(&tx,&GasSchedule::default(),1,0));
+        ctx.mempool.lock().unwrap().prune(&ctx.state.snapshot());
+        {
+            let mut chain=ctx.chain.lock().unwrap();
+            chain.add_block("block 1".to_string());
+            chain.record_receipts(BlockReceipts{height:1,receipts:vec![receipt],..Default::default()});
+        }
+        match ctx.transaction(&hash){
+            Some(TxStatus::Included{receipt,finalized})=>assert!(receipt.is_success() && !finalized),
+            other=>panic!("unexpected status {other:?}"),
+        }
+        assert_eq!((ctx.balance("bob"),ctx.nonce(&sender)),(10,1));
+        assert_eq!(ctx.transaction("unknown"),None);
+    }
+
+    #[test]
+    fn test_paged_listings(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let ctx=context(&sender);
+        let mut hashes=Vec::new();
+        for nonce in 0..3{
+            let tx=SignedTransaction::sign_with_keypair(&Transaction::new(sender.clone(),"bob".to_string(),10,1_000,nonce,None),&kp);
+            let receipt=ctx.state.write(|state| state.apply_with_receipt(&tx,&GasSchedule::default(),nonce+1,0));
+            let mut chain=ctx.chain.lock().unwrap();
+            chain.add_block(format!("block {}",nonce+1));
+            chain.record_receipts(BlockReceipts{height:nonce+1,receipts:vec![receipt],..Default::default()});
+            hashes.push(tx.tx_hash_hex());
+        }
+        let page=|cursor:Option<String>,limit| PageRequest{cursor,limit:Some(limit)};
+        let heights=|page:&Page<Block>| page.items.iter().map(|block| block.index).collect::<Vec<_>>();
+
+        let first=ctx.list_blocks(0,None,&page(None,2)).unwrap();
+        assert_eq!(heights(&first),vec![0,1]);
+        // a block added meanwhile doesn't shift the next page
+        ctx.chain.lock().unwrap().add_block("block 4".to_string());
+        let second=ctx.list_blocks(0,None,&page(first.next_cursor,2)).unwrap();
+        assert_eq!(heights(&second),vec![2,3]);
+        let last=ctx.list_blocks(0,None,&page(second.next_cursor,2)).unwrap();
+        assert_eq!((heights(&last),last.next_cursor),(vec![4],None));
+        assert_eq!(heights(&ctx.list_blocks(1,Some(2),&PageRequest::default()).unwrap()),vec![1,2]);
+
+        let hash=|status:&TxStatus| match status{
+            TxStatus::Included{receipt,..}=>receipt.tx_hash.clone(),
+            TxStatus::Pending{transaction}=>transaction.tx_hash_hex(),
+        };
+        let newest=ctx.address_transactions(&sender,&page(None,2)).unwrap();
+        assert_eq!(newest.items.iter().map(hash).collect::<Vec<_>>(),vec![hashes[2].clone(),hashes[1].clone()]);
+        let oldest=ctx.address_transactions(&sender,&page(newest.next_cursor,2)).unwrap();
+        assert_eq!((oldest.items.iter().map(hash).collect::<Vec<_>>(),oldest.next_cursor),(vec![hashes[0].clone()],None));
+        // the recipient sees the same history
+        assert_eq!(ctx.address_transactions("bob",&PageRequest::default()).unwrap().items.len(),3);
+        assert!(ctx.address_transactions("carol",&PageRequest::default()).unwrap().items.is_empty());
+
+        assert!(matches!(ctx.list_blocks(0,None,&page(Some("not a cursor".to_string()),2)),Err(RpcError::InvalidCursor)));
+        assert!(matches!(ctx.address_transactions(&sender,&page(Some("MTI".to_string()),2)),Err(RpcError::InvalidCursor)));
+    }
+
+    #[test]
+    fn test_mempool_inspection(){
+        let (alice,carol)=(generate_ed25519_keypair(),generate_ed25519_keypair());
+        let (alice_addr,carol_addr)=(pubkey_to_address_hex(&alice.public),pubkey_to_address_hex(&carol.public));
+        let ctx=RpcContext::new(
+            Arc::new(Mutex::new(Blockchain::new())),
+            Arc::new(SharedState::new(State::with_genesis(vec![(alice_addr.clone(),1_000_000),(carol_addr.clone(),1_000_000)]))),
+            Arc::new(Mutex::new(Mempool::new(1_000_000))),
+        );
+        let transfer=|kp,sender:&str,fee,nonce| SignedTransaction::sign_with_keypair(&Transaction::new(sender.to_string(),"bob".to_string(),10,fee,nonce,None),kp);
+        let pooled=[
+            transfer(&alice,&alice_addr,1_000,0),
+            transfer(&alice,&alice_addr,1_000,1),
+            transfer(&carol,&carol_addr,100_000,0),
+        ];
+        for tx in &pooled{
+            ctx.send_transaction(tx.clone()).unwrap();
+        }
+
+        let first=ctx.pooled_transactions(Some(&alice_addr),&PageRequest{cursor:None,limit:Some(1)}).unwrap();
+        assert_eq!(first.items.iter().map(|view| view.transaction.tx.nonce).collect::<Vec<_>>(),vec![0]);
+        let rest=ctx.pooled_transactions(Some(&alice_addr),&PageRequest{cursor:first.next_cursor,limit:Some(1)}).unwrap();
+        assert_eq!((rest.items[0].hash.clone(),rest.next_cursor),(pooled[1].tx_hash_hex(),None));
+        let all=ctx.pooled_transactions(None,&PageRequest::default()).unwrap();
+        assert_eq!(all.items.len(),3);
+
+        assert!(ctx.is_pending(&pooled[2].tx_hash_hex()));
+        assert!(!ctx.is_pending("unknown"));
+
+        let status=ctx.mempool();
+        assert_eq!(status.transactions,3);
+        assert_eq!(status.fee_histogram.iter().map(|bucket| bucket.transactions).sum::<usize>(),3);
+        // carol pays a hundred times more, several buckets up
+        assert_eq!(status.fee_histogram.len(),2);
+        assert_eq!(status.fee_histogram[0].transactions,2);
+        for view in &all.items{
+            let bucket=status.fee_histogram.iter().rev().find(|bucket| bucket.min_gas_price<=view.gas_price).unwrap();
+            assert!(view.gas_price<bucket.min_gas_price.max(1)*2);
+        }
+    }
+
+    #[test]
+    fn test_fee_estimate_follows_recent_blocks(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let ctx=context(&sender);
+        assert_eq!(ctx.fee_estimate(Priority::High,Some(5_000)).fee,Some(0));
+
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(sender.clone(),"bob".to_string(),10,100_000,0,None),&kp);
+        let receipt=ctx.state.write(|state| state.apply_with_receipt(&tx,&GasSchedule::default(),1,0));
+        let paid=100_000u64.div_ceil(receipt.gas_used);
+        {
+            let mut chain=ctx.chain.lock().unwrap();
+            chain.add_block("block 1".to_string());
+            chain.record_receipts(BlockReceipts{height:1,receipts:vec![receipt],..Default::default()});
+        }
+        let estimate=ctx.fee_estimate(Priority::Medium,Some(5_000));
+        assert_eq!((estimate.target_blocks,estimate.gas_price,estimate.fee),(3,paid,Some(5_000*paid)));
+    }
+
+    #[tokio::test]
+    async fn test_roles_gate_submission(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let secret=b"jwt secret".to_vec();
+        let auth=AuthConfig{
+            api_keys:std::collections::HashMap::from([("wallet-key".to_string(),Role::Wallet)]),
+            jwt_secret:Some(secret.clone()),
+            anonymous:Role::Public,
+        };
+        let config=RpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),auth,..RpcConfig::default()};
+        let server=RpcServer::start(config,context(&sender)).await.unwrap();
+        let addr=server.local_addr();
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(sender,"bob".to_string(),10,1_000,0,None),&kp);
+        let send=serde_json::json!({"jsonrpc":"2.0","method":"tx_send","params":[tx],"id":1}).to_string();
+        let parsed=|body:String| serde_json::from_str::<serde_json::Value>(&body).unwrap();
+
+        // anonymous callers may read but not submit
+        assert_eq!(http(addr,"GET","/validators","").await.0,200);
+        assert_eq!(parsed(http(addr,"POST","/",&send).await.1)["error"]["code"],jsonrpc::UNAUTHORIZED);
+        assert_eq!(http(addr,"POST","/transactions",&serde_json::to_string(&tx).unwrap()).await.0,403);
+        // bad credentials are refused, not downgraded
+        assert_eq!(http_with(addr,"GET","/validators",&[("Authorization","Bearer nope")],"").await.0,401);
+
+        let (status,body)=http_with(addr,"POST","/",&[(API_KEY_HEADER,"wallet-key")],&send).await;
+        assert_eq!((status,parsed(body)["result"].clone()),(200,serde_json::json!(tx.tx_hash_hex())));
+        let jwt=format!("Bearer {}",auth::issue_jwt(&secret,Role::Admin,u64::MAX));
+        let (status,body)=http_with(addr,"POST","/",&[("Authorization",&jwt)],&send).await;
+        assert_eq!((status,parsed(body)["error"]["code"].clone()),(200,serde_json::json!(jsonrpc::TX_REJECTED)));
+    }
+
+    #[tokio::test]
+    async fn test_server_limits_body_size(){
+        let config=RpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),max_body_bytes:256,..RpcConfig::default()};
+        let server=RpcServer::start(config,context("alice")).await.unwrap();
+        let (status,body)=http(server.local_addr(),"POST","/",r#"{"jsonrpc":"2.0","method":"state_getBalance","params":["alice"],"id":1}"#).await;
+        assert_eq!(status,200);
+        assert_eq!(serde_json::from_str::<serde_json::Value>(&body).unwrap()["result"],1_000_000);
+
+        let (status,_)=http(server.local_addr(),"POST","/",&format!(r#"{{"jsonrpc":"2.0","method":"x","params":["{}"],"id":1}}"#,"a".repeat(300))).await;
+        assert_eq!(status,413);
+    }
+
+    #[tokio::test]
+    async fn test_server_limits_in_flight_and_time(){
+        let config=RpcConfig{
+            listen_addr:"127.0.0.1:0".parse().unwrap(),
+            max_concurrent_requests:1,
+            request_timeout:Duration::from_millis(300),
+            ..RpcConfig::default()
+        };
+        let server=RpcServer::start(config,context("alice")).await.unwrap();
+        // a request whose body never finishes arriving holds the only slot
+        let mut slow=TcpStream::connect(server.local_addr()).await.unwrap();
+        slow.write_all(b"POST / HTTP/1.1\r\nHost: x\r\nContent-Length: 100\r\nConnection: close\r\n\r\n{").await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert_eq!(http(server.local_addr(),"GET","/validators","").await.0,503);
+
+        let mut response=String::new();
+        slow.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 408"),"{response}");
+        assert_eq!(http(server.local_addr(),"GET","/validators","").await.0,200);
+    }
+}
@@ -0,0 +1,328 @@
+// src/rpc/admin.rs
+
+//! Node operations for operators, served as `admin_*` JSON-RPC methods to the admin role
+//! - Peers: list connections and bans, dial an address, disconnect, ban and unban; needs
+//!   the node's `Network` (`RpcContext::with_network`)
+//! - Mempool: inspect every pooled transaction by sender, or flush the pool
+//! - Snapshots: snapshot the tip for state sync now rather than at the next interval, once
+//!   the tip is final; needs the node's `SnapshotServer` (`RpcContext::with_snapshots`)
+//! - Logs: rotated by the hook the node registers (`RpcContext::with_log_rotation`), as
+//!   only it knows where its logs go
+//! - Transaction acceptance: submission through the API can be paused and resumed; what is
+//!   already pooled or arrives by gossip is left alone
+//! - Without what it needs, a method fails with `RpcError::Unavailable`
+
+use super::{PooledTxView,Reads,RpcContext,RpcError};
+use crate::network::Network;
+use crate::network::identity::PeerId;
+use crate::network::statesync::{SnapshotManifest,SnapshotServer};
+use serde::{Deserialize,Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool,Ordering};
+
+/// Reopens the node's log files, e.g. after logrotate moved them
+pub type LogRotation=Arc<dyn Fn()->io::Result<()>+Send+Sync>;
+
+/// What admin methods act on besides the chain, state and mempool; cheap to clone
+#[derive(Clone)]
+pub(super) struct Admin{
+    pub network:Option<Network>,
+    pub snapshots:Option<SnapshotServer>,
+    pub rotate_logs:Option<LogRotation>,
+    /// Whether `send_transaction` admits transactions; shared by every clone
+    pub accepting_txs:Arc<AtomicBool>,
+}
+
+impl Default for Admin{
+    fn default()->Self{
+        Admin{network:None,snapshots:None,rotate_logs:None,accepting_txs:Arc::new(AtomicBool::new(true))}
+    }
+}
+
+/// A connected peer as shown to operators
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct PeerView{
+    pub peer_id:String,
+    pub addr:SocketAddr,
+    /// Whether this node dialed it
+    pub outbound:bool,
+    /// The peer's chain height when it connected
+    pub height:u64,
+    pub score:i32,
+}
+
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct BanView{
+    pub peer_id:String,
+    pub score:i32,
+    /// Unix time the ban ends
+    pub banned_until:u64,
+}
+
+/// What `add_peer` did
+#[derive(Debug,Clone,Copy,Serialize,Deserialize,PartialEq,Eq)]
+#[serde(rename_all="snake_case")]
+pub enum DialStatus{
+    /// A peer at that address is already connected; nothing was dialed
+    AlreadyConnected,
+    /// Dialing in the background
+    Dialing,
+}
+
+/// One sender's pooled transactions, ready ones in nonce order, then time-locked ones
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct PoolSenderView{
+    pub sender:String,
+    /// Spend of these transactions held against the sender's balance
+    pub reserved:u64,
+    pub transactions:Vec<PooledTxView>,
+}
+
+fn peer_id(peer:&str)->Result<PeerId,RpcError>{
+    let bytes=hex::decode(peer).ok().and_then(|bytes| bytes.try_into().ok());
+    bytes.map(PeerId::from_bytes).ok_or(RpcError::InvalidPeerId)
+}
+
+impl RpcContext{
+    fn network(&self)->Result<&Network,RpcError>{
+        self.admin.network.as_ref().ok_or(RpcError::Unavailable("peer management"))
+    }
+
+    pub fn peers(&self)->Result<Vec<PeerView>,RpcError>{
+        let network=self.network()?;
+        Ok(network
+        .peers()
+        .into_iter()
+        .map(|info| PeerView{
+            peer_id:info.peer_id.to_string(),
+            addr:info.addr,
+            outbound:info.outbound,
+            height:info.height,
+            score:network.peer_score(&info.peer_id),
+        })
+        .collect())
+    }
+
+    pub fn banned_peers(&self)->Result<Vec<BanView>,RpcError>{
+        Ok(self
+        .network()?
+        .banned_peers()
+        .into_iter()
+        .map(|standing| BanView{peer_id:standing.peer_id.to_string(),score:standing.score,banned_until:standing.banned_until.unwrap_or_default()})
+        .collect())
+    }
+
+    /// Dial `addr` in the background unless a peer there is connected. `peers` shows
+    /// whether it connected; a failure is published as `NetworkEvent::DialFailed`.
+    pub fn add_peer(&self,addr:SocketAddr)->Result<DialStatus,RpcError>{
+        let network=self.network()?.clone();
+        if network.peers().iter().any(|peer| peer.addr==addr || peer.listen_addr==Some(addr)){
+            return Ok(DialStatus::AlreadyConnected)
+        }
+        tokio::spawn(async move {
+            let _=network.dial(addr).await;
+        });
+        Ok(DialStatus::Dialing)
+    }
+
+    /// Disconnect `peer`; returns whether it was connected
+    pub fn remove_peer(&self,peer:&str)->Result<bool,RpcError>{
+        let (network,peer)=(self.network()?,peer_id(peer)?);
+        let connected=network.is_connected(&peer);
+        network.disconnect(&peer);
+        Ok(connected)
+    }
+
+    /// Disconnect `peer` and refuse it for `seconds`
+    pub fn ban_peer(&self,peer:&str,seconds:u64)->Result<(),RpcError>{
+        self.network()?.ban(&peer_id(peer)?,seconds).map_err(|e| RpcError::Admin(e.to_string()))
+    }
+
+    pub fn unban_peer(&self,peer:&str)->Result<(),RpcError>{
+        self.network()?.unban(&peer_id(peer)?).map_err(|e| RpcError::Admin(e.to_string()))
+    }
+
+    /// Every pooled transaction, by sender
+    pub fn mempool_content(&self)->Vec<PoolSenderView>{
+        let mempool=self.mempool.lock().expect("mempool lock poisoned");
+        let mut senders:BTreeMap<&str,Vec<PooledTxView>>=BTreeMap::new();
+        for entry in mempool.pooled(){
+            let view=PooledTxView{hash:entry.tx.tx_hash_hex(),gas_price:entry.gas_price,transaction:entry.tx.clone()};
+            senders.entry(&entry.tx.tx.sender).or_default().push(view);
+        }
+        senders
+        .into_iter()
+        .map(|(sender,transactions)| PoolSenderView{sender:sender.to_string(),reserved:mempool.reserved(sender),transactions})
+        .collect()
+    }
+
+    /// Drop every pooled transaction; returns how many there were
+    pub fn flush_mempool(&self)->usize{
+        self.mempool.lock().expect("mempool lock poisoned").clear()
+    }
+
+    /// Snapshot the state at the tip for state sync. Fails while the tip isn't final, as
+    /// peers bootstrap from what is kept.
+    pub fn take_snapshot(&self)->Result<SnapshotManifest,RpcError>{
+        self.reads().take_snapshot()
+    }
+
+    pub fn rotate_logs(&self)->Result<(),RpcError>{
+        let rotate=self.admin.rotate_logs.as_ref().ok_or(RpcError::Unavailable("log rotation"))?;
+        rotate().map_err(|e| RpcError::Admin(format!("log rotation: {e}")))
+    }
+
+    /// Whether `send_transaction` admits transactions
+    pub fn accepting_transactions(&self)->bool{
+        self.admin.accepting_txs.load(Ordering::Relaxed)
+    }
+
+    /// Pause (`false`) or resume transaction submission; returns the previous setting
+    pub fn set_accepting_transactions(&self,accept:bool)->bool{
+        self.admin.accepting_txs.swap(accept,Ordering::Relaxed)
+    }
+}
+
+impl Reads<'_>{
+    pub fn take_snapshot(&mut self)->Result<SnapshotManifest,RpcError>{
+        let snapshots=self.ctx.admin.snapshots.clone().ok_or(RpcError::Unavailable("state snapshots"))?;
+        let state=self.ctx.state.snapshot();
+        let chain=self.chain();
+        let tip=chain.last_block();
+        if !chain.is_final(tip.index){
+            return Err(RpcError::Admin(format!("block {} is not final yet",tip.index)))
+        }
+        Ok(snapshots.take(tip,&state))
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::jsonrpc::{INVALID_PARAMS,INTERNAL_ERROR,TX_REJECTED,UNAUTHORIZED,handle};
+    use super::super::auth::Role;
+    use super::super::tests::context;
+    use crate::finality::Commit;
+    use crate::network::{NetworkConfig,NetworkEvent};
+    use crate::network::identity::NodeIdentity;
+    use crate::network::statesync::StateSyncConfig;
+    use crate::transaction::{SignedTransaction,Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+    use serde_json::{Value,json};
+    use std::sync::atomic::AtomicUsize;
+    use std::time::Duration;
+
+    async fn network()->Network{
+        let config=NetworkConfig{listen_addr:SocketAddr::from(([127,0,0,1],0)),..NetworkConfig::default()};
+        Network::start(NodeIdentity::generate(),config).await.unwrap()
+    }
+
+    fn admin(ctx:&RpcContext,method:&str,params:Value)->Value{
+        handle(ctx,Role::Admin,json!({"jsonrpc":"2.0","method":method,"params":params,"id":1}).to_string().as_bytes()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_peer_management(){
+        let (node,other)=(network().await,network().await);
+        let ctx=context("alice");
+        assert_eq!(admin(&ctx,"admin_peers",json!([]))["error"]["code"],INTERNAL_ERROR);
+        let ctx=ctx.with_network(node.clone());
+
+        assert_eq!(admin(&ctx,"admin_addPeer",json!([other.local_addr()]))["result"],"dialing");
+        let peer=other.local_peer_id().to_string();
+        for _ in 0..100{
+            if node.is_connected(other.local_peer_id()){
+                break
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let peers=admin(&ctx,"admin_peers",json!([]))["result"].clone();
+        assert_eq!((peers[0]["peer_id"].as_str(),&peers[0]["outbound"]),(Some(peer.as_str()),&json!(true)));
+        assert_eq!(admin(&ctx,"admin_addPeer",json!([other.local_addr()]))["result"],"already_connected");
+
+        // a failed dial is published
+        let mut events=node.events();
+        let closed=std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        admin(&ctx,"admin_addPeer",json!([closed]));
+        let failed=tokio::time::timeout(Duration::from_secs(5),events.recv()).await.unwrap().unwrap();
+        assert!(matches!(failed,NetworkEvent::DialFailed{addr,..} if addr==closed));
+
+        assert_eq!(admin(&ctx,"admin_removePeer",json!([peer]))["result"],true);
+        assert_eq!(admin(&ctx,"admin_removePeer",json!({"peer":peer}))["result"],false);
+        assert_eq!(admin(&ctx,"admin_banPeer",json!([peer,60]))["result"],Value::Null);
+        assert!(node.is_banned(other.local_peer_id()));
+        assert_eq!(admin(&ctx,"admin_bannedPeers",json!([]))["result"][0]["peer_id"],peer.as_str());
+        admin(&ctx,"admin_unbanPeer",json!([peer]));
+        assert!(!node.is_banned(other.local_peer_id()));
+        assert_eq!(admin(&ctx,"admin_banPeer",json!(["not-a-peer",60]))["error"]["code"],INVALID_PARAMS);
+
+        // admin only
+        let wallet=handle(&ctx,Role::Wallet,br#"{"jsonrpc":"2.0","method":"admin_peers","id":1}"#).unwrap();
+        assert_eq!(wallet["error"]["code"],UNAUTHORIZED);
+    }
+
+    #[test]
+    fn test_mempool_content_and_flush(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let ctx=context(&sender);
+        for nonce in 0..2{
+            ctx.send_transaction(SignedTransaction::sign_with_keypair(&Transaction::new(sender.clone(),"bob".to_string(),10,1_000,nonce,None),&kp)).unwrap();
+        }
+        let content=admin(&ctx,"admin_mempool",json!([]))["result"].clone();
+        assert_eq!((content[0]["sender"].as_str(),content[0]["reserved"].as_u64()),(Some(sender.as_str()),Some(2_020)));
+        assert_eq!(content[0]["transactions"][1]["transaction"]["tx"]["nonce"],1);
+        assert_eq!(admin(&ctx,"admin_flushMempool",json!([]))["result"],2);
+        assert_eq!(ctx.mempool().transactions,0);
+    }
+
+    #[test]
+    fn test_tx_acceptance_switch(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let ctx=context(&sender);
+        let transfer=|nonce| SignedTransaction::sign_with_keypair(&Transaction::new(sender.clone(),"bob".to_string(),10,1_000,nonce,None),&kp);
+        assert_eq!(admin(&ctx,"admin_setTxAcceptance",json!([false]))["result"],true);
+        assert!(matches!(ctx.send_transaction(transfer(0)),Err(RpcError::SubmissionsPaused)));
+        let refused=handle(&ctx,Role::Wallet,json!({"jsonrpc":"2.0","method":"tx_send","params":[transfer(0)],"id":1}).to_string().as_bytes()).unwrap();
+        assert_eq!(refused["error"]["code"],TX_REJECTED);
+        // clones share the switch
+        assert!(!ctx.clone().accepting_transactions());
+        admin(&ctx,"admin_setTxAcceptance",json!({"accept":true}));
+        ctx.send_transaction(transfer(0)).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_snapshot_only_of_a_final_tip(){
+        let ctx=context("alice");
+        assert_eq!(admin(&ctx,"admin_snapshot",json!([]))["error"]["code"],INTERNAL_ERROR);
+        let snapshots=SnapshotServer::new(network().await,StateSyncConfig::default());
+        let ctx=ctx.with_snapshots(snapshots.clone());
+        ctx.chain.lock().unwrap().add_block("block 1".to_string());
+        assert_eq!(admin(&ctx,"admin_snapshot",json!([]))["error"]["code"],INTERNAL_ERROR);
+        assert_eq!(snapshots.latest(),None);
+
+        let hash=ctx.chain.lock().unwrap().last_block().hash.clone();
+        ctx.chain.lock().unwrap().mark_final(&Commit{height:1,block_hash:hash,precommits:Vec::new()}).unwrap();
+        // in a batch that already holds the chain
+        let batch=json!([{"jsonrpc":"2.0","method":"chain_getHeight","id":1},{"jsonrpc":"2.0","method":"admin_snapshot","id":2}]);
+        let manifest=handle(&ctx,Role::Admin,batch.to_string().as_bytes()).unwrap()[1]["result"].clone();
+        assert_eq!((manifest["height"].as_u64(),manifest["chunk_hashes"].as_array().map(Vec::len)),(Some(1),Some(1)));
+    }
+
+    #[test]
+    fn test_rotate_logs(){
+        let ctx=context("alice");
+        assert_eq!(admin(&ctx,"admin_rotateLogs",json!([]))["error"]["code"],INTERNAL_ERROR);
+        let rotations=Arc::new(AtomicUsize::new(0));
+        let counter=rotations.clone();
+        let ctx=ctx.with_log_rotation(Arc::new(move || {
+            counter.fetch_add(1,Ordering::Relaxed);
+            Ok(())
+        }));
+        admin(&ctx,"admin_rotateLogs",json!([]));
+        assert_eq!(rotations.load(Ordering::Relaxed),1);
+    }
+}
@@ -0,0 +1,220 @@
+// src/rpc/auth.rs
+
+//! Authentication and method permissions for the node APIs
+//! - Roles, lowest to highest: `public` (queries), `wallet` (also transaction submission),
+//!   `admin` (everything). Each role may do what the roles below it may.
+//! - Credentials arrive as `Authorization: Bearer <token>` (or `x-api-key: <key>`), where
+//!   the token is a configured API key or an HS256 JWT signed with the node's secret whose
+//!   claims carry `role` and `exp`
+//! - Requests without credentials get the `anonymous` role; wrong, expired or malformed
+//!   credentials are refused outright (401 / `UNAUTHENTICATED`) rather than downgraded
+//! - API keys are kept as SHA-256 digests and looked up by digest, and JWT signatures are
+//!   checked in constant time
+
+use base64::{Engine as _,engine::general_purpose::URL_SAFE_NO_PAD};
+use hmac::{Hmac,Mac};
+use serde::{Deserialize,Serialize};
+use sha2::{Digest,Sha256};
+use std::collections::HashMap;
+use std::time::{SystemTime,UNIX_EPOCH};
+use thiserror::Error;
+
+type HmacSha256=Hmac<Sha256>;
+
+#[derive(Debug,Clone,Copy,PartialEq,Eq,PartialOrd,Ord,Hash,Serialize,Deserialize)]
+#[serde(rename_all="lowercase")]
+pub enum Role{
+    Public,
+    Wallet,
+    Admin,
+}
+
+impl Role{
+    pub fn name(&self)->&'static str{
+        match self{
+            Role::Public=>"public",
+            Role::Wallet=>"wallet",
+            Role::Admin=>"admin",
+        }
+    }
+}
+
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum AuthError{
+    #[error("unknown api key")]
+    UnknownKey,
+    #[error("malformed token")]
+    Malformed,
+    #[error("bad token signature")]
+    BadSignature,
+    #[error("token expired")]
+    Expired,
+    #[error("requires the {} role",.0.name())]
+    Forbidden(Role),
+}
+
+#[derive(Debug,Clone)]
+pub struct AuthConfig{
+    /// API key -> role it grants
+    pub api_keys:HashMap<String,Role>,
+    /// HS256 secret JWTs are signed with; JWTs are refused when unset
+    pub jwt_secret:Option<Vec<u8>>,
+    /// Role of requests without credentials
+    pub anonymous:Role,
+}
+
+impl Default for AuthConfig{
+    /// No credentials configured; anyone may query and submit transactions, nobody may
+    /// administer the node
+    fn default()->Self{
+        AuthConfig{api_keys:HashMap::new(),jwt_secret:None,anonymous:Role::Wallet}
+    }
+}
+
+#[derive(Serialize,Deserialize)]
+struct Claims{
+    role:Role,
+    /// Unix seconds
+    exp:u64,
+}
+
+/// Role needed to call a JSON-RPC method (or its REST / gRPC counterpart)
+pub fn method_role(method:&str)->Role{
+    match method{
+        "tx_send"=>Role::Wallet,
+        method if method.starts_with("admin_")=>Role::Admin,
+        _=>Role::Public,
+    }
+}
+
+/// Checks that `role` may do what needs `required`
+pub fn authorize(role:Role,required:Role)->Result<(),AuthError>{
+    if role>=required{
+        Ok(())
+    } else{
+        Err(AuthError::Forbidden(required))
+    }
+}
+
+/// HS256 JWT granting `role` until `exp` (unix seconds), signed with `secret`
+pub fn issue_jwt(secret:&[u8],role:Role,exp:u64)->String{
+    let header=URL_SAFE_NO_PAD.encode(br#"{"alg":"HS256","typ":"JWT"}"#);
+    let claims=URL_SAFE_NO_PAD.encode(serde_json::to_vec(&Claims{role,exp}).expect("claims serialize"));
+    let signed=format!("{header}.{claims}");
+    let mut mac=HmacSha256::new_from_slice(secret).expect("hmac takes any key length");
+    mac.update(signed.as_bytes());
+    format!("{signed}.{}",URL_SAFE_NO_PAD.encode(mac.finalize().into_bytes()))
+}
+
+/// Resolves credentials to a role
+#[derive(Debug,Clone)]
+pub struct Authenticator{
+    /// SHA-256 of each API key -> role
+    keys:HashMap<[u8;32],Role>,
+    jwt_secret:Option<Vec<u8>>,
+    anonymous:Role,
+}
+
+impl Authenticator{
+    pub fn new(config:&AuthConfig)->Self{
+        let keys=config.api_keys.iter().map(|(key,role)| (Sha256::digest(key.as_bytes()).into(),*role)).collect();
+        Authenticator{keys,jwt_secret:config.jwt_secret.clone(),anonymous:config.anonymous}
+    }
+
+    /// Role of a request, from its `Authorization` header value
+    pub fn authenticate(&self,authorization:Option<&str>)->Result<Role,AuthError>{
+        let Some(credentials)=authorization else{
+            return Ok(self.anonymous)
+        };
+        let token=credentials.strip_prefix("Bearer ").unwrap_or(credentials).trim();
+        self.authenticate_token(token,now())
+    }
+
+    fn authenticate_token(&self,token:&str,now:u64)->Result<Role,AuthError>{
+        let digest:[u8;32]=Sha256::digest(token.as_bytes()).into();
+        if let Some(role)=self.keys.get(&digest){
+            return Ok(*role)
+        }
+        // anything that isn't shaped like a JWT is taken for an API key
+        let Some(secret)=&self.jwt_secret else{
+            return Err(AuthError::UnknownKey)
+        };
+        let Some((signed,signature))=token.rsplit_once('.') else{
+            return Err(AuthError::UnknownKey)
+        };
+        let Some((header,claims))=signed.split_once('.') else{
+            return Err(AuthError::UnknownKey)
+        };
+        let header:serde_json::Value=decode_part(header)?;
+        if header["alg"]!="HS256"{
+            return Err(AuthError::Malformed)
+        }
+        let signature=URL_SAFE_NO_PAD.decode(signature).map_err(|_| AuthError::Malformed)?;
+        let mut mac=HmacSha256::new_from_slice(secret).expect("hmac takes any key length");
+        mac.update(signed.as_bytes());
+        mac.verify_slice(&signature).map_err(|_| AuthError::BadSignature)?;
+        let claims:Claims=decode_part(claims)?;
+        if claims.exp<=now{
+            return Err(AuthError::Expired)
+        }
+        Ok(claims.role)
+    }
+}
+
+fn decode_part<T:for<'de> Deserialize<'de>>(part:&str)->Result<T,AuthError>{
+    let bytes=URL_SAFE_NO_PAD.decode(part).map_err(|_| AuthError::Malformed)?;
+    serde_json::from_slice(&bytes).map_err(|_| AuthError::Malformed)
+}
+
+fn now()->u64{
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn authenticator()->Authenticator{
+        Authenticator::new(&AuthConfig{
+            api_keys:HashMap::from([("k-admin".to_string(),Role::Admin),("k-wallet".to_string(),Role::Wallet)]),
+            jwt_secret:Some(b"secret".to_vec()),
+            anonymous:Role::Public,
+        })
+    }
+
+    #[test]
+    fn test_api_keys_and_anonymous(){
+        let auth=authenticator();
+        assert_eq!(auth.authenticate(None),Ok(Role::Public));
+        assert_eq!(auth.authenticate(Some("Bearer k-admin")),Ok(Role::Admin));
+        assert_eq!(auth.authenticate(Some("k-wallet")),Ok(Role::Wallet));
+        assert_eq!(auth.authenticate(Some("Bearer k-other")),Err(AuthError::UnknownKey));
+
+        assert_eq!(authorize(Role::Public,method_role("state_getBalance")),Ok(()));
+        assert_eq!(authorize(Role::Public,method_role("tx_send")),Err(AuthError::Forbidden(Role::Wallet)));
+        assert_eq!(authorize(Role::Wallet,method_role("admin_banPeer")),Err(AuthError::Forbidden(Role::Admin)));
+        assert_eq!(authorize(Role::Admin,method_role("tx_send")),Ok(()));
+    }
+
+    #[test]
+    fn test_jwt_roles_expiry_and_signature(){
+        let auth=authenticator();
+        let token=issue_jwt(b"secret",Role::Wallet,2_000);
+        assert_eq!(auth.authenticate_token(&token,1_999),Ok(Role::Wallet));
+        assert_eq!(auth.authenticate_token(&token,2_000),Err(AuthError::Expired));
+        assert_eq!(auth.authenticate_token(&issue_jwt(b"other",Role::Admin,2_000),1_000),Err(AuthError::BadSignature));
+
+        // swapping in claims for a higher role breaks the signature
+        let admin=issue_jwt(b"other",Role::Admin,2_000);
+        let mut parts:Vec<&str>=token.split('.').collect();
+        parts[1]=admin.split('.').nth(1).unwrap();
+        assert_eq!(auth.authenticate_token(&parts.join("."),1_000),Err(AuthError::BadSignature));
+
+        let unsigned=format!("{}.{}.",URL_SAFE_NO_PAD.encode(br#"{"alg":"none"}"#),parts[1]);
+        assert_eq!(auth.authenticate_token(&unsigned,1_000),Err(AuthError::Malformed));
+
+        // without a secret, JWTs are just unknown keys
+        let keys_only=Authenticator::new(&AuthConfig::default());
+        assert_eq!(keys_only.authenticate_token(&token,1_000),Err(AuthError::UnknownKey));
+    }
+}
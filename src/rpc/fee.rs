@@ -0,0 +1,135 @@
+// src/rpc/fee.rs
+
+//! Fee estimation for wallets
+//! - `estimate` suggests a gas price for a `Priority`, aiming at confirmation within its
+//!   `target_blocks`
+//! - Two signals, taking the higher: what transactions in the last `FEE_HISTORY_BLOCKS`
+//!   blocks paid (a percentile of their gas prices, higher for more urgency), and what it
+//!   takes to outbid the mempool backlog that would fill the target's blocks first
+//! - Never below the pool's minimum gas price
+//! - Gas prices are integers, so recent ones are rounded up and the estimate errs high
+
+use crate::mempool::BlockLimits;
+use serde::{Deserialize,Serialize};
+use utoipa::ToSchema;
+
+/// Blocks back from the tip whose transactions inform the estimate
+pub const FEE_HISTORY_BLOCKS:u64=20;
+
+#[derive(Debug,Clone,Copy,Default,PartialEq,Eq,Serialize,Deserialize,ToSchema)]
+#[serde(rename_all="lowercase")]
+pub enum Priority{
+    Low,
+    #[default]
+    Medium,
+    High,
+}
+
+impl Priority{
+    /// Blocks within which a transaction paying the estimate should confirm
+    pub fn target_blocks(&self)->u64{
+        match self{
+            Priority::Low=>10,
+            Priority::Medium=>3,
+            Priority::High=>1,
+        }
+    }
+
+    /// Percentile of recent gas prices to match
+    fn percentile(&self)->usize{
+        match self{
+            Priority::Low=>25,
+            Priority::Medium=>50,
+            Priority::High=>90,
+        }
+    }
+}
+
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct FeeEstimate{
+    pub priority:Priority,
+    pub target_blocks:u64,
+    pub gas_price:u64,
+    /// `gas_price` times the gas asked about, when given
+    pub fee:Option<u64>,
+}
+
+/// A pooled transaction, as far as the estimate cares
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct Pending{
+    pub gas_price:u64,
+    pub gas:u64,
+    pub size:usize,
+}
+
+/// Gas price for `priority` given the gas prices of `recent` included transactions, the
+/// `pending` pool and the limits blocks are built under; `fee` is priced for `gas` if given
+pub fn estimate(priority:Priority,recent:&mut [u64],pending:&mut [Pending],limits:BlockLimits,min_gas_price:u64,gas:Option<u64>)->FeeEstimate{
+    let target_blocks=priority.target_blocks();
+    let gas_price=history_price(recent,priority.percentile()).max(backlog_price(pending,limits,target_blocks)).max(min_gas_price);
+    FeeEstimate{priority,target_blocks,gas_price,fee:gas.map(|gas| gas.saturating_mul(gas_price))}
+}
+
+fn history_price(recent:&mut [u64],percentile:usize)->u64{
+    if recent.is_empty(){
+        return 0
+    }
+    recent.sort_unstable();
+    recent[(recent.len()-1)*percentile/100]
+}
+
+/// Lowest gas price that gets ahead of whatever doesn't fit in `blocks` blocks; 0 when
+/// the whole pool fits
+fn backlog_price(pending:&mut [Pending],limits:BlockLimits,blocks:u64)->u64{
+    pending.sort_unstable_by_key(|tx| std::cmp::Reverse(tx.gas_price));
+    let max_txs=limits.max_txs.saturating_mul(blocks as usize);
+    let max_bytes=limits.max_bytes.saturating_mul(blocks as usize);
+    let max_gas=limits.max_gas.saturating_mul(blocks);
+    let (mut bytes,mut gas)=(0usize,0u64);
+    for (i,tx) in pending.iter().enumerate(){
+        bytes+=tx.size;
+        gas=gas.saturating_add(tx.gas);
+        if i>=max_txs || bytes>max_bytes || gas>max_gas{
+            return tx.gas_price.saturating_add(1)
+        }
+    }
+    0
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    const LIMITS:BlockLimits=BlockLimits{max_txs:2,max_bytes:1_000_000,max_gas:1_000_000};
+
+    fn pending(gas_prices:&[u64])->Vec<Pending>{
+        gas_prices.iter().map(|&gas_price| Pending{gas_price,gas:1_000,size:200}).collect()
+    }
+
+    #[test]
+    fn test_history_sets_the_price_of_a_quiet_pool(){
+        let mut recent=vec![5,1,9,3,7,2,8,4,6,10];
+        let price=|priority,recent:&mut Vec<u64>| estimate(priority,recent,&mut [],LIMITS,0,None).gas_price;
+        assert_eq!(price(Priority::Low,&mut recent),3);
+        assert_eq!(price(Priority::Medium,&mut recent),5);
+        assert_eq!(price(Priority::High,&mut recent),9);
+
+        let estimate=estimate(Priority::Medium,&mut [],&mut [],LIMITS,2,Some(1_500));
+        assert_eq!(estimate,FeeEstimate{priority:Priority::Medium,target_blocks:3,gas_price:2,fee:Some(3_000)});
+    }
+
+    #[test]
+    fn test_backlog_outbids_what_doesnt_fit(){
+        // seven pooled; one block takes two, three blocks take six
+        let mut pool=pending(&[10,40,20,70,30,60,50]);
+        assert_eq!(estimate(Priority::High,&mut [],&mut pool,LIMITS,0,None).gas_price,51);
+        assert_eq!(estimate(Priority::Medium,&mut [],&mut pool,LIMITS,0,None).gas_price,11);
+        assert_eq!(estimate(Priority::Low,&mut [],&mut pool,LIMITS,0,None).gas_price,0);
+        // recent blocks paying more still win
+        assert_eq!(estimate(Priority::Low,&mut [100],&mut pool,LIMITS,0,None).gas_price,100);
+
+        // gas fills blocks before the count does
+        let gas_bound=BlockLimits{max_gas:1_500,..LIMITS};
+        assert_eq!(estimate(Priority::High,&mut [],&mut pool,gas_bound,0,None).gas_price,61);
+    }
+}
@@ -0,0 +1,296 @@
+// src/rpc/graphql.rs
+
+//! GraphQL for block explorers on `POST /graphql`
+//! - Nested queries resolve in one round trip, e.g. a block, its transactions and each
+//!   sender's current balance:
+//!   `{ block(height: 5) { hash transactions { hash success sender { address balance } } } }`
+//! - Roots: `height`, `block(height | hash)`, `blocks(from, limit)`, `account`,
+//!   `transaction`, `validators`
+//! - A block's transactions come from its receipts, so they're known for blocks applied on
+//!   this node; balances are read from the latest published state
+//! - Queries are capped in depth (`MAX_DEPTH`) and cost (`MAX_COMPLEXITY`), and `blocks`
+//!   pages at most `MAX_BLOCKS_PER_QUERY`
+
+use super::{AccountView,RpcContext,TxStatus,ValidatorView};
+use crate::block::Block;
+use crate::receipt::{Event,ReceiptStatus};
+use async_graphql::{Context,EmptyMutation,EmptySubscription,Json,Object,Schema};
+use axum::response::Json as JsonResponse;
+
+/// Deepest selection nesting accepted
+pub const MAX_DEPTH:usize=8;
+
+/// Most fields a query may select, counting those under lists once
+pub const MAX_COMPLEXITY:usize=500;
+
+pub const MAX_BLOCKS_PER_QUERY:u64=100;
+
+pub type NodeSchema=Schema<Query,EmptyMutation,EmptySubscription>;
+
+pub fn schema(context:RpcContext)->NodeSchema{
+    Schema::build(Query,EmptyMutation,EmptySubscription)
+    .data(context)
+    .limit_depth(MAX_DEPTH)
+    .limit_complexity(MAX_COMPLEXITY)
+    .finish()
+}
+
+pub(super) async fn serve(schema:NodeSchema,JsonResponse(request):JsonResponse<async_graphql::Request>)->JsonResponse<async_graphql::Response>{
+    JsonResponse(schema.execute(request).await)
+}
+
+fn node<'a>(ctx:&Context<'a>)->&'a RpcContext{
+    ctx.data_unchecked::<RpcContext>()
+}
+
+pub struct Query;
+
+#[Object]
+impl Query{
+    /// Height of the chain tip
+    async fn height(&self,ctx:&Context<'_>)->u64{
+        node(ctx).height()
+    }
+
+    /// Block by height or by hash
+    async fn block(&self,ctx:&Context<'_>,height:Option<u64>,hash:Option<String>)->async_graphql::Result<Option<Block>>{
+        match (height,hash){
+            (Some(height),None)=>Ok(node(ctx).block(height)),
+            (None,Some(hash))=>Ok(node(ctx).block_by_hash(&hash)),
+            _=>Err("exactly one of height or hash is required".into()),
+        }
+    }
+
+    /// Up to `limit` consecutive blocks starting at height `from`
+    async fn blocks(&self,ctx:&Context<'_>,from:u64,#[graphql(default=10)] limit:u64)->Vec<Block>{
+        let limit=limit.min(MAX_BLOCKS_PER_QUERY);
+        (from..from.saturating_add(limit)).map_while(|height| node(ctx).block(height)).collect()
+    }
+
+    async fn account(&self,ctx:&Context<'_>,address:String)->AccountView{
+        node(ctx).account(&address)
+    }
+
+    /// Pooled or included transaction
+    async fn transaction(&self,ctx:&Context<'_>,hash:String)->Option<TxStatus>{
+        node(ctx).transaction(&hash)
+    }
+
+    async fn validators(&self,ctx:&Context<'_>)->Vec<ValidatorView>{
+        node(ctx).validators()
+    }
+}
+
+#[Object]
+impl Block{
+    async fn height(&self)->u64{
+        self.index
+    }
+
+    async fn hash(&self)->&str{
+        &self.hash
+    }
+
+    async fn previous_hash(&self)->&str{
+        &self.previous_hash
+    }
+
+    /// RFC 3339
+    async fn timestamp(&self)->String{
+        self.timestamp.to_rfc3339()
+    }
+
+    async fn data(&self)->&str{
+        &self.data
+    }
+
+    async fn proposer(&self)->Option<&str>{
+        self.consensus.proposer.as_deref()
+    }
+
+    async fn finalized(&self,ctx:&Context<'_>)->bool{
+        node(ctx).is_final(self.index)
+    }
+
+    /// Transactions applied in this block, in order
+    async fn transactions(&self,ctx:&Context<'_>)->Vec<TxStatus>{
+        let node=node(ctx);
+        let finalized=node.is_final(self.index);
+        node
+        .block_receipts(self.index)
+        .map(|block| block.receipts.into_iter().map(|receipt| TxStatus::Included{receipt,finalized}).collect())
+        .unwrap_or_default()
+    }
+}
+
+#[Object(name="Transaction")]
+impl TxStatus{
+    async fn hash(&self)->String{
+        match self{
+            TxStatus::Pending{transaction}=>transaction.tx_hash_hex(),
+            TxStatus::Included{receipt,..}=>receipt.tx_hash.clone(),
+        }
+    }
+
+    /// "pending" or "included"
+    async fn status(&self)->&str{
+        match self{
+            TxStatus::Pending{..}=>"pending",
+            TxStatus::Included{..}=>"included",
+        }
+    }
+
+    /// Sending account, as it stands now
+    async fn sender(&self,ctx:&Context<'_>)->AccountView{
+        let sender=match self{
+            TxStatus::Pending{transaction}=>&transaction.tx.sender,
+            TxStatus::Included{receipt,..}=>&receipt.sender,
+        };
+        node(ctx).account(sender)
+    }
+
+    /// Whether it executed successfully; null while pending
+    async fn success(&self)->Option<bool>{
+        match self{
+            TxStatus::Pending{..}=>None,
+            TxStatus::Included{receipt,..}=>Some(receipt.is_success()),
+        }
+    }
+
+    async fn failure(&self)->Option<&str>{
+        match self{
+            TxStatus::Included{receipt,..}=>match &receipt.status{
+                ReceiptStatus::Failed{reason}=>Some(reason),
+                ReceiptStatus::Success=>None,
+            },
+            TxStatus::Pending{..}=>None,
+        }
+    }
+
+    async fn gas_used(&self)->Option<u64>{
+        match self{
+            TxStatus::Included{receipt,..}=>Some(receipt.gas_used),
+            TxStatus::Pending{..}=>None,
+        }
+    }
+
+    async fn block(&self,ctx:&Context<'_>)->Option<Block>{
+        match self{
+            TxStatus::Included{receipt,..}=>node(ctx).block(receipt.block_height),
+            TxStatus::Pending{..}=>None,
+        }
+    }
+
+    async fn finalized(&self)->bool{
+        matches!(self,TxStatus::Included{finalized:true,..})
+    }
+
+    /// Emitted events, as JSON
+    async fn events(&self)->Json<Vec<Event>>{
+        match self{
+            TxStatus::Included{receipt,..}=>Json(receipt.events.clone()),
+            TxStatus::Pending{..}=>Json(Vec::new()),
+        }
+    }
+}
+
+#[Object(name="Account")]
+impl AccountView{
+    async fn address(&self)->&str{
+        &self.address
+    }
+
+    async fn balance(&self)->u64{
+        self.balance
+    }
+
+    async fn nonce(&self)->u64{
+        self.nonce
+    }
+
+    async fn stake(&self)->u64{
+        self.stake
+    }
+}
+
+#[Object(name="Validator")]
+impl ValidatorView{
+    async fn address(&self)->&str{
+        &self.address
+    }
+
+    async fn account(&self,ctx:&Context<'_>)->AccountView{
+        node(ctx).account(&self.address)
+    }
+
+    async fn endpoint(&self)->&str{
+        &self.endpoint
+    }
+
+    async fn stake(&self)->u64{
+        self.stake
+    }
+
+    async fn jailed(&self)->bool{
+        self.jailed
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{context,http};
+    use super::super::{RpcConfig,RpcServer};
+    use crate::gas::GasSchedule;
+    use crate::receipt::BlockReceipts;
+    use crate::transaction::{SignedTransaction,Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+    use serde_json::{Value,json};
+
+    async fn query(schema:&NodeSchema,query:&str)->Value{
+        let response=schema.execute(query).await;
+        assert!(response.errors.is_empty(),"{:?}",response.errors);
+        response.data.into_json().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_block_with_transactions_and_sender_balances(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let ctx=context(&sender);
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(sender.clone(),"bob".to_string(),10,1_000,0,None),&kp);
+        let receipt=ctx.state.write(|state| state.apply_with_receipt(&tx,&GasSchedule::default(),1,0));
+        {
+            let mut chain=ctx.chain.lock().unwrap();
+            chain.add_block("block 1".to_string());
+            chain.record_receipts(BlockReceipts{height:1,receipts:vec![receipt],..Default::default()});
+        }
+        let schema=schema(ctx.clone());
+
+        let data=query(&schema,"{ block(height: 1) { height finalized transactions { hash status success sender { address balance nonce } } } }").await;
+        let balance=ctx.account(&sender).balance;
+        assert_eq!(data,json!({"block":{"height":1,"finalized":false,"transactions":[{
+            "hash":tx.tx_hash_hex(),
+            "status":"included",
+            "success":true,
+            "sender":{"address":sender,"balance":balance,"nonce":1},
+        }]}}));
+
+        let data=query(&schema,&format!(r#"{{ transaction(hash: "{}") {{ block {{ height }} events }} blocks(from: 0, limit: 5) {{ height }} }}"#,tx.tx_hash_hex())).await;
+        assert_eq!(data["transaction"]["block"],json!({"height":1}));
+        assert!(data["transaction"]["events"].as_array().is_some_and(|events| !events.is_empty()));
+        assert_eq!(data["blocks"],json!([{"height":0},{"height":1}]));
+
+        assert!(!schema.execute("{ block(height: 1, hash: \"x\") { height } }").await.errors.is_empty());
+        let deep="{ block(height: 1) { transactions { block { transactions { block { transactions { block { transactions { hash } } } } } } } } }";
+        assert!(!schema.execute(deep).await.errors.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_served_over_http(){
+        let config=RpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),..RpcConfig::default()};
+        let server=RpcServer::start(config,context("alice")).await.unwrap();
+        let (status,body)=http(server.local_addr(),"POST","/graphql",r#"{"query":"{ height account(address: \"alice\") { balance } }"}"#).await;
+        assert_eq!(status,200);
+        assert_eq!(serde_json::from_str::<Value>(&body).unwrap(),json!({"data":{"height":0,"account":{"balance":1_000_000}}}));
+    }
+}
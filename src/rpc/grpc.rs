@@ -0,0 +1,339 @@
+// src/rpc/grpc.rs
+
+//! gRPC node API (tonic), generated from `proto/node.proto` by `build.rs`
+//! - Block, account, validator, mempool and transaction queries, and transaction broadcast,
+//!   over the same `RpcContext` as JSON-RPC and REST
+//! - `SubscribeBlocks` streams `NodeEvent` blocks; a stream that falls too far behind ends
+//!   with `RESOURCE_EXHAUSTED` rather than silently skipping blocks
+//! - Transactions, consensus data and receipt events travel in their canonical encoding
+//! - Credentials go in the `authorization` (or `x-api-key`) metadata, as over HTTP (see
+//!   `auth`); `SendTransaction` needs the `wallet` role
+//! - Status codes: `NOT_FOUND` for unknown blocks and transactions, `INVALID_ARGUMENT` for
+//!   undecodable or rejected transactions, `UNAUTHENTICATED` for bad credentials and
+//!   `PERMISSION_DENIED` for a role too low
+
+use super::API_KEY_HEADER;
+use super::auth::{AuthConfig,AuthError,Authenticator,Role,authorize,method_role};
+use super::{AccountView,MempoolView,NodeEvent,RpcContext,RpcError,TxStatus,ValidatorView};
+use crate::canonical;
+use crate::receipt::ReceiptStatus;
+use proto::node_server::{Node,NodeServer};
+use proto::transaction_status::Status as TxState;
+use std::net::SocketAddr;
+use std::time::Duration;
+use tokio::net::TcpListener;
+use tokio::sync::{broadcast,mpsc};
+use tokio::task::AbortHandle;
+use tokio_stream::wrappers::{ReceiverStream,TcpListenerStream};
+use tonic::service::interceptor::InterceptedService;
+use tonic::{Request,Response,Status};
+
+/// Types and service generated from `proto/node.proto`
+pub mod proto{
+    tonic::include_proto!("netchain.node.v1");
+}
+
+/// Blocks buffered per `SubscribeBlocks` stream
+const STREAM_BUFFER:usize=64;
+
+#[derive(Debug,Clone)]
+pub struct GrpcConfig{
+    pub listen_addr:SocketAddr,
+    /// Largest request message accepted
+    pub max_message_bytes:usize,
+    /// Time allowed per unary call
+    pub request_timeout:Duration,
+    pub auth:AuthConfig,
+}
+
+impl Default for GrpcConfig{
+    fn default()->Self{
+        GrpcConfig{
+            listen_addr:SocketAddr::from(([127,0,0,1],50051)),
+            max_message_bytes:4*1024*1024,
+            request_timeout:Duration::from_secs(30),
+            auth:AuthConfig::default(),
+        }
+    }
+}
+
+impl From<&crate::block::Block> for proto::Block{
+    fn from(block:&crate::block::Block)->Self{
+        proto::Block{
+            index:block.index,
+            timestamp:block.timestamp.to_rfc3339(),
+            data:block.data.clone(),
+            previous_hash:block.previous_hash.clone(),
+            hash:block.hash.clone(),
+            consensus:canonical::encode(&block.consensus),
+        }
+    }
+}
+
+impl From<AccountView> for proto::Account{
+    fn from(account:AccountView)->Self{
+        proto::Account{address:account.address,balance:account.balance,nonce:account.nonce,stake:account.stake}
+    }
+}
+
+impl From<ValidatorView> for proto::Validator{
+    fn from(validator:ValidatorView)->Self{
+        proto::Validator{
+            address:validator.address,
+            consensus_pubkey:validator.consensus_pubkey,
+            vrf_pubkey:validator.vrf_pubkey,
+            endpoint:validator.endpoint,
+            stake:validator.stake,
+            jailed:validator.jailed,
+        }
+    }
+}
+
+impl From<MempoolView> for proto::MempoolStatus{
+    fn from(mempool:MempoolView)->Self{
+        proto::MempoolStatus{
+            transactions:mempool.transactions as u64,
+            locked:mempool.locked as u64,
+            bytes:mempool.bytes as u64,
+            fee_histogram:mempool
+            .fee_histogram
+            .into_iter()
+            .map(|bucket| proto::FeeBucket{min_gas_price:bucket.min_gas_price,transactions:bucket.transactions as u64})
+            .collect(),
+        }
+    }
+}
+
+impl From<TxStatus> for proto::TransactionStatus{
+    fn from(status:TxStatus)->Self{
+        match status{
+            TxStatus::Pending{transaction}=>proto::TransactionStatus{
+                status:Some(TxState::Pending(canonical::encode(&transaction))),
+                finalized:false,
+            },
+            TxStatus::Included{receipt,finalized}=>{
+                let failure=match &receipt.status{
+                    ReceiptStatus::Success=>String::new(),
+                    ReceiptStatus::Failed{reason}=>reason.clone(),
+                };
+                let receipt=proto::Receipt{
+                    success:receipt.is_success(),
+                    failure,
+                    gas_used:receipt.gas_used,
+                    block_height:receipt.block_height,
+                    index:receipt.index,
+                    events:canonical::encode(&receipt.events),
+                    tx_hash:receipt.tx_hash,
+                    sender:receipt.sender,
+                };
+                proto::TransactionStatus{status:Some(TxState::Included(receipt)),finalized}
+            }
+        }
+    }
+}
+
+impl From<RpcError> for Status{
+    fn from(error:RpcError)->Self{
+        match error{
+            RpcError::Rejected(_) | RpcError::InvalidCursor=>Status::invalid_argument(error.to_string()),
+            RpcError::SubmissionsPaused=>Status::unavailable(error.to_string()),
+            _=>Status::internal(error.to_string()),
+        }
+    }
+}
+
+impl From<AuthError> for Status{
+    fn from(error:AuthError)->Self{
+        match error{
+            AuthError::Forbidden(_)=>Status::permission_denied(error.to_string()),
+            _=>Status::unauthenticated(error.to_string()),
+        }
+    }
+}
+
+/// Interceptor resolving each call's credentials to a `Role` extension
+fn authenticate(auth:&Authenticator,mut request:Request<()>)->Result<Request<()>,Status>{
+    let metadata=request.metadata();
+    let credentials=metadata.get("authorization").or_else(|| metadata.get(API_KEY_HEADER)).map(|value| value.to_str().unwrap_or_default());
+    let role=auth.authenticate(credentials)?;
+    request.extensions_mut().insert(role);
+    Ok(request)
+}
+
+fn role<T>(request:&Request<T>)->Role{
+    request.extensions().get::<Role>().copied().unwrap_or(Role::Public)
+}
+
+struct NodeService{
+    ctx:RpcContext,
+}
+
+#[tonic::async_trait]
+impl Node for NodeService{
+    async fn get_height(&self,_:Request<proto::GetHeightRequest>)->Result<Response<proto::GetHeightResponse>,Status>{
+        Ok(Response::new(proto::GetHeightResponse{height:self.ctx.height()}))
+    }
+
+    async fn get_block(&self,request:Request<proto::GetBlockRequest>)->Result<Response<proto::Block>,Status>{
+        use proto::get_block_request::Block as Selector;
+        let block=match request.into_inner().block{
+            Some(Selector::Height(height))=>self.ctx.block(height),
+            Some(Selector::Hash(hash))=>self.ctx.block_by_hash(&hash),
+            None=>return Err(Status::invalid_argument("height or hash required")),
+        };
+        block.map(|block| Response::new((&block).into())).ok_or_else(|| Status::not_found("block not found"))
+    }
+
+    async fn get_account(&self,request:Request<proto::GetAccountRequest>)->Result<Response<proto::Account>,Status>{
+        Ok(Response::new(self.ctx.account(&request.into_inner().address).into()))
+    }
+
+    async fn get_validators(&self,_:Request<proto::GetValidatorsRequest>)->Result<Response<proto::GetValidatorsResponse>,Status>{
+        let validators=self.ctx.validators().into_iter().map(Into::into).collect();
+        Ok(Response::new(proto::GetValidatorsResponse{validators}))
+    }
+
+    async fn get_mempool(&self,_:Request<proto::GetMempoolRequest>)->Result<Response<proto::MempoolStatus>,Status>{
+        Ok(Response::new(self.ctx.mempool().into()))
+    }
+
+    async fn get_transaction(&self,request:Request<proto::GetTransactionRequest>)->Result<Response<proto::TransactionStatus>,Status>{
+        self.ctx
+        .transaction(&request.into_inner().hash)
+        .map(|status| Response::new(status.into()))
+        .ok_or_else(|| Status::not_found("transaction not found"))
+    }
+
+    async fn send_transaction(&self,request:Request<proto::SendTransactionRequest>)->Result<Response<proto::SendTransactionResponse>,Status>{
+        authorize(role(&request),method_role("tx_send"))?;
+        let tx=canonical::decode(&request.into_inner().transaction).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let hash=self.ctx.send_transaction(tx)?;
+        Ok(Response::new(proto::SendTransactionResponse{hash}))
+    }
+
+    type SubscribeBlocksStream=ReceiverStream<Result<proto::Block,Status>>;
+
+    async fn subscribe_blocks(&self,request:Request<proto::SubscribeBlocksRequest>)->Result<Response<Self::SubscribeBlocksStream>,Status>{
+        let finalized=request.into_inner().finalized;
+        let mut events=self.ctx.subscribe();
+        let (tx,rx)=mpsc::channel(STREAM_BUFFER);
+        tokio::spawn(async move {
+            loop{
+                let block=match events.recv().await{
+                    Ok(NodeEvent::NewBlock(block)) if !finalized=>block,
+                    Ok(NodeEvent::Finalized(block)) if finalized=>block,
+                    Ok(_)=>continue,
+                    Err(broadcast::error::RecvError::Lagged(missed))=>{
+                        let _=tx.send(Err(Status::resource_exhausted(format!("stream fell behind by {missed} events")))).await;
+                        return
+                    }
+                    Err(broadcast::error::RecvError::Closed)=>return,
+                };
+                if tx.send(Ok(block.as_ref().into())).await.is_err(){
+                    // client went away
+                    return
+                }
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+}
+
+/// Running gRPC server; stops when dropped
+pub struct GrpcServer{
+    local_addr:SocketAddr,
+    task:AbortHandle,
+}
+
+impl GrpcServer{
+    pub async fn start(config:GrpcConfig,context:RpcContext)->Result<Self,RpcError>{
+        let listener=TcpListener::bind(config.listen_addr).await?;
+        let local_addr=listener.local_addr()?;
+        let service=NodeServer::new(NodeService{ctx:context}).max_decoding_message_size(config.max_message_bytes);
+        let auth=Authenticator::new(&config.auth);
+        let service=InterceptedService::new(service,move |request| authenticate(&auth,request));
+        let router=tonic::transport::Server::builder().timeout(config.request_timeout).add_service(service);
+        let task=tokio::spawn(async move {
+            let _=router.serve_with_incoming(TcpListenerStream::new(listener)).await;
+        })
+        .abort_handle();
+        Ok(GrpcServer{local_addr,task})
+    }
+
+    /// Bound address, e.g. to find the port picked for `127.0.0.1:0`
+    pub fn local_addr(&self)->SocketAddr{
+        self.local_addr
+    }
+}
+
+impl Drop for GrpcServer{
+    fn drop(&mut self){
+        self.task.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::context;
+    use crate::block::Block;
+    use crate::transaction::{SignedTransaction,Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+    use proto::get_block_request::Block as Selector;
+    use proto::node_client::NodeClient;
+    use std::sync::Arc;
+    use tonic::Code;
+
+    #[tokio::test]
+    async fn test_queries_broadcast_and_block_stream(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let ctx=context(&sender);
+        let genesis=ctx.block(0).unwrap();
+        let config=GrpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),..GrpcConfig::default()};
+        let server=GrpcServer::start(config,ctx.clone()).await.unwrap();
+        let mut client=NodeClient::connect(format!("http://{}",server.local_addr())).await.unwrap();
+
+        assert_eq!(client.get_height(proto::GetHeightRequest{}).await.unwrap().into_inner().height,0);
+        let block=client.get_block(proto::GetBlockRequest{block:Some(Selector::Hash(genesis.hash.clone()))}).await.unwrap().into_inner();
+        assert_eq!(block,proto::Block::from(&genesis));
+        assert_eq!(canonical::decode::<crate::block::ConsensusData>(&block.consensus).unwrap(),genesis.consensus);
+        let missing=client.get_block(proto::GetBlockRequest{block:Some(Selector::Height(9))}).await.unwrap_err();
+        assert_eq!(missing.code(),Code::NotFound);
+        let account=client.get_account(proto::GetAccountRequest{address:sender.clone()}).await.unwrap().into_inner();
+        assert_eq!((account.balance,account.nonce),(1_000_000,0));
+        assert!(client.get_validators(proto::GetValidatorsRequest{}).await.unwrap().into_inner().validators.is_empty());
+
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(sender,"bob".to_string(),10,1_000,0,None),&kp);
+        let send=proto::SendTransactionRequest{transaction:canonical::encode(&tx)};
+        assert_eq!(client.send_transaction(send.clone()).await.unwrap().into_inner().hash,tx.tx_hash_hex());
+        assert_eq!(client.send_transaction(send).await.unwrap_err().code(),Code::InvalidArgument);
+        let garbage=proto::SendTransactionRequest{transaction:vec![1,2,3]};
+        assert_eq!(client.send_transaction(garbage).await.unwrap_err().code(),Code::InvalidArgument);
+        let status=client.get_transaction(proto::GetTransactionRequest{hash:tx.tx_hash_hex()}).await.unwrap().into_inner();
+        assert_eq!(status.status,Some(TxState::Pending(canonical::encode(&tx))));
+        assert_eq!(client.get_mempool(proto::GetMempoolRequest{}).await.unwrap().into_inner().transactions,1);
+
+        let denied=GrpcServer::start(
+            GrpcConfig{
+                listen_addr:"127.0.0.1:0".parse().unwrap(),
+                auth:AuthConfig{anonymous:Role::Public,..AuthConfig::default()},
+                ..GrpcConfig::default()
+            },
+            ctx.clone(),
+        )
+        .await
+        .unwrap();
+        let mut anonymous=NodeClient::connect(format!("http://{}",denied.local_addr())).await.unwrap();
+        let send=proto::SendTransactionRequest{transaction:canonical::encode(&tx)};
+        assert_eq!(anonymous.send_transaction(send).await.unwrap_err().code(),Code::PermissionDenied);
+        let mut bad_key=Request::new(proto::GetHeightRequest{});
+        bad_key.metadata_mut().insert("authorization","Bearer nope".parse().unwrap());
+        assert_eq!(anonymous.get_height(bad_key).await.unwrap_err().code(),Code::Unauthenticated);
+
+        let mut blocks=client.subscribe_blocks(proto::SubscribeBlocksRequest{finalized:false}).await.unwrap().into_inner();
+        let next=Arc::new(Block::new(1,"block 1".to_string(),genesis.hash.clone()));
+        ctx.publish(NodeEvent::Finalized(Arc::new(genesis)));
+        ctx.publish(NodeEvent::NewBlock(next.clone()));
+        assert_eq!(blocks.message().await.unwrap(),Some(proto::Block::from(next.as_ref())));
+    }
+}
@@ -0,0 +1,325 @@
+// src/rpc/jsonrpc.rs
+
+//! JSON-RPC 2.0 over HTTP
+//! - Methods: `chain_getHeight`, `chain_getBlock`, `chain_getBlockByHash`, `state_getBalance`,
+//!   `state_getNonce`, `tx_send`, `tx_get`, `consensus_validators`
+//! - Paged listings (`page::Page`, taking `cursor` and `limit`): `chain_listBlocks(from, to)`,
+//!   `tx_listByAddress(address)`, `consensus_listValidators()`
+//! - Mempool: `mempool_status` (size and gas price histogram), `mempool_pending(sender?)`
+//!   (paged) and `mempool_isPending(hash)`
+//! - `fee_estimate(priority?, gas?)`: gas price (and, given `gas`, fee) for `low`, `medium`
+//!   (the default) or `high` priority, see `fee`
+//! - Admin (see `admin`): `admin_peers`, `admin_bannedPeers`, `admin_addPeer(addr)`,
+//!   `admin_removePeer(peer)`, `admin_banPeer(peer, seconds)`, `admin_unbanPeer(peer)`,
+//!   `admin_mempool`, `admin_flushMempool`, `admin_snapshot`, `admin_rotateLogs` and
+//!   `admin_setTxAcceptance(accept)`
+//! - Params are positional (`[...]`) or named (`{...}`); unknown blocks and transactions
+//!   answer `null` rather than an error
+//! - Standard error codes, plus `TX_REJECTED` carrying the mempool's reason
+//! - Methods above the caller's role fail with `UNAUTHORIZED` (see `auth::method_role`)
+//! - Notifications (no `id`) are executed and answered with an empty 204
+//! - Batches (arrays of up to `MAX_BATCH_SIZE` requests) are answered with an array in the
+//!   same order, notifications left out; their reads share one `Reads` scope, so hundreds
+//!   of block or balance lookups take the chain lock and a state snapshot once
+
+use super::auth::{AuthError,Role,authorize,method_role};
+use super::fee::Priority;
+use super::page::PageRequest;
+use super::{Reads,RpcContext,RpcError};
+use axum::body::Bytes;
+use axum::extract::{Extension,State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse,Json,Response};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize,Serialize};
+use serde_json::{Map,Value,json};
+
+pub const PARSE_ERROR:i64=-32700;
+pub const INVALID_REQUEST:i64=-32600;
+pub const METHOD_NOT_FOUND:i64=-32601;
+pub const INVALID_PARAMS:i64=-32602;
+pub const INTERNAL_ERROR:i64=-32603;
+/// Submitted transaction refused by the mempool
+pub const TX_REJECTED:i64=-32000;
+/// Method needs a higher role than the caller's
+pub const UNAUTHORIZED:i64=-32001;
+
+/// Requests one batch may hold
+pub const MAX_BATCH_SIZE:usize=1000;
+
+/// JSON-RPC error object
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq)]
+pub struct ErrorObject{
+    pub code:i64,
+    pub message:String,
+    #[serde(default,skip_serializing_if="Option::is_none")]
+    pub data:Option<Value>,
+}
+
+impl ErrorObject{
+    pub fn new(code:i64,message:impl Into<String>)->Self{
+        ErrorObject{code,message:message.into(),data:None}
+    }
+}
+
+impl From<RpcError> for ErrorObject{
+    fn from(error:RpcError)->Self{
+        match error{
+            RpcError::Rejected(reason)=>ErrorObject::new(TX_REJECTED,reason.to_string()),
+            RpcError::SubmissionsPaused=>ErrorObject::new(TX_REJECTED,RpcError::SubmissionsPaused.to_string()),
+            RpcError::InvalidCursor|RpcError::InvalidPeerId=>ErrorObject::new(INVALID_PARAMS,error.to_string()),
+            other=>ErrorObject::new(INTERNAL_ERROR,other.to_string()),
+        }
+    }
+}
+
+impl From<AuthError> for ErrorObject{
+    fn from(error:AuthError)->Self{
+        ErrorObject::new(UNAUTHORIZED,error.to_string())
+    }
+}
+
+/// `params` of a call, looked up by position or by name
+pub(super) struct Params<'a>(Option<&'a Value>);
+
+impl Params<'_>{
+    pub(super) fn get<T:DeserializeOwned>(&self,position:usize,name:&str)->Result<T,ErrorObject>{
+        let value=match self.0{
+            Some(Value::Array(params))=>params.get(position),
+            Some(Value::Object(params))=>params.get(name),
+            _=>None,
+        };
+        serde_json::from_value(value.cloned().unwrap_or(Value::Null))
+        .map_err(|e| ErrorObject::new(INVALID_PARAMS,format!("{name}: {e}")))
+    }
+}
+
+fn call(reads:&mut Reads,role:Role,method:&str,params:Params)->Result<Value,ErrorObject>{
+    authorize(role,method_role(method))?;
+    let value=match method{
+        "chain_getHeight"=>json!(reads.height()),
+        "chain_getBlock"=>json!(reads.block(params.get(0,"height")?)),
+        "chain_getBlockByHash"=>json!(reads.block_by_hash(&params.get::<String>(0,"hash")?)),
+        "state_getBalance"=>json!(reads.balance(&params.get::<String>(0,"address")?)),
+        "state_getNonce"=>json!(reads.nonce(&params.get::<String>(0,"address")?)),
+        "tx_send"=>json!(reads.ctx.send_transaction(params.get(0,"transaction")?)?),
+        "tx_get"=>json!(reads.transaction(&params.get::<String>(0,"hash")?)),
+        "consensus_validators"=>json!(reads.validators()),
+        "chain_listBlocks"=>{
+            let page=PageRequest{cursor:params.get(2,"cursor")?,limit:params.get(3,"limit")?};
+            json!(reads.list_blocks(params.get::<Option<u64>>(0,"from")?.unwrap_or(0),params.get(1,"to")?,&page)?)
+        }
+        "tx_listByAddress"=>{
+            let page=PageRequest{cursor:params.get(1,"cursor")?,limit:params.get(2,"limit")?};
+            json!(reads.address_transactions(&params.get::<String>(0,"address")?,&page)?)
+        }
+        "mempool_status"=>json!(reads.mempool()),
+        "mempool_pending"=>{
+            let page=PageRequest{cursor:params.get(1,"cursor")?,limit:params.get(2,"limit")?};
+            json!(reads.pooled_transactions(params.get::<Option<String>>(0,"sender")?.as_deref(),&page)?)
+        }
+        "mempool_isPending"=>json!(reads.is_pending(&params.get::<String>(0,"hash")?)),
+        "fee_estimate"=>{
+            let priority=params.get::<Option<Priority>>(0,"priority")?.unwrap_or_default();
+            json!(reads.fee_estimate(priority,params.get(1,"gas")?))
+        }
+        "consensus_listValidators"=>{
+            let page=PageRequest{cursor:params.get(0,"cursor")?,limit:params.get(1,"limit")?};
+            json!(reads.list_validators(&page)?)
+        }
+        "admin_peers"=>json!(reads.ctx.peers()?),
+        "admin_bannedPeers"=>json!(reads.ctx.banned_peers()?),
+        "admin_addPeer"=>json!(reads.ctx.add_peer(params.get(0,"addr")?)?),
+        "admin_removePeer"=>json!(reads.ctx.remove_peer(&params.get::<String>(0,"peer")?)?),
+        "admin_banPeer"=>json!(reads.ctx.ban_peer(&params.get::<String>(0,"peer")?,params.get(1,"seconds")?)?),
+        "admin_unbanPeer"=>json!(reads.ctx.unban_peer(&params.get::<String>(0,"peer")?)?),
+        "admin_mempool"=>json!(reads.ctx.mempool_content()),
+        "admin_flushMempool"=>json!(reads.ctx.flush_mempool()),
+        "admin_snapshot"=>json!(reads.take_snapshot()?),
+        "admin_rotateLogs"=>json!(reads.ctx.rotate_logs()?),
+        "admin_setTxAcceptance"=>json!(reads.ctx.set_accepting_transactions(params.get(0,"accept")?)),
+        _=>return Err(ErrorObject::new(METHOD_NOT_FOUND,format!("method not found: {method}"))),
+    };
+    Ok(value)
+}
+
+pub(super) fn response(id:Value,outcome:Result<Value,ErrorObject>)->Value{
+    match outcome{
+        Ok(result)=>json!({"jsonrpc":"2.0","result":result,"id":id}),
+        Err(error)=>json!({"jsonrpc":"2.0","error":error,"id":id}),
+    }
+}
+
+/// Answer one request object from a caller with `role`; `None` for a notification
+pub fn handle_request(ctx:&RpcContext,role:Role,request:Value)->Option<Value>{
+    answer(&mut ctx.reads(),role,request)
+}
+
+fn answer(reads:&mut Reads,role:Role,request:Value)->Option<Value>{
+    let Value::Object(request)=request else{
+        return Some(response(Value::Null,Err(ErrorObject::new(INVALID_REQUEST,"request must be an object"))))
+    };
+    let id=request.get("id").cloned();
+    match parse(&request){
+        Ok((method,params))=>{
+            let outcome=call(reads,role,method,params);
+            id.map(|id| response(id,outcome))
+        }
+        Err(error)=>Some(response(id.unwrap_or(Value::Null),Err(error))),
+    }
+}
+
+pub(super) fn parse(request:&Map<String,Value>)->Result<(&str,Params<'_>),ErrorObject>{
+    if request.get("jsonrpc").and_then(Value::as_str)!=Some("2.0"){
+        return Err(ErrorObject::new(INVALID_REQUEST,"jsonrpc must be \"2.0\""))
+    }
+    if let Some(id)=request.get("id") && !(id.is_string() || id.is_number() || id.is_null()){
+        return Err(ErrorObject::new(INVALID_REQUEST,"id must be a string, number or null"))
+    }
+    let Some(method)=request.get("method").and_then(Value::as_str) else{
+        return Err(ErrorObject::new(INVALID_REQUEST,"method must be a string"))
+    };
+    let params=request.get("params");
+    if params.is_some_and(|params| !(params.is_array() || params.is_object())){
+        return Err(ErrorObject::new(INVALID_REQUEST,"params must be an array or an object"))
+    }
+    Ok((method,Params(params)))
+}
+
+/// Answer a request body from a caller with `role`; `None` when there is nothing to send back
+pub fn handle(ctx:&RpcContext,role:Role,body:&[u8])->Option<Value>{
+    let request=match serde_json::from_slice::<Value>(body){
+        Ok(request)=>request,
+        Err(e)=>return Some(response(Value::Null,Err(ErrorObject::new(PARSE_ERROR,e.to_string())))),
+    };
+    match request{
+        Value::Array(batch)=>handle_batch(ctx,role,batch),
+        request=>handle_request(ctx,role,request),
+    }
+}
+
+fn handle_batch(ctx:&RpcContext,role:Role,batch:Vec<Value>)->Option<Value>{
+    if batch.is_empty(){
+        return Some(response(Value::Null,Err(ErrorObject::new(INVALID_REQUEST,"empty batch"))))
+    }
+    if batch.len()>MAX_BATCH_SIZE{
+        let message=format!("batch holds {} requests, at most {MAX_BATCH_SIZE} allowed",batch.len());
+        return Some(response(Value::Null,Err(ErrorObject::new(INVALID_REQUEST,message))))
+    }
+    let mut reads=ctx.reads();
+    let responses:Vec<Value>=batch.into_iter().filter_map(|request| answer(&mut reads,role,request)).collect();
+    (!responses.is_empty()).then_some(Value::Array(responses))
+}
+
+pub(super) async fn serve(State(ctx):State<RpcContext>,Extension(role):Extension<Role>,body:Bytes)->Response{
+    match handle(&ctx,role,&body){
+        Some(response)=>Json(response).into_response(),
+        None=>StatusCode::NO_CONTENT.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{context,http};
+    use super::super::{RpcConfig,RpcServer,TxStatus};
+    use crate::transaction::{SignedTransaction,Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+
+    fn rpc(ctx:&RpcContext,method:&str,params:Value)->Value{
+        handle(ctx,Role::Wallet,json!({"jsonrpc":"2.0","method":method,"params":params,"id":7}).to_string().as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_queries_and_submission(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let ctx=context(&sender);
+        let genesis=ctx.block(0).unwrap();
+
+        assert_eq!(rpc(&ctx,"chain_getHeight",json!([])),json!({"jsonrpc":"2.0","result":0,"id":7}));
+        assert_eq!(rpc(&ctx,"chain_getBlock",json!([0]))["result"]["hash"],json!(genesis.hash));
+        assert_eq!(rpc(&ctx,"chain_getBlock",json!({"height":5}))["result"],Value::Null);
+        assert_eq!(rpc(&ctx,"chain_getBlockByHash",json!([genesis.hash]))["result"]["index"],0);
+        assert_eq!(rpc(&ctx,"state_getBalance",json!({"address":sender}))["result"],1_000_000);
+        assert_eq!(rpc(&ctx,"consensus_validators",json!([]))["result"],json!([]));
+        assert_eq!(rpc(&ctx,"chain_listBlocks",json!({"limit":5}))["result"]["items"][0]["hash"],json!(genesis.hash));
+        assert_eq!(rpc(&ctx,"tx_listByAddress",json!([sender]))["result"],json!({"items":[],"next_cursor":null}));
+        assert_eq!(rpc(&ctx,"consensus_listValidators",json!({"cursor":"?"}))["error"]["code"],INVALID_PARAMS);
+
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(sender.clone(),"bob".to_string(),10,1_000,0,None),&kp);
+        let sent=rpc(&ctx,"tx_send",json!([tx]));
+        let hash=sent["result"].as_str().unwrap();
+        assert_eq!(hash,tx.tx_hash_hex());
+        let status:TxStatus=serde_json::from_value(rpc(&ctx,"tx_get",json!([hash]))["result"].clone()).unwrap();
+        assert_eq!(status,TxStatus::Pending{transaction:Box::new(tx.clone())});
+        assert_eq!(rpc(&ctx,"tx_send",json!([tx]))["error"]["code"],TX_REJECTED);
+        assert_eq!(rpc(&ctx,"mempool_isPending",json!([hash]))["result"],true);
+        assert_eq!(rpc(&ctx,"mempool_pending",json!({"sender":sender}))["result"]["items"][0]["hash"],json!(hash));
+        assert_eq!(rpc(&ctx,"mempool_status",json!([]))["result"]["transactions"],1);
+        assert_eq!(rpc(&ctx,"fee_estimate",json!({"priority":"high"}))["result"]["target_blocks"],1);
+        assert_eq!(rpc(&ctx,"fee_estimate",json!(["urgent"]))["error"]["code"],INVALID_PARAMS);
+        assert_eq!(rpc(&ctx,"state_getNonce",json!([sender]))["result"],0);
+    }
+
+    #[test]
+    fn test_protocol_errors(){
+        let ctx=context("alice");
+        let code=|body:&str| handle(&ctx,Role::Wallet,body.as_bytes()).unwrap()["error"]["code"].clone();
+        assert_eq!(code("{not json"),PARSE_ERROR);
+        assert_eq!(code(r#"{"method":"chain_getHeight","id":1}"#),INVALID_REQUEST);
+        assert_eq!(code(r#"{"jsonrpc":"2.0","method":"chain_getHeight","params":3,"id":1}"#),INVALID_REQUEST);
+        assert_eq!(code(r#"{"jsonrpc":"2.0","method":"eth_call","id":1}"#),METHOD_NOT_FOUND);
+        assert_eq!(code(r#"{"jsonrpc":"2.0","method":"chain_getBlock","params":["zero"],"id":1}"#),INVALID_PARAMS);
+        assert_eq!(code(r#"{"jsonrpc":"2.0","method":"tx_send","params":[{}],"id":1}"#),INVALID_PARAMS);
+
+        // a notification is executed but not answered, even when it fails
+        assert_eq!(handle(&ctx,Role::Public,br#"{"jsonrpc":"2.0","method":"eth_call"}"#),None);
+        // an explicit null id is a request
+        assert_eq!(handle(&ctx,Role::Public,br#"{"jsonrpc":"2.0","method":"chain_getHeight","id":null}"#).unwrap()["result"],0);
+        // the role is checked before params
+        let public=handle(&ctx,Role::Public,br#"{"jsonrpc":"2.0","method":"tx_send","params":[{}],"id":1}"#).unwrap();
+        assert_eq!(public["error"],json!({"code":UNAUTHORIZED,"message":"requires the wallet role"}));
+    }
+
+    #[test]
+    fn test_batches(){
+        let ctx=context("alice");
+        let batch=json!([
+            {"jsonrpc":"2.0","method":"state_getBalance","params":["alice"],"id":1},
+            {"jsonrpc":"2.0","method":"chain_getHeight"},
+            1,
+            {"jsonrpc":"2.0","method":"eth_call","id":"x"},
+            [{"jsonrpc":"2.0","method":"chain_getHeight","id":2}],
+            {"jsonrpc":"2.0","method":"chain_getHeight","id":3},
+        ]);
+        let responses=handle(&ctx,Role::Public,batch.to_string().as_bytes()).unwrap();
+        let responses=responses.as_array().unwrap();
+        assert_eq!(responses.len(),5);
+        assert_eq!(responses[0],json!({"jsonrpc":"2.0","result":1_000_000,"id":1}));
+        assert_eq!((responses[1]["error"]["code"].clone(),responses[1]["id"].clone()),(json!(INVALID_REQUEST),Value::Null));
+        assert_eq!((responses[2]["error"]["code"].clone(),responses[2]["id"].clone()),(json!(METHOD_NOT_FOUND),json!("x")));
+        // batches don't nest
+        assert_eq!(responses[3]["error"]["code"],INVALID_REQUEST);
+        assert_eq!(responses[4],json!({"jsonrpc":"2.0","result":0,"id":3}));
+
+        assert_eq!(handle(&ctx,Role::Public,b"[]").unwrap()["error"]["code"],INVALID_REQUEST);
+        assert_eq!(handle(&ctx,Role::Public,br#"[{"jsonrpc":"2.0","method":"chain_getHeight"}]"#),None);
+        let oversized=Value::Array(vec![json!({"jsonrpc":"2.0","method":"chain_getHeight","id":1});MAX_BATCH_SIZE+1]);
+        assert_eq!(handle(&ctx,Role::Public,oversized.to_string().as_bytes()).unwrap()["error"]["code"],INVALID_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_served_over_http(){
+        let config=RpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),..RpcConfig::default()};
+        let server=RpcServer::start(config,context("alice")).await.unwrap();
+        let (status,body)=http(server.local_addr(),"POST","/",r#"{"jsonrpc":"2.0","method":"chain_getHeight","id":"a"}"#).await;
+        assert_eq!((status,serde_json::from_str::<Value>(&body).unwrap()),(200,json!({"jsonrpc":"2.0","result":0,"id":"a"})));
+        let (status,body)=http(server.local_addr(),"POST","/",r#"{"jsonrpc":"2.0","method":"chain_getHeight"}"#).await;
+        assert_eq!((status,body.as_str()),(204,""));
+        let (status,body)=http(server.local_addr(),"POST","/",r#"[{"jsonrpc":"2.0","method":"chain_getHeight","id":1},{"jsonrpc":"2.0","method":"chain_getHeight","id":2}]"#).await;
+        assert_eq!((status,serde_json::from_str::<Value>(&body).unwrap()),(200,json!([
+            {"jsonrpc":"2.0","result":0,"id":1},
+            {"jsonrpc":"2.0","result":0,"id":2},
+        ])));
+    }
+}
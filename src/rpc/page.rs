@@ -0,0 +1,111 @@
+// src/rpc/page.rs
+
+//! Cursor pagination for list endpoints
+//! - A request carries an optional `cursor` and a `limit` (default `DEFAULT_PAGE_SIZE`,
+//!   clamped to `1..=MAX_PAGE_SIZE`); a `Page` returns the items and, when more follow, the
+//!   `next_cursor` to pass back
+//! - Cursors are opaque to clients and name the last item served, not an offset, so
+//!   blocks or transactions added meanwhile neither repeat nor skip entries
+
+use super::RpcError;
+use base64::{Engine as _,engine::general_purpose::URL_SAFE_NO_PAD};
+use serde::{Deserialize,Serialize};
+use std::fmt;
+use std::str::FromStr;
+use utoipa::{IntoParams,ToSchema};
+
+pub const DEFAULT_PAGE_SIZE:usize=20;
+pub const MAX_PAGE_SIZE:usize=100;
+
+#[derive(Debug,Clone,Default,Serialize,Deserialize,PartialEq,Eq,IntoParams)]
+#[into_params(parameter_in=Query)]
+pub struct PageRequest{
+    /// `next_cursor` of the previous page; the first page when unset
+    #[serde(default)]
+    pub cursor:Option<String>,
+    #[serde(default)]
+    pub limit:Option<usize>,
+}
+
+impl PageRequest{
+    pub fn limit(&self)->usize{
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1,MAX_PAGE_SIZE)
+    }
+
+    /// Position the cursor names, if one was given
+    pub(super) fn after<T:FromStr>(&self)->Result<Option<T>,RpcError>{
+        let Some(cursor)=&self.cursor else{
+            return Ok(None)
+        };
+        let position=URL_SAFE_NO_PAD.decode(cursor).ok().and_then(|bytes| String::from_utf8(bytes).ok());
+        position.and_then(|position| position.parse().ok()).map(Some).ok_or(RpcError::InvalidCursor)
+    }
+}
+
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct Page<T>{
+    pub items:Vec<T>,
+    /// Cursor of the next page; `None` on the last one
+    pub next_cursor:Option<String>,
+}
+
+impl<T> Page<T>{
+    /// Page of up to `limit` of `items`, which should hold one more than `limit` when there
+    /// are more to come; `position` names an item for the cursor
+    pub(super) fn new<P:fmt::Display>(mut items:Vec<T>,limit:usize,position:impl Fn(&T)->P)->Self{
+        let next_cursor=if items.len()>limit{
+            items.truncate(limit);
+            items.last().map(|last| URL_SAFE_NO_PAD.encode(position(last).to_string()))
+        } else{
+            None
+        };
+        Page{items,next_cursor}
+    }
+
+    pub fn map<U>(self,f:impl FnMut(T)->U)->Page<U>{
+        Page{items:self.items.into_iter().map(f).collect(),next_cursor:self.next_cursor}
+    }
+}
+
+/// Where a receipt sits: block height and index in the block
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub(super) struct TxPosition(pub u64,pub usize);
+
+impl fmt::Display for TxPosition{
+    fn fmt(&self,f:&mut fmt::Formatter)->fmt::Result{
+        write!(f,"{}.{}",self.0,self.1)
+    }
+}
+
+impl FromStr for TxPosition{
+    type Err=();
+
+    fn from_str(s:&str)->Result<Self,()>{
+        let (height,index)=s.split_once('.').ok_or(())?;
+        Ok(TxPosition(height.parse().map_err(|_| ())?,index.parse().map_err(|_| ())?))
+    }
+}
+
+/// Where a pooled transaction sits in mempool listings: by sender, nonce, then hash
+#[derive(Debug,Clone,PartialEq,Eq,PartialOrd,Ord)]
+pub(super) struct PoolPosition{
+    pub sender:String,
+    pub nonce:u64,
+    pub hash:String,
+}
+
+impl fmt::Display for PoolPosition{
+    fn fmt(&self,f:&mut fmt::Formatter)->fmt::Result{
+        write!(f,"{}.{}.{}",self.sender,self.nonce,self.hash)
+    }
+}
+
+impl FromStr for PoolPosition{
+    type Err=();
+
+    fn from_str(s:&str)->Result<Self,()>{
+        let (rest,hash)=s.rsplit_once('.').ok_or(())?;
+        let (sender,nonce)=rest.rsplit_once('.').ok_or(())?;
+        Ok(PoolPosition{sender:sender.to_string(),nonce:nonce.parse().map_err(|_| ())?,hash:hash.to_string()})
+    }
+}
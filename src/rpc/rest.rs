@@ -0,0 +1,480 @@
+// src/rpc/rest.rs
+
+//! REST API for web frontends, next to JSON-RPC on the same server
+//! - `GET /blocks/{height}`, `GET /accounts/{addr}`
+//! - Paged listings (`?cursor=&limit=`, answering a `page::Page`): `GET /blocks?from=&to=`,
+//!   `GET /accounts/{addr}/transactions` (newest first) and `GET /validators`
+//! - `GET /mempool` sizes the mempool, `GET /mempool/transactions?sender=` lists it (paged)
+//!   and `GET /mempool/transactions/{hash}` answers a transaction while it's pending
+//! - `GET /fees/estimate?priority=&gas=` suggests a gas price (see `fee`)
+//! - Probes and status (see `status`): `GET /health` answers 200 while the server runs,
+//!   `GET /ready` 200 once the node is synced and 503 before, `GET /status` reports height,
+//!   peers, validator standing, version and uptime
+//! - Routes are declared with their OpenAPI description (utoipa), and the generated
+//!   document is served on `GET /api/spec` for client SDK generators
+//! - `POST /transactions` takes a signed transaction and answers 202 with its hash;
+//!   `GET /transactions/{hash}` follows it from the mempool into a block
+//! - Errors are `{"error": "..."}`: 400 for a bad path, query or cursor or a rejected
+//!   transaction, 403 for a submission below the `wallet` role, 404 for an unknown block or
+//!   transaction, 415 / 422 for a body that isn't a transaction
+
+use super::auth::{AuthError,Role,authorize,method_role};
+use super::fee::{FeeEstimate,Priority};
+use super::page::{Page,PageRequest};
+use super::status::{NodeStatus,Readiness};
+use super::{API_KEY_HEADER,AccountView,MempoolView,PooledTxView,RpcContext,RpcError,TxStatus,ValidatorView};
+use crate::block::Block;
+use crate::transaction::SignedTransaction;
+use axum::Router;
+use axum::extract::rejection::{JsonRejection,PathRejection,QueryRejection};
+use axum::extract::{Extension,Path,Query,State};
+use axum::http::StatusCode;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::{IntoResponse,Json,Response};
+use axum::routing::get;
+use serde::{Deserialize,Serialize};
+use utoipa::openapi::OpenApi as OpenApiDocument;
+use utoipa::openapi::security::{ApiKey,ApiKeyValue,HttpAuthScheme,HttpBuilder,SecurityScheme};
+use utoipa::{IntoParams,Modify,OpenApi,ToSchema};
+use utoipa_axum::router::OpenApiRouter;
+use utoipa_axum::routes;
+
+/// Where the OpenAPI document is served
+pub const SPEC_PATH:&str="/api/spec";
+
+pub struct ApiError{
+    status:StatusCode,
+    message:String,
+}
+
+impl ApiError{
+    fn not_found(what:&str)->Self{
+        ApiError{status:StatusCode::NOT_FOUND,message:format!("{what} not found")}
+    }
+}
+
+/// Body of every error response
+#[derive(Serialize,ToSchema)]
+struct ErrorBody{
+    error:String,
+}
+
+impl IntoResponse for ApiError{
+    fn into_response(self)->Response{
+        (self.status,Json(ErrorBody{error:self.message})).into_response()
+    }
+}
+
+impl From<RpcError> for ApiError{
+    fn from(error:RpcError)->Self{
+        let status=match error{
+            RpcError::Rejected(_) | RpcError::InvalidCursor=>StatusCode::BAD_REQUEST,
+            RpcError::SubmissionsPaused=>StatusCode::SERVICE_UNAVAILABLE,
+            _=>StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError{status,message:error.to_string()}
+    }
+}
+
+impl From<AuthError> for ApiError{
+    fn from(error:AuthError)->Self{
+        ApiError{status:StatusCode::FORBIDDEN,message:error.to_string()}
+    }
+}
+
+impl From<JsonRejection> for ApiError{
+    fn from(rejection:JsonRejection)->Self{
+        ApiError{status:rejection.status(),message:rejection.body_text()}
+    }
+}
+
+impl From<PathRejection> for ApiError{
+    fn from(rejection:PathRejection)->Self{
+        ApiError{status:rejection.status(),message:rejection.body_text()}
+    }
+}
+
+impl From<QueryRejection> for ApiError{
+    fn from(rejection:QueryRejection)->Self{
+        ApiError{status:rejection.status(),message:rejection.body_text()}
+    }
+}
+
+/// Query of `GET /mempool/transactions`
+#[derive(Deserialize,IntoParams)]
+#[into_params(parameter_in=Query)]
+struct PoolFilter{
+    /// Only this sender's transactions
+    sender:Option<String>,
+    cursor:Option<String>,
+    limit:Option<usize>,
+}
+
+/// Query of `GET /fees/estimate`
+#[derive(Deserialize,IntoParams)]
+#[into_params(parameter_in=Query)]
+struct FeeQuery{
+    /// `medium` when unset
+    #[serde(default)]
+    #[param(inline)]
+    priority:Priority,
+    /// Gas of the transaction to price a fee for
+    gas:Option<u64>,
+}
+
+/// Query of `GET /blocks`
+#[derive(Deserialize,IntoParams)]
+#[into_params(parameter_in=Query)]
+struct BlockRange{
+    /// First height, 0 when unset
+    #[serde(default)]
+    from:u64,
+    /// Last height, the tip when unset
+    to:Option<u64>,
+    cursor:Option<String>,
+    limit:Option<usize>,
+}
+
+/// Answer of `GET /health`
+#[derive(Serialize,ToSchema)]
+struct Health{
+    status:&'static str,
+}
+
+/// Answer to an accepted submission
+#[derive(Serialize,ToSchema)]
+struct Submitted{
+    hash:String,
+}
+
+#[derive(OpenApi)]
+#[openapi(
+    info(title="NetChain node API",description="REST API of a NetChain node. Listings are paged: pass a page's `next_cursor` back as `cursor`."),
+    modifiers(&Credentials),
+    tags(
+        (name="chain",description="Blocks"),
+        (name="accounts",description="Balances and account history"),
+        (name="transactions",description="Submission and lookup"),
+        (name="mempool",description="Transactions waiting for a block"),
+        (name="fees",description="Gas price suggestions"),
+        (name="consensus",description="Validator set"),
+        (name="node",description="Health probes and node status"),
+    ),
+)]
+struct ApiDoc;
+
+/// Declares the credentials `auth` accepts
+struct Credentials;
+
+impl Modify for Credentials{
+    fn modify(&self,openapi:&mut OpenApiDocument){
+        let components=openapi.components.get_or_insert_with(Default::default);
+        components.add_security_scheme("bearer",SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).description(Some("API key or HS256 JWT")).build()));
+        components.add_security_scheme("api_key",SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new(API_KEY_HEADER))));
+    }
+}
+
+fn api()->OpenApiRouter<RpcContext>{
+    OpenApiRouter::with_openapi(ApiDoc::openapi())
+    .routes(routes!(blocks))
+    .routes(routes!(block))
+    .routes(routes!(account))
+    .routes(routes!(account_transactions))
+    .routes(routes!(send_transaction))
+    .routes(routes!(transaction))
+    .routes(routes!(validators))
+    .routes(routes!(fee_estimate))
+    .routes(routes!(mempool))
+    .routes(routes!(pooled_transactions))
+    .routes(routes!(pooled_transaction))
+    .routes(routes!(health))
+    .routes(routes!(ready))
+    .routes(routes!(status))
+}
+
+/// OpenAPI document of the routes below, as served on `SPEC_PATH`
+pub fn openapi()->OpenApiDocument{
+    api().into_openapi()
+}
+
+pub(super) fn routes()->Router<RpcContext>{
+    let (router,spec)=api().split_for_parts();
+    let spec=spec.to_json().expect("openapi document serializes");
+    router.route(SPEC_PATH,get(move || async move {([(CONTENT_TYPE,"application/json")],spec)}))
+}
+
+/// Block at a height
+#[utoipa::path(
+    get,path="/blocks/{height}",tag="chain",
+    params(("height"=u64,Path,description="Block height")),
+    responses((status=200,body=Block),(status=400,body=ErrorBody),(status=404,body=ErrorBody)),
+)]
+async fn block(State(ctx):State<RpcContext>,height:Result<Path<u64>,PathRejection>)->Result<Json<Block>,ApiError>{
+    let Path(height)=height?;
+    ctx.block(height).map(Json).ok_or_else(|| ApiError::not_found("block"))
+}
+
+/// Blocks in a height range, lowest first
+#[utoipa::path(
+    get,path="/blocks",tag="chain",
+    params(BlockRange),
+    responses((status=200,body=Page<Block>),(status=400,body=ErrorBody)),
+)]
+async fn blocks(State(ctx):State<RpcContext>,range:Result<Query<BlockRange>,QueryRejection>)->Result<Json<Page<Block>>,ApiError>{
+    let Query(range)=range?;
+    let page=PageRequest{cursor:range.cursor,limit:range.limit};
+    Ok(Json(ctx.list_blocks(range.from,range.to,&page)?))
+}
+
+/// Balance, nonce and stake of an account
+#[utoipa::path(
+    get,path="/accounts/{addr}",tag="accounts",
+    params(("addr"=String,Path,description="Account address")),
+    responses((status=200,body=AccountView)),
+)]
+async fn account(State(ctx):State<RpcContext>,Path(addr):Path<String>)->Json<AccountView>{
+    Json(ctx.account(&addr))
+}
+
+/// Included transactions an account sent or was paid by, newest first
+#[utoipa::path(
+    get,path="/accounts/{addr}/transactions",tag="accounts",
+    params(("addr"=String,Path,description="Account address"),PageRequest),
+    responses((status=200,body=Page<TxStatus>),(status=400,body=ErrorBody)),
+)]
+async fn account_transactions(
+    State(ctx):State<RpcContext>,
+    Path(addr):Path<String>,
+    page:Result<Query<PageRequest>,QueryRejection>,
+)->Result<Json<Page<TxStatus>>,ApiError>{
+    let Query(page)=page?;
+    Ok(Json(ctx.address_transactions(&addr,&page)?))
+}
+
+/// Submit a signed transaction to the mempool
+#[utoipa::path(
+    post,path="/transactions",tag="transactions",
+    request_body=SignedTransaction,
+    security((),("bearer"=[]),("api_key"=[])),
+    responses(
+        (status=202,body=Submitted),
+        (status=400,description="Rejected by the mempool",body=ErrorBody),
+        (status=403,description="Caller below the wallet role",body=ErrorBody),
+        (status=422,description="Not a transaction",body=ErrorBody),
+        (status=503,description="Submission paused by an admin",body=ErrorBody),
+    ),
+)]
+async fn send_transaction(
+    State(ctx):State<RpcContext>,
+    Extension(role):Extension<Role>,
+    tx:Result<Json<SignedTransaction>,JsonRejection>,
+)->Result<(StatusCode,Json<Submitted>),ApiError>{
+    authorize(role,method_role("tx_send"))?;
+    let Json(tx)=tx?;
+    let hash=ctx.send_transaction(tx)?;
+    Ok((StatusCode::ACCEPTED,Json(Submitted{hash})))
+}
+
+/// Pooled or included transaction
+#[utoipa::path(
+    get,path="/transactions/{hash}",tag="transactions",
+    params(("hash"=String,Path,description="Transaction hash, hex")),
+    responses((status=200,body=TxStatus),(status=404,body=ErrorBody)),
+)]
+async fn transaction(State(ctx):State<RpcContext>,Path(hash):Path<String>)->Result<Json<TxStatus>,ApiError>{
+    ctx.transaction(&hash).map(Json).ok_or_else(|| ApiError::not_found("transaction"))
+}
+
+/// Gas price likely to confirm within the priority's target
+#[utoipa::path(
+    get,path="/fees/estimate",tag="fees",
+    params(FeeQuery),
+    responses((status=200,body=FeeEstimate),(status=400,body=ErrorBody)),
+)]
+async fn fee_estimate(State(ctx):State<RpcContext>,query:Result<Query<FeeQuery>,QueryRejection>)->Result<Json<FeeEstimate>,ApiError>{
+    let Query(query)=query?;
+    Ok(Json(ctx.fee_estimate(query.priority,query.gas)))
+}
+
+/// Mempool size and gas price histogram
+#[utoipa::path(get,path="/mempool",tag="mempool",responses((status=200,body=MempoolView)))]
+async fn mempool(State(ctx):State<RpcContext>)->Json<MempoolView>{
+    Json(ctx.mempool())
+}
+
+/// Pooled transactions by sender and nonce
+#[utoipa::path(
+    get,path="/mempool/transactions",tag="mempool",
+    params(PoolFilter),
+    responses((status=200,body=Page<PooledTxView>),(status=400,body=ErrorBody)),
+)]
+async fn pooled_transactions(
+    State(ctx):State<RpcContext>,
+    filter:Result<Query<PoolFilter>,QueryRejection>,
+)->Result<Json<Page<PooledTxView>>,ApiError>{
+    let Query(filter)=filter?;
+    let page=PageRequest{cursor:filter.cursor,limit:filter.limit};
+    Ok(Json(ctx.pooled_transactions(filter.sender.as_deref(),&page)?))
+}
+
+/// A transaction while it's pending
+#[utoipa::path(
+    get,path="/mempool/transactions/{hash}",tag="mempool",
+    params(("hash"=String,Path,description="Transaction hash, hex")),
+    responses((status=200,body=TxStatus),(status=404,body=ErrorBody)),
+)]
+async fn pooled_transaction(State(ctx):State<RpcContext>,Path(hash):Path<String>)->Result<Json<TxStatus>,ApiError>{
+    match ctx.transaction(&hash){
+        Some(status@TxStatus::Pending{..})=>Ok(Json(status)),
+        _=>Err(ApiError::not_found("pending transaction")),
+    }
+}
+
+/// Registered validators in address order
+#[utoipa::path(
+    get,path="/validators",tag="consensus",
+    params(PageRequest),
+    responses((status=200,body=Page<ValidatorView>),(status=400,body=ErrorBody)),
+)]
+async fn validators(State(ctx):State<RpcContext>,page:Result<Query<PageRequest>,QueryRejection>)->Result<Json<Page<ValidatorView>>,ApiError>{
+    let Query(page)=page?;
+    Ok(Json(ctx.list_validators(&page)?))
+}
+
+/// Liveness probe
+#[utoipa::path(get,path="/health",tag="node",responses((status=200,body=Health)))]
+async fn health()->Json<Health>{
+    Json(Health{status:"ok"})
+}
+
+/// Readiness probe: whether the chain is close enough to the best known height
+#[utoipa::path(
+    get,path="/ready",tag="node",
+    responses((status=200,body=Readiness),(status=503,description="Still syncing",body=Readiness)),
+)]
+async fn ready(State(ctx):State<RpcContext>)->(StatusCode,Json<Readiness>){
+    let readiness=ctx.readiness();
+    let status=if readiness.ready{StatusCode::OK} else{StatusCode::SERVICE_UNAVAILABLE};
+    (status,Json(readiness))
+}
+
+/// Height, peers, validator standing, version and uptime
+#[utoipa::path(get,path="/status",tag="node",responses((status=200,body=NodeStatus)))]
+async fn status(State(ctx):State<RpcContext>)->Json<NodeStatus>{
+    Json(ctx.status())
+}
+
+#[cfg(test)]
+mod tests{
+    use super::super::tests::{context,http};
+    use super::super::{RpcConfig,RpcServer};
+    use super::{SPEC_PATH,openapi};
+    use crate::transaction::{SignedTransaction,Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+    use serde_json::{Value,json};
+
+    /// Every `$ref` in `value`
+    fn refs<'a>(value:&'a Value,found:&mut Vec<&'a str>){
+        match value{
+            Value::Object(fields)=>{
+                for (key,field) in fields{
+                    match (key.as_str(),field){
+                        ("$ref",Value::String(target))=>found.push(target),
+                        _=>refs(field,found),
+                    }
+                }
+            }
+            Value::Array(items)=>items.iter().for_each(|item| refs(item,found)),
+            _=>{}
+        }
+    }
+
+    #[tokio::test]
+    async fn test_openapi_spec_covers_routes(){
+        let spec:Value=serde_json::from_str(&openapi().to_json().unwrap()).unwrap();
+        let mut paths:Vec<&str>=spec["paths"].as_object().unwrap().keys().map(String::as_str).collect();
+        paths.sort_unstable();
+        assert_eq!(paths,vec![
+            "/accounts/{addr}",
+            "/accounts/{addr}/transactions",
+            "/blocks",
+            "/blocks/{height}",
+            "/fees/estimate",
+            "/health",
+            "/mempool",
+            "/mempool/transactions",
+            "/mempool/transactions/{hash}",
+            "/ready",
+            "/status",
+            "/transactions",
+            "/transactions/{hash}",
+            "/validators",
+        ]);
+        assert!(spec["paths"]["/transactions"]["post"]["requestBody"].is_object());
+        let mut found=Vec::new();
+        refs(&spec,&mut found);
+        assert!(found.contains(&"#/components/schemas/SignedTransaction"));
+        for target in found{
+            let name=target.strip_prefix("#/components/schemas/").unwrap();
+            assert!(spec["components"]["schemas"][name].is_object(),"dangling {target}");
+        }
+
+        let config=RpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),..RpcConfig::default()};
+        let server=RpcServer::start(config,context("alice")).await.unwrap();
+        let (status,body)=http(server.local_addr(),"GET",SPEC_PATH,"").await;
+        assert_eq!((status,serde_json::from_str::<Value>(&body).unwrap()),(200,spec));
+    }
+
+    #[tokio::test]
+    async fn test_rest_endpoints(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let ctx=context(&sender);
+        let genesis=ctx.block(0).unwrap();
+        let config=RpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),..RpcConfig::default()};
+        let server=RpcServer::start(config,ctx).await.unwrap();
+        let addr=server.local_addr();
+        let parsed=|body:String| serde_json::from_str::<Value>(&body).unwrap();
+
+        let (status,body)=http(addr,"GET","/blocks/0","").await;
+        assert_eq!((status,parsed(body)["hash"].clone()),(200,json!(genesis.hash)));
+        let (status,body)=http(addr,"GET","/blocks/3","").await;
+        assert_eq!((status,parsed(body)),(404,json!({"error":"block not found"})));
+        let (status,body)=http(addr,"GET","/blocks/latest","").await;
+        assert_eq!(status,400);
+        assert!(parsed(body)["error"].is_string());
+
+        let (status,body)=http(addr,"GET",&format!("/accounts/{sender}"),"").await;
+        assert_eq!((status,parsed(body)),(200,json!({"address":sender,"balance":1_000_000,"nonce":0,"stake":0})));
+        let (status,body)=http(addr,"GET","/blocks?from=0&limit=1","").await;
+        let blocks=parsed(body);
+        assert_eq!((status,blocks["items"][0]["hash"].clone(),blocks["next_cursor"].clone()),(200,json!(genesis.hash),Value::Null));
+        let (status,body)=http(addr,"GET","/blocks?cursor=bogus","").await;
+        assert_eq!((status,parsed(body)),(400,json!({"error":"invalid cursor"})));
+        assert_eq!(http(addr,"GET","/blocks?limit=many","").await.0,400);
+        let (status,body)=http(addr,"GET",&format!("/accounts/{sender}/transactions"),"").await;
+        assert_eq!((status,parsed(body)),(200,json!({"items":[],"next_cursor":null})));
+        let (status,body)=http(addr,"GET","/fees/estimate?priority=low&gas=100","").await;
+        assert_eq!((status,parsed(body)),(200,json!({"priority":"low","target_blocks":10,"gas_price":0,"fee":0})));
+        let (status,body)=http(addr,"GET","/validators","").await;
+        assert_eq!((status,parsed(body)),(200,json!({"items":[],"next_cursor":null})));
+
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(sender.clone(),"bob".to_string(),10,1_000,0,None),&kp);
+        let body=serde_json::to_string(&tx).unwrap();
+        let (status,response)=http(addr,"POST","/transactions",&body).await;
+        assert_eq!((status,parsed(response)),(202,json!({"hash":tx.tx_hash_hex()})));
+        let (status,response)=http(addr,"GET",&format!("/transactions/{}",tx.tx_hash_hex()),"").await;
+        assert_eq!((status,parsed(response)["status"].clone()),(200,json!("pending")));
+        let (status,response)=http(addr,"GET",&format!("/mempool/transactions/{}",tx.tx_hash_hex()),"").await;
+        assert_eq!((status,parsed(response)["status"].clone()),(200,json!("pending")));
+        assert_eq!(http(addr,"GET","/mempool/transactions/unknown","").await.0,404);
+        let (status,response)=http(addr,"GET",&format!("/mempool/transactions?sender={sender}"),"").await;
+        assert_eq!((status,parsed(response)["items"][0]["hash"].clone()),(200,json!(tx.tx_hash_hex())));
+        let (status,response)=http(addr,"GET","/mempool","").await;
+        assert_eq!((status,parsed(response)["transactions"].clone()),(200,json!(1)));
+        let (status,response)=http(addr,"POST","/transactions",&body).await;
+        assert_eq!((status,parsed(response)),(400,json!({"error":"transaction rejected: transaction already pooled"})));
+        let (status,response)=http(addr,"POST","/transactions","{}").await;
+        assert_eq!(status,422);
+        assert!(parsed(response)["error"].is_string());
+    }
+}
@@ -0,0 +1,173 @@
+// src/rpc/status.rs
+
+//! Probes and node status, for load balancers, Kubernetes and dashboards
+//! - Liveness: the server answering at all (`GET /health`)
+//! - Readiness: the chain is at most `max_ready_lag` blocks behind the best height known
+//!   from peers (`GET /ready`, 503 while further behind), so traffic only reaches synced
+//!   nodes. Peer heights come from the `SyncEngine` when the node gives it
+//!   (`RpcContext::with_sync`), else from what peers announced when they connected
+//! - `NodeStatus`: height, finality, peers, validator standing, version and uptime
+//!   (`GET /status`)
+
+use super::{Reads,RpcContext};
+use crate::network::sync::SyncEngine;
+use serde::{Deserialize,Serialize};
+use std::time::Instant;
+use utoipa::ToSchema;
+
+/// Blocks a node may trail the best known height by and still be ready
+pub const DEFAULT_MAX_READY_LAG:u64=5;
+
+/// What probes need beyond the chain and state; cheap to clone
+#[derive(Clone)]
+pub(super) struct Probe{
+    pub sync:Option<SyncEngine>,
+    /// Account this node validates as, if it does
+    pub validator:Option<String>,
+    pub max_ready_lag:u64,
+    pub started:Instant,
+}
+
+impl Default for Probe{
+    fn default()->Self{
+        Probe{sync:None,validator:None,max_ready_lag:DEFAULT_MAX_READY_LAG,started:Instant::now()}
+    }
+}
+
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct Readiness{
+    pub ready:bool,
+    pub height:u64,
+    /// Highest of our height and the heights peers report
+    pub best_known_height:u64,
+}
+
+/// Standing of the account this node validates as
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct ValidatorStatus{
+    pub address:String,
+    pub registered:bool,
+    /// Jailed at the current epoch, for double signing or a low PoI score
+    pub jailed:bool,
+    pub stake:u64,
+}
+
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct NodeStatus{
+    pub version:String,
+    pub uptime_secs:u64,
+    pub height:u64,
+    pub latest_block_hash:String,
+    pub finalized_height:Option<u64>,
+    pub best_known_height:u64,
+    pub ready:bool,
+    /// Connected peers; 0 when the node isn't networked
+    pub peers:usize,
+    /// Whether API submissions are admitted (see `admin`)
+    pub accepting_transactions:bool,
+    /// `None` unless the node validates
+    pub validator:Option<ValidatorStatus>,
+}
+
+impl RpcContext{
+    /// Chain within `max_ready_lag` blocks of the best height known from peers
+    pub fn readiness(&self)->Readiness{
+        self.reads().readiness()
+    }
+
+    pub fn status(&self)->NodeStatus{
+        self.reads().status()
+    }
+}
+
+impl Reads<'_>{
+    pub fn readiness(&mut self)->Readiness{
+        let height=self.height();
+        let probe=&self.ctx.probe;
+        let announced=match (&probe.sync,&self.ctx.admin.network){
+            (Some(sync),_)=>sync.best_peer_height(),
+            (None,Some(network))=>network.peers().iter().map(|peer| peer.height).max(),
+            (None,None)=>None,
+        };
+        let best_known_height=announced.unwrap_or(0).max(height);
+        Readiness{ready:best_known_height-height<=probe.max_ready_lag,height,best_known_height}
+    }
+
+    pub fn status(&mut self)->NodeStatus{
+        let Readiness{ready,height,best_known_height}=self.readiness();
+        let (latest_block_hash,finalized_height)={
+            let chain=self.chain();
+            (chain.last_block().hash.clone(),chain.finalized_height())
+        };
+        let validator=self.ctx.probe.validator.clone().map(|address| {
+            let state=self.state();
+            let registry=state.validators();
+            ValidatorStatus{
+                registered:registry.is_registered(&address),
+                jailed:registry.is_jailed(&address,state.current_epoch()) || registry.is_score_jailed(&address),
+                stake:state.get_stake(&address),
+                address,
+            }
+        });
+        NodeStatus{
+            version:env!("CARGO_PKG_VERSION").to_string(),
+            uptime_secs:self.ctx.probe.started.elapsed().as_secs(),
+            height,
+            latest_block_hash,
+            finalized_height,
+            best_known_height,
+            ready,
+            peers:self.ctx.admin.network.as_ref().map_or(0,|network| network.peers().len()),
+            accepting_transactions:self.ctx.accepting_transactions(),
+            validator,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::{context,http};
+    use super::super::{RpcConfig,RpcServer};
+    use crate::network::identity::NodeIdentity;
+    use crate::network::{Network,NetworkConfig};
+    use serde_json::Value;
+    use std::net::SocketAddr;
+
+    async fn network()->Network{
+        let config=NetworkConfig{listen_addr:SocketAddr::from(([127,0,0,1],0)),..NetworkConfig::default()};
+        Network::start(NodeIdentity::generate(),config).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_probes_follow_peer_heights(){
+        let ctx=context("alice").with_validator("alice".to_string());
+        assert_eq!(ctx.readiness(),Readiness{ready:true,height:0,best_known_height:0});
+        let status=ctx.status();
+        assert_eq!((status.version.as_str(),status.peers,status.accepting_transactions),(env!("CARGO_PKG_VERSION"),0,true));
+        assert_eq!(status.validator,Some(ValidatorStatus{address:"alice".to_string(),registered:false,jailed:false,stake:0}));
+
+        // a peer 10 blocks ahead
+        let (node,ahead)=(network().await,network().await);
+        ahead.set_height(10);
+        node.dial(ahead.local_addr()).await.unwrap();
+        let ctx=ctx.with_network(node);
+        assert_eq!(ctx.readiness(),Readiness{ready:false,height:0,best_known_height:10});
+        let config=RpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),..RpcConfig::default()};
+        let server=RpcServer::start(config,ctx.clone()).await.unwrap();
+        let addr=server.local_addr();
+        assert_eq!(http(addr,"GET","/health","").await,(200,r#"{"status":"ok"}"#.to_string()));
+        let (status,body)=http(addr,"GET","/ready","").await;
+        assert_eq!((status,serde_json::from_str::<Value>(&body).unwrap()["best_known_height"].as_u64()),(503,Some(10)));
+        let (status,body)=http(addr,"GET","/status","").await;
+        let body:Value=serde_json::from_str(&body).unwrap();
+        assert_eq!((status,&body["peers"],&body["ready"],&body["validator"]["registered"]),(200,&1.into(),&false.into(),&false.into()));
+
+        let ctx=ctx.with_max_ready_lag(10);
+        assert!(ctx.readiness().ready);
+        for _ in 0..5{
+            ctx.chain.lock().unwrap().add_block("block".to_string());
+        }
+        assert_eq!(ctx.with_max_ready_lag(DEFAULT_MAX_READY_LAG).readiness(),Readiness{ready:true,height:5,best_known_height:10});
+    }
+}
@@ -0,0 +1,242 @@
+// src/rpc/tls.rs
+
+//! TLS for the HTTP API
+//! - With `RpcConfig::tls` the server only speaks HTTPS (and WSS), with a PEM certificate
+//!   chain and private key read when it starts
+//! - With `client_ca_path` set, clients must present a certificate issued by that CA
+//!   (mutual TLS); otherwise any client may connect and `auth` alone decides what it may do
+//! - Handshakes run off the accept loop, each bounded by the request timeout, so a client
+//!   stalling mid-handshake holds up nobody else; at most `MAX_HANDSHAKES` run at once,
+//!   further connections wait to be accepted
+
+use super::RpcError;
+use rustls::RootCertStore;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer,PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path,PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener,TcpStream};
+use tokio::sync::{Semaphore,mpsc};
+use tokio::task::AbortHandle;
+use tokio_rustls::TlsAcceptor;
+use tokio_rustls::server::TlsStream;
+
+/// Completed handshakes waiting for the server to pick them up
+const ACCEPT_BACKLOG:usize=128;
+
+/// Handshakes in flight at once
+const MAX_HANDSHAKES:usize=256;
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+pub struct TlsConfig{
+    /// PEM certificate chain, leaf first
+    pub cert_path:PathBuf,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1)
+    pub key_path:PathBuf,
+    /// PEM CA certificates client certificates must chain to; clients need none when unset
+    pub client_ca_path:Option<PathBuf>,
+}
+
+fn tls_error(path:&Path,error:impl std::fmt::Display)->RpcError{
+    RpcError::Tls(format!("{}: {error}",path.display()))
+}
+
+fn certificates(path:&Path)->Result<Vec<CertificateDer<'static>>,RpcError>{
+    let certs=CertificateDer::pem_file_iter(path).map_err(|e| tls_error(path,e))?.collect::<Result<Vec<_>,_>>().map_err(|e| tls_error(path,e))?;
+    if certs.is_empty(){
+        return Err(tls_error(path,"no certificates"))
+    }
+    Ok(certs)
+}
+
+pub(super) fn acceptor(config:&TlsConfig)->Result<TlsAcceptor,RpcError>{
+    let provider=Arc::new(rustls::crypto::ring::default_provider());
+    let certs=certificates(&config.cert_path)?;
+    let key=PrivateKeyDer::from_pem_file(&config.key_path).map_err(|e| tls_error(&config.key_path,e))?;
+    let builder=rustls::ServerConfig::builder_with_provider(provider.clone())
+    .with_safe_default_protocol_versions()
+    .map_err(|e| RpcError::Tls(e.to_string()))?;
+    let builder=match &config.client_ca_path{
+        Some(path)=>{
+            let mut roots=RootCertStore::empty();
+            for cert in certificates(path)?{
+                roots.add(cert).map_err(|e| tls_error(path,e))?;
+            }
+            let verifier=WebPkiClientVerifier::builder_with_provider(Arc::new(roots),provider).build().map_err(|e| tls_error(path,e))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None=>builder.with_no_client_auth(),
+    };
+    let mut server=builder.with_single_cert(certs,key).map_err(|e| tls_error(&config.cert_path,e))?;
+    server.alpn_protocols=vec![b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(server)))
+}
+
+/// Listener handing axum connections that finished their TLS handshake
+pub(super) struct TlsListener{
+    local_addr:SocketAddr,
+    streams:mpsc::Receiver<(TlsStream<TcpStream>,SocketAddr)>,
+    accept_task:AbortHandle,
+}
+
+impl TlsListener{
+    pub(super) fn new(listener:TcpListener,acceptor:TlsAcceptor,handshake_timeout:Duration)->io::Result<Self>{
+        let local_addr=listener.local_addr()?;
+        let (sender,streams)=mpsc::channel(ACCEPT_BACKLOG);
+        let handshakes=Arc::new(Semaphore::new(MAX_HANDSHAKES));
+        let accept_task=tokio::spawn(async move {
+            loop{
+                let Ok(permit)=handshakes.clone().acquire_owned().await else{break};
+                let (stream,addr)=match listener.accept().await{
+                    Ok(accepted)=>accepted,
+                    // e.g. out of file descriptors; give it a moment
+                    Err(_)=>{
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        continue
+                    }
+                };
+                let (acceptor,sender)=(acceptor.clone(),sender.clone());
+                tokio::spawn(async move {
+                    // failed or stalled handshakes are dropped
+                    let handshake=tokio::time::timeout(handshake_timeout,acceptor.accept(stream)).await;
+                    drop(permit);
+                    if let Ok(Ok(stream))=handshake{
+                        let _=sender.send((stream,addr)).await;
+                    }
+                });
+            }
+        })
+        .abort_handle();
+        Ok(TlsListener{local_addr,streams,accept_task})
+    }
+}
+
+impl Drop for TlsListener{
+    fn drop(&mut self){
+        self.accept_task.abort();
+    }
+}
+
+impl axum::serve::Listener for TlsListener{
+    type Io=TlsStream<TcpStream>;
+    type Addr=SocketAddr;
+
+    async fn accept(&mut self)->(Self::Io,Self::Addr){
+        match self.streams.recv().await{
+            Some(accepted)=>accepted,
+            // the accept loop only ends when the listener is dropped
+            None=>std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self)->io::Result<SocketAddr>{
+        Ok(self.local_addr)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::context;
+    use super::super::{RpcConfig,RpcServer};
+    use rcgen::{BasicConstraints,CertificateParams,IsCa,KeyPair};
+    use rustls::pki_types::ServerName;
+    use tokio::io::{AsyncReadExt,AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    struct Issued{
+        cert:rcgen::Certificate,
+        key:KeyPair,
+    }
+
+    fn issue(name:&str,issuer:Option<&Issued>)->Issued{
+        let key=KeyPair::generate().unwrap();
+        let mut params=CertificateParams::new(vec![name.to_string()]).unwrap();
+        let cert=match issuer{
+            Some(issuer)=>params.signed_by(&key,&issuer.cert,&issuer.key).unwrap(),
+            None=>{
+                params.is_ca=IsCa::Ca(BasicConstraints::Unconstrained);
+                params.self_signed(&key).unwrap()
+            }
+        };
+        Issued{cert,key}
+    }
+
+    /// Writes `issued` as PEM files named after `name`; (cert path, key path)
+    fn write(dir:&Path,name:&str,issued:&Issued)->(PathBuf,PathBuf){
+        let (cert,key)=(dir.join(format!("{name}.crt")),dir.join(format!("{name}.key")));
+        std::fs::write(&cert,issued.cert.pem()).unwrap();
+        std::fs::write(&key,issued.key.serialize_pem()).unwrap();
+        (cert,key)
+    }
+
+    /// GET `path` over TLS trusting `ca`, presenting `client` if given; the raw response
+    async fn https_get(addr:SocketAddr,ca:&Issued,client:Option<&Issued>,path:&str)->io::Result<String>{
+        let mut roots=RootCertStore::empty();
+        roots.add(ca.cert.der().clone()).unwrap();
+        let builder=rustls::ClientConfig::builder_with_provider(Arc::new(rustls::crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots);
+        let config=match client{
+            Some(client)=>{
+                let key=PrivateKeyDer::try_from(client.key.serialize_der()).unwrap();
+                builder.with_client_auth_cert(vec![client.cert.der().clone()],key).unwrap()
+            }
+            None=>builder.with_no_client_auth(),
+        };
+        let stream=TcpStream::connect(addr).await?;
+        let mut stream=TlsConnector::from(Arc::new(config)).connect(ServerName::try_from("localhost").unwrap(),stream).await?;
+        stream.write_all(format!("GET {path} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").as_bytes()).await?;
+        let mut response=String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    /// Plain-text GET; the status line's code, if anything came back
+    async fn plain_get(addr:SocketAddr)->Option<u16>{
+        let mut stream=TcpStream::connect(addr).await.unwrap();
+        stream.write_all(b"GET /validators HTTP/1.1\r\nHost: x\r\nConnection: close\r\n\r\n").await.unwrap();
+        let mut response=Vec::new();
+        let _=stream.read_to_end(&mut response).await;
+        let response=String::from_utf8_lossy(&response);
+        response.strip_prefix("HTTP/1.1 ").and_then(|rest| rest.get(..3)).and_then(|code| code.parse().ok())
+    }
+
+    #[tokio::test]
+    async fn test_https_and_client_certificates(){
+        let dir=std::env::temp_dir().join(format!("netchain-rpc-tls-{}",std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let ca=issue("netchain test ca",None);
+        let server=issue("localhost",Some(&ca));
+        let client=issue("wallet",Some(&ca));
+        let stranger=issue("wallet",Some(&issue("other ca",None)));
+        let (ca_path,_)=write(&dir,"ca",&ca);
+        let (cert_path,key_path)=write(&dir,"server",&server);
+
+        let tls=TlsConfig{cert_path,key_path,client_ca_path:None};
+        let config=RpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),tls:Some(tls.clone()),..RpcConfig::default()};
+        let open=RpcServer::start(config,context("alice")).await.unwrap();
+        let response=https_get(open.local_addr(),&ca,None,"/validators").await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"),"{response}");
+        // plain HTTP gets no answer
+        assert_eq!(plain_get(open.local_addr()).await,None);
+
+        let mutual=TlsConfig{client_ca_path:Some(ca_path),..tls.clone()};
+        let config=RpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),tls:Some(mutual),..RpcConfig::default()};
+        let mutual=RpcServer::start(config,context("alice")).await.unwrap();
+        let response=https_get(mutual.local_addr(),&ca,Some(&client),"/validators").await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200"),"{response}");
+        assert!(https_get(mutual.local_addr(),&ca,None,"/validators").await.is_err());
+        assert!(https_get(mutual.local_addr(),&ca,Some(&stranger),"/validators").await.is_err());
+
+        let missing=TlsConfig{key_path:dir.join("missing.key"),..tls};
+        let config=RpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),tls:Some(missing),..RpcConfig::default()};
+        assert!(matches!(RpcServer::start(config,context("alice")).await,Err(RpcError::Tls(_))));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
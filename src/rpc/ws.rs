@@ -0,0 +1,300 @@
+// src/rpc/ws.rs
+
+//! WebSocket subscriptions on `GET /ws`
+//! - Messages use the JSON-RPC 2.0 framing of `jsonrpc`, and plain calls work too, with
+//!   the role the connection was authenticated with at the upgrade
+//! - `subscribe` with `["newBlocks"]`, `["finalizedBlocks"]`, `["pendingTx",{"address":..}]`
+//!   (address optional, matched by `Transaction::involves`) or `["txStatus",{"hash":..}]`
+//!   answers a subscription id; `unsubscribe` with `[id]` ends it
+//! - Notifications: `{"jsonrpc":"2.0","method":"subscription","params":{"subscription":id,"result":..}}`
+//! - `txStatus` pushes the transaction's `TxStatus` once it's in a block and again once
+//!   that block is final, then ends
+//! - A session that falls behind the event stream misses the events it couldn't keep up
+//!   with; `txStatus` watchers look at the chain again so they still fire
+
+use super::jsonrpc::{ErrorObject,INVALID_PARAMS,INVALID_REQUEST,PARSE_ERROR,Params,handle_request,parse,response};
+use super::auth::Role;
+use super::{NodeEvent,RpcContext,TxStatus};
+use axum::extract::{Extension,State};
+use axum::extract::ws::{Message,WebSocket,WebSocketUpgrade};
+use axum::response::Response;
+use serde::Deserialize;
+use serde_json::{Value,json};
+use std::collections::BTreeMap;
+use tokio::sync::broadcast::error::RecvError;
+
+/// Subscriptions one session may hold at once
+pub const MAX_SUBSCRIPTIONS:usize=64;
+
+#[derive(Debug,Clone,PartialEq,Eq)]
+enum Subscription{
+    NewBlocks,
+    FinalizedBlocks,
+    PendingTx{address:Option<String>},
+    /// `included` once the inclusion was pushed
+    TxStatus{hash:String,included:bool},
+}
+
+#[derive(Deserialize,Default)]
+struct PendingTxFilter{
+    address:Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TxStatusFilter{
+    hash:String,
+}
+
+/// Subscriptions of one WebSocket session
+#[derive(Debug)]
+pub struct Subscriptions{
+    /// Role of the connection, for plain calls
+    role:Role,
+    next_id:u64,
+    active:BTreeMap<u64,Subscription>,
+}
+
+fn notification(id:u64,result:Value)->Value{
+    json!({"jsonrpc":"2.0","method":"subscription","params":{"subscription":id,"result":result}})
+}
+
+impl Subscriptions{
+    pub fn new(role:Role)->Self{
+        Subscriptions{role,next_id:0,active:BTreeMap::new()}
+    }
+
+    pub fn len(&self)->usize{
+        self.active.len()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.active.is_empty()
+    }
+
+    /// Answer a message from the client: responses first, then any notification it triggers
+    pub fn handle(&mut self,ctx:&RpcContext,text:&str)->Vec<Value>{
+        let request=match serde_json::from_str::<Value>(text){
+            Ok(request)=>request,
+            Err(e)=>return vec![response(Value::Null,Err(ErrorObject::new(PARSE_ERROR,e.to_string())))],
+        };
+        let Some(fields)=request.as_object() else{
+            return handle_request(ctx,self.role,request).into_iter().collect()
+        };
+        let id=fields.get("id").cloned();
+        let (method,params)=match parse(fields){
+            Ok(call) if matches!(call.0,"subscribe" | "unsubscribe")=>call,
+            _=>return handle_request(ctx,self.role,request).into_iter().collect(),
+        };
+        let mut out=Vec::new();
+        let outcome=if method=="subscribe"{
+            self.subscribe(ctx,&params).map(|(subscription,notified)| {
+                out.extend(notified);
+                json!(subscription)
+            })
+        } else{
+            params.get::<u64>(0,"subscription").map(|subscription| json!(self.active.remove(&subscription).is_some()))
+        };
+        if let Some(id)=id{
+            out.insert(0,response(id,outcome));
+        }
+        out
+    }
+
+    /// New subscription id, and for `txStatus` the status the transaction already reached
+    fn subscribe(&mut self,ctx:&RpcContext,params:&Params)->Result<(u64,Option<Value>),ErrorObject>{
+        if self.active.len()>=MAX_SUBSCRIPTIONS{
+            return Err(ErrorObject::new(INVALID_REQUEST,"too many subscriptions"))
+        }
+        let subscription=match params.get::<String>(0,"kind")?.as_str(){
+            "newBlocks"=>Subscription::NewBlocks,
+            "finalizedBlocks"=>Subscription::FinalizedBlocks,
+            "pendingTx"=>{
+                let filter=params.get::<Option<PendingTxFilter>>(1,"filter")?.unwrap_or_default();
+                Subscription::PendingTx{address:filter.address}
+            }
+            "txStatus"=>Subscription::TxStatus{hash:params.get::<TxStatusFilter>(1,"filter")?.hash,included:false},
+            kind=>return Err(ErrorObject::new(INVALID_PARAMS,format!("unknown subscription: {kind}"))),
+        };
+        let id=self.next_id;
+        self.next_id+=1;
+        self.active.insert(id,subscription);
+        Ok((id,self.check_tx(ctx,id)))
+    }
+
+    /// Notifications for `event`
+    pub fn on_event(&mut self,ctx:&RpcContext,event:&NodeEvent)->Vec<Value>{
+        let mut out=Vec::new();
+        for (id,subscription) in &self.active{
+            let result=match (subscription,event){
+                (Subscription::NewBlocks,NodeEvent::NewBlock(block)) | (Subscription::FinalizedBlocks,NodeEvent::Finalized(block))=>json!(block.as_ref()),
+                (Subscription::PendingTx{address},NodeEvent::PendingTx(tx))
+                if address.as_ref().is_none_or(|address| tx.tx.involves(address))=>json!(tx.as_ref()),
+                _=>continue,
+            };
+            out.push(notification(*id,result));
+        }
+        if matches!(event,NodeEvent::NewBlock(_) | NodeEvent::Finalized(_)){
+            out.extend(self.check_txs(ctx));
+        }
+        out
+    }
+
+    /// Look up every watched transaction again, e.g. after missing events
+    pub fn check_txs(&mut self,ctx:&RpcContext)->Vec<Value>{
+        let watched:Vec<u64>=self
+        .active
+        .iter()
+        .filter(|(_,subscription)| matches!(subscription,Subscription::TxStatus{..}))
+        .map(|(id,_)| *id)
+        .collect();
+        watched.into_iter().filter_map(|id| self.check_tx(ctx,id)).collect()
+    }
+
+    /// Notification if watched transaction `id` moved on since the last one
+    fn check_tx(&mut self,ctx:&RpcContext,id:u64)->Option<Value>{
+        let Some(Subscription::TxStatus{hash,included})=self.active.get_mut(&id) else{
+            return None
+        };
+        let status=ctx.transaction(hash)?;
+        let TxStatus::Included{finalized,..}=&status else{
+            return None
+        };
+        if *finalized{
+            self.active.remove(&id);
+        } else if *included{
+            return None
+        } else{
+            *included=true;
+        }
+        Some(notification(id,json!(status)))
+    }
+}
+
+pub(super) async fn upgrade(
+    State(ctx):State<RpcContext>,
+    Extension(role):Extension<Role>,
+    upgrade:WebSocketUpgrade,
+    max_message:usize,
+)->Response{
+    upgrade.max_message_size(max_message).on_upgrade(move |socket| session(ctx,role,socket))
+}
+
+async fn session(ctx:RpcContext,role:Role,mut socket:WebSocket){
+    let mut events=ctx.subscribe();
+    let mut subscriptions=Subscriptions::new(role);
+    loop{
+        let outgoing=tokio::select!{
+            message=socket.recv()=>match message{
+                Some(Ok(Message::Text(text)))=>subscriptions.handle(&ctx,text.as_str()),
+                Some(Ok(Message::Close(_)) | Err(_)) | None=>return,
+                // pings are answered by the socket itself
+                Some(Ok(_))=>continue,
+            },
+            event=events.recv()=>match event{
+                Ok(event)=>subscriptions.on_event(&ctx,&event),
+                Err(RecvError::Lagged(_))=>subscriptions.check_txs(&ctx),
+                Err(RecvError::Closed)=>return,
+            },
+        };
+        for message in outgoing{
+            if socket.send(Message::text(message.to_string())).await.is_err(){
+                return
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use super::super::tests::context;
+    use super::super::{RpcConfig,RpcServer};
+    use crate::block::Block;
+    use crate::gas::GasSchedule;
+    use crate::receipt::BlockReceipts;
+    use crate::transaction::{SignedTransaction,Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+    use futures_util::{SinkExt,StreamExt};
+    use std::sync::Arc;
+    use tokio_tungstenite::tungstenite;
+
+    fn call(subscriptions:&mut Subscriptions,ctx:&RpcContext,method:&str,params:Value)->Vec<Value>{
+        subscriptions.handle(ctx,&json!({"jsonrpc":"2.0","method":method,"params":params,"id":1}).to_string())
+    }
+
+    #[test]
+    fn test_subscriptions_follow_events(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let ctx=context(&sender);
+        let mut subscriptions=Subscriptions::new(Role::Wallet);
+
+        assert_eq!(call(&mut subscriptions,&ctx,"subscribe",json!(["newBlocks"]))[0]["result"],0);
+        assert_eq!(call(&mut subscriptions,&ctx,"subscribe",json!(["pendingTx",{"address":"bob"}]))[0]["result"],1);
+        assert_eq!(call(&mut subscriptions,&ctx,"subscribe",json!(["pendingTx",{"address":"carol"}]))[0]["result"],2);
+        assert_eq!(call(&mut subscriptions,&ctx,"subscribe",json!(["mempool"]))[0]["error"]["code"],INVALID_PARAMS);
+        // plain calls are answered as usual
+        assert_eq!(call(&mut subscriptions,&ctx,"chain_getHeight",json!([]))[0]["result"],0);
+
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(sender.clone(),"bob".to_string(),10,1_000,0,None),&kp);
+        let hash=tx.tx_hash_hex();
+        let notified=subscriptions.on_event(&ctx,&NodeEvent::PendingTx(Arc::new(tx.clone())));
+        assert_eq!(notified,vec![notification(1,json!(tx))]);
+
+        // watching a transaction: once included, once final, then the subscription ends
+        let watch=call(&mut subscriptions,&ctx,"subscribe",json!(["txStatus",{"hash":hash}]));
+        assert_eq!((watch.len(),watch[0]["result"].clone()),(1,json!(3)));
+        let receipt=ctx.state.write(|state| state.apply_with_receipt(&tx,&GasSchedule::default(),1,0));
+        let block={
+            let mut chain=ctx.chain.lock().unwrap();
+            chain.add_block("block 1".to_string());
+            chain.record_receipts(BlockReceipts{height:1,receipts:vec![receipt],..Default::default()});
+            Arc::new(chain.last_block().clone())
+        };
+        let notified=subscriptions.on_event(&ctx,&NodeEvent::NewBlock(block.clone()));
+        assert_eq!(notified.len(),2);
+        assert_eq!(notified[0],notification(0,json!(*block)));
+        assert_eq!(notified[1]["params"]["result"]["status"],"included");
+        assert_eq!(notified[1]["params"]["result"]["finalized"],false);
+        assert!(subscriptions.on_event(&ctx,&NodeEvent::NewBlock(block.clone()))[1..].is_empty());
+
+        assert_eq!(call(&mut subscriptions,&ctx,"unsubscribe",json!([0]))[0]["result"],true);
+        assert_eq!(call(&mut subscriptions,&ctx,"unsubscribe",json!([0]))[0]["result"],false);
+        assert_eq!(subscriptions.len(),3);
+    }
+
+    #[test]
+    fn test_watch_already_included_transaction(){
+        let kp=generate_ed25519_keypair();
+        let sender=pubkey_to_address_hex(&kp.public);
+        let ctx=context(&sender);
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(sender,"bob".to_string(),10,1_000,0,None),&kp);
+        let receipt=ctx.state.write(|state| state.apply_with_receipt(&tx,&GasSchedule::default(),1,0));
+        {
+            let mut chain=ctx.chain.lock().unwrap();
+            chain.add_block("block 1".to_string());
+            chain.record_receipts(BlockReceipts{height:1,receipts:vec![receipt],..Default::default()});
+        }
+        let mut subscriptions=Subscriptions::new(Role::Wallet);
+        let out=call(&mut subscriptions,&ctx,"subscribe",json!(["txStatus",{"hash":tx.tx_hash_hex()}]));
+        assert_eq!(out[0]["result"],0);
+        assert_eq!(out[1]["params"]["result"]["status"],"included");
+        assert_eq!(subscriptions.len(),1);
+    }
+
+    #[tokio::test]
+    async fn test_blocks_pushed_over_websocket(){
+        let ctx=context("alice");
+        let config=RpcConfig{listen_addr:"127.0.0.1:0".parse().unwrap(),..RpcConfig::default()};
+        let server=RpcServer::start(config,ctx.clone()).await.unwrap();
+        let (mut socket,_)=tokio_tungstenite::connect_async(format!("ws://{}/ws",server.local_addr())).await.unwrap();
+        let subscribe=json!({"jsonrpc":"2.0","method":"subscribe","params":["newBlocks"],"id":1}).to_string();
+        socket.send(tungstenite::Message::text(subscribe)).await.unwrap();
+        let answer=socket.next().await.unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Value>(answer.to_text().unwrap()).unwrap()["result"],0);
+
+        let block=Arc::new(Block::new(1,"block 1".to_string(),ctx.block(0).unwrap().hash));
+        ctx.publish(NodeEvent::NewBlock(block.clone()));
+        let pushed=socket.next().await.unwrap().unwrap();
+        assert_eq!(serde_json::from_str::<Value>(pushed.to_text().unwrap()).unwrap(),notification(0,json!(*block)));
+    }
+}
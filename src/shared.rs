@@ -0,0 +1,105 @@
+// src/shared.rs
+
+//! State shared between block execution and readers (RPC queries, mempool validation)
+//! - Single writer: blocks and other mutations run against a private working `State`
+//!   behind a mutex
+//! - Readers get an immutable `Arc<State>` published after each successful write, so a
+//!   query never waits for a block to finish and never sees one half-applied
+//! - Publishing copies the working state with `State::read_view`, which shares accounts,
+//!   the state tree and chain state with it, so it costs as much as what the write changed;
+//!   then it swaps a pointer, holding the read lock only long enough to clone the `Arc`
+
+use crate::state::{BlockApplyError,BlockOutcome,State};
+use crate::transaction::SignedTransaction;
+use std::sync::{Arc,Mutex,RwLock};
+
+/// Single-writer, many-reader handle on the chain state
+#[derive(Debug)]
+pub struct SharedState{
+    /// Working copy mutated by the writer
+    writer:Mutex<State>,
+    /// Last published state, as of the end of the last successful write
+    published:RwLock<Arc<State>>,
+}
+
+impl SharedState{
+    pub fn new(state:State)->Self{
+        SharedState{published:RwLock::new(Arc::new(state.read_view())),writer:Mutex::new(state)}
+    }
+
+    /// Consistent view of the state as of the last published write.
+    /// The view never changes; take a new one to see later blocks.
+    pub fn snapshot(&self)->Arc<State>{
+        self.published.read().expect("published state lock poisoned").clone()
+    }
+
+    /// Apply a block on the working state and publish the result.
+    /// A rejected block leaves both the working and the published state untouched.
+    pub fn apply_block(&self,proposer:&str,txs:&[SignedTransaction])->Result<BlockOutcome,BlockApplyError>{
+        let mut state=self.writer.lock().expect("state writer poisoned");
+        let outcome=state.apply_block(proposer,txs)?;
+        self.publish(&state);
+        Ok(outcome)
+    }
+
+    /// Run any other mutation (epoch change, reward settlement, ...) and publish the result.
+    /// Readers keep seeing the previous state until `f` returns.
+    pub fn write<R>(&self,f:impl FnOnce(&mut State)->R)->R{
+        let mut state=self.writer.lock().expect("state writer poisoned");
+        let result=f(&mut state);
+        self.publish(&state);
+        result
+    }
+
+    fn publish(&self,state:&State){
+        let next=Arc::new(state.read_view());
+        *self.published.write().expect("published state lock poisoned")=next;
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::{Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+    use std::sync::mpsc;
+
+    #[test]
+    fn test_readers_see_whole_blocks_only(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let shared=SharedState::new(State::with_genesis(vec![(addr.clone(),100)]));
+        let before=shared.snapshot();
+
+        let (started,wait_started)=mpsc::channel();
+        let (resume,wait_resume)=mpsc::channel::<()>();
+        std::thread::scope(|s| {
+            let shared=&shared;
+            s.spawn(move || {
+                shared.write(|state| {
+                    state.set_block_context(1,0);
+                    started.send(()).unwrap();
+                    // hold the writer until the reader below has looked
+                    wait_resume.recv().unwrap();
+                });
+            });
+            wait_started.recv().unwrap();
+            // the writer is mid-update; reads don't block and see the old state
+            assert_eq!(shared.snapshot().get_balance(&addr),100);
+            resume.send(()).unwrap();
+        });
+
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),30,1,0,None),&kp);
+        shared.apply_block("proposer",std::slice::from_ref(&tx)).unwrap();
+        assert_eq!(shared.snapshot().get_balance("bob"),30);
+        // an earlier view is unaffected by later blocks
+        assert_eq!(before.get_balance("bob"),0);
+
+        // a rejected block (replayed nonce) publishes nothing
+        assert!(shared.apply_block("proposer",&[tx]).is_err());
+        assert_eq!(shared.snapshot().get_nonce(&addr),1);
+
+        // the writer's snapshots stay with the writer
+        shared.write(State::snapshot);
+        assert_eq!(shared.snapshot().snapshot_count(),0);
+    }
+}
@@ -0,0 +1,95 @@
+// src/signer.rs
+
+//! Pluggable transaction signers
+//! - `TxSigner` is all transaction code needs from a key: its scheme, public key and a
+//!   signature over some bytes
+//! - Local Ed25519 `Keypair`s and secp256k1 `SigningKey`s implement it here; HSMs, remote
+//!   signers and hardware wallets implement it out of tree
+
+use crate::transaction::SignatureScheme;
+use ed25519_dalek::{Keypair,Signer};
+
+/// Something that can sign transaction bytes without exposing its secret key
+pub trait TxSigner{
+    /// Scheme the produced signatures verify under
+    fn scheme(&self)->SignatureScheme;
+
+    /// Raw public key bytes (ed25519 bytes or compressed secp256k1 SEC1 bytes)
+    fn public_key(&self)->Vec<u8>;
+
+    /// Raw signature bytes over `msg`. External signers may fail (device unplugged,
+    /// user rejected the request, ...).
+    fn sign(&self,msg:&[u8])->Result<Vec<u8>,String>;
+}
+
+impl TxSigner for Keypair{
+    fn scheme(&self)->SignatureScheme{
+        SignatureScheme::Ed25519
+    }
+
+    fn public_key(&self)->Vec<u8>{
+        self.public.to_bytes().to_vec()
+    }
+
+    fn sign(&self,msg:&[u8])->Result<Vec<u8>,String>{
+        Ok(Signer::sign(self,msg).to_bytes().to_vec())
+    }
+}
+
+impl TxSigner for k256::ecdsa::SigningKey{
+    fn scheme(&self)->SignatureScheme{
+        SignatureScheme::Secp256k1
+    }
+
+    fn public_key(&self)->Vec<u8>{
+        self.verifying_key().to_encoded_point(true).as_bytes().to_vec()
+    }
+
+    fn sign(&self,msg:&[u8])->Result<Vec<u8>,String>{
+        let sig:k256::ecdsa::Signature=k256::ecdsa::signature::Signer::sign(self,msg);
+        Ok(sig.to_bytes().to_vec())
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::{SignedTransaction,Transaction,TxError};
+
+    /// Stand-in for a remote signer: only ever sees message bytes
+    struct RemoteSigner{
+        device:Keypair,
+        approve:bool,
+    }
+
+    impl TxSigner for RemoteSigner{
+        fn scheme(&self)->SignatureScheme{
+            SignatureScheme::Ed25519
+        }
+
+        fn public_key(&self)->Vec<u8>{
+            self.device.public_key()
+        }
+
+        fn sign(&self,msg:&[u8])->Result<Vec<u8>,String>{
+            if !self.approve{
+                return Err("rejected on device".to_string())
+            }
+            TxSigner::sign(&self.device,msg)
+        }
+    }
+
+    #[test]
+    fn test_external_signer_plugs_in(){
+        let remote=RemoteSigner{device:crate::transaction::generate_ed25519_keypair(),approve:true};
+        let sender=SignatureScheme::Ed25519.address(&remote.public_key()).unwrap();
+        let tx=Transaction::new(sender,"bob".to_string(),5,1,0,None);
+
+        let signed=SignedTransaction::sign_with(&tx,&remote).unwrap();
+        assert!(signed.verify().is_ok());
+        assert_eq!(signed,SignedTransaction::sign_with_keypair(&tx,&remote.device));
+
+        let rejecting=RemoteSigner{approve:false,..remote};
+        assert_eq!(SignedTransaction::sign_with(&tx,&rejecting).unwrap_err(),TxError::Signing("rejected on device".to_string()));
+    }
+}
@@ -0,0 +1,204 @@
+// src/smt.rs
+
+//! Sparse Merkle tree over 256-bit keys
+//! - Leaf = H(0x00 || key || value_hash); internal node = H(0x01 || left || right);
+//!   empty subtree = 32 zero bytes
+//! - A subtree holding a single leaf is represented by that leaf directly, so paths are only
+//!   as deep as needed to separate keys
+//! - `prove` returns the sibling path for a key; the same proof shape shows inclusion (the
+//!   path ends in the key's leaf) or exclusion (it ends empty or in another key's leaf)
+
+use imbl::OrdMap;
+use serde::{Deserialize,Serialize};
+use sha2::{Digest,Sha256};
+
+pub type Hash=[u8;32];
+
+/// Hash of an empty subtree
+pub const EMPTY:Hash=[0u8;32];
+
+fn leaf_hash(key:&Hash,value_hash:&Hash)->Hash{
+    let mut hasher=Sha256::new();
+    hasher.update([0x00]);
+    hasher.update(key);
+    hasher.update(value_hash);
+    hasher.finalize().into()
+}
+
+fn node_hash(left:&Hash,right:&Hash)->Hash{
+    if left==&EMPTY && right==&EMPTY{
+        return EMPTY
+    }
+    let mut hasher=Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// Bit `depth` of `key`, most significant first
+fn bit(key:&Hash,depth:usize)->bool{
+    key[depth/8]&(0x80>>(depth%8))!=0
+}
+
+/// Path from a key's position up to the root
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct SmtProof{
+    /// Sibling hashes from the root downwards
+    pub siblings:Vec<Hash>,
+    /// The leaf found where the path ends: (key, value hash), or None if that slot is empty
+    pub leaf:Option<(Hash,Hash)>,
+}
+
+impl SmtProof{
+    /// Check that `key` maps to `value_hash` (Some) or is absent (None) under `root`
+    pub fn verify(&self,root:&Hash,key:&Hash,value_hash:Option<&Hash>)->bool{
+        if self.siblings.len()>256{
+            return false
+        }
+        let terminal=match (&self.leaf,value_hash){
+            // inclusion: the path must end in exactly this key and value
+            (Some((leaf_key,leaf_value)),Some(value))=>{
+                if leaf_key!=key || leaf_value!=value{
+                    return false
+                }
+                leaf_hash(leaf_key,leaf_value)
+            }
+            (None,Some(_))=>return false,
+            (None,None)=>EMPTY,
+            // exclusion by another key sharing the path prefix
+            (Some((leaf_key,leaf_value)),None)=>{
+                if leaf_key==key || (0..self.siblings.len()).any(|d| bit(leaf_key,d)!=bit(key,d)){
+                    return false
+                }
+                leaf_hash(leaf_key,leaf_value)
+            }
+        };
+        let computed=self
+        .siblings
+        .iter()
+        .enumerate()
+        .rev()
+        .fold(terminal,|node,(depth,sibling)| {
+            if bit(key,depth){
+                node_hash(sibling,&node)
+            }else{
+                node_hash(&node,sibling)
+            }
+        });
+        &computed==root
+    }
+}
+
+/// Sparse Merkle tree of key -> value hash. Leaves are a persistent map, so copies of the
+/// tree share them.
+#[derive(Debug,Clone,Default)]
+pub struct SparseMerkleTree{
+    leaves:OrdMap<Hash,Hash>,
+}
+
+impl SparseMerkleTree{
+    pub fn new()->Self{
+        Self::default()
+    }
+
+    /// Set (Some) or remove (None) the value hash stored under `key`
+    pub fn update(&mut self,key:Hash,value_hash:Option<Hash>){
+        match value_hash{
+            Some(value)=>self.leaves.insert(key,value),
+            None=>self.leaves.remove(&key),
+        };
+    }
+
+    pub fn get(&self,key:&Hash)->Option<&Hash>{
+        self.leaves.get(key)
+    }
+
+    pub fn len(&self)->usize{
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.leaves.is_empty()
+    }
+
+    pub fn root(&self)->Hash{
+        let leaves:Vec<(&Hash,&Hash)>=self.leaves.iter().collect();
+        Self::subtree(&leaves,0)
+    }
+
+    /// Root of the subtree holding `leaves` (sorted, sharing their first `depth` bits)
+    fn subtree(leaves:&[(&Hash,&Hash)],depth:usize)->Hash{
+        match leaves{
+            []=>EMPTY,
+            [(key,value)]=>leaf_hash(key,value),
+            _=>{
+                let split=leaves.partition_point(|(key,_)| !bit(key,depth));
+                node_hash(&Self::subtree(&leaves[..split],depth+1),&Self::subtree(&leaves[split..],depth+1))
+            }
+        }
+    }
+
+    /// Inclusion or exclusion proof for `key`
+    pub fn prove(&self,key:&Hash)->SmtProof{
+        let all:Vec<(&Hash,&Hash)>=self.leaves.iter().collect();
+        let mut leaves=all.as_slice();
+        let mut siblings=Vec::new();
+        let mut depth=0;
+        while leaves.len()>1{
+            let split=leaves.partition_point(|(k,_)| !bit(k,depth));
+            let (left,right)=leaves.split_at(split);
+            if bit(key,depth){
+                siblings.push(Self::subtree(left,depth+1));
+                leaves=right;
+            }else{
+                siblings.push(Self::subtree(right,depth+1));
+                leaves=left;
+            }
+            depth+=1;
+        }
+        SmtProof{
+            siblings,
+            leaf:leaves.first().map(|(k,v)| (**k,**v)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn h(data:&str)->Hash{
+        Sha256::digest(data.as_bytes()).into()
+    }
+
+    #[test]
+    fn test_inclusion_and_exclusion_proofs(){
+        let mut tree=SparseMerkleTree::new();
+        assert_eq!(tree.root(),EMPTY);
+        for i in 0..50{
+            tree.update(h(&format!("key{}",i)),Some(h(&format!("value{}",i))));
+        }
+        let root=tree.root();
+
+        for i in [0,17,49]{
+            let key=h(&format!("key{}",i));
+            let proof=tree.prove(&key);
+            assert!(proof.verify(&root,&key,Some(&h(&format!("value{}",i)))));
+            assert!(!proof.verify(&root,&key,Some(&h("forged"))));
+            assert!(!proof.verify(&root,&key,None));
+        }
+
+        let missing=h("missing");
+        let proof=tree.prove(&missing);
+        assert!(proof.verify(&root,&missing,None));
+        assert!(!proof.verify(&root,&missing,Some(&h("value0"))));
+
+        // root depends on content, not insertion order; removal restores the old root
+        let before=tree.root();
+        tree.update(missing,Some(h("x")));
+        assert_ne!(tree.root(),before);
+        tree.update(missing,None);
+        assert_eq!(tree.root(),before);
+    }
+}
@@ -0,0 +1,135 @@
+// src/stability.rs
+
+//! Packet-loss / stability measurement
+//! - Tracks request/response outcomes per peer over a sliding window
+//! - Exports the aggregate success rate as `NodeMetrics.stability_bps`
+
+use crate::consensus::{BPS_SCALE,NodeMetrics};
+use std::collections::{HashMap,VecDeque};
+
+/// Default number of recent outcomes kept per peer
+pub const DEFAULT_WINDOW:usize=100;
+
+/// Sliding-window success rates per peer
+#[derive(Debug,Clone)]
+pub struct StabilityTracker{
+    window:usize,
+    /// peer id -> recent outcomes (true = response received)
+    outcomes:HashMap<String,VecDeque<bool>>,
+}
+
+impl Default for StabilityTracker{
+    fn default()->Self{
+        Self::new(DEFAULT_WINDOW)
+    }
+}
+
+impl StabilityTracker{
+    /// Keep the last `window` outcomes per peer (at least one)
+    pub fn new(window:usize)->Self{
+        StabilityTracker{
+            window:window.max(1),
+            outcomes:HashMap::new(),
+        }
+    }
+
+    /// Record whether a request to `peer` got a response
+    pub fn record(&mut self,peer:&str,success:bool){
+        let outcomes=self.outcomes.entry(peer.to_string()).or_default();
+        outcomes.push_back(success);
+        while outcomes.len()>self.window{
+            outcomes.pop_front();
+        }
+    }
+
+    pub fn record_success(&mut self,peer:&str){
+        self.record(peer,true);
+    }
+
+    pub fn record_failure(&mut self,peer:&str){
+        self.record(peer,false);
+    }
+
+    /// Success rate for `peer` in basis points, None if never contacted
+    pub fn success_bps(&self,peer:&str)->Option<u64>{
+        let outcomes=self.outcomes.get(peer)?;
+        if outcomes.is_empty(){
+            return None
+        }
+        let ok=outcomes.iter().filter(|o| **o).count() as u64;
+        Some(ok*BPS_SCALE/outcomes.len() as u64)
+    }
+
+    /// Success rate across every tracked request, in basis points
+    pub fn aggregate_bps(&self)->Option<u64>{
+        let (ok,total)=self
+        .outcomes
+        .values()
+        .flatten()
+        .fold((0u64,0u64),|(ok,total),o| (ok+*o as u64,total+1));
+        if total==0{
+            return None
+        }
+        Some(ok*BPS_SCALE/total)
+    }
+
+    /// Overwrite `metrics.stability_bps` with the aggregate. Returns false if nothing was tracked yet.
+    pub fn apply_to(&self,metrics:&mut NodeMetrics)->bool{
+        match self.aggregate_bps(){
+            Some(bps)=>{
+                metrics.stability_bps=bps;
+                true
+            }
+            None=>false,
+        }
+    }
+
+    /// Stop tracking a disconnected peer
+    pub fn forget(&mut self,peer:&str){
+        self.outcomes.remove(peer);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_sliding_window_success_rate(){
+        let mut tracker=StabilityTracker::new(4);
+        assert_eq!(tracker.success_bps("a"),None);
+
+        for ok in [false,true,true,true]{
+            tracker.record("a",ok);
+        }
+        assert_eq!(tracker.success_bps("a"),Some(7_500));
+
+        // the oldest failure slides out of the window
+        tracker.record_success("a");
+        assert_eq!(tracker.success_bps("a"),Some(10_000));
+    }
+
+    #[test]
+    fn test_aggregate_feeds_metrics(){
+        let mut tracker=StabilityTracker::default();
+        let mut metrics=NodeMetrics{
+            node_id:"me".to_string(),
+            upload_mbps:10,
+            download_mbps:10,
+            latency_ms:10,
+            uptime_bps:10_000,
+            stability_bps:10_000,
+        };
+        assert!(!tracker.apply_to(&mut metrics));
+
+        tracker.record_success("a");
+        tracker.record_success("a");
+        tracker.record_success("b");
+        tracker.record_failure("b");
+        assert!(tracker.apply_to(&mut metrics));
+        assert_eq!(metrics.stability_bps,7_500);
+
+        tracker.forget("b");
+        assert_eq!(tracker.aggregate_bps(),Some(10_000));
+    }
+}
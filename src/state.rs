@@ -1,53 +1,723 @@
 // src/state.rs
 
-use std::collections::HashMap;
-use crate::transaction::{SignedTransaction,Transaction};
+use std::collections::{BTreeMap,BTreeSet,HashMap};
+use std::ops::Bound;
+use crate::asset::{AssetInfo,NATIVE_ASSET,is_valid_asset_id};
+use crate::canonical::DecodeError;
+use crate::attestation::{MetricReport,MetricReportError};
+use crate::consensus::{BPS_SCALE,NodeMetrics};
+use crate::diff::{AccountChange,StateDiff};
+use crate::evidence::{EvidenceError,JAIL_EPOCHS,SLASH_FRACTION_BPS};
+use crate::fees::{FeePolicy,FeeSplit};
+use crate::gas::GasSchedule;
+use crate::genesis::{Genesis,GenesisAccount};
+use crate::names::{NAME_PREFIX,NAME_REGISTRATION_COST,NameError,NameRegistry};
+use crate::receipt::{Event,Receipt,ReceiptStatus};
+use crate::rewards::{EpochSummary,distribute};
+use crate::smt::{Hash,SmtProof,SparseMerkleTree};
+use crate::store::{StateStore,StoreError};
+use crate::transaction::{LimitError,MAX_TRANSFER_OUTPUTS,SignedTransaction,TimeLock,Transaction,TxError,TxLimits,TxPayload};
+use crate::validator::{RegistryError,ValidatorRegistry};
+use crate::vesting::VestingSchedule;
+use imbl::{OrdMap,OrdSet};
+use serde::{Deserialize,Serialize};
+use sha2::{Digest,Sha256};
+use thiserror::Error;
 
 /// Errors that can occur during state transitions
-#[derive(Debug,Clone)]
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
 pub enum StateError{
+    #[error("insufficient balance")]
     InsufficientBalance,
+    #[error("invalid nonce")]
     InvalidNonce,
-    InvalidSignature,
+    #[error("invalid transaction: {0}")]
+    InvalidSignature(#[from] TxError),
+    #[error("amount must be non-zero")]
     ZeroAmount,
+    #[error("sender account not found")]
     SenderNotFound,
+    #[error("fee payer account not found")]
+    FeePayerNotFound,
+    #[error("validator registry: {0}")]
+    ValidatorRegistry(#[from] RegistryError),
+    #[error("name registry: {0}")]
+    Name(#[from] NameError),
+    #[error("invalid evidence: {0}")]
+    InvalidEvidence(#[from] EvidenceError),
+    #[error("invalid metric report: {0}")]
+    InvalidMetricReport(#[from] MetricReportError),
+    #[error("epoch already settled")]
+    EpochAlreadySettled,
+    #[error("insufficient stake")]
+    InsufficientStake,
+    /// Governance votes require bonded stake
+    #[error("governance votes require bonded stake")]
+    NoStake,
+    /// `MultiTransfer` without outputs
+    #[error("multi-transfer without outputs")]
+    EmptyBatch,
+    /// `MultiTransfer` with more than `MAX_TRANSFER_OUTPUTS` outputs
+    #[error("too many transfer outputs")]
+    TooManyOutputs,
+    #[error("amount overflows")]
+    AmountOverflow,
+    /// The transaction's time lock hasn't expired at the current block
+    #[error("transaction is time-locked")]
+    TimeLocked,
+    /// Memo or encoded size over the configured `TxLimits`
+    #[error(transparent)]
+    ExceedsLimit(#[from] LimitError),
+    /// A credit would push a balance or stake past `u64::MAX`
+    #[error("balance would overflow")]
+    BalanceOverflow,
+    /// The sender's nonce is exhausted
+    #[error("nonce exhausted")]
+    NonceOverflow,
+    /// A block changed total supply by something other than its burns and slashes
+    #[error("supply changed by {actual}, expected {expected}")]
+    SupplyMismatch{expected:i128,actual:i128},
+    /// A diff being reverted doesn't match the account's current value
+    #[error("diff doesn't match account {0}")]
+    DiffMismatch(String),
+    /// The spend would dip into the still-vesting part of the balance
+    #[error("balance is still vesting")]
+    VestingLocked,
+    /// Malformed or reserved asset symbol (see `asset::is_valid_asset_id`)
+    #[error("invalid asset id")]
+    InvalidAssetId,
+    #[error("asset already issued")]
+    AssetExists,
+    #[error("unknown asset")]
+    UnknownAsset,
+    #[error("insufficient asset balance")]
+    InsufficientAssetBalance,
+    /// A block changed the holdings of an asset by something other than its issuance
+    #[error("supply of asset {0} changed")]
+    AssetSupplyMismatch(String),
+    /// A transfer would leave a receiver holding only a balance below `min_account_balance`
+    #[error("receiver {0} would be left below the minimum balance")]
+    BelowMinimumBalance(String),
 }
 
-/// Account state
+/// A block transaction failed; none of the block's transactions were applied
+#[derive(Debug,Clone)]
+pub struct BlockApplyError{
+    /// Position of the failing transaction in the block, or the number of transactions
+    /// if the block as a whole failed the supply check
+    pub index:usize,
+    pub error:StateError,
+}
+
+/// Result of a successfully applied block
 #[derive(Debug,Clone)]
+pub struct BlockOutcome{
+    /// Events of each transaction, in block order
+    pub events:Vec<Vec<Event>>,
+    /// How the block's fees were routed
+    pub fees:FeeSplit,
+    /// Accounts the block changed, for storage next to it
+    pub diff:StateDiff,
+}
+
+/// Handle to a point `State` can be reverted to (see `State::snapshot`)
+#[derive(Debug,Clone,Copy,PartialEq,Eq)]
+pub struct SnapshotId{
+    id:u64,
+    /// Block height of the state when the snapshot was taken
+    pub height:u64,
+}
+
+/// Reasons a snapshot can't be used
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum SnapshotError{
+    /// Already reverted or released, or never taken
+    #[error("unknown snapshot")]
+    UnknownSnapshot,
+    /// No live snapshot was taken at that height
+    #[error("no snapshot at height {0}")]
+    NoSnapshotAtHeight(u64),
+}
+
+/// State besides accounts, saved when a snapshot is taken and persisted with every block.
+/// Settings the node applies on startup (unbonding period, limits, fee policy) aren't part of it.
+/// Every collection is a persistent map sharing structure with the live state, so taking one
+/// costs nothing however long the chain's history.
+#[derive(Debug,Clone,Serialize,Deserialize)]
+struct Checkpoint{
+    epoch:u64,
+    block_height:u64,
+    block_time:u64,
+    validators:ValidatorRegistry,
+    slashed:OrdSet<(String,u64)>,
+    metric_reports:OrdMap<u64,OrdMap<String,MetricReport>>,
+    settled_epochs:OrdSet<u64>,
+    governance_votes:OrdMap<u64,OrdMap<String,bool>>,
+    fee_pools:OrdMap<u64,u64>,
+    assets:OrdMap<String,AssetInfo>,
+    names:NameRegistry,
+}
+
+/// Copy-on-write overlay over the account ledger: the value every account had when the
+/// snapshot was taken, recorded the first time it's written afterwards.
+/// Reverting restores just those accounts and the checkpoint, which shares structure with
+/// the live state, so a snapshot costs as much as what changes.
+#[derive(Debug,Clone)]
+struct Layer{
+    id:u64,
+    height:u64,
+    originals:HashMap<String,Option<Account>>,
+    checkpoint:Checkpoint,
+}
+
+/// Outcome of `State::simulate_transaction`
+#[derive(Debug,Clone)]
+pub struct Simulation{
+    /// Gas the transaction would be charged under the given schedule
+    pub gas_used:u64,
+    /// address -> balance delta, only for balances that would change
+    pub balance_changes:BTreeMap<String,i128>,
+    /// Why the transaction would be rejected, if it would
+    pub result:Result<(),StateError>,
+}
+
+/// Default number of epochs unstaked funds stay locked (and slashable) before release
+pub const DEFAULT_UNBONDING_EPOCHS:u64=7;
+
+/// Stake on its way out of the bond
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct Unbonding{
+    pub amount:u64,
+    /// First epoch at which `amount` is credited back to the liquid balance
+    pub release_epoch:u64,
+}
+
+/// Account state
+/// - `balance`: liquid, spendable funds
+/// - `staked`: bonded stake, counted for selection weight and governance and slashable
+/// - `unbonding`: unstaked but still locked and slashable until its release epoch
+/// - `vesting`: part of `balance` that only becomes spendable over time
+/// - `assets`: balances of user-issued assets (see `asset`); `balance` is the native coin
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
 pub struct Account{
     pub balance:u64,
-    pub nonce:u64
+    pub nonce:u64,
+    /// Bonded validator stake (not spendable until unstaked)
+    pub staked:u64,
+    #[serde(default)]
+    pub unbonding:Vec<Unbonding>,
+    #[serde(default)]
+    pub vesting:Option<VestingSchedule>,
+    /// asset -> balance, only non-zero balances
+    #[serde(default)]
+    pub assets:BTreeMap<String,u64>,
 }
 
 impl Account{
     pub fn new(balance:u64)->Self{
-        Self{balance,nonce:0}
+        Self{balance,nonce:0,staked:0,unbonding:Vec::new(),vesting:None,assets:BTreeMap::new()}
+    }
+
+    /// Balance of `asset`; the native asset is `balance`
+    pub fn asset_balance(&self,asset:&str)->u64{
+        if asset==NATIVE_ASSET{
+            return self.balance
+        }
+        self.assets.get(asset).copied().unwrap_or(0)
+    }
+
+    /// Whether the dust policy removes this account at minimum balance `min`
+    fn is_dust(&self,min:u64)->bool{
+        self.balance<min && self.is_bare()
+    }
+
+    /// Nothing but a liquid balance: no nonce, stake, unbonding, vesting or assets
+    fn is_bare(&self)->bool{
+        self.nonce==0
+        && self.staked==0
+        && self.unbonding.is_empty()
+        && self.vesting.is_none()
+        && self.assets.is_empty()
+    }
+
+    /// Part of `balance` that can be spent at block `height`
+    pub fn spendable_at(&self,height:u64)->u64{
+        let locked=self.vesting.as_ref().map(|v| v.locked_at(height)).unwrap_or(0);
+        self.balance.saturating_sub(locked)
+    }
+
+    /// Sum of all pending unbonding entries
+    pub fn unbonding_total(&self)->u64{
+        self.unbonding.iter().map(|u| u.amount).sum()
+    }
+
+    /// Everything the account holds: liquid, bonded and unbonding
+    pub fn holdings(&self)->u128{
+        self.balance as u128+self.staked as u128+self.unbonding.iter().map(|u| u.amount as u128).sum::<u128>()
+    }
+
+    /// Burn `fraction_bps` of the liquid, bonded and unbonding funds; returns the total burned
+    fn slash(&mut self,fraction_bps:u64)->u64{
+        let cut=|amount:&mut u64| {
+            let penalty=(*amount as u128*fraction_bps as u128/BPS_SCALE as u128) as u64;
+            *amount-=penalty;
+            penalty
+        };
+        let mut total=cut(&mut self.balance)+cut(&mut self.staked);
+        for entry in &mut self.unbonding{
+            total+=cut(&mut entry.amount);
+        }
+        total
+    }
+
+    /// Move unbonding entries released by `epoch` into the liquid balance. An entry that
+    /// would overflow the balance stays locked.
+    fn release_unbonded(&mut self,epoch:u64){
+        let balance=&mut self.balance;
+        self.unbonding.retain(|u| {
+            if u.release_epoch>epoch{
+                return true
+            }
+            match balance.checked_add(u.amount){
+                Some(released)=>{
+                    *balance=released;
+                    false
+                }
+                None=>true,
+            }
+        });
+    }
+
+    /// Value committed to the state tree: sha256 of the canonical encoding
+    pub fn state_hash(&self)->Hash{
+        Sha256::digest(crate::canonical::encode(self)).into()
+    }
+}
+
+/// Position of `address` in the state tree
+pub fn account_key(address:&str)->Hash{
+    Sha256::digest(address.as_bytes()).into()
+}
+
+/// Merkle proof that an account has a given value, or doesn't exist, under a state root
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct AccountProof{
+    pub address:String,
+    pub proof:SmtProof,
+}
+
+impl AccountProof{
+    /// Check `account` (None = absent) against `state_root`; needs no other state
+    pub fn verify(&self,state_root:&Hash,account:Option<&Account>)->bool{
+        let value=account.map(Account::state_hash);
+        self.proof.verify(state_root,&account_key(&self.address),value.as_ref())
     }
 }
 
 /// Global chain state (ledger)
-#[derive(Debug,Clone)]
+#[derive(Debug,Clone,Default)]
 pub struct State{
-    ///address -> account
-    accounts:HashMap<String,Account>,
+    ///address -> account, in address order so accounts can be paged through. Persistent, so
+    /// copies of the state (published read views) share every unchanged account
+    accounts:OrdMap<String,Account>,
+    /// Registered validators
+    validators:ValidatorRegistry,
+    /// Current consensus epoch (advanced by the block processor)
+    epoch:u64,
+    /// Epochs between an unstake and the release of its funds
+    unbonding_epochs:u64,
+    /// Accounts a block leaves below this balance (and otherwise empty) are removed
+    min_account_balance:u64,
+    /// Height and timestamp of the block being applied (set by the block processor)
+    block_height:u64,
+    block_time:u64,
+    /// Size limits every included transaction must respect
+    tx_limits:TxLimits,
+    /// How block fees are split between proposer, reward pool and burn
+    fee_policy:FeePolicy,
+    /// epoch -> fees routed to that epoch's reward pool, not yet settled
+    fee_pools:OrdMap<u64,u64>,
+    /// Issued assets by symbol
+    assets:OrdMap<String,AssetInfo>,
+    /// Registered account names
+    names:NameRegistry,
+    /// (offender, height) pairs already punished, so evidence can't be replayed
+    slashed:OrdSet<(String,u64)>,
+    /// epoch -> validator -> recorded metric report
+    metric_reports:OrdMap<u64,OrdMap<String,MetricReport>>,
+    /// Epochs whose rewards have been paid out
+    settled_epochs:OrdSet<u64>,
+    /// proposal id -> voter -> approve
+    governance_votes:OrdMap<u64,OrdMap<String,bool>>,
+    /// Live snapshots, oldest first; writes are journaled into the newest one
+    layers:Vec<Layer>,
+    next_snapshot:u64,
+    /// Accounts changed since the last `persist`
+    dirty:BTreeSet<String>,
+    /// Sparse Merkle tree over accounts, as of the last `commit_state_root`
+    tree:SparseMerkleTree,
+    /// Accounts changed since the last `commit_state_root`
+    tree_pending:BTreeSet<String>,
+    state_root:Hash,
 }
 
 impl State{
     /// Create empty state
     pub fn new()-> Self{
         Self{
-            accounts:HashMap::new(),
+            accounts:OrdMap::new(),
+            validators:ValidatorRegistry::new(),
+            epoch:0,
+            unbonding_epochs:DEFAULT_UNBONDING_EPOCHS,
+            min_account_balance:0,
+            block_height:0,
+            block_time:0,
+            tx_limits:TxLimits::default(),
+            fee_policy:FeePolicy::default(),
+            fee_pools:OrdMap::new(),
+            assets:OrdMap::new(),
+            names:NameRegistry::new(),
+            slashed:OrdSet::new(),
+            metric_reports:OrdMap::new(),
+            settled_epochs:OrdSet::new(),
+            governance_votes:OrdMap::new(),
+            layers:Vec::new(),
+            next_snapshot:0,
+            dirty:BTreeSet::new(),
+            tree:SparseMerkleTree::new(),
+            tree_pending:BTreeSet::new(),
+            state_root:crate::smt::EMPTY,
         }
     }
 
     /// Create state with genesis balances
     pub fn with_genesis(genesis:Vec<(String,u64)>)->Self{
-        let mut accounts=HashMap::new();
+        let mut accounts=OrdMap::new();
         for (addr,balance) in genesis{
-            accounts.insert(addr,Account::new(balance))
+            accounts.insert(addr,Account::new(balance));
+        }
+        Self{
+            dirty:accounts.keys().cloned().collect(),
+            tree_pending:accounts.keys().cloned().collect(),
+            accounts,
+            ..Self::new()
+        }
+    }
+
+    /// Create state from a validated genesis file, including vesting schedules, starting at
+    /// the genesis height and epoch
+    pub fn from_genesis(genesis:&Genesis)->Self{
+        let accounts:OrdMap<String,Account>=genesis
+        .accounts
+        .iter()
+        .map(|g| {
+            let account=Account{
+                balance:g.balance,
+                nonce:g.nonce,
+                staked:g.staked,
+                unbonding:g.unbonding.clone(),
+                vesting:g.vesting.clone(),
+                assets:g.assets.clone(),
+            };
+            (g.address.clone(),account)
+        })
+        .collect();
+        Self{
+            dirty:accounts.keys().cloned().collect(),
+            tree_pending:accounts.keys().cloned().collect(),
+            accounts,
+            assets:genesis.assets.clone().into_iter().collect(),
+            epoch:genesis.epoch,
+            block_height:genesis.height,
+            ..Self::new()
         }
-        Self{accounts}
+    }
+
+    /// Dump every account as a genesis for a chain continuing from block `height`.
+    /// `State::from_genesis` of the result has the same accounts, and so the same state root.
+    /// Validators and other chain state aren't carried over.
+    pub fn export_genesis(&self,height:u64)->Genesis{
+        let accounts=self
+        .accounts
+        .iter()
+        .map(|(address,account)| GenesisAccount{
+            address:address.clone(),
+            balance:account.balance,
+            vesting:account.vesting.clone(),
+            nonce:account.nonce,
+            staked:account.staked,
+            unbonding:account.unbonding.clone(),
+            assets:account.assets.clone(),
+        })
+        .collect();
+        Genesis{height,epoch:self.epoch,assets:self.assets.clone().into_iter().collect(),accounts}
+    }
+
+    /// Rebuild the state from a store as of its committed height, which is returned with it
+    /// (`None` for an empty store). Node settings (`set_unbonding_epochs`, `set_tx_limits`,
+    /// ...) still have to be applied.
+    pub fn from_store(store:&dyn StateStore)->Result<(Self,Option<u64>),StoreError>{
+        let accounts:OrdMap<String,Account>=store.accounts()?.into_iter().collect();
+        let mut state=Self{
+            tree_pending:accounts.keys().cloned().collect(),
+            accounts,
+            ..Self::new()
+        };
+        if let Some(bytes)=store.chain_state()?{
+            let checkpoint=crate::canonical::decode(&bytes).map_err(|e| StoreError::Corrupt(e.to_string()))?;
+            state.restore(checkpoint);
+        }
+        state.commit_state_root();
+        Ok((state,store.committed_height()?))
+    }
+
+    /// Rebuild the state from a state snapshot (see `network::statesync`): its accounts and
+    /// the `encode_chain_state` of the same block. Call `commit_state_root` to check the
+    /// accounts; the chain state is checked against the block's `chain_state_hash`.
+    pub fn from_snapshot(accounts:BTreeMap<String,Account>,chain_state:&[u8])->Result<Self,DecodeError>{
+        let mut state=Self{
+            dirty:accounts.keys().cloned().collect(),
+            tree_pending:accounts.keys().cloned().collect(),
+            accounts:accounts.into_iter().collect(),
+            ..Self::new()
+        };
+        state.restore(crate::canonical::decode(chain_state)?);
+        Ok(state)
+    }
+
+    /// Write every account changed since the last call, and the rest of the chain state, to
+    /// `store` as one batch committed at `height`. Call after a block has been applied.
+    pub fn persist(&mut self,store:&mut dyn StateStore,height:u64)->Result<(),StoreError>{
+        let changes:Vec<(String,Option<Account>)>=self
+        .dirty
+        .iter()
+        .map(|addr| (addr.clone(),self.accounts.get(addr).cloned()))
+        .collect();
+        store.commit_block(height,&changes,&self.encode_chain_state())?;
+        self.dirty.clear();
+        Ok(())
+    }
+
+    /// Fold account changes since the last call into the state tree and return the new root.
+    /// Call once per block, after it has been applied; the result goes in the block header.
+    pub fn commit_state_root(&mut self)->Hash{
+        for address in std::mem::take(&mut self.tree_pending){
+            let value=self.accounts.get(&address).map(Account::state_hash);
+            self.tree.update(account_key(&address),value);
+        }
+        self.state_root=self.tree.root();
+        self.state_root
+    }
+
+    /// Root as of the last `commit_state_root`
+    pub fn state_root(&self)->Hash{
+        self.state_root
+    }
+
+    /// Canonical encoding of the state besides accounts: epoch, validators, names, assets,
+    /// fee pools, votes and what keeps evidence and settlements from being replayed.
+    /// Persisted with every block and shipped in state snapshots.
+    pub fn encode_chain_state(&self)->Vec<u8>{
+        crate::canonical::encode(&self.checkpoint())
+    }
+
+    /// SHA-256 of `encode_chain_state`, carried in block headers next to the state root
+    pub fn chain_state_hash(&self)->Hash{
+        Sha256::digest(self.encode_chain_state()).into()
+    }
+
+    /// Inclusion (or exclusion) proof for `address` against `state_root()`
+    pub fn get_proof(&self,address:&str)->AccountProof{
+        AccountProof{
+            address:address.to_string(),
+            proof:self.tree.prove(&account_key(address)),
+        }
+    }
+
+    /// Registered validator set
+    pub fn validators(&self)->&ValidatorRegistry{
+        &self.validators
+    }
+
+    /// Current consensus epoch
+    pub fn current_epoch(&self)->u64{
+        self.epoch
+    }
+
+    /// Advance to `epoch` (called at epoch boundaries; never moves backwards).
+    /// Unbonding stake whose release epoch has been reached becomes liquid.
+    pub fn set_epoch(&mut self,epoch:u64){
+        self.epoch=self.epoch.max(epoch);
+        let released:Vec<String>=self
+        .accounts
+        .iter()
+        .filter(|(_,a)| a.unbonding.iter().any(|u| u.release_epoch<=self.epoch))
+        .map(|(addr,_)| addr.clone())
+        .collect();
+        let epoch=self.epoch;
+        for address in released{
+            self.account_mut(&address).release_unbonded(epoch);
+        }
+    }
+
+    /// Change the unbonding period; applies to unstakes made from now on
+    pub fn set_unbonding_epochs(&mut self,epochs:u64){
+        self.unbonding_epochs=epochs;
+    }
+
+    /// Dust policy: transfers that would leave a receiver with less than `min` and nothing
+    /// else (no nonce, stake, unbonding, vesting or assets) are rejected, and an account a
+    /// block debits below `min` (a fee payer, a slashed offender) is removed and its balance
+    /// burned. Credits alone never remove an account, so fee shares aren't reaped.
+    /// 0 disables the policy, 1 only rejects and removes empty accounts.
+    /// Accounts that ever sent a transaction are kept so their nonce can't be reset.
+    pub fn set_min_account_balance(&mut self,min:u64){
+        self.min_account_balance=min;
+    }
+
+    /// Bonded stake of every registered validator, for stake-weighted selection
+    /// (see `PoiScorer::set_stake`)
+    pub fn bonded_stakes(&self)->BTreeMap<String,u64>{
+        self.validators
+        .iter()
+        .map(|v| (v.address.clone(),self.get_stake(&v.address)))
+        .collect()
+    }
+
+    /// Change how block fees are routed (every node must use the same policy)
+    pub fn set_fee_policy(&mut self,policy:FeePolicy){
+        self.fee_policy=policy;
+    }
+
+    /// Fees routed to the reward pool of `epoch` and not yet paid out
+    pub fn fee_pool(&self,epoch:u64)->u64{
+        self.fee_pools.get(&epoch).copied().unwrap_or(0)
+    }
+
+    /// Replace the transaction size limits (a chain parameter: every node must agree)
+    pub fn set_tx_limits(&mut self,limits:TxLimits){
+        self.tx_limits=limits;
+    }
+
+    /// Set the height and timestamp of the block whose transactions are applied next.
+    /// Time locks are checked against these.
+    pub fn set_block_context(&mut self,height:u64,timestamp:u64){
+        self.block_height=height;
+        self.block_time=timestamp;
+    }
+
+    /// True if a transaction locked with `lock` may be included in the current block
+    pub fn is_unlocked(&self,lock:&TimeLock)->bool{
+        lock.is_unlocked(self.block_height,self.block_time)
+    }
+
+    /// Blocks (height lock) or seconds (timestamp lock) until `lock` ends
+    pub fn lock_remaining(&self,lock:&TimeLock)->u64{
+        lock.remaining(self.block_height,self.block_time)
+    }
+
+    /// Apply the minimum PoI score rule to the registry at an epoch boundary
+    /// (see `ValidatorRegistry::update_eligibility`). Returns newly jailed validators.
+    pub fn update_validator_eligibility(&mut self,scores:&HashMap<String,u64>,min_score:u64,unjail_epochs:u64)->Vec<String>{
+        self.validators.update_eligibility(scores,min_score,unjail_epochs)
+    }
+
+    /// Metric reports recorded on chain for `epoch`, keyed by validator
+    pub fn metric_reports(&self,epoch:u64)->Option<&OrdMap<String,MetricReport>>{
+        self.metric_reports.get(&epoch)
+    }
+
+    /// Selection pool for `epoch` built from on-chain metric reports of validators active at
+    /// that epoch. Every node derives the same pool from the same chain.
+    pub fn metrics_pool(&self,epoch:u64)->HashMap<String,NodeMetrics>{
+        self.metric_reports
+        .get(&epoch)
+        .map(|reports| {
+            reports
+            .iter()
+            .filter(|(addr,_)| self.validators.is_active(addr,epoch))
+            .map(|(addr,report)| (addr.clone(),report.effective_metrics(&self.validators)))
+            .collect()
+        })
+        .unwrap_or_default()
+    }
+
+    /// End-of-epoch settlement: split `reward_pool`, plus the fees routed to this epoch's
+    /// pool, across validators active at `epoch` proportionally to their PoI `scores` and
+    /// credit their accounts. If nobody is eligible, nothing is paid and the routed fees carry
+    /// over to the pool of the next unsettled epoch.
+    /// The returned summary should be recorded on chain (see `Blockchain::add_epoch_summary`).
+    pub fn settle_epoch(&mut self,epoch:u64,reward_pool:u64,scores:&HashMap<String,u64>)->Result<EpochSummary,StateError>{
+        if self.settled_epochs.contains(&epoch){
+            return Err(StateError::EpochAlreadySettled)
+        }
+        let active:HashMap<String,u64>=scores
+        .iter()
+        .filter(|(addr,_)| self.validators.is_active(addr,epoch))
+        .map(|(addr,score)| (addr.clone(),*score))
+        .collect();
+        let fees=self.fee_pool(epoch);
+        let rewards=distribute(reward_pool.saturating_add(fees),&active);
+        if rewards.is_empty(){
+            let next=(epoch+1..).find(|e| !self.settled_epochs.contains(e)).expect("finitely many settled epochs");
+            let carried=self.fee_pool(next).checked_add(fees).ok_or(StateError::BalanceOverflow)?;
+            self.fee_pools.remove(&epoch);
+            if carried>0{
+                self.fee_pools.insert(next,carried);
+            }
+            self.settled_epochs.insert(epoch);
+            return Ok(EpochSummary{epoch,reward_pool,rewards})
+        }
+        let reward_pool=reward_pool.saturating_add(fees);
+        if rewards.iter().any(|(addr,amount)| self.get_balance(addr).checked_add(*amount).is_none()){
+            return Err(StateError::BalanceOverflow)
+        }
+        for (addr,amount) in &rewards{
+            self.credit(addr,*amount)?;
+        }
+        self.fee_pools.remove(&epoch);
+        self.settled_epochs.insert(epoch);
+        Ok(EpochSummary{epoch,reward_pool,rewards})
+    }
+
+    /// Accounts in address order, starting after `start_after` (exclusive), at most `limit`.
+    /// Pass the last address of a page to get the next one.
+    pub fn accounts_iter(&self,start_after:Option<&str>,limit:usize)->impl Iterator<Item=(&str,&Account)>{
+        let start=match start_after{
+            Some(address)=>Bound::Excluded(address),
+            None=>Bound::Unbounded,
+        };
+        self.accounts
+        .range::<_,str>((start,Bound::Unbounded))
+        .take(limit)
+        .map(|(address,account)| (address.as_str(),account))
+    }
+
+    /// The `n` accounts holding the most (liquid + bonded + unbonding), richest first;
+    /// ties go to the lower address
+    pub fn top_accounts(&self,n:usize)->Vec<(&str,&Account)>{
+        let mut ranked:Vec<(&str,&Account)>=self.accounts.iter().map(|(a,acc)| (a.as_str(),acc)).collect();
+        ranked.sort_by(|a,b| b.1.holdings().cmp(&a.1.holdings()).then(a.0.cmp(b.0)));
+        ranked.truncate(n);
+        ranked
+    }
+
+    pub fn account_count(&self)->usize{
+        self.accounts.len()
+    }
+
+    /// Sum of every account's holdings; fees routed to reward pools but not yet paid out
+    /// aren't included
+    pub fn total_supply(&self)->u128{
+        self.accounts.values().map(Account::holdings).sum()
+    }
+
+    /// Balance of an address that isn't locked by vesting at the current block height
+    pub fn get_spendable_balance(&self,address:&str)->u64{
+        self.accounts
+        .get(address)
+        .map(|a| a.spendable_at(self.block_height))
+        .unwrap_or(0)
     }
 
     /// Get balance of an address
@@ -58,6 +728,70 @@ impl State{
         .unwrap_or(0)
     }
 
+    /// Registered account names
+    pub fn names(&self)->&NameRegistry{
+        &self.names
+    }
+
+    /// Address registered under `name` (with or without the leading `@`) at the current height
+    pub fn resolve_name(&self,name:&str)->Option<&str>{
+        let name=name.strip_prefix(NAME_PREFIX).unwrap_or(name);
+        self.names.resolve(name,self.block_height)
+    }
+
+    /// Address a transaction receiver refers to: `@name` is looked up, anything else is
+    /// taken as an address
+    pub fn resolve_receiver(&self,receiver:&str)->Result<String,StateError>{
+        match receiver.strip_prefix(NAME_PREFIX){
+            Some(name)=>Ok(self.names.resolve(name,self.block_height).ok_or(NameError::Unregistered)?.to_string()),
+            None=>Ok(receiver.to_string()),
+        }
+    }
+
+    /// Registry entry of an issued asset
+    pub fn asset(&self,asset:&str)->Option<&AssetInfo>{
+        self.assets.get(asset)
+    }
+
+    /// Balance of `asset` held by an address (the native asset is `get_balance`)
+    pub fn get_asset_balance(&self,address:&str,asset:&str)->u64{
+        self.accounts
+        .get(address)
+        .map(|a| a.asset_balance(asset))
+        .unwrap_or(0)
+    }
+
+    /// Bonded stake of an address
+    pub fn get_stake(&self,address:&str)->u64{
+        self.accounts
+        .get(address)
+        .map(|a| a.staked)
+        .unwrap_or(0)
+    }
+
+    /// Unstaked funds of an address still waiting for release
+    pub fn get_unbonding(&self,address:&str)->u64{
+        self.accounts
+        .get(address)
+        .map(Account::unbonding_total)
+        .unwrap_or(0)
+    }
+
+    /// Stake-weighted tally of a governance proposal: (approve, reject).
+    /// Uses each voter's current bond, so unstaking also withdraws voting weight.
+    pub fn proposal_tally(&self,proposal_id:u64)->(u64,u64){
+        let mut tally=(0,0);
+        for (voter,approve) in self.governance_votes.get(&proposal_id).into_iter().flatten(){
+            let weight=self.get_stake(voter);
+            if *approve{
+                tally.0+=weight;
+            }else{
+                tally.1+=weight;
+            }
+        }
+        tally
+    }
+
     /// Get nonce of an address
     pub fn get_nonce(&self,address:&str)->u64{
         self.accounts
@@ -69,12 +803,35 @@ impl State{
     /// Validate a signed transaction WITHOUT mutating state
     pub fn validate_transaction(&self,tx:&SignedTransaction)->Result<(),StateError>{
         // cryptographic verification
-        tx.verify().map_err(|_| StateError::InvalidSignature)?;
+        tx.check_limits(&self.tx_limits).map_err(StateError::ExceedsLimit)?;
+        tx.verify()?;
         
         let t:&Transaction=&tx.tx;
-        if t.amount==0{
-            return Err(StateError::ZeroAmount)
-        }
+        let amount=match &t.payload{
+            TxPayload::Transfer{amount,..} | TxPayload::Stake{amount}=>{
+                if *amount==0{
+                    return Err(StateError::ZeroAmount)
+                }
+                *amount
+            }
+            TxPayload::MultiTransfer{outputs}=>{
+                if outputs.is_empty(){
+                    return Err(StateError::EmptyBatch)
+                }
+                if outputs.len()>MAX_TRANSFER_OUTPUTS{
+                    return Err(StateError::TooManyOutputs)
+                }
+                if outputs.iter().any(|o| o.amount==0){
+                    return Err(StateError::ZeroAmount)
+                }
+                outputs
+                .iter()
+                .try_fold(0u64,|total,o| total.checked_add(o.amount))
+                .ok_or(StateError::AmountOverflow)?
+            }
+            TxPayload::RegisterName{..}=>NAME_REGISTRATION_COST,
+            _=>0,
+        };
         let sender=self
         .accounts
         .get(&t.sender)
@@ -84,53 +841,660 @@ impl State{
         if t.nonce!=sender.nonce{
             return Err(StateError::InvalidNonce)
         }
+        if sender.nonce==u64::MAX{
+            return Err(StateError::NonceOverflow)
+        }
+
+        if t.not_before.is_some_and(|lock| !self.is_unlocked(&lock)){
+            return Err(StateError::TimeLocked)
+        }
+
+        // payload-specific checks
+        match &t.payload{
+            TxPayload::Transfer{..}=>{}
+            TxPayload::RegisterValidator{consensus_pubkey,vrf_pubkey,endpoint}=>{
+                if self.validators.is_jailed(&t.sender,self.epoch){
+                    return Err(StateError::ValidatorRegistry(RegistryError::Jailed))
+                }
+                self.validators
+                .validate_register(&t.sender,consensus_pubkey,vrf_pubkey,endpoint)
+                .map_err(StateError::ValidatorRegistry)?;
+            }
+            TxPayload::UnregisterValidator=>{
+                self.validators
+                .validate_unregister(&t.sender)
+                .map_err(StateError::ValidatorRegistry)?;
+            }
+            TxPayload::Evidence(evidence)=>{
+                if self.slashed.contains(&(evidence.offender().to_string(),evidence.height())){
+                    return Err(StateError::InvalidEvidence(EvidenceError::AlreadyProcessed))
+                }
+                evidence
+                .verify(&self.validators)
+                .map_err(StateError::InvalidEvidence)?;
+            }
+            TxPayload::MetricReport(report)=>{
+                report
+                .verify(&t.sender,self.epoch,&self.validators)
+                .map_err(StateError::InvalidMetricReport)?;
+                if self.metric_reports.get(&report.epoch).is_some_and(|r| r.contains_key(&t.sender)){
+                    return Err(StateError::InvalidMetricReport(MetricReportError::DuplicateReport))
+                }
+            }
+            TxPayload::Stake{amount}=>{
+                if sender.staked.checked_add(*amount).is_none(){
+                    return Err(StateError::BalanceOverflow)
+                }
+            }
+            TxPayload::Unstake{amount}=>{
+                if *amount==0{
+                    return Err(StateError::ZeroAmount)
+                }
+                if sender.staked<*amount{
+                    return Err(StateError::InsufficientStake)
+                }
+            }
+            TxPayload::GovernanceVote{..}=>{
+                if sender.staked==0{
+                    return Err(StateError::NoStake)
+                }
+            }
+            TxPayload::RegisterBlsKey{bls_pubkey,proof_of_possession}=>{
+                self.validators
+                .validate_bls_key(&t.sender,bls_pubkey,proof_of_possession)
+                .map_err(StateError::ValidatorRegistry)?;
+            }
+            TxPayload::Cancel | TxPayload::MultiTransfer{..}=>{}
+            TxPayload::IssueAsset{asset,supply}=>{
+                if !is_valid_asset_id(asset){
+                    return Err(StateError::InvalidAssetId)
+                }
+                if self.assets.contains_key(asset){
+                    return Err(StateError::AssetExists)
+                }
+                if *supply==0{
+                    return Err(StateError::ZeroAmount)
+                }
+            }
+            TxPayload::TransferAsset{asset,receiver,amount}=>{
+                if !self.assets.contains_key(asset){
+                    return Err(StateError::UnknownAsset)
+                }
+                if *amount==0{
+                    return Err(StateError::ZeroAmount)
+                }
+                // holdings never exceed the asset's u64 supply, so the credit can't overflow
+                if sender.asset_balance(asset)<*amount{
+                    return Err(StateError::InsufficientAssetBalance)
+                }
+                self.resolve_receiver(receiver)?;
+            }
+            TxPayload::RegisterName{name}=>{
+                self.names.validate_register(name,&t.sender,self.block_height)?;
+            }
+        }
+
+        // balance check (amount + fee, unless a fee payer covers the fee), then the same
+        // against what vesting leaves spendable
+        let debits=match &t.fee_payer{
+            Some(payer)=>{
+                let payer=self.accounts.get(payer).ok_or(StateError::FeePayerNotFound)?;
+                if sender.balance<amount || payer.balance<t.fee{
+                    return Err(StateError::InsufficientBalance)
+                }
+                vec![(sender,amount),(payer,t.fee)]
+            }
+            None=>{
+                let total=amount.checked_add(t.fee).ok_or(StateError::AmountOverflow)?;
+                if sender.balance<total{
+                    return Err(StateError::InsufficientBalance)
+                }
+                vec![(sender,total)]
+            }
+        };
+        if debits.iter().any(|(account,debit)| account.spendable_at(self.block_height)<*debit){
+            return Err(StateError::VestingLocked)
+        }
 
-        // balance check (amount + fee)
-        let required=t.amount + t.fee;
-        if sender.balance<required{
-            return Err(StateError::InsufficientBalance)
+        // receivers must be able to take the credit (paying yourself can't overflow)
+        let mut credits:BTreeMap<String,u64>=BTreeMap::new();
+        match &t.payload{
+            TxPayload::Transfer{receiver,amount}=>{
+                credits.insert(self.resolve_receiver(receiver)?,*amount);
+            }
+            TxPayload::MultiTransfer{outputs}=>{
+                for output in outputs{
+                    // bounded by the checked output total above
+                    *credits.entry(self.resolve_receiver(&output.receiver)?).or_insert(0)+=output.amount;
+                }
+            }
+            _=>{}
+        }
+        credits.remove(&t.sender);
+        if credits.iter().any(|(receiver,credit)| self.get_balance(receiver).checked_add(*credit).is_none()){
+            return Err(StateError::BalanceOverflow)
+        }
+        // existential deposit: a receiver must end up with at least the minimum balance
+        if let Some((receiver,_))=credits.iter().find(|(receiver,credit)| {
+            self.get_balance(receiver)+*credit<self.min_account_balance
+            && self.accounts.get(*receiver).is_none_or(Account::is_bare)
+        }){
+            return Err(StateError::BelowMinimumBalance(receiver.clone()))
         }
 
         Ok(())
     }
     
 
-    /// Apply a signed transaction (Mutates state)
-    pub fn apply_transaction(&mut self,tx:&SignedTransaction)->Result<(),StateError>{
+    /// Apply a signed transaction (Mutates state) and return the events it emitted
+    pub fn apply_transaction(&mut self,tx:&SignedTransaction)->Result<Vec<Event>,StateError>{
         self.validate_transaction(tx)?;
+        let mut events=Vec::new();
 
         let t=&tx.tx;
-        // subtract from sender
-        let sender=self
-        .accounts
-        .get_mut(&t.sender)
-        .expect("Sender must exist after validation");
-        sender.balance-=t.amount+t.fee;
-        sender.nonce+=1;
-
-        // add to receiver
-        let receiver=self
-        .account
-        .entry(t.receiver.clone())
-        .or_insert(Account::new(0));
-        receiver.balance+=t.amount;
+        let amount=match &t.payload{
+            TxPayload::Transfer{amount,..} | TxPayload::Stake{amount}=>*amount,
+            TxPayload::MultiTransfer{outputs}=>outputs.iter().map(|o| o.amount).sum(),
+            // burned: debited and never credited
+            TxPayload::RegisterName{..}=>NAME_REGISTRATION_COST,
+            _=>0,
+        };
+        // subtract from sender; validation guarantees none of the checked operations below fail
+        self.debit(&t.sender,amount)?;
+        let sender=self.account_mut(&t.sender);
+        sender.nonce=sender.nonce.checked_add(1).ok_or(StateError::NonceOverflow)?;
+        self.debit(t.fee_payer.as_ref().unwrap_or(&t.sender),t.fee)?;
+        if t.fee>0{
+            events.push(Event::FeePaid{payer:t.fee_payer.clone().unwrap_or_else(|| t.sender.clone()),amount:t.fee});
+        }
+
+        match &t.payload{
+            TxPayload::Transfer{receiver,amount}=>{
+                // add to receiver
+                let receiver=self.resolve_receiver(receiver)?;
+                self.credit(&receiver,*amount)?;
+                events.push(Event::Transfer{from:t.sender.clone(),to:receiver,amount:*amount});
+            }
+            TxPayload::RegisterValidator{consensus_pubkey,vrf_pubkey,endpoint}=>{
+                self.validators
+                .register(t.sender.clone(),consensus_pubkey.clone(),vrf_pubkey.clone(),endpoint.clone())
+                .expect("Registration must succeed after validation");
+                events.push(Event::ValidatorRegistered{address:t.sender.clone()});
+            }
+            TxPayload::UnregisterValidator=>{
+                self.validators
+                .unregister(&t.sender)
+                .expect("Unregistration must succeed after validation");
+                events.push(Event::ValidatorUnregistered{address:t.sender.clone()});
+            }
+            TxPayload::Evidence(evidence)=>{
+                let offender=evidence.offender().to_string();
+                // burn a fraction of the offender's liquid, bonded and unbonding funds
+                if self.accounts.contains_key(&offender){
+                    let penalty=self.account_mut(&offender).slash(SLASH_FRACTION_BPS);
+                    events.push(Event::Slashed{offender:offender.clone(),amount:penalty});
+                }
+                self.validators.jail(&offender,self.epoch+JAIL_EPOCHS);
+                events.push(Event::Jailed{address:offender.clone(),until_epoch:self.epoch+JAIL_EPOCHS});
+                self.slashed.insert((offender,evidence.height()));
+            }
+            TxPayload::MetricReport(report)=>{
+                self.metric_reports
+                .entry(report.epoch)
+                .or_default()
+                .insert(t.sender.clone(),report.clone());
+                events.push(Event::MetricReported{validator:t.sender.clone(),epoch:report.epoch});
+            }
+            TxPayload::Stake{amount}=>{
+                let sender=self.account_mut(&t.sender);
+                sender.staked=sender.staked.checked_add(*amount).ok_or(StateError::BalanceOverflow)?;
+                events.push(Event::Staked{address:t.sender.clone(),amount:*amount});
+            }
+            TxPayload::Unstake{amount}=>{
+                let release_epoch=self.epoch+self.unbonding_epochs;
+                let sender=self.account_mut(&t.sender);
+                sender.staked=sender.staked.checked_sub(*amount).ok_or(StateError::InsufficientStake)?;
+                sender.unbonding.push(Unbonding{amount:*amount,release_epoch});
+                events.push(Event::Unstaked{address:t.sender.clone(),amount:*amount,release_epoch});
+            }
+            TxPayload::GovernanceVote{proposal_id,approve}=>{
+                self.governance_votes
+                .entry(*proposal_id)
+                .or_default()
+                .insert(t.sender.clone(),*approve);
+                events.push(Event::GovernanceVoted{voter:t.sender.clone(),proposal_id:*proposal_id,approve:*approve});
+            }
+            TxPayload::RegisterBlsKey{bls_pubkey,proof_of_possession}=>{
+                self.validators
+                .set_bls_key(&t.sender,bls_pubkey.clone(),proof_of_possession)
+                .expect("BLS key registration must succeed after validation");
+                events.push(Event::BlsKeyRegistered{validator:t.sender.clone()});
+            }
+            TxPayload::Cancel=>{}
+            TxPayload::MultiTransfer{outputs}=>{
+                // every check happened in validation, so crediting can't fail halfway
+                for output in outputs{
+                    let receiver=self.resolve_receiver(&output.receiver)?;
+                    self.credit(&receiver,output.amount)?;
+                    events.push(Event::Transfer{from:t.sender.clone(),to:receiver,amount:output.amount});
+                }
+            }
+            TxPayload::IssueAsset{asset,supply}=>{
+                self.assets.insert(asset.clone(),AssetInfo{issuer:t.sender.clone(),supply:*supply});
+                self.account_mut(&t.sender).assets.insert(asset.clone(),*supply);
+                events.push(Event::AssetIssued{asset:asset.clone(),issuer:t.sender.clone(),supply:*supply});
+            }
+            TxPayload::TransferAsset{asset,receiver,amount}=>{
+                let sender=self.account_mut(&t.sender);
+                let remaining=sender.asset_balance(asset)-amount;
+                if remaining==0{
+                    sender.assets.remove(asset);
+                }else{
+                    sender.assets.insert(asset.clone(),remaining);
+                }
+                let receiver=self.resolve_receiver(receiver)?;
+                *self.account_mut(&receiver).assets.entry(asset.clone()).or_insert(0)+=amount;
+                events.push(Event::AssetTransfer{asset:asset.clone(),from:t.sender.clone(),to:receiver,amount:*amount});
+            }
+            TxPayload::RegisterName{name}=>{
+                let expires_at=self.names.register(name,&t.sender,self.block_height)?;
+                events.push(Event::NameRegistered{
+                    name:name.clone(),
+                    owner:t.sender.clone(),
+                    expires_at,
+                    burned:NAME_REGISTRATION_COST,
+                });
+            }
+        }
         // Note: fee handling (burn / validator reward) happens at block level
+        Ok(events)
+    }
+
+    /// Apply the transaction at position `index` of block `block_height` and describe the
+    /// outcome. A failed transaction leaves the state untouched.
+    pub fn apply_with_receipt(&mut self,tx:&SignedTransaction,gas:&GasSchedule,block_height:u64,index:u32)->Receipt{
+        let (status,events)=match self.apply_transaction(tx){
+            Ok(events)=>(ReceiptStatus::Success,events),
+            Err(e)=>(ReceiptStatus::Failed{reason:e.to_string()},Vec::new()),
+        };
+        Receipt{
+            tx_hash:tx.tx_hash_hex(),
+            sender:tx.tx.sender.clone(),
+            status,
+            gas_used:gas.gas_used(tx),
+            events,
+            block_height,
+            index,
+        }
+    }
+
+    /// Preflight a transaction: validate and apply it under a snapshot, report what changed
+    /// and revert. The state is left as it was, pending writes included.
+    pub fn simulate_transaction(&mut self,tx:&SignedTransaction,gas:&GasSchedule)->Simulation{
+        let t=&tx.tx;
+        let mut touched=vec![t.sender.clone()];
+        touched.extend(t.fee_payer.clone());
+        match &t.payload{
+            TxPayload::Transfer{receiver,..}=>touched.push(receiver.clone()),
+            TxPayload::Evidence(evidence)=>touched.push(evidence.offender().to_string()),
+            TxPayload::MultiTransfer{outputs}=>touched.extend(outputs.iter().map(|o| o.receiver.clone())),
+            TxPayload::TransferAsset{receiver,..}=>touched.push(receiver.clone()),
+            _=>{}
+        }
+
+        // report names as the addresses they stand for
+        for address in &mut touched{
+            if let Ok(resolved)=self.resolve_receiver(address){
+                *address=resolved;
+            }
+        }
+
+        let before:Vec<(u64,String)>=touched.into_iter().map(|addr| (self.get_balance(&addr),addr)).collect();
+        // reverting marks what it restores as changed; nothing is, so keep the old sets
+        let (dirty,tree_pending)=(self.dirty.clone(),self.tree_pending.clone());
+        let snapshot=self.snapshot();
+        let result=self.apply_transaction(tx).map(|_| ());
+        let balance_changes=before
+        .into_iter()
+        .map(|(balance,addr)| {
+            let delta=self.get_balance(&addr) as i128-balance as i128;
+            (addr,delta)
+        })
+        .filter(|(_,delta)| *delta!=0)
+        .collect();
+        self.revert_to(snapshot).expect("simulation snapshot is live");
+        (self.dirty,self.tree_pending)=(dirty,tree_pending);
+        Simulation{gas_used:gas.gas_used(tx),balance_changes,result}
+    }
+
+    /// Apply a block's transactions all-or-nothing: if any transaction fails, every change
+    /// made by the earlier ones is rolled back. On success the block's fees are routed
+    /// according to the fee policy: `proposer`'s share is credited, the reward pool share
+    /// is added to the current epoch's pool and the rest is burned.
+    pub fn apply_block(&mut self,proposer:&str,txs:&[SignedTransaction])->Result<BlockOutcome,BlockApplyError>{
+        let snapshot=self.snapshot();
+        let mut events=Vec::with_capacity(txs.len());
+        for (index,tx) in txs.iter().enumerate(){
+            match self.apply_transaction(tx){
+                Ok(tx_events)=>events.push(tx_events),
+                Err(error)=>{
+                    self.revert_to(snapshot).expect("block snapshot is live");
+                    return Err(BlockApplyError{index,error})
+                }
+            }
+        }
+
+        // every fee was paid out of a u64 balance, but the block total needs checking
+        let total_fees=txs
+        .iter()
+        .try_fold(0u64,|total,tx| total.checked_add(tx.tx.fee));
+        let routed=total_fees.ok_or(StateError::AmountOverflow).and_then(|total| {
+            let fees=self.fee_policy.split(total);
+            self.credit(proposer,fees.proposer)?;
+            let pool=self.fee_pools.entry(self.epoch).or_insert(0);
+            *pool=pool.checked_add(fees.reward_pool).ok_or(StateError::BalanceOverflow)?;
+            Ok(fees)
+        });
+        let result=routed.and_then(|fees| {
+            let dust=self.remove_dust();
+            self.check_supply_change(&fees,&events,&dust)?;
+            Ok((fees,dust))
+        });
+        match result{
+            Ok((fees,dust))=>{
+                let diff=self.block_diff(dust);
+                self.release(snapshot).expect("block snapshot is live");
+                Ok(BlockOutcome{events,fees,diff})
+            }
+            Err(error)=>{
+                self.revert_to(snapshot).expect("block snapshot is live");
+                Err(BlockApplyError{index:txs.len(),error})
+            }
+        }
+    }
+
+    /// Remove the accounts the block drained into dust; returns the burned balances.
+    /// Only accounts whose balance the block lowered are candidates.
+    fn remove_dust(&mut self)->BTreeMap<String,u64>{
+        if self.min_account_balance==0{
+            return BTreeMap::new()
+        }
+        let layer=self.layers.last().expect("block snapshot is live");
+        let dust:BTreeMap<String,u64>=layer
+        .originals
+        .iter()
+        .filter_map(|(address,original)| {
+            let account=self.accounts.get(address)?;
+            let drained=original.as_ref().is_some_and(|original| account.balance<original.balance);
+            (drained && account.is_dust(self.min_account_balance)).then(|| (address.clone(),account.balance))
+        })
+        .collect();
+        for address in dust.keys(){
+            self.account_mut(address);
+            self.accounts.remove(address);
+        }
+        dust
+    }
+
+    /// Supply invariant: accounts may only lose what the block burned (fees, name
+    /// registrations), slashed, moved into the epoch reward pool or removed as dust. Only accounts journaled in the block's
+    /// snapshot (the newest one) can have changed.
+    fn check_supply_change(&self,fees:&FeeSplit,events:&[Vec<Event>],dust:&BTreeMap<String,u64>)->Result<(),StateError>{
+        let layer=self.layers.last().expect("block snapshot is live");
+        let actual:i128=layer
+        .originals
+        .iter()
+        .map(|(address,original)| {
+            let after=self.accounts.get(address).map(Account::holdings).unwrap_or(0);
+            after as i128-original.as_ref().map(Account::holdings).unwrap_or(0) as i128
+        })
+        .sum();
+        // slashes and name registration costs
+        let destroyed:u128=events
+        .iter()
+        .flatten()
+        .map(|event| match event{
+            Event::Slashed{amount,..} | Event::NameRegistered{burned:amount,..}=>*amount as u128,
+            _=>0,
+        })
+        .sum();
+        let dust:u128=dust.values().map(|amount| *amount as u128).sum();
+        let expected=-((fees.burned as u128+fees.reward_pool as u128+destroyed+dust) as i128);
+        if actual!=expected{
+            return Err(StateError::SupplyMismatch{expected,actual})
+        }
+
+        // asset holdings only change by what the block issued
+        let mut asset_changes:BTreeMap<&str,i128>=BTreeMap::new();
+        for (address,original) in &layer.originals{
+            let after=self.accounts.get(address).map(|a| &a.assets);
+            let before=original.as_ref().map(|a| &a.assets);
+            for (assets,sign) in [(after,1),(before,-1)]{
+                for (asset,amount) in assets.into_iter().flatten(){
+                    *asset_changes.entry(asset).or_insert(0)+=sign*(*amount as i128);
+                }
+            }
+        }
+        for event in events.iter().flatten(){
+            if let Event::AssetIssued{asset,supply,..}=event{
+                *asset_changes.entry(asset).or_insert(0)-=*supply as i128;
+            }
+        }
+        if let Some((asset,_))=asset_changes.into_iter().find(|(_,change)| *change!=0){
+            return Err(StateError::AssetSupplyMismatch(asset.to_string()))
+        }
         Ok(())
     }
 
-    /// Apply multiple transactions atomically (used for blocks)
-    pub fn apply_transaction(&mut self,txs:&[SignedTransaction],)->Result<(),StateError>{
-        for tx in txs{
-            self.apply_transaction(tx)?;
+    /// Diff of the block being applied, from the originals journaled in its snapshot
+    fn block_diff(&self,dust_removed:BTreeMap<String,u64>)->StateDiff{
+        let layer=self.layers.last().expect("block snapshot is live");
+        let changes=layer
+        .originals
+        .iter()
+        .map(|(address,before)| {
+            (address.clone(),AccountChange{before:before.clone(),after:self.accounts.get(address).cloned()})
+        })
+        .filter(|(_,change)| change.before!=change.after)
+        .collect();
+        StateDiff{height:self.block_height,changes,dust_removed}
+    }
+
+    /// Undo the account changes of a block by restoring every `before` value. Diffs must be
+    /// reverted newest first; a diff that doesn't match the current accounts is rejected
+    /// without changing anything. Non-account state (validators, votes) isn't covered.
+    pub fn revert_diff(&mut self,diff:&StateDiff)->Result<(),StateError>{
+        if let Some((address,_))=diff
+        .changes
+        .iter()
+        .find(|(address,change)| self.accounts.get(*address)!=change.after.as_ref())
+        {
+            return Err(StateError::DiffMismatch(address.clone()))
+        }
+        for (address,change) in &diff.changes{
+            match &change.before{
+                Some(account)=>*self.account_mut(address)=account.clone(),
+                None=>{
+                    self.account_mut(address);
+                    self.accounts.remove(address);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn credit(&mut self,address:&str,amount:u64)->Result<(),StateError>{
+        if amount==0{
+            return Ok(())
+        }
+        let account=self.account_mut(address);
+        account.balance=account.balance.checked_add(amount).ok_or(StateError::BalanceOverflow)?;
+        Ok(())
+    }
+
+    fn debit(&mut self,address:&str,amount:u64)->Result<(),StateError>{
+        if amount==0{
+            return Ok(())
+        }
+        let account=self.account_mut(address);
+        account.balance=account.balance.checked_sub(amount).ok_or(StateError::InsufficientBalance)?;
+        Ok(())
+    }
+
+    /// Mutable account (created empty if missing). Marks it for `persist` and the state tree,
+    /// and journals its original value into the newest snapshot.
+    fn account_mut(&mut self,address:&str)->&mut Account{
+        if !self.dirty.contains(address){
+            self.dirty.insert(address.to_string());
+        }
+        if !self.tree_pending.contains(address){
+            self.tree_pending.insert(address.to_string());
+        }
+        if let Some(layer)=self.layers.last_mut(){
+            layer
+            .originals
+            .entry(address.to_string())
+            .or_insert_with(|| self.accounts.get(address).cloned());
+        }
+        self.accounts
+        .entry(address.to_string())
+        .or_insert_with(|| Account::new(0))
+    }
+
+    /// Mark the current state so it can be restored with `revert_to`. Snapshots nest;
+    /// reverting to one also discards every snapshot taken after it.
+    pub fn snapshot(&mut self)->SnapshotId{
+        let id=self.next_snapshot;
+        self.next_snapshot+=1;
+        self.layers.push(Layer{
+            id,
+            height:self.block_height,
+            originals:HashMap::new(),
+            checkpoint:self.checkpoint(),
+        });
+        SnapshotId{id,height:self.block_height}
+    }
+
+    /// Restore the state captured by `snapshot`, consuming it and every later snapshot
+    pub fn revert_to(&mut self,snapshot:SnapshotId)->Result<(),SnapshotError>{
+        let position=self.layer_position(snapshot)?;
+        while self.layers.len()>position{
+            let layer=self.layers.pop().expect("len checked above");
+            for (address,original) in layer.originals{
+                match original{
+                    Some(account)=>self.accounts.insert(address.clone(),account),
+                    None=>self.accounts.remove(&address),
+                };
+                self.dirty.insert(address.clone());
+                self.tree_pending.insert(address);
+            }
+            self.restore(layer.checkpoint);
         }
         Ok(())
     }
+
+    /// Revert to the newest live snapshot taken at block height `height` (e.g. on a reorg)
+    pub fn revert_to_height(&mut self,height:u64)->Result<(),SnapshotError>{
+        let layer=self
+        .layers
+        .iter()
+        .rev()
+        .find(|l| l.height==height)
+        .ok_or(SnapshotError::NoSnapshotAtHeight(height))?;
+        self.revert_to(SnapshotId{id:layer.id,height})
+    }
+
+    /// Drop `snapshot` without reverting, keeping the changes made since. Changes it
+    /// journaled move to the snapshot before it, which can still undo them.
+    pub fn release(&mut self,snapshot:SnapshotId)->Result<(),SnapshotError>{
+        let position=self.layer_position(snapshot)?;
+        let layer=self.layers.remove(position);
+        if let Some(previous)=position.checked_sub(1).map(|p| &mut self.layers[p]){
+            for (address,original) in layer.originals{
+                previous.originals.entry(address).or_insert(original);
+            }
+        }
+        Ok(())
+    }
+
+    /// Number of live snapshots
+    pub fn snapshot_count(&self)->usize{
+        self.layers.len()
+    }
+
+    /// Copy of the state for readers. Accounts, the state tree and chain state are shared
+    /// with `self`, so a copy costs as much as what changed since the last one; live
+    /// snapshots and the writes pending `persist` / `commit_state_root` aren't carried.
+    pub fn read_view(&self)->State{
+        State{
+            accounts:self.accounts.clone(),
+            validators:self.validators.clone(),
+            epoch:self.epoch,
+            unbonding_epochs:self.unbonding_epochs,
+            min_account_balance:self.min_account_balance,
+            block_height:self.block_height,
+            block_time:self.block_time,
+            tx_limits:self.tx_limits,
+            fee_policy:self.fee_policy,
+            fee_pools:self.fee_pools.clone(),
+            assets:self.assets.clone(),
+            names:self.names.clone(),
+            slashed:self.slashed.clone(),
+            metric_reports:self.metric_reports.clone(),
+            settled_epochs:self.settled_epochs.clone(),
+            governance_votes:self.governance_votes.clone(),
+            layers:Vec::new(),
+            next_snapshot:self.next_snapshot,
+            dirty:BTreeSet::new(),
+            tree:self.tree.clone(),
+            tree_pending:BTreeSet::new(),
+            state_root:self.state_root,
+        }
+    }
+
+    fn checkpoint(&self)->Checkpoint{
+        Checkpoint{
+            epoch:self.epoch,
+            block_height:self.block_height,
+            block_time:self.block_time,
+            validators:self.validators.clone(),
+            slashed:self.slashed.clone(),
+            metric_reports:self.metric_reports.clone(),
+            settled_epochs:self.settled_epochs.clone(),
+            governance_votes:self.governance_votes.clone(),
+            fee_pools:self.fee_pools.clone(),
+            assets:self.assets.clone(),
+            names:self.names.clone(),
+        }
+    }
+
+    fn restore(&mut self,checkpoint:Checkpoint){
+        self.epoch=checkpoint.epoch;
+        self.block_height=checkpoint.block_height;
+        self.block_time=checkpoint.block_time;
+        self.validators=checkpoint.validators;
+        self.slashed=checkpoint.slashed;
+        self.metric_reports=checkpoint.metric_reports;
+        self.settled_epochs=checkpoint.settled_epochs;
+        self.governance_votes=checkpoint.governance_votes;
+        self.fee_pools=checkpoint.fee_pools;
+        self.assets=checkpoint.assets;
+        self.names=checkpoint.names;
+    }
+
+    fn layer_position(&self,snapshot:SnapshotId)->Result<usize,SnapshotError>{
+        self.layers
+        .iter()
+        .position(|l| l.id==snapshot.id)
+        .ok_or(SnapshotError::UnknownSnapshot)
+    }
 }
 
 #[cfg(test)]
-mod tests[
+mod tests{
     use super::*;
     use crate::transaction::{generate_ed25519_keypair,pubkey_to_address_hex,SignedTransaction};
+    use base64::{engine::general_purpose,Engine as _};
 
     #[test]
     fn test_basic_transfer(){
@@ -163,7 +1527,7 @@ mod tests[
         let kp=generate_ed25519_keypair();
         let addr=pubkey_to_address_hex(&kp.public);
 
-        let mut state=State::with_genesis(vec![(addr.clone(),1000)]);
+        let state=State::with_genesis(vec![(addr.clone(),1000)]);
 
         let tx=Transaction::new(
             addr.clone(),
@@ -181,4 +1545,770 @@ mod tests[
         ))
     }
 
-]
\ No newline at end of file
+    #[test]
+    fn test_register_and_unregister_validator(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let consensus_key=general_purpose::STANDARD.encode(generate_ed25519_keypair().public.to_bytes());
+
+        let mut state=State::with_genesis(vec![(addr.clone(),1000)]);
+
+        let register=Transaction::with_payload(
+            addr.clone(),
+            TxPayload::RegisterValidator{
+                consensus_pubkey:consensus_key,
+                vrf_pubkey:general_purpose::STANDARD.encode(schnorrkel::Keypair::generate().public.to_bytes()),
+                endpoint:"127.0.0.1:30333".to_string(),
+            },
+            5,
+            0,
+            None,
+        );
+        let signed=SignedTransaction::sign_with_keypair(&register,&kp);
+        assert!(state.apply_transaction(&signed).is_ok());
+        assert!(state.validators().is_registered(&addr));
+        assert_eq!(state.get_balance(&addr),995);
+
+        // registering twice is rejected
+        let mut again=register.clone();
+        again.nonce=1;
+        let signed=SignedTransaction::sign_with_keypair(&again,&kp);
+        assert!(matches!(
+            state.validate_transaction(&signed),
+            Err(StateError::ValidatorRegistry(RegistryError::AlreadyRegistered))
+        ));
+
+        let unregister=Transaction::with_payload(addr.clone(),TxPayload::UnregisterValidator,5,1,None);
+        let signed=SignedTransaction::sign_with_keypair(&unregister,&kp);
+        assert!(state.apply_transaction(&signed).is_ok());
+        assert!(!state.validators().is_registered(&addr));
+        assert_eq!(state.get_balance(&addr),990);
+    }
+
+    #[test]
+    fn test_evidence_slashes_and_jails(){
+        use crate::evidence::{DoubleSignEvidence,SignedHeader};
+
+        let val_kp=generate_ed25519_keypair();
+        let val_addr=pubkey_to_address_hex(&val_kp.public);
+        let consensus_kp=generate_ed25519_keypair();
+        let reporter_kp=generate_ed25519_keypair();
+        let reporter_addr=pubkey_to_address_hex(&reporter_kp.public);
+
+        let mut state=State::with_genesis(vec![(val_addr.clone(),10_000),(reporter_addr.clone(),100)]);
+        let register=Transaction::with_payload(
+            val_addr.clone(),
+            TxPayload::RegisterValidator{
+                consensus_pubkey:general_purpose::STANDARD.encode(consensus_kp.public.to_bytes()),
+                vrf_pubkey:general_purpose::STANDARD.encode(schnorrkel::Keypair::generate().public.to_bytes()),
+                endpoint:"127.0.0.1:30333".to_string(),
+            },
+            0,
+            0,
+            None,
+        );
+        state.apply_transaction(&SignedTransaction::sign_with_keypair(&register,&val_kp)).unwrap();
+
+        let evidence=DoubleSignEvidence{
+            first:SignedHeader::sign(3,"aa".to_string(),val_addr.clone(),&consensus_kp),
+            second:SignedHeader::sign(3,"bb".to_string(),val_addr.clone(),&consensus_kp),
+        };
+        let report=Transaction::with_payload(reporter_addr.clone(),TxPayload::Evidence(evidence.clone()),1,0,None);
+        state.apply_transaction(&SignedTransaction::sign_with_keypair(&report,&reporter_kp)).unwrap();
+
+        assert_eq!(state.get_balance(&val_addr),9_500);
+        assert!(state.validators().is_jailed(&val_addr,0));
+        assert!(!state.validators().is_active(&val_addr,JAIL_EPOCHS-1));
+        assert!(state.validators().is_active(&val_addr,JAIL_EPOCHS));
+
+        // the same offence can't be punished twice
+        let replay=Transaction::with_payload(reporter_addr.clone(),TxPayload::Evidence(evidence),1,1,None);
+        assert!(matches!(
+            state.validate_transaction(&SignedTransaction::sign_with_keypair(&replay,&reporter_kp)),
+            Err(StateError::InvalidEvidence(EvidenceError::AlreadyProcessed))
+        ));
+    }
+
+    /// Register the account of `kp` as a validator with fresh consensus / VRF keys
+    fn register_validator(state:&mut State,kp:&ed25519_dalek::Keypair){
+        let addr=pubkey_to_address_hex(&kp.public);
+        let register=Transaction::with_payload(
+            addr.clone(),
+            TxPayload::RegisterValidator{
+                consensus_pubkey:general_purpose::STANDARD.encode(generate_ed25519_keypair().public.to_bytes()),
+                vrf_pubkey:general_purpose::STANDARD.encode(schnorrkel::Keypair::generate().public.to_bytes()),
+                endpoint:"127.0.0.1:30333".to_string(),
+            },
+            0,
+            state.get_nonce(&addr),
+            None,
+        );
+        state.apply_transaction(&SignedTransaction::sign_with_keypair(&register,kp)).unwrap();
+    }
+
+    #[test]
+    fn test_metric_report_recorded_on_chain(){
+        use crate::attestation::MetricReport;
+
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),100)]);
+        register_validator(&mut state,&kp);
+
+        let metrics=NodeMetrics{
+            node_id:addr.clone(),
+            upload_mbps:40,
+            download_mbps:400,
+            latency_ms:30,
+            uptime_bps:9_900,
+            stability_bps:9_800,
+        };
+        let report=|nonce:u64,epoch:u64| SignedTransaction::sign_with_keypair(
+            &Transaction::with_payload(
+                addr.clone(),
+                TxPayload::MetricReport(MetricReport{epoch,metrics:metrics.clone(),attestations:vec![]}),
+                1,
+                nonce,
+                None,
+            ),
+            &kp,
+        );
+
+        assert!(matches!(
+            state.validate_transaction(&report(1,5)),
+            Err(StateError::InvalidMetricReport(MetricReportError::WrongEpoch))
+        ));
+        state.apply_transaction(&report(1,0)).unwrap();
+        assert_eq!(state.metrics_pool(0)[&addr],metrics);
+        assert!(matches!(
+            state.validate_transaction(&report(2,0)),
+            Err(StateError::InvalidMetricReport(MetricReportError::DuplicateReport))
+        ));
+    }
+
+    #[test]
+    fn test_settle_epoch_pays_active_validators(){
+        let (alice,bob)=(generate_ed25519_keypair(),generate_ed25519_keypair());
+        let (alice_addr,bob_addr)=(pubkey_to_address_hex(&alice.public),pubkey_to_address_hex(&bob.public));
+        let mut state=State::with_genesis(vec![(alice_addr.clone(),0),(bob_addr.clone(),0)]);
+        register_validator(&mut state,&alice);
+        register_validator(&mut state,&bob);
+        state.validators.jail(&bob_addr,2);
+
+        let scores:HashMap<String,u64>=[
+            (alice_addr.clone(),6_000),
+            (bob_addr.clone(),9_000),
+            ("unregistered".to_string(),9_000),
+        ]
+        .into_iter()
+        .collect();
+        let summary=state.settle_epoch(1,1_000,&scores).unwrap();
+        assert_eq!(summary.rewards.len(),1);
+        assert_eq!(state.get_balance(&alice_addr),1_000);
+        assert_eq!(state.get_balance(&bob_addr),0);
+        assert!(matches!(state.settle_epoch(1,1_000,&scores),Err(StateError::EpochAlreadySettled)));
+
+        // bob is out of jail and shares epoch 2 rewards by score
+        state.settle_epoch(2,1_000,&scores).unwrap();
+        assert_eq!(state.get_balance(&alice_addr),1_400);
+        assert_eq!(state.get_balance(&bob_addr),600);
+
+        let mut chain=crate::blockchain::Blockchain::new();
+        chain.add_epoch_summary(&summary);
+        assert_eq!(EpochSummary::from_block_data(&chain.last_block().data),Some(summary));
+    }
+
+    #[test]
+    fn test_stake_unstake_and_governance_vote(){
+        let (alice,bob)=(generate_ed25519_keypair(),generate_ed25519_keypair());
+        let (alice_addr,bob_addr)=(pubkey_to_address_hex(&alice.public),pubkey_to_address_hex(&bob.public));
+        let mut state=State::with_genesis(vec![(alice_addr.clone(),1_000),(bob_addr.clone(),1_000)]);
+        let signed=|kp:&ed25519_dalek::Keypair,payload:TxPayload,nonce:u64| {
+            SignedTransaction::sign_with_keypair(
+                &Transaction::with_payload(pubkey_to_address_hex(&kp.public),payload,1,nonce,None),
+                kp,
+            )
+        };
+
+        state.apply_transaction(&signed(&alice,TxPayload::Stake{amount:600},0)).unwrap();
+        assert_eq!(state.get_balance(&alice_addr),399);
+        assert_eq!(state.get_stake(&alice_addr),600);
+        assert!(matches!(
+            state.validate_transaction(&signed(&alice,TxPayload::Unstake{amount:601},1)),
+            Err(StateError::InsufficientStake)
+        ));
+
+        state.apply_transaction(&signed(&alice,TxPayload::GovernanceVote{proposal_id:7,approve:true},1)).unwrap();
+        assert!(matches!(
+            state.validate_transaction(&signed(&bob,TxPayload::GovernanceVote{proposal_id:7,approve:false},0)),
+            Err(StateError::NoStake)
+        ));
+        state.apply_transaction(&signed(&bob,TxPayload::Stake{amount:200},0)).unwrap();
+        state.apply_transaction(&signed(&bob,TxPayload::GovernanceVote{proposal_id:7,approve:false},1)).unwrap();
+        assert_eq!(state.proposal_tally(7),(600,200));
+
+        state.apply_transaction(&signed(&alice,TxPayload::Unstake{amount:500},2)).unwrap();
+        assert_eq!(state.get_balance(&alice_addr),397);
+        assert_eq!(state.get_unbonding(&alice_addr),500);
+        assert_eq!(state.proposal_tally(7),(100,200));
+
+        // locked for the unbonding period, then liquid
+        state.set_epoch(DEFAULT_UNBONDING_EPOCHS-1);
+        assert_eq!(state.get_balance(&alice_addr),397);
+        state.set_epoch(DEFAULT_UNBONDING_EPOCHS);
+        assert_eq!(state.get_balance(&alice_addr),897);
+        assert_eq!(state.get_unbonding(&alice_addr),0);
+    }
+
+    #[test]
+    fn test_multisig_transfer_needs_threshold(){
+        use crate::multisig::MultisigPolicy;
+
+        let keys:Vec<ed25519_dalek::Keypair>=(0..3).map(|_| generate_ed25519_keypair()).collect();
+        let policy=MultisigPolicy::new(
+            2,
+            keys.iter().map(|k| general_purpose::STANDARD.encode(k.public.to_bytes())).collect(),
+        )
+        .unwrap();
+        let vault=policy.address();
+        let mut state=State::with_genesis(vec![(vault.clone(),1_000)]);
+
+        let tx=Transaction::new(vault.clone(),"bob".to_string(),400,1,0,None);
+        let mut signed=SignedTransaction::multisig(&tx,policy);
+        signed.add_multisig_signature(&keys[1]).unwrap();
+        assert!(matches!(state.validate_transaction(&signed),Err(StateError::InvalidSignature(_))));
+
+        signed.add_multisig_signature(&keys[2]).unwrap();
+        state.apply_transaction(&signed).unwrap();
+        assert_eq!(state.get_balance(&vault),599);
+        assert_eq!(state.get_balance("bob"),400);
+    }
+
+    #[test]
+    fn test_fee_payer_covers_fee(){
+        let kp=generate_ed25519_keypair();
+        let sponsor=generate_ed25519_keypair();
+        let sender_addr=pubkey_to_address_hex(&kp.public);
+        let sponsor_addr=pubkey_to_address_hex(&sponsor.public);
+        let mut state=State::with_genesis(vec![(sender_addr.clone(),100),(sponsor_addr.clone(),50)]);
+
+        // sender can afford the amount but not amount + fee
+        let tx=Transaction::new(sender_addr.clone(),"bob".to_string(),100,5,0,None).with_fee_payer(sponsor_addr.clone());
+        let mut signed=SignedTransaction::sign_with_keypair(&tx,&kp);
+        assert!(matches!(state.validate_transaction(&signed),Err(StateError::InvalidSignature(_))));
+
+        signed.sign_fee_payer(&sponsor).unwrap();
+        state.apply_transaction(&signed).unwrap();
+        assert_eq!(state.get_balance(&sender_addr),0);
+        assert_eq!(state.get_balance(&sponsor_addr),45);
+        assert_eq!(state.get_balance("bob"),100);
+        assert_eq!(state.get_nonce(&sponsor_addr),0);
+    }
+
+    #[test]
+    fn test_simulate_does_not_mutate(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),100)]);
+        let gas=GasSchedule::default();
+        let root=state.commit_state_root();
+
+        let ok=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),40,2,0,None),&kp);
+        let sim=state.simulate_transaction(&ok,&gas);
+        assert!(sim.result.is_ok());
+        assert_eq!(sim.gas_used,gas.gas_used(&ok));
+        assert_eq!(sim.balance_changes[&addr],-42);
+        assert_eq!(sim.balance_changes["bob"],40);
+        assert_eq!(state.get_balance(&addr),100);
+        assert_eq!(state.get_nonce(&addr),0);
+        assert_eq!(state.get_balance("bob"),0);
+        assert_eq!((state.snapshot_count(),state.commit_state_root()),(0,root));
+
+        let broke=SignedTransaction::sign_with_keypair(&Transaction::new(addr,"bob".to_string(),100,1,0,None),&kp);
+        let sim=state.simulate_transaction(&broke,&gas);
+        assert!(matches!(sim.result,Err(StateError::InsufficientBalance)));
+        assert!(sim.balance_changes.is_empty());
+    }
+
+    #[test]
+    fn test_multi_transfer_is_all_or_nothing(){
+        use crate::transaction::TransferOutput;
+
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),100)]);
+        let batch=|outputs:Vec<(&str,u64)>,nonce|{
+            let outputs=outputs.into_iter().map(|(r,amount)| TransferOutput{receiver:r.to_string(),amount}).collect();
+            SignedTransaction::sign_with_keypair(
+                &Transaction::with_payload(addr.clone(),TxPayload::MultiTransfer{outputs},1,nonce,None),
+                &kp,
+            )
+        };
+
+        assert!(matches!(state.apply_transaction(&batch(vec![("bob",50),("carol",50)],0)),Err(StateError::InsufficientBalance)));
+        assert!(matches!(state.apply_transaction(&batch(vec![("bob",u64::MAX),("carol",1)],0)),Err(StateError::AmountOverflow)));
+        assert!(matches!(state.apply_transaction(&batch(vec![],0)),Err(StateError::EmptyBatch)));
+        assert_eq!(state.get_balance("bob"),0);
+
+        state.apply_transaction(&batch(vec![("bob",30),("carol",20),("bob",5)],0)).unwrap();
+        assert_eq!(state.get_balance(&addr),44);
+        assert_eq!(state.get_balance("bob"),35);
+        assert_eq!(state.get_balance("carol"),20);
+    }
+
+    #[test]
+    fn test_receipts_record_outcome_and_events(){
+        use crate::blockchain::Blockchain;
+        use crate::receipt::BlockReceipts;
+
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),100)]);
+        let gas=GasSchedule::default();
+        let ok=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),40,2,0,None),&kp);
+        let stale=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),1,1,0,None),&kp);
+
+        let receipts=vec![state.apply_with_receipt(&ok,&gas,1,0),state.apply_with_receipt(&stale,&gas,1,1)];
+        let mut chain=Blockchain::new();
+        chain.record_receipts(BlockReceipts{height:1,receipts,..Default::default()});
+
+        let receipt=chain.receipt(&ok.tx_hash_hex()).unwrap();
+        assert!(receipt.is_success());
+        assert_eq!(receipt.gas_used,gas.gas_used(&ok));
+        assert_eq!(
+            receipt.events,
+            vec![
+                Event::FeePaid{payer:addr.clone(),amount:2},
+                Event::Transfer{from:addr,to:"bob".to_string(),amount:40},
+            ]
+        );
+        let failed=chain.receipt(&stale.tx_hash_hex()).unwrap();
+        assert_eq!(failed.status,ReceiptStatus::Failed{reason:"invalid nonce".to_string()});
+        assert_eq!((failed.block_height,failed.index),(1,1));
+        assert_eq!(chain.block_receipts(1).unwrap().receipts.len(),2);
+    }
+
+    #[test]
+    fn test_apply_block_rolls_back_on_failure(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),100)]);
+        let transfer=|amount,nonce| {
+            SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),amount,1,nonce,None),&kp)
+        };
+        let stake=SignedTransaction::sign_with_keypair(
+            &Transaction::with_payload(addr.clone(),TxPayload::Stake{amount:10},1,1,None),
+            &kp,
+        );
+
+        // third transaction overdraws: the first two must be undone as well
+        let err=state.apply_block("proposer",&[transfer(30,0),stake.clone(),transfer(100,2)]).unwrap_err();
+        assert_eq!(err.index,2);
+        assert!(matches!(err.error,StateError::InsufficientBalance));
+        assert_eq!(state.get_balance(&addr),100);
+        assert_eq!(state.get_stake(&addr),0);
+        assert_eq!(state.get_nonce(&addr),0);
+        assert!(!state.accounts.contains_key("bob"));
+
+        let outcome=state.apply_block("proposer",&[transfer(30,0),stake]).unwrap();
+        assert_eq!(outcome.events.len(),2);
+        assert_eq!(state.get_balance(&addr),58);
+        assert_eq!(state.get_stake(&addr),10);
+        assert_eq!(state.snapshot_count(),0);
+    }
+
+    #[test]
+    fn test_state_root_and_account_proofs(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),100),("carol".to_string(),5)]);
+        let genesis_root=state.commit_state_root();
+        assert_ne!(genesis_root,crate::smt::EMPTY);
+
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),30,1,0,None),&kp);
+        state.apply_block("proposer",&[tx]).unwrap();
+        // uncommitted changes don't move the root
+        assert_eq!(state.state_root(),genesis_root);
+        let root=state.commit_state_root();
+        assert_ne!(root,genesis_root);
+
+        let bob=state.accounts.get("bob").cloned().unwrap();
+        let proof=state.get_proof("bob");
+        assert!(proof.verify(&root,Some(&bob)));
+        assert!(!proof.verify(&root,Some(&Account::new(31))));
+        assert!(!proof.verify(&genesis_root,Some(&bob)));
+
+        let absent=state.get_proof("dave");
+        assert!(absent.verify(&root,None));
+        assert!(!absent.verify(&root,Some(&Account::new(0))));
+
+        // the root is a function of account contents only
+        let mut rebuilt=State::with_genesis(vec![]);
+        rebuilt.accounts=state.accounts.clone();
+        rebuilt.tree_pending=state.accounts.keys().cloned().collect();
+        assert_eq!(rebuilt.commit_state_root(),root);
+    }
+
+    #[test]
+    fn test_block_fees_are_routed_by_policy(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),1_000)]);
+        state.set_fee_policy(FeePolicy::new(5_000,3_000,2_000).unwrap());
+        state.set_epoch(3);
+        let txs:Vec<SignedTransaction>=(0..2)
+        .map(|nonce| SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),10,50,nonce,None),&kp))
+        .collect();
+
+        let outcome=state.apply_block("proposer",&txs).unwrap();
+        assert_eq!(outcome.fees,FeeSplit{total:100,proposer:50,reward_pool:30,burned:20});
+        assert_eq!(state.get_balance("proposer"),50);
+        assert_eq!(state.fee_pool(3),30);
+
+        // a failed block routes nothing
+        assert!(state.apply_block("proposer",&txs[..1]).is_err());
+        assert_eq!(state.get_balance("proposer"),50);
+
+        // with nobody eligible the fees carry over to the next epoch
+        let scores:HashMap<String,u64>=[(addr.clone(),1)].into_iter().collect();
+        let summary=state.settle_epoch(3,100,&scores).unwrap();
+        assert_eq!((summary.reward_pool,summary.total_paid()),(100,0));
+        assert_eq!((state.fee_pool(3),state.fee_pool(4)),(0,30));
+
+        // the epoch's fee pool is paid out with the settlement
+        let vrf_key=general_purpose::STANDARD.encode(schnorrkel::Keypair::generate().public.to_bytes());
+        state.validators.register(addr.clone(),general_purpose::STANDARD.encode(kp.public.to_bytes()),vrf_key,"host:1".to_string()).unwrap();
+        let summary=state.settle_epoch(4,100,&scores).unwrap();
+        assert_eq!(summary.reward_pool,130);
+        assert_eq!(state.fee_pool(4),0);
+    }
+
+    #[test]
+    fn test_slashing_reaches_bonded_and_unbonding_stake(){
+        let mut account=Account::new(1_000);
+        account.staked=2_000;
+        account.unbonding.push(Unbonding{amount:400,release_epoch:9});
+        assert_eq!(account.slash(SLASH_FRACTION_BPS),50+100+20);
+        assert_eq!((account.balance,account.staked,account.unbonding_total()),(950,1_900,380));
+
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![]);
+        state.accounts.insert(addr.clone(),account);
+        let vrf_key=general_purpose::STANDARD.encode(schnorrkel::Keypair::generate().public.to_bytes());
+        state.validators.register(addr.clone(),general_purpose::STANDARD.encode(kp.public.to_bytes()),vrf_key,"host:1".to_string()).unwrap();
+
+        assert_eq!(state.bonded_stakes(),[(addr,1_900)].into_iter().collect());
+    }
+
+    #[test]
+    fn test_snapshots_revert_by_handle_and_height(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),100)]);
+        let transfer=|nonce| {
+            SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),10,0,nonce,None),&kp)
+        };
+
+        state.set_block_context(1,0);
+        state.apply_block("proposer",&[transfer(0)]).unwrap();
+        let at_one=state.snapshot();
+        state.set_block_context(2,0);
+        state.apply_block("proposer",&[transfer(1)]).unwrap();
+        let at_two=state.snapshot();
+        state.set_block_context(3,0);
+        state.apply_block("proposer",&[transfer(2)]).unwrap();
+        state.set_epoch(5);
+        assert_eq!(state.get_balance("bob"),30);
+
+        // only the accounts written since a snapshot are journaled in it
+        assert_eq!(state.layers[1].originals.len(),2);
+
+        state.revert_to_height(2).unwrap();
+        assert_eq!(state.get_balance("bob"),20);
+        assert_eq!(state.current_epoch(),0);
+        assert_eq!(state.revert_to(at_two),Err(SnapshotError::UnknownSnapshot));
+
+        // releasing keeps the changes but hands their undo to the earlier snapshot
+        let middle=state.snapshot();
+        state.apply_block("proposer",&[transfer(2)]).unwrap();
+        state.release(middle).unwrap();
+        assert_eq!(state.get_balance("bob"),30);
+        state.revert_to(at_one).unwrap();
+        assert_eq!(state.get_balance("bob"),10);
+        assert_eq!(state.get_nonce(&addr),1);
+        assert_eq!(state.snapshot_count(),0);
+        assert_eq!(state.revert_to_height(1),Err(SnapshotError::NoSnapshotAtHeight(1)));
+    }
+
+    #[test]
+    fn test_overflowing_credits_are_rejected(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),100),("whale".to_string(),u64::MAX-5)]);
+        let transfer=|receiver:&str,amount| {
+            SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),receiver.to_string(),amount,1,0,None),&kp)
+        };
+
+        assert!(matches!(state.apply_transaction(&transfer("whale",6)),Err(StateError::BalanceOverflow)));
+        assert_eq!(state.get_balance(&addr),100);
+        state.apply_transaction(&transfer("whale",5)).unwrap();
+        assert_eq!(state.get_balance("whale"),u64::MAX);
+
+        state.accounts.get_mut(&addr).unwrap().nonce=u64::MAX;
+        let exhausted=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),1,1,u64::MAX,None),&kp);
+        assert!(matches!(state.validate_transaction(&exhausted),Err(StateError::NonceOverflow)));
+    }
+
+    #[test]
+    fn test_block_supply_change_is_accounted_for(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),1_000)]);
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),10,10,0,None),&kp);
+        let supply=|state:&State| state.accounts.values().map(Account::holdings).sum::<u128>();
+
+        let before=supply(&state);
+        let outcome=state.apply_block("proposer",&[tx]).unwrap();
+        assert_eq!(before-supply(&state),(outcome.fees.burned+outcome.fees.reward_pool) as u128);
+
+        // a mint sneaked into the block's journal is caught and the block reverted
+        let snapshot=state.snapshot();
+        state.credit("bob",1).unwrap();
+        assert!(matches!(
+            state.check_supply_change(&FeeSplit::default(),&[],&BTreeMap::new()),
+            Err(StateError::SupplyMismatch{expected:0,actual:1})
+        ));
+        state.revert_to(snapshot).unwrap();
+        assert_eq!(state.get_balance("bob"),10);
+    }
+
+    #[test]
+    fn test_block_diff_and_revert(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),100)]);
+        state.set_block_context(7,0);
+        let tx=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),10,0,0,None),&kp);
+
+        let diff=state.apply_block("proposer",&[tx]).unwrap().diff;
+        assert_eq!(diff.height,7);
+        assert_eq!(diff.changes.len(),2);
+        assert_eq!(diff.changes["bob"].kind(),crate::diff::ChangeKind::Created);
+        assert_eq!(diff.changes[&addr].after.as_ref().map(|a| a.balance),Some(90));
+
+        let mut chain=crate::blockchain::Blockchain::new();
+        chain.record_state_diff(diff.clone());
+        assert_eq!(chain.state_diff(7),Some(&diff));
+
+        state.revert_diff(&diff).unwrap();
+        assert_eq!(state.get_balance(&addr),100);
+        assert_eq!(state.get_nonce(&addr),0);
+        assert!(!state.accounts.contains_key("bob"));
+        assert!(matches!(state.revert_diff(&diff),Err(StateError::DiffMismatch(_))));
+    }
+
+    #[test]
+    fn test_account_pages_and_richlist(){
+        let mut state=State::with_genesis(vec![
+            ("a".to_string(),5),
+            ("b".to_string(),50),
+            ("c".to_string(),20),
+            ("d".to_string(),20),
+        ]);
+        state.accounts.get_mut("a").unwrap().staked=100;
+
+        let first:Vec<&str>=state.accounts_iter(None,3).map(|(addr,_)| addr).collect();
+        assert_eq!(first,vec!["a","b","c"]);
+        let next:Vec<&str>=state.accounts_iter(Some("c"),3).map(|(addr,_)| addr).collect();
+        assert_eq!(next,vec!["d"]);
+
+        let top:Vec<&str>=state.top_accounts(3).into_iter().map(|(addr,_)| addr).collect();
+        assert_eq!(top,vec!["a","b","c"]);
+        assert_eq!(state.account_count(),4);
+        assert_eq!(state.total_supply(),195);
+    }
+
+    #[test]
+    fn test_vesting_limits_spending(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let genesis=Genesis{
+            accounts:vec![GenesisAccount{
+                vesting:Some(VestingSchedule::new(1_000,0,10,100).unwrap()),
+                ..GenesisAccount::new(addr.clone(),1_100)
+            }],
+            ..Genesis::default()
+        };
+        let mut state=State::from_genesis(&genesis);
+        let transfer=|amount,nonce| {
+            SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),amount,1,nonce,None),&kp)
+        };
+
+        // before the cliff only the unvested 100 can move
+        state.set_block_context(5,0);
+        assert_eq!(state.get_spendable_balance(&addr),100);
+        assert!(matches!(state.validate_transaction(&transfer(100,0)),Err(StateError::VestingLocked)));
+        state.apply_transaction(&transfer(99,0)).unwrap();
+
+        // halfway through, half the allocation is released
+        state.set_block_context(50,0);
+        assert_eq!(state.get_spendable_balance(&addr),500);
+        state.apply_transaction(&transfer(499,1)).unwrap();
+        assert!(matches!(state.validate_transaction(&transfer(1,2)),Err(StateError::VestingLocked)));
+        assert_eq!(state.get_balance(&addr),500);
+    }
+
+    #[test]
+    fn test_export_genesis_round_trips(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),1_000)]);
+        let sign=|payload,nonce| SignedTransaction::sign_with_keypair(&Transaction::with_payload(addr.clone(),payload,1,nonce,None),&kp);
+        state.apply_transaction(&sign(TxPayload::Transfer{receiver:"bob".to_string(),amount:100},0)).unwrap();
+        state.apply_transaction(&sign(TxPayload::Stake{amount:300},1)).unwrap();
+        state.apply_transaction(&sign(TxPayload::Unstake{amount:50},2)).unwrap();
+
+        let genesis=state.export_genesis(12);
+        assert_eq!(genesis.height,12);
+        let exported=&genesis.accounts.iter().find(|a| a.address==addr).unwrap();
+        assert_eq!((exported.balance,exported.nonce,exported.staked,exported.unbonding.len()),(597,3,250,1));
+
+        let path=std::env::temp_dir().join(format!("netchain-export-{}.toml",std::process::id()));
+        genesis.to_file(&path).unwrap();
+        let loaded=Genesis::from_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(loaded,genesis);
+
+        let mut restored=State::from_genesis(&loaded);
+        assert_eq!(restored.commit_state_root(),state.commit_state_root());
+        assert_eq!(restored.total_supply(),state.total_supply());
+        // the old nonce can't be replayed on the continued chain
+        assert!(matches!(
+            restored.validate_transaction(&sign(TxPayload::Transfer{receiver:"bob".to_string(),amount:1},2)),
+            Err(StateError::InvalidNonce)
+        ));
+    }
+
+    #[test]
+    fn test_issue_and_transfer_assets(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),100)]);
+        let sign=|payload,nonce| SignedTransaction::sign_with_keypair(&Transaction::with_payload(addr.clone(),payload,1,nonce,None),&kp);
+        let issue=|asset:&str,nonce| sign(TxPayload::IssueAsset{asset:asset.to_string(),supply:1_000},nonce);
+        let send=|amount,nonce| sign(TxPayload::TransferAsset{asset:"GOLD".to_string(),receiver:"bob".to_string(),amount},nonce);
+
+        assert!(matches!(state.validate_transaction(&issue("NC",0)),Err(StateError::InvalidAssetId)));
+        assert!(matches!(state.validate_transaction(&issue("gold",0)),Err(StateError::InvalidAssetId)));
+        assert!(matches!(state.validate_transaction(&send(1,0)),Err(StateError::UnknownAsset)));
+        state.apply_block("proposer",&[issue("GOLD",0)]).unwrap();
+        assert!(matches!(state.validate_transaction(&issue("GOLD",1)),Err(StateError::AssetExists)));
+        assert_eq!(state.asset("GOLD").map(|a| a.supply),Some(1_000));
+        assert_eq!(state.get_asset_balance(&addr,"GOLD"),1_000);
+
+        let outcome=state.apply_block("proposer",&[send(400,1)]).unwrap();
+        assert_eq!(state.get_asset_balance(&addr,"GOLD"),600);
+        assert_eq!(state.get_asset_balance("bob","GOLD"),400);
+        // fees stay in the native coin
+        assert_eq!(state.get_balance(&addr),98);
+        assert_eq!(state.get_asset_balance(&addr,NATIVE_ASSET),98);
+        assert!(outcome.events[0].contains(&Event::AssetTransfer{asset:"GOLD".to_string(),from:addr.clone(),to:"bob".to_string(),amount:400}));
+        assert!(matches!(state.validate_transaction(&send(601,2)),Err(StateError::InsufficientAssetBalance)));
+
+        // a rejected block takes its issuance with it
+        let err=state.apply_block("proposer",&[issue("SILVER",2),send(601,3)]).unwrap_err();
+        assert_eq!(err.index,1);
+        assert!(state.asset("SILVER").is_none());
+    }
+
+    #[test]
+    fn test_dust_accounts_removed_and_recorded(){
+        let (kp,sponsor)=(generate_ed25519_keypair(),generate_ed25519_keypair());
+        let (addr,sponsor_addr)=(pubkey_to_address_hex(&kp.public),pubkey_to_address_hex(&sponsor.public));
+        let mut state=State::with_genesis(vec![(addr.clone(),100),(sponsor_addr.clone(),13),("carol".to_string(),8)]);
+        state.set_min_account_balance(10);
+        let pay=|receiver:&str,amount,nonce| {
+            SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),receiver.to_string(),amount,1,nonce,None),&kp)
+        };
+
+        // transfers may not leave a receiver as dust
+        assert_eq!(state.validate_transaction(&pay("bob",5,0)),Err(StateError::BelowMinimumBalance("bob".to_string())));
+        assert_eq!(state.validate_transaction(&pay("carol",1,0)),Err(StateError::BelowMinimumBalance("carol".to_string())));
+
+        // the sponsor's fee drains it below the minimum
+        let tx=Transaction::new(addr.clone(),"dave".to_string(),10,4,1,None).with_fee_payer(sponsor_addr.clone());
+        let mut sponsored=SignedTransaction::sign_with_keypair(&tx,&kp);
+        sponsored.sign_fee_payer(&sponsor).unwrap();
+        let outcome=state.apply_block("proposer",&[pay("carol",2,0),sponsored]).unwrap();
+        assert_eq!((state.get_balance("carol"),state.get_balance("dave")),(10,10));
+        // the proposer's 2-unit fee share was only credited, so it stays
+        assert_eq!(state.get_balance("proposer"),2);
+        assert_eq!(state.get_balance(&addr),87);
+
+        let diff=&outcome.diff;
+        assert_eq!(diff.dust_removed,BTreeMap::from([(sponsor_addr.clone(),9)]));
+        assert_eq!(diff.addresses(crate::diff::ChangeKind::Deleted).collect::<Vec<_>>(),vec![sponsor_addr.as_str()]);
+
+        state.revert_diff(diff).unwrap();
+        assert_eq!(state.get_balance(&sponsor_addr),13);
+        assert_eq!(state.get_balance(&addr),100);
+
+        // a sender left below the minimum keeps its account: it has a nonce to protect
+        state.set_min_account_balance(95);
+        state.apply_block("proposer",&[pay("carol",90,0)]).unwrap();
+        assert_eq!((state.get_balance(&addr),state.get_nonce(&addr)),(9,1));
+    }
+
+    #[test]
+    fn test_pay_registered_name(){
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),5_000)]);
+        let sign=|payload,nonce| SignedTransaction::sign_with_keypair(&Transaction::with_payload(addr.clone(),payload,1,nonce,None),&kp);
+        let pay=|receiver:&str,nonce| sign(TxPayload::Transfer{receiver:receiver.to_string(),amount:10},nonce);
+
+        assert!(matches!(state.validate_transaction(&pay("@alice",0)),Err(StateError::Name(NameError::Unregistered))));
+        let outcome=state.apply_block("proposer",&[sign(TxPayload::RegisterName{name:"alice".to_string()},0)]).unwrap();
+        // the registration cost is burned, which the supply check accepts
+        assert_eq!(state.get_balance(&addr),5_000-NAME_REGISTRATION_COST-1);
+        assert!(matches!(outcome.events[0][1],Event::NameRegistered{burned:NAME_REGISTRATION_COST,..}));
+        assert_eq!(state.resolve_name("@alice"),Some(addr.as_str()));
+        assert_eq!(state.names().names_of(&addr,0),vec!["alice"]);
+
+        // paying yourself by name
+        state.apply_block("proposer",&[pay("@alice",1)]).unwrap();
+        assert_eq!(state.get_balance(&addr),5_000-NAME_REGISTRATION_COST-2);
+        assert!(matches!(state.validate_transaction(&pay("@bob",2)),Err(StateError::Name(NameError::Unregistered))));
+    }
+
+    #[test]
+    fn test_restart_restores_chain_state(){
+        use crate::store::MemoryStore;
+
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+        let mut state=State::with_genesis(vec![(addr.clone(),5_000)]);
+        let mut store=MemoryStore::new();
+        assert_eq!(State::from_store(&store).unwrap().1,None);
+        state.persist(&mut store,0).unwrap();
+
+        state.set_epoch(3);
+        state.set_block_context(1,1_000);
+        register_validator(&mut state,&kp);
+        let register=Transaction::with_payload(addr.clone(),TxPayload::RegisterName{name:"alice".to_string()},1,1,None);
+        state.apply_block("proposer",&[SignedTransaction::sign_with_keypair(&register,&kp)]).unwrap();
+        state.validators.jail(&addr,5);
+        let root=state.commit_state_root();
+        state.persist(&mut store,1).unwrap();
+
+        let (restarted,height)=State::from_store(&store).unwrap();
+        assert_eq!(height,Some(1));
+        assert_eq!((restarted.current_epoch(),restarted.block_height),(3,1));
+        assert!(restarted.validators().is_registered(&addr));
+        assert!(restarted.validators().is_jailed(&addr,4));
+        assert_eq!(restarted.resolve_name("@alice"),Some(addr.as_str()));
+        assert_eq!(restarted.get_balance(&addr),state.get_balance(&addr));
+        assert_eq!(restarted.state_root(),root);
+    }
+}
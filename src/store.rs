@@ -0,0 +1,275 @@
+// src/store.rs
+
+//! Persistent account storage
+//! - `StateStore`: accounts keyed by address plus the last committed block height and the
+//!   rest of the chain state (validators, names, epoch, ...) as one encoded value
+//! - Block changes are written as one atomic batch, so after a crash the store holds
+//!   exactly the accounts and chain state as of `committed_height`
+//! - Each commit also keeps the block's changes as a per-height diff, so balances and
+//!   nonces can be read as of any committed height (`account_at`)
+//! - `MemoryStore` for tests / ephemeral nodes, `SledStore` on disk
+
+use crate::canonical;
+use crate::state::Account;
+use std::collections::BTreeMap;
+use std::path::Path;
+use thiserror::Error;
+
+/// Key prefix of account entries in the on-disk store
+const ACCOUNT_PREFIX:&[u8]=b"account/";
+/// Key prefix of history entries: prefix || address || '/' || height (big-endian)
+const HISTORY_PREFIX:&[u8]=b"history/";
+/// Key of the last committed height
+const HEIGHT_KEY:&[u8]=b"meta/committed_height";
+/// Key of the chain state besides accounts, as of the last committed height
+const CHAIN_STATE_KEY:&[u8]=b"meta/chain_state";
+
+/// Storage failures
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum StoreError{
+    #[error("storage I/O error: {0}")]
+    Io(String),
+    /// Stored bytes don't decode
+    #[error("corrupt store data: {0}")]
+    Corrupt(String),
+}
+
+/// Where `State` accounts are persisted
+pub trait StateStore{
+    fn get(&self,address:&str)->Result<Option<Account>,StoreError>;
+
+    /// All stored accounts (used to rebuild `State` on startup)
+    fn accounts(&self)->Result<Vec<(String,Account)>,StoreError>;
+
+    /// Atomically apply a block's account changes (`None` deletes), replace the encoded
+    /// `chain_state` and record `height` as committed. Either all of it is durable or none of it is.
+    fn commit_block(&mut self,height:u64,changes:&[(String,Option<Account>)],chain_state:&[u8])->Result<(),StoreError>;
+
+    /// Height of the last `commit_block`, if any
+    fn committed_height(&self)->Result<Option<u64>,StoreError>;
+
+    /// Chain state written by the last `commit_block`, if any
+    fn chain_state(&self)->Result<Option<Vec<u8>>,StoreError>;
+
+    /// The account as it was after the block at `height` was committed
+    fn account_at(&self,address:&str,height:u64)->Result<Option<Account>,StoreError>;
+
+    /// Liquid balance of `address` after block `height`
+    fn balance_at(&self,address:&str,height:u64)->Result<u64,StoreError>{
+        Ok(self.account_at(address,height)?.map(|a| a.balance).unwrap_or(0))
+    }
+
+    /// Nonce of `address` after block `height`
+    fn nonce_at(&self,address:&str,height:u64)->Result<u64,StoreError>{
+        Ok(self.account_at(address,height)?.map(|a| a.nonce).unwrap_or(0))
+    }
+}
+
+/// In-memory store; contents vanish with the process
+#[derive(Debug,Clone,Default)]
+pub struct MemoryStore{
+    accounts:BTreeMap<String,Account>,
+    /// address -> height -> value written by that block
+    history:BTreeMap<String,BTreeMap<u64,Option<Account>>>,
+    height:Option<u64>,
+    chain_state:Option<Vec<u8>>,
+}
+
+impl MemoryStore{
+    pub fn new()->Self{
+        Self::default()
+    }
+}
+
+impl StateStore for MemoryStore{
+    fn get(&self,address:&str)->Result<Option<Account>,StoreError>{
+        Ok(self.accounts.get(address).cloned())
+    }
+
+    fn accounts(&self)->Result<Vec<(String,Account)>,StoreError>{
+        Ok(self.accounts.iter().map(|(a,acc)| (a.clone(),acc.clone())).collect())
+    }
+
+    fn commit_block(&mut self,height:u64,changes:&[(String,Option<Account>)],chain_state:&[u8])->Result<(),StoreError>{
+        for (address,account) in changes{
+            match account{
+                Some(account)=>self.accounts.insert(address.clone(),account.clone()),
+                None=>self.accounts.remove(address),
+            };
+            self.history.entry(address.clone()).or_default().insert(height,account.clone());
+        }
+        self.height=Some(height);
+        self.chain_state=Some(chain_state.to_vec());
+        Ok(())
+    }
+
+    fn committed_height(&self)->Result<Option<u64>,StoreError>{
+        Ok(self.height)
+    }
+
+    fn chain_state(&self)->Result<Option<Vec<u8>>,StoreError>{
+        Ok(self.chain_state.clone())
+    }
+
+    fn account_at(&self,address:&str,height:u64)->Result<Option<Account>,StoreError>{
+        Ok(self
+        .history
+        .get(address)
+        .and_then(|versions| versions.range(..=height).next_back())
+        .and_then(|(_,account)| account.clone()))
+    }
+}
+
+/// On-disk store backed by sled
+pub struct SledStore{
+    db:sled::Db,
+}
+
+fn io_error(e:sled::Error)->StoreError{
+    StoreError::Io(e.to_string())
+}
+
+fn account_key(address:&str)->Vec<u8>{
+    [ACCOUNT_PREFIX,address.as_bytes()].concat()
+}
+
+fn history_key(address:&str,height:u64)->Vec<u8>{
+    [HISTORY_PREFIX,address.as_bytes(),b"/",&height.to_be_bytes()].concat()
+}
+
+impl SledStore{
+    /// Open (or create) the store at `path`.
+    /// Every commit flushes itself, so sled's background flusher is disabled; it would also
+    /// keep the database locked for a while after the store is dropped.
+    pub fn open(path:impl AsRef<Path>)->Result<Self,StoreError>{
+        let db=sled::Config::new()
+        .path(path)
+        .flush_every_ms(None)
+        .open()
+        .map_err(io_error)?;
+        Ok(SledStore{db})
+    }
+}
+
+impl StateStore for SledStore{
+    fn get(&self,address:&str)->Result<Option<Account>,StoreError>{
+        self.db
+        .get(account_key(address))
+        .map_err(io_error)?
+        .map(|bytes| canonical::decode(&bytes).map_err(|e| StoreError::Corrupt(e.to_string())))
+        .transpose()
+    }
+
+    fn accounts(&self)->Result<Vec<(String,Account)>,StoreError>{
+        self.db
+        .scan_prefix(ACCOUNT_PREFIX)
+        .map(|entry| {
+            let (key,value)=entry.map_err(io_error)?;
+            let address=String::from_utf8(key[ACCOUNT_PREFIX.len()..].to_vec())
+            .map_err(|e| StoreError::Corrupt(e.to_string()))?;
+            Ok((address,canonical::decode(&value).map_err(|e| StoreError::Corrupt(e.to_string()))?))
+        })
+        .collect()
+    }
+
+    fn commit_block(&mut self,height:u64,changes:&[(String,Option<Account>)],chain_state:&[u8])->Result<(),StoreError>{
+        let mut batch=sled::Batch::default();
+        for (address,account) in changes{
+            match account{
+                Some(account)=>batch.insert(account_key(address),canonical::encode(account)),
+                None=>batch.remove(account_key(address)),
+            }
+            batch.insert(history_key(address,height),canonical::encode(account));
+        }
+        batch.insert(HEIGHT_KEY,&height.to_le_bytes());
+        batch.insert(CHAIN_STATE_KEY,chain_state);
+        self.db.apply_batch(batch).map_err(io_error)?;
+        self.db.flush().map_err(io_error)?;
+        Ok(())
+    }
+
+    fn committed_height(&self)->Result<Option<u64>,StoreError>{
+        self.db
+        .get(HEIGHT_KEY)
+        .map_err(io_error)?
+        .map(|bytes| {
+            let bytes:[u8;8]=bytes.as_ref().try_into().map_err(|_| StoreError::Corrupt("committed height".to_string()))?;
+            Ok(u64::from_le_bytes(bytes))
+        })
+        .transpose()
+    }
+
+    fn chain_state(&self)->Result<Option<Vec<u8>>,StoreError>{
+        Ok(self.db.get(CHAIN_STATE_KEY).map_err(io_error)?.map(|bytes| bytes.to_vec()))
+    }
+
+    fn account_at(&self,address:&str,height:u64)->Result<Option<Account>,StoreError>{
+        // latest version written at or below `height`
+        let latest=self
+        .db
+        .range(history_key(address,0)..=history_key(address,height))
+        .next_back()
+        .transpose()
+        .map_err(io_error)?;
+        match latest{
+            Some((_,bytes))=>canonical::decode::<Option<Account>>(&bytes).map_err(|e| StoreError::Corrupt(e.to_string())),
+            None=>Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::state::State;
+    use crate::transaction::{SignedTransaction,Transaction,generate_ed25519_keypair,pubkey_to_address_hex};
+
+    #[test]
+    fn test_state_survives_reopen(){
+        let dir=std::env::temp_dir().join(format!("netchain-store-{}-{}",std::process::id(),rand::random::<u64>()));
+        let kp=generate_ed25519_keypair();
+        let addr=pubkey_to_address_hex(&kp.public);
+
+        {
+            let mut store=SledStore::open(&dir).unwrap();
+            let mut state=State::with_genesis(vec![(addr.clone(),100)]);
+            state.persist(&mut store,0).unwrap();
+            let tx=SignedTransaction::sign_with_keypair(&Transaction::new(addr.clone(),"bob".to_string(),30,1,0,None),&kp);
+            state.apply_block("proposer",&[tx]).unwrap();
+            state.persist(&mut store,1).unwrap();
+        }
+
+        let store=SledStore::open(&dir).unwrap();
+        assert_eq!(store.committed_height().unwrap(),Some(1));
+        let (state,height)=State::from_store(&store).unwrap();
+        assert_eq!(height,Some(1));
+        assert_eq!(state.get_balance(&addr),69);
+        assert_eq!(state.get_nonce(&addr),1);
+        assert_eq!(state.get_balance("bob"),30);
+
+        // history: before and after the transfer
+        assert_eq!(store.balance_at(&addr,0).unwrap(),100);
+        assert_eq!(store.nonce_at(&addr,0).unwrap(),0);
+        assert_eq!(store.balance_at(&addr,1).unwrap(),69);
+        assert_eq!(store.balance_at("bob",0).unwrap(),0);
+        assert_eq!(store.balance_at("bob",5).unwrap(),30);
+        drop(store);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_memory_store_commit(){
+        let mut store=MemoryStore::new();
+        store.commit_block(3,&[("a".to_string(),Some(Account::new(5))),("b".to_string(),None)],b"chain").unwrap();
+        assert_eq!(store.get("a").unwrap(),Some(Account::new(5)));
+        assert_eq!(store.committed_height().unwrap(),Some(3));
+        assert_eq!(store.chain_state().unwrap(),Some(b"chain".to_vec()));
+
+        store.commit_block(4,&[("a".to_string(),Some(Account::new(9)))],&[]).unwrap();
+        store.commit_block(6,&[("a".to_string(),None)],&[]).unwrap();
+        assert_eq!(store.balance_at("a",2).unwrap(),0);
+        assert_eq!(store.balance_at("a",3).unwrap(),5);
+        assert_eq!(store.balance_at("a",5).unwrap(),9);
+        assert_eq!(store.account_at("a",6).unwrap(),None);
+    }
+}
@@ -2,34 +2,196 @@
 
 //! Transaction module for NetChain
 //! - Transaction structure
-//! - Signing (Ed25519) and verification
-//! - Deterministic canonical serialization for signing (bincode)
+//! - Signing (Ed25519 or secp256k1, see `SignatureScheme`) through any `TxSigner`, and verification
+//! - Optional sponsored fees: a separate `fee_payer` co-signs and covers `fee`
+//! - Optional memo encrypted to the receiver (see `memo`)
+//! - Optional time lock: not includable before a block height or timestamp
+//! - Memo and encoded-size limits (`TxLimits`), checked against the node's configured ones
+//!   on admission (`check_limits`)
+//! - Deterministic canonical serialization for signing (see `canonical` for the spec)
 //! - Transaction hashing (SHA-256)
 
 //!
 //! Usage:
 //! - Build a `Transaction` (Without signature), compute hash, then sign using a Keypair
+//!   or any other `TxSigner`
 //! - Create a `SignedTransaction` that carries signature + public key
 //! - Verify with `SignedTransaction::verify();
 
+use crate::attestation::MetricReport;
+use crate::canonical;
+use crate::evidence::DoubleSignEvidence;
+use crate::memo::EncryptedMemo;
+use crate::multisig::{MultisigError,MultisigPolicy,MultisigSignatures};
+use crate::names::NAME_REGISTRATION_COST;
+use crate::signer::TxSigner;
 use base64::{engine::general_purpose,Engine as _};
-use bincode;
-use ed25519_dalek::{Keypair,PublicKey,Signature,Signer,Verifier};
-use rand::rngs::OsRng;
+use ed25519_dalek::{Keypair,PublicKey,Signature,Verifier};
+use rand_core::OsRng;
 use serde::{Deserialize,Serialize};
 use sha2::{Digest,Sha256};
+use std::collections::BTreeMap;
 use std::time::{SystemTime,UNIX_EPOCH};
+use thiserror::Error;
+use utoipa::ToSchema;
+
+/// Most outputs a single `MultiTransfer` may carry
+pub const MAX_TRANSFER_OUTPUTS:usize=256;
+
+/// Size limits on a single transaction
+#[derive(Debug,Clone,Copy,PartialEq,Eq,Serialize,Deserialize)]
+#[serde(default)]
+pub struct TxLimits{
+    /// Plain memo bytes plus encrypted memo ciphertext (base64) bytes
+    pub max_memo_bytes:usize, // e.g., 256
+    /// Encoded size including signatures (see `SignedTransaction::encoded_size`)
+    pub max_tx_bytes:usize, // e.g., 64 * 1024
+}
+
+impl Default for TxLimits{
+    fn default()->Self{
+        TxLimits{
+            max_memo_bytes:256,
+            max_tx_bytes:64*1024,
+        }
+    }
+}
+
+/// Which `TxLimits` bound a transaction exceeds
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum LimitError{
+    #[error("memo is {bytes} bytes, limit is {max}")]
+    MemoTooLarge{bytes:usize,max:usize},
+    #[error("transaction is {bytes} bytes, limit is {max}")]
+    TxTooLarge{bytes:usize,max:usize},
+}
+
+/// Why a transaction can't be signed or doesn't verify
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum TxError{
+    /// A signature or key field isn't valid base64
+    #[error("invalid base64 in {0}")]
+    InvalidEncoding(&'static str),
+    #[error("invalid public key")]
+    InvalidPublicKey,
+    #[error("invalid signature bytes")]
+    InvalidSignatureBytes,
+    #[error("signature verification failed")]
+    BadSignature,
+    /// The signing key (or multisig policy) derives another address than `tx.sender`
+    #[error("sender mismatch: key derives {derived}, transaction claims {claimed}")]
+    SenderMismatch{derived:String,claimed:String},
+    #[error("fee payer signature without a fee payer")]
+    UnexpectedFeePayerSignature,
+    #[error("missing fee payer signature")]
+    MissingFeePayerSignature,
+    #[error("fee payer must differ from sender")]
+    FeePayerIsSender,
+    #[error("fee payer mismatch: key derives {derived}, transaction claims {claimed}")]
+    FeePayerMismatch{derived:String,claimed:String},
+    #[error("not a multisig transaction")]
+    NotMultisig,
+    #[error("multisig: {0}")]
+    Multisig(#[from] MultisigError),
+    /// The `TxSigner` failed (e.g. a remote signer was unreachable)
+    #[error("signing failed: {0}")]
+    Signing(String),
+}
+
+/// Earliest block a time-locked transaction may be included in
+#[derive(Debug,Clone,Copy,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub enum TimeLock{
+    /// Block height at or above this
+    Height(u64),
+    /// Block timestamp (unix seconds) at or above this
+    Timestamp(u64),
+}
+
+impl TimeLock{
+    /// True once a block at `height` / `timestamp` may include the transaction
+    pub fn is_unlocked(&self,height:u64,timestamp:u64)->bool{
+        match self{
+            TimeLock::Height(h)=>height>=*h,
+            TimeLock::Timestamp(t)=>timestamp>=*t,
+        }
+    }
+
+    /// How far past `height` / `timestamp` the lock ends: blocks for a height lock, seconds
+    /// for a timestamp lock
+    pub fn remaining(&self,height:u64,timestamp:u64)->u64{
+        match self{
+            TimeLock::Height(h)=>h.saturating_sub(height),
+            TimeLock::Timestamp(t)=>t.saturating_sub(timestamp),
+        }
+    }
+}
+
+/// One credit of a `MultiTransfer`
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub struct TransferOutput{
+    pub receiver:String,
+    pub amount:u64,
+}
+
+/// What a transaction does once its envelope (sender, fee, nonce) is accepted
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
+pub enum TxPayload{
+    /// Move `amount` from sender to `receiver`
+    Transfer{
+        /// Receiver address
+        receiver:String,
+        /// Amount in smallest unit (u64)
+        amount:u64,
+    },
+    /// Bind a consensus key and node endpoint to the sender account
+    RegisterValidator{
+        /// Ed25519 consensus public key encoded as base64
+        consensus_pubkey:String,
+        /// Schnorrkel (sr25519) VRF public key encoded as base64
+        vrf_pubkey:String,
+        /// Network endpoint of the validator node (e.g. "1.2.3.4:30333")
+        endpoint:String,
+    },
+    /// Remove the sender account from the validator registry
+    UnregisterValidator,
+    /// Report a proposer that signed two blocks at the same height
+    Evidence(DoubleSignEvidence),
+    /// Record the sender's network metrics (and attestations) for the current epoch
+    MetricReport(MetricReport),
+    /// Bond `amount` of the sender's balance as validator stake
+    Stake{amount:u64},
+    /// Release `amount` of bonded stake back to the sender's balance
+    Unstake{amount:u64},
+    /// Vote on a governance proposal, weighted by the sender's bonded stake
+    GovernanceVote{proposal_id:u64,approve:bool},
+    /// Bind a BLS key to the sender's validator for aggregate finality votes
+    RegisterBlsKey{
+        /// Compressed BLS12-381 public key encoded as base64
+        bls_pubkey:String,
+        /// Signature over the key proving possession of the secret, base64
+        proof_of_possession:String,
+    },
+    /// No-op that only consumes the nonce (and fee); replaces a pending transaction to cancel it
+    Cancel,
+    /// Pay several receivers at once; all outputs are credited or the whole transaction fails
+    MultiTransfer{outputs:Vec<TransferOutput>},
+    /// Create asset `asset` (see `asset::is_valid_asset_id`) with its whole `supply`
+    /// credited to the sender
+    IssueAsset{asset:String,supply:u64},
+    /// Move `amount` of an issued asset from sender to `receiver`
+    TransferAsset{asset:String,receiver:String,amount:u64},
+    /// Register or renew `name` for the sender (see `names`), burning the registration cost
+    RegisterName{name:String},
+}
 
 /// The core transcation structure (unsigned).
 /// Keep fields small and canonical. We avoid fields that very in serialization
-#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
 pub struct Transaction{
-    /// Sender address (string representation of public key hash / address)\
+    /// Sender address (string representation of public key hash / address)
     pub sender:String,
-    /// Receiver address
-    pub receiver:String,
-    /// Amount in smallest unit (u64)
-    pub amount:u64,
+    /// Operation carried by this transaction
+    pub payload:TxPayload,
     /// Fee paid to validtors (u64)
     pub fee:u64,
     /// Nonce for replay protection
@@ -38,36 +200,107 @@ pub struct Transaction{
     pub timestamp:u64,
     /// Optional memo/data
     pub memo:Option<String>,
+    /// Account that pays `fee` instead of the sender (must co-sign, see `FeePayerSignature`)
+    #[serde(default)]
+    pub fee_payer:Option<String>,
+    /// Memo readable only by the receiver
+    #[serde(default)]
+    #[schema(value_type=Option<Object>)]
+    pub encrypted_memo:Option<EncryptedMemo>,
+    /// Not valid for inclusion before this height / time
+    #[serde(default)]
+    pub not_before:Option<TimeLock>,
 }
 
 impl Transaction{
-    // Create a new unsigned transaction (timestamp auto-filled)
+    // Create a new unsigned transfer transaction (timestamp auto-filled)
     pub fn new(sender:String,receiver:String,amount:u64,fee:u64,nonce:u64,memo:Option<String>)->Self{
+        Transaction::with_payload(sender,TxPayload::Transfer{receiver,amount},fee,nonce,memo)
+    }
+
+    /// Create a new unsigned transaction carrying any payload (timestamp auto-filled)
+    pub fn with_payload(sender:String,payload:TxPayload,fee:u64,nonce:u64,memo:Option<String>)->Self{
         let timestamp=SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map(|d| d.as_secs())
         .unwrap_or(0);
         Transaction{
             sender,
-            receiver,
-            amount,
+            payload,
             fee,
             nonce,
             timestamp,
-            memo
+            memo,
+            fee_payer:None,
+            encrypted_memo:None,
+            not_before:None,
         }
     }
 
+    /// Lock the transaction until `lock` expires
+    pub fn with_time_lock(mut self,lock:TimeLock)->Self{
+        self.not_before=Some(lock);
+        self
+    }
+
+    /// Attach a memo sealed to the receiver (see `EncryptedMemo::encrypt_for_ed25519`)
+    pub fn with_encrypted_memo(mut self,memo:EncryptedMemo)->Self{
+        self.encrypted_memo=Some(memo);
+        self
+    }
+
+    /// Cancel the pending transaction at `nonce`. Must pay the replacement bump over its fee
+    /// (see `mempool::MIN_REPLACEMENT_BUMP_BPS`).
+    pub fn cancel(sender:String,nonce:u64,fee:u64)->Self{
+        Transaction::with_payload(sender,TxPayload::Cancel,fee,nonce,None)
+    }
+
+    /// Have `fee_payer` cover the fee; the sender then only pays the payload amount
+    pub fn with_fee_payer(mut self,fee_payer:String)->Self{
+        self.fee_payer=Some(fee_payer);
+        self
+    }
+
     /// Produce deterministic bytes for signing / hashing
-    /// Uses bincode serialization ( Compact + deterministic)
+    /// Uses the pinned canonical encoding (fixed-int little-endian bincode, see `canonical`)
     pub fn canonical_bytes(&self)->Vec<u8>{
-        // We rely on bincode default options which are deterministic for primitive types
-        // Avoid Option<> variants chainging ordering by serializing the struct as-is.
-        bincode::DefaultOptions::new()
-        .with_fixint_encoding()      // Ensure u64 always takes 8 bytes
-        .with_little_endian()        // Explicit byte order
-        .serialize(self)
-        .expect("bincode serialization should succed for Transaction")
+        canonical::encode(self)
+    }
+
+    /// Liquid balance this transaction takes out of each account if it executes: the moved
+    /// or bonded amount from the sender, the fee from the fee payer (or the sender).
+    /// Saturates instead of overflowing; such a transaction can't pass validation anyway.
+    pub fn debits(&self)->BTreeMap<&str,u64>{
+        let amount=match &self.payload{
+            TxPayload::Transfer{amount,..} | TxPayload::Stake{amount}=>*amount,
+            TxPayload::MultiTransfer{outputs}=>outputs.iter().fold(0u64,|total,o| total.saturating_add(o.amount)),
+            TxPayload::RegisterName{..}=>NAME_REGISTRATION_COST,
+            _=>0,
+        };
+        let mut debits=BTreeMap::new();
+        debits.insert(self.sender.as_str(),amount);
+        let payer=self.fee_payer.as_deref().unwrap_or(&self.sender);
+        let fee=debits.entry(payer).or_insert(0);
+        *fee=fee.saturating_add(self.fee);
+        debits
+    }
+
+    /// True if `address` sends, pays the fee of, or receives (as written, `@name`s
+    /// unresolved) this transaction
+    pub fn involves(&self,address:&str)->bool{
+        if self.sender==address || self.fee_payer.as_deref()==Some(address){
+            return true
+        }
+        match &self.payload{
+            TxPayload::Transfer{receiver,..} | TxPayload::TransferAsset{receiver,..}=>receiver==address,
+            TxPayload::MultiTransfer{outputs}=>outputs.iter().any(|output| output.receiver==address),
+            _=>false,
+        }
+    }
+
+    /// Memo bytes counted against `TxLimits::max_memo_bytes`
+    pub fn memo_bytes(&self)->usize{
+        self.memo.as_ref().map_or(0,String::len)+self.encrypted_memo.as_ref().map_or(0,|m| m.ciphertext.len())
     }
 
     /// Compute SHA-256 hash of canonical bytes -> hex string
@@ -80,55 +313,251 @@ impl Transaction{
     }
 }
 
-/// SignedTransaction:include the serialized Transaction plus the signature and public key
+/// Signature algorithm used by a single-key transaction.
+/// The scheme tag is part of the signed bytes, so a signature can't be replayed under another scheme.
+#[derive(Debug,Clone,Copy,Default,Serialize,Deserialize,PartialEq,Eq,Hash,ToSchema)]
+pub enum SignatureScheme{
+    #[default]
+    Ed25519,
+    /// ECDSA over secp256k1 (SEC1 compressed public keys, 64-byte r||s signatures)
+    Secp256k1,
+}
+
+impl SignatureScheme{
+    /// Verify `signature` over `msg` with raw public key bytes
+    pub fn verify(&self,pubkey:&[u8],msg:&[u8],signature:&[u8])->Result<(),TxError>{
+        match self{
+            SignatureScheme::Ed25519=>{
+                let signature=Signature::from_bytes(signature).map_err(|_| TxError::InvalidSignatureBytes)?;
+                let public_key=PublicKey::from_bytes(pubkey).map_err(|_| TxError::InvalidPublicKey)?;
+                public_key
+                .verify(msg,&signature)
+                .map_err(|_| TxError::BadSignature)
+            }
+            SignatureScheme::Secp256k1=>{
+                use k256::ecdsa::signature::Verifier as _;
+                let signature=k256::ecdsa::Signature::from_slice(signature)
+                .map_err(|_| TxError::InvalidSignatureBytes)?;
+                let public_key=k256::ecdsa::VerifyingKey::from_sec1_bytes(pubkey)
+                .map_err(|_| TxError::InvalidPublicKey)?;
+                public_key
+                .verify(msg,&signature)
+                .map_err(|_| TxError::BadSignature)
+            }
+        }
+    }
+
+    /// Account address for raw public key bytes under this scheme.
+    /// Ed25519: sha256(pubkey)[0..20]; secp256k1: sha256(0x01 || compressed pubkey)[0..20].
+    pub fn address(&self,pubkey:&[u8])->Result<String,TxError>{
+        match self{
+            SignatureScheme::Ed25519=>{
+                let public_key=PublicKey::from_bytes(pubkey).map_err(|_| TxError::InvalidPublicKey)?;
+                Ok(pubkey_to_address_hex(&public_key))
+            }
+            SignatureScheme::Secp256k1=>{
+                let public_key=k256::ecdsa::VerifyingKey::from_sec1_bytes(pubkey)
+                .map_err(|_| TxError::InvalidPublicKey)?;
+                Ok(secp256k1_address_hex(&public_key))
+            }
+        }
+    }
+}
+
+/// Fee payer's co-signature on a sponsored transaction
 #[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct FeePayerSignature{
+    /// Signature encoded as base64
+    pub signature:String,
+    /// Public key encoded as base64
+    pub pubkey:String,
+    pub scheme:SignatureScheme,
+}
+
+/// SignedTransaction:include the serialized Transaction plus the signature and public key
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq,ToSchema)]
 pub struct SignedTransaction{
     pub tx:Transaction,
     /// Signature encoded as base64
     pub signature:String,
-    /// Public key encoded as base64(ed25519 public key bytes)
+    /// Public key encoded as base64 (ed25519 bytes or compressed secp256k1 SEC1 bytes)
     pub pubkey:String,
+    /// Scheme of `signature` / `pubkey`
+    #[serde(default)]
+    pub scheme:SignatureScheme,
+    /// M-of-N signatures for a multisig sender; `signature` / `pubkey` are empty when set
+    #[serde(default)]
+    #[schema(value_type=Option<Object>)]
+    pub multisig:Option<MultisigSignatures>,
+    /// Co-signature of `tx.fee_payer`; required exactly when it is set
+    #[serde(default)]
+    #[schema(value_type=Option<Object>)]
+    pub fee_payer_signature:Option<FeePayerSignature>,
 }
 
 impl SignedTransaction{
+    /// Bytes actually signed: the scheme tag followed by the canonical transaction
+    pub fn signing_bytes(scheme:SignatureScheme,tx:&Transaction)->Vec<u8>{
+        canonical::encode(&(scheme,tx))
+    }
+
+    /// Bytes signed by the fee payer. Domain-separated from `signing_bytes` so a sender's
+    /// signature can't double as a fee payer's.
+    pub fn fee_payer_signing_bytes(scheme:SignatureScheme,tx:&Transaction)->Vec<u8>{
+        canonical::encode(&("fee_payer",scheme,tx))
+    }
+
+    /// Construct a SignedTransaction from a transaction and any signer (local key, HSM, ...)
+    pub fn sign_with(tx:&Transaction,signer:&dyn TxSigner)->Result<Self,TxError>{
+        let scheme=signer.scheme();
+        let sig=signer.sign(&Self::signing_bytes(scheme,tx)).map_err(TxError::Signing)?;
+        Ok(SignedTransaction{
+            tx:tx.clone(),
+            signature:general_purpose::STANDARD.encode(sig),
+            pubkey:general_purpose::STANDARD.encode(signer.public_key()),
+            scheme,
+            multisig:None,
+            fee_payer_signature:None,
+        })
+    }
+
     /// Construct a SignedTransaction from a transaction and an ed25519 keypair
     pub fn sign_with_keypair(tx:&Transaction,keypair:&Keypair)->Self{
-        let msg=tx.canonical_bytes();
-        let sig:Signature=keypair.sign(&msg);
+        Self::sign_with(tx,keypair).expect("local ed25519 signing is infallible")
+    }
+
+    /// Construct a SignedTransaction from a transaction and a secp256k1 signing key
+    pub fn sign_with_secp256k1(tx:&Transaction,key:&k256::ecdsa::SigningKey)->Self{
+        Self::sign_with(tx,key).expect("local secp256k1 signing is infallible")
+    }
+
+    /// Start a multisig transaction for `policy`; add signatures with `add_multisig_signature`
+    pub fn multisig(tx:&Transaction,policy:MultisigPolicy)->Self{
         SignedTransaction{
             tx:tx.clone(),
-            signature:general_purpose::STANDARD.encode(sig.to_bytes()),
-            pubkey:general_purpose::STANDARD.encode(keypair.public.to_bytes())
+            signature:String::new(),
+            pubkey:String::new(),
+            scheme:SignatureScheme::Ed25519,
+            multisig:Some(MultisigSignatures::new(policy)),
+            fee_payer_signature:None,
+        }
+    }
+
+    /// Add one co-signer's signature to a multisig transaction (Ed25519 signers only)
+    pub fn add_multisig_signature(&mut self,signer:&dyn TxSigner)->Result<(),TxError>{
+        let msg=Self::signing_bytes(SignatureScheme::Ed25519,&self.tx);
+        self.multisig
+        .as_mut()
+        .ok_or(TxError::NotMultisig)?
+        .sign(&msg,signer)?;
+        Ok(())
+    }
+
+    /// Co-sign as the fee payer named in `tx.fee_payer`
+    pub fn sign_fee_payer(&mut self,signer:&dyn TxSigner)->Result<(),TxError>{
+        let scheme=signer.scheme();
+        let sig=signer.sign(&Self::fee_payer_signing_bytes(scheme,&self.tx)).map_err(TxError::Signing)?;
+        self.fee_payer_signature=Some(FeePayerSignature{
+            signature:general_purpose::STANDARD.encode(sig),
+            pubkey:general_purpose::STANDARD.encode(signer.public_key()),
+            scheme,
+        });
+        Ok(())
+    }
+
+    /// Encoded size: canonical transaction bytes plus keys and signatures
+    pub fn encoded_size(&self)->usize{
+        let multisig=self.multisig.as_ref().map_or(0,|m| {
+            m.policy.pubkeys.iter().map(String::len).sum::<usize>()+m.signatures.values().map(String::len).sum::<usize>()
+        });
+        let fee_payer=self.fee_payer_signature.as_ref().map_or(0,|s| s.signature.len()+s.pubkey.len());
+        self.tx.canonical_bytes().len()+self.signature.len()+self.pubkey.len()+multisig+fee_payer
+    }
+
+    /// Check memo and encoded size against `limits`
+    pub fn check_limits(&self,limits:&TxLimits)->Result<(),LimitError>{
+        let memo=self.tx.memo_bytes();
+        if memo>limits.max_memo_bytes{
+            return Err(LimitError::MemoTooLarge{bytes:memo,max:limits.max_memo_bytes})
+        }
+        let size=self.encoded_size();
+        if size>limits.max_tx_bytes{
+            return Err(LimitError::TxTooLarge{bytes:size,max:limits.max_tx_bytes})
         }
+        Ok(())
     }
 
-    /// Verify signature and pubkey match the transaction
-    pub fn verify(&self)->Result<(),String>{
+    /// Verify signature and pubkey match the transaction, and that `tx.sender` is the
+    /// address derived from `pubkey` (see `SignatureScheme::address`).
+    /// Sponsored transactions must also carry a valid signature from `tx.fee_payer`.
+    /// Size limits are separate (`check_limits`), as only the node knows its own.
+    pub fn verify(&self)->Result<(),TxError>{
+        self.verify_sender()?;
+        self.verify_fee_payer()
+    }
+
+    fn verify_sender(&self)->Result<(),TxError>{
+        if let Some(multisig)=&self.multisig{
+            // funds only move once the policy's threshold of co-signers has signed
+            multisig.verify(&Self::signing_bytes(SignatureScheme::Ed25519,&self.tx))?;
+            let derived=multisig.policy.address();
+            if derived!=self.tx.sender{
+                return Err(TxError::SenderMismatch{derived,claimed:self.tx.sender.clone()})
+            }
+            return Ok(())
+        }
+        let derived=self.verify_signature()?;
+        if derived!=self.tx.sender{
+            return Err(TxError::SenderMismatch{derived,claimed:self.tx.sender.clone()})
+        }
+        Ok(())
+    }
+
+    fn verify_fee_payer(&self)->Result<(),TxError>{
+        match (&self.tx.fee_payer,&self.fee_payer_signature){
+            (None,None)=>Ok(()),
+            (None,Some(_))=>Err(TxError::UnexpectedFeePayerSignature),
+            (Some(_),None)=>Err(TxError::MissingFeePayerSignature),
+            (Some(payer),Some(cosig))=>{
+                if *payer==self.tx.sender{
+                    return Err(TxError::FeePayerIsSender)
+                }
+                let sig_bytes=general_purpose::STANDARD
+                .decode(&cosig.signature)
+                .map_err(|_| TxError::InvalidEncoding("fee payer signature"))?;
+                let pk_bytes=general_purpose::STANDARD
+                .decode(&cosig.pubkey)
+                .map_err(|_| TxError::InvalidEncoding("fee payer pubkey"))?;
+                cosig.scheme.verify(&pk_bytes,&Self::fee_payer_signing_bytes(cosig.scheme,&self.tx),&sig_bytes)?;
+                let derived=cosig.scheme.address(&pk_bytes)?;
+                if derived!=*payer{
+                    return Err(TxError::FeePayerMismatch{derived,claimed:payer.clone()})
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Signature check WITHOUT the sender/pubkey binding. Test-only: in production any key
+    /// could then spend from any address.
+    #[cfg(test)]
+    pub(crate) fn verify_without_sender_check(&self)->Result<(),TxError>{
+        self.verify_signature().map(|_| ())
+    }
+
+    /// Check the signature under `scheme` and return the address derived from `pubkey`
+    fn verify_signature(&self)->Result<String,TxError>{
         // decode signature & pubkey
         let sig_bytes=general_purpose::STANDARD
         .decode(&self.signature)
-        .map_err(|e| format!("Invalid signature base64: {}",e))?;
+        .map_err(|_| TxError::InvalidEncoding("signature"))?;
         let pk_bytes=general_purpose::STANDARD
         .decode(&self.pubkey)
-        .map_err(|e| format!("Invalid pubkey base64: {}",e))?;
-
-        let signature=Signature::from_bytes(&sig_bytes).map_err(|e| format!("Invalid signature bytes: {}"))?;
-        let public_key=PublicKey::from_bytes(&pk_bytes).map_err(|e| format!("Invalid pubkey bytes: {}"))?;
-
-        // Verify that the claimed sender address matches public key (Optional mapping)
-        // NOTE: Here we assume sender is hex(pubkey_hash) or base64(pubkey).The address schema is up to you
-        // We'll skip strict address match; projects usually derive an address from pubkey (e.g., hash).
-        // If your sender field is designed as the hex of pubkey hash, verify accordingly.
-        // Example check (optional):
-        // let derived_addr = pubkey_to_address(&public_key);
-        // if derived_addr != self.tx.sender { return Err("sender mismatch".into()); }
-
-        // verify signature
-        let msg=self.tx.canonical_bytes();
-        public_key
-        .verify(&msg,&signature)
-        .map_err(|e| format!("signature verification failed: {}",e))?;
-        Ok(())
+        .map_err(|_| TxError::InvalidEncoding("pubkey"))?;
+
+        let msg=Self::signing_bytes(self.scheme,&self.tx);
+        self.scheme.verify(&pk_bytes,&msg,&sig_bytes)?;
+        self.scheme.address(&pk_bytes)
     }
 
     /// Get SHA-256 tx hash (hex) from inner transaction
@@ -143,8 +572,22 @@ pub fn generate_ed25519_keypair()->Keypair{
     Keypair::generate(&mut cspring)
 }
 
-///OPTIONAL: helper to produce an address string from public key bytes
-///Here we use SHA-256 of public and hex encode first 20 bytes (like an address)
+/// Helper: generate a secp256k1 signing key
+pub fn generate_secp256k1_key()->k256::ecdsa::SigningKey{
+    k256::ecdsa::SigningKey::random(&mut rand::rngs::OsRng)
+}
+
+/// Address of a secp256k1 account: SHA-256 of 0x01 || compressed key, first 20 bytes hex encoded.
+/// The prefix keeps secp256k1 and Ed25519 addresses in separate domains.
+pub fn secp256k1_address_hex(pubkey:&k256::ecdsa::VerifyingKey)->String{
+    let mut hasher=Sha256::new();
+    hasher.update([0x01]);
+    hasher.update(pubkey.to_encoded_point(true).as_bytes());
+    hex::encode(&hasher.finalize()[0..20])
+}
+
+/// Address of an account: SHA-256 of the public key, first 20 bytes hex encoded.
+/// `SignedTransaction::verify` requires `tx.sender` to equal this for the signing key.
 pub fn pubkey_to_address_hex(pubkey:&PublicKey)->String{
     let mut hasher=Sha256::new();
     hasher.update(pubkey.to_bytes());
@@ -157,7 +600,7 @@ pub fn pubkey_to_address_hex(pubkey:&PublicKey)->String{
 mod tests{
     use super::*;
     use ed25519_dalek::Keypair;
-    use rand::rngs::OsRng;
+    use rand_core::OsRng;
 
     #[test]
     fn tx_sign_and_verify_flow(){
@@ -196,7 +639,10 @@ mod tests{
 
         // changing tx should make verification fail
         let mut bad=signed.clone();
-        bad.tx.amount=999999;
+        bad.tx.payload=TxPayload::Transfer{
+            receiver:"receiver_address_example".to_string(),
+            amount:999999,
+        };
         assert!(bad.verify().is_err());
     }
 
@@ -206,4 +652,89 @@ mod tests{
         let addr=pubkey_to_address_hex(&keypair.public);
         assert_eq!(addr.len(),40);  // 20 bytes -> 40 chars
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn rejects_sender_not_matching_pubkey(){
+        let keypair=generate_ed25519_keypair();
+        let victim=pubkey_to_address_hex(&generate_ed25519_keypair().public);
+
+        // validly signed, but claims to spend from someone else's address
+        let tx=Transaction::new(victim,"thief".to_string(),1_000,1,0,None);
+        let signed=SignedTransaction::sign_with_keypair(&tx,&keypair);
+        assert!(matches!(signed.verify(),Err(TxError::SenderMismatch{..})));
+        assert!(signed.verify_without_sender_check().is_ok());
+    }
+
+    #[test]
+    fn secp256k1_sign_and_verify(){
+        let key=generate_secp256k1_key();
+        let addr=secp256k1_address_hex(key.verifying_key());
+        let tx=Transaction::new(addr,"receiver".to_string(),5,1,0,None);
+
+        let signed=SignedTransaction::sign_with_secp256k1(&tx,&key);
+        assert_eq!(signed.scheme,SignatureScheme::Secp256k1);
+        assert!(signed.verify().is_ok());
+
+        // the scheme tag is signed: relabelling the signature breaks it
+        let mut relabelled=signed.clone();
+        relabelled.scheme=SignatureScheme::Ed25519;
+        assert!(relabelled.verify().is_err());
+
+        let mut tampered=signed;
+        tampered.tx.fee=2;
+        assert!(tampered.verify().is_err());
+    }
+
+    #[test]
+    fn fee_payer_cosignature(){
+        let keypair=generate_ed25519_keypair();
+        let sponsor=generate_ed25519_keypair();
+        let tx=Transaction::new(pubkey_to_address_hex(&keypair.public),"bob".to_string(),10,1,0,None)
+        .with_fee_payer(pubkey_to_address_hex(&sponsor.public));
+
+        let mut signed=SignedTransaction::sign_with_keypair(&tx,&keypair);
+        assert_eq!(signed.verify(),Err(TxError::MissingFeePayerSignature));
+
+        // a co-signature from anyone but the named payer is rejected
+        signed.sign_fee_payer(&generate_ed25519_keypair()).unwrap();
+        assert!(matches!(signed.verify(),Err(TxError::FeePayerMismatch{..})));
+
+        signed.sign_fee_payer(&sponsor).unwrap();
+        assert!(signed.verify().is_ok());
+
+        // the sender's signature covers the fee payer, so it can't be swapped out
+        let mut swapped=signed.clone();
+        swapped.tx.fee_payer=Some("someone_else".to_string());
+        assert!(swapped.verify().is_err());
+    }
+
+    #[test]
+    fn encrypted_memo_is_signed(){
+        use crate::memo::EncryptedMemo;
+
+        let keypair=generate_ed25519_keypair();
+        let receiver=generate_ed25519_keypair();
+        let memo=EncryptedMemo::encrypt_for_ed25519("order 7",&receiver.public).unwrap();
+        let tx=Transaction::new(pubkey_to_address_hex(&keypair.public),"bob".to_string(),1,1,0,None)
+        .with_encrypted_memo(memo);
+        let signed=SignedTransaction::sign_with_keypair(&tx,&keypair);
+        assert!(signed.verify().is_ok());
+        assert_eq!(signed.tx.encrypted_memo.as_ref().unwrap().decrypt_with_keypair(&receiver).unwrap(),"order 7");
+
+        let mut swapped=signed;
+        swapped.tx.encrypted_memo=Some(EncryptedMemo::encrypt_for_ed25519("order 8",&receiver.public).unwrap());
+        assert!(swapped.verify().is_err());
+    }
+
+    #[test]
+    fn check_limits_enforces_size_limits(){
+        let keypair=generate_ed25519_keypair();
+        let tx=Transaction::new(pubkey_to_address_hex(&keypair.public),"bob".to_string(),1,1,0,Some("x".repeat(300)));
+        let signed=SignedTransaction::sign_with_keypair(&tx,&keypair);
+        assert!(matches!(signed.check_limits(&TxLimits::default()),Err(LimitError::MemoTooLarge{bytes:300,max:256})));
+        assert_eq!(signed.check_limits(&TxLimits::default()).unwrap_err().to_string(),"memo is 300 bytes, limit is 256");
+        assert!(signed.check_limits(&TxLimits{max_memo_bytes:300,..TxLimits::default()}).is_ok());
+        // signatures verify whatever the size
+        assert!(signed.verify().is_ok());
+    }
+}
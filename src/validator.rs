@@ -0,0 +1,379 @@
+// src/validator.rs
+
+//! On-chain validator registry
+//! - Populated by `RegisterValidator` / `UnregisterValidator` transactions
+//! - Binds a consensus public key, VRF public key and node endpoint to an account address
+//! - Optionally binds a BLS key (with proof of possession) for aggregate finality votes
+//! - Provides the canonical validator pool for PoI selection
+
+use crate::bls::verify_proof_of_possession;
+use crate::consensus::NodeMetrics;
+use base64::{engine::general_purpose,Engine as _};
+use ed25519_dalek::PublicKey;
+use imbl::OrdMap;
+use serde::{Deserialize,Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Errors returned by registry operations
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum RegistryError{
+    #[error("validator already registered")]
+    AlreadyRegistered,
+    #[error("validator not registered")]
+    NotRegistered,
+    #[error("invalid consensus key")]
+    InvalidConsensusKey,
+    #[error("consensus key already in use")]
+    ConsensusKeyInUse,
+    #[error("invalid VRF key")]
+    InvalidVrfKey,
+    #[error("endpoint is empty")]
+    EmptyEndpoint,
+    #[error("validator is jailed")]
+    Jailed,
+    #[error("invalid BLS key or proof of possession")]
+    InvalidBlsKey,
+}
+
+/// A registered validator
+#[derive(Debug,Clone,PartialEq,Eq,Serialize,Deserialize)]
+pub struct ValidatorInfo{
+    /// Account address that owns this validator
+    pub address:String,
+    /// Ed25519 consensus public key encoded as base64
+    pub consensus_pubkey:String,
+    /// Schnorrkel (sr25519) VRF public key encoded as base64
+    pub vrf_pubkey:String,
+    /// Network endpoint of the validator node
+    pub endpoint:String,
+}
+
+/// Set of registered validators keyed by account address.
+/// Uses ordered maps so iteration order is identical on every node; they are persistent,
+/// so cloning the registry (state snapshots) is cheap.
+#[derive(Debug,Clone,Default,Serialize,Deserialize)]
+pub struct ValidatorRegistry{
+    validators:OrdMap<String,ValidatorInfo>,
+    /// address -> first epoch at which the validator is released from jail.
+    /// Kept separately so unregistering doesn't clear a jail sentence.
+    jailed:OrdMap<String,u64>,
+    /// address -> consecutive epochs at or above `min_score` while jailed for low PoI score
+    score_jailed:OrdMap<String,u64>,
+    /// address -> BLS public key (base64) used for aggregate precommits
+    bls_keys:OrdMap<String,String>,
+}
+
+impl ValidatorRegistry{
+    pub fn new()->Self{
+        Self::default()
+    }
+
+    /// Check that a registration would succeed WITHOUT mutating the registry
+    pub fn validate_register(
+        &self,
+        address:&str,
+        consensus_pubkey:&str,
+        vrf_pubkey:&str,
+        endpoint:&str,
+    )->Result<(),RegistryError>{
+        if self.validators.contains_key(address){
+            return Err(RegistryError::AlreadyRegistered)
+        }
+        if endpoint.trim().is_empty(){
+            return Err(RegistryError::EmptyEndpoint)
+        }
+        let key_bytes=general_purpose::STANDARD
+        .decode(consensus_pubkey)
+        .map_err(|_| RegistryError::InvalidConsensusKey)?;
+        PublicKey::from_bytes(&key_bytes).map_err(|_| RegistryError::InvalidConsensusKey)?;
+        if self.validators.values().any(|v| v.consensus_pubkey==consensus_pubkey){
+            return Err(RegistryError::ConsensusKeyInUse)
+        }
+        let vrf_bytes=general_purpose::STANDARD
+        .decode(vrf_pubkey)
+        .map_err(|_| RegistryError::InvalidVrfKey)?;
+        schnorrkel::PublicKey::from_bytes(&vrf_bytes).map_err(|_| RegistryError::InvalidVrfKey)?;
+        Ok(())
+    }
+
+    /// Register a validator for `address`
+    pub fn register(
+        &mut self,
+        address:String,
+        consensus_pubkey:String,
+        vrf_pubkey:String,
+        endpoint:String,
+    )->Result<(),RegistryError>{
+        self.validate_register(&address,&consensus_pubkey,&vrf_pubkey,&endpoint)?;
+        self.validators.insert(
+            address.clone(),
+            ValidatorInfo{
+                address,
+                consensus_pubkey,
+                vrf_pubkey,
+                endpoint,
+            },
+        );
+        Ok(())
+    }
+
+    /// Check that an unregistration would succeed WITHOUT mutating the registry
+    pub fn validate_unregister(&self,address:&str)->Result<(),RegistryError>{
+        if !self.validators.contains_key(address){
+            return Err(RegistryError::NotRegistered)
+        }
+        Ok(())
+    }
+
+    /// Remove the validator owned by `address`
+    pub fn unregister(&mut self,address:&str)->Result<ValidatorInfo,RegistryError>{
+        self.bls_keys.remove(address);
+        self.validators.remove(address).ok_or(RegistryError::NotRegistered)
+    }
+
+    /// Check a BLS key registration WITHOUT mutating the registry
+    pub fn validate_bls_key(&self,address:&str,bls_pubkey:&str,proof_of_possession:&str)->Result<(),RegistryError>{
+        if !self.validators.contains_key(address){
+            return Err(RegistryError::NotRegistered)
+        }
+        verify_proof_of_possession(bls_pubkey,proof_of_possession).map_err(|_| RegistryError::InvalidBlsKey)
+    }
+
+    /// Bind (or rotate) the BLS key of a registered validator
+    pub fn set_bls_key(&mut self,address:&str,bls_pubkey:String,proof_of_possession:&str)->Result<(),RegistryError>{
+        self.validate_bls_key(address,&bls_pubkey,proof_of_possession)?;
+        self.bls_keys.insert(address.to_string(),bls_pubkey);
+        Ok(())
+    }
+
+    /// Registered BLS public key of `address`, base64
+    pub fn bls_key(&self,address:&str)->Option<&str>{
+        self.bls_keys.get(address).map(String::as_str)
+    }
+
+    pub fn get(&self,address:&str)->Option<&ValidatorInfo>{
+        self.validators.get(address)
+    }
+
+    pub fn is_registered(&self,address:&str)->bool{
+        self.validators.contains_key(address)
+    }
+
+    pub fn len(&self)->usize{
+        self.validators.len()
+    }
+
+    pub fn is_empty(&self)->bool{
+        self.validators.is_empty()
+    }
+
+    /// Jail `address` until `release_epoch` (exclusive). Longer sentences are never shortened.
+    pub fn jail(&mut self,address:&str,release_epoch:u64){
+        let entry=self.jailed.entry(address.to_string()).or_insert(release_epoch);
+        *entry=(*entry).max(release_epoch);
+    }
+
+    /// True if `address` is still jailed at `epoch`
+    pub fn is_jailed(&self,address:&str,epoch:u64)->bool{
+        self.jailed.get(address).is_some_and(|release| epoch<*release)
+    }
+
+    /// True if `address` is jailed for falling below the minimum PoI score
+    pub fn is_score_jailed(&self,address:&str)->bool{
+        self.score_jailed.contains_key(address)
+    }
+
+    /// Apply the minimum-score rule at an epoch boundary.
+    /// Validators scoring below `min_score` are jailed (or have their recovery streak reset);
+    /// jailed validators at or above it are released after `unjail_epochs` consecutive epochs.
+    /// Returns the addresses newly jailed this epoch.
+    pub fn update_eligibility(&mut self,scores:&HashMap<String,u64>,min_score:u64,unjail_epochs:u64)->Vec<String>{
+        let mut newly_jailed=Vec::new();
+        for address in self.validators.keys(){
+            let score=scores.get(address).copied().unwrap_or(0);
+            if score<min_score{
+                if self.score_jailed.insert(address.clone(),0).is_none(){
+                    newly_jailed.push(address.clone());
+                }
+            }else if let Some(streak)=self.score_jailed.get_mut(address){
+                *streak+=1;
+            }
+        }
+        let released:Vec<String>=self
+        .score_jailed
+        .iter()
+        .filter(|(_,streak)| **streak>=unjail_epochs)
+        .map(|(address,_)| address.clone())
+        .collect();
+        for address in released{
+            self.score_jailed.remove(&address);
+        }
+        newly_jailed
+    }
+
+    /// True if `address` is registered and not jailed at `epoch`
+    pub fn is_active(&self,address:&str,epoch:u64)->bool{
+        self.is_registered(address) && !self.is_jailed(address,epoch) && !self.is_score_jailed(address)
+    }
+
+    /// Iterate validators in address order
+    pub fn iter(&self)->impl Iterator<Item=&ValidatorInfo>{
+        self.validators.values()
+    }
+
+    /// Build the selection pool from reported metrics, keeping only validators active at `epoch`
+    pub fn pool(&self,metrics:&HashMap<String,NodeMetrics>,epoch:u64)->HashMap<String,NodeMetrics>{
+        metrics
+        .iter()
+        .filter(|(addr,_)| self.is_active(addr,epoch))
+        .map(|(addr,m)| (addr.clone(),m.clone()))
+        .collect()
+    }
+}
+
+/// Registry fixtures for tests across the crate
+#[cfg(test)]
+pub(crate) mod testing{
+    use super::*;
+    use crate::transaction::generate_ed25519_keypair;
+    use ed25519_dalek::Keypair;
+
+    /// Register `address` with the given consensus and VRF keys
+    pub(crate) fn register(registry:&mut ValidatorRegistry,address:&str,consensus:&PublicKey,vrf:&schnorrkel::PublicKey){
+        registry
+        .register(
+            address.to_string(),
+            general_purpose::STANDARD.encode(consensus.to_bytes()),
+            general_purpose::STANDARD.encode(vrf.to_bytes()),
+            "127.0.0.1:30333".to_string(),
+        )
+        .unwrap();
+    }
+
+    /// Registry holding `address` with `keypair` as its consensus key
+    pub(crate) fn registry_with(address:&str,keypair:&Keypair)->ValidatorRegistry{
+        let mut registry=ValidatorRegistry::new();
+        register(&mut registry,address,&keypair.public,&schnorrkel::Keypair::generate().public);
+        registry
+    }
+
+    /// Registry of `n` validators `val0`, `val1`, ... and their consensus keys
+    pub(crate) fn validators(n:usize)->(ValidatorRegistry,Vec<(String,Keypair)>){
+        let mut registry=ValidatorRegistry::new();
+        let keys=(0..n)
+        .map(|i| {
+            let (addr,kp)=(format!("val{}",i),generate_ed25519_keypair());
+            register(&mut registry,&addr,&kp.public,&schnorrkel::Keypair::generate().public);
+            (addr,kp)
+        })
+        .collect();
+        (registry,keys)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::generate_ed25519_keypair;
+
+    fn encoded_key()->String{
+        general_purpose::STANDARD.encode(generate_ed25519_keypair().public.to_bytes())
+    }
+
+    fn vrf_key()->String{
+        general_purpose::STANDARD.encode(schnorrkel::Keypair::generate().public.to_bytes())
+    }
+
+    #[test]
+    fn test_register_and_unregister(){
+        let mut registry=ValidatorRegistry::new();
+        let key=encoded_key();
+
+        registry.register("alice".to_string(),key.clone(),vrf_key(),"127.0.0.1:30333".to_string()).unwrap();
+        assert!(registry.is_registered("alice"));
+        assert_eq!(
+            registry.register("alice".to_string(),encoded_key(),vrf_key(),"127.0.0.1:30334".to_string()),
+            Err(RegistryError::AlreadyRegistered)
+        );
+        assert_eq!(
+            registry.register("bob".to_string(),key,vrf_key(),"127.0.0.1:30335".to_string()),
+            Err(RegistryError::ConsensusKeyInUse)
+        );
+
+        let removed=registry.unregister("alice").unwrap();
+        assert_eq!(removed.endpoint,"127.0.0.1:30333");
+        assert!(registry.is_empty());
+        assert_eq!(registry.unregister("alice"),Err(RegistryError::NotRegistered));
+    }
+
+    #[test]
+    fn test_rejects_bad_key_and_endpoint(){
+        let mut registry=ValidatorRegistry::new();
+        assert_eq!(
+            registry.register("alice".to_string(),"not-base64!".to_string(),vrf_key(),"host:1".to_string()),
+            Err(RegistryError::InvalidConsensusKey)
+        );
+        assert_eq!(
+            registry.register("alice".to_string(),encoded_key(),vrf_key(),"  ".to_string()),
+            Err(RegistryError::EmptyEndpoint)
+        );
+    }
+
+    #[test]
+    fn test_jailed_validators_leave_pool(){
+        let mut registry=ValidatorRegistry::new();
+        registry.register("alice".to_string(),encoded_key(),vrf_key(),"host:1".to_string()).unwrap();
+        registry.register("bob".to_string(),encoded_key(),vrf_key(),"host:2".to_string()).unwrap();
+
+        let mut metrics=HashMap::new();
+        for id in ["alice","bob","carol"]{
+            metrics.insert(
+                id.to_string(),
+                NodeMetrics{
+                    node_id:id.to_string(),
+                    upload_mbps:10,
+                    download_mbps:10,
+                    latency_ms:10,
+                    uptime_bps:10_000,
+                    stability_bps:10_000,
+                },
+            );
+        }
+        assert_eq!(registry.pool(&metrics,0).len(),2);
+
+        registry.jail("alice",3);
+        registry.jail("alice",2);
+        assert!(registry.is_jailed("alice",2));
+        let pool=registry.pool(&metrics,2);
+        assert_eq!(pool.len(),1);
+        assert!(pool.contains_key("bob"));
+        assert!(registry.is_active("alice",3));
+    }
+
+    #[test]
+    fn test_min_score_jail_and_unjail(){
+        let mut registry=ValidatorRegistry::new();
+        registry.register("alice".to_string(),encoded_key(),vrf_key(),"host:1".to_string()).unwrap();
+        registry.register("bob".to_string(),encoded_key(),vrf_key(),"host:2".to_string()).unwrap();
+
+        let scores=|alice:u64| -> HashMap<String,u64> {
+            [("alice".to_string(),alice),("bob".to_string(),9_000)].into_iter().collect()
+        };
+
+        assert_eq!(registry.update_eligibility(&scores(1_000),2_000,2),vec!["alice".to_string()]);
+        assert!(!registry.is_active("alice",0));
+        assert!(registry.is_active("bob",0));
+
+        // one good epoch, then a relapse resets the streak
+        assert!(registry.update_eligibility(&scores(5_000),2_000,2).is_empty());
+        assert!(registry.is_score_jailed("alice"));
+        registry.update_eligibility(&scores(1_000),2_000,2);
+        registry.update_eligibility(&scores(5_000),2_000,2);
+        assert!(registry.is_score_jailed("alice"));
+
+        // second consecutive epoch above the bar releases
+        registry.update_eligibility(&scores(5_000),2_000,2);
+        assert!(registry.is_active("alice",0));
+    }
+}
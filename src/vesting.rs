@@ -0,0 +1,88 @@
+// src/vesting.rs
+
+//! Vesting schedules for genesis allocations
+//! - Nothing is spendable before the cliff; after it the allocation releases linearly
+//!   from `start_height` until `start_height + duration`
+//! - The locked portion stays in the account's balance: it can't be spent, but it is
+//!   still counted in supply and shows up in balance queries
+
+use serde::{Deserialize,Serialize};
+use thiserror::Error;
+
+/// Reasons a schedule is rejected
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum VestingError{
+    /// `duration` is zero
+    #[error("vesting duration must be non-zero")]
+    ZeroDuration,
+    /// The cliff lies past the end of the schedule
+    #[error("vesting cliff is after the end of the schedule")]
+    CliffAfterEnd,
+}
+
+/// Cliff + linear release of `total`, measured in block heights
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct VestingSchedule{
+    pub total:u64,
+    /// Height the linear release is measured from
+    pub start_height:u64,
+    /// Blocks after `start_height` before anything is released
+    pub cliff:u64,
+    /// Blocks after `start_height` until everything is released
+    pub duration:u64,
+}
+
+impl VestingSchedule{
+    pub fn new(total:u64,start_height:u64,cliff:u64,duration:u64)->Result<Self,VestingError>{
+        let schedule=Self{total,start_height,cliff,duration};
+        schedule.validate()?;
+        Ok(schedule)
+    }
+
+    pub fn validate(&self)->Result<(),VestingError>{
+        if self.duration==0{
+            return Err(VestingError::ZeroDuration)
+        }
+        if self.cliff>self.duration{
+            return Err(VestingError::CliffAfterEnd)
+        }
+        Ok(())
+    }
+
+    /// Amount released at `height`
+    pub fn vested_at(&self,height:u64)->u64{
+        let elapsed=height.saturating_sub(self.start_height);
+        if elapsed<self.cliff{
+            return 0
+        }
+        if elapsed>=self.duration{
+            return self.total
+        }
+        (self.total as u128*elapsed as u128/self.duration as u128) as u64
+    }
+
+    /// Amount still locked at `height`
+    pub fn locked_at(&self,height:u64)->u64{
+        self.total-self.vested_at(height)
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn test_cliff_then_linear_release(){
+        let schedule=VestingSchedule::new(1_000,100,50,200).unwrap();
+        assert_eq!(schedule.vested_at(0),0);
+        assert_eq!(schedule.vested_at(149),0);
+        assert_eq!(schedule.vested_at(150),250);
+        assert_eq!(schedule.vested_at(200),500);
+        assert_eq!(schedule.locked_at(299),5);
+        assert_eq!(schedule.vested_at(300),1_000);
+        assert_eq!(schedule.vested_at(u64::MAX),1_000);
+
+        assert_eq!(VestingSchedule::new(1,0,0,0),Err(VestingError::ZeroDuration));
+        assert_eq!(VestingSchedule::new(1,0,11,10),Err(VestingError::CliffAfterEnd));
+    }
+}
@@ -0,0 +1,254 @@
+// src/vrf.rs
+
+//! VRF-based leader election for NetChain
+//! - Each validator evaluates a schnorrkel VRF over (epoch, height) with its VRF key
+//! - The VRF output is turned into a ticket weighted by the validator's PoI score
+//! - The lowest ticket wins; the `LeaderProof` travels in the block so anyone can verify it
+//!
+//! Unlike seed-derived selection, nobody can grind the outcome by influencing the
+//! previous block hash: the output depends only on the validator's secret key.
+
+use crate::block::Block;
+use crate::validator::ValidatorRegistry;
+use base64::{engine::general_purpose,Engine as _};
+use schnorrkel::vrf::{VRFPreOut,VRFProof};
+use schnorrkel::{signing_context,Keypair,PublicKey};
+use serde::{Deserialize,Serialize};
+use std::collections::HashMap;
+use thiserror::Error;
+
+/// Domain separation for leader-election VRF transcripts
+const VRF_CONTEXT:&[u8]=b"netchain-leader-election";
+/// Domain separation when deriving randomness from a VRF output
+const VRF_OUTPUT_CONTEXT:&[u8]=b"netchain-leader-output";
+
+/// Reasons a leader proof can be rejected
+#[derive(Debug,Clone,PartialEq,Eq,Error)]
+pub enum VrfError{
+    #[error("unknown validator")]
+    UnknownValidator,
+    #[error("invalid VRF key")]
+    InvalidKey,
+    #[error("invalid VRF proof")]
+    InvalidProof,
+    #[error("VRF output is above the leader threshold")]
+    NotEligible,
+    #[error("no eligible leader candidates")]
+    NoCandidates,
+    #[error("block has no leader proof")]
+    MissingProof,
+    #[error("leader proof is for another slot")]
+    SlotMismatch,
+}
+
+/// A validator's VRF evaluation for one (epoch, height) slot
+#[derive(Debug,Clone,Serialize,Deserialize,PartialEq,Eq)]
+pub struct LeaderProof{
+    /// Account address of the validator
+    pub validator:String,
+    pub epoch:u64,
+    pub height:u64,
+    /// VRF pre-output encoded as base64
+    pub output:String,
+    /// VRF proof encoded as base64
+    pub proof:String,
+}
+
+fn slot_input(epoch:u64,height:u64)->Vec<u8>{
+    let mut input=Vec::with_capacity(16);
+    input.extend_from_slice(&epoch.to_le_bytes());
+    input.extend_from_slice(&height.to_le_bytes());
+    input
+}
+
+/// Score-weighted ticket: lower is better. Zero-score validators can never win.
+pub fn weighted_ticket(randomness:u128,score:u64)->u128{
+    if score==0{
+        return u128::MAX
+    }
+    randomness/score as u128
+}
+
+impl LeaderProof{
+    /// Evaluate the VRF for (epoch, height) with the validator's VRF keypair
+    pub fn evaluate(keypair:&Keypair,validator:String,epoch:u64,height:u64)->Self{
+        let ctx=signing_context(VRF_CONTEXT);
+        let (inout,proof,_)=keypair.vrf_sign(ctx.bytes(&slot_input(epoch,height)));
+        LeaderProof{
+            validator,
+            epoch,
+            height,
+            output:general_purpose::STANDARD.encode(inout.to_preout().to_bytes()),
+            proof:general_purpose::STANDARD.encode(proof.to_bytes()),
+        }
+    }
+
+    /// Verify the proof against the validator's registered VRF key and return its randomness
+    pub fn verify(&self,registry:&ValidatorRegistry)->Result<u128,VrfError>{
+        let info=registry
+        .get(&self.validator)
+        .ok_or(VrfError::UnknownValidator)?;
+        let pk_bytes=general_purpose::STANDARD
+        .decode(&info.vrf_pubkey)
+        .map_err(|_| VrfError::InvalidKey)?;
+        let public_key=PublicKey::from_bytes(&pk_bytes).map_err(|_| VrfError::InvalidKey)?;
+
+        let out_bytes=general_purpose::STANDARD
+        .decode(&self.output)
+        .map_err(|_| VrfError::InvalidProof)?;
+        let proof_bytes=general_purpose::STANDARD
+        .decode(&self.proof)
+        .map_err(|_| VrfError::InvalidProof)?;
+        let preout=VRFPreOut::from_bytes(&out_bytes).map_err(|_| VrfError::InvalidProof)?;
+        let proof=VRFProof::from_bytes(&proof_bytes).map_err(|_| VrfError::InvalidProof)?;
+
+        let ctx=signing_context(VRF_CONTEXT);
+        let (inout,_)=public_key
+        .vrf_verify(ctx.bytes(&slot_input(self.epoch,self.height)),&preout,&proof)
+        .map_err(|_| VrfError::InvalidProof)?;
+        Ok(u128::from_le_bytes(inout.make_bytes::<[u8;16]>(VRF_OUTPUT_CONTEXT)))
+    }
+
+    /// Verify the proof and compute its score-weighted ticket.
+    /// The validator must be in `scores` (the epoch's eligible set).
+    pub fn ticket(&self,registry:&ValidatorRegistry,scores:&HashMap<String,u64>)->Result<u128,VrfError>{
+        let score=*scores.get(&self.validator).ok_or(VrfError::NotEligible)?;
+        let randomness=self.verify(registry)?;
+        Ok(weighted_ticket(randomness,score))
+    }
+}
+
+/// Pick the leader for (epoch, height) among submitted proofs: lowest weighted ticket wins.
+/// In failover round `r` the `r`-th lowest ticket leads instead (wrapping around).
+/// Proofs for another slot, from ineligible validators or that fail verification are ignored.
+pub fn elect_leader(
+    proofs:&[LeaderProof],
+    registry:&ValidatorRegistry,
+    scores:&HashMap<String,u64>,
+    epoch:u64,
+    height:u64,
+    round:u32,
+)->Result<String,VrfError>{
+    let mut tickets:Vec<(u128,&String)>=proofs
+    .iter()
+    .filter(|p| p.epoch==epoch && p.height==height)
+    .filter_map(|p| p.ticket(registry,scores).ok().map(|t| (t,&p.validator)))
+    .collect();
+    if tickets.is_empty(){
+        return Err(VrfError::NoCandidates)
+    }
+    tickets.sort();
+    tickets.dedup_by(|a,b| a.1==b.1);
+    Ok(tickets[round as usize%tickets.len()].1.clone())
+}
+
+/// Verify the leader proof carried in `block` for `epoch` and return its weighted ticket.
+/// Competing blocks for the same height are ordered by ticket (lowest wins).
+pub fn verify_block_leader(
+    block:&Block,
+    registry:&ValidatorRegistry,
+    scores:&HashMap<String,u64>,
+    epoch:u64,
+)->Result<u128,VrfError>{
+    let proof=block
+    .consensus
+    .leader_proof
+    .as_ref()
+    .ok_or(VrfError::MissingProof)?;
+    if proof.height!=block.index || proof.epoch!=epoch{
+        return Err(VrfError::SlotMismatch)
+    }
+    proof.ticket(registry,scores)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::transaction::generate_ed25519_keypair;
+    use crate::validator::testing::register;
+
+    fn setup(n:usize)->(ValidatorRegistry,Vec<(String,Keypair)>){
+        let mut registry=ValidatorRegistry::new();
+        let keys=(0..n)
+        .map(|i| {
+            let (addr,vrf)=(format!("val{}",i),Keypair::generate());
+            register(&mut registry,&addr,&generate_ed25519_keypair().public,&vrf.public);
+            (addr,vrf)
+        })
+        .collect();
+        (registry,keys)
+    }
+
+    #[test]
+    fn test_proof_roundtrip_and_determinism(){
+        let (registry,keys)=setup(1);
+        let (addr,kp)=&keys[0];
+        let a=LeaderProof::evaluate(kp,addr.clone(),2,40);
+        let b=LeaderProof::evaluate(kp,addr.clone(),2,40);
+        assert_eq!(a.verify(&registry).unwrap(),b.verify(&registry).unwrap());
+
+        // proof is bound to its slot
+        let mut moved=a.clone();
+        moved.height=41;
+        assert_eq!(moved.verify(&registry),Err(VrfError::InvalidProof));
+    }
+
+    #[test]
+    fn test_elect_lowest_weighted_ticket(){
+        let (registry,keys)=setup(3);
+        let proofs:Vec<LeaderProof>=keys
+        .iter()
+        .map(|(addr,kp)| LeaderProof::evaluate(kp,addr.clone(),0,7))
+        .collect();
+        let scores:HashMap<String,u64>=keys.iter().map(|(addr,_)| (addr.clone(),5_000)).collect();
+
+        let leader=elect_leader(&proofs,&registry,&scores,0,7,0).unwrap();
+        let best=proofs
+        .iter()
+        .min_by_key(|p| p.ticket(&registry,&scores).unwrap())
+        .unwrap();
+        assert_eq!(leader,best.validator);
+
+        // failover rounds walk through the remaining candidates in ticket order
+        let round_leaders:std::collections::HashSet<String>=(0..3)
+        .map(|r| elect_leader(&proofs,&registry,&scores,0,7,r).unwrap())
+        .collect();
+        assert_eq!(round_leaders.len(),3);
+
+        // zero-score validators never win while others are eligible
+        let mut skewed=scores.clone();
+        skewed.insert(leader.clone(),0);
+        assert_ne!(elect_leader(&proofs,&registry,&skewed,0,7,0).unwrap(),leader);
+
+        // someone else's proof can't be claimed
+        let mut stolen=proofs[0].clone();
+        stolen.validator=keys[1].0.clone();
+        assert_eq!(stolen.verify(&registry),Err(VrfError::InvalidProof));
+        assert_eq!(elect_leader(&proofs,&registry,&HashMap::new(),0,7,0),Err(VrfError::NoCandidates));
+    }
+
+    #[test]
+    fn test_verify_block_leader(){
+        use crate::block::ConsensusData;
+
+        let (registry,keys)=setup(1);
+        let (addr,kp)=&keys[0];
+        let scores:HashMap<String,u64>=[(addr.clone(),10_000)].into_iter().collect();
+
+        let proof=LeaderProof::evaluate(kp,addr.clone(),1,12);
+        let block=Block::with_consensus(
+            12,
+            "data".to_string(),
+            "prev".to_string(),
+            ConsensusData{
+                leader_proof:Some(proof),
+                ..Default::default()
+            },
+        );
+        assert!(verify_block_leader(&block,&registry,&scores,1).is_ok());
+        assert_eq!(verify_block_leader(&block,&registry,&scores,2),Err(VrfError::SlotMismatch));
+
+        let bare=Block::new(12,"data".to_string(),"prev".to_string());
+        assert_eq!(verify_block_leader(&bare,&registry,&scores,1),Err(VrfError::MissingProof));
+    }
+}